@@ -0,0 +1,151 @@
+//! Adaptive throttling for long unattended runs: the responses are watched in windows and
+//! when too many of them fail or their median time rises above the threshold, the requests
+//! are spaced out twice as far with each unhealthy window. Each healthy window halves the
+//! spacing again, until the requests are sent at full speed.
+
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use tracing::{debug, info, warn};
+
+use crate::transport::{Request, Response, Transport};
+
+/// Responses the health of the target is judged by at once
+const WINDOW: usize = 20;
+
+/// Spacing of the requests when the target first degrades
+const MIN_DELAY: Duration = Duration::from_millis(50);
+
+/// Longest spacing, one request every 10s
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Fraction of failing responses of an unhealthy window by default
+pub const DEFAULT_ERROR_RATE: f64 = 0.25;
+
+/// Without a latency threshold, windows slower than this multiple of the first healthy one
+/// are unhealthy
+const LATENCY_FACTOR: u32 = 5;
+
+/// Shortest latency threshold derived from the first healthy window, fast targets vary by
+/// more than the factor
+const MIN_LATENCY: Duration = Duration::from_millis(250);
+
+/// When the target is considered unhealthy
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// Fraction of responses that are server errors, rate limited or failed to arrive
+    pub error_rate: f64,
+    /// Median response time, derived from the first healthy window if not set
+    pub latency: Option<Duration>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    delay: Duration,
+    /// Time the next request may be sent at
+    next: Option<Instant>,
+    errors: usize,
+    latencies: Vec<Duration>,
+    latency: Option<Duration>,
+}
+
+/// Spaces out the requests of the transport while the target is unhealthy
+#[derive(Debug)]
+pub struct Adaptive<T> {
+    inner: T,
+    thresholds: Thresholds,
+    state: Mutex<State>,
+}
+
+impl<T: Transport> Adaptive<T> {
+    pub fn new(inner: T, thresholds: Thresholds) -> Adaptive<T> {
+        Adaptive {
+            inner,
+            thresholds,
+            state: Mutex::new(State {
+                latency: thresholds.latency,
+                ..State::default()
+            }),
+        }
+    }
+
+    /// Waits for the slot of the request
+    fn wait(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.delay.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let slot = state.next.filter(|next| *next > now).unwrap_or(now);
+        state.next = Some(slot + state.delay);
+        drop(state);
+        thread::sleep(slot - now);
+    }
+
+    /// Counts the response and adjusts the spacing at the end of each window
+    fn observe(&self, result: &Result<Response>, elapsed: Duration) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (failed, latency) = match result {
+            Ok(response) => (
+                response.status / 100 == 5 || response.status == 429,
+                response.elapsed,
+            ),
+            Err(_) => (true, elapsed),
+        };
+        state.errors += failed as usize;
+        state.latencies.push(latency);
+        if state.latencies.len() < WINDOW {
+            return;
+        }
+
+        let error_rate = state.errors as f64 / state.latencies.len() as f64;
+        state.latencies.sort_unstable();
+        let median = state.latencies[state.latencies.len() / 2];
+        state.errors = 0;
+        state.latencies.clear();
+        let failing = error_rate > self.thresholds.error_rate;
+        let slow = state.latency.is_some_and(|latency| median > latency);
+        if !failing && !slow && state.latency.is_none() {
+            state.latency = Some((median * LATENCY_FACTOR).max(MIN_LATENCY));
+        }
+
+        if failing || slow {
+            let delay = (state.delay * 2).clamp(MIN_DELAY, MAX_DELAY);
+            if delay != state.delay {
+                warn!(
+                    error_rate,
+                    ?median,
+                    requests_per_second = 1.0 / delay.as_secs_f64(),
+                    "target degraded, slowing down"
+                );
+            }
+            state.delay = delay;
+        } else if !state.delay.is_zero() {
+            state.delay /= 2;
+            if state.delay < MIN_DELAY {
+                state.delay = Duration::ZERO;
+                state.next = None;
+                info!("target recovered, back to full speed");
+            } else {
+                debug!(
+                    requests_per_second = 1.0 / state.delay.as_secs_f64(),
+                    "target recovering, speeding up"
+                );
+            }
+        }
+    }
+}
+
+impl<T: Transport> Transport for Adaptive<T> {
+    fn send(&self, request: &Request) -> Result<Response> {
+        self.wait();
+        let start = Instant::now();
+        let result = self.inner.send(request);
+        self.observe(&result, start.elapsed());
+        result
+    }
+}
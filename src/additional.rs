@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use tracing::debug;
+
+use crate::conditional::{body_schemas, placeholder, properties_of, resolve};
+use crate::payload::{Generator, Payload};
+
+/// Probability that a payload gets undeclared properties, where they are allowed
+const EXTRA_PROBABILITY: f64 = 0.3;
+
+/// Probability that one of the declared fields is repeated in the raw body
+const DUPLICATE_PROBABILITY: f64 = 0.2;
+
+/// Most undeclared properties added to an object
+const MAX_EXTRA: usize = 3;
+
+/// Objects are followed this deep into the body
+const MAX_DEPTH: usize = 3;
+
+/// Names probing mass assignment and the prototypes of JavaScript parsers, with values that
+/// would matter if they were assigned
+fn hostile() -> Vec<(String, Value)> {
+    vec![
+        ("__proto__".to_string(), json!({"isAdmin": true})),
+        (
+            "constructor".to_string(),
+            json!({"prototype": {"isAdmin": true}}),
+        ),
+        ("prototype".to_string(), json!({"isAdmin": true})),
+        ("isAdmin".to_string(), json!(true)),
+        ("role".to_string(), json!("admin")),
+        ("roles".to_string(), json!(["admin"])),
+        ("permissions".to_string(), json!(["*"])),
+        ("owner_id".to_string(), json!(1)),
+        ("tenant_id".to_string(), json!(1)),
+        ("verified".to_string(), json!(true)),
+        ("price".to_string(), json!(0)),
+        ("$where".to_string(), json!("1 == 1")),
+        ("".to_string(), json!("empty key")),
+        ("A".repeat(4096), json!("long key")),
+        ("fuzz\u{0}key".to_string(), json!("null byte")),
+        ("a.b".to_string(), json!("dotted key")),
+        ("a[b]".to_string(), json!("bracketed key")),
+    ]
+}
+
+/// Whether and which undeclared properties an object schema allows
+#[derive(Debug)]
+struct Extras {
+    /// Keys leading to the object in the body, empty for the body itself
+    keys: Vec<String>,
+    /// `additionalProperties`, `None` when it is `false`
+    additional: Option<Value>,
+    /// `patternProperties` with their schemas
+    patterns: Vec<(Regex, Value)>,
+    declared: Vec<String>,
+}
+
+/// Adds undeclared properties to the objects of request bodies: benign ones matching
+/// `additionalProperties` and `patternProperties`, hostile names like `__proto__` and very long
+/// keys, and repeats declared fields in the raw body. Objects with `additionalProperties: false`
+/// only get them in the negative mode, as violations.
+#[derive(Debug)]
+pub struct AdditionalProperties {
+    /// Objects of the body of each operation
+    objects: BTreeMap<(String, String), Vec<Extras>>,
+    negative: bool,
+}
+
+impl AdditionalProperties {
+    /// Object schemas of the request bodies in the specification, `None` if there are none
+    pub fn new(document: &Value, negative: bool) -> Option<AdditionalProperties> {
+        let mut objects = BTreeMap::new();
+        for (operation, schema) in body_schemas(document) {
+            let mut operation_objects = Vec::new();
+            collect(document, schema, Vec::new(), &mut operation_objects);
+            if !operation_objects.is_empty() {
+                objects.insert(operation, operation_objects);
+            }
+        }
+        debug!(
+            operations = objects.len(),
+            "objects with additional properties"
+        );
+        (!objects.is_empty()).then_some(AdditionalProperties { objects, negative })
+    }
+}
+
+impl Generator for AdditionalProperties {
+    fn generate(&self, payload: &mut Payload<'_>, rng: &mut StdRng) {
+        if payload.violation.is_some() || payload.raw_body.is_some() {
+            return;
+        }
+        let key = (payload.method.to_string(), payload.path.to_string());
+        let objects = match self.objects.get(&key) {
+            Some(objects) => objects,
+            None => return,
+        };
+        let extras = match objects.choose(rng) {
+            Some(object) => object,
+            None => return,
+        };
+        let closed = extras.additional.is_none();
+        let allowed = match closed {
+            true => self.negative && payload.negative,
+            false => rng.gen_bool(EXTRA_PROBABILITY),
+        };
+        if !allowed {
+            return;
+        }
+        let object = match payload
+            .body
+            .first_mut()
+            .and_then(|body| object_at(body, &extras.keys))
+        {
+            Some(object) => object,
+            None => return,
+        };
+
+        let mut candidates = hostile();
+        candidates.push(("extra".to_string(), json!("value")));
+        candidates.push(("metadata".to_string(), json!({})));
+        let mut extra = Vec::new();
+        for _ in 0..rng.gen_range(1..=MAX_EXTRA) {
+            // Names matching the patterns are valid, unless they are declared
+            let matching = extras
+                .patterns
+                .choose(rng)
+                .filter(|_| !closed && rng.gen_bool(0.5))
+                .and_then(|(pattern, schema)| {
+                    pattern_name(pattern).map(|name| (name, placeholder(Some(schema), rng)))
+                });
+            let (name, value) = match matching {
+                Some(matching) => matching,
+                None => match candidates.choose(rng) {
+                    Some((name, value)) => {
+                        // Values of hostile names are kept, benign ones follow the schema
+                        let value = match (&extras.additional, name.as_str()) {
+                            (Some(schema), "extra") => placeholder(Some(schema), rng),
+                            _ => value.clone(),
+                        };
+                        (name.clone(), value)
+                    }
+                    None => break,
+                },
+            };
+            if extras.declared.contains(&name) || object.contains_key(&name) {
+                continue;
+            }
+            object.insert(name.clone(), value);
+            extra.push(name);
+        }
+        if extra.is_empty() {
+            return;
+        }
+        let field = |name: &String| match name.chars().count() > 32 {
+            true => format!("`{}…`", name.chars().take(32).collect::<String>()),
+            false => format!("`{}`", name.escape_debug()),
+        };
+        let fields = extra.iter().map(field).collect::<Vec<_>>().join(", ");
+        if closed {
+            payload.violation = Some(format!(
+                "undeclared fields {} although additional properties are not allowed",
+                fields
+            ));
+        }
+
+        // Parsers disagree on which of the repeated fields wins, only top-level fields are
+        // repeated
+        if !extras.keys.is_empty() {
+            return;
+        }
+        let declared = object
+            .keys()
+            .filter(|name| extras.declared.contains(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(name) = declared
+            .choose(rng)
+            .filter(|_| rng.gen_bool(DUPLICATE_PROBABILITY))
+        {
+            let value = match object.get(name) {
+                Some(Value::String(_)) => json!("admin"),
+                Some(Value::Number(_)) => json!(0),
+                Some(Value::Bool(b)) => json!(!b),
+                _ => Value::Null,
+            };
+            if let (Some(body), Ok(name)) = (payload.body.first(), serde_json::to_string(name)) {
+                let body = body.to_string();
+                let duplicated = format!("{},{}:{}}}", &body[..body.len() - 1], name, value);
+                payload.raw_body = Some(duplicated.into_bytes());
+                if !payload
+                    .headers
+                    .iter()
+                    .any(|(header, _)| header.eq_ignore_ascii_case("content-type"))
+                {
+                    payload
+                        .headers
+                        .push(("Content-Type", "application/json".to_string()));
+                }
+            }
+        }
+    }
+}
+
+/// Objects of the body schema that allow or forbid undeclared properties
+fn collect(document: &Value, schema: &Value, keys: Vec<String>, objects: &mut Vec<Extras>) {
+    let schema = resolve(document, schema);
+    let properties = properties_of(document, schema);
+    if schema.get("type").and_then(Value::as_str) != Some("object") && properties.is_empty() {
+        return;
+    }
+    let additional = match schema.get("additionalProperties") {
+        Some(Value::Bool(false)) => None,
+        Some(additional @ Value::Object(_)) => Some(resolve(document, additional).clone()),
+        _ => Some(json!({})),
+    };
+    let patterns = match schema.get("patternProperties") {
+        Some(Value::Object(patterns)) => patterns
+            .iter()
+            .filter_map(|(pattern, schema)| {
+                Some((Regex::new(pattern).ok()?, resolve(document, schema).clone()))
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    if keys.len() < MAX_DEPTH {
+        for (name, property) in properties.iter() {
+            let mut keys = keys.clone();
+            keys.push(name.clone());
+            collect(document, property, keys, objects);
+        }
+    }
+    objects.push(Extras {
+        keys,
+        additional,
+        patterns,
+        declared: properties.keys().cloned().collect(),
+    });
+}
+
+/// Object of the body at the keys
+fn object_at<'a>(body: &'a mut Value, keys: &[String]) -> Option<&'a mut Map<String, Value>> {
+    keys.iter()
+        .try_fold(body, |value, key| value.get_mut(key))?
+        .as_object_mut()
+}
+
+/// Name matching the pattern, from its literal prefix, e.g. `x-fuzz` for `^x-`
+fn pattern_name(pattern: &Regex) -> Option<String> {
+    let prefix = pattern
+        .as_str()
+        .trim_start_matches('^')
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+        .collect::<String>();
+    [format!("{}fuzz", prefix), prefix, "fuzz".to_string()]
+        .iter()
+        .find(|name| !name.is_empty() && pattern.is_match(name))
+        .cloned()
+}
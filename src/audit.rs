@@ -0,0 +1,150 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer};
+
+use crate::payload::Payload;
+use crate::status::Operations;
+use crate::transport::{Request, Response};
+
+/// Requirement on a response header, the header must be present and its value must satisfy
+/// all of the conditions
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeaderRule {
+    /// Value must be equal, ignoring case
+    equals: Option<String>,
+    /// Value must contain the string, ignoring case
+    contains: Option<String>,
+    /// `max-age` directive of the value must be at least this number of seconds
+    min_max_age: Option<u64>,
+    /// Operations the rule applies to, all operations if empty
+    #[serde(deserialize_with = "operations")]
+    operations: Vec<Operations>,
+    /// Rule applies only to operations with security requirements in the specification
+    authenticated: bool,
+}
+
+fn operations<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Operations>, D::Error> {
+    Vec::<String>::deserialize(d)?
+        .iter()
+        .map(|operations| operations.parse().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+impl HeaderRule {
+    fn applies(&self, payload: &Payload, authenticated: bool) -> bool {
+        (!self.authenticated || authenticated)
+            && (self.operations.is_empty()
+                || self.operations.iter().any(|operations| {
+                    operations.matches(payload.method, payload.path, payload.operation_id)
+                }))
+    }
+
+    fn check(&self, name: &str, value: &str) -> Option<String> {
+        let lowercase = value.to_lowercase();
+        if let Some(expected) = &self.equals {
+            if lowercase != expected.to_lowercase() {
+                return Some(format!(
+                    "`{}` header is `{}`, expected `{}`",
+                    name, value, expected
+                ));
+            }
+        }
+        if let Some(expected) = &self.contains {
+            if !lowercase.contains(&expected.to_lowercase()) {
+                return Some(format!(
+                    "`{}` header `{}` does not contain `{}`",
+                    name, value, expected
+                ));
+            }
+        }
+        if let Some(min) = self.min_max_age {
+            let max_age = lowercase
+                .split(';')
+                .find_map(|directive| directive.trim().strip_prefix("max-age="))
+                .and_then(|max_age| max_age.trim_matches('"').parse::<u64>().ok());
+            if max_age.is_none_or(|max_age| max_age < min) {
+                return Some(format!(
+                    "`{}` header `{}` has max-age lower than {}",
+                    name, value, min
+                ));
+            }
+        }
+        None
+    }
+}
+
+/// Security headers expected in responses, keyed by lowercase header name
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderPolicy {
+    headers: BTreeMap<String, HeaderRule>,
+}
+
+impl Default for HeaderPolicy {
+    fn default() -> HeaderPolicy {
+        let mut headers = BTreeMap::new();
+        headers.insert(
+            "strict-transport-security".to_string(),
+            HeaderRule {
+                min_max_age: Some(15_552_000),
+                ..Default::default()
+            },
+        );
+        headers.insert(
+            "x-content-type-options".to_string(),
+            HeaderRule {
+                equals: Some("nosniff".to_string()),
+                ..Default::default()
+            },
+        );
+        headers.insert(
+            "cache-control".to_string(),
+            HeaderRule {
+                contains: Some("no-store".to_string()),
+                authenticated: true,
+                ..Default::default()
+            },
+        );
+        HeaderPolicy { headers }
+    }
+}
+
+impl HeaderPolicy {
+    pub fn load(file: &Path) -> Result<HeaderPolicy> {
+        let content =
+            fs::read_to_string(file).context(format!("unable to read {}", file.display()))?;
+        let policy: HeaderPolicy = serde_yaml::from_str(&content)
+            .context(format!("unable to parse {}", file.display()))?;
+        Ok(HeaderPolicy {
+            headers: policy
+                .headers
+                .into_iter()
+                .map(|(name, rule)| (name.to_lowercase(), rule))
+                .collect(),
+        })
+    }
+
+    /// Returns missing and weak security headers of the response
+    pub fn audit(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        response: &Response,
+        authenticated: bool,
+    ) -> Vec<String> {
+        self.headers
+            .iter()
+            .filter(|(_, rule)| rule.applies(payload, authenticated))
+            // Browsers ignore HSTS received over plain HTTP
+            .filter(|(name, _)| {
+                *name != "strict-transport-security" || request.url.scheme() == "https"
+            })
+            .filter_map(|(name, rule)| match response.header(name) {
+                Some(value) => rule.check(name, value),
+                None => Some(format!("missing `{}` header", name)),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,535 @@
+//! Credentials added to every request, several of them stacked on the same request as API
+//! gateways require, e.g. the subscription key of Azure API Management and the OAuth token of
+//! the API behind it. Tokens of the OAuth client credentials flow and of commands are fetched
+//! when first needed and again once they expire. Headers and query parameters the request
+//! already has, e.g. of the command line, the identities or the options of its operation, take
+//! precedence. The fuzzer adds the credentials before the requests are signed, so that the
+//! signature covers them, and leaves them out of the requests of the findings and the reports.
+//! Cassettes redact them. The other requests, e.g. of the replays, get them when they are sent.
+
+use std::{
+    process::Command,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use openapiv3::{APIKeyLocation, OAuth2Flow, OpenAPI, ReferenceOr, SecurityScheme};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, info};
+use url::Url;
+
+use crate::config::duration;
+use crate::tls;
+use crate::transport::{Request, Response, Transport};
+
+/// Tokens are fetched again this long before they expire, so that none expires in flight
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Credential {
+    /// Named in the logs and errors, the scheme or the header by default
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Security scheme of the specification the header, the query parameter or the token url
+    /// are taken from
+    #[serde(default)]
+    pub scheme: Option<String>,
+    /// Header the credential is sent in, `Authorization` for tokens by default
+    #[serde(default)]
+    pub header: Option<String>,
+    /// Query parameter the credential is sent in, instead of a header
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Value sent as is, e.g. a subscription key
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Bearer token
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Command printing a bearer token, run again after `refresh`
+    #[serde(default)]
+    pub token_command: Option<String>,
+    /// Token endpoint of the OAuth client credentials flow
+    #[serde(default)]
+    pub token_url: Option<String>,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    /// Scopes requested with the client credentials, separated by spaces
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// Lifetime of the tokens of the command, or of the endpoint when it does not tell
+    #[serde(default, deserialize_with = "duration")]
+    pub refresh: Option<Duration>,
+}
+
+/// Where the credential is sent
+#[derive(Debug)]
+enum Target {
+    Header(String),
+    Query(String),
+}
+
+/// How the credential is obtained
+#[derive(Debug)]
+enum Source {
+    Value(String),
+    Token(String),
+    Command(String),
+    ClientCredentials {
+        url: Url,
+        client_id: String,
+        client_secret: String,
+        scope: Option<String>,
+    },
+}
+
+/// Value of the credential and when it expires
+type Fetched = (String, Option<Instant>);
+
+#[derive(Debug)]
+struct Mechanism {
+    name: String,
+    target: Target,
+    source: Source,
+    refresh: Option<Duration>,
+    fetched: Mutex<Option<Fetched>>,
+}
+
+/// Security scheme of the specification with the name
+fn scheme<'a>(schema: &'a OpenAPI, name: &str) -> Result<&'a SecurityScheme> {
+    schema
+        .components
+        .as_ref()
+        .and_then(|components| components.security_schemes.get(name))
+        .and_then(|scheme| match scheme {
+            ReferenceOr::Item(scheme) => Some(scheme),
+            ReferenceOr::Reference { .. } => None,
+        })
+        .ok_or_else(|| anyhow!("no security scheme {} in the specification", name))
+}
+
+/// Token url of the client credentials flow of the scheme
+fn token_url(scheme: &SecurityScheme) -> Option<&str> {
+    let flows = match scheme {
+        SecurityScheme::OAuth2 { flows } => flows,
+        _ => return None,
+    };
+    [
+        &flows.client_credentials,
+        &flows.implicit,
+        &flows.password,
+        &flows.authorization_code,
+    ]
+    .iter()
+    .filter_map(|flow| flow.as_ref())
+    .find_map(|flow| match flow {
+        OAuth2Flow::ClientCredentials { token_url, .. } => Some(token_url.as_str()),
+        _ => None,
+    })
+}
+
+impl Mechanism {
+    fn new(credential: Credential, schema: &OpenAPI, api: &Url) -> Result<Mechanism> {
+        let scheme = credential
+            .scheme
+            .as_deref()
+            .map(|name| scheme(schema, name))
+            .transpose()?;
+        let token_url = credential
+            .token_url
+            .as_deref()
+            .or_else(|| scheme.and_then(token_url));
+        let source = match (
+            credential.value,
+            credential.token,
+            credential.token_command,
+            credential.client_id,
+        ) {
+            (Some(value), None, None, None) => Source::Value(value),
+            (None, Some(token), None, None) => Source::Token(token),
+            (None, None, Some(command), None) => Source::Command(command),
+            (None, None, None, Some(client_id)) => Source::ClientCredentials {
+                url: api
+                    .join(token_url.ok_or_else(|| anyhow!("missing the `token-url`"))?)
+                    .context("invalid token url")?,
+                client_id,
+                client_secret: credential
+                    .client_secret
+                    .ok_or_else(|| anyhow!("missing the `client-secret`"))?,
+                scope: credential.scope,
+            },
+            _ => {
+                return Err(anyhow!(
+                    "expected one of a `value`, a `token`, a `token-command` or a `client-id`"
+                ))
+            }
+        };
+        let target = match (credential.header, credential.query, scheme) {
+            (Some(header), None, _) => Target::Header(header),
+            (None, Some(query), _) => Target::Query(query),
+            (Some(_), Some(_), _) => return Err(anyhow!("expected a `header` or a `query`")),
+            (None, None, Some(SecurityScheme::APIKey { location, name })) => match location {
+                APIKeyLocation::Header => Target::Header(name.clone()),
+                APIKeyLocation::Query => Target::Query(name.clone()),
+                APIKeyLocation::Cookie => {
+                    return Err(anyhow!(
+                        "API keys in cookies are not supported, pass the cookie with --header"
+                    ))
+                }
+            },
+            (None, None, _) => match source {
+                Source::Value(_) => return Err(anyhow!("missing the `header` or the `query`")),
+                _ => Target::Header("Authorization".to_string()),
+            },
+        };
+        let name = credential
+            .name
+            .or(credential.scheme)
+            .unwrap_or_else(|| match &target {
+                Target::Header(name) | Target::Query(name) => name.clone(),
+            });
+        Ok(Mechanism {
+            name,
+            target,
+            source,
+            refresh: credential.refresh,
+            fetched: Mutex::default(),
+        })
+    }
+
+    /// Token printed by the command
+    fn run(&self, command: &str) -> Result<String> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .context("unable to run the token command")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "the token command failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        match token.is_empty() {
+            true => Err(anyhow!("the token command printed nothing")),
+            false => Ok(token),
+        }
+    }
+
+    /// Access token of the client credentials and its lifetime, if the endpoint tells
+    fn request_token(
+        &self,
+        url: &Url,
+        client_id: &str,
+        client_secret: &str,
+        scope: Option<&str>,
+    ) -> Result<(String, Option<Duration>)> {
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+        ];
+        if let Some(scope) = scope {
+            form.push(("scope", scope));
+        }
+        let response = match tls::agent().request_url("POST", url).send_form(&form) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(status, response)) => {
+                return Err(anyhow!(
+                    "the token endpoint answered {}: {}",
+                    status,
+                    response.into_string().unwrap_or_default().trim()
+                ))
+            }
+            Err(e) => return Err(e).context("unable to reach the token endpoint"),
+        };
+        let body: Value = serde_json::from_str(&response.into_string()?)
+            .context("invalid response of the token endpoint")?;
+        let token = body
+            .get("access_token")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("no access_token in the response of the token endpoint"))?;
+        // Some endpoints send the lifetime as a string
+        let lifetime = match body.get("expires_in") {
+            Some(Value::Number(seconds)) => seconds.as_u64(),
+            Some(Value::String(seconds)) => seconds.parse().ok(),
+            _ => None,
+        };
+        Ok((token.to_string(), lifetime.map(Duration::from_secs)))
+    }
+
+    /// Value of the credential, fetched again once it expired
+    fn value(&self) -> Result<String> {
+        let mut fetched = self.fetched.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((value, expiry)) = fetched.as_ref() {
+            if expiry.is_none_or(|expiry| Instant::now() < expiry) {
+                return Ok(value.clone());
+            }
+        }
+        let (value, lifetime) = match &self.source {
+            Source::Value(value) => (value.clone(), None),
+            Source::Token(token) => (format!("Bearer {}", token), None),
+            Source::Command(command) => (format!("Bearer {}", self.run(command)?), self.refresh),
+            Source::ClientCredentials {
+                url,
+                client_id,
+                client_secret,
+                scope,
+            } => {
+                let (token, lifetime) =
+                    self.request_token(url, client_id, client_secret, scope.as_deref())?;
+                (format!("Bearer {}", token), lifetime.or(self.refresh))
+            }
+        };
+        let expiry =
+            lifetime.map(|lifetime| Instant::now() + lifetime.saturating_sub(EXPIRY_MARGIN));
+        debug!(credential = %self.name, ?lifetime, "credential fetched");
+        *fetched = Some((value.clone(), expiry));
+        Ok(value)
+    }
+
+    fn authenticate(&self, request: &mut Request) -> Result<()> {
+        match &self.target {
+            Target::Header(name) => {
+                if !request
+                    .headers
+                    .iter()
+                    .any(|(header, _)| header.eq_ignore_ascii_case(name))
+                {
+                    let value = self
+                        .value()
+                        .with_context(|| format!("unable to get the credential {}", self.name))?;
+                    request.headers.push((name.to_lowercase(), value));
+                }
+            }
+            Target::Query(name) => {
+                if !request.url.query_pairs().any(|(n, _)| n == name.as_str()) {
+                    let value = self
+                        .value()
+                        .with_context(|| format!("unable to get the credential {}", self.name))?;
+                    request.url.query_pairs_mut().append_pair(name, &value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the credential from the request if it was added, the headers and query
+    /// parameters of the request with other values stay
+    fn strip(&self, request: &mut Request) {
+        let fetched = self.fetched.lock().unwrap_or_else(|e| e.into_inner());
+        let value = match fetched.as_ref() {
+            Some((value, _)) => value,
+            None => return,
+        };
+        match &self.target {
+            Target::Header(name) => request
+                .headers
+                .retain(|(header, v)| !header.eq_ignore_ascii_case(name) || v != value),
+            Target::Query(name) => {
+                let pairs = request
+                    .url
+                    .query_pairs()
+                    .filter(|(n, v)| n != name.as_str() || v != value.as_str())
+                    .map(|(n, v)| (n.into_owned(), v.into_owned()))
+                    .collect::<Vec<_>>();
+                match pairs.is_empty() {
+                    true => request.url.set_query(None),
+                    false => {
+                        request.url.query_pairs_mut().clear().extend_pairs(pairs);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Credentials configured for the API, all of them are sent with every request
+#[derive(Debug, Default)]
+pub struct Credentials {
+    mechanisms: Vec<Mechanism>,
+}
+
+impl Credentials {
+    /// Checks the credentials against the security schemes of the specification, token urls
+    /// are relative to the url of the API
+    pub fn new(schema: &OpenAPI, api: &Url, credentials: Vec<Credential>) -> Result<Credentials> {
+        let mut mechanisms = Vec::new();
+        for (i, credential) in credentials.into_iter().enumerate() {
+            let name = credential
+                .name
+                .clone()
+                .or_else(|| credential.scheme.clone())
+                .unwrap_or_else(|| format!("#{}", i + 1));
+            let mechanism = Mechanism::new(credential, schema, api)
+                .with_context(|| format!("invalid credential {}", name))?;
+            mechanisms.push(mechanism);
+        }
+        Ok(Credentials { mechanisms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mechanisms.is_empty()
+    }
+
+    /// Fetches the credentials, so that wrong ones fail the run before it starts
+    pub fn fetch(&self) -> Result<()> {
+        for mechanism in self.mechanisms.iter() {
+            mechanism
+                .value()
+                .with_context(|| format!("unable to get the credential {}", mechanism.name))?;
+        }
+        let names = self
+            .mechanisms
+            .iter()
+            .map(|mechanism| mechanism.name.as_str())
+            .collect::<Vec<_>>();
+        info!(credentials = ?names, "authenticating the requests");
+        Ok(())
+    }
+
+    /// Adds the credentials the request does not have yet
+    pub fn authenticate(&self, request: &mut Request) -> Result<()> {
+        for mechanism in self.mechanisms.iter() {
+            mechanism.authenticate(request)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the credentials added to the request, before it is stored
+    pub fn strip(&self, request: &mut Request) {
+        for mechanism in self.mechanisms.iter() {
+            mechanism.strip(request);
+        }
+    }
+
+    /// Headers and query parameters the credentials are sent in, to redact
+    pub fn fields(&self) -> Vec<String> {
+        self.mechanisms
+            .iter()
+            .map(|mechanism| match &mechanism.target {
+                Target::Header(name) | Target::Query(name) => name.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Sends the requests with the credentials, shared by the transports of the run
+#[derive(Debug)]
+pub struct Authenticated<T> {
+    pub inner: T,
+    pub credentials: Arc<Credentials>,
+}
+
+impl<T: Transport> Transport for Authenticated<T> {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let mut request = request.clone();
+        self.credentials.authenticate(&mut request)?;
+        self.inner.send(&request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> OpenAPI {
+        serde_json::from_value(json!({
+            "openapi": "3.0.0",
+            "info": {"title": "api", "version": "1"},
+            "paths": {},
+            "components": {"securitySchemes": {
+                "key": {"type": "apiKey", "in": "query", "name": "api_key"},
+                "cookie": {"type": "apiKey", "in": "cookie", "name": "session"}
+            }}
+        }))
+        .unwrap()
+    }
+
+    fn mechanism(credential: Credential) -> Result<Mechanism> {
+        let api = Url::parse("http://localhost/api/").unwrap();
+        Mechanism::new(credential, &schema(), &api)
+    }
+
+    fn request(url: &str) -> Request {
+        Request {
+            method: "GET".to_string(),
+            url: Url::parse(url).unwrap(),
+            headers: Vec::new(),
+            body: None,
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        }
+    }
+
+    #[test]
+    fn takes_the_target_from_the_scheme() {
+        let key = mechanism(Credential {
+            scheme: Some("key".to_string()),
+            value: Some("secret".to_string()),
+            ..Credential::default()
+        })
+        .unwrap();
+        assert!(matches!(&key.target, Target::Query(name) if name == "api_key"));
+        assert_eq!(key.name, "key");
+
+        let token = mechanism(Credential {
+            token: Some("t".to_string()),
+            ..Credential::default()
+        })
+        .unwrap();
+        assert!(matches!(&token.target, Target::Header(name) if name == "Authorization"));
+
+        let cookie = Credential {
+            scheme: Some("cookie".to_string()),
+            value: Some("secret".to_string()),
+            ..Credential::default()
+        };
+        assert!(mechanism(cookie).is_err());
+        let both = Credential {
+            value: Some("secret".to_string()),
+            token: Some("t".to_string()),
+            header: Some("X-Key".to_string()),
+            ..Credential::default()
+        };
+        assert!(mechanism(both).is_err());
+        let client = Credential {
+            client_id: Some("id".to_string()),
+            client_secret: Some("secret".to_string()),
+            ..Credential::default()
+        };
+        assert!(mechanism(client).is_err());
+    }
+
+    #[test]
+    fn strips_the_added_credentials() {
+        let credentials = Credentials {
+            mechanisms: vec![mechanism(Credential {
+                scheme: Some("key".to_string()),
+                value: Some("secret".to_string()),
+                ..Credential::default()
+            })
+            .unwrap()],
+        };
+        let mut added = request("http://localhost/api/items?q=1");
+        credentials.authenticate(&mut added).unwrap();
+        assert_eq!(added.url.query(), Some("q=1&api_key=secret"));
+        credentials.strip(&mut added);
+        assert_eq!(added.url.query(), Some("q=1"));
+
+        // A credential of the request itself stays
+        let mut own = request("http://localhost/api/items?api_key=mine");
+        credentials.authenticate(&mut own).unwrap();
+        credentials.strip(&mut own);
+        assert_eq!(own.url.query(), Some("api_key=mine"));
+    }
+}
@@ -0,0 +1,250 @@
+//! Benchmark of the API with schema-valid requests only, sent back to back from a number of
+//! connections, with the throughput and latency percentiles of each operation: whether the API
+//! can take the load of a run before fuzzing it
+
+use std::{
+    collections::BTreeMap,
+    sync::{mpsc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::fuzzer::Fuzzer;
+use crate::report;
+use crate::transport::Request;
+
+/// How long the benchmark runs without a budget
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(30);
+
+/// Response to a request of the benchmark
+struct Sample {
+    operation: usize,
+    /// `None` when the request failed, e.g. the connection was refused or timed out
+    status: Option<u16>,
+    latency: Duration,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OperationBench {
+    pub operation: String,
+    pub requests: usize,
+    /// Requests without a response
+    pub errors: usize,
+    /// Number of responses by status
+    pub statuses: BTreeMap<u16, usize>,
+    /// Requests per second
+    pub throughput: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Bench {
+    pub concurrency: usize,
+    pub elapsed_ms: u64,
+    pub requests: usize,
+    pub errors: usize,
+    /// Requests per second
+    pub throughput: f64,
+    pub operations: Vec<OperationBench>,
+}
+
+/// Latency of the percentile, by nearest rank, in milliseconds
+fn percentile(sorted: &[Duration], percentile: f64) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        len => {
+            let rank = (percentile / 100.0 * (len - 1) as f64).round() as usize;
+            sorted[rank.min(len - 1)].as_micros() as f64 / 1000.0
+        }
+    }
+}
+
+/// Sends valid requests of the fuzzer to all operations in turns from the connections, threads
+/// sharing the transport of the fuzzer, until the number of requests is sent or the time is up
+pub fn run(
+    fuzzer: &mut Fuzzer,
+    connections: usize,
+    max_requests: Option<u32>,
+    duration: Duration,
+) -> Result<Bench> {
+    let concurrency = connections.max(1);
+    let transport = fuzzer.transport();
+    let (sender, receiver) = mpsc::sync_channel::<(usize, Request)>(concurrency * 2);
+    let receiver = Mutex::new(receiver);
+    let mut operations: Vec<String> = Vec::new();
+    let start = Instant::now();
+
+    let samples = thread::scope(|scope| -> Result<Vec<Sample>> {
+        let workers = (0..concurrency)
+            .map(|_| {
+                let (receiver, transport) = (&receiver, transport.clone());
+                scope.spawn(move || {
+                    let mut samples = Vec::new();
+                    loop {
+                        // The lock is released once the request is received
+                        let next = receiver.lock().unwrap().recv();
+                        let (operation, request) = match next {
+                            Ok(next) => next,
+                            Err(_) => return samples,
+                        };
+                        let sent = Instant::now();
+                        let status = transport.send(&request).ok().map(|resp| resp.status);
+                        samples.push(Sample {
+                            operation,
+                            status,
+                            latency: sent.elapsed(),
+                        });
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut sent = 0;
+        let generated = (|| -> Result<()> {
+            loop {
+                let requests = fuzzer.valid_requests()?;
+                if requests.is_empty() {
+                    return Err(anyhow!("no operations to benchmark"));
+                }
+                for (operation, request) in requests {
+                    if start.elapsed() >= duration || max_requests.is_some_and(|max| sent >= max) {
+                        return Ok(());
+                    }
+                    let index = match operations.iter().position(|o| *o == operation) {
+                        Some(index) => index,
+                        None => {
+                            operations.push(operation);
+                            operations.len() - 1
+                        }
+                    };
+                    if sender.send((index, request)).is_err() {
+                        return Ok(());
+                    }
+                    sent += 1;
+                }
+            }
+        })();
+        drop(sender);
+        let samples = workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect();
+        generated.map(|_| samples)
+    })?;
+    let elapsed = start.elapsed();
+
+    let throughput = |requests: usize| requests as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+    let mut benches = Vec::new();
+    for (index, operation) in operations.into_iter().enumerate() {
+        let samples = samples.iter().filter(|s| s.operation == index);
+        let mut latencies = Vec::new();
+        let (mut errors, mut statuses) = (0, BTreeMap::new());
+        for sample in samples {
+            latencies.push(sample.latency);
+            match sample.status {
+                Some(status) => *statuses.entry(status).or_default() += 1,
+                None => errors += 1,
+            }
+        }
+        latencies.sort();
+        benches.push(OperationBench {
+            operation,
+            requests: latencies.len(),
+            errors,
+            statuses,
+            throughput: throughput(latencies.len()),
+            p50_ms: percentile(&latencies, 50.0),
+            p90_ms: percentile(&latencies, 90.0),
+            p99_ms: percentile(&latencies, 99.0),
+            max_ms: percentile(&latencies, 100.0),
+        });
+    }
+    Ok(Bench {
+        concurrency,
+        elapsed_ms: elapsed.as_millis() as u64,
+        requests: samples.len(),
+        errors: benches.iter().map(|bench| bench.errors).sum(),
+        throughput: throughput(samples.len()),
+        operations: benches,
+    })
+}
+
+impl Bench {
+    /// Prints the results as a table of the operations, or as JSON
+    pub fn print(&self, json: bool) -> Result<()> {
+        if json {
+            println!("{}", serde_json::to_string_pretty(self)?);
+            return Ok(());
+        }
+        let rows = self
+            .operations
+            .iter()
+            .map(|bench| {
+                vec![
+                    bench.operation.clone(),
+                    bench.requests.to_string(),
+                    bench.errors.to_string(),
+                    format!("{:.1}", bench.throughput),
+                    format!("{:.1}", bench.p50_ms),
+                    format!("{:.1}", bench.p90_ms),
+                    format!("{:.1}", bench.p99_ms),
+                    format!("{:.1}", bench.max_ms),
+                    bench
+                        .statuses
+                        .iter()
+                        .map(|(status, count)| format!("{}×{}", status, count))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                ]
+            })
+            .collect::<Vec<_>>();
+        print!(
+            "{}",
+            report::table(
+                &[
+                    "OPERATION",
+                    "REQUESTS",
+                    "ERRORS",
+                    "REQ/S",
+                    "P50 MS",
+                    "P90 MS",
+                    "P99 MS",
+                    "MAX MS",
+                    "STATUSES",
+                ],
+                &rows
+            )
+        );
+        println!(
+            "\n{} requests in {:.1}s from {} connections, {:.1} requests/s, {} errors",
+            self.requests,
+            self.elapsed_ms as f64 / 1000.0,
+            self.concurrency,
+            self.throughput,
+            self.errors
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_latency_percentiles_by_nearest_rank() {
+        let sorted = (1..=10).map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(percentile(&sorted, 50.0), 6.0);
+        assert_eq!(percentile(&sorted, 90.0), 9.0);
+        assert_eq!(percentile(&sorted, 99.0), 10.0);
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted[..1], 99.0), 1.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+}
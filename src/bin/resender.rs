@@ -1,6 +1,9 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use argh::FromArgs;
-use serde::Deserialize;
+use openapi_fuzzer::encryption::Encryption;
+use openapi_fuzzer::finding::FORMAT_VERSION;
+use openapi_fuzzer::transport::base64_bytes;
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -17,6 +20,55 @@ struct Args {
     /// extra header
     #[argh(option, short = 'H')]
     header: Vec<Header>,
+
+    /// value of a secret redacted in the finding as `name=value`, it replaces
+    /// the `{{{{redacted:name}}}}` placeholders, can be repeated
+    #[argh(option)]
+    secret: Vec<Secret>,
+
+    /// rewrite the finding file in the current format if it was stored by an
+    /// older version of the fuzzer
+    #[argh(switch)]
+    upgrade: bool,
+
+    /// identity file of age an encrypted finding (`.age`) is decrypted
+    /// with, gpg uses its keyring
+    #[argh(option)]
+    age_identity: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Secret(String, String);
+
+impl FromStr for Secret {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, value)) => Ok(Secret(name.trim().to_lowercase(), value.to_string())),
+            None => Err("invalid secret format, expected `name=value`".to_string()),
+        }
+    }
+}
+
+/// Fills in the values of the redacted secrets
+fn unredact(mut finding: String, secrets: &[Secret]) -> Result<String> {
+    for Secret(name, value) in secrets {
+        // The value is inserted into JSON strings
+        let escaped = serde_json::to_string(value)?;
+        finding = finding.replace(
+            &format!("{{{{redacted:{}}}}}", name),
+            &escaped[1..escaped.len() - 1],
+        );
+    }
+    if let Some(start) = finding.find("{{redacted:") {
+        let name = finding[start + 11..].split("}}").next().unwrap_or_default();
+        eprintln!(
+            "warning: the finding has redacted secrets, fill them in with --secret {}=...",
+            name
+        );
+    }
+    Ok(finding)
 }
 
 #[derive(Debug)]
@@ -40,8 +92,9 @@ impl From<Header> for (String, String) {
     }
 }
 
+/// Payload as stored by versions of the fuzzer before the format was versioned
 #[derive(Debug, Deserialize)]
-struct Payload {
+struct LegacyPayload {
     url: Url,
     method: String,
     path: String,
@@ -51,25 +104,70 @@ struct Payload {
     body: Vec<serde_json::Value>,
 }
 
-#[derive(Debug, Deserialize)]
-struct ResultFile {
-    payload: Payload,
-}
-
-impl Payload {
-    fn send(&mut self, extra_headers: Vec<(String, String)>) -> Result<Response> {
+impl LegacyPayload {
+    fn to_request(&self) -> Result<Request> {
         let mut path_with_params = self.path.to_owned();
         for (name, value) in self.path_params.iter() {
             path_with_params = path_with_params.replace(&format!("{{{}}}", name), value);
         }
-        let mut request = ureq::request_url(
-            &self.method,
-            &self.url.join(path_with_params.trim_start_matches('/'))?,
+        let mut url = self.url.join(path_with_params.trim_start_matches('/'))?;
+        if !self.query_params.is_empty() {
+            url.query_pairs_mut().extend_pairs(self.query_params.iter());
+        }
+
+        Ok(Request {
+            method: self.method.clone(),
+            url,
+            headers: self.headers.clone(),
+            body: self.body.first().cloned(),
+            raw_body: None,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    method: String,
+    url: Url,
+    headers: Vec<(String, String)>,
+    body: Option<serde_json::Value>,
+    /// Body sent as is instead of `body`, e.g. JSON with duplicate keys or binary data
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "base64_bytes"
+    )]
+    raw_body: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResultFile {
+    request: Request,
+}
+
+/// Upgrades the finding to the current format version
+fn migrate(mut finding: serde_json::Value) -> Result<serde_json::Value> {
+    let version = finding["version"].as_u64().unwrap_or(0);
+    if version > u64::from(FORMAT_VERSION) {
+        bail!(
+            "finding has format version {}, but only versions up to {} are supported",
+            version,
+            FORMAT_VERSION
         );
+    }
 
-        for (param, value) in self.query_params.iter() {
-            request = request.query(param, value)
-        }
+    if version == 0 {
+        let payload: LegacyPayload = serde_json::from_value(finding["payload"].clone())
+            .context("unable to parse payload of the finding")?;
+        finding["request"] = serde_json::to_value(payload.to_request()?)?;
+        finding["version"] = 1.into();
+    }
+    Ok(finding)
+}
+
+impl Request {
+    fn send(&mut self, extra_headers: Vec<(String, String)>) -> Result<Response> {
+        let mut request = ureq::request_url(&self.method, &self.url);
 
         for (name, value) in extra_headers {
             let index = self
@@ -86,20 +184,34 @@ impl Payload {
             request = request.set(header, value)
         }
 
-        if !self.body.is_empty() {
-            Ok(request.send_json(self.body[0].clone()).or_any_status()?)
-        } else {
-            request.call().or_any_status().map_err(|e| e.into())
+        match (&self.raw_body, &self.body) {
+            (Some(raw), _) => Ok(request.send_bytes(raw).or_any_status()?),
+            (None, Some(body)) => Ok(request.send_json(body.clone()).or_any_status()?),
+            (None, None) => request.call().or_any_status().map_err(|e| e.into()),
         }
     }
 }
 
 fn main() -> Result<()> {
     let args: Args = argh::from_env();
-    let mut result: ResultFile = serde_json::from_str(&fs::read_to_string(&args.file)?)?;
+    let encryption = Encryption::new(Vec::new(), args.age_identity.clone())?;
+    let stored = encryption.read_to_string(&args.file)?;
+    let encrypted = matches!(
+        args.file.extension().and_then(|e| e.to_str()),
+        Some("age" | "gpg")
+    );
+    if args.upgrade && encrypted {
+        bail!("encrypted findings cannot be upgraded, decrypt them first");
+    }
+    if args.upgrade {
+        let finding = migrate(serde_json::from_str(&stored)?)?;
+        fs::write(&args.file, serde_json::to_string_pretty(&finding)?)?;
+    }
+    let finding = migrate(serde_json::from_str(&unredact(stored, &args.secret)?)?)?;
+    let mut result: ResultFile = serde_json::from_value(finding)?;
 
     let resp = result
-        .payload
+        .request
         .send(args.header.into_iter().map(|h| h.into()).collect())?;
 
     println!("{:?}", &resp);
@@ -113,3 +225,83 @@ fn main() -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_raw_bodies() {
+        let stored = openapi_fuzzer::transport::Request {
+            method: "POST".to_string(),
+            url: Url::parse("http://localhost/upload").unwrap(),
+            headers: vec![("Content-Type".to_string(), "application/zip".to_string())],
+            body: None,
+            raw_body: Some(vec![0x50, 0x4b, 0x03, 0x04, 0xff, 0x00]),
+            timeout: None,
+            stream: None,
+        };
+        let request: Request =
+            serde_json::from_value(serde_json::to_value(&stored).unwrap()).unwrap();
+        assert_eq!(request.raw_body, stored.raw_body);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::to_value(&stored).unwrap()
+        );
+
+        let request: Request = serde_json::from_value(serde_json::json!({
+            "method": "GET",
+            "url": "http://localhost/items",
+            "headers": [],
+            "body": null,
+        }))
+        .unwrap();
+        assert_eq!(request.raw_body, None);
+    }
+
+    #[test]
+    fn resends_binary_bodies_as_is() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            let mut buffer = [0; 4096];
+            loop {
+                let read = stream.read(&mut buffer).unwrap();
+                received.extend_from_slice(&buffer[..read]);
+                let head = match received.windows(4).position(|w| w == b"\r\n\r\n") {
+                    Some(end) => end + 4,
+                    None => continue,
+                };
+                let length = String::from_utf8_lossy(&received[..head])
+                    .lines()
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                    .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                if read == 0 || received.len() >= head + length {
+                    stream
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                        .unwrap();
+                    return received.split_off(head);
+                }
+            }
+        });
+
+        // Start of a zip archive, not valid UTF-8
+        let body = vec![0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0xff, 0xfe, 0x00, 0x80];
+        let mut request = Request {
+            method: "POST".to_string(),
+            url: Url::parse(&format!("http://{}/upload", address)).unwrap(),
+            headers: vec![("Content-Type".to_string(), "application/zip".to_string())],
+            body: Some(serde_json::json!("ignored")),
+            raw_body: Some(body.clone()),
+        };
+        let response = request.send(Vec::new()).unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(server.join().unwrap(), body);
+    }
+}
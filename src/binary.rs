@@ -0,0 +1,173 @@
+//! Binary request bodies and base64 strings, mostly files with the magic header of a known
+//! format, so that binary-accepting endpoints get past their format detection
+
+use std::{io::Write, sync::OnceLock};
+
+use arbitrary::{Arbitrary, Unstructured};
+use flate2::{write::DeflateEncoder, Compression, Crc};
+use rand::{rngs::StdRng, Rng};
+use tracing::debug;
+
+use crate::payload::{Generator, Payload};
+
+/// Most random bytes after the magic header
+const MAX_RANDOM_BYTES: usize = 1024;
+
+/// Size of the file in the zip bomb once decompressed
+const BOMB_SIZE: usize = 256 * 1024 * 1024;
+
+/// Probability that a zip-accepting body is replaced by the zip bomb, when enabled
+const BOMB_PROBABILITY: f64 = 0.1;
+
+/// Magic headers of common file formats with their media types
+const MAGIC: &[(&str, &[u8])] = &[
+    ("image/png", b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR"),
+    ("image/jpeg", b"\xff\xd8\xff\xe0\x00\x10JFIF\x00"),
+    ("image/gif", b"GIF89a"),
+    ("application/pdf", b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n"),
+    ("application/zip", b"PK\x03\x04\x14\x00\x00\x00\x08\x00"),
+    ("application/gzip", b"\x1f\x8b\x08\x00\x00\x00\x00\x00"),
+];
+
+/// Whether request bodies of the media type are binary
+pub fn is_binary(content_type: &str) -> bool {
+    let content_type = content_type.to_lowercase();
+    ["image/", "audio/", "video/", "font/"]
+        .iter()
+        .any(|prefix| content_type.starts_with(prefix))
+        || [
+            "application/octet-stream",
+            "application/pdf",
+            "application/zip",
+            "application/x-zip-compressed",
+            "application/gzip",
+        ]
+        .contains(&content_type.as_str())
+}
+
+/// Number below `n`, from several bytes as the random data of payloads is UTF-8 and its
+/// bytes are not uniform
+fn pick(gen: &mut Unstructured, n: u32) -> u32 {
+    u32::arbitrary(gen).unwrap_or(0) % n
+}
+
+/// Bytes of a body of the media type: random ones, empty, or a file with the magic header of
+/// the media type or another format followed by random bytes
+pub fn bytes(content_type: &str, gen: &mut Unstructured) -> Vec<u8> {
+    let matching = MAGIC
+        .iter()
+        .find(|(media, _)| content_type.eq_ignore_ascii_case(media));
+    let magic: &[u8] = match pick(gen, 10) {
+        0 => return Vec::new(),
+        1..=2 => &[],
+        // Mismatched formats probe the content sniffing
+        3..=4 => gen.choose(MAGIC).map(|(_, magic)| *magic).unwrap_or(&[]),
+        _ => match matching {
+            Some((_, magic)) => magic,
+            None => gen.choose(MAGIC).map(|(_, magic)| *magic).unwrap_or(&[]),
+        },
+    };
+    let len = gen.int_in_range(0..=MAX_RANDOM_BYTES).unwrap_or(0);
+    let mut bytes = magic.to_vec();
+    bytes.extend(gen.bytes(len.min(gen.len())).unwrap_or(&[]));
+    bytes
+}
+
+/// String of the `byte` format, valid base64 of a file or corrupted: invalid characters,
+/// missing padding, the URL-safe alphabet, line breaks or truncated
+pub fn base64(gen: &mut Unstructured) -> String {
+    let encoded = base64::encode(bytes("", gen));
+    let at = |gen: &mut Unstructured, len: usize| gen.int_in_range(0..=len).unwrap_or(0);
+    match pick(gen, 10) {
+        0 if !encoded.is_empty() => {
+            let mut corrupted = encoded.clone();
+            let i = at(gen, encoded.len() - 1);
+            corrupted.replace_range(i..=i, "*");
+            corrupted
+        }
+        1 => encoded.trim_end_matches('=').to_string(),
+        2 => encoded.replace('+', "-").replace('/', "_"),
+        3 => encoded
+            .as_bytes()
+            .chunks(76)
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect::<Vec<_>>()
+            .join("\r\n"),
+        4 => {
+            let len = encoded.len();
+            encoded[..at(gen, len)].to_string()
+        }
+        5 => format!("data:application/octet-stream;base64,{}", encoded),
+        _ => encoded,
+    }
+}
+
+/// String of the `binary` format in a JSON body, every byte is a character
+pub fn binary_string(gen: &mut Unstructured) -> String {
+    bytes("", gen).into_iter().map(char::from).collect()
+}
+
+/// Zip archive with a single file of zeros that is much larger decompressed
+fn zip_bomb() -> &'static [u8] {
+    static BOMB: OnceLock<Vec<u8>> = OnceLock::new();
+    BOMB.get_or_init(|| {
+        let zeros = vec![0; 1024 * 1024];
+        let mut crc = Crc::new();
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        for _ in 0..BOMB_SIZE / zeros.len() {
+            crc.update(&zeros);
+            encoder.write_all(&zeros).expect("writing to memory");
+        }
+        let data = encoder.finish().expect("writing to memory");
+        let name = b"bomb.txt";
+        let (crc, compressed, size) = (crc.sum(), data.len() as u32, BOMB_SIZE as u32);
+
+        let mut zip = Vec::with_capacity(data.len() + 128);
+        // Local file header, deflate without a data descriptor
+        zip.extend(b"PK\x03\x04\x14\x00\x00\x00\x08\x00\x00\x00\x00\x00");
+        zip.extend(crc.to_le_bytes());
+        zip.extend(compressed.to_le_bytes());
+        zip.extend(size.to_le_bytes());
+        zip.extend((name.len() as u16).to_le_bytes());
+        zip.extend(0u16.to_le_bytes());
+        zip.extend(name);
+        zip.extend(&data);
+        // Central directory with the file
+        let directory = zip.len() as u32;
+        zip.extend(b"PK\x01\x02\x14\x00\x14\x00\x00\x00\x08\x00\x00\x00\x00\x00");
+        zip.extend(crc.to_le_bytes());
+        zip.extend(compressed.to_le_bytes());
+        zip.extend(size.to_le_bytes());
+        zip.extend((name.len() as u16).to_le_bytes());
+        // Extra field, comment, disk, attributes and offset of the local header
+        zip.extend([0; 12]);
+        zip.extend(0u32.to_le_bytes());
+        zip.extend(name);
+        let directory_size = zip.len() as u32 - directory;
+        // End of the central directory
+        zip.extend(b"PK\x05\x06\x00\x00\x00\x00\x01\x00\x01\x00");
+        zip.extend(directory_size.to_le_bytes());
+        zip.extend(directory.to_le_bytes());
+        zip.extend(0u16.to_le_bytes());
+        debug!(compressed = zip.len(), decompressed = BOMB_SIZE, "zip bomb");
+        zip
+    })
+}
+
+/// Replaces binary bodies of media types that may be archives by a zip bomb from time to time
+#[derive(Debug)]
+pub struct ZipBombs;
+
+impl Generator for ZipBombs {
+    fn generate(&self, payload: &mut Payload<'_>, rng: &mut StdRng) {
+        let archive = payload.headers.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("content-type")
+                && ["zip", "octet-stream"]
+                    .iter()
+                    .any(|media| value.to_lowercase().contains(media))
+        });
+        if archive && payload.raw_body.is_some() && rng.gen_bool(BOMB_PROBABILITY) {
+            payload.raw_body = Some(zip_bomb().to_vec());
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use tracing::info;
+
+use openapi_fuzzer::config::{self, duration, Config};
+use openapi_fuzzer::report;
+
+use crate::{prepare, Outputs};
+
+/// Time slice of a target when the campaign does not set it
+const DEFAULT_SLICE: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Schedule {
+    /// Every target gets a slice in turn
+    #[default]
+    RoundRobin,
+    /// Targets get slices in proportion to their weights
+    Weighted,
+}
+
+/// Service fuzzed by the campaign
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Target {
+    /// Name of the target, its findings are saved to `results/<name>`
+    name: String,
+    #[serde(default = "default_weight")]
+    weight: u32,
+    /// Config file of the target
+    config: Option<PathBuf>,
+    /// Profile of the config file
+    profile: Option<String>,
+    /// Options of the target, overriding the ones from its config file
+    #[serde(default)]
+    options: Config,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Campaign {
+    #[serde(default, deserialize_with = "duration")]
+    slice: Option<Duration>,
+    #[serde(default)]
+    schedule: Schedule,
+    /// End of the campaign, it runs until the budgets of all targets are exhausted otherwise
+    #[serde(default, deserialize_with = "duration")]
+    duration: Option<Duration>,
+    targets: Vec<Target>,
+}
+
+impl Target {
+    /// Configurations of the target, the first ones take precedence
+    fn configs(self) -> Result<Vec<Config>> {
+        let mut configs = vec![self.options];
+        match (&self.config, &self.profile) {
+            (Some(file), profile) => {
+                let mut config = Config::load(file)?;
+                if let Some(name) = profile {
+                    configs.push(config.profile(name)?);
+                }
+                configs.push(config);
+            }
+            (None, Some(_)) => return Err(anyhow!("profiles can be used only with config")),
+            (None, None) => {}
+        }
+        Ok(configs)
+    }
+}
+
+/// Picks the next target by smooth weighted round-robin, targets with higher weights get
+/// more slices, but not in a row
+fn next(weights: &[u32], current: &mut [i64], active: &[bool]) -> Option<usize> {
+    let total: i64 = (0..weights.len())
+        .filter(|&i| active[i])
+        .map(|i| weights[i] as i64)
+        .sum();
+    for i in (0..weights.len()).filter(|&i| active[i]) {
+        current[i] += weights[i] as i64;
+    }
+    let chosen = (0..weights.len())
+        .filter(|&i| active[i])
+        .max_by_key(|&i| (current[i], std::cmp::Reverse(i)))?;
+    current[chosen] -= total;
+    Some(chosen)
+}
+
+/// Runs the campaign, returns the reasons why it should fail
+pub fn run(file: &Path) -> Result<Vec<String>> {
+    let campaign: Campaign =
+        config::load(file).context(format!("Failed to load campaign {}", file.display()))?;
+    if campaign.targets.is_empty() {
+        return Err(anyhow!("The campaign has no targets"));
+    }
+    let slice = campaign.slice.unwrap_or(DEFAULT_SLICE);
+    let weights = campaign
+        .targets
+        .iter()
+        .map(|target| match campaign.schedule {
+            Schedule::RoundRobin => 1,
+            Schedule::Weighted => target.weight,
+        })
+        .collect::<Vec<_>>();
+
+    let mut targets: Vec<(String, _, Outputs)> = Vec::new();
+    for target in campaign.targets {
+        let name = target.name.clone();
+        if targets.iter().any(|(other, _, _)| *other == name) {
+            return Err(anyhow!("Duplicate target `{}`", name));
+        }
+        let (builder, outputs) = target
+            .configs()
+            .and_then(prepare)
+            .context(format!("Failed to set up target `{}`", name))?;
+        let fuzzer = builder.results_dir(format!("results/{}", name)).build();
+        targets.push((name, fuzzer, outputs));
+    }
+
+    let start = Instant::now();
+    let mut active = weights.iter().map(|&weight| weight > 0).collect::<Vec<_>>();
+    let mut current = vec![0; weights.len()];
+    while campaign.duration.is_none_or(|max| start.elapsed() < max) {
+        let i = match next(&weights, &mut current, &active) {
+            Some(i) => i,
+            None => break,
+        };
+        let (name, fuzzer, _) = &mut targets[i];
+        info!(%name, "slice started");
+        let exhausted = fuzzer
+            .run_slice(slice)
+            .context(format!("Failed to fuzz target `{}`", name))?;
+        eprintln!(
+            "{}: {} requests, {} findings{}",
+            name,
+            fuzzer.stats().total,
+            fuzzer.findings().len(),
+            match exhausted {
+                true => ", budget exhausted",
+                false => "",
+            }
+        );
+        active[i] &= !exhausted;
+    }
+
+    let mut failures = Vec::new();
+    for (name, fuzzer, outputs) in targets.iter_mut() {
+        fuzzer.finish()?;
+        let results_dir = Path::new("results").join(&name);
+        fs::create_dir_all(&results_dir)?;
+        report::markdown_summary(
+            fuzzer.findings(),
+            fuzzer.stats(),
+            fuzzer.operations(),
+            fuzzer.run_info().namespace.as_deref(),
+            &results_dir.join("summary.md"),
+        )?;
+        failures.extend(
+            outputs
+                .write(fuzzer)?
+                .into_iter()
+                .map(|failure| format!("{}: {}", name, failure)),
+        );
+    }
+    Ok(failures)
+}
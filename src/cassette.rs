@@ -0,0 +1,330 @@
+//! Cassettes of fuzzing sessions: every request sent to the API with its response, when it
+//! was sent and the seed of the run, one JSON object per line after a header. Replaying a
+//! cassette sends the same requests in the same order, e.g. to check whether a bug reported
+//! with a cassette is fixed in another build of the API. Credentials are redacted in the
+//! cassette, the replay sends the configured ones instead.
+
+use std::{
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use url::Url;
+
+use crate::encryption::Encryption;
+use crate::finding::RunInfo;
+use crate::redact::Redactor;
+use crate::stream::StreamBudget;
+use crate::transport::{relative_path, Request, Response, Transport};
+
+/// Version of the format of cassettes, replaying refuses newer ones
+pub const CASSETTE_VERSION: u32 = 1;
+
+/// First line of a cassette
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    pub fuzzer_version: String,
+    /// Base url of the API the session was recorded against
+    pub url: Url,
+    /// SHA-256 of the specification file
+    pub spec_hash: String,
+    pub run_seed: u64,
+    /// Start of the session, RFC 3339
+    pub recorded: String,
+}
+
+/// Request sent during the session and its response, or the error sending it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    /// Time since the start of the session the request was sent at
+    pub offset_ms: u64,
+    pub request: Request,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<Response>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Exchange {
+    fn outcome(&self) -> String {
+        match (&self.response, &self.error) {
+            (Some(response), _) => response.status.to_string(),
+            (None, Some(error)) => format!("error: {}", error),
+            (None, None) => "nothing".to_string(),
+        }
+    }
+}
+
+/// Records the exchanges of the transport to a cassette, each one as soon as it completes
+pub struct Recorder<T> {
+    transport: T,
+    file: Mutex<Box<dyn Write + Send>>,
+    start: Instant,
+    redactor: Redactor,
+}
+
+impl<T: Transport> Recorder<T> {
+    /// Creates the cassette with the header of the session against the url, the secrets the
+    /// redactor finds are left out of the exchanges. The cassette is encrypted as it is written
+    /// if enabled.
+    pub fn create(
+        transport: T,
+        file: &Path,
+        url: &Url,
+        run: &RunInfo,
+        redactor: Redactor,
+        encryption: &Encryption,
+    ) -> Result<Recorder<T>> {
+        let header = Header {
+            version: CASSETTE_VERSION,
+            fuzzer_version: run.fuzzer_version.to_string(),
+            url: url.clone(),
+            spec_hash: run.spec_hash.clone(),
+            run_seed: run.run_seed,
+            recorded: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        };
+        let mut cassette = encryption
+            .create(file)
+            .with_context(|| format!("Failed to create cassette {}", file.display()))?;
+        writeln!(cassette, "{}", serde_json::to_string(&header)?)?;
+        Ok(Recorder {
+            transport,
+            file: Mutex::new(cassette),
+            start: Instant::now(),
+            redactor,
+        })
+    }
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let offset_ms = self.start.elapsed().as_millis() as u64;
+        let result = self.transport.send(request);
+        let exchange = Exchange {
+            offset_ms,
+            request: request.clone(),
+            response: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        };
+        let secrets = match &result {
+            Ok(response) => self.redactor.secrets(request, response),
+            Err(_) => self.redactor.request_secrets(request),
+        };
+        let mut exchange = serde_json::to_value(&exchange)?;
+        secrets.redact(&mut exchange);
+        // A session is still worth fuzzing when its cassette cannot be written
+        let line = serde_json::to_string(&exchange)?;
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(file, "{}", line) {
+            warn!(error = %e, "failed to record exchange to cassette");
+        }
+        result
+    }
+}
+
+/// Recorded session
+#[derive(Debug)]
+pub struct Cassette {
+    pub header: Header,
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Cassette {
+    pub fn load(file: &Path, encryption: &Encryption) -> Result<Cassette> {
+        let content = encryption
+            .read_to_string(file)
+            .with_context(|| format!("Failed to read cassette {}", file.display()))?;
+        let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+        let header: Header = serde_json::from_str(
+            lines
+                .next()
+                .ok_or_else(|| anyhow!("{} is an empty cassette", file.display()))?,
+        )
+        .context("Invalid header of cassette")?;
+        if header.version > CASSETTE_VERSION {
+            return Err(anyhow!(
+                "cassette version {} is newer than the supported version {}",
+                header.version,
+                CASSETTE_VERSION
+            ));
+        }
+        let exchanges = lines
+            .enumerate()
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("Invalid exchange on line {} of cassette", i + 2))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Cassette { header, exchanges })
+    }
+}
+
+/// Url of the request against another base url of the API
+fn rebased(url: &Url, recorded: &Url, base: &Url) -> Url {
+    let rest = match relative_path(url.path(), recorded.path()) {
+        Some(rest) if url.origin() == recorded.origin() => rest,
+        // Requests to other hosts, e.g. the health check, are sent as recorded
+        _ => return url.clone(),
+    };
+    let mut rebased = base.clone();
+    rebased.set_path(&format!("{}{}", base.path().trim_end_matches('/'), rest));
+    rebased.set_query(url.query());
+    rebased
+}
+
+/// Whether the value is a redacted secret
+fn is_redacted(value: &str) -> bool {
+    value.contains("{{redacted:")
+}
+
+/// The recorded request without its redacted credentials, for the configured ones to be sent
+fn unredacted(mut request: Request) -> Request {
+    request.headers.retain(|(_, value)| !is_redacted(value));
+    if request
+        .url
+        .query_pairs()
+        .any(|(_, value)| is_redacted(&value))
+    {
+        let pairs = request
+            .url
+            .query_pairs()
+            .filter(|(_, value)| !is_redacted(value))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect::<Vec<_>>();
+        match pairs.is_empty() {
+            true => request.url.set_query(None),
+            false => {
+                request.url.query_pairs_mut().clear().extend_pairs(pairs);
+            }
+        }
+    }
+    request
+}
+
+/// Exchange whose replay ended unlike its recording
+#[derive(Debug)]
+pub struct Difference {
+    /// Position in the cassette, from 1
+    pub index: usize,
+    pub method: String,
+    pub path: String,
+    pub recorded: String,
+    pub replayed: String,
+}
+
+/// Sends the requests of the cassette in order with the transport, to the base url instead
+/// of the recorded one if given, and at their recorded offsets if timed. Exchanges whose
+/// status or error changed are returned.
+pub fn replay(
+    cassette: &Cassette,
+    transport: &dyn Transport,
+    base: Option<&Url>,
+    timed: bool,
+) -> Vec<Difference> {
+    let start = Instant::now();
+    let mut differences = Vec::new();
+    for (i, exchange) in cassette.exchanges.iter().enumerate() {
+        if timed {
+            let offset = Duration::from_millis(exchange.offset_ms);
+            thread::sleep(offset.saturating_sub(start.elapsed()));
+        }
+        let mut request = unredacted(exchange.request.clone());
+        if let Some(base) = base {
+            request.url = rebased(&request.url, &cassette.header.url, base);
+        }
+        // Streams are read within the default budget, their own is not recorded
+        if exchange
+            .response
+            .as_ref()
+            .is_some_and(|r| !r.events.is_empty())
+        {
+            request.stream = Some(StreamBudget::default());
+        }
+        let offset_ms = start.elapsed().as_millis() as u64;
+        let result = transport.send(&request);
+        let replayed = Exchange {
+            offset_ms,
+            request,
+            response: result.as_ref().ok().cloned(),
+            error: result.err().map(|e| format!("{:#}", e)),
+        };
+        let changed = match (&exchange.response, &replayed.response) {
+            (Some(recorded), Some(response)) => recorded.status != response.status,
+            (None, None) => false,
+            _ => true,
+        };
+        if changed {
+            differences.push(Difference {
+                index: i + 1,
+                method: exchange.request.method.clone(),
+                path: exchange.request.url.path().to_string(),
+                recorded: exchange.outcome(),
+                replayed: replayed.outcome(),
+            });
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redact;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn rebases_urls_under_the_recorded_one() {
+        let recorded = url("http://localhost:8080/api/");
+        let base = url("https://staging/v1");
+        assert_eq!(
+            rebased(
+                &url("http://localhost:8080/api/items?a=1"),
+                &recorded,
+                &base
+            ),
+            url("https://staging/v1/items?a=1")
+        );
+        let other = url("http://localhost:8080/apiv2/items");
+        assert_eq!(rebased(&other, &recorded, &base), other);
+        let other = url("http://health:8080/api/items");
+        assert_eq!(rebased(&other, &recorded, &base), other);
+    }
+
+    #[test]
+    fn leaves_out_redacted_credentials() {
+        let request = Request {
+            method: "GET".to_string(),
+            url: url(&format!(
+                "http://localhost/items?api_key={}&a=1",
+                redact::placeholder("api_key")
+            )),
+            headers: vec![
+                (
+                    "authorization".to_string(),
+                    format!("Bearer {}", redact::placeholder("authorization")),
+                ),
+                ("accept".to_string(), "*/*".to_string()),
+            ],
+            body: None,
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        };
+        let request = unredacted(request);
+        assert_eq!(request.url.query(), Some("a=1"));
+        assert_eq!(
+            request.headers,
+            vec![("accept".to_string(), "*/*".to_string())]
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Chaos on the client side: the request is sent slowly with pauses between its parts, with a
+//! truncated body, aborted in the middle of the body and with the connection closed before the
+//! response is read, the way misbehaving clients and flaky networks send requests. The target
+//! should answer the complete requests, reject or drop the broken ones and afterwards answer
+//! the request as before: server errors, broken requests accepted and a target failing or
+//! slowing down after the faults point to leaked connections, workers or locks, and a safe
+//! request answered with another class of status after them to state corrupted by half
+//! processed requests. Requests that are not idempotent are aborted and closed early once
+//! each instead of in a row, every complete one may change the state of the API.
+
+use std::{
+    io::Write,
+    net::{Shutdown, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use url::Url;
+
+use crate::idempotency::is_safe;
+use crate::proxy::{self, Connection};
+use crate::smuggling::{parse_response, receive};
+use crate::transport::{Request, Response, Transport};
+
+/// Time to wait for the response to a faulty request
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Parts the slow request is sent in
+const SLOW_PARTS: usize = 8;
+
+/// Longest pause between the parts of the slow request
+const MAX_PAUSE: Duration = Duration::from_millis(300);
+
+/// Connections aborted or closed early in a row, enough to exhaust small pools of workers or
+/// connections if the target leaks them
+const ABORTS: usize = 20;
+
+/// Times slower than before the follow-up request may be answered, at least a second
+const SLOWDOWN_FACTOR: u32 = 10;
+
+/// Connection to the host of the url, with the timeout of the responses
+fn connect(url: &Url) -> Result<(Box<dyn Connection>, TcpStream)> {
+    let (connection, socket) = proxy::open(url)?;
+    socket.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    Ok((connection, socket))
+}
+
+/// Head and body of the request as sent over HTTP/1.1, on a connection closed after it
+fn serialize(request: &Request) -> (Vec<u8>, Vec<u8>) {
+    let body = match (&request.raw_body, &request.body) {
+        (Some(raw), _) => raw.clone(),
+        (None, Some(body)) => serde_json::to_vec(body).unwrap_or_default(),
+        (None, None) => Vec::new(),
+    };
+    let mut target = request.url.path().to_string();
+    if let Some(query) = request.url.query() {
+        target = target + "?" + query;
+    }
+    let host = match (request.url.host_str(), request.url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => String::new(),
+    };
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\n",
+        request.method, target, host
+    );
+    let mut content_type = false;
+    for (name, value) in request.headers.iter() {
+        match name.to_lowercase().as_str() {
+            "host" | "content-length" | "transfer-encoding" | "connection" => continue,
+            "content-type" => content_type = true,
+            _ => {}
+        }
+        head += &format!("{}: {}\r\n", name, value);
+    }
+    if request.body.is_some() && request.raw_body.is_none() && !content_type {
+        head += "Content-Type: application/json\r\n";
+    }
+    if !body.is_empty() || request.body.is_some() {
+        head += &format!("Content-Length: {}\r\n", body.len());
+    }
+    head += "Connection: close\r\n\r\n";
+    (head.into_bytes(), body)
+}
+
+/// Fault injected into the request
+#[derive(Debug, Clone, Copy)]
+enum Fault {
+    /// Sent in parts with pauses between them, must be answered as usual
+    Slow,
+    /// Body cut short, or the head without its end without a body, and the sending side of
+    /// the connection closed, must be rejected
+    Truncated,
+    /// Connections closed in the middle of the body
+    Aborted,
+    /// Connections closed right after the request, before the response is read
+    ClosedEarly,
+}
+
+impl Fault {
+    fn name(self) -> &'static str {
+        match self {
+            Fault::Slow => "slow request",
+            Fault::Truncated => "truncated request",
+            Fault::Aborted => "request aborted mid-body",
+            Fault::ClosedEarly => "connection closed before the response",
+        }
+    }
+}
+
+/// Point the request is cut at, in the body if it has one, otherwise in the head
+fn cut(head: &[u8], body: &[u8], rng: &mut StdRng) -> usize {
+    match body.len() {
+        0 => rng.gen_range(head.len() / 2..head.len() - 2),
+        len => head.len() + rng.gen_range(0..len),
+    }
+}
+
+/// Response to the request with the fault, `None` for the faults that do not wait for one.
+/// Connections are aborted or closed early in a row for idempotent requests, once otherwise.
+fn inject(
+    fault: Fault,
+    request: &Request,
+    idempotent: bool,
+    rng: &mut StdRng,
+) -> Result<Option<Response>> {
+    let (head, body) = serialize(request);
+    let raw = [head.as_slice(), body.as_slice()].concat();
+    match fault {
+        Fault::Slow => {
+            let (mut connection, socket) = connect(&request.url)?;
+            let start = Instant::now();
+            for part in raw.chunks(raw.len().div_ceil(SLOW_PARTS)) {
+                connection.write_all(part)?;
+                connection.flush()?;
+                thread::sleep(rng.gen_range(Duration::ZERO..MAX_PAUSE));
+            }
+            let received = receive(connection.as_mut(), &socket)?;
+            Ok(Some(parse_response(&received, start.elapsed())))
+        }
+        Fault::Truncated => {
+            let (mut connection, socket) = connect(&request.url)?;
+            let start = Instant::now();
+            connection.write_all(&raw[..cut(&head, &body, rng)])?;
+            connection.flush()?;
+            socket.shutdown(Shutdown::Write)?;
+            let received = receive(connection.as_mut(), &socket)?;
+            Ok(Some(parse_response(&received, start.elapsed())))
+        }
+        Fault::Aborted | Fault::ClosedEarly => {
+            let times = match idempotent {
+                true => ABORTS,
+                false => 1,
+            };
+            for _ in 0..times {
+                // A target that stops accepting connections is found by the request after
+                let (mut connection, socket) = match connect(&request.url) {
+                    Ok(connected) => connected,
+                    Err(_) => break,
+                };
+                let end = match fault {
+                    Fault::Aborted => cut(&head, &body, rng),
+                    _ => raw.len(),
+                };
+                // The target may close the connection first
+                let _ = connection
+                    .write_all(&raw[..end])
+                    .and_then(|_| connection.flush());
+                let _ = socket.shutdown(Shutdown::Both);
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Sends the successful request with the faults in random order and then normally again, and
+/// returns the indicators of a target mishandling them with the response showing the first
+/// one. The faults are chosen from the seed of the payload.
+pub fn probe(
+    transport: &dyn Transport,
+    request: &Request,
+    before: &Response,
+    idempotent: bool,
+    seed: u64,
+) -> Result<Option<(Response, Vec<String>)>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut faults = [
+        Fault::Slow,
+        Fault::Truncated,
+        Fault::Aborted,
+        Fault::ClosedEarly,
+    ];
+    faults.shuffle(&mut rng);
+
+    let mut indicators = Vec::new();
+    let mut first = None;
+    for fault in faults {
+        let response = match inject(fault, request, idempotent, &mut rng)? {
+            Some(response) => response,
+            None => continue,
+        };
+        let indicator = match fault {
+            _ if response.status / 100 == 5 => {
+                format!("{}: server error {}", fault.name(), response.status)
+            }
+            Fault::Slow if response.status == 0 => format!("{}: no response", fault.name()),
+            Fault::Truncated if response.status / 100 == 2 => format!(
+                "{}: accepted with status {} although the request is incomplete",
+                fault.name(),
+                response.status
+            ),
+            _ => continue,
+        };
+        indicators.push(indicator);
+        first.get_or_insert(response);
+    }
+
+    let start = Instant::now();
+    let after = transport.send(request);
+    let limit = (before.elapsed * SLOWDOWN_FACTOR).max(Duration::from_secs(1));
+    let indicator = match &after {
+        Ok(after) if after.status / 100 == 5 && before.status / 100 != 5 => Some(format!(
+            "after the faults the request failed with {}, it was answered with {} before",
+            after.status, before.status
+        )),
+        Ok(after) if after.elapsed > limit => Some(format!(
+            "after the faults the request took {} ms, {} ms before",
+            after.elapsed.as_millis(),
+            before.elapsed.as_millis()
+        )),
+        Ok(after) if is_safe(&request.method) && after.status / 100 != before.status / 100 => {
+            Some(format!(
+                "after the faults the request was answered with {}, it was answered with {} \
+             before, half processed requests may have changed the state",
+                after.status, before.status
+            ))
+        }
+        Ok(_) => None,
+        Err(e) => Some(format!(
+            "after the faults the request got no response ({:#})",
+            e
+        )),
+    };
+    if let Some(indicator) = indicator {
+        indicators.push(indicator);
+        let after = after.unwrap_or_else(|_| Response::none(start.elapsed()));
+        first.get_or_insert(after);
+    }
+    Ok(first.map(|response| (response, indicators)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serializes_requests_closing_their_connection() {
+        let request = Request {
+            method: "POST".to_string(),
+            url: Url::parse("http://localhost:8080/items?a=1").unwrap(),
+            headers: vec![
+                ("Connection".to_string(), "keep-alive".to_string()),
+                ("X-Trace".to_string(), "1".to_string()),
+            ],
+            body: Some(json!({"a": 1})),
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        };
+        let (head, body) = serialize(&request);
+        assert_eq!(
+            String::from_utf8(head).unwrap(),
+            "POST /items?a=1 HTTP/1.1\r\nHost: localhost:8080\r\nX-Trace: 1\r\n\
+             Content-Type: application/json\r\nContent-Length: 7\r\nConnection: close\r\n\r\n"
+        );
+        assert_eq!(body, b"{\"a\":1}");
+    }
+
+    #[test]
+    fn cuts_the_body_or_the_head() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let head = b"GET / HTTP/1.1\r\nHost: x\r\n\r\n";
+        for _ in 0..100 {
+            let end = cut(head, b"", &mut rng);
+            assert!(end >= head.len() / 2 && end < head.len() - 2);
+            let end = cut(head, b"{}", &mut rng);
+            assert!(end >= head.len() && end < head.len() + 2);
+        }
+    }
+}
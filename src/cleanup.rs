@@ -0,0 +1,254 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use openapi_utils::ReferenceOrExt;
+use openapiv3::{OpenAPI, PathItem};
+use percent_encoding::utf8_percent_encode;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::payload::PATH_SEGMENT;
+use crate::transport::{redacted_url, Request, Response, Transport};
+
+/// When the resources created by the fuzzer are deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CleanupMode {
+    /// Once at the end of the run
+    End,
+    /// After every round
+    Round,
+}
+
+impl FromStr for CleanupMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "end" => Ok(CleanupMode::End),
+            "round" => Ok(CleanupMode::Round),
+            _ => Err(format!("unknown cleanup `{}`, expected end or round", s)),
+        }
+    }
+}
+
+/// Request sent at the end of the run, given as the method and the path relative to the url
+/// of the API, e.g. `DELETE /fixtures?created-by=fuzzer`
+#[derive(Debug, Clone)]
+pub struct Teardown {
+    pub method: String,
+    pub path: String,
+}
+
+impl FromStr for Teardown {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once(' ') {
+            Some((method, path)) if !method.is_empty() && path.trim().starts_with('/') => {
+                Ok(Teardown {
+                    method: method.to_uppercase(),
+                    path: path.trim().to_string(),
+                })
+            }
+            _ => Err(format!(
+                "invalid teardown request `{}`, expected a method and a path like `DELETE /fixtures`",
+                s
+            )),
+        }
+    }
+}
+
+/// Resources created by successful `POST` requests, deleted with the `DELETE` operation of the
+/// item path below the collection, e.g. `DELETE /users/{userId}` for `POST /users`
+#[derive(Debug, Default)]
+pub struct Cleanup {
+    /// Item path and the name of its parameter for each collection path
+    deletes: BTreeMap<String, (String, String)>,
+    created: Vec<Url>,
+}
+
+impl Cleanup {
+    pub fn new(schema: &OpenAPI) -> Cleanup {
+        let deletes = items(schema, |item| item.delete.is_some());
+        debug!(collections = deletes.len(), "deletable resources");
+        Cleanup {
+            deletes,
+            created: Vec::new(),
+        }
+    }
+
+    /// Remembers the resource if the response is to a successful `POST` of a collection with a
+    /// `DELETE` operation, the identifier is taken from the body or the `Location` header
+    pub fn track(&mut self, base: &Url, method: &str, path: &str, response: &Response) {
+        if method != "POST" || response.status / 100 != 2 {
+            return;
+        }
+        let (item_path, name) = match self.deletes.get(path) {
+            Some(delete) => delete,
+            None => return,
+        };
+        if let Some(url) = created(base, path, item_path, name, response) {
+            self.created.push(url);
+        }
+    }
+
+    /// Deletes the created resources, the newest first so that children go before their parents
+    pub fn delete_created(&mut self, transport: &dyn Transport, headers: &[(String, String)]) {
+        let created = std::mem::take(&mut self.created);
+        if created.is_empty() {
+            return;
+        }
+        let mut deleted = 0;
+        for url in created.into_iter().rev() {
+            if send(transport, headers, "DELETE", url) {
+                deleted += 1;
+            }
+        }
+        info!(deleted, "created resources cleaned up");
+    }
+}
+
+/// Sends the user defined teardown requests
+pub fn teardown(
+    transport: &dyn Transport,
+    base: &Url,
+    headers: &[(String, String)],
+    requests: &[Teardown],
+) {
+    for request in requests {
+        match base.join(request.path.trim_start_matches('/')) {
+            Ok(url) => {
+                send(transport, headers, &request.method, url);
+            }
+            Err(e) => warn!(path = %request.path, "invalid teardown url: {}", e),
+        }
+    }
+}
+
+/// Sends the request, returns whether it was successful
+fn send(transport: &dyn Transport, headers: &[(String, String)], method: &str, url: Url) -> bool {
+    let request = Request {
+        method: method.to_string(),
+        url,
+        headers: headers.to_vec(),
+        body: None,
+        raw_body: None,
+        timeout: None,
+        stream: None,
+    };
+    match transport.send(&request) {
+        Ok(response) if response.status / 100 == 2 || response.status == 404 => true,
+        Ok(response) => {
+            warn!(method, url = %redacted_url(&request.url), status = response.status, "cleanup request failed");
+            false
+        }
+        Err(e) => {
+            warn!(method, url = %redacted_url(&request.url), "cleanup request failed: {:#}", e);
+            false
+        }
+    }
+}
+
+/// Item path and the name of its parameter for each collection path with a `POST` operation,
+/// the item path has the operation, e.g. `/users/{userId}` for `/users`
+pub(crate) fn items(
+    schema: &OpenAPI,
+    operation: impl Fn(&PathItem) -> bool,
+) -> BTreeMap<String, (String, String)> {
+    let with_operation = schema
+        .paths
+        .iter()
+        .filter(|(_, item)| operation(item.to_item_ref()))
+        .map(|(path, _)| path)
+        .collect::<Vec<_>>();
+    let mut items = BTreeMap::new();
+    for (path, item) in schema.paths.iter() {
+        if item.to_item_ref().post.is_none() {
+            continue;
+        }
+        let prefix = format!("{}/{{", path.trim_end_matches('/'));
+        let item_path = with_operation.iter().find_map(|item_path| {
+            let name = item_path.strip_prefix(&prefix)?.strip_suffix('}')?;
+            (!name.contains(['/', '{', '}'])).then(|| (item_path.to_string(), name.to_string()))
+        });
+        if let Some(item_path) = item_path {
+            items.insert(path.clone(), item_path);
+        }
+    }
+    items
+}
+
+/// Url of the resource created by the successful `POST` to the collection path, the
+/// identifier is taken from the body or the `Location` header
+pub(crate) fn created(
+    base: &Url,
+    path: &str,
+    item_path: &str,
+    name: &str,
+    response: &Response,
+) -> Option<Url> {
+    let id = serde_json::from_str::<Value>(&response.body)
+        .ok()
+        .and_then(|body| identifier(&body, name))
+        .or_else(|| {
+            let location = response.header("location")?;
+            let segment = location.trim_end_matches('/').rsplit('/').next()?;
+            (!segment.is_empty()).then(|| segment.to_string())
+        });
+    let id = match id {
+        Some(id) => id,
+        None => {
+            debug!(path, "created resource without an identifier");
+            return None;
+        }
+    };
+    let encoded = utf8_percent_encode(&id, PATH_SEGMENT).to_string();
+    let item = item_path.replace(&format!("{{{}}}", name), &encoded);
+    match base.join(item.trim_start_matches('/')) {
+        Ok(url) => Some(url),
+        Err(e) => {
+            debug!(path, "invalid url of created resource: {}", e);
+            None
+        }
+    }
+}
+
+/// Identifier of the created resource in the response body, the field named like the path
+/// parameter or `id`, also in a `data` envelope
+pub(crate) fn identifier(body: &Value, name: &str) -> Option<String> {
+    let object = body.as_object()?;
+    for key in [name, "id"].iter() {
+        match object.get(*key) {
+            Some(Value::String(id)) => return Some(id.clone()),
+            Some(Value::Number(id)) => return Some(id.to_string()),
+            _ => {}
+        }
+    }
+    object.get("data").and_then(|data| identifier(data, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn encodes_identifiers_as_path_segments() {
+        let response = Response {
+            status: 201,
+            status_text: String::new(),
+            http_version: String::new(),
+            headers: Vec::new(),
+            body: r#"{"id": "a b/c+d"}"#.to_string(),
+            elapsed: Duration::ZERO,
+            events: Vec::new(),
+        };
+        let base = Url::parse("http://localhost/api/").unwrap();
+        assert_eq!(
+            created(&base, "/items", "/items/{id}", "id", &response).map(|url| url.to_string()),
+            Some("http://localhost/api/items/a%20b%2Fc+d".to_string())
+        );
+    }
+}
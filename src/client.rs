@@ -0,0 +1,454 @@
+//! HTTP client on hyper for high request rates, e.g. soak tests against local targets: the
+//! connections are pooled and kept alive, driven by the workers of a runtime shared by the
+//! threads of the run, and bodies are written into a buffer of the thread reused once hyper
+//! sent them instead of allocating per request. Redirects are followed like the default client
+//! follows them. The connections are opened
+//! by the proxy module like the ones made by hand, through the proxy if there is one and from
+//! the source address and interface.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    task::{Context as TaskContext, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use bytes::{BufMut, BytesMut};
+use hyper::{
+    body::{Bytes, HttpBody},
+    header::{HeaderName, HeaderValue, CONTENT_TYPE},
+    service::Service,
+    Body, Client, Method, Uri,
+};
+use hyper_rustls::HttpsConnector;
+use tokio::{
+    net::TcpStream,
+    runtime::{self, Runtime},
+    task,
+};
+use tracing::debug;
+
+use crate::proxy;
+use crate::stream::{self, StreamBudget};
+use crate::tls;
+use crate::transport::{redacted, redacted_url, Request, Response, Transport, MAX_BODY_SIZE};
+
+/// Idle connections kept open to each host
+const MAX_IDLE_CONNECTIONS: usize = 32;
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Requests sent for a request including its redirects, like the default client
+const MAX_REDIRECTS: usize = 5;
+
+/// Largest capacity of the body buffer kept for the next requests of the thread
+const MAX_BUFFER_CAPACITY: usize = 1024 * 1024;
+
+thread_local! {
+    /// Buffer the bodies of the thread are written into, the bodies sent are split off it and
+    /// their memory is reused once hyper dropped them
+    static BUFFER: RefCell<BytesMut> = RefCell::new(BytesMut::new());
+}
+
+/// HTTP client the requests are sent with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpClient {
+    /// Blocking client following redirects, the default
+    Ureq,
+    /// Pooled client on hyper
+    Hyper,
+}
+
+impl FromStr for HttpClient {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ureq" => Ok(HttpClient::Ureq),
+            "hyper" => Ok(HttpClient::Hyper),
+            _ => Err(format!(
+                "unknown http client `{}`, expected ureq or hyper",
+                s
+            )),
+        }
+    }
+}
+
+pub struct HyperTransport {
+    runtime: Runtime,
+    client: Client<HttpsConnector<Dialer>, Body>,
+}
+
+impl fmt::Debug for HyperTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<HyperTransport>")
+    }
+}
+
+/// Connector of the hyper client, connecting on the blocking threads of the runtime
+#[derive(Debug, Clone, Copy)]
+pub struct Dialer;
+
+impl Service<Uri> for Dialer {
+    type Response = TcpStream;
+    type Error = anyhow::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<TcpStream>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut TaskContext<'_>) -> Poll<Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        Box::pin(async move {
+            let host = uri
+                .host()
+                .ok_or_else(|| anyhow!("url without host"))?
+                .to_string();
+            let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+                Some("https") => 443,
+                _ => 80,
+            });
+            let stream = task::spawn_blocking(move || proxy::connect(&host, port)).await??;
+            // Small requests are not delayed until the previous segment is acknowledged
+            stream.set_nodelay(true)?;
+            stream.set_nonblocking(true)?;
+            Ok(TcpStream::from_std(stream)?)
+        })
+    }
+}
+
+/// TLS configuration of the settings, trusting the certificates of the operating system like
+/// the default client
+fn tls_config() -> rustls::ClientConfig {
+    let mut config = tls::client_config();
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    config
+}
+
+/// Request following the redirect of the response like the default client, `None` if the
+/// response is not a redirect it follows. Redirected requests have no body and requests with
+/// one are only redirected by `301`, `302` and `303`, as a `GET`.
+fn redirected(request: &Request, response: &Response) -> Result<Option<Request>> {
+    if !(300..399).contains(&response.status) {
+        return Ok(None);
+    }
+    let location = match response
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("location"))
+    {
+        Some((_, location)) => location,
+        None => return Ok(None),
+    };
+    let bodyless = ["GET", "HEAD", "OPTIONS", "TRACE"].contains(&request.method.as_str());
+    let method = match response.status {
+        301..=303 if request.method == "GET" || request.method == "HEAD" => &request.method,
+        301..=303 => "GET",
+        307 | 308 if bodyless => &request.method,
+        _ => return Ok(None),
+    };
+    let url = request
+        .url
+        .join(location)
+        .with_context(|| format!("invalid redirect to {}", location))?;
+    Ok(Some(Request {
+        method: method.to_string(),
+        url,
+        body: None,
+        raw_body: None,
+        ..request.clone()
+    }))
+}
+
+impl HyperTransport {
+    pub fn new() -> Result<HyperTransport> {
+        // The threads of the run block on the runtime, the connections are driven by its
+        // workers meanwhile
+        let runtime = runtime::Builder::new_multi_thread()
+            .thread_name("http-client")
+            .enable_all()
+            .build()
+            .context("unable to start the runtime of the http client")?;
+        let client = Client::builder()
+            .pool_max_idle_per_host(MAX_IDLE_CONNECTIONS)
+            .pool_idle_timeout(IDLE_TIMEOUT)
+            .build(HttpsConnector::from((Dialer, tls_config())));
+        Ok(HyperTransport { runtime, client })
+    }
+
+    fn body(&self, request: &Request) -> Result<Bytes> {
+        if request.raw_body.is_none() && request.body.is_none() {
+            return Ok(Bytes::new());
+        }
+        BUFFER.with(|buffer| {
+            let mut buffer = buffer.borrow_mut();
+            if buffer.capacity() > MAX_BUFFER_CAPACITY {
+                *buffer = BytesMut::new();
+            }
+            match (&request.raw_body, &request.body) {
+                (Some(raw_body), _) => buffer.extend_from_slice(raw_body),
+                (None, Some(body)) => {
+                    if let Err(e) = serde_json::to_writer((&mut *buffer).writer(), body) {
+                        buffer.clear();
+                        return Err(e).context("unable to serialize json");
+                    }
+                }
+                (None, None) => {}
+            }
+            Ok(buffer.split().freeze())
+        })
+    }
+
+    fn to_hyper(&self, request: &Request) -> Result<hyper::Request<Body>> {
+        let mut hyper_request = hyper::Request::builder()
+            .method(Method::from_bytes(request.method.as_bytes())?)
+            .uri(request.url.as_str())
+            .body(Body::from(self.body(request)?))?;
+        let headers = hyper_request.headers_mut();
+        for (name, value) in request.headers.iter() {
+            headers.append(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        if request.raw_body.is_none()
+            && request.body.is_some()
+            && !headers.contains_key(CONTENT_TYPE)
+        {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        }
+        Ok(hyper_request)
+    }
+
+    /// Response to the request, after its redirects, with the time until its headers since the
+    /// first request
+    async fn follow(&self, request: &Request) -> Result<Response> {
+        let start = Instant::now();
+        let mut redirect = None;
+        for _ in 0..MAX_REDIRECTS {
+            let sent = Instant::now();
+            let current = redirect.as_ref().unwrap_or(request);
+            let mut response = self
+                .exchange(self.to_hyper(current)?, current.stream)
+                .await?;
+            redirect = match redirected(current, &response)? {
+                Some(redirect) => Some(redirect),
+                None => {
+                    response.elapsed += sent.duration_since(start);
+                    return Ok(response);
+                }
+            };
+        }
+        Err(anyhow!("more than {} redirects", MAX_REDIRECTS - 1))
+    }
+
+    async fn exchange(
+        &self,
+        request: hyper::Request<Body>,
+        budget: Option<StreamBudget>,
+    ) -> Result<Response> {
+        let start = Instant::now();
+        let mut response = self.client.request(request).await?;
+        let elapsed = start.elapsed();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        // Streams are read until the budget is spent, the default one for operations not
+        // declaring them
+        let (deadline, max_bytes) = match stream::is_stream(content_type.as_deref()) {
+            true => {
+                let budget = budget.unwrap_or_default();
+                (
+                    Some(tokio::time::Instant::from_std(start + budget.duration)),
+                    budget.max_bytes,
+                )
+            }
+            false => (None, MAX_BODY_SIZE),
+        };
+
+        let mut body = Vec::new();
+        loop {
+            let chunk = match deadline {
+                Some(deadline) => {
+                    match tokio::time::timeout_at(deadline, response.body_mut().data()).await {
+                        Ok(chunk) => chunk,
+                        Err(_) => break,
+                    }
+                }
+                None => response.body_mut().data().await,
+            };
+            let chunk = match chunk {
+                Some(chunk) => chunk.context("unable to read response body")?,
+                None => break,
+            };
+            let rest = (max_bytes as usize).saturating_sub(body.len());
+            body.extend_from_slice(&chunk[..chunk.len().min(rest)]);
+            if chunk.len() >= rest {
+                break;
+            }
+        }
+        let status = response.status();
+        let body = String::from_utf8_lossy(&body).into_owned();
+        Ok(Response {
+            status: status.as_u16(),
+            status_text: status.canonical_reason().unwrap_or_default().to_string(),
+            http_version: format!("{:?}", response.version()),
+            headers: response
+                .headers()
+                .iter()
+                .map(|(name, value)| {
+                    let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                    (name.to_string(), value)
+                })
+                .collect(),
+            events: stream::events(content_type.as_deref(), &body),
+            body,
+            elapsed,
+        })
+    }
+}
+
+impl Transport for HyperTransport {
+    fn send(&self, request: &Request) -> Result<Response> {
+        debug!(method = %request.method, url = %redacted_url(&request.url), headers = ?redacted(&request.headers), "sending request");
+        let response = self.runtime.block_on(async {
+            match request.deadline() {
+                Some(timeout) => tokio::time::timeout(timeout, self.follow(request))
+                    .await
+                    .map_err(|_| anyhow!("timed out after {:?}", timeout))?,
+                None => self.follow(request).await,
+            }
+        })?;
+        debug!(
+            status = response.status,
+            elapsed_ms = response.elapsed.as_millis() as u64,
+            headers = ?redacted(&response.headers),
+            "received response"
+        );
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        thread,
+    };
+    use url::Url;
+
+    fn request(method: &str, body: Option<serde_json::Value>) -> Request {
+        Request {
+            method: method.to_string(),
+            url: Url::parse("http://localhost/orders/1").unwrap(),
+            headers: vec![("Accept".to_string(), "*/*".to_string())],
+            body,
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        }
+    }
+
+    fn redirect(status: u16, location: &str) -> Response {
+        Response {
+            status,
+            status_text: String::new(),
+            http_version: "HTTP/1.1".to_string(),
+            headers: vec![("Location".to_string(), location.to_string())],
+            body: String::new(),
+            elapsed: Duration::default(),
+            events: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn follows_redirects_like_the_default_client() {
+        let post = request("POST", Some(serde_json::json!({"id": 1})));
+        let redirected = redirected(&post, &redirect(303, "/orders/2"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(redirected.method, "GET");
+        assert_eq!(redirected.url.as_str(), "http://localhost/orders/2");
+        assert_eq!(redirected.body, None);
+        assert_eq!(redirected.headers, post.headers);
+        assert!(super::redirected(&post, &redirect(307, "/orders/2"))
+            .unwrap()
+            .is_none());
+
+        let head = request("HEAD", None);
+        let redirected = super::redirected(&head, &redirect(308, "http://other/orders"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(redirected.method, "HEAD");
+        assert_eq!(redirected.url.as_str(), "http://other/orders");
+
+        let mut ok = redirect(200, "/orders/2");
+        assert!(super::redirected(&head, &ok).unwrap().is_none());
+        ok.status = 302;
+        ok.headers.clear();
+        assert!(super::redirected(&head, &ok).unwrap().is_none());
+    }
+
+    /// Sends requests to a local server answering without delay from 16 threads, for soak
+    /// tests at 10k requests per second: `cargo test --release -- --ignored sustains`
+    #[test]
+    #[ignore]
+    fn sustains_ten_thousand_requests_per_second() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/health", listener.local_addr().unwrap())).unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut writer = stream;
+                    let mut line = String::new();
+                    while reader.read_line(&mut line).unwrap_or_default() > 0 {
+                        if line == "\r\n" {
+                            let ok = b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\n{}";
+                            if writer.write_all(ok).is_err() {
+                                return;
+                            }
+                        }
+                        line.clear();
+                    }
+                });
+            }
+        });
+
+        let transport = Arc::new(HyperTransport::new().unwrap());
+        let sent = Arc::new(AtomicU64::new(0));
+        let duration = Duration::from_secs(3);
+        let start = Instant::now();
+        let threads = (0..16)
+            .map(|_| {
+                let (transport, sent, url) = (transport.clone(), sent.clone(), url.clone());
+                thread::spawn(move || {
+                    let request = Request {
+                        url,
+                        ..request("GET", None)
+                    };
+                    while start.elapsed() < duration {
+                        assert_eq!(transport.send(&request).unwrap().status, 200);
+                        sent.fetch_add(1, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        threads
+            .into_iter()
+            .for_each(|thread| thread.join().unwrap());
+        let rate = sent.load(Ordering::Relaxed) as f64 / start.elapsed().as_secs_f64();
+        assert!(rate >= 10_000.0, "{:.0} requests per second", rate);
+    }
+}
@@ -0,0 +1,96 @@
+//! Requests with the clock of the client off by a minute, a day or back at the epoch, testing
+//! the window in which the API accepts timestamped and signed requests
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+
+use crate::signing::Signing;
+use crate::transport::{Request, Response};
+
+const MINUTE: Duration = Duration::from_secs(60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Skew of the clock of a probe
+#[derive(Debug, Clone, Copy)]
+pub enum Skew {
+    Behind(Duration),
+    Ahead(Duration),
+    Epoch,
+}
+
+impl Skew {
+    /// Within the usual drift of clocks, the API should accept it
+    fn tolerable(&self) -> bool {
+        match self {
+            Skew::Behind(skew) | Skew::Ahead(skew) => *skew <= MINUTE,
+            Skew::Epoch => false,
+        }
+    }
+
+    fn time(&self, now: SystemTime) -> SystemTime {
+        match self {
+            Skew::Behind(skew) => now - *skew,
+            Skew::Ahead(skew) => now + *skew,
+            Skew::Epoch => UNIX_EPOCH,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let amount = |skew: &Duration| match *skew >= DAY {
+            true => "1 day",
+            false => "1 minute",
+        };
+        match self {
+            Skew::Behind(skew) => format!("{} in the past", amount(skew)),
+            Skew::Ahead(skew) => format!("{} in the future", amount(skew)),
+            Skew::Epoch => "at the epoch (0)".to_string(),
+        }
+    }
+}
+
+const SKEWS: &[Skew] = &[
+    Skew::Behind(MINUTE),
+    Skew::Ahead(MINUTE),
+    Skew::Behind(DAY),
+    Skew::Ahead(DAY),
+    Skew::Epoch,
+];
+
+/// The request stamped and signed with skewed clocks
+pub fn probes(signing: &Signing, request: &Request) -> Result<Vec<(Skew, Request)>> {
+    let now = SystemTime::now();
+    SKEWS
+        .iter()
+        .map(|skew| {
+            let mut probe = request.clone();
+            signing.sign(&mut probe, skew.time(now))?;
+            Ok((*skew, probe))
+        })
+        .collect()
+}
+
+/// Rejected, but not because of rate limiting
+fn rejected(response: &Response) -> bool {
+    response.status / 100 == 4 && response.status != 429
+}
+
+/// Whether the API answering the request with the current time with `accepted` status gets
+/// the skew wrong: it rejects a small skew or accepts a large one
+pub fn misvalidation(skew: Skew, accepted: u16, response: &Response) -> Option<String> {
+    match skew.tolerable() {
+        true if rejected(response) => Some(format!(
+            "timestamp {} rejected with status {} although the current one gets {}, the window \
+             is stricter than the usual drift of clocks",
+            skew.describe(),
+            response.status,
+            accepted
+        )),
+        false if response.status / 100 == 2 => Some(format!(
+            "timestamp {} accepted with status {}, replayed requests are not rejected",
+            skew.describe(),
+            response.status
+        )),
+        _ => None,
+    }
+}
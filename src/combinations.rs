@@ -0,0 +1,293 @@
+//! Combinatorial sweeps of the enum and boolean parameters of operations: the payloads of an
+//! operation walk through rows covering every combination of the values of any `strength`
+//! parameters (pairwise with 2), so flags that only break together are set together, which
+//! random values rarely do.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use openapi_utils::ReferenceOrExt;
+use openapiv3::{OpenAPI, Parameter, ParameterSchemaOrContent, SchemaKind, Type};
+use rand::rngs::StdRng;
+use tracing::{debug, warn};
+
+use crate::config::{Header, OperationOverride};
+use crate::payload::{operations, Generator, Payload};
+use crate::plan::Location;
+
+/// Parameters with more values are left to random values
+const MAX_VALUES: usize = 10;
+
+/// Parameters of an operation swept at most, the first ones
+const MAX_PARAMETERS: usize = 10;
+
+/// Operations with more combinations to cover are not swept
+const MAX_COMBINATIONS: usize = 200_000;
+
+/// Swept parameter, with its values and `None` for leaving an optional parameter out
+#[derive(Debug)]
+struct Swept {
+    location: Location,
+    name: String,
+    values: Vec<Option<String>>,
+}
+
+/// Rows of an operation and the row of the next payload
+#[derive(Debug)]
+struct Sweep {
+    parameters: Vec<Swept>,
+    /// Indices of the values of the parameters
+    rows: Vec<Vec<usize>>,
+    next: AtomicUsize,
+}
+
+/// Sets the enum and boolean parameters of the payloads to the rows of a covering array
+#[derive(Debug)]
+pub struct Combinations {
+    sweeps: BTreeMap<(String, String), Sweep>,
+}
+
+/// Values of the parameter to sweep, `None` when it has none or too many
+fn values(parameter: &Parameter) -> Option<(Location, String, Vec<Option<String>>)> {
+    let (location, data) = match parameter {
+        Parameter::Query { parameter_data, .. } => (Location::Query, parameter_data),
+        Parameter::Path { parameter_data, .. } => (Location::Path, parameter_data),
+        Parameter::Header { parameter_data, .. } => (Location::Header, parameter_data),
+        Parameter::Cookie { .. } => return None,
+    };
+    let schema = match &data.format {
+        ParameterSchemaOrContent::Schema(schema) => schema.to_item_ref(),
+        ParameterSchemaOrContent::Content(_) => return None,
+    };
+    let mut values: Vec<Option<String>> = match &schema.schema_kind {
+        SchemaKind::Type(Type::Boolean {}) => vec![Some("true".into()), Some("false".into())],
+        SchemaKind::Type(Type::String(string)) => {
+            string.enumeration.iter().cloned().map(Some).collect()
+        }
+        SchemaKind::Type(Type::Integer(integer)) => integer
+            .enumeration
+            .iter()
+            .map(|value| Some(value.to_string()))
+            .collect(),
+        SchemaKind::Type(Type::Number(number)) => number
+            .enumeration
+            .iter()
+            .map(|value| Some(value.to_string()))
+            .collect(),
+        _ => return None,
+    };
+    if values.is_empty() || values.len() > MAX_VALUES {
+        return None;
+    }
+    if !data.required && location != Location::Path {
+        values.push(None);
+    }
+    Some((location, data.name.clone(), values))
+}
+
+/// Combinations of `k` of the indices below `n`, in order
+fn subsets(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    (k - 1..n)
+        .flat_map(|last| {
+            subsets(last, k - 1).into_iter().map(move |mut subset| {
+                subset.push(last);
+                subset
+            })
+        })
+        .collect()
+}
+
+/// Number of combinations of values to cover
+fn combinations(sizes: &[usize], strength: usize) -> usize {
+    subsets(sizes.len(), strength)
+        .iter()
+        .map(|subset| subset.iter().map(|&p| sizes[p]).product::<usize>())
+        .sum()
+}
+
+/// Number of uncovered combinations the value of the parameter covers with the values
+/// already in the row
+fn covered(
+    uncovered: &BTreeSet<Vec<(usize, usize)>>,
+    row: &[Option<usize>],
+    parameter: usize,
+    value: usize,
+    strength: usize,
+) -> usize {
+    let assigned = (0..row.len())
+        .filter(|&p| row[p].is_some())
+        .collect::<Vec<_>>();
+    subsets(assigned.len(), strength - 1)
+        .iter()
+        .filter(|subset| {
+            let mut combination = subset
+                .iter()
+                .map(|&i| (assigned[i], row[assigned[i]].unwrap_or_default()))
+                .collect::<Vec<_>>();
+            combination.push((parameter, value));
+            combination.sort_unstable();
+            uncovered.contains(&combination)
+        })
+        .count()
+}
+
+/// Rows covering every combination of the values of any `strength` parameters with the
+/// numbers of values. Each row starts from an uncovered combination and the other parameters
+/// get the values covering the most uncovered combinations.
+fn covering_rows(sizes: &[usize], strength: usize) -> Vec<Vec<usize>> {
+    let strength = strength.min(sizes.len());
+    let mut uncovered = BTreeSet::new();
+    for subset in subsets(sizes.len(), strength) {
+        let mut values = vec![0; strength];
+        'values: loop {
+            uncovered.insert(subset.iter().copied().zip(values.iter().copied()).collect());
+            for i in 0..strength {
+                values[i] += 1;
+                if values[i] < sizes[subset[i]] {
+                    continue 'values;
+                }
+                values[i] = 0;
+            }
+            break;
+        }
+    }
+
+    let mut rows = Vec::new();
+    while let Some(first) = uncovered.iter().next().cloned() {
+        let mut row = vec![None; sizes.len()];
+        for (parameter, value) in first {
+            row[parameter] = Some(value);
+        }
+        for parameter in 0..sizes.len() {
+            if row[parameter].is_some() {
+                continue;
+            }
+            // The first of the values covering the most
+            let best = (0..sizes[parameter])
+                .rev()
+                .max_by_key(|&value| covered(&uncovered, &row, parameter, value, strength))
+                .unwrap_or_default();
+            row[parameter] = Some(best);
+        }
+        let row = row
+            .into_iter()
+            .map(Option::unwrap_or_default)
+            .collect::<Vec<_>>();
+        for subset in subsets(sizes.len(), strength) {
+            uncovered.remove(&subset.iter().map(|&p| (p, row[p])).collect::<Vec<_>>());
+        }
+        rows.push(row);
+    }
+    rows
+}
+
+impl Combinations {
+    /// Sweeps of the operations with at least two enum or boolean parameters covering the
+    /// combinations of `strength` of them. Parameters given fixed values by the headers or
+    /// the overrides of the operation keep them.
+    pub fn new(
+        schema: &OpenAPI,
+        strength: usize,
+        headers: &[Header],
+        overrides: &[OperationOverride],
+    ) -> Combinations {
+        let mut sweeps = BTreeMap::new();
+        for (path, item) in schema.paths.iter() {
+            for (method, operation) in operations(item.to_item_ref()) {
+                let operation = match operation {
+                    Some(operation) => operation,
+                    None => continue,
+                };
+                let operation_id = operation.operation_id.as_deref();
+                let fixed = |location: Location, name: &str| {
+                    (location == Location::Header
+                        && headers.iter().any(|h| h.0.eq_ignore_ascii_case(name)))
+                        || overrides
+                            .iter()
+                            .filter(|o| o.matches(method, path, operation_id))
+                            .any(|o| {
+                                o.params.contains_key(name)
+                                    || (location == Location::Header
+                                        && o.header.iter().any(|h| h.0.eq_ignore_ascii_case(name)))
+                            })
+                };
+                let parameters = operation
+                    .parameters
+                    .iter()
+                    .filter_map(|parameter| values(parameter.to_item_ref()))
+                    .filter(|(location, name, _)| !fixed(*location, name))
+                    .take(MAX_PARAMETERS)
+                    .map(|(location, name, values)| Swept {
+                        location,
+                        name,
+                        values,
+                    })
+                    .collect::<Vec<_>>();
+                if parameters.len() < 2 {
+                    continue;
+                }
+                let sizes = parameters
+                    .iter()
+                    .map(|parameter| parameter.values.len())
+                    .collect::<Vec<_>>();
+                if combinations(&sizes, strength.min(sizes.len())) > MAX_COMBINATIONS {
+                    warn!(method, path, strength, "too many combinations to sweep");
+                    continue;
+                }
+                let rows = covering_rows(&sizes, strength);
+                debug!(method, path, rows = rows.len(), "combinatorial sweep");
+                sweeps.insert(
+                    (method.to_string(), path.clone()),
+                    Sweep {
+                        parameters,
+                        rows,
+                        next: AtomicUsize::new(0),
+                    },
+                );
+            }
+        }
+        Combinations { sweeps }
+    }
+}
+
+impl Generator for Combinations {
+    fn generate(&self, payload: &mut Payload<'_>, _rng: &mut StdRng) {
+        // A payload that is already invalid keeps its single violation
+        if payload.violation.is_some() {
+            return;
+        }
+        let key = (payload.method.to_string(), payload.path.to_string());
+        let sweep = match self.sweeps.get(&key) {
+            Some(sweep) => sweep,
+            None => return,
+        };
+        let next = sweep.next.fetch_add(1, Ordering::Relaxed);
+        let row = &sweep.rows[next % sweep.rows.len()];
+        for (parameter, &value) in sweep.parameters.iter().zip(row.iter()) {
+            let value = &parameter.values[value];
+            let params = match parameter.location {
+                Location::Query => &mut payload.query_params,
+                Location::Path => &mut payload.path_params,
+                Location::Header => &mut payload.headers,
+                Location::Cookie => continue,
+            };
+            let matches = |name: &str| match parameter.location {
+                Location::Header => name.eq_ignore_ascii_case(&parameter.name),
+                _ => name == parameter.name,
+            };
+            match value {
+                Some(value) => {
+                    for (_, param) in params.iter_mut().filter(|(name, _)| matches(name)) {
+                        *param = value.clone();
+                    }
+                }
+                None => params.retain(|(name, _)| !matches(name)),
+            }
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use std::str::FromStr;
+
+/// Name of the executable completions are generated for
+const NAME: &str = "openapi-fuzzer";
+
+/// Long names of the options whose values are paths, completed with files
+const FILE_OPTIONS: &[&str] = &[
+    "config",
+    "spec",
+    "overlay",
+    "compose",
+    "header-policy",
+    "restore",
+    "headers-file",
+    "issue-template",
+    "export-har",
+    "export-burp",
+    "export-postman",
+    "export-csv",
+    "markdown-summary",
+    "coverage",
+    "cassette",
+    "database",
+    "age-identity",
+    "log-file",
+    "postman",
+    "postman-env",
+    "traffic",
+    "corpus",
+    "scenario",
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("unknown shell `{}`, expected bash, zsh or fish", s)),
+        }
+    }
+}
+
+/// Option of the command line, as described by the help
+#[derive(Debug, Default)]
+struct Flag {
+    short: Option<char>,
+    long: String,
+    /// Name of the value, options without it are switches
+    value: Option<String>,
+    description: String,
+}
+
+impl Flag {
+    fn takes_file(&self) -> bool {
+        self.value.is_some() && FILE_OPTIONS.contains(&self.long.as_str())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Command {
+    name: String,
+    description: String,
+}
+
+/// Options and subcommands parsed from the help generated by argh, so they never get out
+/// of sync with the definition of the arguments
+#[derive(Debug, Default)]
+struct Help {
+    description: String,
+    flags: Vec<Flag>,
+    commands: Vec<Command>,
+}
+
+impl Help {
+    fn parse(help: &str) -> Help {
+        let mut result = Help::default();
+        let mut lines = help.lines();
+        let usage = lines.next().unwrap_or_default();
+        let mut section = "";
+        for line in lines {
+            if line.is_empty() {
+                continue;
+            }
+            if !line.starts_with(' ') {
+                match line.strip_suffix(':') {
+                    Some(name) => section = name,
+                    None if result.description.is_empty() => result.description = line.into(),
+                    None => {}
+                }
+                continue;
+            }
+
+            let item = line.trim_start();
+            let is_continuation = line.len() - item.len() > 2;
+            // Long names are followed by a single space, the names are the first words: the
+            // short and long names of an option, separated by a comma, and its `<value>`
+            let mut words = item.split_whitespace().peekable();
+            let mut names = Vec::new();
+            while let Some(word) = words.next() {
+                names.push(word.trim_end_matches(','));
+                if !word.ends_with(',') && !words.peek().is_some_and(|w| w.starts_with('<')) {
+                    break;
+                }
+            }
+            let description = words.collect::<Vec<_>>().join(" ");
+            let description = description.as_str();
+            let description_of_last = match section {
+                "Options" if is_continuation => result.flags.last_mut().map(|f| &mut f.description),
+                "Commands" if is_continuation => {
+                    result.commands.last_mut().map(|c| &mut c.description)
+                }
+                "Options" => {
+                    let mut flag = Flag::default();
+                    for name in names.iter().filter(|name| !name.starts_with('<')) {
+                        match name.strip_prefix("--") {
+                            Some(long) => flag.long = long.to_string(),
+                            None => flag.short = name.trim_start_matches('-').chars().next(),
+                        }
+                    }
+                    let takes_value = |name: String| usage.contains(&format!("[{} <", name));
+                    if takes_value(format!("--{}", flag.long))
+                        || flag
+                            .short
+                            .is_some_and(|short| takes_value(format!("-{}", short)))
+                    {
+                        flag.value = Some(flag.long.clone());
+                    }
+                    result.flags.push(flag);
+                    result.flags.last_mut().map(|f| &mut f.description)
+                }
+                "Commands" => {
+                    result.commands.push(Command {
+                        name: names.first().copied().unwrap_or_default().to_string(),
+                        ..Command::default()
+                    });
+                    result.commands.last_mut().map(|c| &mut c.description)
+                }
+                _ => None,
+            };
+            if let Some(text) = description_of_last {
+                let continued = match is_continuation {
+                    true => item,
+                    false => description,
+                };
+                if !continued.is_empty() {
+                    if !text.is_empty() {
+                        text.push(' ');
+                    }
+                    text.push_str(continued);
+                }
+            }
+        }
+        result
+    }
+}
+
+fn bash(help: &Help) -> String {
+    let words = help
+        .flags
+        .iter()
+        .flat_map(|flag| {
+            let mut words = vec![format!("--{}", flag.long)];
+            words.extend(flag.short.map(|short| format!("-{}", short)));
+            words
+        })
+        .chain(help.commands.iter().map(|command| command.name.clone()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let options = |files: bool| {
+        help.flags
+            .iter()
+            .filter(|flag| flag.value.is_some() && flag.takes_file() == files)
+            .flat_map(|flag| {
+                let mut names = vec![format!("--{}", flag.long)];
+                names.extend(flag.short.map(|short| format!("-{}", short)));
+                names
+            })
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+    let function = format!("_{}", NAME.replace('-', "_"));
+
+    format!(
+        r#"{function}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    case "$prev" in
+        {files})
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return
+            ;;
+        {values})
+            COMPREPLY=()
+            return
+            ;;
+    esac
+    COMPREPLY=($(compgen -W "{words}" -- "$cur"))
+}}
+complete -o default -F {function} {name}
+"#,
+        function = function,
+        files = options(true),
+        values = options(false),
+        words = words,
+        name = NAME,
+    )
+}
+
+/// Escapes the description for a single quoted zsh `_arguments` spec
+fn zsh_description(description: &str) -> String {
+    description
+        .replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh(help: &Help) -> String {
+    let mut script = format!("#compdef {}\n\n_arguments \\\n", NAME);
+    for flag in help.flags.iter() {
+        let description = zsh_description(&flag.description);
+        let names = match flag.short {
+            Some(short) => format!(
+                "'(-{short} --{long})'{{-{short},--{long}}}'",
+                short = short,
+                long = flag.long
+            ),
+            None => format!("'--{}", flag.long),
+        };
+        let value = match (&flag.value, flag.takes_file()) {
+            (Some(value), true) => format!(":{}:_files", value),
+            (Some(value), false) => format!(":{}: ", value),
+            (None, _) => String::new(),
+        };
+        script += &format!("  {}[{}]{}' \\\n", names, description, value);
+    }
+    let commands = help
+        .commands
+        .iter()
+        .map(|command| {
+            format!(
+                "{}\\:\"{}\"",
+                command.name,
+                zsh_description(&command.description).replace('"', "\\\"")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    script += &format!("  '1: :(({}))' \\\n  '*::arg:_default'\n", commands);
+    script
+}
+
+fn fish(help: &Help) -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"));
+    let mut script = String::new();
+    for flag in help.flags.iter() {
+        let mut line = format!("complete -c {}", NAME);
+        if let Some(short) = flag.short {
+            line += &format!(" -s {}", short);
+        }
+        line += &format!(" -l {}", flag.long);
+        match (&flag.value, flag.takes_file()) {
+            (Some(_), true) => line += " -r -F",
+            (Some(_), false) => line += " -x",
+            (None, _) => {}
+        }
+        line += &format!(" -d {}\n", quote(&flag.description));
+        script += &line;
+    }
+    for command in help.commands.iter() {
+        script += &format!(
+            "complete -c {} -n '__fish_use_subcommand' -f -a {} -d {}\n",
+            NAME,
+            command.name,
+            quote(&command.description)
+        );
+    }
+    script
+}
+
+/// Completion script of the shell for the options and subcommands described by the help
+pub fn generate(shell: Shell, help: &str) -> String {
+    let help = Help::parse(help);
+    match shell {
+        Shell::Bash => bash(&help),
+        Shell::Zsh => zsh(&help),
+        Shell::Fish => fish(&help),
+    }
+}
+
+/// Escapes text for roff
+fn roff(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('-', "\\-");
+    match escaped.starts_with('.') || escaped.starts_with('\'') {
+        true => format!("\\&{}", escaped),
+        false => escaped,
+    }
+}
+
+/// Man page in roff format for the options and subcommands described by the help
+pub fn man_page(help: &str) -> String {
+    let help = Help::parse(help);
+    let mut page = format!(
+        ".TH {} 1 \"\" \"{} {}\"\n.SH NAME\n{} \\- {}\n.SH SYNOPSIS\n\\fB{}\\fR [\\fIOPTIONS\\fR] [\\fICOMMAND\\fR]\n.SH OPTIONS\n",
+        NAME.to_uppercase(),
+        NAME,
+        env!("CARGO_PKG_VERSION"),
+        roff(NAME),
+        roff(&help.description),
+        roff(NAME),
+    );
+    for flag in help.flags.iter() {
+        let mut names = Vec::new();
+        if let Some(short) = flag.short {
+            names.push(format!("\\fB\\-{}\\fR", short));
+        }
+        names.push(format!("\\fB{}\\fR", roff(&format!("--{}", flag.long))));
+        let value = flag
+            .value
+            .as_ref()
+            .map(|value| format!(" \\fI<{}>\\fR", roff(value)))
+            .unwrap_or_default();
+        page += &format!(
+            ".TP\n{}{}\n{}\n",
+            names.join(", "),
+            value,
+            roff(&flag.description)
+        );
+    }
+    if !help.commands.is_empty() {
+        page += ".SH COMMANDS\n";
+        for command in help.commands.iter() {
+            page += &format!(
+                ".TP\n\\fB{}\\fR\n{}\n",
+                roff(&command.name),
+                roff(&command.description)
+            );
+        }
+    }
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flag<'a>(help: &'a Help, long: &str) -> &'a Flag {
+        help.flags
+            .iter()
+            .find(|flag| flag.long == long)
+            .unwrap_or_else(|| panic!("no flag --{}", long))
+    }
+
+    #[test]
+    fn parses_the_help() {
+        let help = Help::parse(&crate::help());
+        assert_eq!(help.description, "OpenAPI fuzzer");
+
+        let config = flag(&help, "config");
+        assert_eq!(config.short, Some('c'));
+        assert_eq!(config.value.as_deref(), Some("config"));
+        assert!(config
+            .description
+            .starts_with("TOML or YAML file with options"));
+
+        // Long names followed by a single space, or by their description on the next line
+        let tls = flag(&help, "tls-min-version");
+        assert_eq!(tls.value.as_deref(), Some("tls-min-version"));
+        assert!(tls.description.starts_with("lowest TLS version"));
+        let ignored = flag(&help, "ignore-status-code");
+        assert_eq!(ignored.short, Some('i'));
+        assert!(!ignored.description.is_empty());
+        let smuggling = flag(&help, "probe-smuggling");
+        assert_eq!(smuggling.value, None);
+        assert!(smuggling.description.starts_with("send raw requests"));
+        for flag in help.flags.iter() {
+            assert!(!flag.long.contains(' '), "{:?}", flag);
+        }
+
+        assert!(help
+            .commands
+            .iter()
+            .any(|command| command.name == "list"
+                && command.description.starts_with("List operations")));
+    }
+
+    #[test]
+    fn completes_paths_with_files() {
+        let help = Help::parse(&crate::help());
+        for long in FILE_OPTIONS {
+            assert!(flag(&help, long).takes_file(), "--{}", long);
+        }
+        for long in [
+            "graphql-endpoint",
+            "target-logs",
+            "cleanup",
+            "reporter",
+            "issue-title",
+        ] {
+            assert!(!flag(&help, long).takes_file(), "--{}", long);
+        }
+
+        let bash = generate(Shell::Bash, &crate::help());
+        assert!(bash.contains("--tls-min-version --tls-max-version"));
+        assert!(!bash.contains("lowest TLS version"));
+        let man = man_page(&crate::help());
+        assert!(
+            man.contains(".TP\n\\fB\\-\\-tls\\-min\\-version\\fR \\fI<tls\\-min\\-version>\\fR\n")
+        );
+    }
+}
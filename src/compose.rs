@@ -0,0 +1,240 @@
+//! Targets started with docker before fuzzing: a compose project or a single image. They are
+//! waited for until healthy, and when the run is done the logs of their containers are saved
+//! next to the findings, encrypted like them, and the containers are removed, also when the
+//! run is interrupted.
+
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::{self, Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use openapi_fuzzer::encryption::Encryption;
+use openapi_fuzzer::logs::LogSource;
+use signal_hook::{iterator::Signals, SIGINT, SIGTERM};
+use tracing::{info, warn};
+
+/// Logs of the containers, in the results directory
+pub const LOG_FILE: &str = "containers.log";
+
+/// Time between two checks of the state of the container
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// State of the container, its health when it has a health check
+const STATE_FORMAT: &str =
+    "{{if .State.Health}}{{.State.Health.Status}}{{else}}{{.State.Status}}{{end}}";
+
+#[derive(Debug, Clone)]
+enum Kind {
+    /// Project of the compose file, named after the process so that runs do not share it
+    Compose {
+        file: PathBuf,
+        project: String,
+    },
+    Container {
+        id: String,
+    },
+}
+
+/// Target started for the run, it is stopped when dropped
+#[derive(Debug)]
+pub struct Target {
+    kind: Kind,
+    logs: PathBuf,
+    encryption: Encryption,
+}
+
+fn docker(args: &[&str]) -> Result<Output> {
+    let output = Command::new("docker")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .context("unable to run docker")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "`docker {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output)
+}
+
+impl Target {
+    /// Starts the services of the compose file and waits until their health checks pass
+    pub fn compose(file: &Path, results_dir: &Path, encryption: &Encryption) -> Result<Target> {
+        let target = Target {
+            kind: Kind::Compose {
+                file: file.to_path_buf(),
+                project: format!("openapi-fuzzer-{}", process::id()),
+            },
+            logs: results_dir.join(encryption.path(LOG_FILE)),
+            encryption: encryption.clone(),
+        };
+        info!(file = %file.display(), "starting compose project");
+        // Services that did not start are removed by the drop of the target
+        target.compose_command(&["up", "--detach", "--wait"])?;
+        Ok(target)
+    }
+
+    /// Runs the image with the published ports, e.g. `8080:80`, and waits until the container
+    /// is healthy, or running when the image has no health check
+    pub fn image(
+        image: &str,
+        publish: &[String],
+        results_dir: &Path,
+        timeout: Duration,
+        encryption: &Encryption,
+    ) -> Result<Target> {
+        let mut args = vec!["run", "--detach"];
+        for port in publish.iter() {
+            args.extend(["--publish", port.as_str()]);
+        }
+        args.push(image);
+        info!(image, "starting container");
+        let output = docker(&args)?;
+        let target = Target {
+            kind: Kind::Container {
+                id: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            },
+            logs: results_dir.join(encryption.path(LOG_FILE)),
+            encryption: encryption.clone(),
+        };
+        target.wait(timeout)?;
+        Ok(target)
+    }
+
+    /// Saves the logs and removes the containers when the process is interrupted, e.g. with
+    /// Ctrl-C, and exits
+    pub fn remove_on_signal(&self) -> Result<()> {
+        let signals = Signals::new([SIGINT, SIGTERM]).context("unable to handle signals")?;
+        let target = Target {
+            kind: self.kind.clone(),
+            logs: self.logs.clone(),
+            encryption: self.encryption.clone(),
+        };
+        thread::spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                warn!(signal, "interrupted, removing the containers");
+                drop(target);
+                process::exit(128 + signal);
+            }
+        });
+        Ok(())
+    }
+
+    /// Logs of the containers, to follow them during the run
+    pub fn log_source(&self) -> LogSource {
+        match &self.kind {
+            Kind::Compose { file, project } => LogSource::Compose {
+                file: file.clone(),
+                project: project.clone(),
+            },
+            Kind::Container { id } => LogSource::Docker(id.clone()),
+        }
+    }
+
+    fn compose_command(&self, args: &[&str]) -> Result<Output> {
+        match &self.kind {
+            Kind::Compose { file, project } => {
+                let file = file.to_string_lossy();
+                let mut command = vec!["compose", "--file", &file, "--project-name", project];
+                command.extend(args);
+                docker(&command)
+            }
+            Kind::Container { .. } => Err(anyhow!("not a compose project")),
+        }
+    }
+
+    fn wait(&self, timeout: Duration) -> Result<()> {
+        let id = match &self.kind {
+            Kind::Container { id } => id,
+            Kind::Compose { .. } => return Ok(()),
+        };
+        let start = Instant::now();
+        loop {
+            let output = docker(&["inspect", "--format", STATE_FORMAT, id])?;
+            match String::from_utf8_lossy(&output.stdout).trim() {
+                "healthy" | "running" => return Ok(()),
+                "exited" | "dead" | "unhealthy" => {
+                    return Err(anyhow!(
+                        "the container is {}, see its logs in {}",
+                        String::from_utf8_lossy(&output.stdout).trim(),
+                        self.logs.display()
+                    ))
+                }
+                _ if start.elapsed() >= timeout => {
+                    return Err(anyhow!("the container is not healthy after {:?}", timeout))
+                }
+                _ => thread::sleep(POLL_INTERVAL),
+            }
+        }
+    }
+
+    /// Saves the logs of the containers with their timestamps, to match them with findings
+    fn save_logs(&self) -> Result<()> {
+        if let Some(dir) = self.logs.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut command = Command::new("docker");
+        match &self.kind {
+            Kind::Compose { file, project } => {
+                command.arg("compose").arg("--file").arg(file).args([
+                    "--project-name",
+                    project,
+                    "logs",
+                    "--no-color",
+                    "--timestamps",
+                ])
+            }
+            Kind::Container { id } => command.args(["logs", "--timestamps", id]),
+        };
+        command.stdin(Stdio::null());
+        let status = match self.encryption.enabled() {
+            true => {
+                // The lines have timestamps, the streams of the containers are stored one after
+                // the other
+                let mut output = command.output().context("unable to run docker")?;
+                output.stdout.append(&mut output.stderr);
+                self.encryption.write(&self.logs, &output.stdout)?;
+                output.status
+            }
+            false => {
+                let file = File::create(&self.logs)?;
+                command
+                    .stdout(file.try_clone()?)
+                    .stderr(file)
+                    .status()
+                    .context("unable to run docker")?
+            }
+        };
+        match status.success() {
+            true => Ok(()),
+            false => Err(anyhow!("docker logs exited with {}", status)),
+        }
+    }
+
+    fn remove(&self) -> Result<()> {
+        match &self.kind {
+            Kind::Compose { .. } => self
+                .compose_command(&["down", "--volumes", "--remove-orphans"])
+                .map(drop),
+            Kind::Container { id } => docker(&["rm", "--force", "--volumes", id]).map(drop),
+        }
+    }
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        match self.save_logs() {
+            Ok(()) => info!(file = %self.logs.display(), "saved logs of the containers"),
+            Err(e) => warn!("unable to save logs of the containers: {:#}", e),
+        }
+        if let Err(e) = self.remove() {
+            warn!("unable to remove the containers: {:#}", e);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Limits of the requests in flight to an operation across the processes of `--jobs`, the
+//! slots of an operation are lock files shared by the processes
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs::{self, File, OpenOptions, TryLockError},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+/// Interval between the attempts to take a slot of a busy operation
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Directory with the slots of the operations, the lock files are opened once
+#[derive(Debug)]
+pub struct Slots {
+    dir: PathBuf,
+    requests: Arc<File>,
+    /// Lock files of the slots by operation, a file is taken by this process while a slot
+    /// holds it too
+    operations: Mutex<HashMap<u64, Vec<Arc<File>>>>,
+}
+
+/// Taken slot of an operation, released when dropped
+#[derive(Debug)]
+pub struct Slot(Arc<File>);
+
+fn open(file: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(file)
+        .context(format!("unable to open {}", file.display()))
+}
+
+impl Slots {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Slots> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).context(format!("unable to create {}", dir.display()))?;
+        let requests = Arc::new(open(&dir.join("requests.lock"))?);
+        Ok(Slots {
+            dir,
+            requests,
+            operations: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Takes a free slot of the operation, `None` if all of them are taken
+    fn try_acquire(&self, method: &str, path: &str, limit: u32) -> Result<Option<Slot>> {
+        let mut hasher = DefaultHasher::new();
+        (method, path).hash(&mut hasher);
+        let operation = hasher.finish();
+        let mut operations = self
+            .operations
+            .lock()
+            .map_err(|_| anyhow!("slots poisoned"))?;
+        let files = operations.entry(operation).or_default();
+        for index in files.len()..limit.max(1) as usize {
+            let file = self.dir.join(format!("{:016x}-{}.lock", operation, index));
+            files.push(Arc::new(open(&file)?));
+        }
+        // A file locked by this process would be locked again, the slots it holds are skipped
+        for file in files.iter().filter(|file| Arc::strong_count(file) == 1) {
+            match file.try_lock() {
+                Ok(()) => return Ok(Some(Slot(file.clone()))),
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Waits until fewer than `limit` requests to the operation are in flight and takes one of
+    /// its slots
+    pub fn acquire(&self, method: &str, path: &str, limit: u32) -> Result<Slot> {
+        loop {
+            if let Some(slot) = self.try_acquire(method, path, limit)? {
+                return Ok(slot);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Shared by the processes while they send a request, so that one of them can send a
+    /// request alone
+    pub fn shared(&self) -> Result<Slot> {
+        self.requests.lock_shared()?;
+        Ok(Slot(self.requests.clone()))
+    }
+
+    /// Waits until no process sends a request and keeps the others from sending any until
+    /// the slot is dropped
+    pub fn exclusive(&self) -> Result<Slot> {
+        self.requests.lock()?;
+        Ok(Slot(self.requests.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_the_slots_taken_by_the_process() {
+        let dir = std::env::temp_dir().join(format!("openapi-fuzzer-slots-{}", std::process::id()));
+        let slots = Slots::new(&dir).unwrap();
+        let slot = slots.try_acquire("GET", "/items", 1).unwrap();
+        assert!(slot.is_some());
+        assert!(slots.try_acquire("GET", "/items", 1).unwrap().is_none());
+        assert!(slots.try_acquire("GET", "/items", 2).unwrap().is_some());
+        drop(slot);
+        assert!(slots.try_acquire("GET", "/items", 1).unwrap().is_some());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
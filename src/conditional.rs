@@ -0,0 +1,406 @@
+use std::collections::BTreeMap;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+use serde_json::{json, Map, Value};
+use tracing::debug;
+
+use crate::payload::{Generator, Payload};
+
+/// Probability that a payload of the negative mode violates one of the dependencies, unless
+/// it is already invalid
+const NEGATIVE_PROBABILITY: f64 = 0.5;
+
+/// Fields that must or may only have one of the values, a missing field has any value
+type Values = Vec<(String, Vec<Value>)>;
+
+/// Rules of the request body of an operation and the schemas of its properties
+type BodyRules = (Vec<Rule>, Map<String, Value>);
+
+/// Dependency between the fields of a request body, kept by the raw specification as the
+/// keywords are not part of the parsed one
+#[derive(Debug)]
+enum Rule {
+    /// `dependentRequired` (or `dependencies` of older drafts): the fields are required when
+    /// the field is present
+    Dependent {
+        field: String,
+        required: Vec<String>,
+    },
+    /// `if`, `then` and `else`: the fields of the branch are required and have the values
+    Conditional {
+        condition: Values,
+        present: Vec<String>,
+        then: Branch,
+        otherwise: Branch,
+    },
+}
+
+/// `then` or `else` of a conditional schema
+#[derive(Debug, Default)]
+struct Branch {
+    required: Vec<String>,
+    values: Values,
+    /// Schemas of the fields to generate them when they are missing
+    properties: Map<String, Value>,
+}
+
+/// Satisfies the conditional keywords of the request bodies, so that co-dependent fields (e.g.
+/// `card_number` when `type` is `card`) are present, and violates one of them at a time in the
+/// negative mode
+#[derive(Debug)]
+pub struct Conditionals {
+    /// Rules and the schemas of the properties of the body of each operation
+    rules: BTreeMap<(String, String), BodyRules>,
+    negative: bool,
+}
+
+impl Conditionals {
+    /// Conditional keywords of the specification, `None` if it has none
+    pub fn new(document: &Value, negative: bool) -> Option<Conditionals> {
+        let mut rules = BTreeMap::new();
+        for (operation, schema) in body_schemas(document) {
+            let mut operation_rules = Vec::new();
+            let mut properties = Map::new();
+            collect(document, schema, &mut operation_rules, &mut properties);
+            if !operation_rules.is_empty() {
+                rules.insert(operation, (operation_rules, properties));
+            }
+        }
+        debug!(operations = rules.len(), "conditional schemas");
+        (!rules.is_empty()).then_some(Conditionals { rules, negative })
+    }
+}
+
+impl Generator for Conditionals {
+    fn generate(&self, payload: &mut Payload<'_>, rng: &mut StdRng) {
+        // A payload that is already invalid keeps its single violation
+        if payload.violation.is_some() {
+            return;
+        }
+        let key = (payload.method.to_string(), payload.path.to_string());
+        let (rules, properties) = match self.rules.get(&key) {
+            Some(rules) => rules,
+            None => return,
+        };
+        let body = match payload.body.first_mut() {
+            Some(Value::Object(body)) => body,
+            _ => return,
+        };
+        for rule in rules.iter() {
+            satisfy(rule, body, properties, rng);
+        }
+        if self.negative && payload.negative && rng.gen_bool(NEGATIVE_PROBABILITY) {
+            if let Some(rule) = rules.choose(rng) {
+                payload.violation = violate(rule, body, properties, rng);
+            }
+        }
+    }
+}
+
+/// Schemas of the JSON request bodies of the operations in the raw specification, by method
+/// and path
+pub(crate) fn body_schemas(document: &Value) -> Vec<((String, String), &Value)> {
+    let paths = match document.get("paths").and_then(Value::as_object) {
+        Some(paths) => paths,
+        None => return Vec::new(),
+    };
+    let mut schemas = Vec::new();
+    for (path, item) in paths.iter() {
+        let item = match item.as_object() {
+            Some(item) => item,
+            None => continue,
+        };
+        for (method, operation) in item.iter() {
+            let schema = operation
+                .get("requestBody")
+                .map(|body| resolve(document, body))
+                .and_then(|body| body.get("content")?.as_object())
+                .and_then(|content| {
+                    content
+                        .iter()
+                        .find(|(content, _)| content.contains("json"))
+                        .and_then(|(_, media)| media.get("schema"))
+                });
+            if let Some(schema) = schema {
+                schemas.push(((method.to_uppercase(), path.clone()), schema));
+            }
+        }
+    }
+    schemas
+}
+
+/// Follows a local reference like `#/components/schemas/Payment`
+pub(crate) fn resolve<'a>(document: &'a Value, value: &'a Value) -> &'a Value {
+    let mut value = value;
+    // References to references are followed a few times, cycles are not
+    for _ in 0..8 {
+        match value
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix('#'))
+        {
+            Some(pointer) => match document.pointer(pointer) {
+                Some(target) => value = target,
+                None => break,
+            },
+            None => break,
+        }
+    }
+    value
+}
+
+/// Rules and properties of the object schema, also of the schemas in `allOf`
+fn collect(
+    document: &Value,
+    schema: &Value,
+    rules: &mut Vec<Rule>,
+    properties: &mut Map<String, Value>,
+) {
+    let schema = resolve(document, schema);
+    properties.extend(properties_of(document, schema));
+    for keyword in ["dependentRequired", "dependencies"].iter() {
+        if let Some(Value::Object(dependencies)) = schema.get(*keyword) {
+            for (field, required) in dependencies.iter() {
+                // `dependencies` may also hold schemas, only the lists of fields are supported
+                if let Some(required) = strings(Some(required)) {
+                    rules.push(Rule::Dependent {
+                        field: field.clone(),
+                        required,
+                    });
+                }
+            }
+        }
+    }
+    if let Some(condition) = schema
+        .get("if")
+        .map(|condition| resolve(document, condition))
+    {
+        let branch = |keyword| {
+            let branch = match schema.get(keyword) {
+                Some(branch) => resolve(document, branch),
+                None => return Branch::default(),
+            };
+            let properties = properties_of(document, branch);
+            Branch {
+                required: strings(branch.get("required")).unwrap_or_default(),
+                values: values(&properties),
+                properties,
+            }
+        };
+        rules.push(Rule::Conditional {
+            condition: values(&properties_of(document, condition)),
+            present: strings(condition.get("required")).unwrap_or_default(),
+            then: branch("then"),
+            otherwise: branch("else"),
+        });
+    }
+    if let Some(Value::Array(all_of)) = schema.get("allOf") {
+        for schema in all_of.iter() {
+            collect(document, schema, rules, properties);
+        }
+    }
+}
+
+/// Resolved schemas of the properties of the object schema
+pub(crate) fn properties_of(document: &Value, schema: &Value) -> Map<String, Value> {
+    match schema.get("properties") {
+        Some(Value::Object(properties)) => properties
+            .iter()
+            .map(|(name, property)| (name.clone(), resolve(document, property).clone()))
+            .collect(),
+        _ => Map::new(),
+    }
+}
+
+fn strings(value: Option<&Value>) -> Option<Vec<String>> {
+    value?
+        .as_array()?
+        .iter()
+        .map(|value| value.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Values allowed by `const` or `enum` of the properties
+fn values(properties: &Map<String, Value>) -> Values {
+    properties
+        .iter()
+        .filter_map(|(name, property)| {
+            let allowed = match (property.get("const"), property.get("enum")) {
+                (Some(value), _) => vec![value.clone()],
+                (None, Some(Value::Array(values))) if !values.is_empty() => values.clone(),
+                _ => return None,
+            };
+            Some((name.clone(), allowed))
+        })
+        .collect()
+}
+
+/// Whether the body matches the `if` schema
+fn holds(condition: &Values, present: &[String], body: &Map<String, Value>) -> bool {
+    present.iter().all(|name| body.contains_key(name))
+        && condition
+            .iter()
+            .all(|(name, allowed)| body.get(name).is_none_or(|value| allowed.contains(value)))
+}
+
+/// Value of the field from its `const`, `enum`, `default` or `example`, or of its type
+pub(crate) fn placeholder(schema: Option<&Value>, rng: &mut StdRng) -> Value {
+    let schema = match schema {
+        Some(schema) => schema,
+        None => return json!("fuzz"),
+    };
+    if let Some(value) = schema.get("const") {
+        return value.clone();
+    }
+    if let Some(value) = schema
+        .get("enum")
+        .and_then(Value::as_array)
+        .and_then(|values| values.choose(rng))
+    {
+        return value.clone();
+    }
+    if let Some(value) = schema.get("default").or_else(|| schema.get("example")) {
+        return value.clone();
+    }
+    match schema.get("type").and_then(Value::as_str) {
+        Some("integer") => json!(rng.gen_range(1..1000)),
+        Some("number") => json!(rng.gen_range(1.0..1000.0)),
+        Some("boolean") => json!(rng.gen_bool(0.5)),
+        Some("array") => json!([]),
+        Some("object") => json!({}),
+        _ => json!("fuzz"),
+    }
+}
+
+/// Schema of the field in the branch or the body
+fn property<'a>(
+    name: &str,
+    branch: &'a Map<String, Value>,
+    properties: &'a Map<String, Value>,
+) -> Option<&'a Value> {
+    branch.get(name).or_else(|| properties.get(name))
+}
+
+/// Adds the missing required fields and fixes the values of the rule
+fn satisfy(
+    rule: &Rule,
+    body: &mut Map<String, Value>,
+    properties: &Map<String, Value>,
+    rng: &mut StdRng,
+) {
+    let branch = match rule {
+        Rule::Dependent { field, required } => {
+            if body.contains_key(field) {
+                for name in required.iter() {
+                    if !body.contains_key(name) {
+                        body.insert(name.clone(), placeholder(properties.get(name), rng));
+                    }
+                }
+            }
+            return;
+        }
+        Rule::Conditional {
+            condition,
+            present,
+            then,
+            otherwise,
+        } => match holds(condition, present, body) {
+            true => then,
+            false => otherwise,
+        },
+    };
+    for name in branch.required.iter() {
+        if !body.contains_key(name) {
+            let schema = property(name, &branch.properties, properties);
+            body.insert(name.clone(), placeholder(schema, rng));
+        }
+    }
+    for (name, allowed) in branch.values.iter() {
+        match body.get_mut(name) {
+            Some(value) if !allowed.contains(value) => {
+                *value = allowed.choose(rng).cloned().unwrap_or(Value::Null)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Violates the rule, returns the description of the violation or `None` if it can not be
+fn violate(
+    rule: &Rule,
+    body: &mut Map<String, Value>,
+    properties: &Map<String, Value>,
+    rng: &mut StdRng,
+) -> Option<String> {
+    match rule {
+        Rule::Dependent { field, required } => {
+            let missing = required.choose(rng)?;
+            if !body.contains_key(field) {
+                body.insert(field.clone(), placeholder(properties.get(field), rng));
+            }
+            body.remove(missing);
+            Some(format!(
+                "field `{}` is missing although `{}` is present",
+                missing, field
+            ))
+        }
+        Rule::Conditional {
+            condition,
+            present,
+            then,
+            ..
+        } => {
+            // Makes the condition hold, then breaks the `then` branch
+            for name in present.iter() {
+                if !body.contains_key(name) {
+                    body.insert(name.clone(), placeholder(properties.get(name), rng));
+                }
+            }
+            for (name, allowed) in condition.iter() {
+                if let Some(value) = allowed.choose(rng) {
+                    body.insert(name.clone(), value.clone());
+                }
+            }
+            let cause = condition
+                .iter()
+                .filter_map(|(name, _)| Some(format!("`{}` is {}", name, body.get(name)?)))
+                .collect::<Vec<_>>()
+                .join(" and ");
+            let cause = match cause.is_empty() {
+                true => "the condition holds".to_string(),
+                false => cause,
+            };
+            let names = then
+                .required
+                .iter()
+                .chain(then.values.iter().map(|(name, _)| name))
+                .collect::<Vec<_>>();
+            let name = names.choose(rng)?;
+            match then.values.iter().find(|(n, _)| n == *name) {
+                Some((_, allowed)) if !then.required.contains(name) || rng.gen_bool(0.5) => {
+                    let value = other_value(allowed);
+                    body.insert(name.to_string(), value.clone());
+                    Some(format!("field `{}` is {} although {}", name, value, cause))
+                }
+                _ => {
+                    body.remove(*name);
+                    Some(format!("field `{}` is missing although {}", name, cause))
+                }
+            }
+        }
+    }
+}
+
+/// Value of the same type that is not allowed
+fn other_value(allowed: &[Value]) -> Value {
+    let value = match allowed.first() {
+        Some(Value::Number(number)) => json!(number.as_f64().unwrap_or_default() + 1.0),
+        Some(Value::Bool(b)) => json!(!b),
+        Some(Value::String(s)) => json!(format!("{}-other", s)),
+        _ => json!("other"),
+    };
+    match allowed.contains(&value) {
+        true => Value::Null,
+        false => value,
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    collections::BTreeMap, fmt::Display, fs, net::IpAddr, path::Path, path::PathBuf, str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
+use serde_json::Value;
+use url::{ParseError, Url};
+
+use crate::auth::Credential;
+use crate::cleanup::{CleanupMode, Teardown};
+use crate::client::HttpClient;
+use crate::constraints::Constraint;
+use crate::cookies::Isolation;
+use crate::diff::IgnorePath;
+use crate::encryption::Recipient;
+use crate::faker::Locale;
+use crate::finding::FailOn;
+use crate::graphql::Abuse;
+use crate::identities::IdentityHeader;
+use crate::invariants::Invariant;
+use crate::logs::LogSource;
+use crate::oracle::ExprOracle;
+use crate::queues::Queue;
+use crate::reporter::ReporterSpec;
+use crate::signing::TimestampHeader;
+use crate::status::{Operations, StatusRule};
+use crate::template::Template;
+use crate::tls::TlsVersion;
+
+/// Options loaded from a TOML or YAML file, keys are the long names of the command line
+/// options, e.g. `ignore-status-code = [404]`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub spec: Option<PathBuf>,
+    pub overlay: Vec<PathBuf>,
+    #[serde(deserialize_with = "parsed_all")]
+    pub operation: Vec<Operations>,
+    pub graphql_endpoint: Option<String>,
+    pub graphql_depth: Option<usize>,
+    pub graphql_abuse: Vec<Abuse>,
+    #[serde(deserialize_with = "parsed")]
+    pub url: Option<UrlWithTrailingSlash>,
+    pub health_url: Option<Url>,
+    #[serde(deserialize_with = "duration")]
+    pub health_delay: Option<Duration>,
+    pub wait_for: Option<Url>,
+    #[serde(deserialize_with = "duration")]
+    pub wait_timeout: Option<Duration>,
+    pub compose: Option<PathBuf>,
+    pub image: Option<String>,
+    pub publish: Vec<String>,
+    #[serde(deserialize_with = "parsed")]
+    pub target_logs: Option<LogSource>,
+    pub warm_up: bool,
+    pub reuse_ids: bool,
+    pub round_trip: bool,
+    #[serde(deserialize_with = "parsed")]
+    pub cookie_jar: Option<Isolation>,
+    #[serde(deserialize_with = "parsed")]
+    pub http_client: Option<HttpClient>,
+    #[serde(deserialize_with = "parsed")]
+    pub tls_min_version: Option<TlsVersion>,
+    #[serde(deserialize_with = "parsed")]
+    pub tls_max_version: Option<TlsVersion>,
+    pub tls_cipher: Vec<String>,
+    pub tls_server_name: Option<String>,
+    pub proxy: Option<Url>,
+    pub proxy_auth_command: Option<String>,
+    pub source_address: Option<IpAddr>,
+    pub source_interface: Option<String>,
+    #[serde(deserialize_with = "duration")]
+    pub stream_timeout: Option<Duration>,
+    pub stream_max_bytes: Option<u64>,
+    pub cleanup: Option<CleanupMode>,
+    #[serde(deserialize_with = "parsed_all")]
+    pub teardown: Vec<Teardown>,
+    #[serde(deserialize_with = "parsed")]
+    pub baseline_url: Option<UrlWithTrailingSlash>,
+    #[serde(deserialize_with = "status_rules")]
+    pub ignore_status_code: Vec<StatusRule>,
+    #[serde(deserialize_with = "parsed_all")]
+    pub allow_status: Vec<StatusRule>,
+    #[serde(deserialize_with = "parsed_all")]
+    pub deny_status: Vec<StatusRule>,
+    pub validate_responses: bool,
+    #[serde(deserialize_with = "duration")]
+    pub slow_threshold: Option<Duration>,
+    #[serde(deserialize_with = "duration")]
+    pub hang_threshold: Option<Duration>,
+    pub retry_hangs: bool,
+    pub slow_factor: Option<f64>,
+    pub audit_headers: bool,
+    pub header_policy: Option<PathBuf>,
+    pub probe_cors: bool,
+    pub check_idempotency: bool,
+    pub volatile_field: Vec<String>,
+    #[serde(deserialize_with = "parsed_all")]
+    pub ignore_path: Vec<IgnorePath>,
+    pub rate_limit_burst: Option<u32>,
+    pub probe_smuggling: bool,
+    pub probe_traversal: bool,
+    pub probe_limits: bool,
+    pub chaos: bool,
+    #[serde(deserialize_with = "parsed")]
+    pub timestamp_header: Option<TimestampHeader>,
+    #[serde(deserialize_with = "parsed")]
+    pub hmac_signature: Option<Header>,
+    pub signature_message: Option<String>,
+    pub probe_clock_skew: bool,
+    pub probe_conditional: bool,
+    pub detect_disclosure: bool,
+    pub check_headers: bool,
+    #[serde(deserialize_with = "parsed_all")]
+    pub oracle: Vec<ExprOracle>,
+    #[serde(deserialize_with = "parsed")]
+    pub server_error_when: Option<ExprOracle>,
+    pub negative: bool,
+    pub reverify: Option<u32>,
+    #[serde(deserialize_with = "duration")]
+    pub reverify_interval: Option<Duration>,
+    pub redact: bool,
+    pub redact_field: Vec<String>,
+    pub max_requests: Option<u32>,
+    pub max_requests_per_op: Option<u32>,
+    #[serde(deserialize_with = "duration")]
+    pub max_duration: Option<Duration>,
+    pub adaptive_throttle: bool,
+    pub throttle_error_rate: Option<f64>,
+    #[serde(deserialize_with = "duration")]
+    pub throttle_latency: Option<Duration>,
+    pub jobs: Option<u32>,
+    #[serde(deserialize_with = "duration")]
+    pub snapshot_every: Option<Duration>,
+    pub restore: Option<PathBuf>,
+    #[serde(deserialize_with = "parsed_all")]
+    pub header: Vec<Header>,
+    #[serde(deserialize_with = "parsed_all")]
+    pub identity: Vec<IdentityHeader>,
+    pub headers_file: Option<PathBuf>,
+    #[serde(deserialize_with = "parsed")]
+    pub fail_on: Option<FailOn>,
+    pub max_findings: Option<usize>,
+    pub webhook: Option<Url>,
+    pub webhook_findings: bool,
+    #[serde(deserialize_with = "parsed_all")]
+    pub reporter: Vec<ReporterSpec>,
+    pub issue_title: Option<String>,
+    pub issue_template: Option<PathBuf>,
+    pub issue_label: Vec<String>,
+    pub github_annotations: bool,
+    pub export_har: Option<PathBuf>,
+    pub export_burp: Option<PathBuf>,
+    pub export_postman: Option<PathBuf>,
+    pub export_csv: Option<PathBuf>,
+    pub markdown_summary: Option<PathBuf>,
+    pub coverage: Option<PathBuf>,
+    pub cassette: Option<PathBuf>,
+    pub database: Option<PathBuf>,
+    pub no_database: bool,
+    #[serde(deserialize_with = "parsed_all")]
+    pub encrypt_to: Vec<Recipient>,
+    pub age_identity: Option<PathBuf>,
+    pub log_level: Option<String>,
+    pub log_file: Option<PathBuf>,
+    pub log_json: bool,
+    pub seed: Option<u64>,
+    pub postman: Option<PathBuf>,
+    pub postman_env: Option<PathBuf>,
+    pub traffic: Vec<PathBuf>,
+    pub corpus: Option<PathBuf>,
+    pub success_samples: Option<usize>,
+    pub scenario: Vec<PathBuf>,
+    #[serde(deserialize_with = "parsed")]
+    pub faker: Option<Locale>,
+    pub plain_strings: bool,
+    pub namespace: bool,
+    pub combinations: Option<usize>,
+    pub zip_bombs: bool,
+    /// Options of some of the operations
+    pub operations: Vec<OperationOverride>,
+    /// Work queues of the operations by their tags, fuzzed in parallel
+    pub queues: Vec<Queue>,
+    /// Expectations of the responses to operations following others, e.g. reading back what
+    /// was created
+    pub invariants: Vec<Invariant>,
+    /// Constraints of the specification overridden, e.g. a wrong `maxLength`
+    pub constraints: Vec<Constraint>,
+    /// Credentials sent with every request, stacked, e.g. a subscription key and a token
+    pub auth: Vec<Credential>,
+    /// Named sets of options (e.g. per environment) overriding the options above
+    pub profiles: BTreeMap<String, Config>,
+}
+
+/// Options of the operations matching the pattern, in the format of `--allow-status`
+/// operations, e.g. `GET /legacy/*` or `getItem`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct OperationOverride {
+    #[serde(rename = "match", deserialize_with = "operations")]
+    pub operations: Operations,
+    /// Headers sent to the operations, overriding the ones from the command line
+    #[serde(default, deserialize_with = "parsed_all")]
+    pub header: Vec<Header>,
+    /// Longest time to wait for the response
+    #[serde(default, deserialize_with = "duration")]
+    pub timeout: Option<Duration>,
+    /// Highest number of requests per second sent to each of the operations
+    pub rate_limit: Option<f64>,
+    /// Highest number of requests in flight to each of the operations, across the jobs
+    pub concurrency: Option<u32>,
+    #[serde(default)]
+    pub ignore_status_code: Vec<u16>,
+    /// Values of path, query and header parameters used instead of the generated ones
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+    /// Relative number of payloads of each of the operations per round, 1 by default
+    pub weight: Option<f64>,
+    /// Expression responses of the operations are server errors when it holds, whatever
+    /// their status, instead of `server-error-when`
+    #[serde(default, deserialize_with = "parsed")]
+    pub server_error: Option<ExprOracle>,
+    /// Raw body sent to the operations instead of the generated one, with `{{placeholder}}`
+    /// slots filled with generated values, see `template`
+    #[serde(default, deserialize_with = "parsed")]
+    pub body_template: Option<Template>,
+}
+
+impl OperationOverride {
+    pub fn matches(&self, method: &str, path: &str, operation_id: Option<&str>) -> bool {
+        self.operations.matches(method, path, operation_id)
+    }
+}
+
+fn operations<'de, D: Deserializer<'de>>(d: D) -> Result<Operations, D::Error> {
+    String::deserialize(d)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+fn parsed<'de, D, T>(d: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(d)?
+        .map(|value| value.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn parsed_all<'de, D, T>(d: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Vec::<String>::deserialize(d)?
+        .iter()
+        .map(|value| value.parse().map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// Status rules given as strings or as plain status codes, e.g. `[404, "getItem=503"]`
+fn status_rules<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<StatusRule>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CodeOrRule {
+        Code(u16),
+        Rule(String),
+    }
+
+    Vec::<CodeOrRule>::deserialize(d)?
+        .into_iter()
+        .map(|rule| match rule {
+            CodeOrRule::Code(code) => code.to_string().parse(),
+            CodeOrRule::Rule(rule) => rule.parse(),
+        })
+        .map(|rule| rule.map_err(serde::de::Error::custom))
+        .collect()
+}
+
+pub fn duration<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+    Option::<String>::deserialize(d)?
+        .map(|value| humantime::parse_duration(&value).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Replaces `${VAR}` with the value of the environment variable, `$${` stands for `${`
+fn interpolate(value: &str) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            result.push_str("${");
+            rest = escaped;
+        } else if let Some(reference) = rest.strip_prefix("${") {
+            let end = reference
+                .find('}')
+                .ok_or_else(|| format!("unterminated variable reference in `{}`", value))?;
+            let name = &reference[..end];
+            let variable = std::env::var(name)
+                .map_err(|_| format!("environment variable `{}` is not set", name))?;
+            result.push_str(&variable);
+            rest = &reference[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Interpolates environment variables in all strings of the value, `key` is the location of
+/// the value for errors
+pub fn interpolate_all(value: &mut Value, key: &str) -> Result<()> {
+    match value {
+        Value::String(s) => {
+            *s = interpolate(s).map_err(|e| anyhow!("{} (in `{}`)", e, key))?;
+        }
+        Value::Array(values) => {
+            for value in values.iter_mut() {
+                interpolate_all(value, key)?;
+            }
+        }
+        Value::Object(object) => {
+            for (name, value) in object.iter_mut() {
+                let key = match key.is_empty() {
+                    true => name.clone(),
+                    false => format!("{}.{}", key, name),
+                };
+                interpolate_all(value, &key)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Parses the file without resolving the references to environment variables, files with
+/// the `.toml` extension are parsed as TOML and other files as YAML
+pub fn parse(file: &Path) -> Result<Value> {
+    let content = fs::read_to_string(file).context(format!("unable to read {}", file.display()))?;
+    match file.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => toml::from_str::<Value>(&content).map_err(anyhow::Error::from),
+        _ => serde_yaml::from_str::<Value>(&content).map_err(anyhow::Error::from),
+    }
+    .context(format!("unable to parse {}", file.display()))
+}
+
+/// Loads the file, files with the `.toml` extension are parsed as TOML and other files as
+/// YAML. References to environment variables (`${VAR}`) in values are resolved.
+pub fn load<T: DeserializeOwned>(file: &Path) -> Result<T> {
+    let mut value = parse(file)?;
+    interpolate_all(&mut value, "").context(format!("unable to interpolate {}", file.display()))?;
+    serde_json::from_value(value).context(format!("invalid config {}", file.display()))
+}
+
+impl Config {
+    pub fn load(file: &Path) -> Result<Config> {
+        load(file)
+    }
+
+    /// Takes out the profile with the name
+    pub fn profile(&mut self, name: &str) -> Result<Config> {
+        self.profiles.remove(name).ok_or_else(|| {
+            anyhow!(
+                "unknown profile `{}`, available profiles: {}",
+                name,
+                self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })
+    }
+}
+
+/// Header given as `Name: value`, the name is lowercased
+#[derive(Debug)]
+pub struct Header(pub String, pub String);
+
+impl FromStr for Header {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.splitn(2, ':').collect::<Vec<_>>();
+        if parts.len() != 2 {
+            return Err("invalid header format".to_string());
+        }
+        Ok(Header(
+            parts[0].trim().to_lowercase(),
+            parts[1].trim().to_string(),
+        ))
+    }
+}
+
+impl From<Header> for (String, String) {
+    fn from(val: Header) -> Self {
+        (val.0, val.1)
+    }
+}
+
+/// Url of the API, a trailing slash is added so paths of operations are appended to it
+#[derive(Debug)]
+pub struct UrlWithTrailingSlash(pub Url);
+
+impl FromStr for UrlWithTrailingSlash {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.ends_with('/') {
+            true => Ok(UrlWithTrailingSlash(Url::from_str(s)?)),
+            false => Ok(UrlWithTrailingSlash(Url::from_str(&(s.to_owned() + "/"))?)),
+        }
+    }
+}
+
+impl From<UrlWithTrailingSlash> for Url {
+    fn from(val: UrlWithTrailingSlash) -> Self {
+        val.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn interpolates_environment_variables() {
+        std::env::set_var("OPENAPI_FUZZER_TEST_TOKEN", "s3cr3t");
+        assert_eq!(
+            interpolate("Bearer ${OPENAPI_FUZZER_TEST_TOKEN}!").unwrap(),
+            "Bearer s3cr3t!"
+        );
+        assert_eq!(
+            interpolate("$${OPENAPI_FUZZER_TEST_TOKEN} costs $5").unwrap(),
+            "${OPENAPI_FUZZER_TEST_TOKEN} costs $5"
+        );
+        assert!(interpolate("${OPENAPI_FUZZER_TEST_TOKEN").is_err());
+        assert!(interpolate("${OPENAPI_FUZZER_TEST_UNSET}").is_err());
+
+        let mut value = json!({"auth": [{"token": "${OPENAPI_FUZZER_TEST_TOKEN}"}], "jobs": 2});
+        interpolate_all(&mut value, "").unwrap();
+        assert_eq!(value, json!({"auth": [{"token": "s3cr3t"}], "jobs": 2}));
+
+        let mut value = json!({"auth": {"token": "${OPENAPI_FUZZER_TEST_UNSET}"}});
+        let error = interpolate_all(&mut value, "").unwrap_err().to_string();
+        assert!(error.ends_with("(in `auth.token`)"), "{}", error);
+    }
+}
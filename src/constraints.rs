@@ -0,0 +1,403 @@
+//! Constraints of the specification overridden from the config, for third-party specifications
+//! with wrong ones, e.g. a `maxLength: 10` the server does not enforce or a `pattern` no real
+//! value matches, which keep the generators from sending anything the server would process.
+//! The schemas of the components and the parameters are changed in the raw document before it
+//! is parsed, so the generators and the response validation see the same constraints; a
+//! referenced schema is changed for all the places referencing it. Other schemas can be changed
+//! with overlays.
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Number, Value};
+use tracing::debug;
+
+use crate::status::Operations;
+
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Keywords of the schemas the constraints can be overridden for
+const KEYWORDS: [&str; 14] = [
+    "maxLength",
+    "minLength",
+    "pattern",
+    "format",
+    "enum",
+    "maximum",
+    "minimum",
+    "exclusiveMaximum",
+    "exclusiveMinimum",
+    "multipleOf",
+    "maxItems",
+    "minItems",
+    "uniqueItems",
+    "maxProperties",
+];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Constraint {
+    /// Schema of the components and the properties nested in it, e.g. `Item.name`, arrays are
+    /// looked into for the properties of their items
+    #[serde(default)]
+    pub schema: Option<String>,
+    /// Name of the parameters whose schema is changed, instead of a schema
+    #[serde(default)]
+    pub parameter: Option<String>,
+    /// Operations of the parameters, all by default
+    #[serde(rename = "match", default, deserialize_with = "operations")]
+    pub operations: Option<Operations>,
+    /// Keywords removed from the schema, e.g. `pattern` or `maxLength`
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    #[serde(default)]
+    pub max_length: Option<u64>,
+    #[serde(default)]
+    pub min_length: Option<u64>,
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub maximum: Option<f64>,
+    #[serde(default)]
+    pub minimum: Option<f64>,
+    #[serde(default)]
+    pub max_items: Option<u64>,
+    #[serde(default)]
+    pub min_items: Option<u64>,
+}
+
+fn operations<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Operations>, D::Error> {
+    Option::<String>::deserialize(d)?
+        .map(|operations| operations.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn escaped(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Pointer of the node the reference of the node at the pointer leads to, or the pointer
+fn followed(document: &Value, pointer: String) -> String {
+    let mut pointer = pointer;
+    // References to references are followed a few times, cycles are not
+    for _ in 0..8 {
+        match document
+            .pointer(&pointer)
+            .and_then(|node| node.get("$ref"))
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix('#'))
+        {
+            Some(target) if document.pointer(target).is_some() => pointer = target.to_string(),
+            _ => break,
+        }
+    }
+    pointer
+}
+
+/// Pointer of the property of the schema at the pointer, in its properties, the schemas it
+/// combines or its items
+fn property(document: &Value, pointer: &str, name: &str, depth: usize) -> Option<String> {
+    let schema = document.pointer(pointer)?;
+    if schema
+        .get("properties")
+        .is_some_and(|properties| properties.get(name).is_some())
+    {
+        return Some(followed(
+            document,
+            format!("{}/properties/{}", pointer, escaped(name)),
+        ));
+    }
+    if depth == 0 {
+        return None;
+    }
+    for keyword in ["allOf", "oneOf", "anyOf"] {
+        let count = schema
+            .get(keyword)
+            .and_then(Value::as_array)
+            .map_or(0, Vec::len);
+        for i in 0..count {
+            let member = followed(document, format!("{}/{}/{}", pointer, keyword, i));
+            if let Some(found) = property(document, &member, name, depth - 1) {
+                return Some(found);
+            }
+        }
+    }
+    if schema.get("items").is_some() {
+        let items = followed(document, format!("{}/items", pointer));
+        return property(document, &items, name, depth - 1);
+    }
+    None
+}
+
+/// Pointer of the schema of the components and its nested properties, e.g. `Item.name`
+fn schema(document: &Value, path: &str) -> Result<String> {
+    let mut names = path.split('.');
+    let root = names.next().unwrap_or_default();
+    let mut pointer = followed(document, format!("/components/schemas/{}", escaped(root)));
+    if document.pointer(&pointer).is_none() {
+        return Err(anyhow!("no schema {} in the components", root));
+    }
+    for name in names {
+        pointer = property(document, &pointer, name, 8)
+            .ok_or_else(|| anyhow!("no property {} in {}", name, path))?;
+    }
+    Ok(pointer)
+}
+
+/// Pointers of the schemas of the parameters with the name in the operations
+fn parameters(document: &Value, name: &str, operations: Option<&Operations>) -> Vec<String> {
+    let mut pointers = Vec::new();
+    let paths = match document.get("paths").and_then(Value::as_object) {
+        Some(paths) => paths,
+        None => return pointers,
+    };
+    for (path, item) in paths.iter() {
+        let item_pointer = followed(document, format!("/paths/{}", escaped(path)));
+        let item = document.pointer(&item_pointer).unwrap_or(item);
+        let selected = METHODS
+            .iter()
+            .filter(|method| {
+                item.get(**method).is_some_and(|operation| {
+                    let id = operation.get("operationId").and_then(Value::as_str);
+                    operations.is_none_or(|operations| {
+                        operations.matches(&method.to_uppercase(), path, id)
+                    })
+                })
+            })
+            .map(|method| format!("{}/{}", item_pointer, method))
+            .collect::<Vec<_>>();
+        // Parameters of the path item are parameters of its operations
+        let mut owners = selected.clone();
+        if !selected.is_empty() {
+            owners.push(item_pointer.clone());
+        }
+        for owner in owners {
+            let count = document
+                .pointer(&format!("{}/parameters", owner))
+                .and_then(Value::as_array)
+                .map_or(0, Vec::len);
+            for i in 0..count {
+                let parameter = followed(document, format!("{}/parameters/{}", owner, i));
+                let named = document
+                    .pointer(&parameter)
+                    .and_then(|parameter| parameter.get("name"))
+                    .and_then(Value::as_str)
+                    == Some(name);
+                if named {
+                    pointers.push(followed(document, format!("{}/schema", parameter)));
+                }
+            }
+        }
+    }
+    pointers.sort();
+    pointers.dedup();
+    pointers
+}
+
+fn number(value: f64) -> Value {
+    match value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        true => Value::from(value as i64),
+        false => Number::from_f64(value).map_or(Value::Null, Value::Number),
+    }
+}
+
+impl Constraint {
+    fn name(&self) -> String {
+        match (&self.schema, &self.parameter) {
+            (Some(schema), _) => schema.clone(),
+            (None, Some(parameter)) => format!("parameter {}", parameter),
+            (None, None) => String::new(),
+        }
+    }
+
+    /// Pointers of the schemas the constraint changes
+    fn targets(&self, document: &Value) -> Result<Vec<String>> {
+        match (&self.schema, &self.parameter) {
+            (Some(path), None) => Ok(vec![schema(document, path)?]),
+            (None, Some(name)) => {
+                let pointers = parameters(document, name, self.operations.as_ref());
+                match pointers.is_empty() {
+                    true => Err(anyhow!("no operation has the parameter {}", name)),
+                    false => Ok(pointers),
+                }
+            }
+            _ => Err(anyhow!("expected either a `schema` or a `parameter`")),
+        }
+    }
+
+    fn overrides(&self) -> Vec<(&'static str, Value)> {
+        let mut overrides = Vec::new();
+        if let Some(max_length) = self.max_length {
+            overrides.push(("maxLength", Value::from(max_length)));
+        }
+        if let Some(min_length) = self.min_length {
+            overrides.push(("minLength", Value::from(min_length)));
+        }
+        if let Some(pattern) = &self.pattern {
+            overrides.push(("pattern", Value::from(pattern.as_str())));
+        }
+        if let Some(maximum) = self.maximum {
+            overrides.push(("maximum", number(maximum)));
+        }
+        if let Some(minimum) = self.minimum {
+            overrides.push(("minimum", number(minimum)));
+        }
+        if let Some(max_items) = self.max_items {
+            overrides.push(("maxItems", Value::from(max_items)));
+        }
+        if let Some(min_items) = self.min_items {
+            overrides.push(("minItems", Value::from(min_items)));
+        }
+        overrides
+    }
+
+    fn apply(&self, schema: &mut Map<String, Value>) {
+        for keyword in self.ignore.iter() {
+            schema.remove(keyword);
+        }
+        for (keyword, value) in self.overrides() {
+            schema.insert(keyword.to_string(), value);
+        }
+    }
+}
+
+/// Overrides the constraints of the schemas of the document, in order
+pub fn apply(constraints: &[Constraint], document: &mut Value) -> Result<()> {
+    for constraint in constraints {
+        let name = constraint.name();
+        (|| {
+            for keyword in constraint.ignore.iter() {
+                if !KEYWORDS.contains(&keyword.as_str()) {
+                    return Err(anyhow!(
+                        "unknown keyword {}, expected one of {}",
+                        keyword,
+                        KEYWORDS.join(", ")
+                    ));
+                }
+            }
+            for pointer in constraint.targets(document)? {
+                match document.pointer_mut(&pointer) {
+                    Some(Value::Object(schema)) => constraint.apply(schema),
+                    _ => return Err(anyhow!("{} is not a schema", pointer)),
+                }
+                debug!(%pointer, "constraints overridden");
+            }
+            Ok(())
+        })()
+        .with_context(|| format!("invalid constraints of {}", name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn document() -> Value {
+        json!({
+            "openapi": "3.0.0",
+            "paths": {
+                "/items": {
+                    "parameters": [{"$ref": "#/components/parameters/Limit"}],
+                    "get": {
+                        "operationId": "listItems",
+                        "parameters": [
+                            {"name": "q", "in": "query", "schema": {"type": "string", "maxLength": 3}},
+                        ],
+                    },
+                },
+                "/orders": {
+                    "post": {
+                        "parameters": [
+                            {"name": "q", "in": "query", "schema": {"type": "string", "maxLength": 3}},
+                        ],
+                    },
+                },
+            },
+            "components": {
+                "parameters": {
+                    "Limit": {"name": "limit", "in": "query", "schema": {"type": "integer", "maximum": 10}},
+                },
+                "schemas": {
+                    "Order": {
+                        "allOf": [{"$ref": "#/components/schemas/Base"}],
+                        "properties": {"items": {"type": "array", "items": {"$ref": "#/components/schemas/Item"}}},
+                    },
+                    "Base": {"properties": {"id": {"type": "string", "format": "uuid"}}},
+                    "Item": {"properties": {"sku": {"type": "string", "pattern": "^[A-Z]{3}$"}}},
+                },
+            },
+        })
+    }
+
+    fn constraints(constraints: Value) -> Vec<Constraint> {
+        serde_json::from_value(constraints).unwrap()
+    }
+
+    #[test]
+    fn overrides_nested_properties_of_schemas() {
+        let mut document = document();
+        apply(
+            &constraints(json!([
+                {"schema": "Order.items.sku", "ignore": ["pattern"], "max-length": 64},
+                {"schema": "Order.id", "ignore": ["format"]},
+            ])),
+            &mut document,
+        )
+        .unwrap();
+        assert_eq!(
+            document["components"]["schemas"]["Item"]["properties"]["sku"],
+            json!({"type": "string", "maxLength": 64})
+        );
+        assert_eq!(
+            document["components"]["schemas"]["Base"]["properties"]["id"],
+            json!({"type": "string"})
+        );
+    }
+
+    #[test]
+    fn overrides_parameters_of_the_operations() {
+        let mut document = document();
+        apply(
+            &constraints(json!([
+                {"parameter": "q", "match": "listItems", "ignore": ["maxLength"]},
+                {"parameter": "limit", "maximum": 1000.0},
+            ])),
+            &mut document,
+        )
+        .unwrap();
+        assert_eq!(
+            document.pointer("/paths/~1items/get/parameters/0/schema"),
+            Some(&json!({"type": "string"}))
+        );
+        assert_eq!(
+            document.pointer("/paths/~1orders/post/parameters/0/schema/maxLength"),
+            Some(&json!(3))
+        );
+        assert_eq!(
+            document.pointer("/components/parameters/Limit/schema/maximum"),
+            Some(&json!(1000))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_constraints() {
+        for invalid in [
+            json!([{"schema": "Order", "ignore": ["title"]}]),
+            json!([{"schema": "Missing"}]),
+            json!([{"schema": "Order.missing"}]),
+            json!([{"parameter": "missing"}]),
+            json!([{"parameter": "q", "match": "GET /orders"}]),
+            json!([{"schema": "Order", "parameter": "q"}]),
+        ] {
+            let mut document = document();
+            assert!(
+                apply(&constraints(invalid.clone()), &mut document).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::finding::Finding;
+
+/// State of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum State {
+    Running,
+    /// The budget of the job is exhausted
+    Finished,
+    Stopped,
+    Failed,
+}
+
+/// Progress of a job as returned by the API
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Status {
+    pub id: u64,
+    pub state: State,
+    pub url: String,
+    pub requests: u32,
+    pub findings: usize,
+    /// Highest number of requests per second of the job
+    pub rate_limit: Option<f64>,
+    /// Thresholds of `fail-on` and `max-findings` the finished job exceeded
+    pub failures: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    status: Status,
+    findings: Vec<Finding>,
+}
+
+/// State of a job shared between the fuzzer running it and the API
+#[derive(Debug)]
+pub struct Control {
+    stopped: AtomicBool,
+    shared: Mutex<Shared>,
+    /// Notified about new findings and the end of the job
+    changed: Condvar,
+    last_request: Mutex<Option<Instant>>,
+}
+
+impl Control {
+    pub fn new(id: u64, url: String) -> Control {
+        Control {
+            stopped: AtomicBool::new(false),
+            shared: Mutex::new(Shared {
+                status: Status {
+                    id,
+                    state: State::Running,
+                    url,
+                    requests: 0,
+                    findings: 0,
+                    rate_limit: None,
+                    failures: Vec::new(),
+                    error: None,
+                },
+                findings: Vec::new(),
+            }),
+            changed: Condvar::new(),
+            last_request: Mutex::new(None),
+        }
+    }
+
+    pub fn stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Makes the fuzzer stop before its next request
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Changes the highest number of requests per second of the running job
+    pub fn set_rate_limit(&self, rate_limit: Option<f64>) {
+        self.shared.lock().unwrap().status.rate_limit = rate_limit;
+    }
+
+    /// Waits until the next request fits into the rate limit of the job
+    pub fn throttle(&self) {
+        let rate_limit = self.shared.lock().unwrap().status.rate_limit;
+        let mut last_request = self.last_request.lock().unwrap();
+        if let (Some(rate_limit), Some(last)) = (rate_limit, *last_request) {
+            if rate_limit > 0.0 {
+                let interval = Duration::from_secs_f64(1.0 / rate_limit);
+                if let Some(wait) = interval.checked_sub(last.elapsed()) {
+                    thread::sleep(wait);
+                }
+            }
+        }
+        *last_request = Some(Instant::now());
+    }
+
+    pub fn progress(&self, requests: u32, findings: usize) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.status.requests = requests;
+        shared.status.findings = findings;
+    }
+
+    /// Publishes the new deduplicated finding to the streams of findings
+    pub fn finding(&self, finding: &Finding) {
+        self.shared.lock().unwrap().findings.push(finding.clone());
+        self.changed.notify_all();
+    }
+
+    pub fn status(&self) -> Status {
+        self.shared.lock().unwrap().status.clone()
+    }
+
+    /// Records the result of the job, the reasons why it failed its thresholds or the error
+    pub fn end(&self, result: Result<Vec<String>>) {
+        let mut shared = self.shared.lock().unwrap();
+        match result {
+            Ok(failures) => {
+                shared.status.state = match self.stopped() {
+                    true => State::Stopped,
+                    false => State::Finished,
+                };
+                shared.status.failures = failures;
+            }
+            Err(e) => {
+                shared.status.state = State::Failed;
+                shared.status.error = Some(format!("{:#}", e));
+            }
+        }
+        drop(shared);
+        self.changed.notify_all();
+    }
+
+    /// Waits for findings after the first `seen` ones, returns `None` when the job ended and
+    /// there are no more findings
+    pub fn wait_for_findings(&self, seen: usize) -> Option<Vec<Finding>> {
+        let shared = self
+            .changed
+            .wait_while(self.shared.lock().unwrap(), |shared| {
+                shared.findings.len() <= seen && shared.status.state == State::Running
+            })
+            .unwrap();
+        match shared.findings.get(seen..) {
+            Some(findings) if !findings.is_empty() => Some(findings.to_vec()),
+            _ => None,
+        }
+    }
+}
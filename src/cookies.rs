@@ -0,0 +1,393 @@
+//! Cookies set by the API sent back with the following requests, like a browser session, so
+//! that APIs with sticky sessions do not see every request as a new client
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::transport::{Request, Response};
+
+/// Which requests share the cookies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Isolation {
+    /// All requests of the run, the jobs share them too
+    Shared,
+    /// Requests of each job, the fuzzing process
+    Worker,
+    /// Requests to each operation
+    Operation,
+}
+
+impl FromStr for Isolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shared" => Ok(Isolation::Shared),
+            "worker" => Ok(Isolation::Worker),
+            "operation" => Ok(Isolation::Operation),
+            _ => Err(format!(
+                "unknown cookie isolation `{}`, expected shared, worker or operation",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Isolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Isolation::Shared => "shared",
+            Isolation::Worker => "worker",
+            Isolation::Operation => "operation",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Cookie from a `Set-Cookie` header
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Host the cookie was set by, or the domain it is sent to with its subdomains
+    pub domain: String,
+    pub host_only: bool,
+    pub path: String,
+    pub secure: bool,
+    /// Seconds since the epoch, kept until the end of the run without it
+    pub expires: Option<u64>,
+}
+
+impl Cookie {
+    /// Parses the `Set-Cookie` header of a response to the url
+    fn parse(header: &str, url: &Url) -> Option<Cookie> {
+        let mut attributes = header.split(';');
+        let (name, value) = attributes.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+        let host = url.host_str()?.to_lowercase();
+        // Path of the url up to its last slash
+        let default_path = match url.path().rfind('/') {
+            Some(0) | None => "/".to_string(),
+            Some(end) => url.path()[..end].to_string(),
+        };
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path,
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attribute in attributes {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+                None => (attribute.trim().to_lowercase(), ""),
+            };
+            match key.as_str() {
+                "domain" if !value.is_empty() => {
+                    let domain = value.trim_start_matches('.').to_lowercase();
+                    // Cookies for other sites are ignored, as by browsers
+                    if host != domain && !host.ends_with(&format!(".{}", domain)) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "max-age" => max_age = value.parse::<i64>().ok(),
+                "expires" => {
+                    cookie.expires = httpdate::parse_http_date(value)
+                        .ok()
+                        .map(|expires| seconds(expires).unwrap_or(0))
+                }
+                _ => {}
+            }
+        }
+        // Max-Age takes precedence over Expires
+        if let Some(max_age) = max_age {
+            cookie.expires = Some(match max_age {
+                age if age <= 0 => 0,
+                age => seconds(SystemTime::now() + Duration::from_secs(age as u64)).unwrap_or(0),
+            });
+        }
+        Some(cookie)
+    }
+
+    fn expired(&self) -> bool {
+        self.expires
+            .is_some_and(|expires| seconds(SystemTime::now()).unwrap_or(0) >= expires)
+    }
+
+    /// Whether the cookie is sent with a request to the url
+    fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.to_lowercase(),
+            None => return false,
+        };
+        let domain = match self.host_only {
+            true => host == self.domain,
+            false => host == self.domain || host.ends_with(&format!(".{}", self.domain)),
+        };
+        let path = url.path();
+        let path = path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+        domain && path && (!self.secure || url.scheme() == "https") && !self.expired()
+    }
+}
+
+fn seconds(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|since| since.as_secs())
+}
+
+/// Cookies set by responses, by the scope of the isolation
+#[derive(Debug)]
+pub struct CookieJar {
+    isolation: Isolation,
+    cookies: Mutex<BTreeMap<String, Vec<Cookie>>>,
+    /// Cookies set since they were last taken, to share them with the other jobs, only when
+    /// they are shared
+    changes: Mutex<Vec<(String, Cookie)>>,
+}
+
+impl CookieJar {
+    pub fn new(isolation: Isolation) -> CookieJar {
+        CookieJar {
+            isolation,
+            cookies: Mutex::new(BTreeMap::new()),
+            changes: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn isolation(&self) -> Isolation {
+        self.isolation
+    }
+
+    /// Cookies of the operation are shared with the requests in this scope
+    pub fn scope(&self, method: &str, path: &str) -> String {
+        match self.isolation {
+            Isolation::Shared | Isolation::Worker => String::new(),
+            Isolation::Operation => format!("{} {}", method, path),
+        }
+    }
+
+    /// Adds the cookies of the scope to the `Cookie` header of the request, cookies already in
+    /// it, e.g. generated cookie parameters, are kept
+    pub fn apply(&self, scope: &str, request: &mut Request) {
+        let cookies = self.cookies.lock().unwrap();
+        let cookies = match cookies.get(scope) {
+            Some(cookies) => cookies,
+            None => return,
+        };
+        let header = request
+            .headers
+            .iter()
+            .position(|(name, _)| name.eq_ignore_ascii_case("cookie"));
+        let present = header
+            .map(|i| {
+                request.headers[i]
+                    .1
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('=').map(|(name, _)| name.trim()))
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        // More specific paths first, as browsers send them
+        let mut matching = cookies
+            .iter()
+            .filter(|cookie| cookie.matches(&request.url) && !present.contains(&cookie.name))
+            .collect::<Vec<_>>();
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        if matching.is_empty() {
+            return;
+        }
+        let pairs = matching
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        match header {
+            Some(i) if !request.headers[i].1.trim().is_empty() => {
+                request.headers[i].1 = format!("{}; {}", request.headers[i].1, pairs)
+            }
+            Some(i) => request.headers[i].1 = pairs,
+            None => request.headers.push(("Cookie".to_string(), pairs)),
+        }
+    }
+
+    /// Keeps the cookies set by the response to the request in the scope
+    pub fn store(&self, scope: &str, request: &Request, response: &Response) {
+        for (name, value) in response.headers.iter() {
+            if !name.eq_ignore_ascii_case("set-cookie") {
+                continue;
+            }
+            if let Some(cookie) = Cookie::parse(value, &request.url) {
+                if self.isolation == Isolation::Shared {
+                    self.changes
+                        .lock()
+                        .unwrap()
+                        .push((scope.to_string(), cookie.clone()));
+                }
+                self.set(scope, cookie);
+            }
+        }
+    }
+
+    /// Sets the cookie in the scope, replacing the one with the same name, domain and path, an
+    /// expired cookie removes it
+    pub fn set(&self, scope: &str, cookie: Cookie) {
+        let mut cookies = self.cookies.lock().unwrap();
+        let cookies = cookies.entry(scope.to_string()).or_default();
+        let replaced = |c: &Cookie| {
+            c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path
+        };
+        cookies.retain(|c| !replaced(c) && !c.expired());
+        if !cookie.expired() {
+            cookies.push(cookie);
+        }
+    }
+
+    /// Cookies set since the last time, with their scopes
+    pub fn take_changes(&self) -> Vec<(String, Cookie)> {
+        std::mem::take(&mut *self.changes.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(url: &str, headers: Vec<(String, String)>) -> Request {
+        Request {
+            method: "GET".to_string(),
+            url: Url::parse(url).unwrap(),
+            headers,
+            body: None,
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        }
+    }
+
+    fn response(cookies: &[&str]) -> Response {
+        Response {
+            status: 200,
+            status_text: "OK".to_string(),
+            http_version: "HTTP/1.1".to_string(),
+            headers: cookies
+                .iter()
+                .map(|cookie| ("Set-Cookie".to_string(), cookie.to_string()))
+                .collect(),
+            body: String::new(),
+            elapsed: Duration::default(),
+            events: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn parses_attributes() {
+        let url = Url::parse("https://api.example.com/v1/users").unwrap();
+        let cookie = Cookie::parse(
+            "session=\"abc\"; Domain=.example.com; Path=/v1; Secure; HttpOnly",
+            &url,
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "session");
+        assert_eq!(cookie.value, "abc");
+        assert_eq!(cookie.domain, "example.com");
+        assert!(!cookie.host_only);
+        assert_eq!(cookie.path, "/v1");
+        assert!(cookie.secure);
+        assert_eq!(cookie.expires, None);
+
+        let cookie = Cookie::parse("id=1", &url).unwrap();
+        assert_eq!(cookie.domain, "api.example.com");
+        assert!(cookie.host_only);
+        assert_eq!(cookie.path, "/v1");
+
+        let cookie = Cookie::parse("id=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT", &url).unwrap();
+        assert_eq!(cookie.expires, Some(1_445_412_480));
+        assert!(cookie.expired());
+        let cookie = Cookie::parse(
+            "id=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT; Max-Age=60",
+            &url,
+        )
+        .unwrap();
+        assert!(!cookie.expired());
+
+        assert!(Cookie::parse("id=1; Domain=other.com", &url).is_none());
+        assert!(Cookie::parse("=1", &url).is_none());
+        assert!(Cookie::parse("id", &url).is_none());
+    }
+
+    #[test]
+    fn matches_domains_paths_and_schemes() {
+        let url = Url::parse("https://example.com/v1/users").unwrap();
+        let cookie = Cookie::parse("id=1; Path=/v1", &url).unwrap();
+        let matches = |url: &str| cookie.matches(&Url::parse(url).unwrap());
+        assert!(matches("https://example.com/v1"));
+        assert!(matches("https://example.com/v1/orders"));
+        assert!(!matches("https://example.com/v10"));
+        assert!(!matches("https://api.example.com/v1"));
+
+        let cookie = Cookie::parse("id=1; Domain=example.com; Path=/; Secure", &url).unwrap();
+        let matches = |url: &str| cookie.matches(&Url::parse(url).unwrap());
+        assert!(matches("https://api.example.com/"));
+        assert!(!matches("http://example.com/"));
+        assert!(!matches("https://badexample.com/"));
+    }
+
+    #[test]
+    fn sends_back_stored_cookies() {
+        let jar = CookieJar::new(Isolation::Operation);
+        let scope = jar.scope("POST", "/login");
+        assert_eq!(scope, "POST /login");
+        let login = request("http://localhost/login", Vec::new());
+        jar.store(
+            &scope,
+            &login,
+            &response(&["session=a; Path=/", "theme=dark; Path=/login"]),
+        );
+
+        let mut request = request(
+            "http://localhost/login",
+            vec![("cookie".to_string(), "session=generated".to_string())],
+        );
+        jar.apply(&scope, &mut request);
+        assert_eq!(request.headers[0].1, "session=generated; theme=dark");
+
+        let mut other = login.clone();
+        jar.apply(&jar.scope("GET", "/users"), &mut other);
+        assert!(other.headers.is_empty());
+
+        jar.store(
+            &scope,
+            &login,
+            &response(&["theme=light; Path=/login; Max-Age=0"]),
+        );
+        let mut request = login.clone();
+        jar.apply(&scope, &mut request);
+        assert_eq!(
+            request.headers,
+            vec![("Cookie".to_string(), "session=a".to_string())]
+        );
+        assert!(jar.take_changes().is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use arbitrary::{Arbitrary, Unstructured};
+use openapi_utils::ReferenceOrExt;
+use openapiv3::OpenAPI;
+use rand::{rngs::StdRng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::encryption::Encryption;
+use crate::payload::operations;
+
+/// Probability that a value from a seed is used as is and not replaced by a random one
+const KEEP_PROBABILITY: f64 = 0.8;
+
+/// Known-good values of a request used as a starting point for generating payloads
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Seed {
+    pub path_params: BTreeMap<String, String>,
+    pub query_params: BTreeMap<String, String>,
+    /// Header names are lowercase
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<serde_json::Value>,
+}
+
+impl Seed {
+    /// Returns the value of a parameter, unless it was chosen to be mutated
+    pub fn value(
+        params: &BTreeMap<String, String>,
+        name: &str,
+        rng: &mut StdRng,
+    ) -> Option<String> {
+        params
+            .get(name)
+            .filter(|_| rng.gen_bool(KEEP_PROBABILITY))
+            .cloned()
+    }
+
+    /// Returns the body with some of its leaf values replaced by random ones
+    pub fn mutated_body(
+        &self,
+        gen: &mut Unstructured,
+        rng: &mut StdRng,
+    ) -> Option<serde_json::Value> {
+        self.body.as_ref().map(|body| mutate_json(body, gen, rng))
+    }
+}
+
+fn mutate_json(
+    value: &serde_json::Value,
+    gen: &mut Unstructured,
+    rng: &mut StdRng,
+) -> serde_json::Value {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(name, value)| (name.clone(), mutate_json(value, gen, rng)))
+                .collect(),
+        ),
+        Value::Array(array) => Value::Array(
+            array
+                .iter()
+                .map(|value| mutate_json(value, gen, rng))
+                .collect(),
+        ),
+        _ if rng.gen_bool(KEEP_PROBABILITY) => value.clone(),
+        Value::String(_) | Value::Null => String::arbitrary(gen).unwrap_or_default().into(),
+        Value::Number(number) if number.is_f64() => f64::arbitrary(gen).unwrap_or_default().into(),
+        Value::Number(_) => i64::arbitrary(gen).unwrap_or_default().into(),
+        Value::Bool(_) => bool::arbitrary(gen).unwrap_or_default().into(),
+    }
+}
+
+/// Seed of an operation, as saved to corpus files with one entry per line
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CorpusEntry {
+    pub method: String,
+    pub path: String,
+    pub seed: Seed,
+}
+
+/// Seeds for each operation, keyed by method and path from the specification
+#[derive(Debug, Default)]
+pub struct Corpus {
+    seeds: BTreeMap<(String, String), Vec<Seed>>,
+}
+
+impl Corpus {
+    pub fn add(&mut self, method: &str, path: &str, seed: Seed) {
+        self.seeds
+            .entry((method.to_uppercase(), path.to_string()))
+            .or_default()
+            .push(seed);
+    }
+
+    /// All seeds with the methods and paths of their operations
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str, &Seed)> {
+        self.seeds.iter().flat_map(|((method, path), seeds)| {
+            seeds
+                .iter()
+                .map(move |seed| (method.as_str(), path.as_str(), seed))
+        })
+    }
+
+    /// Adds the seeds of the corpus file, decrypted if it is encrypted
+    pub fn load(&mut self, file: &Path, encryption: &Encryption) -> Result<()> {
+        let content = encryption.read_to_string(file)?;
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: CorpusEntry =
+                serde_json::from_str(line).context(format!("invalid seed on line {}", i + 1))?;
+            self.add(&entry.method, &entry.path, entry.seed);
+        }
+        Ok(())
+    }
+
+    /// Saves the corpus to the file, encrypted if enabled, the file should have the path given
+    /// by `Encryption::path`
+    pub fn save(&self, file: &Path, encryption: &Encryption) -> Result<()> {
+        let mut out = String::new();
+        for (method, path, seed) in self.entries() {
+            let entry = CorpusEntry {
+                method: method.to_string(),
+                path: path.to_string(),
+                seed: seed.clone(),
+            };
+            out.push_str(&serde_json::to_string(&entry)?);
+            out.push('\n');
+        }
+        encryption.write(file, out.as_bytes())
+    }
+
+    pub fn seeds(&self, method: &str, path: &str) -> &[Seed] {
+        self.seeds
+            .get(&(method.to_string(), path.to_string()))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+/// Matches a concrete request path to the path template of an operation and returns the
+/// template with the values of its path parameters. The request path may contain a base
+/// prefix, e.g. `/api/v1/items/1` matches `/items/{id}`.
+pub fn match_operation(
+    schema: &OpenAPI,
+    method: &str,
+    request_path: &str,
+) -> Option<(String, BTreeMap<String, String>)> {
+    let segments = request_path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+
+    schema
+        .paths
+        .iter()
+        .filter(|(_, item)| {
+            operations(item.to_item_ref())
+                .iter()
+                .any(|(m, operation)| m.eq_ignore_ascii_case(method) && operation.is_some())
+        })
+        .filter_map(|(template, _)| {
+            let template_segments = template
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>();
+            if template_segments.len() > segments.len() {
+                return None;
+            }
+
+            let mut path_params = BTreeMap::new();
+            let suffix = &segments[segments.len() - template_segments.len()..];
+            for (template_segment, segment) in template_segments.iter().zip(suffix) {
+                if template_segment.starts_with('{') && template_segment.ends_with('}') {
+                    let name = &template_segment[1..template_segment.len() - 1];
+                    let value = percent_encoding::percent_decode_str(segment).decode_utf8_lossy();
+                    path_params.insert(name.to_string(), value.into_owned());
+                } else if template_segment != segment {
+                    return None;
+                }
+            }
+            // Prefer the most specific template
+            let literals = template_segments.len() - path_params.len();
+            Some((
+                (literals, template_segments.len()),
+                template.clone(),
+                path_params,
+            ))
+        })
+        .max_by_key(|(specificity, _, _)| *specificity)
+        .map(|(_, template, path_params)| (template, path_params))
+}
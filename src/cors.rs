@@ -0,0 +1,85 @@
+use url::Url;
+
+use crate::transport::{Request, Response};
+
+/// Origins no API should trust, including one that passes a naive check of the API host
+fn hostile_origins(url: &Url) -> Vec<String> {
+    let mut origins = vec![
+        "https://openapi-fuzzer.example".to_string(),
+        "null".to_string(),
+    ];
+    if let Some(host) = url.host_str() {
+        origins.push(format!("https://{}.openapi-fuzzer.example", host));
+    }
+    origins
+}
+
+/// Preflight and simple requests with hostile origins, each with the origin it was sent with
+pub fn probes(request: &Request) -> Vec<(String, Request)> {
+    let mut probes = Vec::new();
+    for origin in hostile_origins(&request.url) {
+        let mut preflight_headers = vec![
+            ("Origin".to_string(), origin.clone()),
+            (
+                "Access-Control-Request-Method".to_string(),
+                request.method.clone(),
+            ),
+        ];
+        if !request.headers.is_empty() {
+            let names = request
+                .headers
+                .iter()
+                .map(|(name, _)| name.to_lowercase())
+                .collect::<Vec<_>>();
+            preflight_headers.push((
+                "Access-Control-Request-Headers".to_string(),
+                names.join(","),
+            ));
+        }
+        let preflight = Request {
+            method: "OPTIONS".to_string(),
+            url: request.url.clone(),
+            headers: preflight_headers,
+            body: None,
+            raw_body: None,
+            timeout: request.timeout,
+            stream: None,
+        };
+
+        let mut simple = request.clone();
+        simple
+            .headers
+            .retain(|(name, _)| !name.eq_ignore_ascii_case("origin"));
+        simple.headers.push(("Origin".to_string(), origin.clone()));
+
+        probes.push((origin.clone(), preflight));
+        probes.push((origin, simple));
+    }
+    probes
+}
+
+/// Returns the CORS misconfigurations of a response to a request with the origin
+pub fn misconfigurations(origin: &str, response: &Response) -> Vec<String> {
+    let allowed_origin = match response.header("access-control-allow-origin") {
+        Some(allowed_origin) => allowed_origin.trim(),
+        None => return Vec::new(),
+    };
+    let credentials = response
+        .header("access-control-allow-credentials")
+        .is_some_and(|credentials| credentials.trim().eq_ignore_ascii_case("true"));
+    let with_credentials = match credentials {
+        true => " with credentials allowed",
+        false => "",
+    };
+
+    if allowed_origin == "*" && credentials {
+        vec!["wildcard origin allowed with credentials".to_string()]
+    } else if allowed_origin == origin {
+        vec![format!(
+            "origin `{}` reflected in Access-Control-Allow-Origin{}",
+            origin, with_credentials
+        )]
+    } else {
+        Vec::new()
+    }
+}
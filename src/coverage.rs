@@ -0,0 +1,221 @@
+//! Coverage of the operations across runs, to prioritize what the earlier runs left out: the
+//! statuses each operation returned are kept in a file updated after each run, and the next
+//! run fuzzes the operations never answered with a success first and with more payloads per
+//! round, then the ones missing documented statuses, and the covered ones with fewer. Short
+//! runs, e.g. in CI, so converge on covering the whole API instead of fuzzing the same easy
+//! operations every time.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+use openapi_utils::ReferenceOrExt;
+use openapiv3::{OpenAPI, Operation, PathItem, ReferenceOr, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::config::OperationOverride;
+use crate::fuzzer::Stats;
+use crate::payload::operations;
+use crate::status::Operations;
+
+pub const COVERAGE_VERSION: u32 = 1;
+
+/// Payloads per round of the operations never answered with a success
+const UNCOVERED_WEIGHT: f64 = 4.0;
+
+/// Payloads per round of the operations missing documented statuses
+const PARTIAL_WEIGHT: f64 = 2.0;
+
+/// Payloads per round of the operations that returned all their documented statuses
+const COVERED_WEIGHT: f64 = 0.5;
+
+/// How well the operation was covered by the earlier runs, the least covered first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    Uncovered,
+    Partial,
+    Covered,
+}
+
+impl Priority {
+    fn weight(self) -> f64 {
+        match self {
+            Priority::Uncovered => UNCOVERED_WEIGHT,
+            Priority::Partial => PARTIAL_WEIGHT,
+            Priority::Covered => COVERED_WEIGHT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationCoverage {
+    pub method: String,
+    pub path: String,
+    pub requests: u64,
+    /// Responses of all runs by status code
+    pub statuses: BTreeMap<u16, u64>,
+    /// Documented statuses none of the runs got, e.g. `404` or `2XX`
+    pub missing: Vec<String>,
+}
+
+impl OperationCoverage {
+    fn priority(&self) -> Priority {
+        if !self.statuses.keys().any(|status| status / 100 == 2) {
+            Priority::Uncovered
+        } else if !self.missing.is_empty() {
+            Priority::Partial
+        } else {
+            Priority::Covered
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Coverage {
+    pub version: u32,
+    /// Runs recorded in the file
+    pub runs: u32,
+    pub operations: Vec<OperationCoverage>,
+}
+
+/// Documented statuses of the operation the statuses did not cover
+fn missing(operation: &Operation, statuses: &BTreeMap<u16, u64>) -> Vec<String> {
+    operation
+        .responses
+        .responses
+        .keys()
+        .filter(|documented| match documented {
+            StatusCode::Code(code) => !statuses.contains_key(code),
+            StatusCode::Range(range) => !statuses.keys().any(|status| status / 100 == *range),
+        })
+        .map(|documented| documented.to_string())
+        .collect()
+}
+
+impl Coverage {
+    /// Coverage of the earlier runs, empty if there were none
+    pub fn load(file: &Path) -> Result<Coverage> {
+        if !file.exists() {
+            return Ok(Coverage::default());
+        }
+        let coverage: Coverage = serde_json::from_slice(&fs::read(file)?)
+            .with_context(|| format!("invalid coverage file {}", file.display()))?;
+        if coverage.version > COVERAGE_VERSION {
+            return Err(anyhow!(
+                "the coverage file {} was written by a newer version",
+                file.display()
+            ));
+        }
+        Ok(coverage)
+    }
+
+    /// Orders the paths of the specification by the least covered of their operations and
+    /// returns the weights of the operations, to be applied before the options of the
+    /// operations from the specification and the config. Nothing changes without earlier runs.
+    pub fn prioritize(&self, schema: &mut OpenAPI) -> Vec<OperationOverride> {
+        if self.runs == 0 {
+            return Vec::new();
+        }
+        let known = self
+            .operations
+            .iter()
+            .map(|operation| {
+                (
+                    (operation.method.as_str(), operation.path.as_str()),
+                    operation.priority(),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        // Operations new to the specification were never fuzzed
+        let priority = |method: &str, path: &str| {
+            known
+                .get(&(method, path))
+                .copied()
+                .unwrap_or(Priority::Uncovered)
+        };
+        let of_path = |path: &str, item: &ReferenceOr<PathItem>| {
+            operations(item.to_item_ref())
+                .into_iter()
+                .filter(|(_, operation)| operation.is_some())
+                .map(|(method, _)| priority(method, path))
+                .min()
+                .unwrap_or(Priority::Covered)
+        };
+        schema
+            .paths
+            .sort_by(|a, a_item, b, b_item| of_path(a, a_item).cmp(&of_path(b, b_item)));
+
+        let mut overrides = Vec::new();
+        for (path, item) in schema.paths.iter() {
+            for (method, operation) in operations(item.to_item_ref()) {
+                if operation.is_none() {
+                    continue;
+                }
+                overrides.push(OperationOverride {
+                    operations: Operations::Endpoint(method.to_string(), path.clone()),
+                    header: Vec::new(),
+                    timeout: None,
+                    rate_limit: None,
+                    concurrency: None,
+                    ignore_status_code: Vec::new(),
+                    params: BTreeMap::new(),
+                    weight: Some(priority(method, path).weight()),
+                    server_error: None,
+                    body_template: None,
+                });
+            }
+        }
+        overrides
+    }
+
+    /// Adds the responses of the run to the operations of the specification, the operations of
+    /// the earlier runs left out by the run are kept
+    pub fn record(&mut self, schema: &OpenAPI, stats: &Stats) {
+        for (path, item) in schema.paths.iter() {
+            for (method, operation) in operations(item.to_item_ref()) {
+                let operation = match operation {
+                    Some(operation) => operation,
+                    None => continue,
+                };
+                let tries = stats
+                    .frequencies
+                    .get(path)
+                    .and_then(|methods| methods.get(method));
+                let position = self
+                    .operations
+                    .iter()
+                    .position(|covered| covered.method == method && &covered.path == path);
+                let covered = match position {
+                    Some(position) => &mut self.operations[position],
+                    None => {
+                        self.operations.push(OperationCoverage {
+                            method: method.to_string(),
+                            path: path.clone(),
+                            requests: 0,
+                            statuses: BTreeMap::new(),
+                            missing: Vec::new(),
+                        });
+                        self.operations.last_mut().unwrap()
+                    }
+                };
+                if let Some(tries) = tries {
+                    covered.requests += u64::from(tries.total);
+                    for (status, count) in tries.statuses.iter() {
+                        *covered.statuses.entry(*status).or_default() += u64::from(*count);
+                    }
+                }
+                covered.missing = missing(operation, &covered.statuses);
+            }
+        }
+        self.version = COVERAGE_VERSION;
+        self.runs += 1;
+    }
+
+    pub fn save(&self, file: &Path) -> Result<()> {
+        fs::write(file, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("unable to write {}", file.display()))
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+use tracing::{info, warn};
+
+use openapi_fuzzer::config::Config;
+use openapi_fuzzer::control::{Control, Status};
+use openapi_fuzzer::reporter::ReporterSpec;
+
+/// Job of the daemon, `run` fuzzes and returns the exceeded thresholds
+pub struct Job {
+    pub url: String,
+    pub run: Box<dyn FnOnce(Arc<Control>) -> Result<Vec<String>> + Send>,
+}
+
+struct Daemon<F> {
+    token: String,
+    /// Directory the paths of the configs of jobs are relative to
+    jobs_dir: PathBuf,
+    jobs: Mutex<BTreeMap<u64, Arc<Control>>>,
+    /// Prepares the job from its configuration
+    prepare: F,
+}
+
+pub fn json_response(status: u16, body: &impl Serialize) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_data(serde_json::to_vec(body).unwrap_or_default())
+        .with_status_code(status)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap())
+}
+
+pub fn error(status: u16, message: impl ToString) -> Response<std::io::Cursor<Vec<u8>>> {
+    json_response(status, &json!({ "error": message.to_string() }))
+}
+
+/// Whether the request has the bearer token, if there is one
+pub fn authorized(token: Option<&str>, request: &Request) -> bool {
+    let token = match token {
+        Some(token) => token,
+        None => return true,
+    };
+    request.headers().iter().any(|header| {
+        header.field.equiv("Authorization") && header.value.as_str() == format!("Bearer {}", token)
+    })
+}
+
+/// Path of the request split to segments, without the query
+pub fn segments(request: &Request) -> Vec<String> {
+    request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .trim_matches('/')
+        .split('/')
+        .map(String::from)
+        .collect()
+}
+
+/// Path relative to the jobs directory, paths leaving it are rejected
+fn confined(dir: &Path, path: &Path) -> Result<PathBuf> {
+    match path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        true => Ok(dir.join(path)),
+        false => Err(anyhow!(
+            "path `{}` is outside of the jobs directory",
+            path.display()
+        )),
+    }
+}
+
+/// Makes the paths of the config of a job relative to the jobs directory, so that clients
+/// read and write only files in it, and rejects the options running commands or containers
+fn confine(config: &mut Config, dir: &Path) -> Result<()> {
+    let commands = [
+        ("compose", config.compose.is_some()),
+        ("image", config.image.is_some()),
+        ("target-logs", config.target_logs.is_some()),
+        ("proxy-auth-command", config.proxy_auth_command.is_some()),
+        (
+            "auth.token-command",
+            config.auth.iter().any(|auth| auth.token_command.is_some()),
+        ),
+    ];
+    if let Some((option, _)) = commands.iter().find(|(_, set)| *set) {
+        return Err(anyhow!("`{}` is not supported in jobs", option));
+    }
+
+    let mut paths = config
+        .overlay
+        .iter_mut()
+        .chain(config.traffic.iter_mut())
+        .chain(config.scenario.iter_mut())
+        .chain(
+            vec![
+                &mut config.spec,
+                &mut config.header_policy,
+                &mut config.restore,
+                &mut config.headers_file,
+                &mut config.issue_template,
+                &mut config.export_har,
+                &mut config.export_burp,
+                &mut config.export_postman,
+                &mut config.export_csv,
+                &mut config.markdown_summary,
+                &mut config.coverage,
+                &mut config.cassette,
+                &mut config.database,
+                &mut config.age_identity,
+                &mut config.log_file,
+                &mut config.postman,
+                &mut config.postman_env,
+                &mut config.corpus,
+            ]
+            .into_iter()
+            .flat_map(Option::as_mut),
+        )
+        .collect::<Vec<_>>();
+    for reporter in config.reporter.iter_mut() {
+        if let ReporterSpec::Jsonl(path) | ReporterSpec::Sarif(path) = reporter {
+            paths.push(path);
+        }
+    }
+    for path in paths {
+        *path = confined(dir, path)?;
+    }
+    Ok(())
+}
+
+impl<F> Daemon<F>
+where
+    F: Fn(Config) -> Result<Job> + Send + Sync + 'static,
+{
+    fn job(&self, id: &str) -> Option<Arc<Control>> {
+        let id = id.parse().ok()?;
+        self.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    fn start(&self, body: Value) -> Result<Status> {
+        let mut config: Config = serde_json::from_value(body)?;
+        if !config.profiles.is_empty() {
+            return Err(anyhow!("profiles are not supported in jobs"));
+        }
+        confine(&mut config, &self.jobs_dir)?;
+        let job = (self.prepare)(config)?;
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = jobs.keys().next_back().map_or(1, |id| id + 1);
+        let control = Arc::new(Control::new(id, job.url));
+        jobs.insert(id, control.clone());
+        let run = job.run;
+        thread::spawn(move || {
+            info!(job = id, "job started");
+            let result = run(control.clone());
+            if let Err(e) = &result {
+                warn!(job = id, error = %format!("{:#}", e), "job failed");
+            }
+            control.end(result);
+            info!(job = id, "job ended");
+        });
+        Ok(jobs[&id].status())
+    }
+
+    /// Streams the findings of the job as JSON lines until it ends
+    fn stream_findings(request: Request, control: Arc<Control>) -> std::io::Result<()> {
+        let mut writer = request.into_writer();
+        write!(
+            writer,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nTransfer-Encoding: chunked\r\n\r\n"
+        )?;
+        writer.flush()?;
+        let mut seen = 0;
+        while let Some(findings) = control.wait_for_findings(seen) {
+            seen += findings.len();
+            for finding in findings {
+                let mut line = serde_json::to_vec(&finding)?;
+                line.push(b'\n');
+                write!(writer, "{:x}\r\n", line.len())?;
+                writer.write_all(&line)?;
+                write!(writer, "\r\n")?;
+            }
+            writer.flush()?;
+        }
+        write!(writer, "0\r\n\r\n")?;
+        writer.flush()
+    }
+
+    fn handle(&self, mut request: Request) -> std::io::Result<()> {
+        if !authorized(Some(&self.token), &request) {
+            return request.respond(error(401, "missing or invalid token"));
+        }
+        let segments = segments(&request);
+        let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut body = String::new();
+        if let Err(e) = request.as_reader().read_to_string(&mut body) {
+            return request.respond(error(400, e));
+        }
+
+        let response = match (request.method(), segments.as_slice()) {
+            (Method::Get, ["jobs"]) => {
+                let jobs = self.jobs.lock().unwrap();
+                json_response(200, &jobs.values().map(|c| c.status()).collect::<Vec<_>>())
+            }
+            (Method::Post, ["jobs"]) => match serde_json::from_str(&body)
+                .map_err(anyhow::Error::from)
+                .and_then(|body| self.start(body))
+            {
+                Ok(status) => json_response(201, &status),
+                Err(e) => error(400, format!("{:#}", e)),
+            },
+            (method, ["jobs", id, rest @ ..]) => {
+                let control = match self.job(id) {
+                    Some(control) => control,
+                    None => return request.respond(error(404, "unknown job")),
+                };
+                match (method, rest) {
+                    (Method::Get, []) => json_response(200, &control.status()),
+                    (Method::Post, ["stop"]) => {
+                        control.stop();
+                        json_response(202, &control.status())
+                    }
+                    (Method::Put, ["rate-limit"]) => {
+                        match serde_json::from_str::<Option<f64>>(&body) {
+                            Ok(rate_limit) => {
+                                control.set_rate_limit(rate_limit);
+                                json_response(200, &control.status())
+                            }
+                            Err(e) => error(400, e),
+                        }
+                    }
+                    (Method::Get, ["findings"]) => {
+                        return Self::stream_findings(request, control);
+                    }
+                    _ => error(404, "not found"),
+                }
+            }
+            _ => error(404, "not found"),
+        };
+        request.respond(response)
+    }
+}
+
+/// Serves the control API of the daemon, `prepare` turns the configuration of a new job
+/// (with the keys of the config file, its paths in `jobs_dir`) into the job
+pub fn serve<F>(address: SocketAddr, token: String, jobs_dir: PathBuf, prepare: F) -> Result<()>
+where
+    F: Fn(Config) -> Result<Job> + Send + Sync + 'static,
+{
+    let server =
+        Server::http(address).map_err(|e| anyhow!("unable to listen on {}: {}", address, e))?;
+    info!(%address, "daemon listening");
+    eprintln!("Listening on http://{}", address);
+    let daemon = Arc::new(Daemon {
+        token,
+        jobs_dir,
+        jobs: Mutex::new(BTreeMap::new()),
+        prepare,
+    });
+    for request in server.incoming_requests() {
+        let daemon = daemon.clone();
+        thread::spawn(move || {
+            if let Err(e) = daemon.handle(request) {
+                warn!(error = %e, "unable to respond");
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confines_paths_to_the_jobs_directory() {
+        let mut config: Config = serde_json::from_value(json!({
+            "spec": "api/openapi.yaml",
+            "reporter": ["jsonl:findings.jsonl"],
+        }))
+        .unwrap();
+        confine(&mut config, Path::new("jobs")).unwrap();
+        assert_eq!(config.spec, Some(PathBuf::from("jobs/api/openapi.yaml")));
+        assert!(matches!(
+            &config.reporter[0],
+            ReporterSpec::Jsonl(path) if path == Path::new("jobs/findings.jsonl")
+        ));
+
+        for config in [
+            json!({ "spec": "/etc/passwd" }),
+            json!({ "export-har": "../out.har" }),
+            json!({ "proxy-auth-command": "id" }),
+        ] {
+            let mut config: Config = serde_json::from_value(config).unwrap();
+            assert!(confine(&mut config, Path::new("jobs")).is_err());
+        }
+    }
+}
@@ -0,0 +1,659 @@
+//! SQLite database of the findings of all runs, deduplicated by their signature, so that a
+//! run tells the findings it has seen before from the new ones
+
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Context, Result};
+use rusqlite::{params, params_from_iter, types, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use url::Url;
+
+use crate::encryption::Encryption;
+use crate::finding::{Classification, Finding, FindingKind, Findings, RunInfo};
+use crate::fuzzer::Stats;
+use crate::report;
+
+/// Name of the database in the results directory, unless it is configured
+pub const DATABASE_FILE: &str = "findings.db";
+
+/// Version of the schema, stored as the `user_version` of the database
+const SCHEMA_VERSION: i64 = 2;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS findings (
+    signature TEXT PRIMARY KEY,
+    api TEXT NOT NULL,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    operation_id TEXT,
+    status INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    details TEXT NOT NULL,
+    file TEXT NOT NULL,
+    first_seen TEXT NOT NULL,
+    last_seen TEXT NOT NULL,
+    runs INTEGER NOT NULL,
+    reproduced INTEGER NOT NULL,
+    triage TEXT NOT NULL,
+    spec_hash TEXT NOT NULL,
+    run_seed INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS findings_triage ON findings (triage);
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    api TEXT NOT NULL,
+    finished TEXT NOT NULL,
+    fuzzer_version TEXT NOT NULL,
+    spec_hash TEXT NOT NULL,
+    requests INTEGER NOT NULL,
+    operations INTEGER NOT NULL,
+    covered INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS sightings (
+    run INTEGER NOT NULL REFERENCES runs (id),
+    signature TEXT NOT NULL REFERENCES findings (signature),
+    count INTEGER NOT NULL,
+    new INTEGER NOT NULL,
+    PRIMARY KEY (run, signature)
+);
+";
+
+/// Triage status of a finding
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Triage {
+    New,
+    Acknowledged,
+    /// Reopened as new when the finding is seen again
+    Fixed,
+}
+
+impl FromStr for Triage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "new" => Ok(Triage::New),
+            "acknowledged" | "ack" => Ok(Triage::Acknowledged),
+            "fixed" => Ok(Triage::Fixed),
+            _ => Err(format!(
+                "unknown triage status `{}`, expected new, acknowledged or fixed",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Triage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Triage::New => "new",
+            Triage::Acknowledged => "acknowledged",
+            Triage::Fixed => "fixed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Finding as it is recorded in the database
+#[derive(Debug, Serialize)]
+pub struct Recorded {
+    pub signature: String,
+    pub api: String,
+    pub method: String,
+    pub path: String,
+    pub operation_id: Option<String>,
+    pub status: u16,
+    pub kind: String,
+    pub severity: String,
+    /// Classification of the kind, the kinds of older versions have none
+    #[serde(flatten)]
+    pub classification: Option<Classification>,
+    pub details: Vec<String>,
+    /// File with the payload of the last run that saw it
+    pub file: String,
+    pub first_seen: String,
+    pub last_seen: String,
+    /// Number of runs that saw it
+    pub runs: u32,
+    /// Number of responses that triggered it in all runs
+    pub reproduced: u32,
+    pub triage: String,
+}
+
+/// Run as it is recorded in the database
+#[derive(Debug, Serialize)]
+pub struct RecordedRun {
+    pub id: i64,
+    pub api: String,
+    pub finished: String,
+    pub fuzzer_version: String,
+    pub spec_hash: String,
+    pub requests: u32,
+    pub operations: u32,
+    /// Operations that returned a documented response
+    pub covered: u32,
+}
+
+/// Finding seen in a run
+#[derive(Debug)]
+pub struct Sighting {
+    pub run: i64,
+    pub operation: String,
+    pub severity: String,
+    pub count: u32,
+    /// Seen for the first time, or again after it was fixed
+    pub new: bool,
+}
+
+/// How the findings of a run relate to the recorded ones
+#[derive(Debug, Default)]
+pub struct Recording {
+    pub new: usize,
+    pub known: usize,
+    /// Findings marked as fixed that were seen again
+    pub reopened: usize,
+}
+
+/// Signature the findings are deduplicated by across runs: the API, operation, status code and
+/// kind
+pub fn signature(api: &Url, finding: &Finding) -> String {
+    let key = format!(
+        "{}\n{}\n{}\n{}\n{}",
+        api, finding.method, finding.path, finding.status, finding.kind
+    );
+    ring::digest::digest(&ring::digest::SHA256, key.as_bytes())
+        .as_ref()
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn to_json(value: types::Value) -> Value {
+    match value {
+        types::Value::Null => Value::Null,
+        types::Value::Integer(i) => Value::from(i),
+        types::Value::Real(f) => Value::from(f),
+        types::Value::Text(s) => Value::String(s),
+        types::Value::Blob(bytes) => Value::from(bytes),
+    }
+}
+
+fn from_json(value: &Value) -> types::Value {
+    match value {
+        Value::Null | Value::Object(_) => types::Value::Null,
+        Value::Bool(b) => types::Value::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => types::Value::Integer(i),
+            None => types::Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => types::Value::Text(s.clone()),
+        Value::Array(bytes) => types::Value::Blob(
+            bytes
+                .iter()
+                .filter_map(|byte| byte.as_u64().map(|byte| byte as u8))
+                .collect(),
+        ),
+    }
+}
+
+/// Restores the database from its dump, columns of older versions keep their defaults
+fn restore(connection: &mut Connection, dump: &Value, file: &Path) -> Result<()> {
+    let version = dump["user_version"].as_i64().unwrap_or_default();
+    if version > SCHEMA_VERSION {
+        return Err(anyhow!(
+            "{} was created by a newer version of the fuzzer",
+            file.display()
+        ));
+    }
+    connection.execute_batch(SCHEMA)?;
+    let transaction = connection.transaction()?;
+    for table in TABLES {
+        let columns = dump["tables"][table]["columns"]
+            .as_array()
+            .map(|columns| columns.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+            .unwrap_or_default();
+        if columns.is_empty() {
+            continue;
+        }
+        let mut statement = transaction.prepare(&format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table,
+            columns.join(", "),
+            vec!["?"; columns.len()].join(", ")
+        ))?;
+        for row in dump["tables"][table]["rows"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let values = row.as_array().into_iter().flatten().map(from_json);
+            statement.execute(params_from_iter(values))?;
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Dump of the tables of the database, with the columns of each and its rows
+fn dump(connection: &Connection) -> Result<Value> {
+    let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    let mut tables = Map::new();
+    for table in TABLES {
+        let mut statement = connection.prepare(&format!("SELECT * FROM {}", table))?;
+        let columns = statement
+            .column_names()
+            .iter()
+            .map(|column| column.to_string())
+            .collect::<Vec<_>>();
+        let rows = statement
+            .query_map([], |row| {
+                (0..columns.len())
+                    .map(|i| row.get::<_, types::Value>(i).map(to_json))
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        tables.insert(table.to_string(), json!({"columns": columns, "rows": rows}));
+    }
+    Ok(json!({"user_version": version, "tables": tables}))
+}
+
+fn timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+/// Tables of the database, in the order they are restored from encrypted dumps
+const TABLES: &[&str] = &["findings", "runs", "sightings"];
+
+/// Encrypted dump of the database the in-memory database is restored from and saved to
+struct Encrypted {
+    file: PathBuf,
+}
+
+/// Lock of the dump, held while it is read or changed and saved, writers would overwrite each
+/// other
+fn lock(stored: &Path) -> Result<File> {
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(format!("{}.lock", stored.display()))?;
+    lock.lock()?;
+    Ok(lock)
+}
+
+/// In-memory database restored from the dump
+fn restored(stored: &Path, encryption: &Encryption) -> Result<Connection> {
+    let mut connection = Connection::open_in_memory()?;
+    let dumped = serde_json::from_slice(&encryption.read(stored)?)
+        .with_context(|| format!("{} is not a dump of the database", stored.display()))?;
+    restore(&mut connection, &dumped, stored)?;
+    Ok(connection)
+}
+
+pub struct Database {
+    connection: Connection,
+    encryption: Encryption,
+    encrypted: Option<Encrypted>,
+}
+
+impl Database {
+    /// Opens the database, it is created with its directory if it does not exist. With
+    /// encryption, or if an encrypted dump of it exists, the database is kept in memory and
+    /// only its encrypted dump is stored, next to the file with the extension of the
+    /// encryption.
+    pub fn open(file: &Path, encryption: &Encryption) -> Result<Database> {
+        if let Some(dir) = file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let name = file.display().to_string();
+        let stored = match encryption.enabled() {
+            true => Some(PathBuf::from(encryption.path(&name))),
+            false => ["age", "gpg"]
+                .iter()
+                .map(|extension| PathBuf::from(format!("{}.{}", name, extension)))
+                .find(|stored| stored.exists()),
+        };
+        let (connection, encrypted) = match stored {
+            Some(stored) => {
+                let _lock = lock(&stored)?;
+                let mut connection = Connection::open_in_memory()?;
+                if stored.exists() {
+                    connection = restored(&stored, encryption)?;
+                } else if file.exists() {
+                    tracing::warn!(file = %file.display(), "the database is still stored in plaintext, remove it once it is encrypted");
+                    let plaintext = Connection::open(file)
+                        .context(format!("unable to open {}", file.display()))?;
+                    restore(&mut connection, &dump(&plaintext)?, file)?;
+                }
+                (connection, Some(Encrypted { file: stored }))
+            }
+            None => {
+                let connection =
+                    Connection::open(file).context(format!("unable to open {}", file.display()))?;
+                // Daemon jobs and campaign targets may record at the same time
+                connection.busy_timeout(std::time::Duration::from_secs(10))?;
+                (connection, None)
+            }
+        };
+        let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(anyhow!(
+                "{} was created by a newer version of the fuzzer",
+                file.display()
+            ));
+        }
+        connection.execute_batch(SCHEMA)?;
+        connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Database {
+            connection,
+            encryption: encryption.clone(),
+            encrypted,
+        })
+    }
+
+    /// Encryption of the database, the files of the findings are read with
+    pub fn encryption(&self) -> &Encryption {
+        &self.encryption
+    }
+
+    /// Restores the latest dump of an encrypted database before it is changed, so the changes
+    /// saved by others since it was opened are kept, returns the lock held until it is saved
+    fn reload(&mut self) -> Result<Option<File>> {
+        let encrypted = match &self.encrypted {
+            Some(encrypted) => encrypted,
+            None => return Ok(None),
+        };
+        let lock = lock(&encrypted.file)?;
+        if encrypted.file.exists() {
+            let connection = restored(&encrypted.file, &self.encryption)?;
+            connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            self.connection = connection;
+        }
+        Ok(Some(lock))
+    }
+
+    /// Saves the encrypted dump of the database if it is encrypted
+    fn save(&self) -> Result<()> {
+        let encrypted = match &self.encrypted {
+            Some(encrypted) => encrypted,
+            None => return Ok(()),
+        };
+        if !self.encryption.enabled() {
+            return Err(anyhow!(
+                "{} is encrypted, give the recipients to encrypt it to",
+                encrypted.file.display()
+            ));
+        }
+        let dump = dump(&self.connection)?;
+        // The dump is replaced at once, an interrupted write leaves the previous one
+        let partial = PathBuf::from(format!("{}.partial", encrypted.file.display()));
+        self.encryption
+            .write(&partial, &serde_json::to_vec(&dump)?)?;
+        fs::rename(&partial, &encrypted.file)
+            .with_context(|| format!("unable to save {}", encrypted.file.display()))
+    }
+
+    /// Records the run against the API and its findings, findings marked as fixed are reopened
+    pub fn record(
+        &mut self,
+        api: &Url,
+        run: &RunInfo,
+        findings: &Findings,
+        stats: &Stats,
+        operations: usize,
+    ) -> Result<Recording> {
+        let mut recording = Recording::default();
+        let _lock = self.reload()?;
+        let now = timestamp(SystemTime::now());
+        let covered = stats
+            .frequencies
+            .values()
+            .flat_map(|methods| methods.values())
+            .filter(|tries| tries.documented > 0)
+            .count();
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO runs (api, finished, fuzzer_version, spec_hash, requests, operations,
+                 covered)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                api.as_str(),
+                now,
+                run.fuzzer_version,
+                run.spec_hash,
+                stats.total,
+                operations,
+                covered
+            ],
+        )?;
+        let run_id = transaction.last_insert_rowid();
+        for finding in findings.iter() {
+            let signature = signature(api, finding);
+            let triage: Option<String> = transaction
+                .query_row(
+                    "SELECT triage FROM findings WHERE signature = ?1",
+                    [&signature],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            match triage.as_deref() {
+                None => recording.new += 1,
+                Some("fixed") => recording.reopened += 1,
+                Some(_) => recording.known += 1,
+            }
+            let new = matches!(triage.as_deref(), None | Some("fixed"));
+            transaction.execute(
+                "INSERT INTO findings (signature, api, method, path, operation_id, status, kind,
+                     severity, details, file, first_seen, last_seen, runs, reproduced, triage,
+                     spec_hash, run_seed)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, 1, ?13, 'new', ?14, ?15)
+                 ON CONFLICT (signature) DO UPDATE SET
+                     operation_id = excluded.operation_id,
+                     severity = excluded.severity,
+                     details = excluded.details,
+                     file = excluded.file,
+                     last_seen = excluded.last_seen,
+                     runs = runs + 1,
+                     reproduced = reproduced + excluded.reproduced,
+                     triage = CASE triage WHEN 'fixed' THEN 'new' ELSE triage END,
+                     spec_hash = excluded.spec_hash,
+                     run_seed = excluded.run_seed",
+                params![
+                    signature,
+                    api.as_str(),
+                    finding.method,
+                    finding.path,
+                    finding.operation_id,
+                    finding.status,
+                    finding.kind.to_string(),
+                    finding.severity.to_string(),
+                    serde_json::to_string(&finding.details)?,
+                    finding.file,
+                    timestamp(finding.first_seen),
+                    now,
+                    finding.count,
+                    run.spec_hash,
+                    run.run_seed as i64,
+                ],
+            )?;
+            transaction.execute(
+                "INSERT INTO sightings (run, signature, count, new) VALUES (?1, ?2, ?3, ?4)",
+                params![run_id, signature, finding.count, new],
+            )?;
+        }
+        transaction.commit()?;
+        self.save()?;
+        Ok(recording)
+    }
+
+    /// Recorded findings, the most recently seen first, optionally only those in the status
+    pub fn findings(&self, triage: Option<Triage>) -> Result<Vec<Recorded>> {
+        let mut statement = self.connection.prepare(
+            "SELECT signature, api, method, path, operation_id, status, kind, severity, details,
+                 file, first_seen, last_seen, runs, reproduced, triage
+             FROM findings WHERE ?1 IS NULL OR triage = ?1
+             ORDER BY last_seen DESC, signature",
+        )?;
+        let rows = statement.query_map([triage.map(|triage| triage.to_string())], |row| {
+            let details: String = row.get(8)?;
+            let kind: String = row.get(6)?;
+            let classification = serde_json::from_value::<FindingKind>(kind.clone().into())
+                .ok()
+                .map(|kind| kind.classification());
+            Ok(Recorded {
+                signature: row.get(0)?,
+                api: row.get(1)?,
+                method: row.get(2)?,
+                path: row.get(3)?,
+                operation_id: row.get(4)?,
+                status: row.get(5)?,
+                kind,
+                severity: row.get(7)?,
+                classification,
+                details: serde_json::from_str(&details).unwrap_or_default(),
+                file: row.get(9)?,
+                first_seen: row.get(10)?,
+                last_seen: row.get(11)?,
+                runs: row.get(12)?,
+                reproduced: row.get(13)?,
+                triage: row.get(14)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// The last runs, optionally only those against the API, the oldest first
+    pub fn runs(&self, api: Option<&str>, last: usize) -> Result<Vec<RecordedRun>> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, api, finished, fuzzer_version, spec_hash, requests, operations, covered
+             FROM runs WHERE ?1 IS NULL OR api = ?1
+             ORDER BY id DESC LIMIT ?2",
+        )?;
+        let rows = statement.query_map(params![api, last as i64], |row| {
+            Ok(RecordedRun {
+                id: row.get(0)?,
+                api: row.get(1)?,
+                finished: row.get(2)?,
+                fuzzer_version: row.get(3)?,
+                spec_hash: row.get(4)?,
+                requests: row.get(5)?,
+                operations: row.get(6)?,
+                covered: row.get(7)?,
+            })
+        })?;
+        let mut runs = rows.collect::<Result<Vec<_>, _>>()?;
+        runs.reverse();
+        Ok(runs)
+    }
+
+    /// Findings seen in the runs since the one with the id
+    pub fn sightings(&self, since: i64) -> Result<Vec<Sighting>> {
+        let mut statement = self.connection.prepare(
+            "SELECT sightings.run, findings.method, findings.path, findings.operation_id,
+                 findings.severity, sightings.count, sightings.new
+             FROM sightings JOIN findings ON findings.signature = sightings.signature
+             WHERE sightings.run >= ?1",
+        )?;
+        let rows = statement.query_map([since], |row| {
+            let (method, path): (String, String) = (row.get(1)?, row.get(2)?);
+            let operation_id: Option<String> = row.get(3)?;
+            Ok(Sighting {
+                run: row.get(0)?,
+                operation: operation_id.unwrap_or_else(|| format!("{} {}", method, path)),
+                severity: row.get(4)?,
+                count: row.get(5)?,
+                new: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Sets the triage status of the findings with the signature or a prefix of it, returns the
+    /// number of findings changed
+    pub fn triage(&mut self, signature: &str, triage: Triage) -> Result<usize> {
+        if signature.is_empty() {
+            return Err(anyhow!("empty signature"));
+        }
+        let _lock = self.reload()?;
+        let matching: usize = self.connection.query_row(
+            "SELECT COUNT(*) FROM findings WHERE substr(signature, 1, length(?1)) = ?1",
+            [signature],
+            |row| row.get(0),
+        )?;
+        match matching {
+            0 => Err(anyhow!("no finding with the signature `{}`", signature)),
+            1 => {
+                let updated = self.connection.execute(
+                    "UPDATE findings SET triage = ?2 WHERE substr(signature, 1, length(?1)) = ?1",
+                    params![signature, triage.to_string()],
+                )?;
+                self.save()?;
+                Ok(updated)
+            }
+            _ => Err(anyhow!(
+                "{} findings have signatures starting with `{}`",
+                matching,
+                signature
+            )),
+        }
+    }
+}
+
+/// Prints the recorded findings as a table or as JSON
+pub fn print(findings: &[Recorded], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(findings)?);
+        return Ok(());
+    }
+
+    let rows = findings
+        .iter()
+        .map(|finding| {
+            vec![
+                finding.signature.clone(),
+                finding.triage.clone(),
+                finding.severity.clone(),
+                finding.kind.clone(),
+                finding
+                    .classification
+                    .and_then(|classification| classification.cwe)
+                    .unwrap_or("-")
+                    .to_string(),
+                finding.status.to_string(),
+                match &finding.operation_id {
+                    Some(operation_id) => operation_id.clone(),
+                    None => format!("{} {}", finding.method, finding.path),
+                },
+                finding.runs.to_string(),
+                finding.reproduced.to_string(),
+                finding.first_seen.clone(),
+                finding.last_seen.clone(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = [
+        "SIGNATURE",
+        "TRIAGE",
+        "SEVERITY",
+        "KIND",
+        "CWE",
+        "STATUS",
+        "OPERATION",
+        "RUNS",
+        "COUNT",
+        "FIRST SEEN",
+        "LAST SEEN",
+    ];
+    print!("{}", report::table(&header, &rows));
+    Ok(())
+}
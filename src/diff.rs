@@ -0,0 +1,200 @@
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::transport::Response;
+
+/// Differences reported for one pair of responses at most
+const MAX_DIFFERENCES: usize = 10;
+
+/// JSON pointer of values not compared, `*` matches any field or item, e.g. `/meta/requestId`
+/// or `/items/*/updatedAt`
+#[derive(Debug, Clone)]
+pub struct IgnorePath(Vec<String>);
+
+impl FromStr for IgnorePath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().strip_prefix('/') {
+            Some(pointer) => Ok(IgnorePath(pointer.split('/').map(String::from).collect())),
+            None => Err(format!(
+                "invalid path `{}`, expected a JSON pointer like `/items/*/updatedAt`",
+                s
+            )),
+        }
+    }
+}
+
+impl IgnorePath {
+    fn matches(&self, pointer: &str) -> bool {
+        let segments = pointer.strip_prefix('/').unwrap_or(pointer).split('/');
+        segments.clone().count() == self.0.len()
+            && segments
+                .zip(self.0.iter())
+                .all(|(segment, pattern)| pattern == "*" || segment == pattern)
+    }
+}
+
+/// Parts of the responses that differ on every response, e.g. timestamps and request ids
+#[derive(Debug, Clone, Default)]
+pub struct Ignored {
+    /// Names of fields ignored wherever they are
+    pub fields: Vec<String>,
+    pub paths: Vec<IgnorePath>,
+}
+
+impl Ignored {
+    fn contains(&self, pointer: &str, name: &str) -> bool {
+        self.fields.iter().any(|field| field == name)
+            || self.paths.iter().any(|path| path.matches(pointer))
+    }
+}
+
+/// JSON pointer of a value or `body` for the whole body
+fn location(pointer: &str) -> &str {
+    match pointer.is_empty() {
+        true => "body",
+        false => pointer,
+    }
+}
+
+fn json_differences(
+    first: &Value,
+    second: &Value,
+    pointer: &str,
+    ignored: &Ignored,
+    differences: &mut Vec<String>,
+) {
+    match (first, second) {
+        (Value::Object(first), Value::Object(second)) => {
+            for (name, value) in first {
+                let field_pointer = format!("{}/{}", pointer, name);
+                if ignored.contains(&field_pointer, name) {
+                    continue;
+                }
+                match second.get(name) {
+                    Some(other) => {
+                        json_differences(value, other, &field_pointer, ignored, differences)
+                    }
+                    None => differences.push(format!("{}: field removed", field_pointer)),
+                }
+            }
+            for name in second.keys() {
+                let field_pointer = format!("{}/{}", pointer, name);
+                if !first.contains_key(name) && !ignored.contains(&field_pointer, name) {
+                    differences.push(format!("{}: field added", field_pointer));
+                }
+            }
+        }
+        // Items are compared by position, the ones past the end of the other array were
+        // added or removed
+        (Value::Array(first), Value::Array(second)) => {
+            for i in 0..first.len().max(second.len()) {
+                let item_pointer = format!("{}/{}", pointer, i);
+                if ignored.paths.iter().any(|path| path.matches(&item_pointer)) {
+                    continue;
+                }
+                match (first.get(i), second.get(i)) {
+                    (Some(value), Some(other)) => {
+                        json_differences(value, other, &item_pointer, ignored, differences)
+                    }
+                    (Some(_), None) => differences.push(format!("{}: item removed", item_pointer)),
+                    (None, Some(_)) => differences.push(format!("{}: item added", item_pointer)),
+                    (None, None) => {}
+                }
+            }
+        }
+        (first, second) if first != second => differences.push(format!(
+            "{}: {} changed to {}",
+            location(pointer),
+            first,
+            second
+        )),
+        _ => {}
+    }
+}
+
+/// Returns the differences between two responses, JSON bodies are compared field by field
+/// except for the ignored ones
+pub fn differences(first: &Response, second: &Response, ignored: &Ignored) -> Vec<String> {
+    if first.status != second.status {
+        return vec![format!(
+            "status {} changed to {}",
+            first.status, second.status
+        )];
+    }
+
+    let mut differences = Vec::new();
+    match (
+        serde_json::from_str::<Value>(&first.body),
+        serde_json::from_str::<Value>(&second.body),
+    ) {
+        (Ok(first), Ok(second)) => json_differences(&first, &second, "", ignored, &mut differences),
+        _ if first.body != second.body => differences.push("body changed".to_string()),
+        _ => {}
+    }
+    differences.truncate(MAX_DIFFERENCES);
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn response(status: u16, body: &str) -> Response {
+        Response {
+            status,
+            status_text: String::new(),
+            http_version: "HTTP/1.1".to_string(),
+            headers: Vec::new(),
+            body: body.to_string(),
+            elapsed: Duration::default(),
+            events: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn matches_pointers_with_wildcards() {
+        let path = "/items/*/updatedAt".parse::<IgnorePath>().unwrap();
+        assert!(path.matches("/items/0/updatedAt"));
+        assert!(path.matches("/items/id/updatedAt"));
+        assert!(!path.matches("/items/0"));
+        assert!(!path.matches("/items/0/updatedAt/seconds"));
+        assert!(!path.matches("/orders/0/updatedAt"));
+        assert!("items/updatedAt".parse::<IgnorePath>().is_err());
+    }
+
+    #[test]
+    fn skips_ignored_fields_and_paths() {
+        let ignored = Ignored {
+            fields: vec!["requestId".to_string()],
+            paths: vec!["/items/*/updatedAt".parse().unwrap()],
+        };
+        let first = response(
+            200,
+            r#"{"requestId": 1, "items": [{"id": 1, "updatedAt": 1}], "total": 1}"#,
+        );
+        let second = response(
+            200,
+            r#"{"requestId": 2, "items": [{"id": 1, "updatedAt": 2}, {"id": 2}], "count": 2}"#,
+        );
+        assert_eq!(
+            differences(&first, &second, &ignored),
+            vec![
+                "/items/1: item added".to_string(),
+                "/total: field removed".to_string(),
+                "/count: field added".to_string(),
+            ]
+        );
+        assert_eq!(
+            differences(&first, &response(500, ""), &ignored),
+            vec!["status 200 changed to 500".to_string()]
+        );
+        assert_eq!(
+            differences(&response(200, "a"), &response(200, "b"), &ignored),
+            vec!["body changed".to_string()]
+        );
+    }
+}
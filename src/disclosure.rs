@@ -0,0 +1,118 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::transport::Response;
+
+/// Characters of the response around the leaked detail included in the finding
+const CONTEXT_LENGTH: usize = 60;
+
+/// Headers naming the software of the server whatever their value
+const DISCLOSING_HEADERS: [&str; 5] = [
+    "x-powered-by",
+    "x-aspnet-version",
+    "x-aspnetmvc-version",
+    "x-generator",
+    "x-runtime",
+];
+
+/// Kinds of leaked implementation details with patterns matching them
+const PATTERNS: &[(&str, &str)] = &[
+    (
+        "SQL error",
+        r#"(?i)(you have an error in your sql syntax|syntax error at or near|unclosed quotation mark|sqlstate\[|ORA-\d{5}|PG::\w+Error|sqlite3?\.\w*Error|SQLITE_ERROR|psycopg2\.\w+|java\.sql\.SQLException|mysql_fetch|near "[^"]*": syntax error)"#,
+    ),
+    (
+        "stack trace",
+        r"(Traceback \(most recent call last\)|\bat [\w$.]+\([\w$]+\.(java|kt|scala):\d+\)|\bat [\w.<>]+ in [^\n]+:line \d+|\bat (Object\.)?[\w.<>]+ \(/[^)]+:\d+:\d+\)|goroutine \d+ \[running\]|\.rb:\d+:in `|Fatal error: .+ on line \d+|panicked at )",
+    ),
+    (
+        "file path",
+        r#"((/var/www|/home/[\w.-]+|/usr/(local/)?lib/(python|ruby|node)[\w./-]*|/srv/[\w.-]+|/opt/[\w.-]+)/[\w./-]+|[A-Z]:\\(inetpub|Users|Windows|Program Files)\\[^\s"'<]+|File "[^"]+\.py", line \d+)"#,
+    ),
+    (
+        "software version",
+        r"(?i)\b(apache|nginx|php|express|werkzeug|gunicorn|jetty|tomcat|iis|kestrel|django|rails|spring boot)[/ ]v?\d+\.\d+(\.\d+)?",
+    ),
+];
+
+fn patterns() -> &'static [(&'static str, Regex)] {
+    static PATTERNS_CELL: OnceLock<Vec<(&str, Regex)>> = OnceLock::new();
+    PATTERNS_CELL.get_or_init(|| {
+        PATTERNS
+            .iter()
+            .map(|(kind, pattern)| (*kind, Regex::new(pattern).expect("invalid pattern")))
+            .collect()
+    })
+}
+
+/// Returns the match with the surrounding context
+fn snippet(text: &str, start: usize, end: usize) -> String {
+    let from = text[..start]
+        .char_indices()
+        .rev()
+        .nth(CONTEXT_LENGTH)
+        .map_or(0, |(i, _)| i);
+    let to = text[end..]
+        .char_indices()
+        .nth(CONTEXT_LENGTH)
+        .map_or(text.len(), |(i, _)| end + i);
+    text[from..to]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether the value has a version number, e.g. `nginx/1.25.3`
+fn has_version(value: &str) -> bool {
+    let chars = value.chars().collect::<Vec<_>>();
+    chars
+        .windows(3)
+        .any(|w| w[0].is_ascii_digit() && w[1] == '.' && w[2].is_ascii_digit())
+}
+
+/// Headers of any response disclosing the software of the server, by name with their value:
+/// the headers naming it whatever their value, and the `server` header with a version, as
+/// most servers name themselves
+pub fn disclosing_headers(response: &Response) -> Vec<(&'static str, &str)> {
+    let server = response
+        .header("server")
+        .filter(|server| has_version(server))
+        .map(|server| ("server", server));
+    server
+        .into_iter()
+        .chain(
+            DISCLOSING_HEADERS
+                .iter()
+                .filter_map(|name| response.header(name).map(|value| (*name, value))),
+        )
+        .collect()
+}
+
+/// Returns implementation details leaked by an error response, each with its snippet
+pub fn leaks(response: &Response) -> Vec<String> {
+    if response.status < 400 {
+        return Vec::new();
+    }
+
+    let headers = std::iter::once("server")
+        .chain(DISCLOSING_HEADERS)
+        .filter_map(|name| response.header(name).map(|value| (name, value)))
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut leaks = Vec::new();
+    for (kind, pattern) in patterns() {
+        if let Some(m) = pattern.find(&response.body) {
+            leaks.push(format!(
+                "{} in body: `{}`",
+                kind,
+                snippet(&response.body, m.start(), m.end())
+            ));
+        } else if let Some(m) = pattern.find(&headers) {
+            leaks.push(format!("{} in headers: `{}`", kind, m.as_str()));
+        }
+    }
+    leaks
+}
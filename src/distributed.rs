@@ -0,0 +1,522 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tiny_http::{Method, Request, Server};
+use tracing::{info, warn};
+use url::Url;
+
+use openapi_fuzzer::config::{self, Config};
+use openapi_fuzzer::corpus::Seed;
+use openapi_fuzzer::encryption::Encryption;
+use openapi_fuzzer::finding::{Finding, FindingKind, Verification};
+use openapi_fuzzer::fuzzer::{results_path, Budget, Fuzzer, FuzzerBuilder, Stats};
+use openapi_fuzzer::{tls, transport};
+
+use crate::daemon::{authorized, error, json_response, segments};
+use crate::{prepare, Outputs};
+
+/// Seeds of each operation the coordinator keeps and hands out with batches
+const MAX_SEEDS: usize = 10;
+
+/// Batches not reported in this time are given up, their operations are fuzzed again in
+/// later batches anyway
+const BATCH_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Options of the config file the workers set up themselves
+const WORKER_KEYS: &[&str] = &[
+    "spec",
+    "overlay",
+    "webhook",
+    "webhook-findings",
+    "reporter",
+    "encrypt-to",
+    "age-identity",
+    "seed",
+    "snapshot-every",
+    "restore",
+];
+
+/// Method and path of an operation
+type Operation = (String, String);
+
+/// What the workers fuzz, sent to every worker when it joins
+#[derive(Debug, Serialize, Deserialize)]
+struct Campaign {
+    /// Content of the config file, with its profiles, references to environment variables
+    /// are resolved by the workers
+    config: Value,
+    profile: Option<String>,
+    /// Content of the specification
+    spec: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Batch {
+    id: u64,
+    operations: Vec<Operation>,
+    /// Number of requests sent to each operation
+    requests: u32,
+    seeds: Vec<(String, String, Seed)>,
+}
+
+/// Finding of a worker with the record of the payload that triggered it
+#[derive(Debug, Serialize, Deserialize)]
+struct Reported {
+    path: String,
+    method: String,
+    operation_id: Option<String>,
+    status: u16,
+    kind: FindingKind,
+    details: Vec<String>,
+    count: u32,
+    #[serde(default)]
+    verification: Option<Verification>,
+    request: transport::Request,
+    response: transport::Response,
+    #[serde(default)]
+    minimized: Option<transport::Request>,
+    record: Option<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BatchResult {
+    stats: Stats,
+    findings: Vec<Reported>,
+    /// Payloads the API accepted, shared with the other workers
+    seeds: Vec<(String, String, Seed)>,
+}
+
+/// Options of the coordinator
+pub struct Options {
+    pub listen: SocketAddr,
+    pub token: Option<String>,
+    /// Number of operations in a batch
+    pub batch_size: usize,
+    /// Number of requests sent to each operation of a batch
+    pub batch_requests: u32,
+    pub max_requests: Option<u32>,
+    pub max_duration: Option<Duration>,
+    pub config: Value,
+    pub profile: Option<String>,
+    pub spec: String,
+    /// Operations of the specification
+    pub operations: Vec<Operation>,
+}
+
+struct State {
+    /// Collects the findings and stats of the workers, it does not fuzz itself
+    fuzzer: Fuzzer,
+    next_batch: u64,
+    next_operation: usize,
+    /// Batches handed out and not reported yet, with the time they were handed out at
+    outstanding: BTreeMap<u64, Instant>,
+    seeds: BTreeMap<Operation, Vec<Seed>>,
+    done: bool,
+}
+
+struct Coordinator {
+    options: Options,
+    campaign: Value,
+    start: Instant,
+    state: Mutex<State>,
+}
+
+impl Coordinator {
+    fn exhausted(&self, state: &State) -> bool {
+        self.options
+            .max_requests
+            .is_some_and(|max| state.fuzzer.stats().total >= max)
+            || self
+                .options
+                .max_duration
+                .is_some_and(|max| self.start.elapsed() >= max)
+    }
+
+    /// Hands out the next operations in turn, `None` when the budget is exhausted
+    fn batch(&self) -> Option<Batch> {
+        let mut state = self.state.lock().unwrap();
+        if state.done || self.exhausted(&state) {
+            state.done = true;
+            return None;
+        }
+        let operations = &self.options.operations;
+        let count = self.options.batch_size.clamp(1, operations.len());
+        let chosen = (0..count)
+            .map(|i| operations[(state.next_operation + i) % operations.len()].clone())
+            .collect::<Vec<_>>();
+        state.next_operation = (state.next_operation + count) % operations.len();
+        state.next_batch += 1;
+        let id = state.next_batch;
+        state.outstanding.insert(id, Instant::now());
+        let seeds = chosen
+            .iter()
+            .flat_map(|operation| {
+                state
+                    .seeds
+                    .get(operation)
+                    .into_iter()
+                    .flatten()
+                    .map(move |seed| (operation.0.clone(), operation.1.clone(), seed.clone()))
+            })
+            .collect();
+        info!(batch = id, operations = chosen.len(), "batch handed out");
+        Some(Batch {
+            id,
+            operations: chosen,
+            requests: self.options.batch_requests,
+            seeds,
+        })
+    }
+
+    /// Merges the result of the batch, returns whether the campaign is done
+    fn report(&self, id: &str, result: BatchResult) -> Result<bool> {
+        let mut state = self.state.lock().unwrap();
+        let id = id.parse::<u64>().ok();
+        if id.and_then(|id| state.outstanding.remove(&id)).is_none() {
+            return Err(anyhow!("unknown batch"));
+        }
+        let known = |method: &str, path: &str| {
+            self.options
+                .operations
+                .iter()
+                .any(|(m, p)| m == method && p == path)
+        };
+        if let Some(reported) = result.findings.iter().find(|r| !known(&r.method, &r.path)) {
+            return Err(anyhow!(
+                "unknown operation {} {}",
+                reported.method,
+                reported.path
+            ));
+        }
+
+        state.fuzzer.merge_stats(&result.stats);
+        for reported in result.findings {
+            let file = save_record(&reported, state.fuzzer.encryption())?;
+            state.fuzzer.merge(Finding {
+                path: reported.path,
+                method: reported.method,
+                operation_id: reported.operation_id,
+                status: reported.status,
+                kind: reported.kind,
+                severity: reported.kind.severity(),
+                classification: reported.kind.classification(),
+                details: reported.details,
+                file,
+                count: reported.count,
+                first_seen: SystemTime::now(),
+                verification: reported.verification,
+                request: reported.request,
+                response: reported.response,
+                minimized: reported.minimized,
+            })?;
+        }
+        for (method, path, seed) in result.seeds {
+            if !known(&method, &path) {
+                continue;
+            }
+            let seeds = state.seeds.entry((method, path)).or_default();
+            if seeds.len() < MAX_SEEDS {
+                seeds.push(seed);
+            }
+        }
+        info!(
+            batch = id,
+            requests = state.fuzzer.stats().total,
+            findings = state.fuzzer.findings().len(),
+            "batch reported"
+        );
+        Ok(state.done || self.exhausted(&state))
+    }
+
+    fn handle(&self, mut request: Request) -> std::io::Result<()> {
+        if !authorized(self.options.token.as_deref(), &request) {
+            return request.respond(error(401, "missing or invalid token"));
+        }
+        let segments = segments(&request);
+        let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut body = String::new();
+        if let Err(e) = request.as_reader().read_to_string(&mut body) {
+            return request.respond(error(400, e));
+        }
+
+        let response = match (request.method(), segments.as_slice()) {
+            (Method::Get, ["campaign"]) => json_response(200, &self.campaign),
+            (Method::Post, ["batches"]) => match self.batch() {
+                Some(batch) => json_response(200, &batch),
+                None => json_response(204, &()),
+            },
+            (Method::Post, ["batches", id]) => match serde_json::from_str(&body)
+                .map_err(anyhow::Error::from)
+                .and_then(|result| self.report(id, result))
+            {
+                Ok(done) => json_response(200, &json!({ "done": done })),
+                Err(e) => error(400, format!("{:#}", e)),
+            },
+            _ => error(404, "not found"),
+        };
+        request.respond(response)
+    }
+
+    /// Whether the budget is exhausted and all batches are reported or given up
+    fn finished(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.outstanding.retain(|id, handed_out| {
+            let expired = handed_out.elapsed() >= BATCH_TIMEOUT;
+            if expired {
+                warn!(batch = id, "batch given up");
+            }
+            !expired
+        });
+        state.done |= self.exhausted(&state);
+        state.done && state.outstanding.is_empty()
+    }
+}
+
+/// Saves the record of the finding to the results directory like the fuzzer does, the path
+/// is made from the known operation and never taken from the worker
+fn save_record(reported: &Reported, encryption: &Encryption) -> Result<String> {
+    let record = match &reported.record {
+        Some(record) => record,
+        None => return Ok(String::new()),
+    };
+    let results_dir = results_path(
+        "results",
+        &reported.path,
+        &reported.method,
+        reported.status,
+        reported.kind,
+    );
+    let results_file =
+        encryption.path(&format!("{}/{:x}.json", results_dir, rand::random::<u32>()));
+    fs::create_dir_all(&results_dir)?;
+    encryption.write(
+        Path::new(&results_file),
+        &serde_json::to_vec_pretty(record)?,
+    )?;
+    Ok(results_file)
+}
+
+/// Hands out batches of operations to workers until the budget is exhausted and collects
+/// their findings, returns the reasons why the campaign should fail
+pub fn coordinate(
+    options: Options,
+    builder: FuzzerBuilder,
+    outputs: Outputs,
+) -> Result<Vec<String>> {
+    if options.max_requests.is_none() && options.max_duration.is_none() {
+        return Err(anyhow!(
+            "The coordinator needs a budget, set max-requests or max-duration"
+        ));
+    }
+    if options.operations.is_empty() {
+        return Err(anyhow!("The specification has no operations"));
+    }
+    if options.token.is_none() && !options.listen.ip().is_loopback() {
+        return Err(anyhow!(
+            "The coordinator needs a --token to listen on {}",
+            options.listen
+        ));
+    }
+    let server = Server::http(options.listen)
+        .map_err(|e| anyhow!("unable to listen on {}: {}", options.listen, e))?;
+    info!(address = %options.listen, "coordinator listening");
+    eprintln!("Listening on http://{}", options.listen);
+    let campaign = serde_json::to_value(Campaign {
+        config: options.config.clone(),
+        profile: options.profile.clone(),
+        spec: options.spec.clone(),
+    })?;
+    let coordinator = Arc::new(Coordinator {
+        options,
+        campaign,
+        start: Instant::now(),
+        state: Mutex::new(State {
+            fuzzer: builder.build(),
+            next_batch: 0,
+            next_operation: 0,
+            outstanding: BTreeMap::new(),
+            seeds: BTreeMap::new(),
+            done: false,
+        }),
+    });
+
+    while !coordinator.finished() {
+        let request = match server.recv_timeout(Duration::from_secs(1))? {
+            Some(request) => request,
+            None => continue,
+        };
+        let coordinator = coordinator.clone();
+        thread::spawn(move || {
+            if let Err(e) = coordinator.handle(request) {
+                warn!(error = %e, "unable to respond");
+            }
+        });
+    }
+
+    let mut state = coordinator.state.lock().unwrap();
+    eprintln!(
+        "{} requests, {} findings",
+        state.fuzzer.stats().total,
+        state.fuzzer.findings().len()
+    );
+    state.fuzzer.finish()?;
+    outputs.write(&state.fuzzer)
+}
+
+/// Client of the coordinator
+struct Client {
+    url: Url,
+    token: Option<String>,
+}
+
+impl Client {
+    fn request(&self, method: &str, path: &str) -> Result<ureq::Request> {
+        let mut request = tls::agent().request_url(method, &self.url.join(path)?);
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        Ok(request)
+    }
+}
+
+/// Configurations of the campaign as the worker uses them, the first ones take precedence
+fn configs(campaign: &Campaign, spec: &Path) -> Result<Vec<Config>> {
+    let mut value = campaign.config.clone();
+    if let Some(profiles) = value.get_mut("profiles").and_then(Value::as_object_mut) {
+        for profile in profiles.values_mut().filter_map(Value::as_object_mut) {
+            for key in WORKER_KEYS {
+                profile.remove(*key);
+            }
+        }
+    }
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("invalid config of the campaign"))?;
+    for key in WORKER_KEYS {
+        object.remove(*key);
+    }
+    object.insert("spec".to_string(), json!(spec));
+    config::interpolate_all(&mut value, "").context("unable to interpolate the campaign")?;
+
+    let mut config: Config = serde_json::from_value(value)?;
+    let mut configs = Vec::new();
+    if let Some(name) = &campaign.profile {
+        configs.push(config.profile(name)?);
+    }
+    configs.push(config);
+    Ok(configs)
+}
+
+/// Fuzzes the operations of the batch and collects what it found, the fuzzer goes on with the
+/// next batch so that the readiness check, the warm-up and the probes are done once
+fn run_batch(fuzzer: &mut Fuzzer, batch: Batch) -> Result<BatchResult> {
+    for (method, path, seed) in batch.seeds {
+        fuzzer.add_seed(&method, &path, seed);
+    }
+    fuzzer.select(
+        &batch.operations,
+        Budget {
+            max_requests_per_operation: Some(batch.requests),
+            ..Budget::default()
+        },
+    );
+    fuzzer.run_batch()?;
+
+    let (stats, found) = fuzzer.take_results();
+    let mut findings = Vec::new();
+    for finding in found.iter() {
+        let record = fs::read(&finding.file)
+            .ok()
+            .and_then(|record| serde_json::from_slice(&record).ok());
+        findings.push(Reported {
+            path: finding.path.clone(),
+            method: finding.method.clone(),
+            operation_id: finding.operation_id.clone(),
+            status: finding.status,
+            kind: finding.kind,
+            details: finding.details.clone(),
+            count: finding.count,
+            verification: finding.verification.clone(),
+            request: finding.request.clone(),
+            response: finding.response.clone(),
+            minimized: finding.minimized.clone(),
+            record,
+        });
+    }
+    let seeds = fuzzer.take_seeds();
+    Ok(BatchResult {
+        stats,
+        findings,
+        seeds,
+    })
+}
+
+/// Pulls batches from the coordinator and fuzzes them until the campaign is done
+pub fn work(url: Url, token: Option<String>) -> Result<()> {
+    let client = Client { url, token };
+    let campaign: Campaign = client
+        .request("GET", "campaign")?
+        .call()
+        .context("Failed to join the campaign")?
+        .into_json()?;
+    let work_dir =
+        std::env::temp_dir().join(format!("openapi-fuzzer-worker-{}", std::process::id()));
+    fs::create_dir_all(&work_dir)?;
+    let spec = work_dir.join("spec.yaml");
+    fs::write(&spec, &campaign.spec)?;
+    let results_dir: PathBuf = work_dir.join("results");
+
+    let result = (|| -> Result<()> {
+        let (builder, _) = prepare(configs(&campaign, &spec)?)?;
+        let mut fuzzer = builder
+            .results_dir(results_dir.to_string_lossy())
+            // Teardown requests are sent once by the coordinator
+            .teardown(Vec::new())
+            .learn_seeds()
+            .build();
+        loop {
+            let response = client
+                .request("POST", "batches")?
+                .call()
+                .context("Failed to get a batch")?;
+            if response.status() == 204 {
+                return fuzzer.finish();
+            }
+            let batch: Batch = response.into_json()?;
+            let id = batch.id;
+            info!(
+                batch = id,
+                operations = batch.operations.len(),
+                "batch started"
+            );
+            let result =
+                run_batch(&mut fuzzer, batch).context(format!("Failed to fuzz batch {}", id))?;
+            eprintln!(
+                "batch {}: {} requests, {} findings",
+                id,
+                result.stats.total,
+                result.findings.len()
+            );
+            let done: Value = client
+                .request("POST", &format!("batches/{}", id))?
+                .send_json(serde_json::to_value(result)?)
+                .context(format!("Failed to report batch {}", id))?
+                .into_json()?;
+            if done["done"].as_bool().unwrap_or_default() {
+                return fuzzer.finish();
+            }
+        }
+    })();
+    let _ = fs::remove_dir_all(&work_dir);
+    result
+}
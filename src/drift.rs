@@ -0,0 +1,421 @@
+//! Drift of the live service from its specification: status codes it answers with that are
+//! not declared, fields of the responses missing from the schemas, and methods and paths it
+//! responds on that are not in the specification, found by probing the variations APIs
+//! commonly grow: other versions, actions on collections and internal endpoints. Methods are
+//! probed with safe requests and read from the `Allow` header of `OPTIONS` unless the unsafe
+//! ones are asked for, and each probe is compared with a request of the same method to a path
+//! that cannot exist.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use openapi_utils::ReferenceOrExt;
+use openapiv3::{OpenAPI, Responses};
+use serde::Serialize;
+use url::Url;
+
+use crate::payload::operations;
+use crate::report;
+use crate::transport::{Request, Response};
+use crate::validate::{self, Violation};
+
+/// Methods probed on the paths of the specification that do not declare them
+const SAFE_METHODS: &[&str] = &["GET", "HEAD"];
+
+/// Methods changing state, only probed when asked for
+const UNSAFE_METHODS: &[&str] = &["POST", "PUT", "PATCH", "DELETE"];
+
+/// Actions probed on the collections of the specification
+const ACTIONS: &[&str] = &["search", "count", "export", "bulk", "all", "admin", "debug"];
+
+/// Endpoints probed at the base of the API, often left out of the specification
+const INTERNAL: &[&str] = &[
+    "admin",
+    "debug",
+    "internal",
+    "metrics",
+    "health",
+    "status",
+    "env",
+    "config",
+    "actuator",
+    "actuator/env",
+    "graphql",
+    "console",
+    "swagger.json",
+    "openapi.json",
+    "v2/api-docs",
+    "server-status",
+];
+
+/// Statuses of requests to methods and paths that do not exist
+const MISSING: &[u16] = &[404, 405, 501];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DriftKind {
+    /// Response with a status code the operation does not declare
+    UndeclaredStatus,
+    /// Field of a response missing from the schema
+    UndeclaredField,
+    /// Method answered on a path of the specification that does not declare it
+    UndocumentedMethod,
+    /// Path answered that is not in the specification
+    UndocumentedPath,
+}
+
+impl DriftKind {
+    fn name(self) -> &'static str {
+        match self {
+            DriftKind::UndeclaredStatus => "undeclared-status",
+            DriftKind::UndeclaredField => "undeclared-field",
+            DriftKind::UndocumentedMethod => "undocumented-method",
+            DriftKind::UndocumentedPath => "undocumented-path",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Drift {
+    pub kind: DriftKind,
+    pub method: String,
+    /// Path of the operation, or the path probed
+    pub path: String,
+    pub status: u16,
+    pub detail: String,
+}
+
+/// Drift of the response from the responses the operation declares
+pub fn of_response(
+    method: &str,
+    path: &str,
+    responses: &Responses,
+    response: &Response,
+) -> Vec<Drift> {
+    validate::validate_response(responses, response)
+        .into_iter()
+        .filter_map(|violation| {
+            let kind = match violation {
+                Violation::UndeclaredStatus(_) => DriftKind::UndeclaredStatus,
+                Violation::UndocumentedField(_) => DriftKind::UndeclaredField,
+                Violation::Invalid(_) => return None,
+            };
+            Some(Drift {
+                kind,
+                method: method.to_string(),
+                path: path.to_string(),
+                status: response.status,
+                detail: violation.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Whether the path matches one of the paths of the specification, parameters match any
+/// segment
+fn documented(schema: &OpenAPI, path: &str) -> bool {
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    schema.paths.keys().any(|documented| {
+        let documented = documented.trim_matches('/').split('/').collect::<Vec<_>>();
+        documented.len() == segments.len()
+            && documented
+                .iter()
+                .zip(segments.iter())
+                .all(|(documented, segment)| documented.starts_with('{') || documented == segment)
+    })
+}
+
+/// Request probing undocumented behavior
+pub struct Probe {
+    pub kind: DriftKind,
+    /// Path shown for the probe, the path of the specification for methods
+    pub path: String,
+    pub request: Request,
+    /// Methods the path declares, for `OPTIONS` probes whose `Allow` header lists the methods
+    pub declared: Option<Vec<&'static str>>,
+}
+
+/// Other versions of a path with a version segment, e.g. `/v2/items` for `/v1/items`
+fn versions(path: &str) -> Vec<String> {
+    let segments = path.split('/').collect::<Vec<_>>();
+    let mut versions = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let version = match segment
+            .strip_prefix('v')
+            .and_then(|v| v.parse::<u32>().ok())
+        {
+            Some(version) => version,
+            None => continue,
+        };
+        for other in version.checked_sub(1).into_iter().chain(Some(version + 1)) {
+            let mut changed = segments.clone();
+            let other = format!("v{}", other);
+            changed[i] = &other;
+            versions.push(changed.join("/"));
+        }
+    }
+    versions
+}
+
+/// Probes of methods and paths not in the specification, from one sent request of each path
+/// of the specification with its path, with the methods changing state if `unsafe_methods`.
+/// The requests keep the headers of the sent ones.
+pub fn probes(
+    schema: &OpenAPI,
+    base: &Url,
+    sent: &[(String, Request)],
+    unsafe_methods: bool,
+) -> Vec<Probe> {
+    let mut probes = Vec::new();
+    let mut probed = BTreeSet::new();
+    let mut probe_path = |probes: &mut Vec<Probe>, template: &Request, path: String| {
+        if documented(schema, &path) || !probed.insert(path.clone()) {
+            return;
+        }
+        let mut request = template.clone();
+        request.method = "GET".to_string();
+        request.body = None;
+        request.raw_body = None;
+        request.url = match base.join(path.trim_start_matches('/')) {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        probes.push(Probe {
+            kind: DriftKind::UndocumentedPath,
+            path,
+            request,
+            declared: None,
+        });
+    };
+
+    for (path, request) in sent {
+        let declared = schema
+            .paths
+            .get(path)
+            .map(|item| {
+                operations(item.to_item_ref())
+                    .into_iter()
+                    .filter(|(_, operation)| operation.is_some())
+                    .map(|(method, _)| method)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let unsafe_methods = UNSAFE_METHODS.iter().filter(|_| unsafe_methods);
+        for method in SAFE_METHODS.iter().chain(unsafe_methods) {
+            // Servers answer `HEAD` wherever they answer `GET`
+            if declared.contains(method) || (*method == "HEAD" && declared.contains(&"GET")) {
+                continue;
+            }
+            let mut probe = request.clone();
+            probe.method = method.to_string();
+            if !matches!(*method, "POST" | "PUT" | "PATCH") {
+                probe.body = None;
+                probe.raw_body = None;
+            }
+            probes.push(Probe {
+                kind: DriftKind::UndocumentedMethod,
+                path: path.clone(),
+                request: probe,
+                declared: None,
+            });
+        }
+        if !declared.contains(&"OPTIONS") {
+            let mut probe = request.clone();
+            probe.method = "OPTIONS".to_string();
+            probe.body = None;
+            probe.raw_body = None;
+            probes.push(Probe {
+                kind: DriftKind::UndocumentedMethod,
+                path: path.clone(),
+                request: probe,
+                declared: Some(declared),
+            });
+        }
+
+        // Paths are probed relative to the base, the concrete path of the request without it
+        let concrete = request
+            .url
+            .path()
+            .strip_prefix(base.path().trim_end_matches('/'))
+            .unwrap_or(request.url.path())
+            .to_string();
+        for version in versions(&concrete) {
+            probe_path(&mut probes, request, version);
+        }
+        if !path.trim_end_matches('/').ends_with('}') {
+            for action in ACTIONS {
+                probe_path(
+                    &mut probes,
+                    request,
+                    format!("{}/{}", concrete.trim_end_matches('/'), action),
+                );
+            }
+        }
+    }
+    if let Some((_, request)) = sent.first() {
+        for internal in INTERNAL {
+            probe_path(&mut probes, request, format!("/{}", internal));
+        }
+    }
+    probes
+}
+
+/// Request with the method to a path that does not exist, how the target answers everything
+/// it does not know
+pub fn control(base: &Url, template: &Request, method: &str) -> Result<Request> {
+    let mut request = template.clone();
+    request.method = method.to_string();
+    if !matches!(method, "POST" | "PUT" | "PATCH") {
+        request.body = None;
+        request.raw_body = None;
+    }
+    request.url = base.join(&format!(
+        "openapi-fuzzer-drift-{:08x}",
+        rand::random::<u32>()
+    ))?;
+    Ok(request)
+}
+
+/// Methods the `Allow` header of the response lists
+fn allowed(response: &Response) -> BTreeSet<String> {
+    response
+        .header("allow")
+        .unwrap_or_default()
+        .split(',')
+        .map(|method| method.trim().to_uppercase())
+        .filter(|method| !method.is_empty())
+        .collect()
+}
+
+/// Drift of the target answering the probe, unless it answered as if it did not exist or like
+/// the control request of the method. Errors of the client answer the control the same way
+/// whatever their bodies say, without a control authentication errors tell nothing.
+pub fn of_probe(probe: &Probe, response: &Response, control: Option<&Response>) -> Vec<Drift> {
+    let drift = |method: &str, detail: String| Drift {
+        kind: probe.kind,
+        method: method.to_string(),
+        path: probe.path.clone(),
+        status: response.status,
+        detail,
+    };
+    if let Some(declared) = &probe.declared {
+        let known = control.map(allowed).unwrap_or_default();
+        return allowed(response)
+            .into_iter()
+            .filter(|method| {
+                SAFE_METHODS
+                    .iter()
+                    .chain(UNSAFE_METHODS)
+                    .any(|m| m == method)
+                    && !declared.contains(&method.as_str())
+                    && !known.contains(method)
+            })
+            .map(|method| drift(&method, "listed in the Allow header".to_string()))
+            .collect();
+    }
+
+    let like_control = control.is_some_and(|c| {
+        c.status == response.status && (response.status / 100 == 4 || c.body == response.body)
+    });
+    let unauthorized = control.is_none() && matches!(response.status, 401 | 403);
+    if MISSING.contains(&response.status) || like_control || unauthorized {
+        return Vec::new();
+    }
+    vec![drift(
+        &probe.request.method,
+        format!("answered with {}", response.status),
+    )]
+}
+
+/// Prints the drift as a table, or as JSON
+pub fn print(drifts: &[Drift], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(drifts)?);
+        return Ok(());
+    }
+    let rows = drifts
+        .iter()
+        .map(|drift| {
+            vec![
+                drift.kind.name().to_string(),
+                drift.method.clone(),
+                drift.path.clone(),
+                drift.status.to_string(),
+                drift.detail.clone(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print!(
+        "{}",
+        report::table(&["DRIFT", "METHOD", "PATH", "STATUS", "DETAIL"], &rows)
+    );
+    println!("\n{} differences from the specification", drifts.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn response(status: u16, headers: &[(&str, &str)], body: &str) -> Response {
+        Response {
+            status,
+            status_text: String::new(),
+            http_version: "HTTP/1.1".to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: body.to_string(),
+            elapsed: Duration::ZERO,
+            events: Vec::new(),
+        }
+    }
+
+    fn probe(method: &str, declared: Option<Vec<&'static str>>) -> Probe {
+        let base = Url::parse("http://localhost/api/").unwrap();
+        let template = Request {
+            method: "GET".to_string(),
+            url: base.join("items").unwrap(),
+            headers: Vec::new(),
+            body: None,
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        };
+        Probe {
+            kind: DriftKind::UndocumentedMethod,
+            path: "/items".to_string(),
+            request: control(&base, &template, method).unwrap(),
+            declared,
+        }
+    }
+
+    #[test]
+    fn compares_probes_with_the_control() {
+        let probe = probe("GET", None);
+        let denied = response(401, &[], "{\"path\": \"/items\"}");
+        let control = response(401, &[], "{\"path\": \"/nothing\"}");
+        assert!(of_probe(&probe, &denied, Some(&control)).is_empty());
+        assert!(of_probe(&probe, &denied, None).is_empty());
+        assert!(of_probe(&probe, &response(404, &[], ""), None).is_empty());
+
+        let answered = of_probe(&probe, &response(200, &[], "[]"), Some(&control));
+        assert_eq!(answered.len(), 1);
+        assert_eq!(answered[0].method, "GET");
+        assert_eq!(answered[0].status, 200);
+    }
+
+    #[test]
+    fn reads_undeclared_methods_from_the_allow_header() {
+        let probe = probe("OPTIONS", Some(vec!["GET"]));
+        let allow = response(204, &[("Allow", "GET, HEAD, DELETE")], "");
+        let control = response(204, &[("Allow", "HEAD")], "");
+        let drifts = of_probe(&probe, &allow, Some(&control));
+        assert_eq!(
+            drifts.iter().map(|d| d.method.as_str()).collect::<Vec<_>>(),
+            vec!["DELETE"]
+        );
+    }
+}
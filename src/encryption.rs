@@ -0,0 +1,240 @@
+//! Encryption at rest of the stored findings: the finding files, the warm-up and snapshot files,
+//! the database, the exports, corpora, cassettes and logs of the containers are encrypted to the
+//! recipients with `age` or `gpg`, which have to be installed, so that request data of staging
+//! systems is never written in plaintext. Encrypted
+//! files are read by their extension, `.age` with the identity of age and `.gpg` with the
+//! keyring of gpg.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    str::FromStr,
+    thread,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+/// Recipient of the encrypted files, `age1...` or `age:<recipient>`, e.g. an SSH public key, for
+/// age and `gpg:<key id or email>` for gpg
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recipient {
+    Age(String),
+    Gpg(String),
+}
+
+impl FromStr for Recipient {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(recipient) = s.strip_prefix("age:") {
+            Ok(Recipient::Age(recipient.to_string()))
+        } else if let Some(key) = s.strip_prefix("gpg:") {
+            Ok(Recipient::Gpg(key.to_string()))
+        } else if s.starts_with("age1") {
+            Ok(Recipient::Age(s.to_string()))
+        } else {
+            Err(format!(
+                "invalid recipient `{}`, expected `age1...`, `age:<recipient>` or \
+                 `gpg:<key id or email>`",
+                s
+            ))
+        }
+    }
+}
+
+/// Recipients the stored files are encrypted to, and the identity of age they are decrypted
+/// with. Files are stored in plaintext without recipients.
+#[derive(Debug, Clone, Default)]
+pub struct Encryption {
+    recipients: Vec<Recipient>,
+    identity: Option<PathBuf>,
+}
+
+impl Encryption {
+    pub fn new(recipients: Vec<Recipient>, identity: Option<PathBuf>) -> Result<Encryption> {
+        let age = recipients.iter().any(|r| matches!(r, Recipient::Age(_)));
+        let gpg = recipients.iter().any(|r| matches!(r, Recipient::Gpg(_)));
+        if age && gpg {
+            return Err(anyhow!(
+                "files are encrypted with either age or gpg, not both"
+            ));
+        }
+        Ok(Encryption {
+            recipients,
+            identity,
+        })
+    }
+
+    /// Whether the stored files are encrypted
+    pub fn enabled(&self) -> bool {
+        !self.recipients.is_empty()
+    }
+
+    fn extension(&self) -> Option<&'static str> {
+        match self.recipients.first()? {
+            Recipient::Age(_) => Some("age"),
+            Recipient::Gpg(_) => Some("gpg"),
+        }
+    }
+
+    /// Path the file is stored at, with the extension of the encryption
+    pub fn path(&self, file: &str) -> String {
+        match self.extension() {
+            Some(extension) => format!("{}.{}", file, extension),
+            None => file.to_string(),
+        }
+    }
+
+    /// Command encrypting its input to the recipients, `None` without recipients
+    fn command(&self) -> Option<Command> {
+        let mut command = match self.recipients.first()? {
+            Recipient::Age(_) => Command::new("age"),
+            Recipient::Gpg(_) => {
+                let mut command = Command::new("gpg");
+                // Keys imported on CI runners are rarely signed
+                command.args(["--batch", "--yes", "--trust-model", "always", "--encrypt"]);
+                command
+            }
+        };
+        for recipient in self.recipients.iter() {
+            match recipient {
+                Recipient::Age(recipient) | Recipient::Gpg(recipient) => {
+                    command.args(["--recipient", recipient])
+                }
+            };
+        }
+        Some(command)
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        match self.command() {
+            Some(command) => run(command, Some(plaintext)),
+            None => Ok(plaintext.to_vec()),
+        }
+    }
+
+    /// Creates the file to write as it goes, e.g. a cassette appended to during the run,
+    /// encrypted as it is written if enabled. The file should have the path given by `path`,
+    /// it is complete once the writer is dropped.
+    pub fn create(&self, file: &Path) -> Result<Box<dyn Write + Send>> {
+        let mut command = match self.command() {
+            Some(command) => command,
+            None => {
+                let created = fs::File::create(file)
+                    .with_context(|| format!("unable to create {}", file.display()))?;
+                return Ok(Box::new(created));
+            }
+        };
+        let program = command.get_program().to_string_lossy().to_string();
+        let mut child = command
+            .arg("--output")
+            .arg(file)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .with_context(|| format!("unable to run {}, is it installed?", program))?;
+        Ok(Box::new(Streamed {
+            stdin: child.stdin.take(),
+            child,
+        }))
+    }
+
+    /// Writes the contents to the file, encrypted if enabled, the file should have the path
+    /// given by `path`
+    pub fn write(&self, file: &Path, contents: &[u8]) -> Result<()> {
+        fs::write(file, self.encrypt(contents)?)
+            .with_context(|| format!("unable to write {}", file.display()))
+    }
+
+    /// Contents of the file, decrypted if it has the extension of age or gpg
+    pub fn read(&self, file: &Path) -> Result<Vec<u8>> {
+        let mut command = match file.extension().and_then(|e| e.to_str()) {
+            Some("age") => {
+                let identity = self.identity.as_ref().ok_or_else(|| {
+                    anyhow!("{} is encrypted, give the identity of age", file.display())
+                })?;
+                let mut command = Command::new("age");
+                command.arg("--decrypt").arg("--identity").arg(identity);
+                command
+            }
+            Some("gpg") => {
+                let mut command = Command::new("gpg");
+                command.args(["--batch", "--quiet", "--decrypt"]);
+                command
+            }
+            _ => {
+                return fs::read(file).with_context(|| format!("unable to read {}", file.display()))
+            }
+        };
+        command.arg(file);
+        run(command, None).with_context(|| format!("unable to decrypt {}", file.display()))
+    }
+
+    pub fn read_to_string(&self, file: &Path) -> Result<String> {
+        String::from_utf8(self.read(file)?)
+            .with_context(|| format!("{} is not valid UTF-8", file.display()))
+    }
+}
+
+/// Input of the command encrypting a file as it is written
+struct Streamed {
+    stdin: Option<ChildStdin>,
+    child: Child,
+}
+
+impl Write for Streamed {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.stdin {
+            Some(stdin) => stdin.write(buf),
+            None => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stdin {
+            Some(stdin) => stdin.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Streamed {
+    fn drop(&mut self) {
+        // The end of the input completes the file
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+/// Output of the command, given the input
+fn run(mut command: Command, input: Option<&[u8]>) -> Result<Vec<u8>> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("unable to run {}, is it installed?", program))?;
+    // The input is written while the output is read, large files would fill both pipes
+    let output = thread::scope(|scope| {
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            scope.spawn(move || stdin.write_all(input));
+        }
+        child.wait_with_output()
+    })?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
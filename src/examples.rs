@@ -0,0 +1,347 @@
+//! Check of the examples of the specification: only the documented examples of the operations
+//! are sent and the responses are validated against the declared schemas, whether the contract
+//! holds for the happy path, without any randomness
+
+use anyhow::Result;
+use openapi_utils::{ParameterExt, ReferenceOrExt};
+use openapiv3::{
+    Components, OpenAPI, Operation, Parameter, ParameterData, ParameterSchemaOrContent,
+    ReferenceOr, Responses, Schema, SchemaKind, Type,
+};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::report;
+use crate::transport::Response;
+use crate::validate;
+
+/// Requests of an operation built from its examples
+#[derive(Debug, Default)]
+pub struct Example {
+    /// Names of the named examples the request is made of
+    pub name: Option<String>,
+    pub query_params: Vec<(String, String)>,
+    pub path_params: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Value>,
+}
+
+/// Values of the examples of a parameter or a body, with their names if they are named
+type Values = Vec<(Option<String>, Value)>;
+
+fn named<'a>(
+    examples: impl IntoIterator<Item = (&'a String, &'a ReferenceOr<openapiv3::Example>)>,
+    components: Option<&Components>,
+) -> Values {
+    examples
+        .into_iter()
+        .filter_map(|(name, example)| {
+            let example = match example {
+                ReferenceOr::Item(example) => example,
+                ReferenceOr::Reference { reference } => {
+                    let name = reference.strip_prefix("#/components/examples/")?;
+                    match components?.examples.get(name)? {
+                        ReferenceOr::Item(example) => example,
+                        ReferenceOr::Reference { .. } => return None,
+                    }
+                }
+            };
+            Some((Some(name.clone()), example.value.clone()?))
+        })
+        .collect()
+}
+
+/// Example of the schema, assembled from the examples of its properties or items if it has
+/// none itself, `None` if a required property has no example
+fn schema_example(schema: &Schema) -> Option<Value> {
+    if let Some(example) = schema
+        .schema_data
+        .example
+        .as_ref()
+        .or(schema.schema_data.default.as_ref())
+    {
+        return Some(example.clone());
+    }
+    match &schema.schema_kind {
+        SchemaKind::Type(Type::Object(object)) if !object.properties.is_empty() => {
+            let mut example = Map::new();
+            for (name, property) in object.properties.iter() {
+                match schema_example(property.to_item_ref()) {
+                    Some(value) => {
+                        example.insert(name.clone(), value);
+                    }
+                    None if object.required.contains(name) => return None,
+                    None => {}
+                }
+            }
+            Some(Value::Object(example))
+        }
+        SchemaKind::Type(Type::Array(array)) => {
+            let items = array.items.to_item_ref();
+            Some(Value::Array(vec![schema_example(items)?]))
+        }
+        SchemaKind::AllOf { all_of } => {
+            let mut example = Map::new();
+            for schema in all_of {
+                match schema_example(schema.to_item_ref())? {
+                    Value::Object(object) => example.extend(object),
+                    _ => return None,
+                }
+            }
+            Some(Value::Object(example))
+        }
+        SchemaKind::OneOf { one_of: schemas } | SchemaKind::AnyOf { any_of: schemas } => {
+            schemas.iter().find_map(|s| schema_example(s.to_item_ref()))
+        }
+        _ => None,
+    }
+}
+
+fn parameter_examples(data: &ParameterData, components: Option<&Components>) -> Values {
+    if let Some(example) = &data.example {
+        return vec![(None, example.clone())];
+    }
+    let examples = named(&data.examples, components);
+    if !examples.is_empty() {
+        return examples;
+    }
+    let schema = match &data.format {
+        ParameterSchemaOrContent::Schema(schema) => schema.to_item_ref(),
+        ParameterSchemaOrContent::Content(_) => return Vec::new(),
+    };
+    schema_example(schema)
+        .map(|example| vec![(None, example)])
+        .unwrap_or_default()
+}
+
+fn body_examples(operation: &Operation, components: Option<&Components>) -> Option<Values> {
+    let body = operation.request_body.as_ref()?.to_item_ref();
+    let media = body
+        .content
+        .iter()
+        .find(|(media_type, _)| media_type.contains("json"))
+        .map(|(_, media)| media)?;
+    if let Some(example) = &media.example {
+        return Some(vec![(None, example.clone())]);
+    }
+    let examples = named(&media.examples, components);
+    if !examples.is_empty() {
+        return Some(examples);
+    }
+    let example = media
+        .schema
+        .as_ref()
+        .and_then(|schema| schema_example(schema.to_item_ref()));
+    Some(
+        example
+            .map(|example| vec![(None, example)])
+            .unwrap_or_default(),
+    )
+}
+
+/// Value of a parameter in the request, items of arrays are separated by commas
+fn parameter_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(parameter_value)
+            .collect::<Vec<_>>()
+            .join(","),
+        value => value.to_string(),
+    }
+}
+
+/// Requests of the operation made of its examples, one for each named example of the
+/// parameters and the body, or why it has none
+pub fn of_operation(schema: &OpenAPI, operation: &Operation) -> Result<Vec<Example>, String> {
+    let components = schema.components.as_ref();
+    let mut parameters = Vec::new();
+    for parameter in operation.parameters.iter() {
+        let parameter = parameter.to_item_ref();
+        let data = parameter.parameter_data();
+        let examples = parameter_examples(data, components);
+        if examples.is_empty() {
+            if data.required {
+                return Err(format!("no example of parameter `{}`", data.name));
+            }
+            continue;
+        }
+        parameters.push((parameter, examples));
+    }
+    let body = match body_examples(operation, components) {
+        Some(examples) if examples.is_empty() => {
+            let required = operation
+                .request_body
+                .as_ref()
+                .map(|b| b.to_item_ref().required);
+            match required {
+                Some(true) => return Err("no example of the body".to_string()),
+                _ => None,
+            }
+        }
+        examples => examples,
+    };
+
+    let count = parameters
+        .iter()
+        .map(|(_, examples)| examples.len())
+        .chain(body.iter().map(Vec::len))
+        .max()
+        .unwrap_or(1);
+    let requests = (0..count)
+        .map(|i| {
+            let mut example = Example::default();
+            let mut names = Vec::new();
+            // Parameters and bodies with fewer examples repeat their first one
+            let mut pick = |examples: &Values| {
+                let (name, value) = examples.get(i).unwrap_or(&examples[0]).clone();
+                if let Some(name) = name.filter(|name| !names.contains(name)) {
+                    names.push(name);
+                }
+                value
+            };
+            for (parameter, examples) in parameters.iter() {
+                let value = parameter_value(&pick(examples));
+                let name = parameter.parameter_data().name.clone();
+                match parameter {
+                    Parameter::Query { .. } => example.query_params.push((name, value)),
+                    Parameter::Path { .. } => example.path_params.push((name, value)),
+                    Parameter::Header { .. } => example.headers.push((name, value)),
+                    Parameter::Cookie { .. } => example
+                        .headers
+                        .push(("Cookie".to_string(), format!("{}={}", name, value))),
+                }
+            }
+            example.body = body.as_ref().map(pick);
+            if !names.is_empty() {
+                example.name = Some(names.join(", "));
+            }
+            example
+        })
+        .collect();
+    Ok(requests)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// The response is successful and matches the specification
+    Passed,
+    /// The operation has no example of a required parameter or body
+    Skipped,
+    /// The example was rejected with a client error
+    Rejected,
+    ServerError,
+    /// The response does not match the specification
+    ContractViolation,
+    /// No response, e.g. the connection was refused or timed out
+    Failed,
+}
+
+impl Outcome {
+    fn is_failure(self) -> bool {
+        !matches!(self, Outcome::Passed | Outcome::Skipped)
+    }
+}
+
+/// Result of sending one example of an operation
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub method: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub example: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+}
+
+impl Check {
+    /// Checks the response to the example against the responses of the operation
+    pub fn new(
+        method: &str,
+        path: &str,
+        example: Option<String>,
+        responses: &Responses,
+        response: Result<Response>,
+    ) -> Check {
+        let (status, outcome, details) = match response {
+            Err(e) => (None, Outcome::Failed, vec![format!("{:#}", e)]),
+            Ok(response) => {
+                let violations = validate::validate_response(responses, &response)
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                let outcome = match response.status / 100 {
+                    5 => Outcome::ServerError,
+                    4 => Outcome::Rejected,
+                    _ if !violations.is_empty() => Outcome::ContractViolation,
+                    _ => Outcome::Passed,
+                };
+                (Some(response.status), outcome, violations)
+            }
+        };
+        Check {
+            method: method.to_string(),
+            path: path.to_string(),
+            example,
+            status,
+            outcome,
+            details,
+        }
+    }
+
+    pub fn skipped(method: &str, path: &str, reason: String) -> Check {
+        Check {
+            method: method.to_string(),
+            path: path.to_string(),
+            example: None,
+            status: None,
+            outcome: Outcome::Skipped,
+            details: vec![reason],
+        }
+    }
+}
+
+/// Prints the checks as a table, or as JSON, and returns whether any example failed
+pub fn print(checks: &[Check], json: bool) -> Result<bool> {
+    let failed = checks.iter().filter(|c| c.outcome.is_failure()).count();
+    if json {
+        println!("{}", serde_json::to_string_pretty(checks)?);
+        return Ok(failed > 0);
+    }
+    let rows = checks
+        .iter()
+        .map(|check| {
+            vec![
+                check.method.clone(),
+                check.path.clone(),
+                check.example.clone().unwrap_or_default(),
+                check.status.map(|s| s.to_string()).unwrap_or_default(),
+                serde_json::to_value(check.outcome)
+                    .ok()
+                    .and_then(|v| v.as_str().map(String::from))
+                    .unwrap_or_default(),
+                check.details.join("; "),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print!(
+        "{}",
+        report::table(
+            &["METHOD", "PATH", "EXAMPLE", "STATUS", "OUTCOME", "DETAILS"],
+            &rows
+        )
+    );
+    let count = |outcome| checks.iter().filter(|c| c.outcome == outcome).count();
+    println!(
+        "\n{} examples passed, {} failed, {} operations skipped",
+        count(Outcome::Passed),
+        failed,
+        count(Outcome::Skipped)
+    );
+    Ok(failed > 0)
+}
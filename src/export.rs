@@ -0,0 +1,229 @@
+use std::path::Path;
+
+use anyhow::Result;
+use serde_json::json;
+
+use crate::encryption::Encryption;
+use crate::finding::{Finding, Findings};
+
+/// CWE and OWASP category of the finding appended to its description
+fn classification(finding: &Finding) -> String {
+    match finding.classification.labels() {
+        labels if labels.is_empty() => labels,
+        labels => format!(" ({})", labels),
+    }
+}
+
+fn har_entry(finding: &Finding) -> Result<serde_json::Value> {
+    let (request, response) = (&finding.request, &finding.response);
+    let headers = |headers: &[(String, String)]| {
+        headers
+            .iter()
+            .map(|(name, value)| json!({"name": name, "value": value}))
+            .collect::<Vec<_>>()
+    };
+
+    let mut har_request = json!({
+        "method": request.method,
+        "url": request.url.as_str(),
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": headers(&request.headers),
+        "queryString": request
+            .url
+            .query_pairs()
+            .map(|(name, value)| json!({"name": name, "value": value}))
+            .collect::<Vec<_>>(),
+        "headersSize": -1,
+        "bodySize": -1,
+    });
+    if let Some(body) = request.body_string()? {
+        har_request["bodySize"] = json!(body.len());
+        har_request["postData"] = json!({"mimeType": "application/json", "text": body});
+    }
+
+    Ok(json!({
+        "startedDateTime": humantime::format_rfc3339_millis(finding.first_seen).to_string(),
+        "time": response.elapsed.as_millis() as u64,
+        "request": har_request,
+        "response": {
+            "status": response.status,
+            "statusText": response.status_text,
+            "httpVersion": response.http_version,
+            "cookies": [],
+            "headers": headers(&response.headers),
+            "content": {
+                "size": response.body.len(),
+                "mimeType": response.header("content-type").unwrap_or(""),
+                "text": response.body,
+            },
+            "redirectURL": "",
+            "headersSize": -1,
+            "bodySize": response.body.len(),
+        },
+        "cache": {},
+        "timings": {"send": 0, "wait": response.elapsed.as_millis() as u64, "receive": 0},
+        "comment": format!(
+            "{} severity finding, payload saved in {}{}",
+            finding.severity,
+            finding.file,
+            classification(finding)
+        ),
+    }))
+}
+
+/// Exports the first request and response of each finding as HAR, which can be imported
+/// to ZAP and other tools
+pub fn har(findings: &Findings, file: &Path, encryption: &Encryption) -> Result<()> {
+    let har = json!({
+        "log": {
+            "version": "1.2",
+            "creator": {"name": "openapi-fuzzer", "version": env!("CARGO_PKG_VERSION")},
+            "entries": findings.iter().map(har_entry).collect::<Result<Vec<_>>>()?,
+        }
+    });
+
+    encryption.write(file, serde_json::to_string_pretty(&har)?.as_bytes())
+}
+
+fn cdata(s: &str) -> String {
+    format!("<![CDATA[{}]]>", s.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn burp_item(finding: &Finding) -> Result<String> {
+    let (request, response) = (&finding.request, &finding.response);
+    let url = &request.url;
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query() {
+        path = path + "?" + query;
+    }
+    let raw_response = response.to_raw();
+
+    Ok(format!(
+        concat!(
+            "  <item>\n",
+            "    <time>{}</time>\n",
+            "    <url>{}</url>\n",
+            "    <host ip=\"\">{}</host>\n",
+            "    <port>{}</port>\n",
+            "    <protocol>{}</protocol>\n",
+            "    <method>{}</method>\n",
+            "    <path>{}</path>\n",
+            "    <extension>null</extension>\n",
+            "    <request base64=\"true\">{}</request>\n",
+            "    <status>{}</status>\n",
+            "    <responselength>{}</responselength>\n",
+            "    <mimetype>{}</mimetype>\n",
+            "    <response base64=\"true\">{}</response>\n",
+            "    <comment>{}</comment>\n",
+            "  </item>\n"
+        ),
+        humantime::format_rfc3339_seconds(finding.first_seen),
+        cdata(url.as_str()),
+        escape_xml(url.host_str().unwrap_or_default()),
+        url.port_or_known_default().unwrap_or_default(),
+        url.scheme(),
+        cdata(&request.method),
+        cdata(&path),
+        cdata(&base64::encode(request.to_raw()?)),
+        response.status,
+        raw_response.len(),
+        escape_xml(response.header("content-type").unwrap_or_default()),
+        cdata(&base64::encode(&raw_response)),
+        cdata(&format!(
+            "{} severity finding{}",
+            finding.severity,
+            classification(finding)
+        )),
+    ))
+}
+
+/// Exports the first request and response of each finding in Burp's XML items format
+pub fn burp(findings: &Findings, file: &Path, encryption: &Encryption) -> Result<()> {
+    let mut xml = String::from("<?xml version=\"1.0\"?>\n<items>\n");
+    for finding in findings.iter() {
+        xml += &burp_item(finding)?;
+    }
+    xml += "</items>\n";
+
+    encryption.write(file, xml.as_bytes())
+}
+
+fn postman_item(finding: &Finding) -> Result<serde_json::Value> {
+    let (request, response) = (&finding.request, &finding.response);
+    let headers = |headers: &[(String, String)]| {
+        headers
+            .iter()
+            .map(|(key, value)| json!({"key": key, "value": value}))
+            .collect::<Vec<_>>()
+    };
+
+    let url = &request.url;
+    let mut postman_request = json!({
+        "method": request.method,
+        "header": headers(&request.headers),
+        "url": {
+            "raw": url.as_str(),
+            "protocol": url.scheme(),
+            "host": url.host_str().unwrap_or_default().split('.').collect::<Vec<_>>(),
+            "path": url.path_segments().map(|s| s.collect::<Vec<_>>()).unwrap_or_default(),
+            "query": url
+                .query_pairs()
+                .map(|(key, value)| json!({"key": key, "value": value}))
+                .collect::<Vec<_>>(),
+        },
+        "description": format!(
+            "{} severity finding{}. The server returned {} {} ({} times during fuzzing), \
+             the payload is saved in {}.",
+            finding.severity,
+            classification(finding),
+            response.status,
+            response.status_text,
+            finding.count,
+            finding.file
+        ),
+    });
+    if let Some(port) = url.port() {
+        postman_request["url"]["port"] = json!(port.to_string());
+    }
+    if let Some(body) = request.body_string()? {
+        postman_request["body"] = json!({
+            "mode": "raw",
+            "raw": body,
+            "options": {"raw": {"language": "json"}},
+        });
+    }
+
+    Ok(json!({
+        "name": format!("{} returned {}", finding.operation(), response.status),
+        "request": postman_request,
+        "response": [{
+            "name": format!("Observed {} response", response.status),
+            "originalRequest": postman_request,
+            "status": response.status_text,
+            "code": response.status,
+            "header": headers(&response.headers),
+            "body": response.body,
+        }],
+    }))
+}
+
+/// Exports a Postman collection with a request for each finding
+pub fn postman(findings: &Findings, file: &Path, encryption: &Encryption) -> Result<()> {
+    let collection = json!({
+        "info": {
+            "name": "OpenAPI fuzzer findings",
+            "schema": "https://schema.getpostman.com/json/collection/v2.1.0/collection.json",
+        },
+        "item": findings.iter().map(postman_item).collect::<Result<Vec<_>>>()?,
+    });
+
+    encryption.write(file, serde_json::to_string_pretty(&collection)?.as_bytes())
+}
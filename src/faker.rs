@@ -0,0 +1,477 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use openapi_utils::ReferenceOrExt;
+use openapiv3::{
+    OpenAPI, Parameter, ReferenceOr, Schema, SchemaKind, Type, VariantOrUnknownOrEmpty,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+use serde_json::Value;
+
+use crate::payload::{operations, Generator, Payload};
+
+/// Probability that a field recognized by its name or format gets a realistic value, the
+/// other payloads keep the random one
+const REALISTIC_PROBABILITY: f64 = 0.5;
+
+/// Country whose conventions the realistic values follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    EnUs,
+    EnGb,
+    DeDe,
+    FrFr,
+    EsEs,
+    NlNl,
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.replace('_', "-").to_lowercase().as_str() {
+            "en-us" | "en" => Ok(Locale::EnUs),
+            "en-gb" => Ok(Locale::EnGb),
+            "de-de" | "de" => Ok(Locale::DeDe),
+            "fr-fr" | "fr" => Ok(Locale::FrFr),
+            "es-es" | "es" => Ok(Locale::EsEs),
+            "nl-nl" | "nl" => Ok(Locale::NlNl),
+            _ => Err(format!(
+                "unknown locale `{}`, expected en-US, en-GB, de-DE, fr-FR, es-ES or nl-NL",
+                s
+            )),
+        }
+    }
+}
+
+/// Data of a locale
+struct Data {
+    country: &'static str,
+    first_names: &'static [&'static str],
+    last_names: &'static [&'static str],
+    streets: &'static [&'static str],
+    cities: &'static [&'static str],
+    /// `#` is a digit and `@` an uppercase letter
+    postal_code: &'static str,
+    phone: &'static str,
+    /// Country of the IBAN and its BBAN, countries without IBANs use the German one
+    iban: (&'static str, &'static str),
+    email_domains: &'static [&'static str],
+}
+
+impl Locale {
+    fn data(self) -> Data {
+        match self {
+            Locale::EnUs => Data {
+                country: "US",
+                first_names: &["James", "Mary", "Robert", "Patricia", "Michael", "Jennifer"],
+                last_names: &["Smith", "Johnson", "Williams", "Brown", "Jones", "Miller"],
+                streets: &[
+                    "Main Street",
+                    "Oak Avenue",
+                    "Maple Drive",
+                    "Cedar Lane",
+                    "Elm Street",
+                ],
+                cities: &["Springfield", "Portland", "Austin", "Denver", "Columbus"],
+                postal_code: "#####",
+                phone: "+1##########",
+                iban: ("DE", "##################"),
+                email_domains: &["example.com", "mail.example.org"],
+            },
+            Locale::EnGb => Data {
+                country: "GB",
+                first_names: &["Oliver", "Amelia", "George", "Isla", "Harry", "Ava"],
+                last_names: &["Smith", "Jones", "Taylor", "Brown", "Williams", "Wilson"],
+                streets: &[
+                    "High Street",
+                    "Station Road",
+                    "Church Lane",
+                    "Victoria Road",
+                ],
+                cities: &["London", "Manchester", "Bristol", "Leeds", "Glasgow"],
+                postal_code: "@@# #@@",
+                phone: "+447700900###",
+                iban: ("GB", "@@@@##############"),
+                email_domains: &["example.co.uk", "example.com"],
+            },
+            Locale::DeDe => Data {
+                country: "DE",
+                first_names: &["Lukas", "Anna", "Leon", "Lena", "Felix", "Mia"],
+                last_names: &[
+                    "Müller",
+                    "Schmidt",
+                    "Schneider",
+                    "Fischer",
+                    "Weber",
+                    "Meyer",
+                ],
+                streets: &["Hauptstraße", "Schulstraße", "Gartenweg", "Bahnhofstraße"],
+                cities: &["Berlin", "München", "Köln", "Hamburg", "Leipzig"],
+                postal_code: "#####",
+                phone: "+4930########",
+                iban: ("DE", "##################"),
+                email_domains: &["beispiel.de", "example.com"],
+            },
+            Locale::FrFr => Data {
+                country: "FR",
+                first_names: &["Gabriel", "Louise", "Raphaël", "Jade", "Léo", "Emma"],
+                last_names: &["Martin", "Bernard", "Dubois", "Thomas", "Robert", "Petit"],
+                streets: &["rue de la Paix", "avenue Victor Hugo", "boulevard Voltaire"],
+                cities: &["Paris", "Lyon", "Marseille", "Toulouse", "Nantes"],
+                postal_code: "#####",
+                phone: "+336########",
+                iban: ("FR", "#######################"),
+                email_domains: &["exemple.fr", "example.com"],
+            },
+            Locale::EsEs => Data {
+                country: "ES",
+                first_names: &["Hugo", "Lucía", "Martín", "Sofía", "Daniel", "María"],
+                last_names: &["García", "Rodríguez", "González", "Fernández", "López"],
+                streets: &["Calle Mayor", "Avenida de la Constitución", "Calle Real"],
+                cities: &["Madrid", "Barcelona", "Valencia", "Sevilla", "Bilbao"],
+                postal_code: "28###",
+                phone: "+346########",
+                iban: ("ES", "####################"),
+                email_domains: &["ejemplo.es", "example.com"],
+            },
+            Locale::NlNl => Data {
+                country: "NL",
+                first_names: &["Daan", "Emma", "Sem", "Julia", "Lucas", "Tess"],
+                last_names: &["de Jong", "Jansen", "de Vries", "van den Berg", "Bakker"],
+                streets: &["Kerkstraat", "Dorpsstraat", "Molenweg", "Stationsweg"],
+                cities: &[
+                    "Amsterdam",
+                    "Rotterdam",
+                    "Utrecht",
+                    "Eindhoven",
+                    "Groningen",
+                ],
+                postal_code: "#### @@",
+                phone: "+316########",
+                iban: ("NL", "@@@@##########"),
+                email_domains: &["voorbeeld.nl", "example.com"],
+            },
+        }
+    }
+}
+
+/// Kind of realistic value recognized by the name or format of a string field, the kinds from
+/// `Url` on have values of no locale, see `smart`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    FirstName,
+    LastName,
+    FullName,
+    Email,
+    Phone,
+    Street,
+    City,
+    PostalCode,
+    Country,
+    Iban,
+    Url,
+    Color,
+    Uuid,
+    Ipv4,
+    Ipv6,
+    Hostname,
+    Currency,
+    Language,
+    TimeZone,
+}
+
+/// Kind of the field with the name or string format, if it is recognized. Fields with other
+/// formats are not recognized by their names, the format says what their values are.
+pub fn kind(name: &str, format: Option<&str>) -> Option<Kind> {
+    if let Some(format) = format {
+        return match format.to_lowercase().as_str() {
+            "email" | "idn-email" => Some(Kind::Email),
+            "iban" => Some(Kind::Iban),
+            "phone" | "tel" => Some(Kind::Phone),
+            "uri" | "url" | "iri" => Some(Kind::Url),
+            "uuid" | "guid" => Some(Kind::Uuid),
+            "ipv4" => Some(Kind::Ipv4),
+            "ipv6" => Some(Kind::Ipv6),
+            "hostname" | "idn-hostname" => Some(Kind::Hostname),
+            _ => None,
+        };
+    }
+    let name = name
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    let ends = |suffixes: &[&str]| suffixes.iter().any(|suffix| name.ends_with(suffix));
+    Some(match name.as_str() {
+        "firstname" | "givenname" | "forename" => Kind::FirstName,
+        "lastname" | "surname" | "familyname" => Kind::LastName,
+        "name" | "fullname" | "displayname" | "holdername" | "accountholder" => Kind::FullName,
+        "mail" => Kind::Email,
+        "street" | "streetaddress" | "address" | "addressline1" | "address1" | "line1" => {
+            Kind::Street
+        }
+        "city" | "town" | "locality" => Kind::City,
+        "iban" => Kind::Iban,
+        "ip" => Kind::Ipv4,
+        "lang" | "locale" => Kind::Language,
+        "tz" => Kind::TimeZone,
+        "host" => Kind::Hostname,
+        _ if name.contains("phone") || name.contains("mobile") || name == "tel" => Kind::Phone,
+        _ if ends(&["fax"]) => Kind::Phone,
+        _ if ends(&["email", "emailaddress"]) => Kind::Email,
+        _ if ends(&["url", "uri", "link", "homepage", "website"]) => Kind::Url,
+        _ if ends(&["zip", "zipcode", "postcode", "postalcode"]) => Kind::PostalCode,
+        _ if ends(&["color", "colour"]) => Kind::Color,
+        _ if ends(&["uuid", "guid"]) => Kind::Uuid,
+        _ if ends(&["ipaddress", "ipv4"]) => Kind::Ipv4,
+        _ if ends(&["ipv6"]) => Kind::Ipv6,
+        _ if ends(&["hostname", "domain"]) => Kind::Hostname,
+        _ if ends(&["country", "countrycode"]) => Kind::Country,
+        _ if ends(&["currency", "currencycode"]) => Kind::Currency,
+        _ if ends(&["language", "languagecode"]) => Kind::Language,
+        _ if ends(&["timezone"]) => Kind::TimeZone,
+        _ => return None,
+    })
+}
+
+/// Replaces `#` by a random digit and `@` by a random uppercase letter
+fn pattern(pattern: &str, rng: &mut StdRng) -> String {
+    pattern
+        .chars()
+        .map(|c| match c {
+            '#' => char::from(b'0' + rng.gen_range(0..10)),
+            '@' => char::from(b'A' + rng.gen_range(0..26)),
+            c => c,
+        })
+        .collect()
+}
+
+/// IBAN with valid check digits (ISO 13616, mod 97)
+fn iban(country: &str, bban: &str) -> String {
+    let rearranged = format!("{}{}00", bban, country);
+    let remainder = rearranged.chars().fold(0u32, |remainder, c| {
+        let value = c.to_digit(36).unwrap_or(0);
+        match value {
+            0..=9 => (remainder * 10 + value) % 97,
+            _ => (remainder * 100 + value) % 97,
+        }
+    });
+    format!("{}{:02}{}", country, 98 - remainder, bban)
+}
+
+/// Value of the kind in the locale, `None` for the kinds without values of a locale
+fn value(kind: Kind, data: &Data, rng: &mut StdRng) -> Option<String> {
+    let mut pick = |values: &[&str]| values.choose(rng).copied().unwrap_or_default().to_string();
+    Some(match kind {
+        Kind::FirstName => pick(data.first_names),
+        Kind::LastName => pick(data.last_names),
+        Kind::FullName => format!("{} {}", pick(data.first_names), pick(data.last_names)),
+        Kind::Email => {
+            let local = format!("{}.{}", pick(data.first_names), pick(data.last_names))
+                .to_lowercase()
+                .chars()
+                .flat_map(|c| match c {
+                    'ä' => "ae".chars().collect(),
+                    'ö' => "oe".chars().collect(),
+                    'ü' => "ue".chars().collect(),
+                    'ß' => "ss".chars().collect(),
+                    'á' | 'à' => vec!['a'],
+                    'é' | 'è' | 'ë' => vec!['e'],
+                    'í' => vec!['i'],
+                    'ó' => vec!['o'],
+                    c if c.is_ascii_alphanumeric() || c == '.' => vec![c],
+                    _ => Vec::new(),
+                })
+                .collect::<String>();
+            format!("{}@{}", local, pick(data.email_domains))
+        }
+        Kind::Phone => pattern(data.phone, rng),
+        Kind::Street => format!("{} {}", pick(data.streets), rng.gen_range(1..200)),
+        Kind::City => pick(data.cities),
+        Kind::PostalCode => pattern(data.postal_code, rng),
+        Kind::Country => data.country.to_string(),
+        Kind::Iban => {
+            let (country, bban) = data.iban;
+            iban(country, &pattern(bban, rng))
+        }
+        _ => return None,
+    })
+}
+
+/// Gives string fields recognized by their names (e.g. `lastName` or `postalCode`) or formats
+/// (e.g. `email`) realistic values of the locale, backends validating them would reject the
+/// random ones before reaching interesting code
+pub struct Faker {
+    locale: Locale,
+    /// Formats of the string properties and parameters in the specification by name
+    formats: BTreeMap<String, String>,
+}
+
+impl Faker {
+    pub fn new(schema: &OpenAPI, locale: Locale) -> Faker {
+        Faker {
+            locale,
+            formats: formats(schema),
+        }
+    }
+
+    fn realistic(&self, name: &str, rng: &mut StdRng) -> Option<String> {
+        let kind = kind(name, self.formats.get(name).map(String::as_str))?;
+        value(kind, &self.locale.data(), rng)
+    }
+
+    fn fill(&self, value: &mut Value, rng: &mut StdRng) {
+        match value {
+            Value::Object(object) => {
+                for (name, value) in object.iter_mut() {
+                    match value {
+                        Value::String(s) if rng.gen_bool(REALISTIC_PROBABILITY) => {
+                            if let Some(realistic) = self.realistic(name, rng) {
+                                *s = realistic;
+                            }
+                        }
+                        value => self.fill(value, rng),
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values.iter_mut() {
+                    self.fill(value, rng);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Generator for Faker {
+    fn generate(&self, payload: &mut Payload<'_>, rng: &mut StdRng) {
+        for (name, value) in payload.query_params.iter_mut() {
+            if rng.gen_bool(REALISTIC_PROBABILITY) {
+                if let Some(realistic) = self.realistic(name, rng) {
+                    *value = realistic;
+                }
+            }
+        }
+        for body in payload.body.iter_mut() {
+            self.fill(body, rng);
+        }
+    }
+}
+
+/// Formats of the string properties and parameters in the specification by name
+pub(crate) fn formats(schema: &OpenAPI) -> BTreeMap<String, String> {
+    let mut formats = BTreeMap::new();
+    for item in schema.paths.values() {
+        for (_, operation) in operations(item.to_item_ref()) {
+            let operation = match operation {
+                Some(operation) => operation,
+                None => continue,
+            };
+            for parameter in operation.parameters.iter() {
+                let data = match parameter.to_item_ref() {
+                    Parameter::Query { parameter_data, .. }
+                    | Parameter::Header { parameter_data, .. }
+                    | Parameter::Path { parameter_data, .. }
+                    | Parameter::Cookie { parameter_data, .. } => parameter_data,
+                };
+                if let openapiv3::ParameterSchemaOrContent::Schema(schema) = &data.format {
+                    collect_formats(&data.name, schema.to_item_ref(), &mut formats);
+                }
+            }
+            if let Some(body) = &operation.request_body {
+                for media in body.to_item_ref().content.values() {
+                    if let Some(schema) = &media.schema {
+                        collect_formats("", schema.to_item_ref(), &mut formats);
+                    }
+                }
+            }
+        }
+    }
+    formats
+}
+
+/// Adds the formats of the string schema or its properties, recursively
+fn collect_formats(name: &str, schema: &Schema, formats: &mut BTreeMap<String, String>) {
+    match &schema.schema_kind {
+        SchemaKind::Type(Type::String(string)) => {
+            if let (VariantOrUnknownOrEmpty::Unknown(format), false) =
+                (&string.format, name.is_empty())
+            {
+                formats.insert(name.to_string(), format.to_lowercase());
+            }
+        }
+        SchemaKind::Type(Type::Object(object)) => {
+            for (name, property) in object.properties.iter() {
+                if let ReferenceOr::Item(property) = property {
+                    collect_formats(name, property, formats);
+                }
+            }
+        }
+        SchemaKind::Type(Type::Array(array)) => {
+            if let ReferenceOr::Item(items) = &array.items {
+                collect_formats(name, items, formats);
+            }
+        }
+        SchemaKind::OneOf { one_of: schemas }
+        | SchemaKind::AnyOf { any_of: schemas }
+        | SchemaKind::AllOf { all_of: schemas } => {
+            for schema in schemas.iter() {
+                if let ReferenceOr::Item(schema) = schema {
+                    collect_formats(name, schema, formats);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// Whether the check digits of the IBAN are valid, its mod 97 is 1
+    fn valid(iban: &str) -> bool {
+        let rearranged = format!("{}{}", &iban[4..], &iban[..4]);
+        rearranged.chars().fold(0, |remainder, c| {
+            let value = c.to_digit(36).unwrap();
+            match value {
+                0..=9 => (remainder * 10 + value) % 97,
+                _ => (remainder * 100 + value) % 97,
+            }
+        }) == 1
+    }
+
+    #[test]
+    fn computes_iban_check_digits() {
+        assert_eq!(iban("GB", "WEST12345698765432"), "GB82WEST12345698765432");
+        assert_eq!(iban("DE", "370400440532013000"), "DE89370400440532013000");
+        assert_eq!(iban("NL", "ABNA0417164300"), "NL91ABNA0417164300");
+    }
+
+    #[test]
+    fn generates_valid_ibans_of_the_locale() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (locale, country) in [
+            (Locale::EnUs, "DE"),
+            (Locale::EnGb, "GB"),
+            (Locale::FrFr, "FR"),
+            (Locale::EsEs, "ES"),
+            (Locale::NlNl, "NL"),
+        ] {
+            for _ in 0..100 {
+                let iban = value(Kind::Iban, &locale.data(), &mut rng).unwrap();
+                assert!(iban.starts_with(country), "{}", iban);
+                assert!(valid(&iban), "{}", iban);
+            }
+        }
+    }
+
+    #[test]
+    fn recognizes_ibans_by_name_and_format() {
+        assert_eq!(kind("iban", None), Some(Kind::Iban));
+        assert_eq!(kind("account", Some("iban")), Some(Kind::Iban));
+        assert_eq!(kind("iban", Some("byte")), None);
+    }
+}
@@ -0,0 +1,774 @@
+use std::{collections::BTreeMap, fmt, str::FromStr, time::SystemTime};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::payload::Payload;
+use crate::samples::Sample;
+use crate::transport::{Request, Response};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "low" => Ok(Severity::Low),
+            "medium" => Ok(Severity::Medium),
+            "high" => Ok(Severity::High),
+            "critical" => Ok(Severity::Critical),
+            _ => Err(format!("unknown severity `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FindingKind {
+    /// Response with 5xx status code
+    ServerError,
+    /// Response with a status code not documented in the specification
+    UndocumentedStatus,
+    /// Response with a status code declared as a finding by the status rules
+    UnexpectedStatus,
+    /// Response not matching the schema or headers declared in the specification
+    ContractViolation,
+    /// Response taking much longer than usual, possibly an algorithmic complexity issue
+    SlowResponse,
+    /// Response with missing or weak security headers
+    SecurityHeaders,
+    /// Response allowing cross-origin requests from untrusted origins
+    Cors,
+    /// Repeated request of an idempotent operation with a different response or side effects
+    NonIdempotent,
+    /// Operation not rate limiting a burst of requests
+    MissingRateLimit,
+    /// Response differing from the response of the baseline deployment
+    Regression,
+    /// Response to a request with conflicting framing indicating a possible desync
+    RequestSmuggling,
+    /// Path parameter with a traversal or encoding attack reaching a file, another resource or
+    /// route, or causing a server error
+    PathTraversal,
+    /// Timestamped request with a skewed clock accepted although it is far off, or rejected
+    /// although it is within the usual drift of clocks
+    ClockSkew,
+    /// Error response leaking implementation details, e.g. SQL errors or stack traces
+    Disclosure,
+    /// Response matching a user defined oracle
+    Oracle,
+    /// Successful response to a payload violating the specification on purpose
+    ValidationGap,
+    /// WebSocket connection dropped or closed with an internal error after a message
+    AbnormalClosure,
+    /// Conditional or range request causing a server error, or write succeeding although its
+    /// precondition fails
+    Precondition,
+    /// Unkeyed header reflected in, or malformed header causing an error response cacheable
+    /// by shared caches
+    CachePoisoning,
+    /// Resource created by one identity read by another one
+    Bola,
+    /// Oversized header, URL or body causing a server error or a dropped connection instead of
+    /// a client error
+    SizeLimit,
+    /// Request left without a response for longer than the hang threshold
+    Hang,
+    /// Slow, truncated or aborted request mishandled, or the target failing or slowing down
+    /// after such requests
+    ClientFault,
+    /// Response to the follow-up of an operation breaking an invariant declared in the config
+    Inconsistency,
+    /// Response with headers leaking the software of the server, missing its `Content-Type` or
+    /// framed with an invalid or conflicting `Content-Length`
+    HeaderAnomaly,
+}
+
+impl FindingKind {
+    pub fn of_status(status: u16) -> FindingKind {
+        match status / 100 {
+            5 => FindingKind::ServerError,
+            _ => FindingKind::UndocumentedStatus,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            FindingKind::ServerError => Severity::High,
+            FindingKind::UndocumentedStatus => Severity::Low,
+            FindingKind::UnexpectedStatus => Severity::Medium,
+            FindingKind::ContractViolation => Severity::Medium,
+            FindingKind::SlowResponse => Severity::Medium,
+            FindingKind::SecurityHeaders => Severity::Low,
+            FindingKind::Cors => Severity::Medium,
+            FindingKind::NonIdempotent => Severity::Medium,
+            FindingKind::MissingRateLimit => Severity::Low,
+            FindingKind::Regression => Severity::Medium,
+            FindingKind::RequestSmuggling => Severity::High,
+            FindingKind::PathTraversal => Severity::High,
+            FindingKind::ClockSkew => Severity::Medium,
+            FindingKind::Disclosure => Severity::Medium,
+            FindingKind::Oracle => Severity::Medium,
+            FindingKind::ValidationGap => Severity::Low,
+            FindingKind::AbnormalClosure => Severity::Medium,
+            FindingKind::Precondition => Severity::Medium,
+            FindingKind::CachePoisoning => Severity::High,
+            FindingKind::Bola => Severity::High,
+            FindingKind::SizeLimit => Severity::Medium,
+            FindingKind::Hang => Severity::High,
+            FindingKind::ClientFault => Severity::Medium,
+            FindingKind::Inconsistency => Severity::Medium,
+            FindingKind::HeaderAnomaly => Severity::Low,
+        }
+    }
+}
+
+/// Weakness behind a kind of findings in the taxonomies compliance tooling reports by
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Classification {
+    /// Identifier of the Common Weakness Enumeration, e.g. `CWE-22`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwe: Option<&'static str>,
+    /// Category of the OWASP API Security Top 10 2023
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owasp: Option<&'static str>,
+}
+
+const API1: &str = "API1:2023 Broken Object Level Authorization";
+const API2: &str = "API2:2023 Broken Authentication";
+const API3: &str = "API3:2023 Broken Object Property Level Authorization";
+const API4: &str = "API4:2023 Unrestricted Resource Consumption";
+const API6: &str = "API6:2023 Unrestricted Access to Sensitive Business Flows";
+const API8: &str = "API8:2023 Security Misconfiguration";
+const API9: &str = "API9:2023 Improper Inventory Management";
+
+impl FindingKind {
+    /// Oracles are defined by the user, their findings are not classified
+    pub fn classification(&self) -> Classification {
+        let (cwe, owasp) = match self {
+            // Improper Handling of Exceptional Conditions
+            FindingKind::ServerError => (Some("CWE-755"), Some(API8)),
+            // Insufficient Technical Documentation
+            FindingKind::UndocumentedStatus => (Some("CWE-1059"), Some(API9)),
+            // Return of Wrong Status Code
+            FindingKind::UnexpectedStatus => (Some("CWE-393"), Some(API8)),
+            // Exposure of Sensitive Information, e.g. undeclared properties
+            FindingKind::ContractViolation => (Some("CWE-200"), Some(API3)),
+            // Uncontrolled Resource Consumption
+            FindingKind::SlowResponse => (Some("CWE-400"), Some(API4)),
+            // Protection Mechanism Failure
+            FindingKind::SecurityHeaders => (Some("CWE-693"), Some(API8)),
+            // Permissive Cross-domain Policy with Untrusted Domains
+            FindingKind::Cors => (Some("CWE-942"), Some(API8)),
+            // Improper Enforcement of Behavioral Workflow
+            FindingKind::NonIdempotent => (Some("CWE-841"), Some(API6)),
+            // Allocation of Resources Without Limits or Throttling
+            FindingKind::MissingRateLimit => (Some("CWE-770"), Some(API4)),
+            // Incorrect Provision of Specified Functionality
+            FindingKind::Regression => (Some("CWE-684"), Some(API9)),
+            // Inconsistent Interpretation of HTTP Requests
+            FindingKind::RequestSmuggling => (Some("CWE-444"), Some(API8)),
+            // Path Traversal, reaching resources of other objects
+            FindingKind::PathTraversal => (Some("CWE-22"), Some(API1)),
+            // Authentication Bypass by Capture-replay
+            FindingKind::ClockSkew => (Some("CWE-294"), Some(API2)),
+            // Generation of Error Message Containing Sensitive Information
+            FindingKind::Disclosure => (Some("CWE-209"), Some(API8)),
+            FindingKind::Oracle => (None, None),
+            // Improper Input Validation
+            FindingKind::ValidationGap => (Some("CWE-20"), Some(API8)),
+            // Improper Handling of Exceptional Conditions
+            FindingKind::AbnormalClosure => (Some("CWE-755"), Some(API8)),
+            // Race condition, concurrent writes overwrite each other
+            FindingKind::Precondition => (Some("CWE-362"), Some(API8)),
+            // Acceptance of Extraneous Untrusted Data With Trusted Data
+            FindingKind::CachePoisoning => (Some("CWE-349"), Some(API8)),
+            // Authorization Bypass Through User-Controlled Key
+            FindingKind::Bola => (Some("CWE-639"), Some(API1)),
+            // Allocation of Resources Without Limits or Throttling
+            FindingKind::SizeLimit => (Some("CWE-770"), Some(API4)),
+            // Uncontrolled Resource Consumption
+            FindingKind::Hang => (Some("CWE-400"), Some(API4)),
+            // Missing Release of Resource after Effective Lifetime
+            FindingKind::ClientFault => (Some("CWE-772"), Some(API4)),
+            // Incorrect Provision of Specified Functionality
+            FindingKind::Inconsistency => (Some("CWE-684"), None),
+            // Exposure of Sensitive System Information to an Unauthorized Control Sphere
+            FindingKind::HeaderAnomaly => (Some("CWE-497"), Some(API8)),
+        };
+        Classification { cwe, owasp }
+    }
+}
+
+impl Classification {
+    /// The CWE and OWASP category separated by commas, empty for unclassified findings
+    pub fn labels(&self) -> String {
+        self.cwe
+            .iter()
+            .chain(self.owasp.iter())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl FindingKind {
+    /// Whether replaying the request alone shows if the finding reproduces, findings comparing
+    /// responses, timing or bursts of requests, smuggling probes or WebSocket messages sent
+    /// over sockets, oversized requests the connection was dropped for, hung requests,
+    /// requests with faults injected and follow-ups checking invariants are not
+    pub fn replayable(&self) -> bool {
+        !matches!(
+            self,
+            FindingKind::SlowResponse
+                | FindingKind::NonIdempotent
+                | FindingKind::MissingRateLimit
+                | FindingKind::Regression
+                | FindingKind::RequestSmuggling
+                | FindingKind::AbnormalClosure
+                | FindingKind::SizeLimit
+                | FindingKind::Hang
+                | FindingKind::ClientFault
+                | FindingKind::Inconsistency
+        )
+    }
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FindingKind::ServerError => "server-error",
+            FindingKind::UndocumentedStatus => "undocumented-status",
+            FindingKind::UnexpectedStatus => "unexpected-status",
+            FindingKind::ContractViolation => "contract-violation",
+            FindingKind::SlowResponse => "slow-response",
+            FindingKind::SecurityHeaders => "security-headers",
+            FindingKind::Cors => "cors",
+            FindingKind::NonIdempotent => "non-idempotent",
+            FindingKind::MissingRateLimit => "missing-rate-limit",
+            FindingKind::Regression => "regression",
+            FindingKind::RequestSmuggling => "request-smuggling",
+            FindingKind::PathTraversal => "path-traversal",
+            FindingKind::ClockSkew => "clock-skew",
+            FindingKind::Disclosure => "disclosure",
+            FindingKind::Oracle => "oracle",
+            FindingKind::ValidationGap => "validation-gap",
+            FindingKind::AbnormalClosure => "abnormal-closure",
+            FindingKind::Precondition => "precondition",
+            FindingKind::CachePoisoning => "cache-poisoning",
+            FindingKind::Bola => "bola",
+            FindingKind::SizeLimit => "size-limit",
+            FindingKind::Hang => "hang",
+            FindingKind::ClientFault => "client-fault",
+            FindingKind::Inconsistency => "inconsistency",
+            FindingKind::HeaderAnomaly => "header-anomaly",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Whether the request of a finding triggers it again when it is replayed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Verdict {
+    /// Every replay got the same status code
+    Deterministic,
+    /// Some of the replays got another status code, e.g. a proxy failing from time to time
+    Flaky,
+    /// None of the replays got the same status code
+    NotReproduced,
+    /// None of the replays got the same status code, but some of the later ones did, a bug
+    /// showing up from time to time
+    Intermittent,
+    /// Neither the replays nor the later ones got the same status code, e.g. a deployment of
+    /// the environment during the request
+    Noise,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Verdict::Deterministic => "deterministic",
+            Verdict::Flaky => "flaky",
+            Verdict::NotReproduced => "not reproduced",
+            Verdict::Intermittent => "intermittent",
+            Verdict::Noise => "environment noise",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Outcome of replaying the request of a new finding before it is reported
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Verification {
+    pub replays: u32,
+    /// Replays that got a response with the status code of the finding
+    pub reproduced: u32,
+    pub verdict: Verdict,
+    /// Fields of the bodies of reproducing replays that differ from the finding
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub differences: Vec<String>,
+    /// Replays spread over an interval after none of the replays reproduced the finding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub later: Option<LaterReplays>,
+}
+
+/// Replays of a finding spread over an interval, telling bugs showing up from time to time
+/// from noise of the environment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LaterReplays {
+    /// Interval the replays were spread over, e.g. `10m`
+    pub interval: String,
+    pub replays: u32,
+    pub reproduced: u32,
+    /// Status code of each replay, none if it got no response
+    pub statuses: Vec<Option<u16>>,
+}
+
+impl Verification {
+    pub fn new(replays: u32, reproduced: u32) -> Verification {
+        let verdict = match reproduced {
+            0 => Verdict::NotReproduced,
+            reproduced if reproduced == replays => Verdict::Deterministic,
+            _ => Verdict::Flaky,
+        };
+        Verification {
+            replays,
+            reproduced,
+            verdict,
+            differences: Vec::new(),
+            later: None,
+        }
+    }
+
+    /// Records the later replays of a finding that did not reproduce and classifies it
+    pub fn replayed_later(&mut self, later: LaterReplays) {
+        self.verdict = match later.reproduced {
+            0 => Verdict::Noise,
+            _ => Verdict::Intermittent,
+        };
+        self.later = Some(later);
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, reproduced by {} of {} replays",
+            self.verdict, self.reproduced, self.replays
+        )?;
+        if let Some(later) = &self.later {
+            write!(
+                f,
+                " and {} of {} later ones over {}",
+                later.reproduced, later.replays, later.interval
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Minimal severity of a finding that fails the run, e.g. `severity>=high` or just `high`
+#[derive(Debug, Clone, Copy)]
+pub struct FailOn(pub Severity);
+
+impl FromStr for FailOn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let severity = s.trim().trim_start_matches("severity").trim_start();
+        let severity = severity.strip_prefix(">=").unwrap_or(severity);
+        Ok(FailOn(severity.parse()?))
+    }
+}
+
+/// Version of the format of findings stored in the results directory. Bump it when the
+/// format changes and add a migration of older versions to the resender.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Information about the run stored with each finding, so it can be replayed later
+#[derive(Debug, Clone, Serialize)]
+pub struct RunInfo {
+    pub fuzzer_version: &'static str,
+    /// SHA-256 of the specification file
+    pub spec_hash: String,
+    pub run_seed: u64,
+    pub auth_profile: Option<String>,
+    /// Token the data created by the run starts with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+impl RunInfo {
+    pub fn new(spec: &str, run_seed: u64) -> RunInfo {
+        let digest = ring::digest::digest(&ring::digest::SHA256, spec.as_bytes());
+        RunInfo {
+            fuzzer_version: env!("CARGO_PKG_VERSION"),
+            spec_hash: digest
+                .as_ref()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            run_seed,
+            auth_profile: None,
+            namespace: None,
+        }
+    }
+}
+
+/// Finding as it is stored in the results directory
+#[derive(Debug, Serialize)]
+pub struct FindingRecord<'a> {
+    pub version: u32,
+    #[serde(flatten)]
+    pub run: &'a RunInfo,
+    /// Seed of the random generator the payload was generated with
+    pub seed: u64,
+    pub timestamp: String,
+    pub kind: FindingKind,
+    /// Violations of the contract for contract violation findings
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub details: &'a [String],
+    #[serde(flatten)]
+    pub classification: Classification,
+    /// Replays of the request before the finding was reported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verification>,
+    /// Latest successful response of the operation, if successful responses are sampled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normal: Option<&'a Sample>,
+    pub payload: &'a Payload<'a>,
+    pub curl: String,
+    pub request: &'a Request,
+    pub response: &'a Response,
+}
+
+impl<'a> FindingRecord<'a> {
+    pub fn new(
+        run: &'a RunInfo,
+        payload: &'a Payload,
+        request: &'a Request,
+        response: &'a Response,
+        kind: FindingKind,
+        details: &'a [String],
+    ) -> Result<FindingRecord<'a>> {
+        Ok(FindingRecord {
+            version: FORMAT_VERSION,
+            run,
+            seed: payload.seed,
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            kind,
+            details,
+            classification: kind.classification(),
+            verification: None,
+            normal: None,
+            payload,
+            curl: payload.to_curl()?,
+            request,
+            response,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub path: String,
+    pub method: String,
+    pub operation_id: Option<String>,
+    pub status: u16,
+    pub kind: FindingKind,
+    pub severity: Severity,
+    #[serde(flatten)]
+    pub classification: Classification,
+    /// Violations of the contract found in the first response
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+    /// File with the first payload that triggered the finding
+    pub file: String,
+    /// Number of responses that triggered the finding
+    pub count: u32,
+    pub first_seen: SystemTime,
+    /// Replays of the first request before the finding was reported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verification>,
+    /// First request that triggered the finding
+    #[serde(skip)]
+    pub request: Request,
+    #[serde(skip)]
+    pub response: Response,
+    /// Smallest request found still triggering the finding, if it was minimized
+    #[serde(skip)]
+    pub minimized: Option<Request>,
+}
+
+impl Finding {
+    /// Name of the operation, its operationId if present, otherwise method and path
+    pub fn operation(&self) -> String {
+        match &self.operation_id {
+            Some(operation_id) => operation_id.clone(),
+            None => format!("{} {}", self.method, self.path),
+        }
+    }
+}
+
+/// Finding with its first request and response, as it is stored by fuzzers sharing their
+/// findings or in snapshots
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredFinding {
+    pub path: String,
+    pub method: String,
+    pub operation_id: Option<String>,
+    pub status: u16,
+    pub kind: FindingKind,
+    pub details: Vec<String>,
+    pub file: String,
+    pub count: u32,
+    pub first_seen: SystemTime,
+    #[serde(default)]
+    pub verification: Option<Verification>,
+    pub request: Request,
+    pub response: Response,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimized: Option<Request>,
+}
+
+impl From<&Finding> for StoredFinding {
+    fn from(finding: &Finding) -> StoredFinding {
+        StoredFinding {
+            path: finding.path.clone(),
+            method: finding.method.clone(),
+            operation_id: finding.operation_id.clone(),
+            status: finding.status,
+            kind: finding.kind,
+            details: finding.details.clone(),
+            file: finding.file.clone(),
+            count: finding.count,
+            first_seen: finding.first_seen,
+            verification: finding.verification.clone(),
+            request: finding.request.clone(),
+            response: finding.response.clone(),
+            minimized: finding.minimized.clone(),
+        }
+    }
+}
+
+impl From<StoredFinding> for Finding {
+    fn from(stored: StoredFinding) -> Finding {
+        Finding {
+            path: stored.path,
+            method: stored.method,
+            operation_id: stored.operation_id,
+            status: stored.status,
+            severity: stored.kind.severity(),
+            classification: stored.kind.classification(),
+            kind: stored.kind,
+            details: stored.details,
+            file: stored.file,
+            count: stored.count,
+            first_seen: stored.first_seen,
+            verification: stored.verification,
+            request: stored.request,
+            response: stored.response,
+            minimized: stored.minimized,
+        }
+    }
+}
+
+/// Findings deduplicated by endpoint, method, status code and kind
+#[derive(Debug, Default)]
+pub struct Findings {
+    findings: BTreeMap<(String, String, u16, FindingKind), Finding>,
+}
+
+impl Findings {
+    /// Records a finding and returns it if it was not seen before
+    pub fn add(
+        &mut self,
+        payload: &Payload,
+        request: &Request,
+        response: &Response,
+        kind: FindingKind,
+        details: &[String],
+        file: &str,
+    ) -> Option<&mut Finding> {
+        let status = response.status;
+        let key = (
+            payload.path.to_string(),
+            payload.method.to_string(),
+            status,
+            kind,
+        );
+        if let Some(finding) = self.findings.get_mut(&key) {
+            finding.count += 1;
+            return None;
+        }
+
+        Some(self.findings.entry(key).or_insert(Finding {
+            path: payload.path.to_string(),
+            method: payload.method.to_string(),
+            operation_id: payload.operation_id.map(String::from),
+            status,
+            kind,
+            severity: kind.severity(),
+            classification: kind.classification(),
+            details: details.to_vec(),
+            file: file.to_string(),
+            count: 1,
+            first_seen: SystemTime::now(),
+            verification: None,
+            request: request.clone(),
+            response: response.clone(),
+            minimized: None,
+        }))
+    }
+
+    /// Records a finding found by another fuzzer, e.g. a worker of a distributed campaign, and
+    /// returns it if it was not seen before
+    pub fn merge(&mut self, finding: Finding) -> Option<&Finding> {
+        let key = (
+            finding.path.clone(),
+            finding.method.clone(),
+            finding.status,
+            finding.kind,
+        );
+        if let Some(known) = self.findings.get_mut(&key) {
+            known.count += finding.count;
+            // Verdicts of later replays replace the earlier ones
+            if finding
+                .verification
+                .as_ref()
+                .is_some_and(|verification| verification.later.is_some())
+            {
+                known.verification = finding.verification;
+            }
+            return None;
+        }
+        Some(self.findings.entry(key).or_insert(finding))
+    }
+
+    /// Finding of the kind for the operation with the status code, if it was recorded
+    pub fn get_mut(
+        &mut self,
+        path: &str,
+        method: &str,
+        status: u16,
+        kind: FindingKind,
+    ) -> Option<&mut Finding> {
+        self.findings
+            .get_mut(&(path.to_string(), method.to_string(), status, kind))
+    }
+
+    /// Whether the finding of the kind for the operation with the status code was recorded
+    pub fn is_known(&self, path: &str, method: &str, status: u16, kind: FindingKind) -> bool {
+        self.findings
+            .contains_key(&(path.to_string(), method.to_string(), status, kind))
+    }
+
+    /// Whether there is a finding of the kind for the operation, with any status code
+    pub fn contains(&self, path: &str, method: &str, kind: FindingKind) -> bool {
+        self.iter()
+            .any(|f| f.path == path && f.method == method && f.kind == kind)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Finding> {
+        self.findings.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.findings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Returns the reasons why the run should fail, if any
+    pub fn check_thresholds(
+        &self,
+        fail_on: Option<FailOn>,
+        max_findings: Option<usize>,
+    ) -> Vec<String> {
+        let mut failures = Vec::new();
+        if let Some(FailOn(severity)) = fail_on {
+            let count = self.iter().filter(|f| f.severity >= severity).count();
+            if count > 0 {
+                failures.push(format!(
+                    "{} finding(s) with severity {} or higher",
+                    count, severity
+                ));
+            }
+        }
+        if let Some(max) = max_findings {
+            if self.len() > max {
+                failures.push(format!(
+                    "{} findings exceed the maximum of {}",
+                    self.len(),
+                    max
+                ));
+            }
+        }
+        failures
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn later(reproduced: u32) -> LaterReplays {
+        LaterReplays {
+            interval: "10m".to_string(),
+            replays: 3,
+            reproduced,
+            statuses: vec![Some(500), None, Some(200)],
+        }
+    }
+
+    #[test]
+    fn classifies_replays() {
+        assert_eq!(Verification::new(3, 3).verdict, Verdict::Deterministic);
+        assert_eq!(Verification::new(3, 1).verdict, Verdict::Flaky);
+        assert_eq!(Verification::new(3, 0).verdict, Verdict::NotReproduced);
+    }
+
+    #[test]
+    fn classifies_later_replays() {
+        let mut verification = Verification::new(3, 0);
+        verification.replayed_later(later(1));
+        assert_eq!(verification.verdict, Verdict::Intermittent);
+        assert_eq!(verification.later, Some(later(1)));
+        assert_eq!(
+            verification.to_string(),
+            "intermittent, reproduced by 0 of 3 replays and 1 of 3 later ones over 10m"
+        );
+
+        let mut verification = Verification::new(3, 0);
+        verification.replayed_later(later(0));
+        assert_eq!(verification.verdict, Verdict::Noise);
+        assert_eq!(
+            verification.to_string(),
+            "environment noise, reproduced by 0 of 3 replays and 0 of 3 later ones over 10m"
+        );
+    }
+}
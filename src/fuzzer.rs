@@ -1,170 +1,3374 @@
 use std::{
-    collections::BTreeMap,
-    fs::{self, File},
-    u32,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use openapi_utils::ReferenceOrExt;
-use openapiv3::{OpenAPI, StatusCode};
-use serde_json::json;
-use ureq::OrAnyStatus;
+use openapiv3::OpenAPI;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
 use url::Url;
 
-use crate::payload::Payload;
+use crate::audit::HeaderPolicy;
+use crate::auth::Credentials;
+use crate::chaos;
+use crate::cleanup::{self, Cleanup, CleanupMode, Teardown};
+use crate::clockskew;
+use crate::concurrency::{Slot, Slots};
+use crate::config::OperationOverride;
+use crate::control::Control;
+use crate::cookies::{CookieJar, Isolation};
+use crate::corpus::{Corpus, Seed};
+use crate::cors;
+use crate::diff::{self, Ignored};
+use crate::disclosure;
+use crate::drift::{self, Drift};
+use crate::encryption::Encryption;
+use crate::examples;
+use crate::finding::{
+    Finding, FindingKind, FindingRecord, Findings, RunInfo, StoredFinding, Verdict, Verification,
+};
+use crate::headers;
+use crate::idempotency;
+use crate::identities::{Identities, Identity};
+use crate::ids::IdPool;
+use crate::invariants::Invariants;
+use crate::limits;
+use crate::logs::TargetLogs;
+use crate::maintenance::{self, Maintenance, Outage};
+use crate::minimize;
+use crate::oracle::{ExprOracle, Oracle};
+use crate::parameters;
+use crate::payload::{operations, retain_operations, Generator, Payload};
+use crate::plan::Plans;
+use crate::pool;
+use crate::preconditions::{self, Validators};
+use crate::ratelimit;
+use crate::readiness::{Readiness, WarmUp};
+use crate::recheck::Rechecks;
+use crate::redact::Redactor;
+use crate::reporter::Reporter;
+use crate::roundtrip;
+use crate::samples::{Samples, SAMPLES_FILE};
+use crate::scenario::Scenario;
+use crate::signing::Signing;
+use crate::sizes::SizeLimits;
+use crate::smuggling;
+use crate::snapshot::{Snapshot, SNAPSHOT_VERSION};
+use crate::spec;
+use crate::status::StatusRule;
+use crate::stream::{self, StreamBudget};
+use crate::transport::{redacted_url, HttpTransport, Request, Response, Transport};
+use crate::traversal;
 use crate::tui::Tui;
+use crate::validate;
+use crate::websocket::{self, Upgrading, WebSockets};
 
-#[derive(Debug, Default)]
+/// Directory the findings and the other results of a run are saved to, unless another is set
+pub const RESULTS_DIR: &str = "results";
+
+/// Number of responses of an operation needed before its average response time is used as
+/// the baseline for detecting slow responses
+const MIN_LATENCY_SAMPLES: u32 = 20;
+
+/// Interval at which a run waiting for the end of an outage checks whether it is quit
+const OUTAGE_POLL: Duration = Duration::from_secs(1);
+
+/// Number of successful requests of an idempotent operation that are repeated
+const IDEMPOTENCY_CHECKS: u32 = 5;
+
+/// Number of successful payloads of an operation kept as seeds when learning seeds
+const MAX_LEARNED_SEEDS: usize = 10;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Tries {
     pub total: u32,
     pub successful: u32,
+    /// Responses with a status code documented in the specification
+    pub documented: u32,
+    pub total_time: Duration,
+    /// Responses by status code
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub statuses: BTreeMap<u16, u32>,
 }
 
 impl Tries {
-    fn update(&mut self, success: bool) {
+    fn update(&mut self, success: bool, documented: bool, status: u16, elapsed: Duration) {
         self.total += 1;
+        self.total_time += elapsed;
+        *self.statuses.entry(status).or_default() += 1;
         if success {
             self.successful += 1;
         }
+        if documented {
+            self.documented += 1;
+        }
+    }
+
+    pub fn average_time(&self) -> Duration {
+        self.total_time / self.total.max(1)
+    }
+
+    fn merge(&mut self, other: &Tries) {
+        self.total += other.total;
+        self.successful += other.successful;
+        self.documented += other.documented;
+        self.total_time += other.total_time;
+        for (status, count) in other.statuses.iter() {
+            *self.statuses.entry(*status).or_default() += count;
+        }
     }
 }
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Stats {
-    pub ignored_status_codes: Vec<u16>,
     pub frequencies: BTreeMap<String, BTreeMap<String, Tries>>,
     pub total: u32,
+    /// Outages announced by the API, their responses are not counted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outages: Vec<Outage>,
 }
 
 impl Stats {
-    fn new(ignored_status_codes: Vec<u16>) -> Stats {
-        Stats {
-            ignored_status_codes,
-            total: 0,
-            ..Default::default()
-        }
-    }
-
-    fn update(&mut self, resp: &ureq::Response, payload: &Payload) {
+    fn update(&mut self, resp: &Response, payload: &Payload, finding: bool) {
         self.total += 1;
-        let success = !(self.ignored_status_codes.contains(&resp.status())
-            || (payload
-                .responses
-                .responses
-                .contains_key(&StatusCode::Code(resp.status()))
-                && resp.status() / 100 != 5));
+        let documented =
+            validate::is_documented(payload.responses, resp.status) && resp.status / 100 != 5;
 
         self.frequencies
             .entry(payload.path.to_string())
-            .or_insert_with(BTreeMap::new)
+            .or_default()
             .entry(payload.method.to_string())
-            .or_insert_with(Tries::default)
-            .update(success);
+            .or_default()
+            .update(finding, documented, resp.status, resp.elapsed);
+    }
+
+    /// Adds the requests counted by another fuzzer
+    pub fn merge(&mut self, other: &Stats) {
+        self.total += other.total;
+        for (path, methods) in other.frequencies.iter() {
+            for (method, tries) in methods.iter() {
+                self.frequencies
+                    .entry(path.clone())
+                    .or_default()
+                    .entry(method.clone())
+                    .or_default()
+                    .merge(tries);
+            }
+        }
+        for outage in other.outages.iter() {
+            maintenance::merge(&mut self.outages, outage);
+        }
     }
 }
 
+/// Checks deciding which responses are findings
+#[derive(Debug, Default)]
+pub struct Checks {
+    /// Status codes of responses that are never checked for findings, e.g. known noise
+    pub ignored_statuses: Vec<StatusRule>,
+    /// Status codes that are acceptable for the operations they apply to
+    pub allowed_statuses: Vec<StatusRule>,
+    /// Status codes that are a finding for the operations they apply to, even if documented
+    pub denied_statuses: Vec<StatusRule>,
+    /// Validate responses against the schemas and headers declared in the specification
+    pub validate_responses: bool,
+    /// Responses taking longer are a finding
+    pub slow_threshold: Option<Duration>,
+    /// Responses taking longer than this multiple of the average of the operation are a finding
+    pub slow_factor: Option<f64>,
+    /// Security headers expected in responses
+    pub header_policy: Option<HeaderPolicy>,
+    /// Probe each operation once with hostile origins for CORS misconfigurations
+    pub probe_cors: bool,
+    /// Repeat successful requests of idempotent operations and compare the responses
+    pub check_idempotency: bool,
+    /// Fields ignored when comparing responses, e.g. timestamps
+    pub ignored: Ignored,
+    /// Send this many requests to each operation once and report it if none is rate limited
+    pub rate_limit_burst: Option<u32>,
+    /// Another deployment of the API each request is sent to as well, differing responses
+    /// are reported as regressions
+    pub baseline_url: Option<Url>,
+    /// Probe each operation once with requests with conflicting and malformed framing
+    pub probe_smuggling: bool,
+    /// Probe the path parameters of each operation once with traversal sequences and
+    /// encoding attacks
+    pub probe_traversal: bool,
+    /// Probe each operation once with header values, header counts, URLs and bodies growing
+    /// past the usual limits of servers
+    pub probe_limits: bool,
+    /// Send each operation once slowly, truncated and aborted, see `chaos`
+    pub chaos: bool,
+    /// Probe each operation once with timestamps off by a minute, a day and at the epoch
+    pub probe_clock_skew: bool,
+    /// Probe each operation once with conditional and range headers, writes once validators
+    /// of their resource are known
+    pub probe_conditional: bool,
+    /// Report error responses leaking implementation details
+    pub detect_disclosure: bool,
+    /// Report anomalies of the response headers, see `headers`
+    pub check_headers: bool,
+    /// Expression responses are server errors when it holds, e.g. for APIs answering errors
+    /// with `200 OK`, overrides of the operations can replace it
+    pub server_error_when: Option<ExprOracle>,
+    /// Make payloads invalid on purpose and report operations accepting them
+    pub negative: bool,
+    /// Requests without a response for this long are given up on and reported as hangs,
+    /// shorter timeouts of operations are not
+    pub hang_threshold: Option<Duration>,
+    /// Send hung requests again with no other requests in flight, and report them only if
+    /// they hang again, the target may just be overloaded
+    pub retry_hangs: bool,
+    /// Replay the request of each new finding this many times before it is reported
+    pub reverify: u32,
+    /// Replay findings none of the replays reproduced as many times again spread over this
+    /// interval, see `recheck`
+    pub reverify_interval: Option<Duration>,
+    /// Leave out the parts of the request of each new finding not needed to trigger it, see
+    /// `minimize`
+    pub minimize: bool,
+}
+
+/// Limits after which the run ends, it runs until it is quit otherwise
+#[derive(Debug, Default)]
+pub struct Budget {
+    /// Number of fuzzed requests in the whole run
+    pub max_requests: Option<u32>,
+    /// Number of fuzzed requests of each operation
+    pub max_requests_per_operation: Option<u32>,
+    /// Time spent fuzzing in the whole run, runs of a campaign are resumed
+    pub max_duration: Option<Duration>,
+}
+
+/// Why fuzzing stopped
+#[derive(Debug, PartialEq, Eq)]
+enum End {
+    /// Quit by the user
+    Quit,
+    Exhausted,
+    Stopped,
+    /// The time slice of a campaign is over
+    SliceOver,
+}
+
+/// Finding detected in a response, saved to the results file
 #[derive(Debug)]
+struct Detected {
+    kind: FindingKind,
+    details: Vec<String>,
+    results_file: String,
+    verification: Option<Verification>,
+    /// Request and response with the secrets replaced, when they are redacted
+    redacted: Option<(Request, Response)>,
+    /// Smallest request found still triggering the finding, if it was minimized
+    minimized: Option<Request>,
+}
+
 pub struct Fuzzer {
     schema: OpenAPI,
+    /// Generators of the operations of the schema
+    plans: Plans,
     url: Url,
-    ignored_status_codes: Vec<u16>,
+    checks: Checks,
+    budget: Budget,
+    /// Options of operations from the config
+    overrides: Vec<OperationOverride>,
+    /// Time of the last request of each operation, for operations with a rate limit
+    last_requests: BTreeMap<(String, String), Instant>,
+    /// Requests per second to all operations together, e.g. of a queue of tags
+    rate_limit: Option<f64>,
+    last_request: Option<Instant>,
     extra_headers: Vec<(String, String)>,
+    corpus: Corpus,
+    run: RunInfo,
+    rng: StdRng,
     stats: Stats,
-    tui: Tui,
+    findings: Findings,
+    /// Number of idempotency checks done for each operation
+    idempotency_checks: BTreeMap<(String, String), u32>,
+    /// Validators of the responses of each resource, by its concrete path, for conditional
+    /// requests
+    validators: BTreeMap<String, Validators>,
+    /// Operations probed with conditional requests, by path and method
+    conditional_probed: BTreeSet<(String, String)>,
+    /// Operations paused during outages announced by the API
+    maintenance: Maintenance,
+    /// Sizes of requests the operations reject as too large
+    sizes: SizeLimits,
+    /// Findings replayed again later, scheduled while they are saved, which only borrows the
+    /// fuzzer
+    rechecks: Option<RefCell<Rechecks>>,
+    /// Headers disclosing the software of the server already reported, by name and value
+    disclosed: RefCell<BTreeSet<(String, String)>>,
+    reporters: Vec<Box<dyn Reporter>>,
+    /// State shared with the daemon running the fuzzer as a job
+    control: Option<Arc<Control>>,
+    /// Directory the findings are saved to
+    results_dir: String,
+    /// Number of finished rounds
+    rounds: u32,
+    /// Paths whose first round is done, the probes are sent in it
+    probed_paths: BTreeSet<String>,
+    /// Operations fuzzed from now on, all of them if `None`
+    selected: Option<BTreeSet<(String, String)>>,
+    /// Time spent fuzzing before the current run, runs of a campaign are resumed
+    fuzzing_time: Duration,
+    /// User defined findings
+    oracles: Vec<Box<dyn Oracle>>,
+    generators: Vec<Box<dyn Generator>>,
+    /// Streams of new deduplicated findings
+    subscribers: Vec<Sender<Finding>>,
+    /// Shared with the connections of benchmarks
+    transport: Arc<dyn Transport>,
+    /// Seeds learned from successful payloads of each operation, if enabled
+    learned_seeds: Option<BTreeMap<(String, String), Vec<Seed>>>,
+    /// Distinct successful responses of each operation, if enabled
+    samples: Option<Samples>,
+    /// Identifiers seen in successful responses, if reused
+    ids: Option<IdPool>,
+    /// Updates are sent as round trips of the fetched resources, see `roundtrip`
+    round_trip: bool,
+    /// Encryption of the finding, warm-up and snapshot files
+    encryption: Encryption,
+    /// Slots limiting the requests in flight to operations, shared with the other jobs
+    slots: Option<Slots>,
+    /// Credentials added to the requests before they are signed
+    credentials: Option<Arc<Credentials>>,
+    /// Timestamp and signature added to requests
+    signing: Option<Signing>,
+    identities: Option<Identities>,
+    invariants: Invariants,
+    redactor: Option<Redactor>,
+    /// Cookies set by the API, sent with the following requests
+    cookies: Option<CookieJar>,
+    /// Interval of snapshots and the file they are saved to
+    snapshot_every: Option<(Duration, PathBuf)>,
+    last_snapshot: Instant,
+    /// Endpoint polled until the service is ready before the first round
+    readiness: Option<Readiness>,
+    /// Send one valid request to each operation before the first round
+    warm_up: bool,
+    /// Responses of the warm-up, empty until it is done
+    warm_ups: Vec<WarmUp>,
+    /// Whether the readiness check and the warm-up are done
+    prepared: bool,
+    /// Resources created by the fuzzer and when they are deleted, if they are
+    cleanup: Option<(CleanupMode, Cleanup)>,
+    /// Requests sent at the end of the run
+    teardown: Vec<Teardown>,
+    /// Run after the operations in every round
+    scenarios: Vec<Scenario>,
+    /// Logs of the target stored with new findings
+    target_logs: Option<TargetLogs>,
+    /// Budget of the responses of operations declaring streams
+    stream_budget: StreamBudget,
+    /// Operations upgraded to WebSocket
+    websockets: Option<WebSockets>,
+}
+
+/// Sets up the fuzzer, only the specification and the url of the API are required, e.g.
+/// `Fuzzer::builder(schema, url).budget(budget).header("authorization", "Bearer ...").build()`
+pub struct FuzzerBuilder {
+    schema: OpenAPI,
+    url: Url,
+    checks: Checks,
+    budget: Budget,
+    overrides: Vec<OperationOverride>,
+    extra_headers: Vec<(String, String)>,
+    corpus: Corpus,
+    run: Option<RunInfo>,
+    reporters: Vec<Box<dyn Reporter>>,
+    control: Option<Arc<Control>>,
+    results_dir: String,
+    oracles: Vec<Box<dyn Oracle>>,
+    generators: Vec<Box<dyn Generator>>,
+    learn_seeds: bool,
+    success_samples: usize,
+    reuse_ids: bool,
+    round_trip: bool,
+    plain_strings: bool,
+    encryption: Encryption,
+    rate_limit: Option<f64>,
+    slots: Option<Slots>,
+    credentials: Option<Arc<Credentials>>,
+    signing: Option<Signing>,
+    identities: Vec<Identity>,
+    invariants: Invariants,
+    redactor: Option<Redactor>,
+    cookies: Option<Isolation>,
+    transport: Box<dyn Transport>,
+    snapshot_every: Option<Duration>,
+    restore: Option<Snapshot>,
+    readiness: Option<Readiness>,
+    warm_up: bool,
+    cleanup: Option<CleanupMode>,
+    teardown: Vec<Teardown>,
+    scenarios: Vec<Scenario>,
+    target_logs: Option<TargetLogs>,
+    stream_budget: StreamBudget,
+}
+
+impl FuzzerBuilder {
+    pub fn checks(mut self, checks: Checks) -> FuzzerBuilder {
+        self.checks = checks;
+        self
+    }
+
+    pub fn budget(mut self, budget: Budget) -> FuzzerBuilder {
+        self.budget = budget;
+        self
+    }
+
+    /// Options of the operations, later ones take precedence
+    pub fn overrides(mut self, overrides: Vec<OperationOverride>) -> FuzzerBuilder {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Header sent with every request
+    pub fn header(mut self, name: &str, value: &str) -> FuzzerBuilder {
+        self.extra_headers
+            .push((name.to_lowercase(), value.to_string()));
+        self
+    }
+
+    /// Requests (e.g. from a Postman collection) used as seeds of the payloads
+    pub fn corpus(mut self, corpus: Corpus) -> FuzzerBuilder {
+        self.corpus = corpus;
+        self
+    }
+
+    /// Adds a seed of the operation to the corpus
+    pub fn seed(mut self, method: &str, path: &str, seed: Seed) -> FuzzerBuilder {
+        self.corpus.add(method, path, seed);
+        self
+    }
+
+    /// Fuzzes only the operations with the methods and paths, e.g. a batch of a distributed
+    /// campaign
+    pub fn operations(mut self, chosen: &[(String, String)]) -> FuzzerBuilder {
+        retain_operations(&mut self.schema, chosen);
+        self
+    }
+
+    /// Sends the requests with the transport instead of over HTTP, e.g. to an in-process
+    /// service
+    pub fn transport(mut self, transport: impl Transport + 'static) -> FuzzerBuilder {
+        self.transport = Box::new(transport);
+        self
+    }
+
+    /// Saves a snapshot of the state to `snapshot.json` in the results directory after the
+    /// round in which the interval passed and at the end of the run, replacing the previous one
+    pub fn snapshot_every(mut self, interval: Duration) -> FuzzerBuilder {
+        self.snapshot_every = Some(interval);
+        self
+    }
+
+    /// Continues the run the snapshot was taken of, the seed of the run is taken from it
+    pub fn restore(mut self, snapshot: Snapshot) -> FuzzerBuilder {
+        self.restore = Some(snapshot);
+        self
+    }
+
+    /// Keeps successful payloads of the operations as seeds, see `Fuzzer::take_seeds`
+    pub fn learn_seeds(mut self) -> FuzzerBuilder {
+        self.learn_seeds = true;
+        self
+    }
+
+    /// Keeps up to the number of distinct successful responses of each operation, see
+    /// `Samples`
+    pub fn success_samples(mut self, max_per_operation: usize) -> FuzzerBuilder {
+        self.success_samples = max_per_operation;
+        self
+    }
+
+    /// Substitutes identifiers seen in successful responses into the parameters with matching
+    /// names, see `IdPool`
+    pub fn reuse_ids(mut self) -> FuzzerBuilder {
+        self.reuse_ids = true;
+        self
+    }
+
+    /// Sends updates as changed representations of the resources fetched with the `GET` of
+    /// their paths, see `roundtrip`
+    pub fn round_trip(mut self) -> FuzzerBuilder {
+        self.round_trip = true;
+        self
+    }
+
+    /// Generates random strings for fields recognized by their names, see `smart`
+    pub fn plain_strings(mut self) -> FuzzerBuilder {
+        self.plain_strings = true;
+        self
+    }
+
+    /// Limits the requests per second to all operations together
+    pub fn rate_limit(mut self, rate_limit: f64) -> FuzzerBuilder {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Encrypts the files of the findings, the warm-up and the snapshots
+    pub fn encryption(mut self, encryption: Encryption) -> FuzzerBuilder {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Waits for a slot of the operation before sending a request to an operation with a
+    /// `concurrency` limit, the slots are shared by the processes of the jobs
+    pub fn concurrency_slots(mut self, slots: Slots) -> FuzzerBuilder {
+        self.slots = Some(slots);
+        self
+    }
+
+    /// Adds the credentials to the requests, before they are signed
+    pub fn credentials(mut self, credentials: Arc<Credentials>) -> FuzzerBuilder {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Stamps the requests with the current time and signs them before they are sent
+    pub fn signing(mut self, signing: Signing) -> FuzzerBuilder {
+        self.signing = Some(signing);
+        self
+    }
+
+    /// Sends the requests as each of the identities in turn and reads the resources created
+    /// by one of them as the others, with more than one identity
+    pub fn identities(mut self, identities: Vec<Identity>) -> FuzzerBuilder {
+        self.identities = identities;
+        self
+    }
+
+    /// Follows the operations the invariants hold after with the requests checking them
+    pub fn invariants(mut self, invariants: Invariants) -> FuzzerBuilder {
+        self.invariants = invariants;
+        self
+    }
+
+    /// Replaces credentials and sensitive fields in the stored findings with placeholders
+    pub fn redactor(mut self, redactor: Redactor) -> FuzzerBuilder {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Keeps the cookies set by the API and sends them with the following requests, shared as
+    /// the isolation says
+    pub fn cookie_jar(mut self, isolation: Isolation) -> FuzzerBuilder {
+        self.cookies = Some(isolation);
+        self
+    }
+
+    /// Information about the run stored with the findings, the hash of the specification is
+    /// empty and the seed random by default
+    pub fn run_info(mut self, run: RunInfo) -> FuzzerBuilder {
+        self.run = Some(run);
+        self
+    }
+
+    /// Adds a target of the findings, all of them are reported to
+    pub fn reporter(mut self, reporter: Box<dyn Reporter>) -> FuzzerBuilder {
+        self.reporters.push(reporter);
+        self
+    }
+
+    /// State shared with the daemon running the fuzzer as a job
+    pub fn control(mut self, control: Arc<Control>) -> FuzzerBuilder {
+        self.control = Some(control);
+        self
+    }
+
+    /// Directory the findings are saved to, `results` by default
+    pub fn results_dir(mut self, results_dir: impl Into<String>) -> FuzzerBuilder {
+        self.results_dir = results_dir.into();
+        self
+    }
+
+    /// Polls the endpoint until the service is ready before fuzzing
+    pub fn wait_for(mut self, readiness: Readiness) -> FuzzerBuilder {
+        self.readiness = Some(readiness);
+        self
+    }
+
+    /// Sends one valid request to each operation before fuzzing, so that cold starts are not
+    /// reported as findings, the responses are saved to `warm-up.json` in the results directory
+    pub fn warm_up(mut self) -> FuzzerBuilder {
+        self.warm_up = true;
+        self
+    }
+
+    /// Deletes the resources created by successful `POST` requests at the end of the run or
+    /// after every round, with the `DELETE` operation of the item path below the collection
+    pub fn cleanup(mut self, mode: CleanupMode) -> FuzzerBuilder {
+        self.cleanup = Some(mode);
+        self
+    }
+
+    /// Requests sent at the end of the run, replacing the ones set before
+    pub fn teardown(mut self, requests: Vec<Teardown>) -> FuzzerBuilder {
+        self.teardown = requests;
+        self
+    }
+
+    /// Runs the steps of the scenario in every round while mutating its chosen fields
+    pub fn scenario(mut self, scenario: Scenario) -> FuzzerBuilder {
+        self.scenarios.push(scenario);
+        self
+    }
+
+    /// Stores the lines of the logs of the target emitted around the request with each new
+    /// finding
+    pub fn target_logs(mut self, logs: TargetLogs) -> FuzzerBuilder {
+        self.target_logs = Some(logs);
+        self
+    }
+
+    /// How long and how much of the streaming responses of operations declaring streams, e.g.
+    /// `text/event-stream`, is read, 5s and 1 MiB by default
+    pub fn stream_budget(mut self, budget: StreamBudget) -> FuzzerBuilder {
+        self.stream_budget = budget;
+        self
+    }
+
+    pub fn oracle(mut self, oracle: impl Oracle + 'static) -> FuzzerBuilder {
+        self.oracles.push(Box::new(oracle));
+        self
+    }
+
+    /// Generator applied to every payload, in the order they are added
+    pub fn generator(mut self, generator: impl Generator + 'static) -> FuzzerBuilder {
+        self.generators.push(Box::new(generator));
+        self
+    }
+
+    pub fn build(self) -> Fuzzer {
+        let cleanup = self.cleanup.map(|mode| (mode, Cleanup::new(&self.schema)));
+        let plans = Plans::new(&self.schema, !self.plain_strings);
+        let mut run = self.run.unwrap_or_else(|| RunInfo::new("", rand::random()));
+        if let Some(snapshot) = &self.restore {
+            if snapshot.spec_hash != run.spec_hash {
+                warn!("the specification changed since the snapshot was taken");
+            }
+            run.run_seed = snapshot.run_seed;
+        }
+        let snapshot_file =
+            Path::new(&self.results_dir).join(self.encryption.path("snapshot.json"));
+        let snapshot_every = self
+            .snapshot_every
+            .map(|interval| (interval, snapshot_file));
+        let samples = match self.success_samples {
+            0 => None,
+            max_per_operation => {
+                let mut samples = Samples::new(max_per_operation);
+                let file = Path::new(&self.results_dir).join(self.encryption.path(SAMPLES_FILE));
+                if let Err(e) = samples.load(&file, &self.encryption) {
+                    warn!("unable to load the samples of earlier runs: {:#}", e);
+                }
+                Some(samples)
+            }
+        };
+        let identities = match self.identities.len() {
+            0 | 1 => None,
+            _ => Some(Identities::new(&self.schema, self.identities)),
+        };
+        let websockets = WebSockets::new(&self.schema);
+        let transport: Arc<dyn Transport> = match websockets {
+            Some(_) => Arc::new(Upgrading {
+                transport: self.transport,
+            }),
+            None => Arc::from(self.transport),
+        };
+        let replays = self.checks.reverify;
+        let rechecks = self
+            .checks
+            .reverify_interval
+            .map(|interval| RefCell::new(Rechecks::new(interval, replays)));
+        let mut fuzzer = Fuzzer {
+            schema: self.schema,
+            plans,
+            url: self.url,
+            checks: self.checks,
+            budget: self.budget,
+            overrides: self.overrides,
+            last_requests: BTreeMap::new(),
+            rate_limit: self.rate_limit,
+            last_request: None,
+            extra_headers: self.extra_headers,
+            corpus: self.corpus,
+            rng: StdRng::seed_from_u64(run.run_seed),
+            run,
+            stats: Stats::default(),
+            findings: Findings::default(),
+            idempotency_checks: BTreeMap::new(),
+            validators: BTreeMap::new(),
+            conditional_probed: BTreeSet::new(),
+            maintenance: Maintenance::default(),
+            sizes: SizeLimits::default(),
+            rechecks,
+            disclosed: RefCell::default(),
+            reporters: self.reporters,
+            control: self.control,
+            results_dir: self.results_dir,
+            rounds: 0,
+            probed_paths: BTreeSet::new(),
+            selected: None,
+            fuzzing_time: Duration::ZERO,
+            oracles: self.oracles,
+            generators: self.generators,
+            subscribers: Vec::new(),
+            learned_seeds: self.learn_seeds.then(BTreeMap::new),
+            samples,
+            ids: self.reuse_ids.then(IdPool::default),
+            round_trip: self.round_trip,
+            encryption: self.encryption,
+            slots: self.slots,
+            credentials: self.credentials,
+            signing: self.signing,
+            identities,
+            invariants: self.invariants,
+            redactor: self.redactor,
+            cookies: self.cookies.map(CookieJar::new),
+            transport,
+            snapshot_every,
+            last_snapshot: Instant::now(),
+            readiness: self.readiness,
+            warm_up: self.warm_up,
+            warm_ups: Vec::new(),
+            prepared: false,
+            cleanup,
+            teardown: self.teardown,
+            scenarios: self.scenarios,
+            target_logs: self.target_logs,
+            stream_budget: self.stream_budget,
+            websockets,
+        };
+        if let Some(snapshot) = self.restore {
+            fuzzer.rng = StdRng::seed_from_u64(snapshot.rng_seed);
+            fuzzer.rounds = snapshot.rounds;
+            if snapshot.rounds > 0 {
+                fuzzer.probed_paths = fuzzer.schema.paths.keys().cloned().collect();
+            }
+            fuzzer.fuzzing_time = snapshot.fuzzing_time;
+            fuzzer.stats = snapshot.stats;
+            for stored in snapshot.findings {
+                fuzzer.findings.merge(Finding::from(stored));
+            }
+            for (method, path, seed) in snapshot.corpus {
+                fuzzer.corpus.add(&method, &path, seed);
+            }
+            fuzzer.idempotency_checks = snapshot
+                .idempotency_checks
+                .into_iter()
+                .map(|(path, method, checks)| ((path, method), checks))
+                .collect();
+        }
+        fuzzer
+    }
 }
 
 impl Fuzzer {
-    pub fn new(
-        schema: OpenAPI,
-        url: Url,
-        ignored_status_codes: Vec<u16>,
-        extra_headers: Vec<(String, String)>,
-    ) -> Fuzzer {
-        Fuzzer {
-            schema,
+    /// References in the specification are resolved, so it may be passed as parsed
+    pub fn builder(mut schema: OpenAPI, url: Url) -> FuzzerBuilder {
+        websocket::document(&mut schema);
+        parameters::inherit(&mut schema);
+        FuzzerBuilder {
+            schema: spec::resolve(schema),
             url,
-            extra_headers,
-            ignored_status_codes: ignored_status_codes.clone(),
-            stats: Stats::new(ignored_status_codes),
-            tui: Tui::new().expect("unable to setup tui"),
+            checks: Checks::default(),
+            budget: Budget::default(),
+            overrides: Vec::new(),
+            extra_headers: Vec::new(),
+            corpus: Corpus::default(),
+            run: None,
+            reporters: Vec::new(),
+            control: None,
+            results_dir: RESULTS_DIR.to_string(),
+            oracles: Vec::new(),
+            generators: Vec::new(),
+            learn_seeds: false,
+            success_samples: 0,
+            reuse_ids: false,
+            round_trip: false,
+            plain_strings: false,
+            encryption: Encryption::default(),
+            rate_limit: None,
+            slots: None,
+            credentials: None,
+            signing: None,
+            identities: Vec::new(),
+            invariants: Invariants::default(),
+            redactor: None,
+            cookies: None,
+            transport: Box::new(HttpTransport),
+            snapshot_every: None,
+            restore: None,
+            readiness: None,
+            warm_up: false,
+            cleanup: None,
+            teardown: Vec::new(),
+            scenarios: Vec::new(),
+            target_logs: None,
+            stream_budget: StreamBudget::default(),
+        }
+    }
+
+    /// Stream of the new deduplicated findings, e.g. to fail a test as soon as something is
+    /// found while the fuzzer runs in another thread
+    pub fn subscribe(&mut self) -> Receiver<Finding> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Records a finding of another fuzzer, e.g. a worker of a distributed campaign, and
+    /// notifies about it if it is new
+    pub fn merge(&mut self, finding: Finding) -> Result<()> {
+        if let Some(finding) = self.findings.merge(finding) {
+            info!(kind = %finding.kind, method = %finding.method, path = %finding.path, status = finding.status, "new finding");
+            self.subscribers
+                .retain(|subscriber| subscriber.send(finding.clone()).is_ok());
+            for reporter in self.reporters.iter_mut() {
+                reporter.finding(finding)?;
+            }
         }
+        Ok(())
+    }
+
+    /// Adds the requests counted by another fuzzer
+    pub fn merge_stats(&mut self, stats: &Stats) {
+        self.stats.merge(stats);
+    }
+
+    /// State of the fuzzer, the random generator is reset to the seed stored in the snapshot
+    /// so that both the run and a restored run continue the same way
+    pub fn snapshot(&mut self) -> Snapshot {
+        let rng_seed = self.rng.gen();
+        self.rng = StdRng::seed_from_u64(rng_seed);
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            fuzzer_version: self.run.fuzzer_version.to_string(),
+            spec_hash: self.run.spec_hash.clone(),
+            run_seed: self.run.run_seed,
+            rng_seed,
+            rounds: self.rounds,
+            fuzzing_time: self.fuzzing_time,
+            stats: self.stats.clone(),
+            findings: self.findings.iter().map(StoredFinding::from).collect(),
+            corpus: self
+                .corpus
+                .entries()
+                .map(|(method, path, seed)| (method.to_string(), path.to_string(), seed.clone()))
+                .collect(),
+            idempotency_checks: self
+                .idempotency_checks
+                .iter()
+                .map(|((path, method), checks)| (path.clone(), method.clone(), *checks))
+                .collect(),
+        }
+    }
+
+    /// Saves a snapshot if snapshots are enabled, `elapsed` is the fuzzing time not counted
+    /// yet
+    fn save_snapshot(&mut self, elapsed: Duration) -> Result<()> {
+        let file = match &self.snapshot_every {
+            Some((_, file)) => file.clone(),
+            None => return Ok(()),
+        };
+        let mut snapshot = self.snapshot();
+        snapshot.fuzzing_time += elapsed;
+        snapshot.save(&file, &self.encryption)?;
+        self.last_snapshot = Instant::now();
+        info!(file = %file.display(), rounds = self.rounds, "snapshot saved");
+        Ok(())
+    }
+
+    /// Adds a seed of the operation to the corpus while the fuzzer runs, e.g. one learned by
+    /// another fuzzer
+    pub fn add_seed(&mut self, method: &str, path: &str, seed: Seed) {
+        self.corpus.add(method, path, seed);
+    }
+
+    /// Seeds learned since the last call, with the methods and paths of their operations
+    pub fn take_seeds(&mut self) -> Vec<(String, String, Seed)> {
+        let learned = match &mut self.learned_seeds {
+            Some(learned) => std::mem::take(learned),
+            None => return Vec::new(),
+        };
+        learned
+            .into_iter()
+            .flat_map(|((method, path), seeds)| {
+                seeds
+                    .into_iter()
+                    .map(move |seed| (method.clone(), path.clone(), seed))
+            })
+            .collect()
+    }
+
+    /// Fuzzes only the operations with the methods and paths from now on with the budget, e.g.
+    /// the batches of a distributed campaign handed to the same fuzzer one after another
+    pub fn select(&mut self, operations: &[(String, String)], budget: Budget) {
+        self.selected = Some(operations.iter().cloned().collect());
+        self.budget = budget;
+    }
+
+    /// Stats and findings since the last call, both start over, e.g. after a batch of a
+    /// distributed campaign
+    pub fn take_results(&mut self) -> (Stats, Findings) {
+        (
+            std::mem::take(&mut self.stats),
+            std::mem::take(&mut self.findings),
+        )
+    }
+
+    fn is_selected(&self, method: &str, path: &str) -> bool {
+        self.selected.as_ref().is_none_or(|selected| {
+            selected
+                .iter()
+                .any(|(m, p)| m.eq_ignore_ascii_case(method) && p == path)
+        })
+    }
+
+    /// Number of fuzzed requests sent to the operation
+    fn requests(&self, payload: &Payload) -> u32 {
+        self.stats
+            .frequencies
+            .get(payload.path)
+            .and_then(|methods| methods.get(payload.method))
+            .map_or(0, |tries| tries.total)
+    }
+
+    fn budget_exhausted(&self, start: Instant) -> bool {
+        self.budget
+            .max_requests
+            .is_some_and(|max| self.stats.total >= max)
+            || self
+                .budget
+                .max_duration
+                .is_some_and(|max| self.fuzzing_time + start.elapsed() >= max)
+    }
+
+    /// Waits for the remaining later replays of findings and classifies them
+    pub fn finish_rechecks(&mut self) -> Result<()> {
+        match &mut self.rechecks {
+            Some(rechecks) => rechecks.get_mut().finish(
+                self.transport.as_ref(),
+                self.signing.as_ref(),
+                &mut self.findings,
+                &self.encryption,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Logs the end of the run, cleans up and sends the summary to the webhook
+    pub fn finish(&mut self) -> Result<()> {
+        self.finish_rechecks()?;
+        if let Some(logs) = &self.target_logs {
+            logs.finish(&self.encryption)?;
+        }
+        if let Some((_, cleanup)) = &mut self.cleanup {
+            cleanup.delete_created(self.transport.as_ref(), &self.extra_headers);
+        }
+        cleanup::teardown(
+            self.transport.as_ref(),
+            &self.url,
+            &self.extra_headers,
+            &self.teardown,
+        );
+        info!(
+            requests = self.stats.total,
+            findings = self.findings.iter().count(),
+            "run finished"
+        );
+        if let Some(samples) = &self.samples {
+            let file = Path::new(&self.results_dir).join(self.encryption.path(SAMPLES_FILE));
+            samples
+                .save(&file, &self.encryption)
+                .with_context(|| format!("unable to save {}", file.display()))?;
+        }
+        // A failing reporter, e.g. an unreachable webhook, leaves the others and the thresholds
+        for reporter in self.reporters.iter_mut() {
+            if let Err(e) = reporter.finish(&self.findings, self.stats.total) {
+                warn!("unable to report the findings: {:#}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the requests of the first rounds without sending them, grouped by operation
+    pub fn dry_run(&mut self, rounds: usize) -> Result<()> {
+        let mut planned: Vec<(&str, &str, Option<&str>, Vec<String>)> = Vec::new();
+        for _ in 0..rounds {
+            for path in self.schema.paths.keys() {
+                for mut payload in Payload::for_all_methods(
+                    &self.url,
+                    path,
+                    &self.plans,
+                    &self.extra_headers,
+                    &self.corpus,
+                    self.checks.negative,
+                    &mut self.rng,
+                )? {
+                    if let Some(ids) = &self.ids {
+                        ids.substitute(&mut payload);
+                    }
+                    apply_overrides(&self.overrides, &mut payload);
+                    generate(&self.generators, &mut payload);
+                    let mut raw = payload.to_request()?.to_raw()?.replace("\r\n", "\n");
+                    if let Some(violation) = &payload.violation {
+                        raw = format!("# invalid on purpose: {}\n{}", violation, raw);
+                    }
+                    match planned
+                        .iter_mut()
+                        .find(|(p, method, _, _)| *p == payload.path && *method == payload.method)
+                    {
+                        Some((_, _, _, requests)) => requests.push(raw),
+                        None => planned.push((
+                            payload.path,
+                            payload.method,
+                            payload.operation_id,
+                            vec![raw],
+                        )),
+                    }
+                }
+            }
+        }
+
+        for (path, method, operation_id, requests) in planned {
+            match operation_id {
+                Some(operation_id) => println!("### {} {} ({})\n", method, path, operation_id),
+                None => println!("### {} {}\n", method, path),
+            }
+            for raw in requests {
+                println!("{}\n", raw.trim_end());
+            }
+        }
+        Ok(())
     }
 
     pub fn run(&mut self) -> Result<()> {
+        let mut tui = Tui::new().context("unable to setup tui")?;
+        let end = self.fuzz(None, |stats, message| {
+            tui.display(stats, message).context("unable to draw tui")
+        })?;
+        if end != End::Quit {
+            tui.close()?;
+        }
+        self.finish()
+    }
+
+    /// Runs without the TUI until the budget is exhausted or the job is stopped
+    pub fn run_headless(&mut self) -> Result<()> {
+        self.fuzz(None, |_, _| Ok(false))?;
+        self.finish()
+    }
+
+    /// Runs without the TUI until the budget is exhausted, the run is not finished so that the
+    /// fuzzer goes on with the next batch of a distributed campaign
+    pub fn run_batch(&mut self) -> Result<()> {
+        self.fuzz(None, |_, _| Ok(false)).map(|_| ())
+    }
+
+    /// Runs without the TUI for the time slice, rounds are not interrupted so it may take a bit
+    /// longer. Returns whether the budget is exhausted.
+    pub fn run_slice(&mut self, slice: Duration) -> Result<bool> {
+        let end = self.fuzz(Some(Instant::now() + slice), |_, _| Ok(false))?;
+        Ok(end == End::Exhausted)
+    }
+
+    /// Fuzzes the operations round after round until the deadline, `display` is called after
+    /// every round and returns whether to quit
+    fn fuzz(
+        &mut self,
+        deadline: Option<Instant>,
+        display: impl FnMut(&Stats, &Option<String>) -> Result<bool>,
+    ) -> Result<End> {
+        if self.rounds == 0 {
+            info!(url = %self.url, seed = self.run.run_seed, operations = self.operations(), "run started");
+        }
+        if !self.prepared {
+            if let Some(readiness) = &self.readiness {
+                readiness.wait()?;
+            }
+            if self.warm_up && self.rounds == 0 {
+                self.warm_up_operations()?;
+            }
+            self.prepared = true;
+        }
+        let start = Instant::now();
+        let end = self.fuzz_rounds(start, deadline, display);
+        self.fuzzing_time += start.elapsed();
+        // The last snapshot has the final state, e.g. for analysis after the run
+        if end.is_ok() {
+            self.save_snapshot(Duration::ZERO)?;
+        }
+        end
+    }
+
+    /// Sends the documented examples of each operation and checks the responses against the
+    /// specification, operations without examples of their required inputs are skipped
+    pub fn check_examples(&mut self) -> Vec<examples::Check> {
+        fn pairs(params: &[(String, String)]) -> Vec<(&str, String)> {
+            params
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.clone()))
+                .collect()
+        }
+        let mut checks = Vec::new();
+        for (path, item) in self.schema.paths.iter() {
+            for (method, operation) in operations(item.to_item_ref()) {
+                let (operation, plan) = match (operation, self.plans.get(method, path)) {
+                    (Some(operation), Some(plan)) => (operation, plan),
+                    _ => continue,
+                };
+                let requests = match examples::of_operation(&self.schema, operation) {
+                    Ok(requests) => requests,
+                    Err(reason) => {
+                        checks.push(examples::Check::skipped(method, path, reason));
+                        continue;
+                    }
+                };
+                for example in requests.iter() {
+                    let mut payload = Payload {
+                        url: &self.url,
+                        method,
+                        path,
+                        operation_id: plan.operation_id.as_deref(),
+                        query_params: pairs(&example.query_params),
+                        path_params: pairs(&example.path_params),
+                        headers: pairs(&example.headers),
+                        body: example.body.iter().cloned().collect(),
+                        raw_body: None,
+                        seed: 0,
+                        responses: &plan.responses,
+                        idempotent: plan.idempotent,
+                        violation: None,
+                        negative: false,
+                        timeout: None,
+                        endpoint: None,
+                    };
+                    for (name, value) in self.extra_headers.iter() {
+                        if !payload
+                            .headers
+                            .iter()
+                            .any(|(h, _)| h.eq_ignore_ascii_case(name))
+                        {
+                            payload.headers.push((name, value.clone()));
+                        }
+                    }
+                    apply_overrides(&self.overrides, &mut payload);
+                    let response = self.send_request(&payload).map(|(_, response)| response);
+                    checks.push(examples::Check::new(
+                        method,
+                        path,
+                        example.name.clone(),
+                        &plan.responses,
+                        response,
+                    ));
+                }
+            }
+        }
+        checks
+    }
+
+    /// Sends valid requests to each operation for the rounds and probes methods and paths that
+    /// are not in the specification, the methods changing state if `unsafe_methods`, and
+    /// returns how the target differs from it
+    pub fn check_drift(&mut self, rounds: u32, unsafe_methods: bool) -> Result<Vec<Drift>> {
+        let mut drifts = BTreeSet::new();
+        let mut sent: Vec<(String, Request)> = Vec::new();
+        for _ in 0..rounds.max(1) {
+            for path in self.schema.paths.keys() {
+                for mut payload in Payload::for_all_methods(
+                    &self.url,
+                    path,
+                    &self.plans,
+                    &self.extra_headers,
+                    &self.corpus,
+                    false,
+                    &mut self.rng,
+                )? {
+                    if let Some(ids) = &self.ids {
+                        ids.substitute(&mut payload);
+                    }
+                    apply_overrides(&self.overrides, &mut payload);
+                    generate(&self.generators, &mut payload);
+                    throttle(
+                        &self.overrides,
+                        &mut self.last_requests,
+                        self.rate_limit,
+                        &mut self.last_request,
+                        &payload,
+                    );
+                    match self.send_request(&payload) {
+                        Ok((request, resp)) => {
+                            if let (Some(ids), 2) = (&mut self.ids, resp.status / 100) {
+                                ids.observe(payload.path, &resp.body);
+                            }
+                            drifts.extend(drift::of_response(
+                                payload.method,
+                                payload.path,
+                                payload.responses,
+                                &resp,
+                            ));
+                            if !sent.iter().any(|(sent, _)| sent == path) {
+                                sent.push((path.clone(), request));
+                            }
+                        }
+                        Err(e) => warn!(
+                            method = payload.method,
+                            path = payload.path,
+                            "request failed: {:#}",
+                            e
+                        ),
+                    }
+                    payload.recycle();
+                }
+            }
+        }
+
+        // Each method is compared with how the target answers it on a path that cannot exist
+        let probes = drift::probes(&self.schema, &self.url, &sent, unsafe_methods);
+        let mut controls = BTreeMap::new();
+        for probe in probes.iter() {
+            let method = probe.request.method.as_str();
+            if controls.contains_key(method) {
+                continue;
+            }
+            let control = drift::control(&self.url, &probe.request, method)?;
+            let response = ratelimit::send(self.transport.as_ref(), &control).ok();
+            controls.insert(method.to_string(), response);
+        }
+        for probe in probes.iter() {
+            let control = controls.get(&probe.request.method).and_then(Option::as_ref);
+            match ratelimit::send(self.transport.as_ref(), &probe.request) {
+                Ok(resp) => drifts.extend(drift::of_probe(probe, &resp, control)),
+                Err(e) => debug!(
+                    method = probe.request.method.as_str(),
+                    path = probe.path.as_str(),
+                    "probe failed: {:#}",
+                    e
+                ),
+            }
+        }
+        Ok(drifts.into_iter().collect())
+    }
+
+    /// One valid request to each operation with its method and path, signed when requests are
+    /// signed, e.g. for benchmarks. Payloads are not made invalid on purpose.
+    pub fn valid_requests(&mut self) -> Result<Vec<(String, Request)>> {
+        let mut requests = Vec::new();
+        for path in self.schema.paths.keys() {
+            for mut payload in Payload::for_all_methods(
+                &self.url,
+                path,
+                &self.plans,
+                &self.extra_headers,
+                &self.corpus,
+                false,
+                &mut self.rng,
+            )? {
+                if let Some(ids) = &self.ids {
+                    ids.substitute(&mut payload);
+                }
+                apply_overrides(&self.overrides, &mut payload);
+                generate(&self.generators, &mut payload);
+                let mut request = payload.to_request()?;
+                self.sign(&mut request)?;
+                requests.push((format!("{} {}", payload.method, payload.path), request));
+                payload.recycle();
+            }
+        }
+        Ok(requests)
+    }
+
+    /// Sends one valid request to each operation without checking the responses or counting
+    /// them, and saves the responses as the baseline of the run
+    fn warm_up_operations(&mut self) -> Result<()> {
+        let mut warm_ups = Vec::new();
+        for path in self.schema.paths.keys() {
+            for mut payload in Payload::for_all_methods(
+                &self.url,
+                path,
+                &self.plans,
+                &self.extra_headers,
+                &self.corpus,
+                false,
+                &mut self.rng,
+            )? {
+                if let Some(ids) = &self.ids {
+                    ids.substitute(&mut payload);
+                }
+                apply_overrides(&self.overrides, &mut payload);
+                generate(&self.generators, &mut payload);
+                throttle(
+                    &self.overrides,
+                    &mut self.last_requests,
+                    self.rate_limit,
+                    &mut self.last_request,
+                    &payload,
+                );
+                match self.send_request(&payload) {
+                    Ok((request, response)) => {
+                        if let Some((_, cleanup)) = &mut self.cleanup {
+                            cleanup.track(&self.url, payload.method, payload.path, &response);
+                        }
+                        if let (Some(ids), 2) = (&mut self.ids, response.status / 100) {
+                            ids.observe(payload.path, &response.body);
+                        }
+                        if response.status / 100 != 2 {
+                            debug!(
+                                method = payload.method,
+                                path = payload.path,
+                                status = response.status,
+                                "unsuccessful warm-up request"
+                            );
+                        }
+                        warm_ups.push(WarmUp {
+                            method: payload.method.to_string(),
+                            path: payload.path.to_string(),
+                            request,
+                            response,
+                        });
+                    }
+                    Err(e) => warn!(
+                        method = payload.method,
+                        path = payload.path,
+                        "warm-up request failed: {:#}",
+                        e
+                    ),
+                }
+            }
+        }
+
+        let successful = warm_ups
+            .iter()
+            .filter(|warm_up| warm_up.response.status / 100 == 2)
+            .count();
+        info!(requests = warm_ups.len(), successful, "warm-up done");
+        fs::create_dir_all(&self.results_dir)?;
+        let file = Path::new(&self.results_dir).join(self.encryption.path("warm-up.json"));
+        self.encryption
+            .write(&file, &serde_json::to_vec_pretty(&warm_ups)?)
+            .with_context(|| format!("unable to save {}", file.display()))?;
+        self.warm_ups = warm_ups;
+        Ok(())
+    }
+
+    /// Responses of the warm-up requests sent before the first round
+    pub fn warm_ups(&self) -> &[WarmUp] {
+        &self.warm_ups
+    }
+
+    /// Runs each scenario once, it ends at the first failing step. Returns the message of the
+    /// last finding or error.
+    fn run_scenarios(&mut self, start: Instant) -> Result<Option<String>> {
+        let mut message = None;
+        let mutate = self.rounds > 0;
+        for scenario in self.scenarios.iter() {
+            let mut variables = scenario.variables.clone();
+            for (i, step) in scenario.steps.iter().enumerate() {
+                if self.budget_exhausted(start) {
+                    return Ok(message);
+                }
+                let (method, path, _) = step.operation(&self.schema)?;
+                let plan = self
+                    .plans
+                    .get(method, path)
+                    .ok_or_else(|| anyhow!("no operation `{} {}`", method, path))?;
+                let mut payload = Payload::new(
+                    &self.url,
+                    method,
+                    path,
+                    plan,
+                    &self.extra_headers,
+                    &[],
+                    self.rng.gen(),
+                )?;
+                apply_overrides(&self.overrides, &mut payload);
+                if let Err(e) = step.apply(
+                    &mut payload,
+                    &self.extra_headers,
+                    &variables,
+                    mutate,
+                    &mut self.rng,
+                ) {
+                    debug!(scenario = %scenario.name, step = i + 1, "scenario ended: {:#}", e);
+                    break;
+                }
+                throttle(
+                    &self.overrides,
+                    &mut self.last_requests,
+                    self.rate_limit,
+                    &mut self.last_request,
+                    &payload,
+                );
+
+                let (request, resp) = match self.send_request(&payload) {
+                    Ok(sent) => sent,
+                    Err(e) => {
+                        message = Some(failed(&payload, "scenario step", e));
+                        break;
+                    }
+                };
+                let detected = self.check_response(&payload, &request, &resp)?;
+                self.stats.update(&resp, &payload, !detected.is_empty());
+                if let Some((_, cleanup)) = &mut self.cleanup {
+                    cleanup.track(&self.url, payload.method, payload.path, &resp);
+                }
+                if detected.is_empty() && !self.invariants.is_empty() {
+                    match self.check_invariants(&payload, &request, &resp) {
+                        Ok(Some((request, resp, detected))) => {
+                            message = record(
+                                &mut self.findings,
+                                &mut self.reporters,
+                                &mut self.subscribers,
+                                &payload,
+                                &request,
+                                &resp,
+                                detected,
+                            )
+                        }
+                        Ok(None) => {}
+                        Err(e) => message = Some(failed(&payload, "invariant check", e)),
+                    }
+                }
+                for detected in detected {
+                    message = record(
+                        &mut self.findings,
+                        &mut self.reporters,
+                        &mut self.subscribers,
+                        &payload,
+                        &request,
+                        &resp,
+                        detected,
+                    );
+                }
+                if let Err(e) = step.bind(&resp, &mut variables) {
+                    match mutate {
+                        true => {
+                            debug!(scenario = %scenario.name, step = i + 1, "scenario ended: {:#}", e)
+                        }
+                        false => {
+                            warn!(scenario = %scenario.name, step = i + 1, status = resp.status, "scenario failed without mutations: {:#}", e)
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(message)
+    }
+
+    fn fuzz_rounds(
+        &mut self,
+        start: Instant,
+        deadline: Option<Instant>,
+        mut display: impl FnMut(&Stats, &Option<String>) -> Result<bool>,
+    ) -> Result<End> {
         let mut message = None;
         loop {
-            for (path, ref_or_item) in self.schema.paths.iter() {
-                let item = ref_or_item.to_item_ref();
-                for payload in Payload::for_all_methods(&self.url, path, item, &self.extra_headers)?
-                {
-                    match self.send_request(&payload) {
-                        Ok(resp) => {
-                            self.check_response(&resp, &payload)?;
-                            self.stats.update(&resp, &payload);
-                            message = None;
+            let mut sent = false;
+            for path in self.schema.paths.keys() {
+                if let Some(selected) = &self.selected {
+                    if !selected.iter().any(|(_, p)| p == path) {
+                        continue;
+                    }
+                }
+                let repetitions = self
+                    .plans
+                    .of_path(path)
+                    .iter()
+                    .map(|(method, plan)| {
+                        weight(&self.overrides, method, path, plan.operation_id.as_deref()).ceil()
+                            as usize
+                    })
+                    .max()
+                    .unwrap_or(1)
+                    .max(1);
+                for repetition in 0..repetitions {
+                    // The probes are sent with the first payload of the first round
+                    let first_round = repetition == 0 && !self.probed_paths.contains(path);
+                    for mut payload in Payload::for_all_methods(
+                        &self.url,
+                        path,
+                        &self.plans,
+                        &self.extra_headers,
+                        &self.corpus,
+                        self.checks.negative,
+                        &mut self.rng,
+                    )? {
+                        if !self.is_selected(payload.method, payload.path)
+                            || !first_round
+                                && !drawn(&self.overrides, &mut self.rng, repetition, &payload)
+                        {
+                            payload.recycle();
+                            continue;
                         }
-                        Err(e) => message = Some(e.to_string()),
-                    };
+                        if let Some(ids) = &self.ids {
+                            ids.substitute(&mut payload);
+                        }
+                        apply_overrides(&self.overrides, &mut payload);
+                        generate(&self.generators, &mut payload);
+                        if let Some(logs) = &self.target_logs {
+                            logs.store_due(&self.encryption)?;
+                        }
+                        if let Some(rechecks) = &self.rechecks {
+                            rechecks.borrow_mut().replay_due(
+                                self.transport.as_ref(),
+                                self.signing.as_ref(),
+                                &mut self.findings,
+                                &self.encryption,
+                            )?;
+                        }
+                        if self.budget_exhausted(start) {
+                            info!("budget exhausted");
+                            return Ok(End::Exhausted);
+                        }
+                        if let Some(control) = &self.control {
+                            if control.stopped() {
+                                info!("job stopped");
+                                return Ok(End::Stopped);
+                            }
+                            control.throttle();
+                        }
+                        if self
+                            .budget
+                            .max_requests_per_operation
+                            .is_some_and(|max| self.requests(&payload) >= max)
+                            || self.maintenance.is_paused(payload.method, payload.path)
+                        {
+                            continue;
+                        }
+                        if self.round_trip
+                            && roundtrip::is_update(payload.method)
+                            && payload.violation.is_none()
+                        {
+                            if let Err(e) = self.round_trip(&mut payload) {
+                                debug!(method = payload.method, path = payload.path, error = %e, "round trip failed");
+                            }
+                        }
+                        self.sizes.fit(&mut payload, &self.extra_headers);
+                        sent = true;
+                        throttle(
+                            &self.overrides,
+                            &mut self.last_requests,
+                            self.rate_limit,
+                            &mut self.last_request,
+                            &payload,
+                        );
+
+                        let sent_at = Instant::now();
+                        let sent = self.prepare(&payload).map(|request| {
+                            let response = self.send_prepared(&payload, &request);
+                            (self.stored(request), response)
+                        });
+                        match sent {
+                            Ok((_, Ok(resp)))
+                                if self.maintenance.observe(
+                                    &mut self.stats.outages,
+                                    payload.method,
+                                    payload.path,
+                                    &resp,
+                                ) =>
+                            {
+                                message = Some(format!(
+                                    "{} {} unavailable, paused",
+                                    payload.method, payload.path
+                                ));
+                            }
+                            Ok((request, Ok(resp))) => {
+                                message = None;
+                                // The probes and replays of the request count toward the
+                                // concurrency of the operation too
+                                let _slot = self.slot(&payload)?;
+                                self.sizes.observe(&payload, &resp);
+                                if let Some((_, cleanup)) = &mut self.cleanup {
+                                    cleanup.track(&self.url, payload.method, payload.path, &resp);
+                                }
+                                let detected = self.check_response(&payload, &request, &resp)?;
+                                self.stats.update(&resp, &payload, !detected.is_empty());
+                                learn(
+                                    &mut self.learned_seeds,
+                                    &self.extra_headers,
+                                    &payload,
+                                    &resp,
+                                );
+                                if let (Some(samples), true, None) =
+                                    (&mut self.samples, detected.is_empty(), &payload.violation)
+                                {
+                                    sample(
+                                        samples,
+                                        self.redactor.as_ref(),
+                                        &self.extra_headers,
+                                        &payload,
+                                        &request,
+                                        &resp,
+                                    );
+                                }
+                                if let (Some(ids), 2) = (&mut self.ids, resp.status / 100) {
+                                    ids.observe(payload.path, &resp.body);
+                                }
+                                if let Some(validators) = Validators::of(&resp) {
+                                    self.validators
+                                        .insert(request.url.path().to_string(), validators);
+                                }
+                                for detected in detected {
+                                    message = record(
+                                        &mut self.findings,
+                                        &mut self.reporters,
+                                        &mut self.subscribers,
+                                        &payload,
+                                        &request,
+                                        &resp,
+                                        detected,
+                                    );
+                                }
+
+                                if let (Some(baseline_url), false) =
+                                    (&self.checks.baseline_url, self.is_ignored(&payload, &resp))
+                                {
+                                    match self.compare_with_baseline(
+                                        &payload,
+                                        baseline_url,
+                                        &request,
+                                        &resp,
+                                    ) {
+                                        Ok(Some(detected)) => {
+                                            message = record(
+                                                &mut self.findings,
+                                                &mut self.reporters,
+                                                &mut self.subscribers,
+                                                &payload,
+                                                &request,
+                                                &resp,
+                                                detected,
+                                            )
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            message =
+                                                Some(failed(&payload, "baseline comparison", e))
+                                        }
+                                    }
+                                }
+
+                                if self.checks.check_idempotency
+                                    && payload.idempotent
+                                    && resp.status / 100 == 2
+                                {
+                                    let checks = self
+                                        .idempotency_checks
+                                        .entry((
+                                            payload.path.to_string(),
+                                            payload.method.to_string(),
+                                        ))
+                                        .or_default();
+                                    *checks += 1;
+                                    if *checks <= IDEMPOTENCY_CHECKS {
+                                        match self.check_idempotency(&payload, &request, &resp) {
+                                            Ok(Some((resp, detected))) => {
+                                                message = record(
+                                                    &mut self.findings,
+                                                    &mut self.reporters,
+                                                    &mut self.subscribers,
+                                                    &payload,
+                                                    &request,
+                                                    &resp,
+                                                    detected,
+                                                )
+                                            }
+                                            Ok(None) => {}
+                                            Err(e) => {
+                                                message =
+                                                    Some(failed(&payload, "idempotency check", e))
+                                            }
+                                        }
+                                    }
+                                }
+
+                                if let (true, Some(burst)) =
+                                    (first_round, self.checks.rate_limit_burst)
+                                {
+                                    match self.probe_rate_limit(&payload, &request, burst) {
+                                        Ok(Some((resp, detected))) => {
+                                            message = record(
+                                                &mut self.findings,
+                                                &mut self.reporters,
+                                                &mut self.subscribers,
+                                                &payload,
+                                                &request,
+                                                &resp,
+                                                detected,
+                                            )
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            message = Some(failed(&payload, "rate limit probe", e))
+                                        }
+                                    }
+                                }
+
+                                if first_round && self.checks.probe_smuggling {
+                                    match self.probe_smuggling(&payload, &request) {
+                                        Ok(Some((resp, detected))) => {
+                                            message = record(
+                                                &mut self.findings,
+                                                &mut self.reporters,
+                                                &mut self.subscribers,
+                                                &payload,
+                                                &request,
+                                                &resp,
+                                                detected,
+                                            )
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            message = Some(failed(&payload, "smuggling probe", e))
+                                        }
+                                    }
+                                }
+
+                                if first_round
+                                    && self.checks.probe_traversal
+                                    && !payload.path_params.is_empty()
+                                {
+                                    match self.probe_traversal(&payload, &request) {
+                                        Ok(Some((request, resp, detected))) => {
+                                            message = record(
+                                                &mut self.findings,
+                                                &mut self.reporters,
+                                                &mut self.subscribers,
+                                                &payload,
+                                                &request,
+                                                &resp,
+                                                detected,
+                                            )
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            message = Some(failed(&payload, "traversal probe", e))
+                                        }
+                                    }
+                                }
+
+                                if first_round && self.checks.probe_limits {
+                                    match self.probe_limits(&payload, &request) {
+                                        Ok(Some((request, resp, detected))) => {
+                                            message = record(
+                                                &mut self.findings,
+                                                &mut self.reporters,
+                                                &mut self.subscribers,
+                                                &payload,
+                                                &request,
+                                                &resp,
+                                                detected,
+                                            )
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            message = Some(failed(&payload, "size limit probe", e))
+                                        }
+                                    }
+                                }
+
+                                if first_round && self.checks.chaos {
+                                    match self.probe_chaos(&payload, &request, &resp) {
+                                        Ok(Some((resp, detected))) => {
+                                            message = record(
+                                                &mut self.findings,
+                                                &mut self.reporters,
+                                                &mut self.subscribers,
+                                                &payload,
+                                                &request,
+                                                &resp,
+                                                detected,
+                                            )
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            message = Some(failed(&payload, "chaos probe", e))
+                                        }
+                                    }
+                                }
+
+                                if first_round && self.checks.probe_clock_skew {
+                                    match self.probe_clock_skew(&payload, &request, &resp) {
+                                        Ok(Some((request, resp, detected))) => {
+                                            message = record(
+                                                &mut self.findings,
+                                                &mut self.reporters,
+                                                &mut self.subscribers,
+                                                &payload,
+                                                &request,
+                                                &resp,
+                                                detected,
+                                            )
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            message = Some(failed(&payload, "clock skew probe", e))
+                                        }
+                                    }
+                                }
+
+                                let operation =
+                                    (payload.path.to_string(), payload.method.to_string());
+                                if self.checks.probe_conditional
+                                    && resp.status / 100 == 2
+                                    && !self.conditional_probed.contains(&operation)
+                                {
+                                    match self.probe_conditional(&payload, &request) {
+                                        Ok(None) => {}
+                                        Ok(Some(probed)) => {
+                                            self.conditional_probed.insert(operation);
+                                            for (request, resp, detected) in probed {
+                                                message = record(
+                                                    &mut self.findings,
+                                                    &mut self.reporters,
+                                                    &mut self.subscribers,
+                                                    &payload,
+                                                    &request,
+                                                    &resp,
+                                                    detected,
+                                                )
+                                            }
+                                        }
+                                        Err(e) => {
+                                            message = Some(failed(&payload, "conditional probe", e))
+                                        }
+                                    }
+                                }
+
+                                if !self.invariants.is_empty() {
+                                    match self.check_invariants(&payload, &request, &resp) {
+                                        Ok(Some((request, resp, detected))) => {
+                                            message = record(
+                                                &mut self.findings,
+                                                &mut self.reporters,
+                                                &mut self.subscribers,
+                                                &payload,
+                                                &request,
+                                                &resp,
+                                                detected,
+                                            )
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            message = Some(failed(&payload, "invariant check", e))
+                                        }
+                                    }
+                                }
+
+                                if self.identities.is_some() {
+                                    match self.probe_identities(&payload, &request, &resp) {
+                                        Ok(Some((request, resp, detected))) => {
+                                            message = record(
+                                                &mut self.findings,
+                                                &mut self.reporters,
+                                                &mut self.subscribers,
+                                                &payload,
+                                                &request,
+                                                &resp,
+                                                detected,
+                                            )
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => {
+                                            message = Some(failed(&payload, "identity probe", e))
+                                        }
+                                    }
+                                }
+
+                                if first_round && self.checks.probe_cors {
+                                    match self.probe_cors(&payload, &request) {
+                                        Ok(Some((request, resp, detected))) => {
+                                            message = record(
+                                                &mut self.findings,
+                                                &mut self.reporters,
+                                                &mut self.subscribers,
+                                                &payload,
+                                                &request,
+                                                &resp,
+                                                detected,
+                                            )
+                                        }
+                                        Ok(None) => {}
+                                        Err(e) => message = Some(failed(&payload, "cors probe", e)),
+                                    }
+                                }
+                                if let Some(body) = request.body {
+                                    pool::recycle(body);
+                                }
+                            }
+                            Ok((request, Err(_)))
+                                if self
+                                    .checks
+                                    .hang_threshold
+                                    .is_some_and(|threshold| sent_at.elapsed() >= threshold) =>
+                            {
+                                match self.check_hang(&payload, request, sent_at.elapsed()) {
+                                    Ok(Some((request, resp, detected))) => {
+                                        message = record(
+                                            &mut self.findings,
+                                            &mut self.reporters,
+                                            &mut self.subscribers,
+                                            &payload,
+                                            &request,
+                                            &resp,
+                                            detected,
+                                        )
+                                    }
+                                    Ok(None) => {}
+                                    Err(e) => message = Some(failed(&payload, "hang check", e)),
+                                }
+                            }
+                            Ok((_, Err(e))) | Err(e) => {
+                                message = Some(failed(&payload, "request", e))
+                            }
+                        };
+                        if let Some(control) = &self.control {
+                            control.progress(self.stats.total, self.findings.len());
+                        }
+                        payload.recycle();
+                    }
                 }
+                self.probed_paths.insert(path.clone());
+            }
+            if let Some(scenario_message) = self.run_scenarios(start)? {
+                message = Some(scenario_message);
+            }
+            self.rounds += 1;
+            if let Some((CleanupMode::Round, cleanup)) = &mut self.cleanup {
+                cleanup.delete_created(self.transport.as_ref(), &self.extra_headers);
             }
             if self
-                .tui
-                .display(&self.stats, &message)
-                .context("unable to draw tui")?
+                .snapshot_every
+                .as_ref()
+                .is_some_and(|(interval, _)| self.last_snapshot.elapsed() >= *interval)
             {
-                return Ok(());
+                self.save_snapshot(start.elapsed())?;
+            }
+            // The operations left are paused until the outage is over
+            if let (false, Some(resume)) = (sent, self.maintenance.resume()) {
+                while Instant::now() < resume {
+                    if display(&self.stats, &message)? {
+                        return Ok(End::Quit);
+                    }
+                    if self.budget_exhausted(start)
+                        || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+                    {
+                        break;
+                    }
+                    thread::sleep(
+                        OUTAGE_POLL.min(resume.saturating_duration_since(Instant::now())),
+                    );
+                }
+                continue;
+            }
+            // All operations used up their budget
+            if !sent {
+                info!("budget of all operations exhausted");
+                return Ok(End::Exhausted);
+            }
+            if display(&self.stats, &message)? {
+                return Ok(End::Quit);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(End::SliceOver);
             }
         }
     }
 
-    fn send_request(&self, payload: &Payload) -> Result<ureq::Response> {
-        let mut path_with_params = payload.path.to_owned();
-        for (name, value) in payload.path_params.iter() {
-            path_with_params = path_with_params.replace(&format!("{{{}}}", name), value);
+    /// Sends the payload to the baseline deployment and saves the response of the fuzzed one
+    /// if they differ
+    fn compare_with_baseline(
+        &self,
+        payload: &Payload,
+        baseline_url: &Url,
+        request: &Request,
+        resp: &Response,
+    ) -> Result<Option<Detected>> {
+        let baseline_request = payload.to_request_with_base(baseline_url)?;
+        let baseline = ratelimit::send(self.transport.as_ref(), &baseline_request)?;
+        let differences = diff::differences(&baseline, resp, &self.checks.ignored);
+        if differences.is_empty() {
+            return Ok(None);
         }
-        let mut request = ureq::request_url(
-            payload.method,
-            &payload.url.join(path_with_params.trim_start_matches('/'))?,
-        );
 
-        for (param, value) in payload.query_params.iter() {
-            request = request.query(param, value)
+        let detected =
+            self.save_finding(payload, request, resp, FindingKind::Regression, differences)?;
+        Ok(Some(detected))
+    }
+
+    /// Repeats the request and saves the repeated response if it differs. As the first
+    /// request of idempotent unsafe operations may change the state, those are compared
+    /// with another repetition.
+    fn check_idempotency(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+    ) -> Result<Option<(Response, Detected)>> {
+        let mut details = idempotency::side_effect(payload.method, resp)
+            .into_iter()
+            .collect::<Vec<_>>();
+        let first = match idempotency::is_safe(payload.method) {
+            true => resp.clone(),
+            false => ratelimit::send(self.transport.as_ref(), request)?,
+        };
+        let repeated = ratelimit::send(self.transport.as_ref(), request)?;
+        details.extend(diff::differences(&first, &repeated, &self.checks.ignored));
+        if details.is_empty() {
+            return Ok(None);
+        }
+
+        let detected = self.save_finding(
+            payload,
+            request,
+            &repeated,
+            FindingKind::NonIdempotent,
+            details,
+        )?;
+        Ok(Some((repeated, detected)))
+    }
+
+    /// Sends a burst of the request and saves the last response if none was rate limited
+    fn probe_rate_limit(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        burst: u32,
+    ) -> Result<Option<(Response, Detected)>> {
+        match ratelimit::probe(self.transport.as_ref(), request, burst)? {
+            Some((resp, description)) => {
+                let detected = self.save_finding(
+                    payload,
+                    request,
+                    &resp,
+                    FindingKind::MissingRateLimit,
+                    vec![description],
+                )?;
+                Ok(Some((resp, detected)))
+            }
+            None => Ok(None),
         }
+    }
 
-        for (header, value) in payload.headers.iter() {
-            request = request.set(header, value)
+    /// Sends raw requests with conflicting framing and saves the first suspicious response
+    fn probe_smuggling(
+        &self,
+        payload: &Payload,
+        request: &Request,
+    ) -> Result<Option<(Response, Detected)>> {
+        match smuggling::probe(request)? {
+            Some((resp, indicators)) => {
+                let detected = self.save_finding(
+                    payload,
+                    request,
+                    &resp,
+                    FindingKind::RequestSmuggling,
+                    indicators,
+                )?;
+                Ok(Some((resp, detected)))
+            }
+            None => Ok(None),
         }
+    }
 
-        if !payload.body.is_empty() {
-            Ok(request.send_json(payload.body[0].clone()).or_any_status()?)
-        } else {
-            request.call().or_any_status().map_err(|e| e.into())
+    /// Sends the path parameters with traversal and encoding attacks and saves the probe that
+    /// got the first suspicious response
+    fn probe_traversal(
+        &self,
+        payload: &Payload,
+        request: &Request,
+    ) -> Result<Option<(Request, Response, Detected)>> {
+        match traversal::probe(payload, request)? {
+            Some((probe, resp, indicators)) => {
+                let detected = self.save_finding(
+                    payload,
+                    &probe,
+                    &resp,
+                    FindingKind::PathTraversal,
+                    indicators,
+                )?;
+                Ok(Some((probe, resp, detected)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Saves the request left without a response as a hang, unless it is answered in time when
+    /// it is sent again alone with `retry_hangs`
+    fn check_hang(
+        &self,
+        payload: &Payload,
+        request: Request,
+        elapsed: Duration,
+    ) -> Result<Option<(Request, Response, Detected)>> {
+        let threshold = self.checks.hang_threshold.unwrap_or(elapsed);
+        let mut details = vec![format!(
+            "no response within the hang threshold of {:?}",
+            threshold
+        )];
+        if self.checks.retry_hangs {
+            let _alone = self.slots.as_ref().map(Slots::exclusive).transpose()?;
+            let start = Instant::now();
+            match self.exchange(payload) {
+                Ok((_, resp)) if start.elapsed() < threshold => {
+                    warn!(
+                        method = payload.method,
+                        path = payload.path,
+                        status = resp.status,
+                        elapsed_ms = start.elapsed().as_millis() as u64,
+                        "request hung under load but was answered alone, the target may be overloaded"
+                    );
+                    return Ok(None);
+                }
+                _ => details.push(
+                    "hung again when it was sent with no other requests in flight".to_string(),
+                ),
+            }
+        }
+        let resp = Response::none(elapsed);
+        let detected = self.save_finding(payload, &request, &resp, FindingKind::Hang, details)?;
+        Ok(Some((request, resp, detected)))
+    }
+
+    /// Sends the request with growing header values, header counts, URL and body and saves the
+    /// first probe causing a server error or dropping the connection
+    fn probe_limits(
+        &self,
+        payload: &Payload,
+        request: &Request,
+    ) -> Result<Option<(Request, Response, Detected)>> {
+        match limits::probe(self.transport.as_ref(), request) {
+            Some((probe, resp, indicators)) => {
+                let detected =
+                    self.save_finding(payload, &probe, &resp, FindingKind::SizeLimit, indicators)?;
+                Ok(Some((probe, resp, detected)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Sends the request slowly, truncated, aborted and closed early, then normally again,
+    /// and saves the first response showing it mishandled
+    fn probe_chaos(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+    ) -> Result<Option<(Response, Detected)>> {
+        match chaos::probe(
+            self.transport.as_ref(),
+            request,
+            resp,
+            payload.idempotent,
+            payload.seed,
+        )? {
+            Some((resp, indicators)) => {
+                let detected = self.save_finding(
+                    payload,
+                    request,
+                    &resp,
+                    FindingKind::ClientFault,
+                    indicators,
+                )?;
+                Ok(Some((resp, detected)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Sends the successful request again with skewed timestamps and saves the first response
+    /// validating them wrong
+    fn probe_clock_skew(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        response: &Response,
+    ) -> Result<Option<(Request, Response, Detected)>> {
+        let signing = match &self.signing {
+            Some(signing) if response.status / 100 == 2 => signing,
+            _ => return Ok(None),
+        };
+        let mut misvalidated = None;
+        let mut details = Vec::new();
+        for (skew, probe) in clockskew::probes(signing, request)? {
+            let resp = ratelimit::send(self.transport.as_ref(), &probe)?;
+            if let Some(detail) = clockskew::misvalidation(skew, response.status, &resp) {
+                details.push(detail);
+                misvalidated.get_or_insert((probe, resp));
+            }
+        }
+
+        match misvalidated {
+            Some((probe, resp)) => {
+                let detected =
+                    self.save_finding(payload, &probe, &resp, FindingKind::ClockSkew, details)?;
+                Ok(Some((probe, resp, detected)))
+            }
+            None => Ok(None),
         }
     }
 
-    fn check_response(&self, resp: &ureq::Response, payload: &Payload) -> Result<()> {
-        let responses = &payload.responses.responses;
+    /// Sends conditional and range requests based on the successful request, and saves the
+    /// first probe failing and the first indicating cache poisoning. `None` if there is
+    /// nothing to probe yet, writes wait for the validators of their resource.
+    fn probe_conditional(
+        &self,
+        payload: &Payload,
+        request: &Request,
+    ) -> Result<Option<Vec<(Request, Response, Detected)>>> {
+        let probes = preconditions::probes(request, self.validators.get(request.url.path()));
+        if probes.is_empty() {
+            return Ok(None);
+        }
+
+        let mut failed = None;
+        let mut poisoned = None;
+        let (mut violations, mut indicators) = (Vec::new(), Vec::new());
+        for probe in probes {
+            let resp = ratelimit::send(self.transport.as_ref(), &probe.request)?;
+            if let Some(violation) = preconditions::violation(&probe, &resp) {
+                violations.push(violation);
+                if failed.is_none() {
+                    failed = Some((probe.request.clone(), resp.clone()));
+                }
+            }
+            if let Some(indicator) = preconditions::cache_poisoning(&probe, &resp) {
+                indicators.push(indicator);
+                if poisoned.is_none() {
+                    poisoned = Some((probe.request, resp));
+                }
+            }
+        }
+
+        let mut detected = Vec::new();
+        for (probed, kind, details) in [
+            (failed, FindingKind::Precondition, violations),
+            (poisoned, FindingKind::CachePoisoning, indicators),
+        ] {
+            if let Some((probe, resp)) = probed {
+                let finding = self.save_finding(payload, &probe, &resp, kind, details)?;
+                detected.push((probe, resp, finding));
+            }
+        }
+        Ok(Some(detected))
+    }
 
-        // known non 500 and ingored status codes are OK
-        if self.ignored_status_codes.contains(&resp.status())
-            || (responses.contains_key(&StatusCode::Code(resp.status()))
-                && resp.status() / 100 != 5)
+    /// Sends the requests checking the invariants that hold after the response, and saves the
+    /// first response breaking its invariant with the ways all of them broke theirs
+    fn check_invariants(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+    ) -> Result<Option<(Request, Response, Detected)>> {
+        let mut broken = None;
+        let mut details = Vec::new();
+        for mut follow_up in self
+            .invariants
+            .follow_ups(&self.url, payload, request, resp)
         {
+            if let Some(signing) = &self.signing {
+                signing.sign(&mut follow_up.request, SystemTime::now())?;
+            }
+            let answer = ratelimit::send(self.transport.as_ref(), &follow_up.request)?;
+            let mut violations = follow_up.violations(payload, request, resp, &answer);
+            if violations.is_empty() {
+                continue;
+            }
+            details.append(&mut violations);
+            if broken.is_none() {
+                broken = Some((follow_up.request, answer));
+            }
+        }
+        match broken {
+            Some((follow_up, answer)) => {
+                let detected = self.save_finding(
+                    payload,
+                    &follow_up,
+                    &answer,
+                    FindingKind::Inconsistency,
+                    details,
+                )?;
+                Ok(Some((follow_up, answer, detected)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the resource created by the request as its owner and as the other identities,
+    /// and saves the first response to another identity if the owner could read it too
+    fn probe_identities(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+    ) -> Result<Option<(Request, Response, Detected)>> {
+        let identities = match &self.identities {
+            Some(identities) => identities,
+            None => return Ok(None),
+        };
+        let mut probes = identities.probes(&self.url, payload.path, request, resp);
+        if let Some(signing) = &self.signing {
+            for (_, probe) in probes.iter_mut() {
+                signing.sign(probe, SystemTime::now())?;
+            }
+        }
+        let mut probes = probes.into_iter();
+        let (owner, read) = match probes.next() {
+            Some(probe) => probe,
+            None => return Ok(None),
+        };
+        let owned = ratelimit::send(self.transport.as_ref(), &read)?;
+        if owned.status / 100 != 2 {
+            return Ok(None);
+        }
+
+        let mut leaked = None;
+        let mut details = Vec::new();
+        for (identity, probe) in probes {
+            let resp = ratelimit::send(self.transport.as_ref(), &probe)?;
+            if resp.status / 100 != 2 {
+                continue;
+            }
+            let same = match resp.body == owned.body {
+                true => ", with the body the owner got",
+                false => "",
+            };
+            details.push(format!(
+                "GET {} created by {} answered {} to {}{}",
+                probe.url.path(),
+                owner,
+                resp.status,
+                identity,
+                same
+            ));
+            if leaked.is_none() {
+                leaked = Some((probe, resp));
+            }
+        }
+        match leaked {
+            Some((probe, resp)) => {
+                let detected =
+                    self.save_finding(payload, &probe, &resp, FindingKind::Bola, details)?;
+                Ok(Some((probe, resp, detected)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Sends CORS probes based on the request and saves the first misconfigured response
+    fn probe_cors(
+        &self,
+        payload: &Payload,
+        request: &Request,
+    ) -> Result<Option<(Request, Response, Detected)>> {
+        let mut misconfigured = None;
+        let mut details = Vec::new();
+        for (origin, probe) in cors::probes(request) {
+            let resp = ratelimit::send(self.transport.as_ref(), &probe)?;
+            let misconfigurations = cors::misconfigurations(&origin, &resp);
+            if misconfigurations.is_empty() {
+                continue;
+            }
+            details.extend(
+                misconfigurations
+                    .iter()
+                    .map(|m| format!("{} request: {}", probe.method, m)),
+            );
+            misconfigured.get_or_insert((probe, resp));
+        }
+
+        match misconfigured {
+            Some((probe, resp)) => {
+                let detected =
+                    self.save_finding(payload, &probe, &resp, FindingKind::Cors, details)?;
+                Ok(Some((probe, resp, detected)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn send_request(&self, payload: &Payload) -> Result<(Request, Response)> {
+        let request = self.prepare(payload)?;
+        let response = self.send_prepared(payload, &request)?;
+        Ok((self.stored(request), response))
+    }
+
+    fn send_prepared(&self, payload: &Payload, request: &Request) -> Result<Response> {
+        // Hung requests are sent again alone, the other requests are sent under a shared lock
+        let _shared = match (&self.slots, self.checks.retry_hangs) {
+            (Some(slots), true) => Some(slots.shared()?),
+            _ => None,
+        };
+        self.transmit(payload, request)
+    }
+
+    /// Replaces the body of the update with a changed representation of the resource, fetched
+    /// with the `GET` of the same path and parameters, if it could be fetched
+    fn round_trip(&self, payload: &mut Payload) -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(payload.seed.rotate_left(32));
+        let plan = match self.plans.get("GET", payload.path) {
+            Some(plan) if rng.gen_bool(roundtrip::ROUND_TRIP_PROBABILITY) => plan,
+            _ => return Ok(()),
+        };
+        let fetch = Payload {
+            url: payload.url,
+            method: "GET",
+            path: payload.path,
+            operation_id: plan.operation_id.as_deref(),
+            query_params: Vec::new(),
+            path_params: payload.path_params.clone(),
+            headers: payload.headers.clone(),
+            body: Vec::new(),
+            raw_body: None,
+            seed: payload.seed,
+            responses: &plan.responses,
+            idempotent: true,
+            violation: None,
+            negative: false,
+            timeout: payload.timeout,
+            endpoint: payload.endpoint.clone(),
+        };
+        let (_, response) = self.send_request(&fetch)?;
+        if response.status / 100 != 2 {
             return Ok(());
         }
+        let representation = match serde_json::from_str(&response.body) {
+            Ok(representation) => representation,
+            Err(_) => return Ok(()),
+        };
+        if let Some(body) = roundtrip::mutate(&representation, payload.method == "PATCH", &mut rng)
+        {
+            payload.body = vec![body];
+            payload.raw_body = None;
+        }
+        Ok(())
+    }
+
+    fn exchange(&self, payload: &Payload) -> Result<(Request, Response)> {
+        let request = self.prepare(payload)?;
+        let response = self.transmit(payload, &request)?;
+        Ok((self.stored(request), response))
+    }
+
+    /// Adds the credentials to the request and signs it, so that the signature covers them
+    fn sign(&self, request: &mut Request) -> Result<()> {
+        if let Some(credentials) = &self.credentials {
+            credentials.authenticate(request)?;
+        }
+        if let Some(signing) = &self.signing {
+            signing.sign(request, SystemTime::now())?;
+        }
+        Ok(())
+    }
+
+    /// The sent request without the credentials, as it is stored in the findings
+    fn stored(&self, mut request: Request) -> Request {
+        if let Some(credentials) = &self.credentials {
+            credentials.strip(&mut request);
+        }
+        request
+    }
+
+    /// Request of the payload as it is sent, with the identity, cookies, credentials and
+    /// signature
+    fn prepare(&self, payload: &Payload) -> Result<Request> {
+        let mut request = payload.to_request()?;
+        if let Some(identities) = &self.identities {
+            identities.interleave(payload.method, payload.path, &mut request);
+        }
+        if stream::declared(payload.responses) {
+            request.stream = Some(self.stream_budget);
+        }
+        if self.is_websocket(payload) {
+            request.stream = Some(self.stream_budget);
+            request
+                .headers
+                .push(("upgrade".to_string(), "websocket".to_string()));
+        }
+        if let (Some(threshold), None) = (self.checks.hang_threshold, &request.stream) {
+            request.timeout = Some(request.timeout.map_or(threshold, |t| t.min(threshold)));
+        }
+        if let Some(cookies) = &self.cookies {
+            cookies.apply(&cookies.scope(payload.method, payload.path), &mut request);
+        }
+        self.sign(&mut request)?;
+        Ok(request)
+    }
+
+    /// Slot of the operation of the payload, if its concurrency is limited
+    fn slot(&self, payload: &Payload) -> Result<Option<Slot>> {
+        let concurrency = self
+            .overrides
+            .iter()
+            .rev()
+            .filter(|o| o.matches(payload.method, payload.path, payload.operation_id))
+            .find_map(|o| o.concurrency);
+        match (&self.slots, concurrency) {
+            (Some(slots), Some(limit)) => {
+                Ok(Some(slots.acquire(payload.method, payload.path, limit)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Sends the prepared request in a slot of its operation and keeps the cookies it sets
+    fn transmit(&self, payload: &Payload, request: &Request) -> Result<Response> {
+        let _slot = self.slot(payload)?;
+        let response = ratelimit::send(self.transport.as_ref(), request)?;
+        if let Some(cookies) = &self.cookies {
+            cookies.store(
+                &cookies.scope(payload.method, payload.path),
+                request,
+                &response,
+            );
+        }
+        Ok(response)
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn results_dir(&self) -> &str {
+        &self.results_dir
+    }
+
+    /// Transport of the requests with the wrappers of the run, e.g. authenticating them, for
+    /// the connections of a benchmark
+    pub fn transport(&self) -> Arc<dyn Transport> {
+        self.transport.clone()
+    }
+
+    /// Encryption of the stored files, the files of the findings are read with
+    pub fn encryption(&self) -> &Encryption {
+        &self.encryption
+    }
+
+    pub fn run_info(&self) -> &RunInfo {
+        &self.run
+    }
+
+    /// Cookies set by the API, if they are kept
+    pub fn cookie_jar(&self) -> Option<&CookieJar> {
+        self.cookies.as_ref()
+    }
+
+    pub fn findings(&self) -> &Findings {
+        &self.findings
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn schema(&self) -> &OpenAPI {
+        &self.schema
+    }
+
+    /// Number of operations in the specification
+    pub fn operations(&self) -> usize {
+        self.schema
+            .paths
+            .values()
+            .map(|item| {
+                operations(item.to_item_ref())
+                    .iter()
+                    .filter(|(_, operation)| operation.is_some())
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Whether the status of the response is ignored for the operation, globally or in the
+    /// config of the operation
+    fn is_ignored(&self, payload: &Payload, resp: &Response) -> bool {
+        self.checks.ignored_statuses.iter().any(|rule| {
+            rule.matches(
+                payload.method,
+                payload.path,
+                payload.operation_id,
+                resp.status,
+            )
+        }) || self.overrides.iter().any(|o| {
+            o.matches(payload.method, payload.path, payload.operation_id)
+                && o.ignore_status_code.contains(&resp.status)
+        })
+    }
+
+    /// Expression telling server errors of the operation apart, the one of the last matching
+    /// override or the global one
+    fn server_error_predicate(&self, payload: &Payload) -> Option<&ExprOracle> {
+        self.overrides
+            .iter()
+            .rev()
+            .filter(|o| o.matches(payload.method, payload.path, payload.operation_id))
+            .find_map(|o| o.server_error.as_ref())
+            .or(self.checks.server_error_when.as_ref())
+    }
+
+    /// Classifies the response by its status code, or as a server error by the body
+    fn status_finding(
+        &self,
+        payload: &Payload,
+        resp: &Response,
+    ) -> Option<(FindingKind, Vec<String>)> {
+        let rule_matches = |rules: &[StatusRule]| {
+            rules.iter().any(|rule| {
+                rule.matches(
+                    payload.method,
+                    payload.path,
+                    payload.operation_id,
+                    resp.status,
+                )
+            })
+        };
+
+        if let Some(predicate) = self.server_error_predicate(payload) {
+            if resp.status / 100 != 5 && predicate.matches(payload, resp) {
+                let details = format!("response matched server error `{}`", predicate.source());
+                return Some((FindingKind::ServerError, vec![details]));
+            }
+        }
+
+        if rule_matches(&self.checks.denied_statuses) {
+            Some((FindingKind::UnexpectedStatus, Vec::new()))
+        } else if rule_matches(&self.checks.allowed_statuses) {
+            None
+        } else if validate::is_documented(payload.responses, resp.status) && resp.status / 100 != 5
+        {
+            // known non 500 status codes are OK, unless the response breaks the contract
+            if !self.checks.validate_responses {
+                return None;
+            }
+            let violations = validate::validate_response(payload.responses, resp);
+            match violations.is_empty() {
+                true => None,
+                false => Some((
+                    FindingKind::ContractViolation,
+                    violations.iter().map(ToString::to_string).collect(),
+                )),
+            }
+        } else {
+            Some((FindingKind::of_status(resp.status), Vec::new()))
+        }
+    }
+
+    /// Compares the response time with the thresholds and the average of the operation
+    fn latency_finding(
+        &self,
+        payload: &Payload,
+        resp: &Response,
+    ) -> Option<(FindingKind, Vec<String>)> {
+        let elapsed = resp.elapsed;
+        if let Some(threshold) = self.checks.slow_threshold {
+            if elapsed > threshold {
+                let details = format!(
+                    "response took {} ms, more than {} ms",
+                    elapsed.as_millis(),
+                    threshold.as_millis()
+                );
+                return Some((FindingKind::SlowResponse, vec![details]));
+            }
+        }
+
+        let factor = self.checks.slow_factor?;
+        let baseline = self
+            .stats
+            .frequencies
+            .get(payload.path)
+            .and_then(|methods| methods.get(payload.method))
+            .filter(|tries| tries.total >= MIN_LATENCY_SAMPLES)?
+            .average_time();
+        if elapsed.as_secs_f64() > baseline.as_secs_f64() * factor {
+            let details = format!(
+                "response took {} ms, {:.1}x the average of {} ms",
+                elapsed.as_millis(),
+                elapsed.as_secs_f64() / baseline.as_secs_f64().max(f64::EPSILON),
+                baseline.as_millis()
+            );
+            return Some((FindingKind::SlowResponse, vec![details]));
+        }
+        None
+    }
+
+    /// Audits security headers of the response, once per operation
+    fn header_finding(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+    ) -> Option<(FindingKind, Vec<String>)> {
+        let policy = self.checks.header_policy.as_ref()?;
+        if self
+            .findings
+            .contains(payload.path, payload.method, FindingKind::SecurityHeaders)
+        {
+            return None;
+        }
+
+        let violations = policy.audit(
+            payload,
+            request,
+            resp,
+            self.requires_authentication(payload),
+        );
+        match violations.is_empty() {
+            true => None,
+            false => Some((FindingKind::SecurityHeaders, violations)),
+        }
+    }
+
+    /// Whether the operation has security requirements in the specification
+    fn requires_authentication(&self, payload: &Payload) -> bool {
+        let operation = self
+            .schema
+            .paths
+            .get(payload.path)
+            .and_then(|item| {
+                operations(item.to_item_ref())
+                    .into_iter()
+                    .find(|(method, _)| *method == payload.method)
+            })
+            .and_then(|(_, operation)| operation.as_ref());
+        operation
+            .and_then(|operation| operation.security.as_ref())
+            .or(self.schema.security.as_ref())
+            .is_some_and(|security| !security.is_empty())
+    }
+
+    /// Reports implementation details leaked by error responses
+    fn disclosure_finding(&self, resp: &Response) -> Option<(FindingKind, Vec<String>)> {
+        if !self.checks.detect_disclosure {
+            return None;
+        }
+        let leaks = disclosure::leaks(resp);
+        match leaks.is_empty() {
+            true => None,
+            false => Some((FindingKind::Disclosure, leaks)),
+        }
+    }
+
+    /// Reports anomalies of the response headers, once per operation and status code
+    fn header_anomaly_finding(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+    ) -> Option<(FindingKind, Vec<String>)> {
+        if !self.checks.check_headers
+            || self.findings.is_known(
+                payload.path,
+                payload.method,
+                resp.status,
+                FindingKind::HeaderAnomaly,
+            )
+        {
+            return None;
+        }
+        let anomalies = headers::anomalies(request, resp, &mut self.disclosed.borrow_mut());
+        match anomalies.is_empty() {
+            true => None,
+            false => Some((FindingKind::HeaderAnomaly, anomalies)),
+        }
+    }
+
+    fn is_websocket(&self, payload: &Payload) -> bool {
+        self.websockets
+            .as_ref()
+            .is_some_and(|websockets| websockets.contains(payload.method, payload.path))
+    }
+
+    /// Reports WebSocket connections dropped or closed with an internal error after a message
+    fn websocket_finding(
+        &self,
+        payload: &Payload,
+        resp: &Response,
+    ) -> Option<(FindingKind, Vec<String>)> {
+        if !self.is_websocket(payload) {
+            return None;
+        }
+        websocket::abnormal_closure(resp).map(|close| {
+            (
+                FindingKind::AbnormalClosure,
+                vec![format!("connection closed abnormally: {}", close)],
+            )
+        })
+    }
+
+    /// Reports successful responses to payloads that were made invalid on purpose
+    fn validation_finding(
+        &self,
+        payload: &Payload,
+        resp: &Response,
+    ) -> Option<(FindingKind, Vec<String>)> {
+        match (&payload.violation, resp.status / 100) {
+            (Some(violation), 2) => Some((
+                FindingKind::ValidationGap,
+                vec![format!("invalid input accepted, {}", violation)],
+            )),
+            _ => None,
+        }
+    }
+
+    /// Evaluates the user defined oracles against the response
+    fn oracle_finding(
+        &self,
+        payload: &Payload,
+        resp: &Response,
+    ) -> Option<(FindingKind, Vec<String>)> {
+        // Oracles check each event of a stream
+        let matched = match resp.events.is_empty() {
+            true => self
+                .oracles
+                .iter()
+                .filter_map(|oracle| oracle.check(payload, resp))
+                .collect::<Vec<_>>(),
+            false => {
+                let events = resp
+                    .events
+                    .iter()
+                    .map(|event| Response {
+                        status: resp.status,
+                        status_text: resp.status_text.clone(),
+                        http_version: resp.http_version.clone(),
+                        headers: resp.headers.clone(),
+                        body: event.data.clone(),
+                        elapsed: resp.elapsed,
+                        events: vec![event.clone()],
+                    })
+                    .collect::<Vec<_>>();
+                self.oracles
+                    .iter()
+                    .filter_map(|oracle| {
+                        events.iter().enumerate().find_map(|(index, event)| {
+                            oracle
+                                .check(payload, event)
+                                .map(|matched| format!("{} in event {}", matched, index + 1))
+                        })
+                    })
+                    .collect()
+            }
+        };
+        match matched.is_empty() {
+            true => None,
+            false => Some((FindingKind::Oracle, matched)),
+        }
+    }
+
+    /// Findings of the checks of the response, without saving them
+    fn detect(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+    ) -> Vec<(FindingKind, Vec<String>)> {
+        if self.is_ignored(payload, resp) {
+            return Vec::new();
+        }
+        vec![
+            self.status_finding(payload, resp),
+            self.latency_finding(payload, resp),
+            self.header_finding(payload, request, resp),
+            self.disclosure_finding(resp),
+            self.header_anomaly_finding(payload, request, resp),
+            self.oracle_finding(payload, resp),
+            self.validation_finding(payload, resp),
+            self.websocket_finding(payload, resp),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
 
-        let results_dir = format!(
-            "results/{}/{}/{}",
-            payload.path.trim_matches('/').replace('/', "-"),
+    /// Whether the checks of the response find the kind
+    fn detects(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+        kind: FindingKind,
+    ) -> bool {
+        self.detect(payload, request, resp)
+            .iter()
+            .any(|(detected, _)| *detected == kind)
+    }
+
+    /// Saves the payload for each finding of the response and returns what was detected
+    fn check_response(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+    ) -> Result<Vec<Detected>> {
+        self.detect(payload, request, resp)
+            .into_iter()
+            .map(|(kind, details)| self.save_finding(payload, request, resp, kind, details))
+            .collect()
+    }
+
+    /// Sends the request again, signed again with the credentials as the signature covers the
+    /// time, the body and the credentials, like when it was first sent
+    fn resend(&self, request: &Request) -> Result<Response> {
+        let mut request = request.clone();
+        self.sign(&mut request)?;
+        ratelimit::send(self.transport.as_ref(), &request)
+    }
+
+    /// Smallest request found whose response the checks still find the kind in, see
+    /// `minimize`, `None` if no part could be left out or the kind is not found by them
+    fn minimize(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+        kind: FindingKind,
+    ) -> Option<Request> {
+        if !self.detects(payload, request, resp, kind) {
+            return None;
+        }
+        let mut smallest = None;
+        let mut sent = 0;
+        for removal in minimize::removals(request) {
+            if sent == minimize::MAX_REQUESTS {
+                break;
+            }
+            let candidate = match removal.apply(smallest.as_ref().unwrap_or(request)) {
+                Some(candidate) => candidate,
+                None => continue,
+            };
+            sent += 1;
+            match self.resend(&candidate) {
+                Ok(response) if self.detects(payload, &candidate, &response, kind) => {
+                    smallest = Some(candidate)
+                }
+                Ok(_) => {}
+                Err(e) => debug!(error = %format!("{:#}", e), "minimizing request failed"),
+            }
+        }
+        smallest
+    }
+
+    /// Saves the finding to the results directory
+    fn save_finding(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+        kind: FindingKind,
+        details: Vec<String>,
+    ) -> Result<Detected> {
+        let sent = Instant::now() - resp.elapsed;
+        let results_dir = results_path(
+            &self.results_dir,
+            payload.path,
             payload.method,
-            resp.status()
+            resp.status,
+            kind,
         );
-        let results_file = format!("{}/{:x}.json", results_dir, rand::random::<u32>());
+        let results_file =
+            self.encryption
+                .path(&format!("{}/{:x}.json", results_dir, rand::random::<u32>()));
         fs::create_dir_all(&results_dir)?;
 
-        serde_json::to_writer_pretty(
-            &File::create(&results_file).context(format!("unable to create {}", &results_file))?,
-            &json!({"payload": payload, "curl": payload.to_curl()?}),
+        let known = self
+            .findings
+            .is_known(payload.path, payload.method, resp.status, kind);
+        let verification = match self.checks.reverify {
+            replays if replays > 0 && kind.replayable() && !known => {
+                Some(self.reverify(payload, request, resp, kind, replays))
+            }
+            _ => None,
+        };
+        let minimized = match self.checks.minimize {
+            true if kind.replayable() && !known => self.minimize(payload, request, resp, kind),
+            _ => None,
+        };
+        if let (Some(rechecks), Some(Verdict::NotReproduced)) = (
+            &self.rechecks,
+            verification
+                .as_ref()
+                .map(|verification| verification.verdict),
+        ) {
+            rechecks.borrow_mut().schedule(
+                payload.path,
+                payload.method,
+                resp.status,
+                kind,
+                &results_file,
+                request,
+            );
+        }
+        let secrets = self
+            .redactor
+            .as_ref()
+            .map(|redactor| redactor.secrets(request, resp));
+        let redacted = secrets
+            .as_ref()
+            .map(|secrets| secrets.exchange(request, resp))
+            .transpose()?;
+        let minimized = match &secrets {
+            Some(secrets) => minimized
+                .map(|minimized| {
+                    secrets
+                        .exchange(&minimized, resp)
+                        .map(|(request, _)| request)
+                })
+                .transpose()?,
+            None => minimized,
+        };
+        let (stored_request, stored_response) = match &redacted {
+            Some((request, response)) => (request, response),
+            None => (request, resp),
+        };
+        let mut record = FindingRecord::new(
+            &self.run,
+            payload,
+            stored_request,
+            stored_response,
+            kind,
+            &details,
+        )?;
+        record.verification = verification.clone();
+        record.normal = self
+            .samples
+            .as_ref()
+            .and_then(|samples| samples.latest(payload.method, payload.path));
+        let mut record = serde_json::to_value(&record)?;
+        if let Some(secrets) = &secrets {
+            // The payload and the curl command have them too
+            secrets.redact(&mut record);
+        }
+        self.encryption.write(
+            Path::new(&results_file),
+            &serde_json::to_vec_pretty(&record)?,
+        )?;
+        if let (Some(logs), false) = (&self.target_logs, known) {
+            logs.attach(Path::new(&results_file), sent, secrets);
+        }
+        Ok(Detected {
+            kind,
+            details,
+            results_file,
+            verification,
+            redacted,
+            minimized,
+        })
+    }
+
+    /// Replays the exact request of a new finding and counts the responses with its status
+    /// code the checks still find it in, telling real crashes from e.g. a proxy failing from
+    /// time to time. Findings of probes only need the status. The bodies of the reproducing
+    /// responses are compared with the one of the finding.
+    fn reverify(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        resp: &Response,
+        kind: FindingKind,
+        replays: u32,
+    ) -> Verification {
+        let checked = self.detects(payload, request, resp, kind);
+        let mut differences = Vec::new();
+        let reproduced = (0..replays)
+            .filter(|_| match self.resend(request) {
+                Ok(response)
+                    if response.status == resp.status
+                        && (!checked || self.detects(payload, request, &response, kind)) =>
+                {
+                    // The first difference of each value is kept
+                    for difference in diff::differences(resp, &response, &self.checks.ignored) {
+                        let location = difference.split(": ").next();
+                        if !differences
+                            .iter()
+                            .any(|d: &String| d.split(": ").next() == location)
+                        {
+                            differences.push(difference);
+                        }
+                    }
+                    true
+                }
+                Ok(_) => false,
+                Err(e) => {
+                    debug!(error = %format!("{:#}", e), "replay failed");
+                    false
+                }
+            })
+            .count();
+        let mut verification = Verification::new(replays, reproduced as u32);
+        verification.differences = differences;
+        debug!(method = %request.method, url = %redacted_url(&request.url), %verification, "reverified");
+        verification
+    }
+}
+
+/// Applies the options of the matching operations from the config, later ones take precedence
+fn apply_overrides<'a>(overrides: &'a [OperationOverride], payload: &mut Payload<'a>) {
+    let (method, path, operation_id) = (payload.method, payload.path, payload.operation_id);
+    for operation_override in overrides
+        .iter()
+        .filter(|o| o.matches(method, path, operation_id))
+    {
+        payload.apply_override(operation_override);
+    }
+}
+
+/// Applies the user defined generators to the payload
+fn generate(generators: &[Box<dyn Generator>], payload: &mut Payload) {
+    let mut rng = StdRng::seed_from_u64(payload.seed.rotate_left(16));
+    for generator in generators {
+        generator.generate(payload, &mut rng);
+    }
+}
+
+/// Weight of the operation from the config, the last matching one
+fn weight(
+    overrides: &[OperationOverride],
+    method: &str,
+    path: &str,
+    operation_id: Option<&str>,
+) -> f64 {
+    overrides
+        .iter()
+        .rev()
+        .filter(|o| o.matches(method, path, operation_id))
+        .find_map(|o| o.weight)
+        .unwrap_or(1.0)
+}
+
+/// Whether the payload is sent in the repetition of its path, operations get as many payloads
+/// per round as their weight and one more with the probability of its fraction
+fn drawn(
+    overrides: &[OperationOverride],
+    rng: &mut StdRng,
+    repetition: usize,
+    payload: &Payload,
+) -> bool {
+    let weight = weight(
+        overrides,
+        payload.method,
+        payload.path,
+        payload.operation_id,
+    );
+    let repetition = repetition as f64;
+    match weight - repetition {
+        rest if rest >= 1.0 => true,
+        rest if rest > 0.0 => rng.gen_bool(rest),
+        _ => false,
+    }
+}
+
+/// Waits until the next request of the operation fits into its rate limit from the config,
+/// and the next request of the fuzzer into the rate limit of all operations
+fn throttle(
+    overrides: &[OperationOverride],
+    last_requests: &mut BTreeMap<(String, String), Instant>,
+    total_rate_limit: Option<f64>,
+    last_request: &mut Option<Instant>,
+    payload: &Payload,
+) {
+    if let Some(rate_limit) = total_rate_limit.filter(|&rate_limit| rate_limit > 0.0) {
+        if let Some(wait) = last_request
+            .and_then(|last| Duration::from_secs_f64(1.0 / rate_limit).checked_sub(last.elapsed()))
+        {
+            std::thread::sleep(wait);
+        }
+        *last_request = Some(Instant::now());
+    }
+    let rate_limit = overrides
+        .iter()
+        .rev()
+        .filter(|o| o.matches(payload.method, payload.path, payload.operation_id))
+        .find_map(|o| o.rate_limit);
+    let rate_limit = match rate_limit {
+        Some(rate_limit) if rate_limit > 0.0 => rate_limit,
+        _ => return,
+    };
+
+    let key = (payload.path.to_string(), payload.method.to_string());
+    if let Some(last) = last_requests.get(&key) {
+        let interval = Duration::from_secs_f64(1.0 / rate_limit);
+        if let Some(wait) = interval.checked_sub(last.elapsed()) {
+            std::thread::sleep(wait);
+        }
+    }
+    last_requests.insert(key, Instant::now());
+}
+
+/// Directory the findings of the operation with the status and kind are saved to, findings
+/// of status codes are grouped only by the status
+pub fn results_path(
+    results_dir: &str,
+    path: &str,
+    method: &str,
+    status: u16,
+    kind: FindingKind,
+) -> String {
+    let operation = format!(
+        "{}/{}/{}",
+        path.trim_matches('/').replace('/', "-"),
+        method,
+        status
+    );
+    match kind {
+        FindingKind::ServerError
+        | FindingKind::UndocumentedStatus
+        | FindingKind::UnexpectedStatus => {
+            format!("{}/{}", results_dir, operation)
+        }
+        _ => format!("{}/{}/{}", results_dir, operation, kind),
+    }
+}
+
+/// Logs the error of the check of the payload and returns the message shown in the TUI
+fn failed(payload: &Payload, check: &str, e: anyhow::Error) -> String {
+    warn!(method = payload.method, path = payload.path, check, error = %format!("{:#}", e), "check failed");
+    e.to_string()
+}
+
+/// Records the finding and reports it if it is new, returns the errors of the reporters
+fn record(
+    findings: &mut Findings,
+    reporters: &mut [Box<dyn Reporter>],
+    subscribers: &mut Vec<Sender<Finding>>,
+    payload: &Payload,
+    request: &Request,
+    resp: &Response,
+    detected: Detected,
+) -> Option<String> {
+    let (request, resp) = match &detected.redacted {
+        Some((request, response)) => (request, response),
+        None => (request, resp),
+    };
+    let new_finding = findings
+        .add(
+            payload,
+            request,
+            resp,
+            detected.kind,
+            &detected.details,
+            &detected.results_file,
         )
-        .map_err(|e| e.into())
+        .map(|finding| {
+            finding.verification = detected.verification.clone();
+            finding.minimized = detected.minimized.clone();
+            &*finding
+        });
+    debug!(
+        kind = %detected.kind,
+        method = payload.method,
+        path = payload.path,
+        status = resp.status,
+        file = %detected.results_file,
+        "finding"
+    );
+    if let Some(finding) = &new_finding {
+        info!(
+            kind = %finding.kind,
+            severity = %finding.severity,
+            method = payload.method,
+            path = payload.path,
+            status = resp.status,
+            file = %detected.results_file,
+            "new finding"
+        );
+    }
+    if let Some(finding) = &new_finding {
+        subscribers.retain(|subscriber| subscriber.send((*finding).clone()).is_ok());
+    }
+    let finding = new_finding?;
+    let errors = reporters
+        .iter_mut()
+        .filter_map(|reporter| reporter.finding(finding).err())
+        .map(|e| e.to_string())
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        None
+    } else {
+        Some(errors.join(", "))
+    }
+}
+
+/// Seed of the payload without the headers added to all requests
+fn seed_of(extra_headers: &[(String, String)], payload: &Payload) -> Seed {
+    let params = |params: &[(&str, String)]| {
+        params
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    };
+    Seed {
+        path_params: params(&payload.path_params),
+        query_params: params(&payload.query_params),
+        headers: payload
+            .headers
+            .iter()
+            .filter(|(name, _)| {
+                !extra_headers
+                    .iter()
+                    .any(|(extra, _)| name.eq_ignore_ascii_case(extra))
+            })
+            .map(|(name, value)| (name.to_lowercase(), value.clone()))
+            .collect(),
+        body: payload.body.first().cloned(),
+    }
+}
+
+/// Keeps the successful response if it is a new sample of the operation, with the secrets
+/// replaced if findings are redacted
+fn sample(
+    samples: &mut Samples,
+    redactor: Option<&Redactor>,
+    extra_headers: &[(String, String)],
+    payload: &Payload,
+    request: &Request,
+    resp: &Response,
+) {
+    let secrets = redactor.map(|redactor| redactor.secrets(request, resp));
+    if let Err(e) = samples.observe(
+        payload.method,
+        payload.path,
+        || seed_of(extra_headers, payload),
+        resp,
+        secrets.as_ref(),
+    ) {
+        warn!("unable to keep the sample: {:#}", e);
+    }
+}
+
+/// Keeps the payload as a seed if it was accepted and valid
+fn learn(
+    learned_seeds: &mut Option<BTreeMap<(String, String), Vec<Seed>>>,
+    extra_headers: &[(String, String)],
+    payload: &Payload,
+    resp: &Response,
+) {
+    let learned = match learned_seeds {
+        Some(learned) if resp.status / 100 == 2 && payload.violation.is_none() => learned,
+        _ => return,
+    };
+    let seeds = learned
+        .entry((payload.method.to_string(), payload.path.to_string()))
+        .or_default();
+    if seeds.len() >= MAX_LEARNED_SEEDS {
+        return;
     }
+    seeds.push(seed_of(extra_headers, payload));
 }
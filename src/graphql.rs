@@ -0,0 +1,683 @@
+//! GraphQL schemas are fuzzed as OpenAPI specifications with an operation for each field of
+//! the query and mutation types, e.g. `POST /query/user`. The request body of an operation
+//! is an object with its arguments, which `GraphqlRequests` turns into the variables of a
+//! GraphQL request to the endpoint of the API.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use graphql_parser::schema::{Definition, TypeDefinition, TypeExtension};
+use openapi_utils::ReferenceOrExt;
+use openapiv3::OpenAPI;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::payload::{operations, Generator, Payload};
+
+/// Extension of the operations with the parts of their GraphQL requests
+const EXTENSION: &str = "x-graphql";
+
+/// Depth of the selections and of nested input objects when it is not configured
+pub const DEFAULT_DEPTH: usize = 2;
+
+/// Depth of the selections of deep queries
+const DEEP_DEPTH: usize = 10;
+
+/// Most fields selected of each object
+const MAX_FIELDS: usize = 20;
+
+/// Most fields of object types selected of each object in deep queries, the selection grows
+/// by this factor at every level
+const DEEP_WIDTH: usize = 2;
+
+/// Number of aliases of the field in alias abuse queries
+const ALIASES: usize = 50;
+
+/// Number of requests in batch abuse requests
+const BATCH_SIZE: usize = 50;
+
+/// Probability that a request abuses the API in one of the enabled ways
+const ABUSE_PROBABILITY: f64 = 0.2;
+
+/// Ways to abuse a GraphQL API beyond fuzzing the variables
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Abuse {
+    /// The field is queried many times under different aliases
+    Alias,
+    /// Many queries are sent in one request
+    Batch,
+    /// The selection goes much deeper than usual
+    Deep,
+}
+
+impl std::str::FromStr for Abuse {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alias" => Ok(Abuse::Alias),
+            "batch" => Ok(Abuse::Batch),
+            "deep" => Ok(Abuse::Deep),
+            _ => Err(format!(
+                "unknown abuse `{}`, expected alias, batch or deep",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum TypeRef {
+    Named(String),
+    List(Box<TypeRef>),
+    NonNull(Box<TypeRef>),
+}
+
+impl TypeRef {
+    fn name(&self) -> &str {
+        match self {
+            TypeRef::Named(name) => name,
+            TypeRef::List(inner) | TypeRef::NonNull(inner) => inner.name(),
+        }
+    }
+
+    fn required(&self) -> bool {
+        matches!(self, TypeRef::NonNull(_))
+    }
+
+    /// Type in GraphQL syntax, e.g. `[ID!]!`
+    fn to_graphql(&self) -> String {
+        match self {
+            TypeRef::Named(name) => name.clone(),
+            TypeRef::List(inner) => format!("[{}]", inner.to_graphql()),
+            TypeRef::NonNull(inner) => format!("{}!", inner.to_graphql()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct InputValue {
+    name: String,
+    ty: TypeRef,
+}
+
+#[derive(Debug)]
+struct Field {
+    name: String,
+    args: Vec<InputValue>,
+    ty: TypeRef,
+}
+
+#[derive(Debug)]
+enum TypeDef {
+    Scalar,
+    /// Objects and interfaces
+    Object(Vec<Field>),
+    Union,
+    Enum(Vec<String>),
+    InputObject(Vec<InputValue>),
+}
+
+/// Types and root types of a GraphQL schema
+#[derive(Debug, Default)]
+pub struct Schema {
+    types: BTreeMap<String, TypeDef>,
+    query: Option<String>,
+    mutation: Option<String>,
+}
+
+/// Whether the specification file is a GraphQL schema, in SDL or as the result of an
+/// introspection query
+pub fn is_graphql(file: &Path, content: &str) -> bool {
+    matches!(
+        file.extension().and_then(|extension| extension.to_str()),
+        Some("graphql" | "graphqls" | "gql")
+    ) || content.trim_start().starts_with('{') && content.contains("\"__schema\"")
+}
+
+fn sdl_type(ty: &graphql_parser::schema::Type<'_, String>) -> TypeRef {
+    use graphql_parser::schema::Type;
+
+    match ty {
+        Type::NamedType(name) => TypeRef::Named(name.clone()),
+        Type::ListType(inner) => TypeRef::List(Box::new(sdl_type(inner))),
+        Type::NonNullType(inner) => TypeRef::NonNull(Box::new(sdl_type(inner))),
+    }
+}
+
+fn sdl_input_values(values: &[graphql_parser::schema::InputValue<'_, String>]) -> Vec<InputValue> {
+    values
+        .iter()
+        .map(|value| InputValue {
+            name: value.name.clone(),
+            ty: sdl_type(&value.value_type),
+        })
+        .collect()
+}
+
+fn sdl_fields(fields: &[graphql_parser::schema::Field<'_, String>]) -> Vec<Field> {
+    fields
+        .iter()
+        .map(|field| Field {
+            name: field.name.clone(),
+            args: sdl_input_values(&field.arguments),
+            ty: sdl_type(&field.field_type),
+        })
+        .collect()
+}
+
+/// Type of the introspection result, e.g. `{"kind": "NON_NULL", "ofType": {...}}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IntrospectedTypeRef {
+    kind: String,
+    name: Option<String>,
+    of_type: Option<Box<IntrospectedTypeRef>>,
+}
+
+impl IntrospectedTypeRef {
+    fn to_type(&self) -> Result<TypeRef> {
+        let inner = || {
+            self.of_type
+                .as_ref()
+                .ok_or_else(|| anyhow!("{} type without ofType", self.kind))?
+                .to_type()
+        };
+        Ok(match self.kind.as_str() {
+            "NON_NULL" => TypeRef::NonNull(Box::new(inner()?)),
+            "LIST" => TypeRef::List(Box::new(inner()?)),
+            _ => TypeRef::Named(
+                self.name
+                    .clone()
+                    .ok_or_else(|| anyhow!("{} type without name", self.kind))?,
+            ),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IntrospectedInputValue {
+    name: String,
+    #[serde(rename = "type")]
+    ty: IntrospectedTypeRef,
+}
+
+impl IntrospectedInputValue {
+    fn to_input_value(&self) -> Result<InputValue> {
+        Ok(InputValue {
+            name: self.name.clone(),
+            ty: self.ty.to_type()?,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct IntrospectedField {
+    name: String,
+    #[serde(default)]
+    args: Vec<IntrospectedInputValue>,
+    #[serde(rename = "type")]
+    ty: IntrospectedTypeRef,
+}
+
+#[derive(Debug, Deserialize)]
+struct IntrospectedEnumValue {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IntrospectedType {
+    kind: String,
+    name: String,
+    fields: Option<Vec<IntrospectedField>>,
+    input_fields: Option<Vec<IntrospectedInputValue>>,
+    enum_values: Option<Vec<IntrospectedEnumValue>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NamedType {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IntrospectedSchema {
+    query_type: Option<NamedType>,
+    mutation_type: Option<NamedType>,
+    types: Vec<IntrospectedType>,
+}
+
+impl Schema {
+    /// Parses the schema in SDL or the result of an introspection query
+    pub fn parse(content: &str) -> Result<Schema> {
+        match content.trim_start().starts_with('{') {
+            true => Schema::from_introspection(content),
+            false => Schema::from_sdl(content),
+        }
+    }
+
+    fn from_sdl(sdl: &str) -> Result<Schema> {
+        let document = graphql_parser::parse_schema::<String>(sdl)
+            .context("unable to parse GraphQL schema")?;
+        let mut schema = Schema::default();
+        let mut extensions = Vec::new();
+        for definition in document.definitions.iter() {
+            match definition {
+                Definition::SchemaDefinition(definition) => {
+                    schema.query = definition.query.clone();
+                    schema.mutation = definition.mutation.clone();
+                }
+                Definition::TypeDefinition(definition) => {
+                    let (name, ty) = match definition {
+                        TypeDefinition::Scalar(ty) => (&ty.name, TypeDef::Scalar),
+                        TypeDefinition::Object(ty) => {
+                            (&ty.name, TypeDef::Object(sdl_fields(&ty.fields)))
+                        }
+                        TypeDefinition::Interface(ty) => {
+                            (&ty.name, TypeDef::Object(sdl_fields(&ty.fields)))
+                        }
+                        TypeDefinition::Union(ty) => (&ty.name, TypeDef::Union),
+                        TypeDefinition::Enum(ty) => (
+                            &ty.name,
+                            TypeDef::Enum(
+                                ty.values.iter().map(|value| value.name.clone()).collect(),
+                            ),
+                        ),
+                        TypeDefinition::InputObject(ty) => {
+                            (&ty.name, TypeDef::InputObject(sdl_input_values(&ty.fields)))
+                        }
+                    };
+                    schema.types.insert(name.clone(), ty);
+                }
+                Definition::TypeExtension(TypeExtension::Object(extension)) => {
+                    extensions.push((extension.name.clone(), sdl_fields(&extension.fields)));
+                }
+                _ => {}
+            }
+        }
+        // Types are often extended before they are defined, e.g. `extend type Query`
+        for (name, mut fields) in extensions {
+            match schema
+                .types
+                .entry(name)
+                .or_insert(TypeDef::Object(Vec::new()))
+            {
+                TypeDef::Object(existing) => existing.append(&mut fields),
+                _ => return Err(anyhow!("only object types can be extended")),
+            }
+        }
+        schema.query = schema.query.or_else(|| Some("Query".to_string()));
+        schema.mutation = schema.mutation.or_else(|| Some("Mutation".to_string()));
+        Ok(schema)
+    }
+
+    fn from_introspection(content: &str) -> Result<Schema> {
+        let value: Value =
+            serde_json::from_str(content).context("unable to parse introspection")?;
+        let introspected = value
+            .pointer("/data/__schema")
+            .or_else(|| value.get("__schema"))
+            .ok_or_else(|| anyhow!("introspection result without __schema"))?;
+        let introspected: IntrospectedSchema =
+            serde_json::from_value(introspected.clone()).context("invalid introspection")?;
+
+        let mut schema = Schema {
+            query: introspected.query_type.map(|ty| ty.name),
+            mutation: introspected.mutation_type.map(|ty| ty.name),
+            ..Schema::default()
+        };
+        for ty in introspected.types {
+            let def = match ty.kind.as_str() {
+                "OBJECT" | "INTERFACE" => TypeDef::Object(
+                    ty.fields
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|field| {
+                            Ok(Field {
+                                name: field.name.clone(),
+                                args: field
+                                    .args
+                                    .iter()
+                                    .map(IntrospectedInputValue::to_input_value)
+                                    .collect::<Result<_>>()?,
+                                ty: field.ty.to_type()?,
+                            })
+                        })
+                        .collect::<Result<_>>()?,
+                ),
+                "UNION" => TypeDef::Union,
+                "ENUM" => TypeDef::Enum(
+                    ty.enum_values
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|value| value.name)
+                        .collect(),
+                ),
+                "INPUT_OBJECT" => TypeDef::InputObject(
+                    ty.input_fields
+                        .unwrap_or_default()
+                        .iter()
+                        .map(IntrospectedInputValue::to_input_value)
+                        .collect::<Result<_>>()?,
+                ),
+                _ => TypeDef::Scalar,
+            };
+            schema.types.insert(ty.name, def);
+        }
+        Ok(schema)
+    }
+
+    /// JSON schema of the values of an input type, optional fields of nested input objects
+    /// are left out below the depth
+    fn input_schema(&self, ty: &TypeRef, depth: usize) -> Value {
+        match ty {
+            TypeRef::NonNull(inner) => self.input_schema(inner, depth),
+            TypeRef::List(inner) => json!({
+                "type": "array",
+                "items": self.input_schema(inner, depth),
+            }),
+            TypeRef::Named(name) => match (name.as_str(), self.types.get(name)) {
+                ("Int", _) => json!({ "type": "integer", "format": "int32" }),
+                ("Float", _) => json!({ "type": "number" }),
+                ("Boolean", _) => json!({ "type": "boolean" }),
+                (_, Some(TypeDef::Enum(values))) => json!({ "type": "string", "enum": values }),
+                (_, Some(TypeDef::InputObject(fields))) => self.object_schema(
+                    fields.iter().filter(|f| depth > 0 || f.ty.required()),
+                    depth,
+                ),
+                _ => json!({ "type": "string" }),
+            },
+        }
+    }
+
+    fn object_schema<'a>(
+        &self,
+        values: impl Iterator<Item = &'a InputValue>,
+        depth: usize,
+    ) -> Value {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for value in values {
+            properties.insert(
+                value.name.clone(),
+                self.input_schema(&value.ty, depth.saturating_sub(1)),
+            );
+            if value.ty.required() {
+                required.push(value.name.clone());
+            }
+        }
+        let mut schema = json!({ "type": "object", "properties": properties });
+        if !required.is_empty() {
+            schema["required"] = json!(required);
+        }
+        schema
+    }
+
+    /// Selection of the fields of the output type up to the depth, with at most `width`
+    /// fields of object types of each object. Fields with required arguments and types
+    /// already selected on the way from the root are left out, cycles would repeat them.
+    fn selection<'a>(
+        &'a self,
+        ty: &'a TypeRef,
+        depth: usize,
+        width: usize,
+        ancestors: &mut Vec<&'a str>,
+    ) -> Option<String> {
+        match self.types.get(ty.name()) {
+            Some(TypeDef::Object(fields)) => {
+                ancestors.push(ty.name());
+                let mut nested = 0;
+                let selected = fields
+                    .iter()
+                    .filter(|field| !field.args.iter().any(|arg| arg.ty.required()))
+                    .filter_map(|field| match self.types.get(field.ty.name()) {
+                        Some(TypeDef::Object(_)) | Some(TypeDef::Union)
+                            if depth > 1
+                                && nested < width
+                                && !ancestors.contains(&field.ty.name()) =>
+                        {
+                            nested += 1;
+                            self.selection(&field.ty, depth - 1, width, ancestors)
+                                .map(|selection| format!("{} {}", field.name, selection))
+                        }
+                        Some(TypeDef::Object(_)) | Some(TypeDef::Union) => None,
+                        _ => Some(field.name.clone()),
+                    })
+                    .take(MAX_FIELDS)
+                    .collect::<Vec<_>>();
+                ancestors.pop();
+                match selected.is_empty() {
+                    true => Some("{ __typename }".to_string()),
+                    false => Some(format!("{{ {} }}", selected.join(" "))),
+                }
+            }
+            Some(TypeDef::Union) => Some("{ __typename }".to_string()),
+            _ => None,
+        }
+    }
+
+    /// OpenAPI specification with an operation for each field of the root types, `depth`
+    /// is the depth of the selections of the queries
+    pub fn to_openapi(&self, depth: usize) -> Result<OpenAPI> {
+        let mut paths = Map::new();
+        for (operation, root) in [("query", &self.query), ("mutation", &self.mutation)] {
+            let fields = match root.as_ref().and_then(|root| self.types.get(root)) {
+                Some(TypeDef::Object(fields)) => fields,
+                _ => continue,
+            };
+            for field in fields {
+                let variables = field
+                    .args
+                    .iter()
+                    .map(|arg| format!("${}: {}", arg.name, arg.ty.to_graphql()))
+                    .collect::<Vec<_>>();
+                let arguments = field
+                    .args
+                    .iter()
+                    .map(|arg| format!("{}: ${}", arg.name, arg.name))
+                    .collect::<Vec<_>>();
+                let call = match arguments.is_empty() {
+                    true => field.name.clone(),
+                    false => format!("{}({})", field.name, arguments.join(", ")),
+                };
+                let select =
+                    |depth, width| match self.selection(&field.ty, depth, width, &mut Vec::new()) {
+                        Some(selection) => format!("{} {}", call, selection),
+                        None => call.clone(),
+                    };
+
+                let mut spec = json!({
+                    "operationId": field.name,
+                    "tags": [operation],
+                    "responses": {
+                        "200": { "description": "GraphQL response" },
+                        "400": { "description": "Invalid GraphQL request" },
+                    },
+                    EXTENSION: {
+                        "operation": operation,
+                        "variables": match variables.is_empty() {
+                            true => String::new(),
+                            false => format!("({})", variables.join(", ")),
+                        },
+                        "selection": select(depth, MAX_FIELDS),
+                        "deep-selection": select(DEEP_DEPTH, DEEP_WIDTH),
+                    },
+                });
+                if !field.args.is_empty() {
+                    spec["requestBody"] = json!({
+                        "required": true,
+                        "content": {
+                            "application/json": {
+                                "schema": self.object_schema(field.args.iter(), depth),
+                            },
+                        },
+                    });
+                }
+                paths.insert(
+                    format!("/{}/{}", operation, field.name),
+                    json!({ "post": spec }),
+                );
+            }
+        }
+        if paths.is_empty() {
+            return Err(anyhow!("The GraphQL schema has no queries or mutations"));
+        }
+        let spec = json!({
+            "openapi": "3.0.0",
+            "info": { "title": "GraphQL API", "version": "" },
+            "paths": paths,
+        });
+        serde_json::from_value(spec).context("unable to convert GraphQL schema")
+    }
+}
+
+/// Parts of the GraphQL request of an operation
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Request {
+    operation: String,
+    variables: String,
+    selection: String,
+    deep_selection: String,
+}
+
+impl Request {
+    fn document(&self, selection: &str) -> String {
+        format!("{}{} {{ {} }}", self.operation, self.variables, selection)
+    }
+}
+
+/// Turns the generated arguments of the operations of a converted GraphQL schema into
+/// GraphQL requests to the endpoint
+#[derive(Debug)]
+pub struct GraphqlRequests {
+    endpoint: String,
+    abuse: Vec<Abuse>,
+    /// Requests of the operations by method and path
+    requests: BTreeMap<(String, String), Request>,
+}
+
+impl GraphqlRequests {
+    /// Generator for the operations of the specification converted from a GraphQL schema,
+    /// `None` for other specifications
+    pub fn from_spec(
+        schema: &OpenAPI,
+        endpoint: &str,
+        abuse: &[Abuse],
+    ) -> Result<Option<GraphqlRequests>> {
+        let mut requests = BTreeMap::new();
+        for (path, item) in schema.paths.iter() {
+            for (method, operation) in operations(item.to_item_ref()) {
+                let extension = match operation.as_ref().and_then(|o| o.extensions.get(EXTENSION)) {
+                    Some(extension) => extension,
+                    None => continue,
+                };
+                requests.insert(
+                    (method.to_string(), path.clone()),
+                    serde_json::from_value(extension.clone())
+                        .context(format!("invalid {} of {} {}", EXTENSION, method, path))?,
+                );
+            }
+        }
+        Ok((!requests.is_empty()).then(|| GraphqlRequests {
+            endpoint: endpoint.to_string(),
+            abuse: abuse.to_vec(),
+            requests,
+        }))
+    }
+}
+
+impl Generator for GraphqlRequests {
+    fn generate(&self, payload: &mut Payload<'_>, rng: &mut StdRng) {
+        let request = match self
+            .requests
+            .get(&(payload.method.to_string(), payload.path.to_string()))
+        {
+            Some(request) => request,
+            None => return,
+        };
+        let mut variables = payload.body.first().cloned().unwrap_or_else(|| json!({}));
+        // Seeds learned from accepted requests are GraphQL requests already, maybe batched
+        if let Some(first) = variables.as_array().and_then(|batch| batch.first()) {
+            variables = first.clone();
+        }
+        if let Some(learned) = variables.get("variables").cloned() {
+            variables = learned;
+        }
+
+        let abuse = match rng.gen_bool(ABUSE_PROBABILITY) {
+            true => self.abuse.choose(rng).copied(),
+            false => None,
+        };
+        let body = match abuse {
+            None => {
+                json!({ "query": request.document(&request.selection), "variables": variables })
+            }
+            Some(Abuse::Deep) => {
+                json!({ "query": request.document(&request.deep_selection), "variables": variables })
+            }
+            Some(Abuse::Alias) => {
+                let aliased = (0..ALIASES)
+                    .map(|i| format!("a{}: {}", i, request.selection))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                json!({ "query": request.document(&aliased), "variables": variables })
+            }
+            Some(Abuse::Batch) => Value::Array(vec![
+                json!({
+                    "query": request.document(&request.selection),
+                    "variables": variables,
+                });
+                BATCH_SIZE
+            ]),
+        };
+        payload.body = vec![body];
+        payload.endpoint = Some(self.endpoint.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_cycles_of_deep_selections() {
+        let schema = Schema::parse(
+            "type Query { user: User }
+             type User { id: ID friends: [User] posts: [Post] }
+             type Post { title: String author: User tags: [Tag] }
+             type Tag { name: String }",
+        )
+        .unwrap();
+        let ty = TypeRef::Named("User".to_string());
+        assert_eq!(
+            schema.selection(&ty, DEEP_DEPTH, DEEP_WIDTH, &mut Vec::new()),
+            Some("{ id posts { title tags { name } } }".to_string())
+        );
+    }
+
+    #[test]
+    fn caps_the_fields_of_objects() {
+        let fields = (0..30)
+            .map(|i| format!("f{}: Int", i))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let schema = Schema::parse(&format!(
+            "type Query {{ wide: Wide }} type Wide {{ {} }}",
+            fields
+        ))
+        .unwrap();
+        let selection = schema
+            .selection(
+                &TypeRef::Named("Wide".to_string()),
+                2,
+                MAX_FIELDS,
+                &mut Vec::new(),
+            )
+            .unwrap();
+        assert_eq!(selection.split(' ').count(), MAX_FIELDS + 2);
+    }
+}
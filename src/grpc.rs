@@ -0,0 +1,786 @@
+//! gRPC services exposed as REST by grpc-gateway or another JSON transcoder are fuzzed from
+//! their descriptor set, e.g. `protoc --include_imports --descriptor_set_out=api.pb`, as the
+//! OpenAPI specification of their `google.api.http` bindings. Each binding of a method is an
+//! operation: the variables of its path template are path parameters, the body is the request
+//! message or the field of the binding, and the other fields are query parameters. Messages are
+//! the schemas of the components with the JSON names of their fields, and client errors are
+//! the `google.rpc.Status` the transcoder answers them with. Methods without a binding and client
+//! streaming ones are not transcoded and are left out.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{anyhow, Result};
+use prost::Message;
+use serde_json::{json, Map, Value};
+use tracing::debug;
+
+/// Nested messages are flattened into query parameters up to this depth
+const MAX_QUERY_DEPTH: usize = 3;
+
+const STATUS: &str = "google.rpc.Status";
+
+/// Statuses the transcoder answers the gRPC codes of client errors with, e.g. `400` for
+/// `INVALID_ARGUMENT` and `409` for `ALREADY_EXISTS`, server errors are findings
+const CLIENT_ERRORS: [&str; 6] = ["400", "401", "403", "404", "409", "429"];
+
+/// The parts of `google/protobuf/descriptor.proto` and `google/api/http.proto` the operations
+/// are made from, other fields are skipped when the descriptor set is decoded
+#[derive(Clone, PartialEq, Message)]
+struct FileDescriptorSet {
+    #[prost(message, repeated, tag = "1")]
+    file: Vec<FileDescriptorProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FileDescriptorProto {
+    #[prost(string, optional, tag = "2")]
+    package: Option<String>,
+    #[prost(message, repeated, tag = "4")]
+    message_type: Vec<DescriptorProto>,
+    #[prost(message, repeated, tag = "5")]
+    enum_type: Vec<EnumDescriptorProto>,
+    #[prost(message, repeated, tag = "6")]
+    service: Vec<ServiceDescriptorProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct DescriptorProto {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(message, repeated, tag = "2")]
+    field: Vec<FieldDescriptorProto>,
+    #[prost(message, repeated, tag = "3")]
+    nested_type: Vec<DescriptorProto>,
+    #[prost(message, repeated, tag = "4")]
+    enum_type: Vec<EnumDescriptorProto>,
+    #[prost(message, optional, tag = "7")]
+    options: Option<MessageOptions>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MessageOptions {
+    #[prost(bool, optional, tag = "7")]
+    map_entry: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FieldDescriptorProto {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(int32, optional, tag = "4")]
+    label: Option<i32>,
+    #[prost(int32, optional, tag = "5")]
+    r#type: Option<i32>,
+    #[prost(string, optional, tag = "6")]
+    type_name: Option<String>,
+    #[prost(string, optional, tag = "10")]
+    json_name: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct EnumDescriptorProto {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(message, repeated, tag = "2")]
+    value: Vec<EnumValueDescriptorProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct EnumValueDescriptorProto {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ServiceDescriptorProto {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(message, repeated, tag = "2")]
+    method: Vec<MethodDescriptorProto>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MethodDescriptorProto {
+    #[prost(string, optional, tag = "1")]
+    name: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    input_type: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    output_type: Option<String>,
+    #[prost(message, optional, tag = "4")]
+    options: Option<MethodOptions>,
+    #[prost(bool, optional, tag = "5")]
+    client_streaming: Option<bool>,
+    #[prost(bool, optional, tag = "6")]
+    server_streaming: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MethodOptions {
+    /// The `google.api.http` extension
+    #[prost(message, optional, tag = "72295728")]
+    http: Option<HttpRule>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct HttpRule {
+    #[prost(string, optional, tag = "2")]
+    get: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    put: Option<String>,
+    #[prost(string, optional, tag = "4")]
+    post: Option<String>,
+    #[prost(string, optional, tag = "5")]
+    delete: Option<String>,
+    #[prost(string, optional, tag = "6")]
+    patch: Option<String>,
+    #[prost(string, optional, tag = "7")]
+    body: Option<String>,
+    #[prost(message, optional, tag = "8")]
+    custom: Option<CustomHttpPattern>,
+    #[prost(message, repeated, tag = "11")]
+    additional_bindings: Vec<HttpRule>,
+    #[prost(string, optional, tag = "12")]
+    response_body: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct CustomHttpPattern {
+    #[prost(string, optional, tag = "1")]
+    kind: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    path: Option<String>,
+}
+
+const LABEL_REPEATED: i32 = 3;
+const TYPE_MESSAGE: i32 = 11;
+const TYPE_ENUM: i32 = 14;
+
+impl HttpRule {
+    /// Method and path template of the binding
+    fn pattern(&self) -> Option<(String, &str)> {
+        let patterns = [
+            ("get", &self.get),
+            ("put", &self.put),
+            ("post", &self.post),
+            ("delete", &self.delete),
+            ("patch", &self.patch),
+        ];
+        if let Some((method, Some(template))) =
+            patterns.iter().find(|(_, template)| template.is_some())
+        {
+            return Some((method.to_string(), template));
+        }
+        let custom = self.custom.as_ref()?;
+        Some((
+            custom.kind.as_deref()?.to_lowercase(),
+            custom.path.as_deref()?,
+        ))
+    }
+}
+
+impl FieldDescriptorProto {
+    /// Name of the field in JSON, the lowerCamelCase of its name unless it is set
+    fn key(&self) -> String {
+        if let Some(json_name) = &self.json_name {
+            return json_name.clone();
+        }
+        let mut json_name = String::new();
+        let mut upper = false;
+        for c in self.name().chars() {
+            match (c, upper) {
+                ('_', _) => upper = true,
+                (c, true) => {
+                    json_name.extend(c.to_uppercase());
+                    upper = false;
+                }
+                (c, false) => json_name.push(c),
+            }
+        }
+        json_name
+    }
+
+    fn repeated(&self) -> bool {
+        self.label == Some(LABEL_REPEATED)
+    }
+}
+
+/// Schema of well-known types, which have a JSON mapping of their own
+fn well_known(type_name: &str) -> Option<Value> {
+    let schema = match type_name.strip_prefix(".google.protobuf.")? {
+        "Timestamp" => json!({"type": "string", "format": "date-time"}),
+        "Duration" | "FieldMask" | "StringValue" => json!({"type": "string"}),
+        // Any JSON value
+        "Value" => json!({}),
+        "BytesValue" => json!({"type": "string", "format": "byte"}),
+        "BoolValue" => json!({"type": "boolean"}),
+        "DoubleValue" | "FloatValue" => json!({"type": "number"}),
+        "Int32Value" | "UInt32Value" => json!({"type": "integer", "format": "int32"}),
+        "Int64Value" => json!({"type": "string", "format": "int64"}),
+        "UInt64Value" => json!({"type": "string", "format": "uint64"}),
+        "Struct" | "Empty" => json!({"type": "object"}),
+        "ListValue" => json!({"type": "array", "items": {}}),
+        "Any" => json!({"type": "object", "properties": {"@type": {"type": "string"}}}),
+        _ => return None,
+    };
+    Some(schema)
+}
+
+/// Schema of a scalar type of fields, by its number in `FieldDescriptorProto.Type`. 64-bit
+/// integers are strings in the JSON mapping, they do not fit in a JSON number.
+fn scalar(type_number: i32) -> Value {
+    match type_number {
+        1 => json!({"type": "number", "format": "double"}),
+        2 => json!({"type": "number", "format": "float"}),
+        5 | 15 | 17 => json!({"type": "integer", "format": "int32"}),
+        13 | 7 => json!({"type": "integer", "format": "int64", "minimum": 0, "maximum": u32::MAX}),
+        3 | 16 | 18 => json!({"type": "string", "format": "int64"}),
+        4 | 6 => json!({"type": "string", "format": "uint64"}),
+        8 => json!({"type": "boolean"}),
+        12 => json!({"type": "string", "format": "byte"}),
+        _ => json!({"type": "string"}),
+    }
+}
+
+/// Variable of a path template bound to a field of the request
+struct Variable {
+    /// Path of the field, e.g. `book.name`
+    field: String,
+    /// Path parameter, the field of a variable of a single segment, and the literal segment
+    /// before them for the segments of a variable with a pattern
+    parameter: String,
+    /// Whether the parameter is the whole value of the field
+    whole: bool,
+}
+
+/// OpenAPI path of the path template with its variables, e.g. `/v1/shelves/{shelves}/books/{books}`
+/// of `/v1/{name=shelves/*/books/*}`
+fn path(template: &str) -> Result<(String, Vec<Variable>)> {
+    let mut path = String::new();
+    let mut variables = Vec::<Variable>::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        path += &rest[..start];
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| anyhow!("unclosed variable in {}", template))?;
+        let variable = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+        let (field, pattern) = match variable.split_once('=') {
+            Some((field, pattern)) => (field.trim(), Some(pattern.trim())),
+            None => (variable.trim(), None),
+        };
+        let pattern = match pattern {
+            None | Some("*") => {
+                path += &format!("{{{}}}", field);
+                variables.push(Variable {
+                    field: field.to_string(),
+                    parameter: field.to_string(),
+                    whole: true,
+                });
+                continue;
+            }
+            Some(pattern) => pattern,
+        };
+        let mut previous = field;
+        let mut segments = Vec::new();
+        for segment in pattern.split('/') {
+            match segment {
+                "*" | "**" => {
+                    let mut parameter = previous.to_string();
+                    let mut n = 2;
+                    while variables.iter().any(|v| v.parameter == parameter) {
+                        parameter = format!("{}{}", previous, n);
+                        n += 1;
+                    }
+                    segments.push(format!("{{{}}}", parameter));
+                    variables.push(Variable {
+                        field: field.to_string(),
+                        parameter,
+                        whole: false,
+                    });
+                }
+                literal => {
+                    previous = literal;
+                    segments.push(literal.to_string());
+                }
+            }
+        }
+        path += &segments.join("/");
+    }
+    Ok((path + rest, variables))
+}
+
+/// Messages and enums of the descriptor set by their full names, e.g. `.library.v1.Book`
+struct Types<'a> {
+    messages: BTreeMap<String, &'a DescriptorProto>,
+    enums: BTreeMap<String, &'a EnumDescriptorProto>,
+}
+
+impl<'a> Types<'a> {
+    fn new(set: &'a FileDescriptorSet) -> Types<'a> {
+        let mut types = Types {
+            messages: BTreeMap::new(),
+            enums: BTreeMap::new(),
+        };
+        for file in set.file.iter() {
+            let prefix = match file.package.as_deref() {
+                Some(package) if !package.is_empty() => format!(".{}", package),
+                _ => String::new(),
+            };
+            types.add(&prefix, &file.message_type, &file.enum_type);
+        }
+        types
+    }
+
+    fn add(
+        &mut self,
+        prefix: &str,
+        messages: &'a [DescriptorProto],
+        enums: &'a [EnumDescriptorProto],
+    ) {
+        for enumeration in enums {
+            let name = format!("{}.{}", prefix, enumeration.name());
+            self.enums.insert(name, enumeration);
+        }
+        for message in messages {
+            let name = format!("{}.{}", prefix, message.name());
+            self.add(&name, &message.nested_type, &message.enum_type);
+            self.messages.insert(name, message);
+        }
+    }
+
+    fn message(&self, type_name: &str) -> Result<&'a DescriptorProto> {
+        self.messages
+            .get(type_name)
+            .copied()
+            .ok_or_else(|| anyhow!("unknown message {}, include the imports", type_name))
+    }
+
+    /// Entry of a map field, its message has a key and a value field
+    fn map_entry(&self, field: &FieldDescriptorProto) -> Option<&'a DescriptorProto> {
+        let message = self.messages.get(field.type_name())?;
+        match message
+            .options
+            .as_ref()
+            .and_then(|options| options.map_entry)
+        {
+            Some(true) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Field of the message at the path, e.g. `book.name`
+    fn field(&self, message: &'a DescriptorProto, path: &str) -> Option<&'a FieldDescriptorProto> {
+        let (name, rest) = match path.split_once('.') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (path, None),
+        };
+        let field = message.field.iter().find(|field| field.name() == name)?;
+        match rest {
+            Some(rest) => self.field(self.messages.get(field.type_name())?, rest),
+            None => Some(field),
+        }
+    }
+}
+
+/// Schemas of the components being made from the messages
+struct Schemas<'a> {
+    types: &'a Types<'a>,
+    schemas: Map<String, Value>,
+    /// Messages whose schemas are being made, references to them are cycles
+    pending: Vec<String>,
+}
+
+impl<'a> Schemas<'a> {
+    /// Schema of a value of the field, an item of it if it is repeated
+    fn value(&mut self, field: &FieldDescriptorProto) -> Value {
+        match field.r#type() {
+            TYPE_MESSAGE => self.reference(field.type_name()),
+            TYPE_ENUM => match self.types.enums.get(field.type_name()) {
+                Some(enumeration) => json!({
+                    "type": "string",
+                    "enum": enumeration
+                        .value
+                        .iter()
+                        .filter_map(|value| value.name.clone())
+                        .collect::<Vec<_>>(),
+                }),
+                None => json!({"type": "string"}),
+            },
+            type_number => scalar(type_number),
+        }
+    }
+
+    fn field(&mut self, field: &FieldDescriptorProto) -> Value {
+        if let Some(entry) = self.types.map_entry(field) {
+            let value = entry
+                .field
+                .iter()
+                .find(|field| field.name() == "value")
+                .map_or_else(|| json!({"type": "string"}), |value| self.value(value));
+            return json!({"type": "object", "additionalProperties": value});
+        }
+        match field.repeated() {
+            true => json!({"type": "array", "items": self.value(field)}),
+            false => self.value(field),
+        }
+    }
+
+    /// Schema of the message with its fields, except for the left out ones
+    fn message(&mut self, message: &DescriptorProto, left_out: &[&str]) -> Value {
+        let properties = message
+            .field
+            .iter()
+            .filter(|field| !left_out.contains(&field.name()))
+            .map(|field| (field.key(), self.field(field)))
+            .collect::<Map<_, _>>();
+        json!({"type": "object", "properties": properties})
+    }
+
+    /// Reference to the schema of the message in the components, made the first time
+    fn reference(&mut self, type_name: &str) -> Value {
+        if let Some(schema) = well_known(type_name) {
+            return schema;
+        }
+        let name = type_name.trim_start_matches('.').to_string();
+        // Recursive messages end with an object of any fields
+        if self.pending.contains(&name) {
+            return json!({"type": "object"});
+        }
+        if !self.schemas.contains_key(&name) {
+            let message = match self.types.messages.get(type_name) {
+                Some(message) => *message,
+                None => return json!({"type": "object"}),
+            };
+            self.pending.push(name.clone());
+            let schema = self.message(message, &[]);
+            self.pending.pop();
+            self.schemas.insert(name.clone(), schema);
+        }
+        json!({"$ref": format!("#/components/schemas/{}", name)})
+    }
+
+    /// Query parameters of the fields of the message not bound to the path or the body,
+    /// nested messages with the JSON names of their fields joined by dots
+    fn query(
+        &mut self,
+        message: &DescriptorProto,
+        (prefix, json_prefix): (&str, &str),
+        bound: &[String],
+        depth: usize,
+        parameters: &mut Vec<Value>,
+    ) {
+        for field in message.field.iter() {
+            let path = format!("{}{}", prefix, field.name());
+            if bound.iter().any(|bound| bound == &path) || self.types.map_entry(field).is_some() {
+                continue;
+            }
+            let name = format!("{}{}", json_prefix, field.key());
+            let nested = match field.r#type {
+                Some(TYPE_MESSAGE) if well_known(field.type_name()).is_none() => {
+                    self.types.messages.get(field.type_name()).copied()
+                }
+                _ => None,
+            };
+            match nested {
+                Some(nested) if !field.repeated() && depth < MAX_QUERY_DEPTH => self.query(
+                    nested,
+                    (&format!("{}.", path), &format!("{}.", name)),
+                    bound,
+                    depth + 1,
+                    parameters,
+                ),
+                Some(_) => {}
+                None => parameters.push(json!({
+                    "name": name,
+                    "in": "query",
+                    "schema": self.field(field),
+                })),
+            }
+        }
+    }
+}
+
+/// Operation of a binding of the method, with its path and method
+fn operation(
+    schemas: &mut Schemas,
+    method: &MethodDescriptorProto,
+    rule: &HttpRule,
+    operation_id: String,
+    service: &str,
+) -> Result<Option<(String, String, Value)>> {
+    let (http_method, template) = match rule.pattern() {
+        Some(pattern) => pattern,
+        None => return Ok(None),
+    };
+    let input = schemas.types.message(method.input_type())?;
+    let (path, variables) = path(template)?;
+
+    let mut parameters = variables
+        .iter()
+        .map(|variable| {
+            let schema = match (variable.whole, schemas.types.field(input, &variable.field)) {
+                (true, Some(field)) if field.r#type != Some(TYPE_MESSAGE) => schemas.value(field),
+                _ => json!({"type": "string"}),
+            };
+            json!({
+                "name": variable.parameter,
+                "in": "path",
+                "required": true,
+                "schema": schema,
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut bound = variables
+        .iter()
+        .map(|variable| variable.field.clone())
+        .collect::<Vec<_>>();
+    bound.dedup();
+
+    let body = match rule.body() {
+        "" => None,
+        "*" => {
+            let left_out = bound
+                .iter()
+                .filter(|field| !field.contains('.'))
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            Some(schemas.message(input, &left_out))
+        }
+        name => {
+            let field = schemas
+                .types
+                .field(input, name)
+                .ok_or_else(|| anyhow!("no field {} of the body in the request", name))?;
+            bound.push(name.to_string());
+            Some(schemas.field(field))
+        }
+    };
+    if rule.body.as_deref() != Some("*") {
+        schemas.query(input, ("", ""), &bound, 0, &mut parameters);
+    }
+
+    let output = method.output_type();
+    let response = match rule.response_body() {
+        "" => schemas.reference(output),
+        name => match schemas.types.field(schemas.types.message(output)?, name) {
+            Some(field) => schemas.field(field),
+            None => return Err(anyhow!("no field {} of the response body", name)),
+        },
+    };
+    // Streamed messages are sent as lines of JSON, which the responses are not validated for
+    let success = match method.server_streaming() {
+        true => json!({"description": "Stream of messages"}),
+        false => json!({
+            "description": "A successful response",
+            "content": {"application/json": {"schema": response}},
+        }),
+    };
+
+    let mut responses = Map::new();
+    responses.insert("200".to_string(), success);
+    for status in CLIENT_ERRORS.iter() {
+        responses.insert(
+            status.to_string(),
+            json!({
+                "description": "An error response",
+                "content": {"application/json": {"schema": {
+                    "$ref": format!("#/components/schemas/{}", STATUS),
+                }}},
+            }),
+        );
+    }
+    let mut operation = json!({
+        "operationId": operation_id,
+        "tags": [service],
+        "parameters": parameters,
+        "responses": responses,
+    });
+    if let Some(body) = body {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": {"application/json": {"schema": body}},
+        });
+    }
+    Ok(Some((path, http_method, operation)))
+}
+
+/// Whether the specification file is a binary descriptor set, by its extension
+pub fn is_descriptor_set(file: &Path, content: &[u8]) -> bool {
+    matches!(
+        file.extension().and_then(|extension| extension.to_str()),
+        Some("pb" | "binpb" | "desc" | "protoset")
+    ) || std::str::from_utf8(content).is_err()
+        && FileDescriptorSet::decode(content).is_ok_and(|set| !set.file.is_empty())
+}
+
+/// OpenAPI specification of the `google.api.http` bindings of the services of the descriptor
+/// set, as JSON
+pub fn to_openapi(content: &[u8]) -> Result<String> {
+    let set = FileDescriptorSet::decode(content)?;
+    let types = Types::new(&set);
+    let mut schemas = Schemas {
+        types: &types,
+        schemas: Map::new(),
+        pending: Vec::new(),
+    };
+    schemas.schemas.insert(
+        STATUS.to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "code": {"type": "integer", "format": "int32"},
+                "message": {"type": "string"},
+                "details": {"type": "array", "items": {
+                    "type": "object",
+                    "properties": {"@type": {"type": "string"}},
+                }},
+            },
+        }),
+    );
+
+    let mut paths = Map::new();
+    let mut services = Vec::new();
+    for file in set.file.iter() {
+        for service in file.service.iter() {
+            let service_name = service.name();
+            services.push(service_name);
+            for method in service.method.iter() {
+                let method_name = method.name();
+                let rule = match method
+                    .options
+                    .as_ref()
+                    .and_then(|options| options.http.as_ref())
+                {
+                    Some(rule) if !method.client_streaming() => rule,
+                    _ => {
+                        debug!(
+                            service = service_name,
+                            method = method_name,
+                            "not transcoded"
+                        );
+                        continue;
+                    }
+                };
+                let rules = std::iter::once(rule).chain(rule.additional_bindings.iter());
+                for (i, rule) in rules.enumerate() {
+                    let operation_id = match i {
+                        0 => format!("{}_{}", service_name, method_name),
+                        i => format!("{}_{}{}", service_name, method_name, i + 1),
+                    };
+                    let operation =
+                        operation(&mut schemas, method, rule, operation_id, service_name).map_err(
+                            |e| {
+                                e.context(format!(
+                                    "invalid binding of {}.{}",
+                                    service_name, method_name
+                                ))
+                            },
+                        )?;
+                    if let Some((path, http_method, operation)) = operation {
+                        let item = paths.entry(path).or_insert_with(|| json!({}));
+                        if item.get(&http_method).is_none() {
+                            item[http_method] = operation;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if paths.is_empty() {
+        return Err(anyhow!(
+            "no method of the descriptor set has a google.api.http binding"
+        ));
+    }
+    let document = json!({
+        "openapi": "3.0.0",
+        "info": {"title": services.join(", "), "version": "1"},
+        "paths": paths,
+        "components": {"schemas": schemas.schemas},
+    });
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, type_number: i32, type_name: Option<&str>) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            label: None,
+            r#type: Some(type_number),
+            type_name: type_name.map(str::to_string),
+            json_name: None,
+        }
+    }
+
+    #[test]
+    fn makes_paths_of_templates() {
+        let (path, variables) = path("/v1/{name=shelves/*/books/*}:get").unwrap();
+        assert_eq!(path, "/v1/shelves/{shelves}/books/{books}:get");
+        assert_eq!(
+            variables
+                .iter()
+                .map(|v| (v.field.as_str(), v.parameter.as_str(), v.whole))
+                .collect::<Vec<_>>(),
+            vec![("name", "shelves", false), ("name", "books", false)]
+        );
+        let (path, variables) = super::path("/v1/books/{book_id}").unwrap();
+        assert_eq!(path, "/v1/books/{book_id}");
+        assert!(variables[0].whole);
+        assert!(super::path("/v1/{name").is_err());
+    }
+
+    #[test]
+    fn converts_bindings() {
+        let book = DescriptorProto {
+            name: Some("Book".to_string()),
+            field: vec![
+                field("book_id", 3, None),
+                field("size", 4, None),
+                field("extra", TYPE_MESSAGE, Some(".google.protobuf.Value")),
+                field("tags", TYPE_MESSAGE, Some(".google.protobuf.ListValue")),
+            ],
+            ..DescriptorProto::default()
+        };
+        let method = MethodDescriptorProto {
+            name: Some("GetBook".to_string()),
+            input_type: Some(".library.Book".to_string()),
+            output_type: Some(".library.Book".to_string()),
+            options: Some(MethodOptions {
+                http: Some(HttpRule {
+                    get: Some("/v1/books/{book_id}".to_string()),
+                    ..HttpRule::default()
+                }),
+            }),
+            ..MethodDescriptorProto::default()
+        };
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                package: Some("library".to_string()),
+                message_type: vec![book],
+                enum_type: Vec::new(),
+                service: vec![ServiceDescriptorProto {
+                    name: Some("Library".to_string()),
+                    method: vec![method],
+                }],
+            }],
+        };
+        let openapi = to_openapi(&set.encode_to_vec()).unwrap();
+        let document: Value = serde_json::from_str(&openapi).unwrap();
+        let operation = &document["paths"]["/v1/books/{book_id}"]["get"];
+        assert_eq!(operation["operationId"], "Library_GetBook");
+        assert_eq!(
+            operation["parameters"][0]["schema"],
+            json!({"type": "string", "format": "int64"})
+        );
+        let book = &document["components"]["schemas"]["library.Book"]["properties"];
+        assert_eq!(book["size"], json!({"type": "string", "format": "uint64"}));
+        assert_eq!(book["extra"], json!({}));
+        assert_eq!(book["tags"], json!({"type": "array", "items": {}}));
+        serde_json::from_str::<openapiv3::OpenAPI>(&openapi).unwrap();
+    }
+}
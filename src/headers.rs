@@ -0,0 +1,226 @@
+//! Anomalies of the headers of responses, which the checks of the status and the body miss:
+//! headers naming the software of the server and its version, see `disclosure`, bodies
+//! without a `Content-Type`, and `Content-Length` headers that are malformed, repeated with
+//! other values, sent along a `Transfer-Encoding` or not matching the body, on which proxies
+//! and clients disagree where the response ends. The headers disclosing the software are
+//! usually sent by the whole server, so each of them is reported once per run.
+
+use std::collections::BTreeSet;
+
+use crate::disclosure;
+use crate::transport::{Request, Response, MAX_BODY_SIZE};
+
+/// Headers a response has a single value of
+const SINGLETONS: [&str; 6] = [
+    "content-type",
+    "content-encoding",
+    "location",
+    "etag",
+    "last-modified",
+    "access-control-allow-origin",
+];
+
+/// Values of the header, in the order they were received
+fn values<'a>(response: &'a Response, name: &str) -> Vec<&'a str> {
+    response
+        .headers
+        .iter()
+        .filter(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+        .collect()
+}
+
+/// Whether the response to the request has no body by definition
+fn without_body(request: &Request, response: &Response) -> bool {
+    request.method.eq_ignore_ascii_case("HEAD")
+        || response.status / 100 == 1
+        || response.status == 204
+        || response.status == 304
+}
+
+fn content_length(request: &Request, response: &Response) -> Vec<String> {
+    // Repeated values may also be sent as a list in one header
+    let lengths = values(response, "content-length")
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    let first = match lengths.first() {
+        Some(first) => *first,
+        None => return Vec::new(),
+    };
+
+    let mut anomalies = Vec::new();
+    if let Some(invalid) = lengths
+        .iter()
+        .find(|length| length.is_empty() || !length.chars().all(|c| c.is_ascii_digit()))
+    {
+        anomalies.push(format!("invalid `content-length` header `{}`", invalid));
+        return anomalies;
+    }
+    if lengths.iter().any(|length| length != &first) {
+        anomalies.push(format!(
+            "conflicting `content-length` headers `{}`",
+            lengths.join("`, `")
+        ));
+        return anomalies;
+    }
+    if let Some(encoding) = response.header("transfer-encoding") {
+        anomalies.push(format!(
+            "`content-length` header `{}` sent with `transfer-encoding` `{}`",
+            first, encoding
+        ));
+    }
+    if response.status / 100 == 1 || response.status == 204 {
+        anomalies.push(format!(
+            "`content-length` header `{}` in a response with status {}",
+            first, response.status
+        ));
+        return anomalies;
+    }
+
+    let length = match first.parse::<u64>() {
+        Ok(length) => length,
+        Err(_) => {
+            anomalies.push(format!("`content-length` header `{}` is too large", first));
+            return anomalies;
+        }
+    };
+    // Only bodies read whole and decoded as they were received can be compared
+    let comparable = !without_body(request, response)
+        && response.events.is_empty()
+        && response.header("content-encoding").is_none()
+        && (response.body.len() as u64) < MAX_BODY_SIZE
+        && !response.body.contains(char::REPLACEMENT_CHARACTER);
+    if comparable && length != response.body.len() as u64 {
+        anomalies.push(format!(
+            "`content-length` header is {} but the body has {} bytes",
+            length,
+            response.body.len()
+        ));
+    }
+    anomalies
+}
+
+/// Returns the anomalies of the headers of the response to the request, leaving out the
+/// disclosing headers already reported, which are added to them
+pub fn anomalies(
+    request: &Request,
+    response: &Response,
+    reported: &mut BTreeSet<(String, String)>,
+) -> Vec<String> {
+    let mut anomalies = Vec::new();
+    for (name, value) in disclosure::disclosing_headers(response) {
+        if reported.insert((name.to_string(), value.to_string())) {
+            anomalies.push(format!("`{}` header discloses `{}`", name, value));
+        }
+    }
+
+    if !without_body(request, response)
+        && !response.body.is_empty()
+        && response.header("content-type").is_none_or(str::is_empty)
+    {
+        anomalies.push(format!(
+            "missing `content-type` header for a body of {} bytes",
+            response.body.len()
+        ));
+    }
+    anomalies.extend(content_length(request, response));
+
+    for name in SINGLETONS.iter() {
+        let mut values = values(response, name);
+        values.dedup();
+        if values.len() > 1 {
+            anomalies.push(format!(
+                "conflicting `{}` headers `{}`",
+                name,
+                values.join("`, `")
+            ));
+        }
+    }
+    anomalies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    fn request() -> Request {
+        Request {
+            method: "GET".to_string(),
+            url: Url::parse("http://localhost/items").unwrap(),
+            headers: Vec::new(),
+            body: None,
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        }
+    }
+
+    fn response(headers: &[(&str, &str)], body: &str) -> Response {
+        Response {
+            status: 200,
+            status_text: "OK".to_string(),
+            http_version: "HTTP/1.1".to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: body.to_string(),
+            events: Vec::new(),
+            elapsed: Default::default(),
+        }
+    }
+
+    #[test]
+    fn reports_disclosing_headers_once() {
+        let response = response(
+            &[
+                ("Server", "nginx/1.25.3"),
+                ("X-Powered-By", "Express"),
+                ("Content-Type", "application/json"),
+            ],
+            "{}",
+        );
+        let mut reported = BTreeSet::new();
+        assert_eq!(
+            anomalies(&request(), &response, &mut reported),
+            vec![
+                "`server` header discloses `nginx/1.25.3`",
+                "`x-powered-by` header discloses `Express`",
+            ]
+        );
+        assert!(anomalies(&request(), &response, &mut reported).is_empty());
+    }
+
+    #[test]
+    fn reports_bodies_and_lengths_headers_disagree_on() {
+        let mut reported = BTreeSet::new();
+        let body = response(&[("Content-Length", "5")], "{}");
+        assert_eq!(
+            anomalies(&request(), &body, &mut reported),
+            vec![
+                "missing `content-type` header for a body of 2 bytes",
+                "`content-length` header is 5 but the body has 2 bytes",
+            ]
+        );
+        let conflicting = response(
+            &[
+                ("Content-Type", "text/plain"),
+                ("Content-Length", "2, 3"),
+                ("Content-Type", "application/json"),
+            ],
+            "{}",
+        );
+        assert_eq!(
+            anomalies(&request(), &conflicting, &mut reported),
+            vec![
+                "conflicting `content-length` headers `2`, `3`",
+                "conflicting `content-type` headers `text/plain`, `application/json`",
+            ]
+        );
+        let fine = response(&[("Content-Type", "application/json")], "{}");
+        assert!(anomalies(&request(), &fine, &mut reported).is_empty());
+    }
+}
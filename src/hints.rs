@@ -0,0 +1,338 @@
+//! Fuzzing guidance embedded in the specification with vendor extensions, so that the authors
+//! of an API can keep it in the contract they already maintain:
+//!
+//! - `x-fuzzer-skip: true` on an operation leaves it out, on a parameter or on the schema of a
+//!   property the parameter or property is never sent
+//! - `x-fuzzer-weight` of an operation is its relative number of payloads per round, e.g. `3`
+//!   or `0.1`
+//! - `x-fuzzer-examples` of a parameter or schema are values sent instead of generated ones
+//!   from time to time, of an operation they are seeds of its payloads (path_params,
+//!   query_params, headers and body)
+//! - `x-fuzzer-max-rate` of an operation is its highest number of requests per second
+//!
+//! Weights and rates of the config take precedence.
+
+use std::collections::BTreeMap;
+
+use openapiv3::{OpenAPI, ReferenceOr};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+use serde_json::{Map, Value};
+use tracing::{debug, warn};
+
+use crate::conditional::{properties_of, resolve};
+use crate::config::OperationOverride;
+use crate::corpus::Seed;
+use crate::payload::{Generator, Payload};
+use crate::status::Operations;
+
+/// Probability that a value with examples is replaced by one of them
+const EXAMPLE_PROBABILITY: f64 = 0.5;
+
+/// Schemas nested deeper are not looked into, e.g. recursive ones
+const MAX_DEPTH: usize = 16;
+
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Hints of a parameter, or of a schema and the schemas nested in it
+#[derive(Debug, Default)]
+struct Field {
+    skip: bool,
+    examples: Vec<Value>,
+    /// Properties with hints
+    properties: BTreeMap<String, Field>,
+    items: Option<Box<Field>>,
+}
+
+impl Field {
+    fn of(value: &Value) -> Field {
+        Field {
+            skip: value.get("x-fuzzer-skip").and_then(Value::as_bool) == Some(true),
+            examples: match value.get("x-fuzzer-examples") {
+                Some(Value::Array(examples)) => examples.clone(),
+                _ => Vec::new(),
+            },
+            ..Field::default()
+        }
+    }
+
+    /// Hints of the schema and of its properties and items
+    fn of_schema(document: &Value, schema: &Value, depth: usize) -> Field {
+        let schema = resolve(document, schema);
+        let mut field = Field::of(schema);
+        if depth >= MAX_DEPTH {
+            return field;
+        }
+        let mut properties = properties_of(document, schema);
+        for keyword in ["allOf", "oneOf", "anyOf"].iter() {
+            if let Some(Value::Array(schemas)) = schema.get(*keyword) {
+                for schema in schemas.iter() {
+                    properties.extend(properties_of(document, resolve(document, schema)));
+                }
+            }
+        }
+        for (name, property) in properties.iter() {
+            let property = Field::of_schema(document, property, depth + 1);
+            if !property.is_empty() {
+                field.properties.insert(name.clone(), property);
+            }
+        }
+        field.items = schema
+            .get("items")
+            .map(|items| Field::of_schema(document, items, depth + 1))
+            .filter(|items| !items.is_empty())
+            .map(Box::new);
+        field
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.skip && self.examples.is_empty() && self.properties.is_empty() && self.items.is_none()
+    }
+
+    /// Example to send instead of the generated value, if it is chosen
+    fn example(&self, rng: &mut StdRng) -> Option<&Value> {
+        match self.examples.is_empty() {
+            true => None,
+            false if rng.gen_bool(EXAMPLE_PROBABILITY) => self.examples.choose(rng),
+            false => None,
+        }
+    }
+
+    /// Removes the skipped properties of the value and sends the examples, if enabled
+    fn apply(&self, value: &mut Value, examples: bool, rng: &mut StdRng) {
+        if let Some(example) = self.example(rng).filter(|_| examples) {
+            *value = example.clone();
+            return;
+        }
+        match value {
+            Value::Object(object) => {
+                for (name, property) in self.properties.iter() {
+                    match object.get_mut(name) {
+                        Some(_) if property.skip => {
+                            object.remove(name);
+                        }
+                        Some(value) => property.apply(value, examples, rng),
+                        None => {}
+                    }
+                }
+            }
+            Value::Array(values) => {
+                if let Some(items) = &self.items {
+                    for value in values.iter_mut() {
+                        items.apply(value, examples, rng);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct OperationHints {
+    skip: bool,
+    weight: Option<f64>,
+    max_rate: Option<f64>,
+    seeds: Vec<Seed>,
+    /// Parameters with hints, by name
+    parameters: BTreeMap<String, Field>,
+    body: Option<Field>,
+}
+
+/// Hints of the operations of the specification, by method and path
+#[derive(Debug)]
+pub struct Hints {
+    operations: BTreeMap<(String, String), OperationHints>,
+}
+
+/// Hints of the operation, its parameters and its JSON request body
+fn operation_hints(
+    document: &Value,
+    item: &Map<String, Value>,
+    operation: &Map<String, Value>,
+) -> OperationHints {
+    let number = |name| operation.get(name).and_then(Value::as_f64);
+    let mut hints = OperationHints {
+        skip: operation.get("x-fuzzer-skip").and_then(Value::as_bool) == Some(true),
+        weight: number("x-fuzzer-weight").filter(|weight| *weight >= 0.0),
+        max_rate: number("x-fuzzer-max-rate").filter(|rate| *rate > 0.0),
+        ..OperationHints::default()
+    };
+    if let Some(Value::Array(examples)) = operation.get("x-fuzzer-examples") {
+        for example in examples.iter() {
+            match serde_json::from_value(example.clone()) {
+                Ok(seed) => hints.seeds.push(seed),
+                Err(e) => warn!(error = %e, "invalid x-fuzzer-examples of an operation"),
+            }
+        }
+    }
+    let parameters = [item.get("parameters"), operation.get("parameters")];
+    for parameter in parameters
+        .iter()
+        .flatten()
+        .filter_map(|p| p.as_array())
+        .flatten()
+    {
+        let parameter = resolve(document, parameter);
+        let name = match parameter.get("name").and_then(Value::as_str) {
+            Some(name) => name,
+            None => continue,
+        };
+        let mut field = Field::of(parameter);
+        if let Some(schema) = parameter.get("schema") {
+            let schema = Field::of(resolve(document, schema));
+            field.skip |= schema.skip;
+            if field.examples.is_empty() {
+                field.examples = schema.examples;
+            }
+        }
+        if !field.is_empty() {
+            hints.parameters.insert(name.to_string(), field);
+        }
+    }
+    hints.body = operation
+        .get("requestBody")
+        .map(|body| resolve(document, body))
+        .and_then(|body| body.get("content")?.as_object())
+        .and_then(|content| {
+            content
+                .iter()
+                .find(|(content, _)| content.contains("json"))
+                .and_then(|(_, media)| media.get("schema"))
+        })
+        .map(|schema| Field::of_schema(document, schema, 0))
+        .filter(|field| !field.is_empty());
+    hints
+}
+
+impl Hints {
+    /// Hints of the raw specification, `None` if it has none
+    pub fn new(document: &Value) -> Option<Hints> {
+        let mut operations = BTreeMap::new();
+        for (path, item) in document.get("paths")?.as_object()?.iter() {
+            let item = match item.as_object() {
+                Some(item) => item,
+                None => continue,
+            };
+            for method in METHODS.iter() {
+                let operation = match item.get(*method).and_then(Value::as_object) {
+                    Some(operation) => operation,
+                    None => continue,
+                };
+                let hints = operation_hints(document, item, operation);
+                let empty = !hints.skip
+                    && hints.weight.is_none()
+                    && hints.max_rate.is_none()
+                    && hints.seeds.is_empty()
+                    && hints.parameters.is_empty()
+                    && hints.body.is_none();
+                if !empty {
+                    operations.insert((method.to_uppercase(), path.clone()), hints);
+                }
+            }
+        }
+        debug!(
+            operations = operations.len(),
+            "operations with fuzzer hints"
+        );
+        (!operations.is_empty()).then_some(Hints { operations })
+    }
+
+    /// Removes the operations with `x-fuzzer-skip` from the specification
+    pub fn remove_skipped(&self, schema: &mut OpenAPI) {
+        for (path, ref_or_item) in schema.paths.iter_mut() {
+            let item = match ref_or_item {
+                ReferenceOr::Item(item) => item,
+                ReferenceOr::Reference { .. } => continue,
+            };
+            for (method, operation) in [
+                ("GET", &mut item.get),
+                ("PUT", &mut item.put),
+                ("POST", &mut item.post),
+                ("DELETE", &mut item.delete),
+                ("OPTIONS", &mut item.options),
+                ("HEAD", &mut item.head),
+                ("PATCH", &mut item.patch),
+                ("TRACE", &mut item.trace),
+            ] {
+                let key = (method.to_string(), path.clone());
+                if self.operations.get(&key).is_some_and(|hints| hints.skip) {
+                    debug!(method, path = %path, "skipping operation");
+                    *operation = None;
+                }
+            }
+        }
+    }
+
+    /// Options of the operations with a weight or a highest rate, to be applied before the
+    /// ones of the config
+    pub fn overrides(&self) -> Vec<OperationOverride> {
+        self.operations
+            .iter()
+            .filter(|(_, hints)| hints.weight.is_some() || hints.max_rate.is_some())
+            .map(|((method, path), hints)| OperationOverride {
+                operations: Operations::Endpoint(method.clone(), path.clone()),
+                header: Vec::new(),
+                timeout: None,
+                rate_limit: hints.max_rate,
+                concurrency: None,
+                ignore_status_code: Vec::new(),
+                params: BTreeMap::new(),
+                weight: hints.weight,
+                server_error: None,
+                body_template: None,
+            })
+            .collect()
+    }
+
+    /// Seeds of the operations from their examples, with their methods and paths
+    pub fn seeds(&self) -> impl Iterator<Item = (&str, &str, &Seed)> {
+        self.operations.iter().flat_map(|((method, path), hints)| {
+            hints
+                .seeds
+                .iter()
+                .map(move |seed| (method.as_str(), path.as_str(), seed))
+        })
+    }
+}
+
+impl Generator for Hints {
+    fn generate(&self, payload: &mut Payload<'_>, rng: &mut StdRng) {
+        let key = (payload.method.to_string(), payload.path.to_string());
+        let hints = match self.operations.get(&key) {
+            Some(hints) => hints,
+            None => return,
+        };
+        for (name, field) in hints.parameters.iter() {
+            if field.skip {
+                payload.query_params.retain(|(param, _)| param != name);
+                payload.headers.retain(|(header, _)| header != name);
+                continue;
+            }
+            // A payload that is already invalid keeps its single violation
+            if payload.violation.is_some() {
+                continue;
+            }
+            let example = match field.example(rng) {
+                Some(Value::String(example)) => example.clone(),
+                Some(example) => example.to_string(),
+                None => continue,
+            };
+            for (param, value) in payload
+                .path_params
+                .iter_mut()
+                .chain(payload.query_params.iter_mut())
+                .chain(payload.headers.iter_mut())
+            {
+                if param == name {
+                    *value = example.clone();
+                }
+            }
+        }
+        let examples = payload.violation.is_none();
+        if let (Some(body), Some(value)) = (&hints.body, payload.body.first_mut()) {
+            body.apply(value, examples, rng);
+        }
+    }
+}
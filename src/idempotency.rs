@@ -0,0 +1,29 @@
+use openapiv3::Operation;
+use serde_json::Value;
+
+use crate::transport::Response;
+
+/// Safe methods must not change the state of the server
+pub fn is_safe(method: &str) -> bool {
+    matches!(method, "GET" | "HEAD" | "OPTIONS" | "TRACE")
+}
+
+/// Whether repeating the request must have the same effect as sending it once, which holds
+/// for safe methods, PUT, DELETE and operations with the `x-idempotent: true` extension
+pub fn is_idempotent(method: &str, operation: &Operation) -> bool {
+    is_safe(method)
+        || method == "PUT"
+        || method == "DELETE"
+        || operation.extensions.get("x-idempotent") == Some(&Value::Bool(true))
+}
+
+/// Returns an observable side effect of a request with a safe method, if any
+pub fn side_effect(method: &str, response: &Response) -> Option<String> {
+    match is_safe(method) && response.status == 201 {
+        true => Some(format!(
+            "{} request created a resource (201 Created)",
+            method
+        )),
+        false => None,
+    }
+}
@@ -0,0 +1,159 @@
+//! Identities of several users or tenants: the requests are sent as each of them in turn and
+//! every resource one of them creates is read as the others right away. A successful response
+//! to another identity indicates broken object level authorization (BOLA, also known as IDOR).
+
+use std::{collections::BTreeMap, str::FromStr, sync::Mutex};
+
+use openapiv3::OpenAPI;
+use url::Url;
+
+use crate::cleanup;
+use crate::config::Header;
+use crate::transport::{Request, Response};
+
+/// Header of an identity given as `name=Header: value`, e.g. `alice=Authorization: Bearer a`
+#[derive(Debug, Clone)]
+pub struct IdentityHeader {
+    pub identity: String,
+    pub header: (String, String),
+}
+
+impl FromStr for IdentityHeader {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid identity header `{}`, expected a name and a header like `alice=Authorization: Bearer a`",
+                s
+            )
+        };
+        let (identity, header) = s.split_once('=').ok_or_else(invalid)?;
+        let header = header.parse::<Header>().map_err(|_| invalid())?;
+        if identity.trim().is_empty() {
+            return Err(invalid());
+        }
+        Ok(IdentityHeader {
+            identity: identity.trim().to_string(),
+            header: header.into(),
+        })
+    }
+}
+
+/// User or tenant the requests are sent as, with the headers authenticating it
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub name: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// Identities of the headers, in the order they first appear
+pub fn identities(headers: &[IdentityHeader]) -> Vec<Identity> {
+    let mut identities: Vec<Identity> = Vec::new();
+    for header in headers {
+        match identities.iter_mut().find(|i| i.name == header.identity) {
+            Some(identity) => identity.headers.push(header.header.clone()),
+            None => identities.push(Identity {
+                name: header.identity.clone(),
+                headers: vec![header.header.clone()],
+            }),
+        }
+    }
+    identities
+}
+
+/// Identities the requests of each operation are sent as in turn, with the item paths the
+/// resources created in collections are read at
+#[derive(Debug)]
+pub struct Identities {
+    identities: Vec<Identity>,
+    /// Identity of the next request of each operation, by method and path
+    next: Mutex<BTreeMap<(String, String), usize>>,
+    /// Item path with a `GET` operation and the name of its parameter for each collection
+    items: BTreeMap<String, (String, String)>,
+}
+
+impl Identities {
+    pub fn new(schema: &OpenAPI, identities: Vec<Identity>) -> Identities {
+        Identities {
+            identities,
+            next: Mutex::default(),
+            items: cleanup::items(schema, |item| item.get.is_some()),
+        }
+    }
+
+    /// Sends the request as the identity, its headers replace the ones of all identities
+    fn authenticate(&self, request: &mut Request, identity: &Identity) {
+        request.headers.retain(|(name, _)| {
+            !self
+                .identities
+                .iter()
+                .flat_map(|identity| identity.headers.iter())
+                .any(|(header, _)| header.eq_ignore_ascii_case(name))
+        });
+        request.headers.extend(identity.headers.iter().cloned());
+    }
+
+    /// Sends the request to the operation as its next identity in turn, so that each
+    /// identity creates resources
+    pub fn interleave(&self, method: &str, path: &str, request: &mut Request) {
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+        let next = next
+            .entry((method.to_string(), path.to_string()))
+            .or_default();
+        let identity = &self.identities[*next % self.identities.len()];
+        *next += 1;
+        self.authenticate(request, identity);
+    }
+
+    /// Identity the request was sent as, the first one with all of its headers in it
+    fn owner(&self, request: &Request) -> Option<usize> {
+        self.identities.iter().position(|identity| {
+            identity.headers.iter().all(|(name, value)| {
+                request
+                    .headers
+                    .iter()
+                    .any(|(header, v)| header.eq_ignore_ascii_case(name) && v == value)
+            })
+        })
+    }
+
+    /// Requests reading the resource created by the successful `POST` request, first as its
+    /// owner and then as each of the other identities, empty if it created none
+    pub fn probes(
+        &self,
+        base: &Url,
+        path: &str,
+        request: &Request,
+        response: &Response,
+    ) -> Vec<(&str, Request)> {
+        if request.method != "POST" || response.status / 100 != 2 {
+            return Vec::new();
+        }
+        let (owner, (item_path, name)) = match (self.owner(request), self.items.get(path)) {
+            (Some(owner), Some(item)) => (owner, item),
+            _ => return Vec::new(),
+        };
+        let url = match cleanup::created(base, path, item_path, name, response) {
+            Some(url) => url,
+            None => return Vec::new(),
+        };
+        let mut read = request.clone();
+        read.method = "GET".to_string();
+        read.url = url;
+        read.body = None;
+        read.raw_body = None;
+        read.headers
+            .retain(|(name, _)| !name.eq_ignore_ascii_case("content-type"));
+        let others = (0..self.identities.len()).filter(|&i| i != owner);
+        std::iter::once(owner)
+            .chain(others)
+            .map(|i| {
+                let identity = &self.identities[i];
+                let mut probe = read.clone();
+                self.authenticate(&mut probe, identity);
+                (identity.name.as_str(), probe)
+            })
+            .collect()
+    }
+}
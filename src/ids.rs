@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde_json::Value;
+
+use crate::payload::Payload;
+
+/// Probability that a parameter with known identifiers gets one of them
+const REUSE_PROBABILITY: f64 = 0.8;
+
+/// Most identifiers kept for a name, the oldest are dropped first
+const MAX_IDS: usize = 100;
+
+/// Nesting of the response bodies searched for identifiers
+const MAX_DEPTH: usize = 5;
+
+/// Identifiers of live resources seen in successful responses, by normalized names like
+/// `userid`, substituted into the path and query parameters with matching names so that
+/// parameterized operations are exercised with existing resources
+#[derive(Debug, Default)]
+pub struct IdPool {
+    ids: BTreeMap<String, Vec<String>>,
+}
+
+/// Lowercase name without separators, e.g. `userid` for `user_id` or `userId`
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Whether the normalized name is the one of an identifier
+fn is_id(name: &str) -> bool {
+    name.ends_with("id") || name.ends_with("uuid") || name == "slug"
+}
+
+/// Singular resource name of the last static segment of the path, e.g. `user` for
+/// `/users/{id}`
+fn resource(path: &str, before: Option<&str>) -> Option<String> {
+    let segments = path.split('/').collect::<Vec<_>>();
+    let end = match before {
+        Some(param) => segments.iter().position(|s| *s == param)?,
+        None => segments.len(),
+    };
+    let segment = segments[..end]
+        .iter()
+        .rev()
+        .find(|s| !s.is_empty() && !s.starts_with('{'))?;
+    let segment = normalize(segment);
+    Some(match segment.strip_suffix("ies") {
+        Some(stem) => format!("{}y", stem),
+        None => segment.strip_suffix('s').unwrap_or(&segment).to_string(),
+    })
+}
+
+impl IdPool {
+    /// Remembers the identifiers in the body of a successful response to the path, an `id`
+    /// field is also remembered with the name of its object or the resource of the path
+    pub fn observe(&mut self, path: &str, body: &str) {
+        if let Ok(body) = serde_json::from_str::<Value>(body) {
+            let resource = resource(path, None);
+            self.collect(&body, resource.as_deref(), 0);
+        }
+    }
+
+    fn collect(&mut self, value: &Value, owner: Option<&str>, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object.iter() {
+                    let name = normalize(key);
+                    let id = match value {
+                        Value::String(id) if !id.is_empty() => Some(id.clone()),
+                        Value::Number(id) => Some(id.to_string()),
+                        _ => None,
+                    };
+                    match id {
+                        Some(id) if is_id(&name) => {
+                            if name == "id" {
+                                if let Some(owner) = owner {
+                                    self.add(format!("{}id", owner), id.clone());
+                                }
+                            }
+                            self.add(name, id);
+                        }
+                        Some(_) => {}
+                        None => {
+                            // Objects of lists are named after the list, e.g. `users`
+                            let owner = resource(key, None);
+                            self.collect(value, owner.as_deref(), depth + 1)
+                        }
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values.iter() {
+                    self.collect(value, owner, depth + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn add(&mut self, name: String, id: String) {
+        let ids = self.ids.entry(name).or_default();
+        if ids.contains(&id) {
+            return;
+        }
+        if ids.len() >= MAX_IDS {
+            ids.remove(0);
+        }
+        ids.push(id);
+    }
+
+    /// Replaces the values of path and query parameters named like known identifiers, most of
+    /// the time
+    pub fn substitute(&self, payload: &mut Payload) {
+        if self.ids.is_empty() {
+            return;
+        }
+        let mut rng = StdRng::seed_from_u64(payload.seed.rotate_left(48));
+        let path = payload.path;
+        for (param, value) in payload.path_params.iter_mut() {
+            let name = normalize(param);
+            // `/users/{id}` takes the identifiers of users
+            let qualified = match name.as_str() {
+                "id" => resource(path, Some(&format!("{{{}}}", param)))
+                    .map(|resource| format!("{}id", resource)),
+                _ => None,
+            };
+            if let Some(id) = self.choose(qualified.as_deref().unwrap_or(&name), &mut rng) {
+                *value = id;
+            }
+        }
+        for (param, value) in payload.query_params.iter_mut() {
+            let name = normalize(param);
+            if is_id(&name) {
+                if let Some(id) = self.choose(&name, &mut rng) {
+                    *value = id;
+                }
+            }
+        }
+    }
+
+    fn choose(&self, name: &str, rng: &mut StdRng) -> Option<String> {
+        let ids = self.ids.get(name)?;
+        match rng.gen_bool(REUSE_PROBABILITY) {
+            true => ids.choose(rng).cloned(),
+            false => None,
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Invariants across pairs of operations declared in the config, e.g. after `POST /items`
+//! answers 201, `GET /items/{id}` must answer 200 with the `name` that was sent. Every response
+//! of the first operation the invariant holds after, in the rounds and in the steps of the
+//! scenarios, is followed by the request to the second one, and a response breaking the
+//! expectations is an inconsistency: writes lost, truncated or normalized on the way to the
+//! store, or resources that can not be read back.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context, Result};
+use openapiv3::OpenAPI;
+use percent_encoding::utf8_percent_encode;
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::debug;
+use url::Url;
+
+use crate::cleanup;
+use crate::payload::{Payload, PATH_SEGMENT};
+use crate::scenario::{self, Location};
+use crate::transport::{Request, Response};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Invariant {
+    /// Named in the findings, `<after> -> <then>` by default
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Method and path of the first operation, e.g. `POST /items`
+    pub after: String,
+    /// Statuses of the first operation the invariant holds after, any success by default
+    #[serde(default)]
+    pub status: Vec<u16>,
+    /// Method and path of the operation requested next, e.g. `GET /items/{id}`, without a
+    /// body and with the headers of the first request
+    pub then: String,
+    /// Values of its path parameters taken from the first request or response, e.g.
+    /// `id = "response.body.id"`. The others take the path parameter of the same name of the
+    /// first request, or the identifier in the first response.
+    #[serde(default)]
+    pub path: BTreeMap<String, String>,
+    /// Statuses the second operation must answer with, any success by default
+    #[serde(default)]
+    pub expect_status: Vec<u16>,
+    /// Fields of the second response and the values of the first request or response they
+    /// must equal, e.g. `"body.name" = "request.body.name"`
+    #[serde(default)]
+    pub equal: BTreeMap<String, String>,
+}
+
+/// Invariant with the operations found in the specification
+#[derive(Debug)]
+struct Declared {
+    invariant: Invariant,
+    name: String,
+    after: (&'static str, String),
+    then: (&'static str, String),
+}
+
+/// Value in the first request or response the location starts with, e.g. `request.body.name`
+fn exchanged<'a>(reference: &'a str) -> Result<(bool, Location<'a>)> {
+    match reference.split_once('.') {
+        Some(("request", location)) => Ok((true, Location::parse(location))),
+        Some(("response", location)) => match Location::parse(location) {
+            location @ (Location::Header(_) | Location::Body(_)) => Ok((false, location)),
+            _ => Err(anyhow!(
+                "responses have only headers and a body, got `{}`",
+                reference
+            )),
+        },
+        _ => Err(anyhow!(
+            "expected a value of the `request` or the `response`, got `{}`",
+            reference
+        )),
+    }
+}
+
+fn body(body: &str) -> Option<Value> {
+    serde_json::from_str(body).ok()
+}
+
+fn of_request(location: &Location, payload: &Payload, request: &Request) -> Option<Value> {
+    match location {
+        Location::Path(name) => payload
+            .path_params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| Value::from(value.as_str())),
+        Location::Query(name) => request
+            .url
+            .query_pairs()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| Value::from(value.into_owned())),
+        Location::Header(name) => request
+            .headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| Value::from(value.as_str())),
+        Location::Body(keys) => {
+            let body = match (&request.body, &request.raw_body) {
+                (_, Some(raw)) => body(&String::from_utf8_lossy(raw))?,
+                (Some(body), None) => body.clone(),
+                (None, None) => return None,
+            };
+            scenario::get(&body, keys).cloned()
+        }
+    }
+}
+
+fn of_response(location: &Location, response: &Response) -> Option<Value> {
+    match location {
+        Location::Header(name) => response.header(name).map(Value::from),
+        Location::Body(keys) => scenario::get(&body(&response.body)?, keys).cloned(),
+        _ => None,
+    }
+}
+
+/// Whether the values are the same, strings of headers and parameters also equal the numbers
+/// and booleans they spell
+fn same(a: &Value, b: &Value) -> bool {
+    a == b || scenario::to_string(a) == scenario::to_string(b)
+}
+
+impl Declared {
+    fn holds_after(&self, payload: &Payload, response: &Response) -> bool {
+        let status = match self.invariant.status.is_empty() {
+            true => response.status / 100 == 2,
+            false => self.invariant.status.contains(&response.status),
+        };
+        status && payload.method == self.after.0 && payload.path == self.after.1
+    }
+
+    fn value(
+        &self,
+        reference: &str,
+        payload: &Payload,
+        request: &Request,
+        response: &Response,
+    ) -> Option<Value> {
+        match exchanged(reference).ok()? {
+            (true, location) => of_request(&location, payload, request),
+            (false, location) => of_response(&location, response),
+        }
+    }
+
+    /// Request to the second operation, `None` if a path parameter has no value
+    fn follow_up(
+        &self,
+        base: &Url,
+        payload: &Payload,
+        request: &Request,
+        response: &Response,
+    ) -> Option<Request> {
+        let mut path = self.then.1.clone();
+        let names = self
+            .then
+            .1
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect::<Vec<_>>();
+        for name in names {
+            let value = match self.invariant.path.get(name) {
+                Some(reference) => self
+                    .value(reference, payload, request, response)
+                    .map(|value| scenario::to_string(&value)),
+                None => payload
+                    .path_params
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, value)| value.clone())
+                    .or_else(|| cleanup::identifier(&body(&response.body)?, name)),
+            };
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    debug!(invariant = %self.name, parameter = name, "no value of the path parameter");
+                    return None;
+                }
+            };
+            let encoded = utf8_percent_encode(&value, PATH_SEGMENT).to_string();
+            path = path.replace(&format!("{{{}}}", name), &encoded);
+        }
+        let mut follow_up = request.clone();
+        follow_up.method = self.then.0.to_string();
+        follow_up.url = base.join(path.trim_start_matches('/')).ok()?;
+        follow_up.body = None;
+        follow_up.raw_body = None;
+        follow_up
+            .headers
+            .retain(|(name, _)| !name.eq_ignore_ascii_case("content-type"));
+        Some(follow_up)
+    }
+
+    /// How the response to the second operation breaks the invariant
+    fn violations(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        response: &Response,
+        follow_up: &Request,
+        answer: &Response,
+    ) -> Vec<String> {
+        let then = format!("{} {}", follow_up.method, follow_up.url.path());
+        let status = match self.invariant.expect_status.is_empty() {
+            true => answer.status / 100 == 2,
+            false => self.invariant.expect_status.contains(&answer.status),
+        };
+        if !status {
+            return vec![format!(
+                "{}: {} answered {} after {} answered {}",
+                self.name, then, answer.status, self.invariant.after, response.status
+            )];
+        }
+        let mut violations = Vec::new();
+        for (field, reference) in self.invariant.equal.iter() {
+            // The invariant says nothing about the values the first exchange did not have
+            let expected = match self.value(reference, payload, request, response) {
+                Some(expected) => expected,
+                None => continue,
+            };
+            match of_response(&Location::parse(field), answer) {
+                Some(actual) if same(&actual, &expected) => {}
+                Some(actual) => violations.push(format!(
+                    "{}: {} of {} is {}, {} was {}",
+                    self.name, field, then, actual, reference, expected
+                )),
+                None => violations.push(format!(
+                    "{}: no {} in the response to {}, {} was {}",
+                    self.name, field, then, reference, expected
+                )),
+            }
+        }
+        violations
+    }
+}
+
+/// Invariants declared in the config
+#[derive(Debug, Default)]
+pub struct Invariants {
+    declared: Vec<Declared>,
+}
+
+impl Invariants {
+    /// Checks that the operations and the values of the invariants exist
+    pub fn new(schema: &OpenAPI, invariants: Vec<Invariant>) -> Result<Invariants> {
+        let mut declared = Vec::new();
+        for invariant in invariants {
+            let name = invariant
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("{} -> {}", invariant.after, invariant.then));
+            let resolved = (|| {
+                let (method, path, _) = scenario::operation(schema, &invariant.after)?;
+                let after = (method, path.to_string());
+                let (method, path, _) = scenario::operation(schema, &invariant.then)?;
+                let then = (method, path.to_string());
+                for reference in invariant.path.values().chain(invariant.equal.values()) {
+                    exchanged(reference)?;
+                }
+                for field in invariant.equal.keys() {
+                    if let Location::Path(_) | Location::Query(_) = Location::parse(field) {
+                        return Err(anyhow!(
+                            "responses have only headers and a body, got `{}`",
+                            field
+                        ));
+                    }
+                }
+                Ok((after, then))
+            })();
+            let (after, then) =
+                resolved.with_context(|| format!("invalid invariant `{}`", name))?;
+            declared.push(Declared {
+                invariant,
+                name,
+                after,
+                then,
+            });
+        }
+        Ok(Invariants { declared })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.declared.is_empty()
+    }
+
+    /// Requests checking the invariants that hold after the response
+    pub fn follow_ups(
+        &self,
+        base: &Url,
+        payload: &Payload,
+        request: &Request,
+        response: &Response,
+    ) -> Vec<FollowUp<'_>> {
+        self.declared
+            .iter()
+            .filter(|declared| declared.holds_after(payload, response))
+            .filter_map(|declared| {
+                let request = declared.follow_up(base, payload, request, response)?;
+                Some(FollowUp { declared, request })
+            })
+            .collect()
+    }
+}
+
+/// Request to the second operation of an invariant
+#[derive(Debug)]
+pub struct FollowUp<'a> {
+    declared: &'a Declared,
+    pub request: Request,
+}
+
+impl FollowUp<'_> {
+    /// How its response breaks the invariant after the first request and response
+    pub fn violations(
+        &self,
+        payload: &Payload,
+        request: &Request,
+        response: &Response,
+        answer: &Response,
+    ) -> Vec<String> {
+        self.declared
+            .violations(payload, request, response, &self.request, answer)
+    }
+}
@@ -0,0 +1,417 @@
+//! Issues of the findings in GitHub or GitLab: an issue is filed for each new deduplicated
+//! finding with its severity, a curl command reproducing it, its payload and the headers of the
+//! response, or the issue filed for it by an earlier run is commented on, and reopened if it was
+//! closed. Issues are found by the signature of the finding, which is kept in a comment of their
+//! description.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::database;
+use crate::finding::Finding;
+use crate::redact::Redactor;
+use crate::reporter::Reporter;
+use crate::tls;
+use crate::transport::{Request, Response};
+
+/// Title of the issues unless `--issue-title` is given
+pub const DEFAULT_TITLE: &str = "{severity}: {kind} of {method} {path} (status {status})";
+
+/// Description of the issues unless `--issue-template` is given
+pub const DEFAULT_TEMPLATE: &str = "\
+A **{severity}** severity `{kind}` finding of `{method} {path}` (`{operation}`): the API \
+responded with status {status}.
+
+{details}
+
+Reproduce it with:
+
+```sh
+{curl}
+```
+
+Payload, without the parts not needed to trigger it:
+
+```
+{payload}
+```
+
+Response headers:
+
+```
+{headers}
+```
+";
+
+/// Issue tracker the issues are filed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tracker {
+    Github,
+    Gitlab,
+}
+
+impl FromStr for Tracker {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(Tracker::Github),
+            "gitlab" => Ok(Tracker::Gitlab),
+            _ => Err(format!("unknown issue tracker `{}`", s)),
+        }
+    }
+}
+
+impl Tracker {
+    /// Environment variable with the token of the API of the tracker
+    fn token_var(self) -> &'static str {
+        match self {
+            Tracker::Github => "GITHUB_TOKEN",
+            Tracker::Gitlab => "GITLAB_TOKEN",
+        }
+    }
+
+    /// Base of the API of the tracker, from the variable set by its CI if present
+    fn api(self) -> Result<Url> {
+        let (var, default) = match self {
+            Tracker::Github => ("GITHUB_API_URL", "https://api.github.com"),
+            Tracker::Gitlab => ("CI_API_V4_URL", "https://gitlab.com/api/v4"),
+        };
+        let api = std::env::var(var).unwrap_or_else(|_| default.to_string());
+        Url::parse(&format!("{}/", api.trim_end_matches('/')))
+            .with_context(|| format!("invalid {}", var))
+    }
+}
+
+/// How the issues are written, the templates have placeholders of the finding: `{severity}`,
+/// `{kind}`, `{method}`, `{path}`, `{operation}`, `{status}`, `{count}`, `{cwe}`,
+/// `{details}`, `{curl}`, `{payload}` of the minimized request, `{headers}` of the response,
+/// `{file}` and `{signature}`
+#[derive(Debug, Clone)]
+pub struct IssueTemplate {
+    pub title: String,
+    pub body: String,
+    pub labels: Vec<String>,
+    /// URL of the fuzzed API, the signatures of the findings are computed with it
+    pub api: Url,
+    /// Finds the credentials replaced in the issues, which are often public, whether or not
+    /// the findings are redacted
+    pub redactor: Redactor,
+}
+
+/// Body of the request, pretty printed if it is JSON
+fn payload(request: &Request) -> String {
+    match (&request.raw_body, &request.body) {
+        (Some(raw), _) => String::from_utf8_lossy(raw).to_string(),
+        (None, Some(body)) => serde_json::to_string_pretty(body).unwrap_or_default(),
+        (None, None) => "(no body)".to_string(),
+    }
+}
+
+/// Headers of the response a line each, as they were received
+fn headers(response: &Response) -> String {
+    match response.headers.is_empty() {
+        true => "(no headers)".to_string(),
+        false => response
+            .headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+impl IssueTemplate {
+    fn render(&self, template: &str, finding: &Finding, signature: &str) -> String {
+        let details = finding
+            .details
+            .iter()
+            .map(|detail| format!("- {}", detail))
+            .collect::<Vec<_>>()
+            .join("\n");
+        [
+            ("{severity}", finding.severity.to_string()),
+            ("{kind}", finding.kind.to_string()),
+            ("{method}", finding.method.clone()),
+            ("{path}", finding.path.clone()),
+            ("{operation}", finding.operation()),
+            ("{status}", finding.status.to_string()),
+            ("{count}", finding.count.to_string()),
+            (
+                "{cwe}",
+                finding.classification.cwe.unwrap_or_default().to_string(),
+            ),
+            ("{details}", details),
+            ("{curl}", finding.request.to_curl()),
+            (
+                "{payload}",
+                payload(finding.minimized.as_ref().unwrap_or(&finding.request)),
+            ),
+            ("{headers}", headers(&finding.response)),
+            ("{file}", finding.file.clone()),
+            ("{signature}", signature.to_string()),
+        ]
+        .iter()
+        .fold(template.to_string(), |text, (placeholder, value)| {
+            text.replace(placeholder, value)
+        })
+    }
+}
+
+/// Files the findings as issues of a GitHub repository or GitLab project
+pub struct Issues {
+    tracker: Tracker,
+    /// `owner/repo` of GitHub, the path or id of the project of GitLab
+    project: String,
+    api: Url,
+    token: String,
+    template: IssueTemplate,
+}
+
+/// Issue filed for a finding before
+struct Filed {
+    number: u64,
+    open: bool,
+}
+
+impl Issues {
+    pub fn new(tracker: Tracker, project: &str, template: IssueTemplate) -> Result<Issues> {
+        let token = std::env::var(tracker.token_var())
+            .map_err(|_| anyhow!("set {} to file issues", tracker.token_var()))?;
+        Ok(Issues {
+            tracker,
+            project: project.to_string(),
+            api: tracker.api()?,
+            token,
+            template,
+        })
+    }
+
+    fn url(&self, path: &str) -> Result<Url> {
+        let project = match self.tracker {
+            Tracker::Github => format!("repos/{}", self.project),
+            Tracker::Gitlab => format!(
+                "projects/{}",
+                utf8_percent_encode(&self.project, NON_ALPHANUMERIC)
+            ),
+        };
+        Ok(self.api.join(&format!("{}/{}", project, path))?)
+    }
+
+    fn request(&self, method: &str, url: &Url) -> ureq::Request {
+        let request = tls::agent()
+            .request_url(method, url)
+            .set("User-Agent", "openapi-fuzzer");
+        match self.tracker {
+            Tracker::Github => request
+                .set("Authorization", &format!("Bearer {}", self.token))
+                .set("Accept", "application/vnd.github+json"),
+            Tracker::Gitlab => request.set("PRIVATE-TOKEN", &self.token),
+        }
+    }
+
+    fn send(&self, method: &str, url: Url, body: Value) -> Result<Value> {
+        self.request(method, &url)
+            .send_json(body)
+            .with_context(|| format!("unable to {} {}", method, url))?
+            .into_json()
+            .context("invalid response of the issue tracker")
+    }
+
+    /// Issue with the marker in its description
+    fn find(&self, marker: &str) -> Result<Option<Filed>> {
+        let url = match self.tracker {
+            Tracker::Github => {
+                let mut url = self.api.join("search/issues")?;
+                url.query_pairs_mut().append_pair(
+                    "q",
+                    &format!("repo:{} is:issue in:body \"{}\"", self.project, marker),
+                );
+                url
+            }
+            Tracker::Gitlab => {
+                let mut url = self.url("issues")?;
+                url.query_pairs_mut()
+                    .append_pair("search", marker)
+                    .append_pair("in", "description");
+                url
+            }
+        };
+        let found: Value = self
+            .request("GET", &url)
+            .call()
+            .with_context(|| format!("unable to search the issues of {}", self.project))?
+            .into_json()?;
+        let (issues, number, open) = match self.tracker {
+            Tracker::Github => (&found["items"], "number", "open"),
+            Tracker::Gitlab => (&found, "iid", "opened"),
+        };
+        Ok(issues
+            .as_array()
+            .and_then(|issues| issues.first())
+            .and_then(|issue| {
+                Some(Filed {
+                    number: issue[number].as_u64()?,
+                    open: issue["state"] == open,
+                })
+            }))
+    }
+
+    /// The finding with the credentials of its requests and response replaced
+    fn redacted(&self, finding: &Finding) -> Result<Finding> {
+        let secrets = self
+            .template
+            .redactor
+            .secrets(&finding.request, &finding.response);
+        let (request, response) = secrets.exchange(&finding.request, &finding.response)?;
+        let minimized = finding
+            .minimized
+            .as_ref()
+            .map(|minimized| {
+                secrets
+                    .exchange(minimized, &finding.response)
+                    .map(|(request, _)| request)
+            })
+            .transpose()?;
+        Ok(Finding {
+            request,
+            response,
+            minimized,
+            ..finding.clone()
+        })
+    }
+}
+
+impl Reporter for Issues {
+    fn finding(&mut self, finding: &Finding) -> Result<()> {
+        let signature = database::signature(&self.template.api, finding);
+        let finding = &self.redacted(finding)?;
+        let marker = format!("openapi-fuzzer:{}", signature);
+        let mut body = self
+            .template
+            .render(&self.template.body, finding, &signature);
+        // Placeholders without a value, e.g. the details, leave empty paragraphs
+        while body.contains("\n\n\n") {
+            body = body.replace("\n\n\n", "\n\n");
+        }
+        body += &format!("\n<!-- {} -->\n", marker);
+
+        let filed = match self.find(&marker)? {
+            Some(filed) => filed,
+            None => {
+                let title = self
+                    .template
+                    .render(&self.template.title, finding, &signature);
+                let labels = self
+                    .template
+                    .labels
+                    .iter()
+                    .map(|label| self.template.render(label, finding, &signature))
+                    .collect::<Vec<_>>();
+                return match self.tracker {
+                    Tracker::Github => self.send(
+                        "POST",
+                        self.url("issues")?,
+                        json!({"title": title, "body": body, "labels": labels}),
+                    ),
+                    Tracker::Gitlab => self.send(
+                        "POST",
+                        self.url("issues")?,
+                        json!({"title": title, "description": body, "labels": labels.join(",")}),
+                    ),
+                }
+                .map(drop);
+            }
+        };
+
+        let (comments, reopen) = match self.tracker {
+            Tracker::Github => ("comments", ("PATCH", json!({"state": "open"}))),
+            Tracker::Gitlab => ("notes", ("PUT", json!({"state_event": "reopen"}))),
+        };
+        let issue = format!("issues/{}", filed.number);
+        self.send(
+            "POST",
+            self.url(&format!("{}/{}", issue, comments))?,
+            json!({ "body": format!("Found again by a later run.\n\n{}", body) }),
+        )?;
+        if !filed.open {
+            self.send(reopen.0, self.url(&issue)?, reopen.1)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finding::FindingKind;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn issues_never_show_credentials() {
+        let api = Url::parse("http://localhost/").unwrap();
+        let request = Request {
+            method: "POST".to_string(),
+            url: api.join("items?api_key=abcdef&page=2").unwrap(),
+            headers: vec![("authorization".to_string(), "Bearer s3cr3t".to_string())],
+            body: Some(json!({"name": "x", "password": "hunter2"})),
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        };
+        let kind = FindingKind::ServerError;
+        let finding = Finding {
+            path: "/items".to_string(),
+            method: "POST".to_string(),
+            operation_id: None,
+            status: 500,
+            kind,
+            severity: kind.severity(),
+            classification: kind.classification(),
+            details: Vec::new(),
+            file: String::new(),
+            count: 1,
+            first_seen: SystemTime::now(),
+            verification: None,
+            minimized: Some(Request {
+                body: Some(json!({"password": "hunter2"})),
+                ..request.clone()
+            }),
+            request,
+            response: Response {
+                status: 500,
+                status_text: String::new(),
+                http_version: String::new(),
+                headers: Vec::new(),
+                body: String::new(),
+                elapsed: Duration::ZERO,
+                events: Vec::new(),
+            },
+        };
+        let issues = Issues {
+            tracker: Tracker::Github,
+            project: "owner/repo".to_string(),
+            api: api.clone(),
+            token: String::new(),
+            template: IssueTemplate {
+                title: DEFAULT_TITLE.to_string(),
+                body: DEFAULT_TEMPLATE.to_string(),
+                labels: Vec::new(),
+                api,
+                redactor: Redactor::default(),
+            },
+        };
+        let finding = issues.redacted(&finding).unwrap();
+        let body = issues
+            .template
+            .render(&issues.template.body, &finding, "signature");
+        for secret in ["s3cr3t", "abcdef", "hunter2"].iter() {
+            assert!(!body.contains(secret), "{} in {}", secret, body);
+        }
+        assert!(body.contains("page=2"));
+    }
+}
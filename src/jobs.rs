@@ -0,0 +1,325 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{info, warn};
+
+use openapi_fuzzer::concurrency::Slots;
+use openapi_fuzzer::cookies::{Cookie, Isolation};
+use openapi_fuzzer::corpus::Seed;
+use openapi_fuzzer::finding::{Finding, StoredFinding};
+use openapi_fuzzer::fuzzer::{Fuzzer, Stats};
+
+/// Set for the fuzzing processes to their index and the number of jobs, e.g. `0/4`
+const JOB_VAR: &str = "OPENAPI_FUZZER_JOB";
+
+/// Set for the fuzzing processes to the operations picked with `--pick`
+const OPERATIONS_VAR: &str = "OPENAPI_FUZZER_JOB_OPERATIONS";
+
+/// Environment variable with the namespace of the data created by the run, shared by the jobs
+const NAMESPACE_VAR: &str = "OPENAPI_FUZZER_JOB_NAMESPACE";
+
+/// Directory the jobs share their seeds and findings in
+const DIR: &str = "results/.jobs";
+
+/// Seeds learned by the jobs, appended by them
+const CORPUS_FILE: &str = "corpus.jsonl";
+
+/// Findings of the jobs, appended by them
+const FINDINGS_FILE: &str = "findings.jsonl";
+
+/// Cookies set by the API, appended by the jobs when the cookie jar is shared
+const COOKIES_FILE: &str = "cookies.jsonl";
+
+/// Directory of the slots of operations with a concurrency limit
+const SLOTS_DIR: &str = "slots";
+
+/// Interval the jobs share their seeds and findings in
+const SYNC_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Seeds of each operation a job takes from the other jobs
+const MAX_SHARED_SEEDS: usize = 20;
+
+/// Method and path of an operation
+type Operation = (String, String);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SharedCookie {
+    job: u32,
+    scope: String,
+    cookie: Cookie,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SharedSeed {
+    job: u32,
+    method: String,
+    path: String,
+    seed: Seed,
+}
+
+/// Fuzzing process started by `--jobs`
+#[derive(Debug)]
+pub struct Job {
+    index: u32,
+    count: u32,
+    /// Operations picked with `--pick`, all of them otherwise
+    pub operations: Option<Vec<Operation>>,
+    /// Token of the run with `--namespace`
+    pub namespace: Option<String>,
+}
+
+impl Job {
+    /// The job of this process, if it was started by `--jobs`
+    pub fn current() -> Result<Option<Job>> {
+        let var = match env::var(JOB_VAR) {
+            Ok(var) => var,
+            Err(_) => return Ok(None),
+        };
+        let (index, count) = var
+            .split_once('/')
+            .and_then(|(index, count)| Some((index.parse().ok()?, count.parse().ok()?)))
+            .ok_or_else(|| anyhow!("invalid {} `{}`", JOB_VAR, var))?;
+        let operations = match env::var(OPERATIONS_VAR) {
+            Ok(operations) => Some(serde_json::from_str(&operations)?),
+            Err(_) => None,
+        };
+        Ok(Some(Job {
+            index,
+            count,
+            operations,
+            namespace: env::var(NAMESPACE_VAR).ok(),
+        }))
+    }
+
+    /// Part of the budget of the job, the budget is split evenly between the jobs
+    pub fn share(&self, budget: u32) -> u32 {
+        budget / self.count + u32::from(self.index < budget % self.count)
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Slots of the operations, shared by all jobs
+    pub fn slots(&self) -> Result<Slots> {
+        Slots::new(Path::new(DIR).join(SLOTS_DIR))
+    }
+}
+
+/// Appends the entries as JSON lines, the file is locked so that readers see only whole lines
+fn append<T: Serialize>(file: &Path, entries: impl IntoIterator<Item = T>) -> Result<()> {
+    let mut lines = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut lines, &entry)?;
+        lines.push(b'\n');
+    }
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)
+        .context(format!("unable to open {}", file.display()))?;
+    file.lock()?;
+    let written = file.write_all(&lines);
+    file.unlock()?;
+    Ok(written?)
+}
+
+/// Reads the JSON lines appended since the offset and moves the offset past them
+fn read_from<T: DeserializeOwned>(file: &Path, offset: &mut u64) -> Result<Vec<T>> {
+    let mut file = match File::open(file) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).context(format!("unable to open {}", file.display())),
+    };
+    let mut content = String::new();
+    file.lock_shared()?;
+    let read = file
+        .seek(SeekFrom::Start(*offset))
+        .and_then(|_| file.read_to_string(&mut content));
+    file.unlock()?;
+    read?;
+    *offset += content.len() as u64;
+    content
+        .lines()
+        .map(|line| serde_json::from_str(line).map_err(anyhow::Error::from))
+        .collect()
+}
+
+/// Fuzzes as one of the jobs, seeds, findings and shared cookies are shared after every time
+/// slice
+pub fn work(mut fuzzer: Fuzzer, job: &Job) -> Result<()> {
+    let dir = Path::new(DIR);
+    let mut corpus_offset = 0;
+    let mut cookies_offset = 0;
+    let mut shared_seeds: BTreeMap<Operation, usize> = BTreeMap::new();
+    let mut stored_counts = BTreeMap::new();
+    loop {
+        let exhausted = fuzzer.run_slice(SYNC_INTERVAL)?;
+        if exhausted {
+            // Shared with the last findings
+            fuzzer.finish_rechecks()?;
+        }
+
+        let learned = fuzzer
+            .take_seeds()
+            .into_iter()
+            .map(|(method, path, seed)| SharedSeed {
+                job: job.index,
+                method,
+                path,
+                seed,
+            });
+        append(&dir.join(CORPUS_FILE), learned)?;
+        for shared in read_from::<SharedSeed>(&dir.join(CORPUS_FILE), &mut corpus_offset)? {
+            let taken = shared_seeds
+                .entry((shared.method.clone(), shared.path.clone()))
+                .or_default();
+            if shared.job != job.index && *taken < MAX_SHARED_SEEDS {
+                *taken += 1;
+                fuzzer.add_seed(&shared.method, &shared.path, shared.seed);
+            }
+        }
+
+        if let Some(jar) = fuzzer
+            .cookie_jar()
+            .filter(|jar| jar.isolation() == Isolation::Shared)
+        {
+            let set = jar
+                .take_changes()
+                .into_iter()
+                .map(|(scope, cookie)| SharedCookie {
+                    job: job.index,
+                    scope,
+                    cookie,
+                });
+            append(&dir.join(COOKIES_FILE), set)?;
+            for shared in read_from::<SharedCookie>(&dir.join(COOKIES_FILE), &mut cookies_offset)? {
+                if shared.job != job.index {
+                    jar.set(&shared.scope, shared.cookie);
+                }
+            }
+        }
+
+        let mut findings = Vec::new();
+        for finding in fuzzer.findings().iter() {
+            let key = (
+                finding.path.clone(),
+                finding.method.clone(),
+                finding.status,
+                finding.kind,
+            );
+            let verdict = finding.verification.as_ref().map(|v| v.verdict);
+            let (stored, stored_verdict) = stored_counts
+                .insert(key, (finding.count, verdict))
+                .unwrap_or_default();
+            // Only the responses since the last time are stored, the counts of all jobs are
+            // added up. Findings replayed again later are stored again with their verdict.
+            if finding.count > stored || verdict != stored_verdict {
+                findings.push(StoredFinding {
+                    count: finding.count - stored,
+                    ..StoredFinding::from(finding)
+                });
+            }
+        }
+        append(&dir.join(FINDINGS_FILE), findings)?;
+
+        if exhausted {
+            break;
+        }
+    }
+    fs::write(
+        dir.join(format!("stats-{}.json", job.index)),
+        serde_json::to_vec(fuzzer.stats())?,
+    )?;
+    fuzzer.finish()
+}
+
+/// Starts the jobs with the arguments of this process, waits for them and merges their
+/// findings and stats into the fuzzer
+pub fn run(jobs: u32, operations: Option<&[Operation]>, fuzzer: &mut Fuzzer) -> Result<()> {
+    let dir = Path::new(DIR);
+    if dir.exists() {
+        fs::remove_dir_all(dir).context(format!("unable to remove {}", dir.display()))?;
+    }
+    fs::create_dir_all(dir)?;
+    let failed = start(jobs, operations, fuzzer, dir);
+    // The jobs share their findings with the requests in plaintext, removed also when the jobs
+    // could not be merged
+    if fuzzer.encryption().enabled() {
+        fs::remove_dir_all(dir).context(format!("unable to remove {}", dir.display()))?;
+    }
+    let failed = failed?;
+    eprintln!(
+        "{} requests, {} findings",
+        fuzzer.stats().total,
+        fuzzer.findings().len()
+    );
+    match failed {
+        0 => Ok(()),
+        _ if failed == jobs => Err(anyhow!("All jobs failed")),
+        _ => {
+            eprintln!("{} of {} jobs failed", failed, jobs);
+            Ok(())
+        }
+    }
+}
+
+/// Starts the jobs in the directory, waits for them and merges their findings and stats into
+/// the fuzzer, returns the number of jobs that failed
+fn start(
+    jobs: u32,
+    operations: Option<&[Operation]>,
+    fuzzer: &mut Fuzzer,
+    dir: &Path,
+) -> Result<u32> {
+    let exe = env::current_exe()?;
+    let mut children = Vec::new();
+    for index in 0..jobs {
+        let mut command = Command::new(&exe);
+        command
+            .args(env::args_os().skip(1))
+            .env(JOB_VAR, format!("{}/{}", index, jobs))
+            .stdin(Stdio::null());
+        if let Some(operations) = operations {
+            command.env(OPERATIONS_VAR, serde_json::to_string(operations)?);
+        }
+        if let Some(namespace) = &fuzzer.run_info().namespace {
+            command.env(NAMESPACE_VAR, namespace);
+        }
+        children.push(command.spawn().context("unable to start a job")?);
+    }
+    info!(jobs, "jobs started");
+    eprintln!("Fuzzing with {} jobs", jobs);
+
+    let mut failed = 0;
+    for (index, mut child) in children.into_iter().enumerate() {
+        let status = child.wait()?;
+        if !status.success() {
+            warn!(job = index, %status, "job failed");
+            failed += 1;
+        }
+    }
+
+    for stored in read_from::<StoredFinding>(&dir.join(FINDINGS_FILE), &mut 0)? {
+        fuzzer.merge(Finding::from(stored))?;
+    }
+    for index in 0..jobs {
+        let file = dir.join(format!("stats-{}.json", index));
+        if let Ok(stats) = fs::read(&file) {
+            fuzzer.merge_stats(&serde_json::from_slice::<Stats>(&stats)?);
+        }
+    }
+    Ok(failed)
+}
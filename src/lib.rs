@@ -0,0 +1,113 @@
+//! Black-box fuzzer of APIs described by OpenAPI specifications. Besides the command line
+//! tool, the fuzzer can be embedded, e.g. into integration tests:
+//!
+//! ```no_run
+//! use openapi_fuzzer::{Budget, Fuzzer};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let schema = serde_yaml::from_str(&std::fs::read_to_string("openapi.yaml")?)?;
+//! let mut fuzzer = Fuzzer::builder(schema, "http://localhost:8080/".parse()?)
+//!     .budget(Budget {
+//!         max_requests: Some(1000),
+//!         ..Budget::default()
+//!     })
+//!     .build();
+//! fuzzer.run_headless()?;
+//! assert!(fuzzer.findings().is_empty());
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod adaptive;
+pub mod additional;
+pub mod asyncapi;
+pub mod audit;
+pub mod auth;
+pub mod bench;
+pub mod binary;
+pub mod cassette;
+pub mod chaos;
+pub mod cleanup;
+pub mod client;
+pub mod clockskew;
+pub mod combinations;
+pub mod concurrency;
+pub mod conditional;
+pub mod config;
+pub mod constraints;
+pub mod control;
+pub mod cookies;
+pub mod corpus;
+pub mod cors;
+pub mod coverage;
+pub mod database;
+pub mod diff;
+pub mod disclosure;
+pub mod drift;
+pub mod encryption;
+pub mod examples;
+pub mod export;
+pub mod faker;
+pub mod finding;
+pub mod fuzzer;
+pub mod graphql;
+pub mod grpc;
+pub mod headers;
+pub mod hints;
+pub mod idempotency;
+pub mod identities;
+pub mod ids;
+pub mod invariants;
+pub mod issues;
+pub mod limits;
+pub mod logs;
+pub mod maintenance;
+pub mod minimize;
+pub mod namespace;
+pub mod network;
+pub mod notify;
+pub mod oracle;
+pub mod overlay;
+pub mod parameters;
+pub mod payload;
+pub mod plan;
+pub mod pool;
+pub mod postman;
+pub mod preconditions;
+pub mod proxy;
+pub mod queues;
+pub mod ratelimit;
+pub mod readiness;
+pub mod recheck;
+pub mod redact;
+pub mod report;
+pub mod reporter;
+pub mod repro;
+pub mod roundtrip;
+pub mod samples;
+pub mod scenario;
+pub mod signing;
+pub mod sizes;
+pub mod smart;
+pub mod smuggling;
+pub mod snapshot;
+pub mod spec;
+pub mod status;
+pub mod stream;
+pub mod template;
+pub mod temporal;
+pub mod tls;
+pub mod traffic;
+pub mod transport;
+pub mod traversal;
+pub mod trends;
+pub mod triage;
+pub mod tui;
+pub mod validate;
+pub mod websocket;
+
+pub use finding::{Finding, Findings};
+pub use fuzzer::{Budget, Checks, Fuzzer, FuzzerBuilder};
+pub use oracle::Oracle;
+pub use payload::{Generator, Payload};
+pub use transport::Transport;
@@ -0,0 +1,304 @@
+//! Probing of the size limits of the target: a header value, the number of headers, the URL
+//! and the body of a request grow toward and past the usual limits of servers and proxies,
+//! 8KB of headers and 1MB of body. Oversized requests should be rejected with a client error,
+//! e.g. `413`, `414` or `431`, a server error or a dropped connection instead indicates a limit
+//! enforced past the point the request is already being processed.
+
+use std::{io, time::Instant};
+
+use anyhow::{Error, Result};
+use tracing::debug;
+
+use crate::transport::{Request, Response, Transport};
+
+/// Header padded in the probes
+const PADDING_HEADER: &str = "X-Fuzzer-Padding";
+
+/// Query parameter padding the URL
+const PADDING_PARAM: &str = "fuzzer_padding";
+
+/// Bytes of padding kept in the saved probe, the size sent is in the indicators
+const KEPT_PADDING: usize = 256;
+
+/// Padded headers kept in the saved probe
+const KEPT_HEADERS: usize = 10;
+
+/// Parts of the request that grow, with their sizes
+const DIMENSIONS: &[(Dimension, &[usize])] = &[
+    (
+        Dimension::HeaderValue,
+        &[4 << 10, 8 << 10, 16 << 10, 64 << 10],
+    ),
+    (Dimension::HeaderCount, &[50, 100, 200, 1000]),
+    (Dimension::Url, &[2 << 10, 8 << 10, 16 << 10, 64 << 10]),
+    (Dimension::Body, &[512 << 10, 1 << 20, 2 << 20, 8 << 20]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dimension {
+    HeaderValue,
+    HeaderCount,
+    Url,
+    Body,
+}
+
+impl Dimension {
+    fn name(self) -> &'static str {
+        match self {
+            Dimension::HeaderValue => "header value",
+            Dimension::HeaderCount => "header count",
+            Dimension::Url => "URL",
+            Dimension::Body => "body",
+        }
+    }
+
+    fn size(self, size: usize) -> String {
+        match self {
+            Dimension::HeaderCount => size.to_string(),
+            _ if size >= 1 << 20 => format!("{}MB", size >> 20),
+            _ => format!("{}KB", size >> 10),
+        }
+    }
+
+    /// The request with the part grown to the size, `None` if the request has no such part
+    fn grow(self, request: &Request, size: usize) -> Option<Request> {
+        let mut probe = request.clone();
+        match self {
+            Dimension::HeaderValue => probe
+                .headers
+                .push((PADDING_HEADER.to_string(), "a".repeat(size))),
+            Dimension::HeaderCount => probe.headers.extend(
+                (request.headers.len()..size)
+                    .map(|i| (format!("{}-{}", PADDING_HEADER, i), "a".to_string())),
+            ),
+            Dimension::Url => {
+                let length = request.url.as_str().len() + PADDING_PARAM.len() + 2;
+                let padding = "a".repeat(size.saturating_sub(length));
+                probe
+                    .url
+                    .query_pairs_mut()
+                    .append_pair(PADDING_PARAM, &padding);
+            }
+            // Trailing whitespace keeps the body valid JSON
+            Dimension::Body => {
+                let mut body = match (&request.raw_body, &request.body) {
+                    (None, Some(body)) => serde_json::to_vec(body).ok()?,
+                    _ => return None,
+                };
+                body.resize(size.max(body.len()), b' ');
+                probe.raw_body = Some(body);
+                if !probe.has_header("content-type") {
+                    probe
+                        .headers
+                        .push(("Content-Type".to_string(), "application/json".to_string()));
+                }
+            }
+        }
+        Some(probe)
+    }
+
+    /// The probe with its padding cut, to be saved with the finding
+    fn trim(self, mut probe: Request, request: &Request) -> Request {
+        match self {
+            Dimension::HeaderValue => {
+                if let Some((_, value)) = probe.headers.last_mut() {
+                    value.truncate(KEPT_PADDING);
+                }
+            }
+            Dimension::HeaderCount => probe.headers.truncate(request.headers.len() + KEPT_HEADERS),
+            Dimension::Url => {
+                let pairs = probe
+                    .url
+                    .query_pairs()
+                    .map(|(name, value)| match name == PADDING_PARAM {
+                        true => (
+                            name.into_owned(),
+                            value.chars().take(KEPT_PADDING).collect(),
+                        ),
+                        false => (name.into_owned(), value.into_owned()),
+                    })
+                    .collect::<Vec<_>>();
+                probe.url.query_pairs_mut().clear().extend_pairs(pairs);
+            }
+            Dimension::Body => {
+                if let Some(body) = probe.raw_body.as_mut() {
+                    let length = body.iter().rposition(|b| *b != b' ').map_or(0, |i| i + 1);
+                    body.truncate(length + KEPT_PADDING);
+                }
+            }
+        }
+        probe
+    }
+}
+
+/// Why the probe got no response, `None` for errors the size does not explain, e.g. a refused
+/// connection
+fn unanswered(e: &Error) -> Option<&'static str> {
+    const DROPPED: &str = "connection dropped without a response";
+    for cause in e.chain() {
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            match e.kind() {
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                    return Some("no response within the timeout")
+                }
+                io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof => return Some(DROPPED),
+                _ => {}
+            }
+        }
+        // An empty or cut status line, the connection was closed before the response
+        if let Some(e) = cause.downcast_ref::<ureq::Error>() {
+            if e.kind() == ureq::ErrorKind::BadStatus {
+                return Some(DROPPED);
+            }
+        }
+        if let Some(e) = cause.downcast_ref::<hyper::Error>() {
+            if e.is_incomplete_message() {
+                return Some(DROPPED);
+            }
+            if e.is_timeout() {
+                return Some("no response within the timeout");
+            }
+        }
+    }
+    None
+}
+
+/// How the target answered a probe
+fn outcome(result: &Result<Response>) -> String {
+    match result {
+        Ok(response) => response.status.to_string(),
+        Err(_) => "no response".to_string(),
+    }
+}
+
+/// Grows each part of the request in turn until the target fails to answer it with anything
+/// but a server error or no response, and returns the indicators with the first failing probe
+/// and its response, with status 0 if there was none. The padding of the probe is cut to keep
+/// the finding small. The indicators end with the outcomes of all sizes sent.
+pub fn probe(
+    transport: &dyn Transport,
+    request: &Request,
+) -> Option<(Request, Response, Vec<String>)> {
+    let mut indicators = Vec::new();
+    let mut profiles = Vec::new();
+    let mut failed = None;
+    for (dimension, sizes) in DIMENSIONS {
+        let mut outcomes = Vec::new();
+        let mut handled: Option<(usize, String)> = None;
+        for &size in sizes.iter() {
+            let probe = match dimension.grow(request, size) {
+                Some(probe) => probe,
+                None => break,
+            };
+            let start = Instant::now();
+            let result = transport.send(&probe);
+            let answer = outcome(&result);
+            outcomes.push(format!("{} {}", dimension.size(size), answer));
+            let failure = match &result {
+                Ok(response) if response.status / 100 == 5 => {
+                    Some(format!("server error {}", response.status))
+                }
+                Ok(_) => None,
+                Err(e) => match unanswered(e) {
+                    Some(unanswered) => Some(format!("{} ({:#})", unanswered, e)),
+                    None => {
+                        debug!(error = %format!("{:#}", e), dimension = dimension.name(), "size limit probe failed");
+                        break;
+                    }
+                },
+            };
+            let failure = match failure {
+                Some(failure) => failure,
+                None => {
+                    handled = Some((size, answer));
+                    continue;
+                }
+            };
+            let before = match &handled {
+                Some((handled, answer)) => {
+                    format!(", answered with {} at {}", answer, dimension.size(*handled))
+                }
+                None => String::new(),
+            };
+            indicators.push(format!(
+                "{} of {}: {}{}",
+                dimension.name(),
+                dimension.size(size),
+                failure,
+                before
+            ));
+            if failed.is_none() {
+                let response = result.unwrap_or_else(|_| Response::none(start.elapsed()));
+                failed = Some((dimension.trim(probe, request), response));
+            }
+            // Larger sizes fail the same way, the target is not pushed further
+            break;
+        }
+        if !outcomes.is_empty() {
+            profiles.push(format!("{}: {}", dimension.name(), outcomes.join(", ")));
+        }
+    }
+
+    failed.map(|(probe, response)| {
+        indicators.extend(profiles);
+        (probe, response, indicators)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use url::Url;
+
+    #[test]
+    fn trims_the_saved_probe() {
+        let request = Request {
+            method: "POST".to_string(),
+            url: Url::parse("http://localhost/items?a=1").unwrap(),
+            headers: vec![("Accept".to_string(), "*/*".to_string())],
+            body: Some(json!({"a": 1})),
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        };
+        for (dimension, sizes) in DIMENSIONS {
+            let size = *sizes.last().unwrap();
+            let probe = dimension.grow(&request, size).unwrap();
+            let trimmed = dimension.trim(probe, &request);
+            let length = trimmed.url.as_str().len()
+                + trimmed
+                    .headers
+                    .iter()
+                    .map(|(n, v)| n.len() + v.len())
+                    .sum::<usize>()
+                + trimmed.raw_body.as_ref().map_or(0, Vec::len);
+            assert!(
+                length < 2 << 10,
+                "{} kept {} bytes",
+                dimension.name(),
+                length
+            );
+        }
+        let probe = Dimension::Body.grow(&request, 1 << 20).unwrap();
+        let trimmed = Dimension::Body.trim(probe, &request);
+        assert!(trimmed.raw_body.unwrap().starts_with(b"{\"a\":1}"));
+        let probe = Dimension::Url.grow(&request, 8 << 10).unwrap();
+        let trimmed = Dimension::Url.trim(probe, &request);
+        assert!(trimmed
+            .url
+            .as_str()
+            .starts_with("http://localhost/items?a=1&fuzzer_padding=aaa"));
+    }
+
+    #[test]
+    fn tells_dropped_connections_from_other_errors() {
+        let reset = Error::from(io::Error::from(io::ErrorKind::ConnectionReset));
+        assert!(unanswered(&reset).is_some());
+        let refused = Error::from(io::Error::from(io::ErrorKind::ConnectionRefused));
+        assert!(unanswered(&refused).is_none());
+    }
+}
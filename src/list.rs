@@ -0,0 +1,138 @@
+use anyhow::Result;
+use openapi_utils::ReferenceOrExt;
+use openapiv3::{OpenAPI, Operation};
+use serde::Serialize;
+
+use openapi_fuzzer::binary;
+use openapi_fuzzer::payload::operations;
+
+/// Operation of the specification as listed by the `list` subcommand
+#[derive(Debug, Serialize)]
+pub struct OperationInfo<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub operation_id: Option<&'a str>,
+    pub tags: &'a [String],
+    /// Names of the security schemes of the operation, alternatives are separated by `|`
+    pub auth: Vec<String>,
+    /// How the request body is generated, `none` for operations without a body
+    pub body: String,
+}
+
+/// Describes the body the fuzzer generates for the operation, only JSON and binary bodies are
+/// supported
+fn body(operation: &Operation) -> String {
+    let request_body = match &operation.request_body {
+        Some(request_body) => request_body.to_item_ref(),
+        None => return "none".to_string(),
+    };
+    let (json, other): (Vec<_>, Vec<_>) = request_body
+        .content
+        .iter()
+        .partition(|(content, media)| content.contains("json") && media.schema.is_some());
+    let binary = other.iter().find(|(content, _)| binary::is_binary(content));
+    match (json.first(), binary) {
+        (Some((content, _)), _) => content.to_string(),
+        (None, Some((content, _))) => format!("{} (binary)", content),
+        (None, None) => format!(
+            "unsupported ({})",
+            other
+                .iter()
+                .map(|(content, _)| content.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+pub fn operation_infos(schema: &OpenAPI) -> Vec<OperationInfo<'_>> {
+    let mut infos = Vec::new();
+    for (path, ref_or_item) in schema.paths.iter() {
+        for (method, operation) in operations(ref_or_item.to_item_ref()) {
+            let operation = match operation {
+                Some(operation) => operation,
+                None => continue,
+            };
+            let auth = operation
+                .security
+                .as_ref()
+                .or(schema.security.as_ref())
+                .map(|requirements| {
+                    requirements
+                        .iter()
+                        .map(|requirement| {
+                            requirement.keys().cloned().collect::<Vec<_>>().join("+")
+                        })
+                        .filter(|schemes| !schemes.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+            infos.push(OperationInfo {
+                method,
+                path,
+                operation_id: operation.operation_id.as_deref(),
+                tags: &operation.tags,
+                auth,
+                body: body(operation),
+            });
+        }
+    }
+    infos
+}
+
+/// Prints the operations of the specification as a table or as JSON
+pub fn print(schema: &OpenAPI, json: bool) -> Result<()> {
+    let infos = operation_infos(schema);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&infos)?);
+        return Ok(());
+    }
+
+    let rows = infos
+        .iter()
+        .map(|info| {
+            vec![
+                info.method.to_string(),
+                info.path.to_string(),
+                info.operation_id.unwrap_or("-").to_string(),
+                match info.tags.is_empty() {
+                    true => "-".to_string(),
+                    false => info.tags.join(","),
+                },
+                match info.auth.is_empty() {
+                    true => "-".to_string(),
+                    false => info.auth.join("|"),
+                },
+                info.body.clone(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = ["METHOD", "PATH", "OPERATION", "TAGS", "AUTH", "BODY"];
+    let widths = header
+        .iter()
+        .enumerate()
+        .map(|(i, title)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(title.len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", line(header.to_vec()));
+    for row in rows.iter() {
+        println!("{}", line(row.iter().map(String::as_str).collect()));
+    }
+    Ok(())
+}
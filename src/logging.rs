@@ -0,0 +1,38 @@
+use std::{fs::OpenOptions, io, path::Path, sync::Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use tracing_subscriber::{fmt::writer::BoxMakeWriter, EnvFilter};
+
+/// Level of logs written to a log file when no filter is given
+const DEFAULT_FILTER: &str = "info";
+
+/// Sets up logging to the file, or to stderr when only the filter is given. The filter has
+/// the format of `RUST_LOG`, e.g. `info,openapi_fuzzer::transport=debug`. Nothing is logged
+/// when neither of them is given, the TUI uses the terminal.
+pub fn init(filter: Option<&str>, file: Option<&Path>, json: bool) -> Result<()> {
+    if filter.is_none() && file.is_none() {
+        return Ok(());
+    }
+
+    let filter = EnvFilter::try_new(filter.unwrap_or(DEFAULT_FILTER))
+        .map_err(|e| anyhow!("invalid log filter: {}", e))?;
+    let writer = match file {
+        Some(file) => BoxMakeWriter::new(Mutex::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(file)
+                .context(format!("unable to open {}", file.display()))?,
+        )),
+        None => BoxMakeWriter::new(io::stderr),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    match json {
+        true => builder.json().try_init(),
+        false => builder.try_init(),
+    }
+    .map_err(|e| anyhow!("unable to set up logging: {}", e))
+}
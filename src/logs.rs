@@ -0,0 +1,239 @@
+//! Logs of the target followed during the run, e.g. a log file or the output of its container:
+//! the lines emitted around the request of a new finding are stored with it, as server-side
+//! stack traces usually tell more than the response. They are added to the file of the finding
+//! once the late lines had time to be logged, while the run goes on.
+
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::encryption::Encryption;
+use crate::redact::Secrets;
+
+/// Lines logged before the request was sent that are stored with the finding
+const BEFORE: Duration = Duration::from_secs(1);
+
+/// Time the lines logged after the response are waited for, e.g. a stack trace written once
+/// the response was sent
+const AFTER: Duration = Duration::from_millis(500);
+
+/// Lines kept in memory, the older ones are dropped
+const MAX_LINES: usize = 10_000;
+
+/// Lines stored with a finding, the first ones are dropped
+const MAX_FINDING_LINES: usize = 200;
+
+/// Where the logs of the target are read from, e.g. `file:/var/log/api.log`,
+/// `docker:<container>` or `journald:<unit>`, a plain path is a file
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogSource {
+    File(PathBuf),
+    Docker(String),
+    Journald(String),
+    /// Services of a compose project
+    Compose {
+        file: PathBuf,
+        project: String,
+    },
+}
+
+impl FromStr for LogSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = match s.split_once(':') {
+            Some(("file", path)) => LogSource::File(path.into()),
+            Some(("docker", container)) => LogSource::Docker(container.to_string()),
+            Some(("journald", unit)) => LogSource::Journald(unit.to_string()),
+            _ => LogSource::File(s.into()),
+        };
+        match &source {
+            LogSource::Docker(name) | LogSource::Journald(name) if name.is_empty() => {
+                Err(format!("missing the name in the log source `{}`", s))
+            }
+            _ => Ok(source),
+        }
+    }
+}
+
+impl LogSource {
+    /// Command following the new lines of the logs
+    fn command(&self) -> Command {
+        let mut command;
+        match self {
+            LogSource::File(path) => {
+                command = Command::new("tail");
+                command.args(["-F", "-n", "0"]).arg(path);
+            }
+            LogSource::Docker(container) => {
+                command = Command::new("docker");
+                command.args(["logs", "--follow", "--tail", "0", container]);
+            }
+            LogSource::Journald(unit) => {
+                command = Command::new("journalctl");
+                command.args([
+                    "--follow",
+                    "--lines",
+                    "0",
+                    "--output",
+                    "short-iso",
+                    "--unit",
+                    unit,
+                ]);
+            }
+            LogSource::Compose { file, project } => {
+                command = Command::new("docker");
+                command
+                    .args(["compose", "--file"])
+                    .arg(file)
+                    .args(["--project-name", project])
+                    .args(["logs", "--follow", "--no-color", "--tail", "0"]);
+            }
+        }
+        command
+    }
+}
+
+type Lines = Arc<Mutex<VecDeque<(Instant, String)>>>;
+
+/// Finding waiting for the late lines of the logs
+#[derive(Debug)]
+struct Pending {
+    file: PathBuf,
+    sent: Instant,
+    due: Instant,
+    secrets: Option<Secrets>,
+}
+
+/// Lines of the logs of the target received lately, they are followed until it is dropped
+#[derive(Debug)]
+pub struct TargetLogs {
+    lines: Lines,
+    child: Child,
+    pending: Mutex<Vec<Pending>>,
+}
+
+/// Adds the lines of the stream to the buffer as they are received
+fn read_lines(stream: impl Read + Send + 'static, lines: Lines) {
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    debug!(error = %e, "unable to read the logs of the target");
+                    return;
+                }
+            };
+            let mut lines = lines.lock().unwrap();
+            if lines.len() >= MAX_LINES {
+                lines.pop_front();
+            }
+            lines.push_back((Instant::now(), line));
+        }
+    });
+}
+
+impl TargetLogs {
+    /// Starts following the logs of the source
+    pub fn follow(source: &LogSource) -> Result<TargetLogs> {
+        let mut child = source
+            .command()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("unable to follow the logs of {:?}", source))?;
+        let lines = Lines::default();
+        // Containers write to both
+        if let Some(stdout) = child.stdout.take() {
+            read_lines(stdout, lines.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            read_lines(stderr, lines.clone());
+        }
+        info!(?source, "following the logs of the target");
+        Ok(TargetLogs {
+            lines,
+            child,
+            pending: Mutex::default(),
+        })
+    }
+
+    /// Lines logged from shortly before the request was sent until now
+    fn around(&self, sent: Instant) -> Vec<String> {
+        let since = sent.checked_sub(BEFORE).unwrap_or(sent);
+        let lines = self.lines.lock().unwrap();
+        let mut around = lines
+            .iter()
+            .filter(|(received, _)| *received >= since)
+            .map(|(_, line)| line.clone())
+            .collect::<Vec<_>>();
+        let extra = around.len().saturating_sub(MAX_FINDING_LINES);
+        around.drain(..extra);
+        around
+    }
+
+    /// Stores the lines logged around the request in the file of the finding shortly after
+    /// now, with the secrets redacted
+    pub fn attach(&self, file: &Path, sent: Instant, secrets: Option<Secrets>) {
+        self.pending.lock().unwrap().push(Pending {
+            file: file.to_path_buf(),
+            sent,
+            due: Instant::now() + AFTER,
+            secrets,
+        });
+    }
+
+    /// Stores the lines of the findings that are due in their files
+    pub fn store_due(&self, encryption: &Encryption) -> Result<()> {
+        let now = Instant::now();
+        let due = {
+            let mut pending = self.pending.lock().unwrap();
+            let (due, waiting) = pending.drain(..).partition::<Vec<_>, _>(|p| p.due <= now);
+            *pending = waiting;
+            due
+        };
+        for finding in due {
+            let lines = self.around(finding.sent);
+            if lines.is_empty() {
+                continue;
+            }
+            let mut lines = Value::from(lines);
+            if let Some(secrets) = &finding.secrets {
+                secrets.redact(&mut lines);
+            }
+            let mut record: Value = serde_json::from_slice(&encryption.read(&finding.file)?)
+                .with_context(|| format!("invalid finding file {}", finding.file.display()))?;
+            record["logs"] = lines;
+            encryption.write(&finding.file, &serde_json::to_vec_pretty(&record)?)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the late lines of the remaining findings, at the end of the run
+    pub fn finish(&self, encryption: &Encryption) -> Result<()> {
+        let last = self.pending.lock().unwrap().iter().map(|p| p.due).max();
+        if let Some(last) = last {
+            thread::sleep(last.saturating_duration_since(Instant::now()));
+        }
+        self.store_due(encryption)
+    }
+}
+
+impl Drop for TargetLogs {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
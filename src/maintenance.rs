@@ -0,0 +1,188 @@
+//! Outages announced by the API with `503 Service Unavailable` and `Retry-After`: the
+//! operation is paused for the time asked for, and the whole run when another operation is
+//! unavailable at the same time. The responses are no findings, the outage windows are
+//! recorded with the stats of the run instead.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::ratelimit;
+use crate::transport::Response;
+
+/// Longest pause, an API asking for more is checked again afterwards
+const MAX_PAUSE: Duration = Duration::from_secs(5 * 60);
+
+/// Unavailability of an operation or of the whole API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Outage {
+    /// Method and path of the operation, none when the whole API was unavailable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<(String, String)>,
+    /// First and last unavailable response, RFC 3339
+    pub start: String,
+    pub end: String,
+    /// Number of unavailable responses
+    pub responses: u32,
+}
+
+impl fmt::Display for Outage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.operation {
+            Some((method, path)) => write!(f, "{} {}", method, path)?,
+            None => write!(f, "API")?,
+        }
+        write!(
+            f,
+            " unavailable from {} to {} ({} responses)",
+            self.start, self.end, self.responses
+        )
+    }
+}
+
+/// Adds the outage seen by another fuzzer, outages of the same operation that overlap are
+/// one, e.g. the ones seen by parallel jobs
+pub fn merge(outages: &mut Vec<Outage>, other: &Outage) {
+    // Timestamps of the same format sort as the times they stand for
+    let overlapping = outages.iter_mut().find(|outage| {
+        outage.operation == other.operation
+            && outage.start <= other.end
+            && other.start <= outage.end
+    });
+    match overlapping {
+        Some(outage) => {
+            outage.start = outage.start.clone().min(other.start.clone());
+            outage.end = outage.end.clone().max(other.end.clone());
+            outage.responses += other.responses;
+        }
+        None => outages.push(other.clone()),
+    }
+}
+
+/// How long the API asks to wait, if the response announces an outage
+pub fn announced(response: &Response) -> Option<Duration> {
+    match response.status {
+        503 => ratelimit::requested_wait(response).map(|wait| wait.min(MAX_PAUSE)),
+        _ => None,
+    }
+}
+
+/// Outage in progress, the index of its record
+#[derive(Debug)]
+struct Ongoing {
+    resume: Instant,
+    outage: usize,
+}
+
+/// Pauses of the operations and of the run
+#[derive(Debug, Default)]
+pub struct Maintenance {
+    operations: BTreeMap<(String, String), Ongoing>,
+    run: Option<Ongoing>,
+}
+
+fn now() -> String {
+    humantime::format_rfc3339_seconds(SystemTime::now()).to_string()
+}
+
+impl Maintenance {
+    /// Whether requests to the operation wait for the end of an outage
+    pub fn is_paused(&self, method: &str, path: &str) -> bool {
+        let now = Instant::now();
+        self.run.iter().any(|run| run.resume > now)
+            || self
+                .operations
+                .get(&(method.to_string(), path.to_string()))
+                .is_some_and(|ongoing| ongoing.resume > now)
+    }
+
+    /// When the first paused operation resumes, if any is paused
+    pub fn resume(&self) -> Option<Instant> {
+        let now = Instant::now();
+        self.run
+            .iter()
+            .chain(self.operations.values())
+            .map(|ongoing| ongoing.resume)
+            .filter(|resume| *resume > now)
+            .min()
+    }
+
+    /// Records the response of the operation in the outages and returns whether it announced
+    /// one, an operation answering otherwise is available again
+    pub fn observe(
+        &mut self,
+        outages: &mut Vec<Outage>,
+        method: &str,
+        path: &str,
+        response: &Response,
+    ) -> bool {
+        let key = (method.to_string(), path.to_string());
+        let wait = match announced(response) {
+            Some(wait) => wait,
+            None => {
+                if self.operations.remove(&key).is_some() {
+                    info!(method, path, "operation available again");
+                }
+                if self.operations.is_empty() {
+                    self.run = None;
+                }
+                return false;
+            }
+        };
+        let resume = Instant::now() + wait;
+        let now = now();
+        let others = self
+            .operations
+            .keys()
+            .any(|operation| *operation != key && self.is_paused(&operation.0, &operation.1));
+
+        // The whole API is unavailable when other operations are too
+        if others || self.run.is_some() {
+            let run = match &mut self.run {
+                Some(run) => run,
+                None => {
+                    info!(?wait, "API unavailable, pausing the run");
+                    outages.push(Outage {
+                        operation: None,
+                        start: now.clone(),
+                        end: now.clone(),
+                        responses: 0,
+                    });
+                    self.run.insert(Ongoing {
+                        resume,
+                        outage: outages.len() - 1,
+                    })
+                }
+            };
+            run.resume = run.resume.max(resume);
+            let outage = &mut outages[run.outage];
+            outage.end = now;
+            outage.responses += 1;
+            return true;
+        }
+
+        let ongoing = self.operations.entry(key).or_insert_with(|| {
+            info!(method, path, ?wait, "operation unavailable, pausing it");
+            outages.push(Outage {
+                operation: Some((method.to_string(), path.to_string())),
+                start: now.clone(),
+                end: now.clone(),
+                responses: 0,
+            });
+            Ongoing {
+                resume,
+                outage: outages.len() - 1,
+            }
+        });
+        ongoing.resume = ongoing.resume.max(resume);
+        let outage = &mut outages[ongoing.outage];
+        outage.end = now;
+        outage.responses += 1;
+        true
+    }
+}
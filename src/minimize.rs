@@ -0,0 +1,148 @@
+//! Minimization of the request of a new finding for its issue: the query parameters and the
+//! fields of the JSON body are left out one at a time, and each removal after which the
+//! response still has the finding is kept. What is left is what triggers the bug, which is
+//! quicker to read than a whole generated payload.
+
+use serde_json::Value;
+
+use crate::transport::Request;
+
+/// Most requests sent to minimize a finding
+pub const MAX_REQUESTS: usize = 32;
+
+/// Part of a request left out
+#[derive(Debug, Clone, PartialEq)]
+pub enum Removal {
+    /// First query parameter with the name
+    Query(String),
+    /// Field of the JSON body at the pointer
+    Field(String),
+}
+
+/// Pointers to the fields of the value, parents before their fields, fields of objects in
+/// arrays too
+fn fields(value: &Value, pointer: &str, pointers: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            for (name, value) in object.iter() {
+                let pointer = format!("{}/{}", pointer, name.replace('~', "~0").replace('/', "~1"));
+                pointers.push(pointer.clone());
+                fields(value, &pointer, pointers);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                fields(item, &format!("{}/{}", pointer, i), pointers);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Parts of the request that may be left out, in the order they are tried
+pub fn removals(request: &Request) -> Vec<Removal> {
+    let mut removals = request
+        .url
+        .query_pairs()
+        .map(|(name, _)| Removal::Query(name.into_owned()))
+        .collect::<Vec<_>>();
+    if let (None, Some(body)) = (&request.raw_body, &request.body) {
+        let mut pointers = Vec::new();
+        fields(body, "", &mut pointers);
+        removals.extend(pointers.into_iter().map(Removal::Field));
+    }
+    removals
+}
+
+impl Removal {
+    /// The request without the part, `None` if it does not have it, e.g. its parent was left
+    /// out already
+    pub fn apply(&self, request: &Request) -> Option<Request> {
+        let mut request = request.clone();
+        match self {
+            Removal::Query(name) => {
+                let mut pairs = request
+                    .url
+                    .query_pairs()
+                    .map(|(name, value)| (name.into_owned(), value.into_owned()))
+                    .collect::<Vec<_>>();
+                let position = pairs.iter().position(|(n, _)| n == name)?;
+                pairs.remove(position);
+                match pairs.is_empty() {
+                    true => request.url.set_query(None),
+                    false => {
+                        request.url.query_pairs_mut().clear().extend_pairs(pairs);
+                    }
+                }
+            }
+            Removal::Field(pointer) => {
+                let (parent, name) = pointer.rsplit_once('/')?;
+                let name = name.replace("~1", "/").replace("~0", "~");
+                request
+                    .body
+                    .as_mut()?
+                    .pointer_mut(parent)?
+                    .as_object_mut()?
+                    .remove(&name)?;
+            }
+        }
+        Some(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use url::Url;
+
+    fn request(url: &str, body: Option<Value>) -> Request {
+        Request {
+            method: "POST".to_string(),
+            url: Url::parse(url).unwrap(),
+            headers: Vec::new(),
+            body,
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        }
+    }
+
+    #[test]
+    fn lists_query_parameters_and_fields() {
+        let request = request(
+            "http://localhost/items?a=1&b=2",
+            Some(json!({"name": "x", "tags": [{"id": 1}]})),
+        );
+        assert_eq!(
+            removals(&request),
+            vec![
+                Removal::Query("a".to_string()),
+                Removal::Query("b".to_string()),
+                Removal::Field("/name".to_string()),
+                Removal::Field("/tags".to_string()),
+                Removal::Field("/tags/0/id".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_the_part() {
+        let request = request(
+            "http://localhost/items?a=1&b=2",
+            Some(json!({"a/b": 1, "tags": [{"id": 1}]})),
+        );
+        let removed = Removal::Query("a".to_string()).apply(&request).unwrap();
+        assert_eq!(removed.url.query(), Some("b=2"));
+        let removed = Removal::Field("/a~1b".to_string()).apply(&removed).unwrap();
+        let removed = Removal::Field("/tags/0/id".to_string())
+            .apply(&removed)
+            .unwrap();
+        assert_eq!(removed.body, Some(json!({"tags": [{}]})));
+
+        let removed = Removal::Field("/tags".to_string()).apply(&request).unwrap();
+        assert!(Removal::Field("/tags/0/id".to_string())
+            .apply(&removed)
+            .is_none());
+    }
+}
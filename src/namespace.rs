@@ -0,0 +1,104 @@
+//! Namespace of the data created by a run: the emails, usernames and names of the payloads
+//! start with a random token of the run, e.g. `fz3f9a1c2e-`, so that cleanup jobs of staging
+//! data and searches of the logs find everything the run created. The generated values follow
+//! the token.
+
+use std::collections::BTreeMap;
+
+use openapiv3::OpenAPI;
+use rand::rngs::StdRng;
+use serde_json::Value;
+
+use crate::faker;
+use crate::payload::{Generator, Payload};
+
+/// New random token of a run
+pub fn token() -> String {
+    format!("fz{:08x}", rand::random::<u32>())
+}
+
+/// Whether the field with the name or string format identifies a user or names something
+fn is_identifying(name: &str, format: Option<&str>) -> bool {
+    if format == Some("email") {
+        return true;
+    }
+    let name = name
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    matches!(
+        name.as_str(),
+        "email"
+            | "emailaddress"
+            | "mail"
+            | "username"
+            | "login"
+            | "handle"
+            | "nickname"
+            | "screenname"
+            | "accountname"
+            | "name"
+            | "fullname"
+            | "displayname"
+            | "firstname"
+            | "givenname"
+            | "lastname"
+            | "surname"
+            | "familyname"
+    )
+}
+
+/// Prefixes the identifying string fields of the payloads with the token of the run
+pub struct Namespace {
+    token: String,
+    /// Formats of the string properties and parameters in the specification by name
+    formats: BTreeMap<String, String>,
+}
+
+impl Namespace {
+    pub fn new(schema: &OpenAPI, token: String) -> Namespace {
+        Namespace {
+            token,
+            formats: faker::formats(schema),
+        }
+    }
+
+    fn prefix(&self, name: &str, value: &mut String) {
+        if is_identifying(name, self.formats.get(name).map(String::as_str))
+            && !value.starts_with(&self.token)
+        {
+            *value = format!("{}-{}", self.token, value);
+        }
+    }
+
+    fn fill(&self, value: &mut Value) {
+        match value {
+            Value::Object(object) => {
+                for (name, value) in object.iter_mut() {
+                    match value {
+                        Value::String(s) => self.prefix(name, s),
+                        value => self.fill(value),
+                    }
+                }
+            }
+            Value::Array(values) => {
+                for value in values.iter_mut() {
+                    self.fill(value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Generator for Namespace {
+    fn generate(&self, payload: &mut Payload<'_>, _rng: &mut StdRng) {
+        for (name, value) in payload.query_params.iter_mut() {
+            self.prefix(name, value);
+        }
+        for body in payload.body.iter_mut() {
+            self.fill(body);
+        }
+    }
+}
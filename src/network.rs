@@ -0,0 +1,291 @@
+//! Connections to the API over IPv4 and IPv6: hosts are resolved to the addresses of both
+//! families, which are tried in turn with the next attempt started while the previous one is
+//! pending, IPv6 first, as in happy eyeballs (RFC 8305). A broken family costs only the delay
+//! of the attempt instead of a timeout. The default client tries the addresses one after the
+//! other, so it gets the addresses of the family that won the last race to the host first, and
+//! the race is run again once it is older than `RACE_TTL`. Connections can be pinned to a
+//! source address or network interface, e.g. the one the target allow-lists. The default
+//! client cannot bind its sockets and then connects through the relay of the proxy module, the
+//! hyper client binds the source address itself.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{mpsc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use socket2::{Domain, Protocol, Socket, Type};
+use tracing::{debug, info};
+use url::Url;
+
+use crate::{proxy, tls};
+
+static CONFIGURED: OnceLock<NetworkSettings> = OnceLock::new();
+
+/// Whether IPv6 won the last race to the host and port and when, by netloc
+static RACES: Mutex<BTreeMap<String, (Instant, bool)>> = Mutex::new(BTreeMap::new());
+
+/// Time after which the families of a host race again, so that the default client moves to
+/// the other family when one breaks during the run
+const RACE_TTL: Duration = Duration::from_secs(30);
+
+/// Delay before the next address is tried while the attempts before are pending
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Longest time an attempt to connect to an address takes
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Default)]
+pub struct NetworkSettings {
+    /// Local address the connections are made from, only the addresses of its family are
+    /// connected to
+    pub source_address: Option<IpAddr>,
+    /// Network interface the connections go through, Linux only
+    pub source_interface: Option<String>,
+}
+
+impl NetworkSettings {
+    fn is_default(&self) -> bool {
+        self.source_address.is_none() && self.source_interface.is_none()
+    }
+}
+
+/// Host of a url without the brackets of an IPv6 address, e.g. `::1` of `http://[::1]:8080`
+fn unbracketed(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+/// Addresses of both families taking turns, IPv6 first, in the order of the resolver within
+/// a family
+fn interleaved(addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (mut v6, mut v4): (Vec<_>, Vec<_>) = addresses.into_iter().partition(SocketAddr::is_ipv6);
+    let mut interleaved = Vec::with_capacity(v6.len() + v4.len());
+    v6.reverse();
+    v4.reverse();
+    while let Some(address) = v6.pop().or_else(|| v4.pop()) {
+        interleaved.push(address);
+        if address.is_ipv6() {
+            if let Some(address) = v4.pop() {
+                interleaved.push(address);
+            }
+        }
+    }
+    interleaved
+}
+
+/// Addresses of the host the connections are tried with
+fn addresses(host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+    let host = unbracketed(host);
+    let resolved = (host, port)
+        .to_socket_addrs()
+        .with_context(|| format!("unable to resolve {}", host))?
+        .collect::<Vec<_>>();
+    let source = source_address();
+    let addresses = resolved
+        .into_iter()
+        .filter(|address| source.is_none_or(|source| source.is_ipv6() == address.is_ipv6()))
+        .collect::<Vec<_>>();
+    match (addresses.is_empty(), source) {
+        (true, Some(source)) => Err(anyhow!(
+            "{} has no address of the family of the source address {}",
+            host,
+            source
+        )),
+        (true, None) => Err(anyhow!("{} has no address", host)),
+        (false, _) => Ok(interleaved(addresses)),
+    }
+}
+
+/// Connection to the address from the source address and interface
+fn attempt(address: SocketAddr) -> io::Result<TcpStream> {
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if let Some(settings) = CONFIGURED.get() {
+        if let Some(source) = settings.source_address {
+            socket.bind(&SocketAddr::new(source, 0).into())?;
+        }
+        if let Some(interface) = &settings.source_interface {
+            bind_interface(&socket, interface)?;
+        }
+    }
+    socket.connect_timeout(&address.into(), CONNECT_TIMEOUT)?;
+    Ok(socket.into())
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+fn bind_interface(socket: &Socket, interface: &str) -> io::Result<()> {
+    socket.bind_device(Some(interface.as_bytes()))
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+fn bind_interface(_socket: &Socket, _interface: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "source interfaces are only supported on Linux",
+    ))
+}
+
+/// Connection to the first of the addresses accepting it, the next one is tried while the
+/// previous ones are pending
+fn race(addresses: Vec<SocketAddr>) -> Result<TcpStream> {
+    let mut addresses = addresses.into_iter();
+    let (sender, receiver) = mpsc::channel();
+    let mut pending = 0;
+    let mut last_error = None;
+    loop {
+        if let Some(address) = addresses.next() {
+            let sender = sender.clone();
+            pending += 1;
+            thread::spawn(move || {
+                debug!(%address, "connecting");
+                // The connections after the first one are dropped
+                let _ = sender.send(attempt(address));
+            });
+        } else if pending == 0 {
+            break;
+        }
+        let wait = match addresses.len() {
+            0 => CONNECT_TIMEOUT,
+            _ => ATTEMPT_DELAY,
+        };
+        match receiver.recv_timeout(wait) {
+            Ok(Ok(stream)) => return Ok(stream),
+            Ok(Err(e)) => {
+                pending -= 1;
+                last_error = Some(e);
+            }
+            Err(_) if addresses.len() == 0 => break,
+            Err(_) => {}
+        }
+    }
+    Err(match last_error {
+        Some(e) => anyhow!(e),
+        None => anyhow!("timed out"),
+    })
+}
+
+/// Connection to the host, to the first of its addresses accepting it
+pub(crate) fn dial(host: &str, port: u16) -> Result<TcpStream> {
+    // A server name of the TLS settings is connected to at the address of the API
+    let host = tls::connected_host(host);
+    race(addresses(&host, port)?).with_context(|| format!("unable to connect to {}:{}", host, port))
+}
+
+/// Applies the settings to all connections to the API at the url, the settings applied first
+/// are kept for the process. The relay is started for the default client if it cannot apply
+/// them itself, with `relay`.
+pub fn configure(settings: &NetworkSettings, api: &Url, relay: bool) -> Result<()> {
+    if CONFIGURED.get().is_some() {
+        return Ok(());
+    }
+    if let Some(interface) = &settings.source_interface {
+        let socket = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP))?;
+        bind_interface(&socket, interface)
+            .with_context(|| format!("unable to use the network interface {}", interface))?;
+    }
+    if !settings.is_default() {
+        info!(?settings, "using network settings");
+    }
+    let _ = CONFIGURED.set(settings.clone());
+    // Only the proxy connects to the API, through the relay it started
+    if settings.is_default() || proxy::is_configured() {
+        return Ok(());
+    }
+    if let (Some(host), Some(port)) = (api.host_str(), api.port_or_known_default()) {
+        // Hosts the resolver does not know may still be reached, e.g. once a container runs
+        if (unbracketed(host), port).to_socket_addrs().is_ok() {
+            addresses(host, port)?;
+        }
+    }
+    match relay {
+        true => proxy::relay(),
+        false => Ok(()),
+    }
+}
+
+/// Whether IPv6 wins the race to the addresses of the netloc, raced again when the last race
+/// is older than `RACE_TTL`, `None` if they could not be connected to
+fn ipv6_first(netloc: &str, addresses: &[SocketAddr]) -> Option<bool> {
+    let last = RACES.lock().unwrap().get(netloc).copied();
+    if let Some((_, ipv6_first)) = last.filter(|(at, _)| at.elapsed() < RACE_TTL) {
+        return Some(ipv6_first);
+    }
+    match race(interleaved(addresses.to_vec())).and_then(|stream| Ok(stream.peer_addr()?)) {
+        Ok(winner) => {
+            debug!(netloc, %winner, "connected to the dual-stack host");
+            let entry = (Instant::now(), winner.is_ipv6());
+            RACES.lock().unwrap().insert(netloc.to_string(), entry);
+            Some(winner.is_ipv6())
+        }
+        Err(e) => {
+            debug!(netloc, "unable to connect to the dual-stack host: {:#}", e);
+            None
+        }
+    }
+}
+
+/// Addresses of the host and port of the netloc for the default client, the ones of the
+/// family that won the last race to them first
+pub(crate) fn resolve(netloc: &str) -> io::Result<Vec<SocketAddr>> {
+    let mut addresses = netloc.to_socket_addrs()?.collect::<Vec<_>>();
+    if !addresses.iter().any(SocketAddr::is_ipv6) || !addresses.iter().any(SocketAddr::is_ipv4) {
+        return Ok(addresses);
+    }
+    if let Some(ipv6_first) = ipv6_first(netloc, &addresses) {
+        addresses.sort_by_key(|address| address.is_ipv6() != ipv6_first);
+    }
+    Ok(addresses)
+}
+
+/// Local address of the connections, if there is one
+pub(crate) fn source_address() -> Option<IpAddr> {
+    CONFIGURED.get()?.source_address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interleaves_the_families() {
+        let addresses = [
+            "[::1]:80",
+            "[::2]:80",
+            "10.0.0.1:80",
+            "10.0.0.2:80",
+            "10.0.0.3:80",
+        ]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect::<Vec<SocketAddr>>();
+        let interleaved = interleaved(addresses)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            interleaved,
+            vec![
+                "[::1]:80",
+                "10.0.0.1:80",
+                "[::2]:80",
+                "10.0.0.2:80",
+                "10.0.0.3:80"
+            ]
+        );
+    }
+
+    #[test]
+    fn strips_the_brackets_of_ipv6_hosts() {
+        assert_eq!(unbracketed("[::1]"), "::1");
+        assert_eq!(unbracketed("example.com"), "example.com");
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use serde_json::json;
+use url::Url;
+
+use crate::finding::{Finding, Findings};
+use crate::tls;
+
+/// Posts notifications to a webhook. The message is sent in the `text` field, which makes it
+/// compatible with Slack incoming webhooks.
+#[derive(Debug)]
+pub struct Notifier {
+    url: Url,
+    each_finding: bool,
+}
+
+impl Notifier {
+    pub fn new(url: Url, each_finding: bool) -> Notifier {
+        Notifier { url, each_finding }
+    }
+
+    fn post(&self, body: serde_json::Value) -> Result<()> {
+        tls::agent()
+            .request_url("POST", &self.url)
+            .send_json(body)
+            .context("unable to send webhook notification")?;
+        Ok(())
+    }
+
+    pub fn finding(&self, finding: &Finding) -> Result<()> {
+        if !self.each_finding {
+            return Ok(());
+        }
+
+        self.post(json!({
+            "text": format!(
+                "New {} severity finding: {} {} returned {}{}",
+                finding.severity,
+                finding.method,
+                finding.path,
+                finding.status,
+                match finding.classification.cwe {
+                    Some(cwe) => format!(" ({})", cwe),
+                    None => String::new(),
+                }
+            ),
+            "finding": finding,
+        }))
+    }
+
+    pub fn summary(&self, findings: &Findings, total_requests: u32) -> Result<()> {
+        let mut text = format!(
+            "Fuzzing finished after {} requests with {} finding(s)",
+            total_requests,
+            findings.len()
+        );
+        for finding in findings.iter() {
+            text += &format!(
+                "\n• [{}] {} {} returned {} ({}x)",
+                finding.severity, finding.method, finding.path, finding.status, finding.count
+            );
+        }
+
+        self.post(json!({
+            "text": text,
+            "findings": findings.iter().collect::<Vec<_>>(),
+        }))
+    }
+}
@@ -0,0 +1,514 @@
+use std::{cmp::Ordering, str::FromStr};
+
+use serde_json::{json, Value};
+
+use crate::payload::Payload;
+use crate::transport::Response;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Str(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Dot,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1
+            }
+            '[' => {
+                tokens.push(Token::LBracket);
+                i += 1
+            }
+            ']' => {
+                tokens.push(Token::RBracket);
+                i += 1
+            }
+            '.' => {
+                tokens.push(Token::Dot);
+                i += 1
+            }
+            '"' | '\'' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&q| q == c)
+                    .ok_or_else(|| "unterminated string".to_string())?;
+                tokens.push(Token::Str(chars[i + 1..i + 1 + end].iter().collect()));
+                i += end + 2;
+            }
+            '=' | '!' | '<' | '>' => {
+                let next = chars.get(i + 1).copied();
+                let op = match (c, next) {
+                    ('=', Some('=')) => "==",
+                    ('!', Some('=')) => "!=",
+                    ('<', Some('=')) => "<=",
+                    ('>', Some('=')) => ">=",
+                    ('<', _) => "<",
+                    ('>', _) => ">",
+                    _ => return Err(format!("unexpected `{}`", c)),
+                };
+                tokens.push(Token::Op(op));
+                i += op.len();
+            }
+            _ if c.is_ascii_digit()
+                || (c == '-' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) =>
+            {
+                let start = i;
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let number = chars[start..i].iter().collect::<String>();
+                tokens.push(Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| format!("invalid number `{}`", number))?,
+                ));
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '-')
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            }
+            _ => return Err(format!("unexpected `{}`", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    /// Field of the response, e.g. `body.items[0].id`
+    Path(Vec<Value>),
+    Len(Box<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, &'static str, Box<Expr>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(ident)) if ident == keyword => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), String> {
+        match self.next() {
+            Some(next) if next == token => Ok(()),
+            next => Err(format!("expected {:?}, found {:?}", token, next)),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        match self.keyword("not") {
+            true => Ok(Expr::Not(Box::new(self.not()?))),
+            false => self.comparison(),
+        }
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.value()?;
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            Some(Token::Ident(ident)) if ident == "contains" => "contains",
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        Ok(Expr::Compare(Box::new(left), op, Box::new(self.value()?)))
+    }
+
+    fn value(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Literal(json!(n))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                "len" => {
+                    self.expect(Token::LParen)?;
+                    let expr = self.or()?;
+                    self.expect(Token::RParen)?;
+                    Ok(Expr::Len(Box::new(expr)))
+                }
+                _ => self.path(ident),
+            },
+            Some(token) => Err(format!("unexpected {:?}", token)),
+            None => Err("unexpected end of the expression".to_string()),
+        }
+    }
+
+    fn path(&mut self, root: String) -> Result<Expr, String> {
+        let mut path = vec![Value::String(root)];
+        loop {
+            match self.peek() {
+                Some(Token::Dot) => {
+                    self.position += 1;
+                    match self.next() {
+                        Some(Token::Ident(field)) => path.push(Value::String(field)),
+                        token => return Err(format!("expected field name, found {:?}", token)),
+                    }
+                }
+                Some(Token::LBracket) => {
+                    self.position += 1;
+                    match self.next() {
+                        Some(Token::Number(index)) => path.push(json!(index as u64)),
+                        Some(Token::Str(field)) => path.push(Value::String(field)),
+                        token => return Err(format!("expected index, found {:?}", token)),
+                    }
+                    self.expect(Token::RBracket)?;
+                }
+                _ => return Ok(Expr::Path(path)),
+            }
+        }
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    !matches!(value, Value::Null | Value::Bool(false))
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => l.as_f64()?.partial_cmp(&r.as_f64()?),
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        _ => None,
+    }
+}
+
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => l.as_f64() == r.as_f64(),
+        _ => left == right,
+    }
+}
+
+impl Expr {
+    fn eval(&self, context: &Value) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Path(path) => {
+                let mut value = context;
+                for segment in path {
+                    value = match (segment, value) {
+                        (Value::String(field), Value::Object(object)) => {
+                            object.get(field).unwrap_or(&Value::Null)
+                        }
+                        (Value::Number(index), Value::Array(array)) => index
+                            .as_u64()
+                            .and_then(|index| array.get(index as usize))
+                            .unwrap_or(&Value::Null),
+                        _ => &Value::Null,
+                    };
+                }
+                value.clone()
+            }
+            Expr::Len(expr) => match expr.eval(context) {
+                Value::Array(array) => json!(array.len()),
+                Value::Object(object) => json!(object.len()),
+                Value::String(s) => json!(s.chars().count()),
+                _ => Value::Null,
+            },
+            Expr::Not(expr) => Value::Bool(!truthy(&expr.eval(context))),
+            Expr::And(left, right) => {
+                Value::Bool(truthy(&left.eval(context)) && truthy(&right.eval(context)))
+            }
+            Expr::Or(left, right) => {
+                Value::Bool(truthy(&left.eval(context)) || truthy(&right.eval(context)))
+            }
+            Expr::Compare(left, op, right) => {
+                let (left, right) = (left.eval(context), right.eval(context));
+                let result = match *op {
+                    "==" => equals(&left, &right),
+                    "!=" => !equals(&left, &right),
+                    "<" => compare(&left, &right) == Some(Ordering::Less),
+                    "<=" => matches!(
+                        compare(&left, &right),
+                        Some(Ordering::Less | Ordering::Equal)
+                    ),
+                    ">" => compare(&left, &right) == Some(Ordering::Greater),
+                    ">=" => matches!(
+                        compare(&left, &right),
+                        Some(Ordering::Greater | Ordering::Equal)
+                    ),
+                    "contains" => match (&left, &right) {
+                        (Value::String(l), Value::String(r)) => l.contains(r.as_str()),
+                        (Value::Array(array), value) => array.iter().any(|v| equals(v, value)),
+                        (Value::Object(object), Value::String(key)) => object.contains_key(key),
+                        _ => false,
+                    },
+                    _ => false,
+                };
+                Value::Bool(result)
+            }
+        }
+    }
+}
+
+/// Decides whether a response is a finding, implement it for checks of domain invariants
+/// that do not fit into an expression
+pub trait Oracle: Send {
+    /// Returns the description of the finding if the response is one
+    fn check(&self, payload: &Payload, response: &Response) -> Option<String>;
+}
+
+/// Finding defined by an expression evaluated against each response, e.g.
+/// `negative-balance: status == 200 and body.balance < 0`
+#[derive(Debug, Clone)]
+pub struct ExprOracle {
+    name: Option<String>,
+    source: String,
+    expr: Expr,
+}
+
+impl FromStr for ExprOracle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, source) = match s.split_once(':') {
+            Some((name, source))
+                if !name.trim().is_empty()
+                    && name
+                        .trim()
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '-' || c == '_') =>
+            {
+                (Some(name.trim().to_string()), source.trim())
+            }
+            _ => (None, s.trim()),
+        };
+        let expression = source.strip_prefix("when ").unwrap_or(source);
+
+        let mut parser = Parser {
+            tokens: tokenize(expression)?,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected {:?} in `{}`", token, expression));
+        }
+        Ok(ExprOracle {
+            name,
+            source: source.to_string(),
+            expr,
+        })
+    }
+}
+
+impl ExprOracle {
+    /// Whether the expression holds for the response
+    pub fn matches(&self, payload: &Payload, response: &Response) -> bool {
+        truthy(&self.expr.eval(&ExprOracle::context(payload, response)))
+    }
+
+    /// Expression as it was given, without the name
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Values the expression is evaluated against, the body is parsed as JSON if possible. For
+    /// an event of a stream the body is its data and `event` its type and id
+    fn context(payload: &Payload, response: &Response) -> Value {
+        // Values of repeated headers are combined, separated by commas
+        let mut headers = serde_json::Map::new();
+        for (name, value) in response.headers.iter() {
+            match headers.entry(name.to_lowercase()) {
+                serde_json::map::Entry::Vacant(entry) => {
+                    entry.insert(Value::String(value.clone()));
+                }
+                serde_json::map::Entry::Occupied(mut entry) => {
+                    let combined =
+                        format!("{}, {}", entry.get().as_str().unwrap_or_default(), value);
+                    entry.insert(Value::String(combined));
+                }
+            }
+        }
+        json!({
+            "status": response.status,
+            "headers": headers,
+            "body": serde_json::from_str::<Value>(&response.body)
+                .unwrap_or_else(|_| Value::String(response.body.clone())),
+            "method": payload.method,
+            "path": payload.path,
+            "operation": payload.operation_id,
+            "elapsed_ms": response.elapsed.as_millis() as u64,
+            "event": response.events.first().map(|event| json!({
+                "type": event.event.as_deref().unwrap_or("message"),
+                "id": event.id,
+            })),
+        })
+    }
+}
+
+impl Oracle for ExprOracle {
+    fn check(&self, payload: &Payload, response: &Response) -> Option<String> {
+        match self.matches(payload, response) {
+            true => Some(match &self.name {
+                Some(name) => format!("oracle `{}` matched: {}", name, self.source),
+                None => format!("oracle matched: {}", self.source),
+            }),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openapiv3::Responses;
+    use std::time::Duration;
+    use url::Url;
+
+    fn check(oracle: &str, status: u16, body: &str) -> Option<String> {
+        let url = Url::parse("http://localhost").unwrap();
+        let responses = Responses::default();
+        let payload = Payload {
+            url: &url,
+            method: "POST",
+            path: "/accounts/{id}",
+            operation_id: Some("withdraw"),
+            query_params: Vec::new(),
+            path_params: Vec::new(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            raw_body: None,
+            seed: 0,
+            responses: &responses,
+            idempotent: false,
+            violation: None,
+            negative: false,
+            timeout: None,
+            endpoint: None,
+        };
+        let response = Response {
+            status,
+            status_text: String::new(),
+            http_version: "HTTP/1.1".to_string(),
+            headers: vec![
+                ("Set-Cookie".to_string(), "a=1".to_string()),
+                ("set-cookie".to_string(), "b=2".to_string()),
+            ],
+            body: body.to_string(),
+            elapsed: Duration::from_millis(12),
+            events: Vec::new(),
+        };
+        oracle
+            .parse::<ExprOracle>()
+            .unwrap()
+            .check(&payload, &response)
+    }
+
+    #[test]
+    fn evaluates_expressions_against_the_response() {
+        let body = r#"{"balance": -5, "items": [{"id": "x"}], "owner": "a b"}"#;
+        assert_eq!(
+            check(
+                "negative-balance: status == 200 and body.balance < 0",
+                200,
+                body
+            ),
+            Some("oracle `negative-balance` matched: status == 200 and body.balance < 0".into())
+        );
+        assert_eq!(
+            check("status == 200 and body.balance >= 0", 200, body),
+            None
+        );
+        assert!(check(
+            "when operation == 'withdraw' and method == \"POST\"",
+            200,
+            body
+        )
+        .is_some());
+        assert!(check(
+            "len(body.items) == 1 and body.items[0].id == 'x'",
+            200,
+            body
+        )
+        .is_some());
+        assert!(check(
+            "body['owner'] contains 'a ' and not (elapsed_ms > 100)",
+            200,
+            body
+        )
+        .is_some());
+        assert!(check("headers['set-cookie'] == 'a=1, b=2'", 200, body).is_some());
+        assert!(check("body.missing.field == null or status != 500", 500, body).is_some());
+        assert!(check("body contains 'error'", 500, "internal error").is_some());
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert!("status ==".parse::<ExprOracle>().is_err());
+        assert!("status = 200".parse::<ExprOracle>().is_err());
+        assert!("body.name == 'unterminated".parse::<ExprOracle>().is_err());
+        assert!("status == 200)".parse::<ExprOracle>().is_err());
+        // A name is only split off if it looks like one
+        let oracle = "body.url == 'http://x'".parse::<ExprOracle>().unwrap();
+        assert_eq!(oracle.source(), "body.url == 'http://x'");
+    }
+}
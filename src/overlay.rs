@@ -0,0 +1,220 @@
+//! OpenAPI Overlay documents applied on top of the specification before fuzzing, e.g. to add
+//! examples, remove operations or tweak constraints of a third-party specification without
+//! forking it. The actions select nodes with JSONPath and update or remove them in order.
+//! Overlays of OpenAPI specifications are applied to the raw document before it is parsed, so
+//! that the keywords and extensions they change are seen by the generators reading it, e.g.
+//! `if`/`then` or `x-fuzzer-*`, which the parsed specification does not keep.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use tracing::{debug, warn};
+
+#[derive(Debug, Deserialize)]
+pub struct Action {
+    /// JSONPath of the nodes of the specification, e.g. `$.paths['/admin']`
+    pub target: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Merged into the nodes, targeted arrays get it appended
+    #[serde(default)]
+    pub update: Option<Value>,
+    /// Removes the nodes from their parents
+    #[serde(default)]
+    pub remove: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Overlay {
+    /// Version of the Overlay specification, e.g. `1.0.0`
+    pub overlay: String,
+    pub actions: Vec<Action>,
+}
+
+/// Loads the overlay from a YAML or JSON file
+pub fn load(path: &Path) -> Result<Overlay> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("unable to read {}", path.display()))?;
+    let overlay: Overlay = serde_yaml::from_str(&content)
+        .with_context(|| format!("invalid overlay {}", path.display()))?;
+    if !overlay.overlay.starts_with("1.") {
+        return Err(anyhow!("unsupported overlay version {}", overlay.overlay));
+    }
+    Ok(overlay)
+}
+
+/// Applies the overlay files in order to the document
+pub fn apply_files(files: &[PathBuf], document: &mut Value) -> Result<()> {
+    for file in files.iter() {
+        load(file)
+            .and_then(|overlay| overlay.apply(document))
+            .with_context(|| format!("unable to apply overlay {}", file.display()))?;
+    }
+    Ok(())
+}
+
+/// Merges the update into the node: properties of objects recursively, arrays are concatenated
+/// and other nodes are replaced
+fn merge(node: &mut Value, update: &Value) {
+    match (node, update) {
+        (Value::Object(node), Value::Object(update)) => {
+            for (key, value) in update {
+                match node.get_mut(key) {
+                    Some(property) => merge(property, value),
+                    None => {
+                        node.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (Value::Array(node), Value::Array(update)) => node.extend(update.iter().cloned()),
+        (node, update) => *node = update.clone(),
+    }
+}
+
+/// Removes the node at the JSON pointer from its parent
+fn remove(spec: &mut Value, pointer: &str) {
+    let (parent, key) = match pointer.rsplit_once('/') {
+        Some(split) => split,
+        // The whole specification
+        None => return,
+    };
+    let key = key.replace("~1", "/").replace("~0", "~");
+    match spec.pointer_mut(parent) {
+        Some(Value::Object(object)) => {
+            object.remove(&key);
+        }
+        Some(Value::Array(array)) => {
+            if let Some(index) = key.parse::<usize>().ok().filter(|&i| i < array.len()) {
+                array.remove(index);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl Overlay {
+    /// Applies the actions to the specification in order
+    pub fn apply(&self, spec: &mut Value) -> Result<()> {
+        for action in self.actions.iter() {
+            let path = JsonPath::parse(&action.target)
+                .with_context(|| format!("invalid target `{}`", action.target))?;
+            let pointers = path
+                .query_located(spec)
+                .locations()
+                .map(|location| location.to_json_pointer())
+                .collect::<Vec<_>>();
+            if pointers.is_empty() {
+                warn!(jsonpath = %action.target, "the target of the overlay action matches nothing");
+                continue;
+            }
+            debug!(jsonpath = %action.target, description = ?action.description, nodes = pointers.len(), "applying overlay action");
+            // Later nodes first, so that removals keep the indices of the earlier ones
+            for pointer in pointers.iter().rev() {
+                if action.remove {
+                    remove(spec, pointer);
+                } else if let (Some(update), Some(node)) =
+                    (&action.update, spec.pointer_mut(pointer))
+                {
+                    match node {
+                        // The update is an entry of targeted arrays
+                        Value::Array(array) => array.push(update.clone()),
+                        node => merge(node, update),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn document() -> Value {
+        json!({
+            "openapi": "3.0.0",
+            "paths": {
+                "/orders": {
+                    "post": {
+                        "parameters": [{"name": "a", "in": "query"}],
+                        "requestBody": {"content": {"application/json": {"schema": {
+                            "type": "object",
+                            "properties": {"sku": {"type": "string"}},
+                        }}}},
+                    },
+                },
+                "/admin": {"get": {}},
+            },
+        })
+    }
+
+    #[test]
+    fn applies_actions_in_order() {
+        let overlay: Overlay = serde_yaml::from_str(
+            "overlay: 1.0.0
+actions:
+  - target: $.paths['/admin']
+    remove: true
+  - target: $.paths['/orders'].post.parameters
+    update: {name: b, in: query}
+  - target: $.paths['/orders'].post.requestBody.content['application/json'].schema
+    update:
+      x-fuzzer-skip: true
+      properties: {sku: {pattern: '^[A-Z]{3}$'}}
+      if: {required: [sku]}
+  - target: $.paths['/missing']
+    remove: true
+",
+        )
+        .unwrap();
+        let mut document = document();
+        overlay.apply(&mut document).unwrap();
+        assert!(document.pointer("/paths/~1admin").is_none());
+        let operation = &document["paths"]["/orders"]["post"];
+        assert_eq!(operation["parameters"][1]["name"], "b");
+        let schema = &operation["requestBody"]["content"]["application/json"]["schema"];
+        // Keywords and extensions the parsed specification does not keep are in the document
+        assert_eq!(schema["x-fuzzer-skip"], true);
+        assert_eq!(schema["if"], json!({"required": ["sku"]}));
+        assert_eq!(
+            schema["properties"]["sku"],
+            json!({"type": "string", "pattern": "^[A-Z]{3}$"})
+        );
+    }
+
+    #[test]
+    fn applies_files_to_the_document() {
+        let dir = std::env::temp_dir().join(format!("overlay-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.yaml");
+        let second = dir.join("second.yaml");
+        fs::write(
+            &first,
+            "overlay: 1.0.0\nactions:\n  - target: $.paths\n    update: {/health: {get: {}}}\n",
+        )
+        .unwrap();
+        fs::write(
+            &second,
+            "overlay: 1.0.0\nactions:\n  - target: $.paths['/health']\n    remove: true\n",
+        )
+        .unwrap();
+        let mut document = document();
+        apply_files(std::slice::from_ref(&first), &mut document).unwrap();
+        assert!(document["paths"]["/health"].is_object());
+        apply_files(&[first.clone(), second], &mut document).unwrap();
+        assert!(document["paths"].get("/health").is_none());
+
+        fs::write(&first, "overlay: 2.0.0\nactions: []\n").unwrap();
+        assert!(apply_files(&[first], &mut document).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
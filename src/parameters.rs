@@ -0,0 +1,132 @@
+//! Parameters of the operations as the specification means them: the ones declared for the
+//! whole path are inherited by its operations, unless an operation declares a parameter of the
+//! same name and location, and references to `components.parameters` and to the schemas of
+//! parameters are resolved. Resolving the references alone appends the parameters of the path
+//! to the operations every time it is done, overridden ones included.
+
+use openapiv3::{
+    Components, OpenAPI, Parameter, ParameterData, ParameterSchemaOrContent, ReferenceOr,
+};
+use tracing::warn;
+
+/// Longest chain of references followed, e.g. a schema referencing another one
+const MAX_REFERENCES: usize = 10;
+
+fn data(parameter: &Parameter) -> (&'static str, &ParameterData) {
+    match parameter {
+        Parameter::Query { parameter_data, .. } => ("query", parameter_data),
+        Parameter::Path { parameter_data, .. } => ("path", parameter_data),
+        Parameter::Header { parameter_data, .. } => ("header", parameter_data),
+        Parameter::Cookie { parameter_data, .. } => ("cookie", parameter_data),
+    }
+}
+
+fn data_mut(parameter: &mut Parameter) -> &mut ParameterData {
+    match parameter {
+        Parameter::Query { parameter_data, .. }
+        | Parameter::Path { parameter_data, .. }
+        | Parameter::Header { parameter_data, .. }
+        | Parameter::Cookie { parameter_data, .. } => parameter_data,
+    }
+}
+
+/// Name and location identifying the parameter, names of headers are case insensitive
+fn key(parameter: &Parameter) -> (&'static str, String) {
+    match data(parameter) {
+        ("header", data) => ("header", data.name.to_lowercase()),
+        (location, data) => (location, data.name.clone()),
+    }
+}
+
+/// Name of the component the reference points to, e.g. `#/components/parameters/Page`
+fn component<'a>(reference: &'a str, kind: &str) -> Option<&'a str> {
+    reference
+        .strip_prefix("#/components/")?
+        .strip_prefix(kind)?
+        .strip_prefix('/')
+}
+
+/// The parameter with its schema, `None` when a reference cannot be resolved
+fn resolve(parameter: &ReferenceOr<Parameter>, components: &Components) -> Option<Parameter> {
+    let mut parameter = parameter.clone();
+    for _ in 0..MAX_REFERENCES {
+        let reference = match &parameter {
+            ReferenceOr::Item(_) => break,
+            ReferenceOr::Reference { reference } => reference,
+        };
+        match component(reference, "parameters").and_then(|name| components.parameters.get(name)) {
+            Some(resolved) => parameter = resolved.clone(),
+            None => {
+                warn!(reference = reference.as_str(), "unresolved parameter");
+                return None;
+            }
+        }
+    }
+    let mut parameter = match parameter {
+        ReferenceOr::Item(parameter) => parameter,
+        ReferenceOr::Reference { .. } => return None,
+    };
+    if let ParameterSchemaOrContent::Schema(schema) = &mut data_mut(&mut parameter).format {
+        for _ in 0..MAX_REFERENCES {
+            let reference = match schema {
+                ReferenceOr::Item(_) => break,
+                ReferenceOr::Reference { reference } => reference,
+            };
+            match component(reference, "schemas").and_then(|name| components.schemas.get(name)) {
+                Some(resolved) => *schema = resolved.clone(),
+                None => {
+                    warn!(
+                        reference = reference.as_str(),
+                        "unresolved schema of parameter"
+                    );
+                    return None;
+                }
+            }
+        }
+    }
+    Some(parameter)
+}
+
+/// Moves the parameters of the paths to their operations and resolves the references of all
+/// parameters, so that every operation lists each of its parameters once
+pub fn inherit(schema: &mut OpenAPI) {
+    let components = schema.components.clone().unwrap_or_default();
+    for item in schema.paths.values_mut() {
+        let item = match item {
+            ReferenceOr::Item(item) => item,
+            ReferenceOr::Reference { .. } => continue,
+        };
+        let inherited = item
+            .parameters
+            .drain(..)
+            .filter_map(|parameter| resolve(&parameter, &components))
+            .collect::<Vec<_>>();
+        let operations = [
+            &mut item.get,
+            &mut item.put,
+            &mut item.post,
+            &mut item.delete,
+            &mut item.options,
+            &mut item.head,
+            &mut item.patch,
+            &mut item.trace,
+        ];
+        for operation in operations {
+            let operation = match operation {
+                Some(operation) => operation,
+                None => continue,
+            };
+            let mut parameters = operation
+                .parameters
+                .iter()
+                .filter_map(|parameter| resolve(parameter, &components))
+                .collect::<Vec<_>>();
+            for parameter in inherited.iter() {
+                if !parameters.iter().any(|p| key(p) == key(parameter)) {
+                    parameters.push(parameter.clone());
+                }
+            }
+            operation.parameters = parameters.into_iter().map(ReferenceOr::Item).collect();
+        }
+    }
+}
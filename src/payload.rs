@@ -1,138 +1,224 @@
-use anyhow::{Context, Error, Result};
-use arbitrary::{Arbitrary, Unstructured};
-use openapi_utils::ReferenceOrExt;
-use openapiv3::{
-    ArrayType, ObjectType, Operation, Parameter, PathItem, ReferenceOr, Responses, Schema,
-    SchemaKind, Type,
-};
-use rand::{prelude::IteratorRandom, Rng};
+use anyhow::Result;
+use arbitrary::Unstructured;
+use openapiv3::{OpenAPI, Operation, PathItem, ReferenceOr, Responses};
+use percent_encoding::{AsciiSet, CONTROLS};
+use rand::{prelude::SliceRandom, rngs::StdRng, Rng, SeedableRng};
 use serde::Serialize;
-use serde_json::json;
-use std::fmt::Write;
+use std::{collections::BTreeMap, time::Duration};
 use url::Url;
 
+use crate::binary;
+use crate::config::OperationOverride;
+use crate::corpus::{Corpus, Seed};
+use crate::plan::{Location, ParameterPlan, Plan, Plans};
+use crate::pool;
+use crate::transport::Request;
+
+/// Characters percent-encoded in values put in path segments, e.g. identifiers of resources
+pub const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Probability that a payload is generated from a seed of the operation, if it has any
+const SEED_PROBABILITY: f64 = 0.5;
+
+/// Probability that a payload is made invalid on purpose in the negative mode
+const NEGATIVE_PROBABILITY: f64 = 0.5;
+
+/// Changes generated payloads, e.g. to use identifiers of existing resources. The random number
+/// generator is seeded with the seed of the payload, so the payload can be generated again.
+pub trait Generator: Send {
+    fn generate(&self, payload: &mut Payload<'_>, rng: &mut StdRng);
+}
+
 #[derive(Debug, Serialize)]
 pub struct Payload<'a> {
     pub url: &'a Url,
     pub method: &'a str,
     pub path: &'a str,
+    pub operation_id: Option<&'a str>,
     pub query_params: Vec<(&'a str, String)>,
     pub path_params: Vec<(&'a str, String)>,
     pub headers: Vec<(&'a str, String)>,
     pub body: Vec<serde_json::Value>,
+    /// Body sent as is instead of `body`, e.g. JSON with duplicate keys
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::transport::base64_bytes::serialize"
+    )]
+    pub raw_body: Option<Vec<u8>>,
+    /// Seed of the random generator the payload was generated with
+    pub seed: u64,
     #[serde(skip)]
     pub responses: &'a Responses,
+    /// Repeating the request must not change the response
+    #[serde(skip)]
+    pub idempotent: bool,
+    /// Violation of the specification made on purpose, the request must be rejected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub violation: Option<String>,
+    /// Generators may make the payload invalid on purpose, in the negative mode
+    #[serde(skip)]
+    pub negative: bool,
+    /// Longest time to wait for the response
+    #[serde(skip)]
+    pub timeout: Option<Duration>,
+    /// Path the request is sent to instead of the path of the operation, e.g. the endpoint
+    /// of a GraphQL API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
 }
 
-fn generate_json_object(object: &ObjectType, gen: &mut Unstructured) -> Result<serde_json::Value> {
-    let mut json_object = serde_json::Map::with_capacity(object.properties.len());
-    for (name, schema) in &object.properties {
-        let schema_kind = &schema.to_item_ref().schema_kind;
-        json_object.insert(name.clone(), schema_kind_to_json(schema_kind, gen)?);
-    }
-    Ok(serde_json::Value::Object(json_object))
+/// Ways to make a payload invalid
+enum Violation<'a> {
+    MissingParameter(&'a str),
+    MissingBody,
+    MissingField(&'a str),
+    WrongType(&'a str, serde_json::Value),
+    /// Body field with a value close to the enum values, e.g. with another case
+    NotInEnum(&'a str, serde_json::Value),
+    /// Parameter with a value close to the enum values
+    ParameterNotInEnum(&'a str, String),
 }
 
-fn generate_json_array(array: &ArrayType, gen: &mut Unstructured) -> Result<serde_json::Value> {
-    let items = array.items.to_item_ref();
-    let (min, max) = (array.min_items.unwrap_or(1), array.max_items.unwrap_or(10));
-    let json_array = (min..=max)
-        .map(|_| schema_kind_to_json(&items.schema_kind, gen))
-        .collect::<Result<Vec<serde_json::Value>>>();
-    Ok(serde_json::Value::Array(json_array?))
+/// Returns the value of the parameter from the seed or a random one
+fn param_value(
+    seeded: Option<&BTreeMap<String, String>>,
+    parameter: &ParameterPlan,
+    gen: &mut Unstructured,
+    rng: &mut StdRng,
+) -> Result<String> {
+    match seeded.and_then(|params| Seed::value(params, &parameter.seed_name, rng)) {
+        Some(value) => Ok(value),
+        None => parameter.generate(gen),
+    }
 }
 
-fn schema_type_to_json(schema_type: &Type, gen: &mut Unstructured) -> Result<serde_json::Value> {
-    match schema_type {
-        Type::String(_string_type) => Ok(json!(String::arbitrary(gen)?)),
-        Type::Number(_number_type) => Ok(json!(f64::arbitrary(gen)?)),
-        Type::Integer(_integer_type) => Ok(json!(i64::arbitrary(gen)?)),
-        Type::Object(object_type) => generate_json_object(object_type, gen),
-        Type::Array(array_type) => generate_json_array(array_type, gen),
-        Type::Boolean {} => Ok(json!(bool::arbitrary(gen)?)),
-    }
+/// Operations of the path item with their methods
+pub fn operations(item: &PathItem) -> Vec<(&'static str, &Option<Operation>)> {
+    vec![
+        ("GET", &item.get),
+        ("PUT", &item.put),
+        ("POST", &item.post),
+        ("DELETE", &item.delete),
+        ("OPTIONS", &item.options),
+        ("HEAD", &item.head),
+        ("PATCH", &item.patch),
+        ("TRACE", &item.trace),
+    ]
 }
 
-fn schema_kind_to_json(
-    schema_kind: &SchemaKind,
-    gen: &mut Unstructured,
-) -> Result<serde_json::Value> {
-    let f = |vec: &Vec<ReferenceOr<Schema>>,
-             gen: &mut Unstructured|
-     -> Result<Vec<serde_json::Value>> {
-        vec.iter()
-            .map(|ref_of_schema| schema_kind_to_json(&ref_of_schema.to_item_ref().schema_kind, gen))
-            .collect()
-    };
-
-    match schema_kind {
-        SchemaKind::Any(_any) => Ok(json!(String::arbitrary(gen)?)),
-        SchemaKind::Type(schema_type) => Ok(schema_type_to_json(schema_type, gen)?),
-        SchemaKind::OneOf { one_of } => f(one_of, gen)?
-            .into_iter()
-            .choose(&mut rand::thread_rng())
-            .ok_or_else(|| Error::msg("unable to generate JSON")),
-        SchemaKind::AnyOf { any_of } => Ok(f(any_of, gen)?
-            .into_iter()
-            .choose_multiple(&mut rand::thread_rng(), 5)
-            .into()),
-        SchemaKind::AllOf { all_of } => Ok(f(all_of, gen)?.into()),
+/// Removes the operations that were not chosen from the specification
+pub fn retain_operations(schema: &mut OpenAPI, chosen: &[(String, String)]) {
+    for (path, ref_or_item) in schema.paths.iter_mut() {
+        let item = match ref_or_item {
+            ReferenceOr::Item(item) => item,
+            ReferenceOr::Reference { .. } => continue,
+        };
+        for (method, operation) in [
+            ("GET", &mut item.get),
+            ("PUT", &mut item.put),
+            ("POST", &mut item.post),
+            ("DELETE", &mut item.delete),
+            ("OPTIONS", &mut item.options),
+            ("HEAD", &mut item.head),
+            ("PATCH", &mut item.patch),
+            ("TRACE", &mut item.trace),
+        ] {
+            if !chosen.iter().any(|(m, p)| m == method && p == path) {
+                *operation = None;
+            }
+        }
     }
 }
 
 impl<'a> Payload<'a> {
-    fn new(
+    pub(crate) fn new(
         url: &'a Url,
         method: &'a str,
         path: &'a str,
-        operation: &'a Operation,
+        plan: &'a Plan,
         extra_headers: &'a [(String, String)],
+        seeds: &'a [Seed],
+        seed: u64,
     ) -> Result<Payload<'a>> {
         let mut query_params: Vec<(&str, String)> = Vec::new();
         let mut path_params: Vec<(&str, String)> = Vec::new();
         let mut headers: Vec<(&str, String)> = Vec::new();
 
-        // Set-up random data generator
-        let fuzzer_input: String = rand::thread_rng()
+        // Set-up random data generator, the payload can be generated again from the same seed
+        let mut rng = StdRng::seed_from_u64(seed);
+        let fuzzer_input: String = (&mut rng)
             .sample_iter::<char, _>(rand::distributions::Standard)
             .take(1024)
             .collect();
 
         let mut generator = Unstructured::new(fuzzer_input.as_bytes());
-        for ref_or_param in operation.parameters.iter() {
-            match ref_or_param.to_item_ref() {
-                Parameter::Query { parameter_data, .. } => {
-                    query_params.push((&parameter_data.name, String::arbitrary(&mut generator)?))
-                }
-                Parameter::Path { parameter_data, .. } => {
-                    path_params.push((&parameter_data.name, String::arbitrary(&mut generator)?))
-                }
-                Parameter::Header { parameter_data, .. } => {
-                    headers.push((&parameter_data.name, String::arbitrary(&mut generator)?))
-                }
-                Parameter::Cookie { parameter_data, .. } => headers.push((
+
+        // Use one of the seeds of the operation as the starting point from time to time
+        let corpus_seed = seeds
+            .choose(&mut rng)
+            .filter(|_| rng.gen_bool(SEED_PROBABILITY));
+        for parameter in plan.parameters.iter() {
+            let seeded = corpus_seed.map(|seed| match parameter.location {
+                Location::Query => &seed.query_params,
+                Location::Path => &seed.path_params,
+                Location::Header | Location::Cookie => &seed.headers,
+            });
+            match parameter.location {
+                Location::Query => query_params.push((
+                    &parameter.name,
+                    param_value(seeded, parameter, &mut generator, &mut rng)?,
+                )),
+                Location::Path => path_params.push((
+                    &parameter.name,
+                    param_value(seeded, parameter, &mut generator, &mut rng)?,
+                )),
+                Location::Header => headers.push((
+                    &parameter.name,
+                    param_value(seeded, parameter, &mut generator, &mut rng)?,
+                )),
+                Location::Cookie => headers.push((
                     "Cookie",
-                    format!(
-                        "{}={}",
-                        parameter_data.name,
-                        String::arbitrary(&mut generator)?
-                    ),
+                    format!("{}={}", parameter.name, parameter.generate(&mut generator)?),
                 )),
             }
         }
 
-        let body = operation.request_body.as_ref().map(|ref_or_body| {
-            let request_body = ref_or_body.to_item_ref();
-            request_body
-                .content
-                .iter()
-                .filter(|(content, _)| content.contains("json"))
-                .filter_map(|(_, media)| {
-                    media.schema.as_ref().map(|schema| {
-                        schema_kind_to_json(&schema.to_item_ref().schema_kind, &mut generator)
-                    })
-                })
-                .collect::<Result<Vec<_>>>()
+        if let Some(corpus_seed) = corpus_seed {
+            for (name, value) in corpus_seed.headers.iter() {
+                let present = headers
+                    .iter()
+                    .any(|(header_name, _)| header_name.eq_ignore_ascii_case(name));
+                if !present && name != "content-length" && name != "host" {
+                    headers.push((name, value.clone()));
+                }
+            }
+        }
+
+        let seeded_body = corpus_seed.and_then(|seed| seed.mutated_body(&mut generator, &mut rng));
+        let body = plan.body.as_ref().map(|body_plan| match seeded_body {
+            Some(body) => Ok(vec![body]),
+            None => body_plan.generate(&mut generator),
+        });
+
+        // Bodies of binary media types are sent as is, when there is no JSON one
+        let binary_body = match (&body, &plan.body) {
+            (Some(Ok(body)), Some(body_plan)) if body.is_empty() => body_plan.binary.as_ref(),
+            _ => None,
+        };
+        let raw_body = binary_body.map(|content_type| {
+            headers.push(("Content-Type", content_type.clone()));
+            binary::bytes(content_type, &mut generator)
         });
 
         for (name, value) in extra_headers {
@@ -149,64 +235,217 @@ impl<'a> Payload<'a> {
             url,
             method,
             path,
+            operation_id: plan.operation_id.as_deref(),
             query_params,
             path_params,
             headers,
             body: body.unwrap_or_else(|| Ok(Vec::new()))?,
-            responses: &operation.responses,
+            raw_body,
+            seed,
+            responses: &plan.responses,
+            idempotent: plan.idempotent,
+            violation: None,
+            timeout: None,
+            endpoint: None,
+            negative: false,
         })
     }
 
+    /// Applies the options of the operation from the config, fixed parameters replace the
+    /// generated values
+    pub fn apply_override(&mut self, operation_override: &'a OperationOverride) {
+        for header in operation_override.header.iter() {
+            match self
+                .headers
+                .iter_mut()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&header.0))
+            {
+                Some((_, value)) => *value = header.1.clone(),
+                None => self.headers.push((&header.0, header.1.clone())),
+            }
+        }
+        for (name, fixed) in operation_override.params.iter() {
+            for (param, value) in self
+                .path_params
+                .iter_mut()
+                .chain(self.query_params.iter_mut())
+                .chain(self.headers.iter_mut())
+            {
+                if param == name {
+                    *value = fixed.clone();
+                }
+            }
+        }
+        if operation_override.timeout.is_some() {
+            self.timeout = operation_override.timeout;
+        }
+    }
+
+    /// Violates the specification of the operation on purpose, from time to time
+    fn make_invalid(&mut self, plan: &'a Plan) {
+        let mut rng = StdRng::seed_from_u64(self.seed.rotate_left(32));
+        if !rng.gen_bool(NEGATIVE_PROBABILITY) {
+            return;
+        }
+
+        let mut violations = Vec::new();
+        for parameter in plan.parameters.iter() {
+            match parameter.location {
+                Location::Query | Location::Header => {
+                    if parameter.required {
+                        violations.push(Violation::MissingParameter(&parameter.name))
+                    }
+                }
+                Location::Path => {}
+                Location::Cookie => continue,
+            }
+            if let Some(value) = parameter.near_misses.choose(&mut rng) {
+                violations.push(Violation::ParameterNotInEnum(
+                    &parameter.name,
+                    value.clone(),
+                ));
+            }
+        }
+        if let Some(body) = &plan.body {
+            if body.required && !self.body.is_empty() {
+                violations.push(Violation::MissingBody);
+            }
+            if let (true, Some(serde_json::Value::Object(_))) = (body.object, self.body.first()) {
+                for name in body.required_fields.iter() {
+                    violations.push(Violation::MissingField(name));
+                }
+                for property in body.properties.iter() {
+                    if let Some(value) = &property.wrong_type {
+                        violations.push(Violation::WrongType(&property.name, value.clone()));
+                    }
+                    if let Some(value) = property.near_misses.choose(&mut rng) {
+                        violations.push(Violation::NotInEnum(&property.name, value.clone()));
+                    }
+                }
+            }
+        }
+
+        let violation = match violations.choose(&mut rng) {
+            Some(violation) => violation,
+            None => return,
+        };
+        let description = match violation {
+            Violation::MissingParameter(name) => {
+                self.query_params.retain(|(param, _)| param != name);
+                self.headers
+                    .retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+                format!("required parameter `{}` is missing", name)
+            }
+            Violation::MissingBody => {
+                self.body.clear();
+                "required body is missing".to_string()
+            }
+            Violation::MissingField(name) => {
+                if let Some(serde_json::Value::Object(body)) = self.body.first_mut() {
+                    body.remove(*name);
+                }
+                format!("required field `{}` is missing", name)
+            }
+            Violation::WrongType(name, value) => {
+                if let Some(serde_json::Value::Object(body)) = self.body.first_mut() {
+                    body.insert(name.to_string(), value.clone());
+                }
+                format!("field `{}` has a wrong type", name)
+            }
+            Violation::NotInEnum(name, value) => {
+                if let Some(serde_json::Value::Object(body)) = self.body.first_mut() {
+                    body.insert(name.to_string(), value.clone());
+                }
+                format!("field `{}` is {}, not one of the enum values", name, value)
+            }
+            Violation::ParameterNotInEnum(name, value) => {
+                for (param, param_value) in self
+                    .path_params
+                    .iter_mut()
+                    .chain(self.query_params.iter_mut())
+                    .chain(self.headers.iter_mut())
+                {
+                    if param.eq_ignore_ascii_case(name) {
+                        *param_value = value.clone();
+                    }
+                }
+                format!(
+                    "parameter `{}` is {:?}, not one of the enum values",
+                    name, value
+                )
+            }
+        };
+        self.violation = Some(description);
+    }
+
     pub fn for_all_methods(
         url: &'a Url,
         path: &'a str,
-        item: &'a PathItem,
+        plans: &'a Plans,
         extra_headers: &'a [(String, String)],
+        corpus: &'a Corpus,
+        negative: bool,
+        rng: &mut StdRng,
     ) -> Result<Vec<Payload<'a>>> {
         // TODO: Pass parameters to fuzz operation
-        let operations = vec![
-            ("GET", &item.get),
-            ("PUT", &item.put),
-            ("POST", &item.post),
-            ("DELETE", &item.delete),
-            ("OPTIONS", &item.options),
-            ("HEAD", &item.head),
-            ("PATCH", &item.patch),
-            ("TRACE", &item.trace),
-        ];
-
         let mut payloads = Vec::new();
-        for (method, op) in operations {
-            if let Some(operation) = op {
-                payloads.push(Payload::new(url, method, path, operation, extra_headers)?)
+        for (method, plan) in plans.of_path(path) {
+            let seeds = corpus.seeds(method, path);
+            let mut payload =
+                Payload::new(url, method, path, plan, extra_headers, seeds, rng.gen())?;
+            if negative {
+                payload.negative = true;
+                payload.make_invalid(plan);
             }
+            payloads.push(payload)
         }
 
         Ok(payloads)
     }
 
-    pub fn to_curl(&self) -> Result<String> {
-        let mut curl_command = format!("curl -X {} ", self.method);
-        if !self.body.is_empty() {
-            write!(
-                &mut curl_command,
-                "-d '{}' ",
-                serde_json::to_string(&self.body[0]).context("unable to serialize json")?
-            )?;
-        }
-        for (name, value) in &self.headers {
-            write!(&mut curl_command, "-H '{}:{}' ", name, value)?;
+    fn url_with_path_params(&self, base: &Url) -> Result<Url> {
+        if let Some(endpoint) = &self.endpoint {
+            return Ok(base.join(endpoint.trim_start_matches('/'))?);
         }
-
         let mut path_with_params = self.path.to_owned();
         for (name, value) in self.path_params.iter() {
             path_with_params = path_with_params.replace(&format!("{{{}}}", name), value);
         }
+        Ok(base.join(path_with_params.trim_start_matches('/'))?)
+    }
 
-        Ok(curl_command
-            + self
-                .url
-                .join(path_with_params.trim_start_matches('/'))?
-                .as_str())
+    /// Gives the allocations of the generated bodies back to the pools once the payload is sent
+    pub fn recycle(self) {
+        self.body.into_iter().for_each(pool::recycle);
+    }
+
+    pub fn to_request(&self) -> Result<Request> {
+        self.to_request_with_base(self.url)
+    }
+
+    /// Request to the same endpoint of another deployment of the API
+    pub fn to_request_with_base(&self, base: &Url) -> Result<Request> {
+        let mut url = self.url_with_path_params(base)?;
+        if !self.query_params.is_empty() {
+            url.query_pairs_mut().extend_pairs(self.query_params.iter());
+        }
+
+        Ok(Request {
+            method: self.method.to_string(),
+            url,
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+            body: self.body.first().map(pool::copy),
+            raw_body: self.raw_body.clone(),
+            timeout: self.timeout,
+            stream: None,
+        })
+    }
+
+    pub fn to_curl(&self) -> Result<String> {
+        Ok(self.to_request()?.to_curl())
     }
 }
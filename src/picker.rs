@@ -0,0 +1,229 @@
+use std::io;
+
+use anyhow::Result;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent},
+    execute, terminal,
+};
+use openapiv3::OpenAPI;
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, List, ListItem, ListState, Paragraph},
+    Terminal,
+};
+
+use crate::list::{operation_infos, OperationInfo};
+
+struct Entry {
+    method: String,
+    path: String,
+    /// Lowercased method, path, operation id and tags, matched against the search
+    haystack: String,
+    label: String,
+    selected: bool,
+}
+
+impl Entry {
+    fn new(info: &OperationInfo) -> Entry {
+        let mut label = format!("{:7} {}", info.method, info.path);
+        if let Some(operation_id) = info.operation_id {
+            label += &format!("  ({})", operation_id);
+        }
+        if !info.tags.is_empty() {
+            label += &format!("  #{}", info.tags.join(" #"));
+        }
+        Entry {
+            method: info.method.to_string(),
+            path: info.path.to_string(),
+            haystack: label.to_lowercase(),
+            label,
+            selected: true,
+        }
+    }
+}
+
+struct Picker {
+    entries: Vec<Entry>,
+    query: String,
+    searching: bool,
+    state: ListState,
+}
+
+impl Picker {
+    /// Indices of the entries matching the search
+    fn visible(&self) -> Vec<usize> {
+        let query = self.query.to_lowercase();
+        (0..self.entries.len())
+            .filter(|&i| self.entries[i].haystack.contains(&query))
+            .collect()
+    }
+
+    fn current(&self) -> Option<usize> {
+        let visible = self.visible();
+        self.state.selected().and_then(|i| visible.get(i).copied())
+    }
+
+    fn clamp(&mut self) {
+        let len = self.visible().len();
+        let selected = match (len, self.state.selected()) {
+            (0, _) => None,
+            (_, Some(i)) => Some(i.min(len - 1)),
+            (_, None) => Some(0),
+        };
+        self.state.select(selected);
+    }
+
+    fn step(&mut self, forward: bool) {
+        let len = self.visible().len();
+        if len == 0 {
+            return;
+        }
+        let i = self.state.selected().unwrap_or(0);
+        self.state.select(Some(match forward {
+            true => (i + 1) % len,
+            false => (i + len - 1) % len,
+        }));
+    }
+
+    /// Selects all matching entries, or deselects them when they are all selected already
+    fn toggle_visible(&mut self) {
+        let visible = self.visible();
+        let select = !visible.iter().all(|&i| self.entries[i].selected);
+        for i in visible {
+            self.entries[i].selected = select;
+        }
+    }
+
+    fn draw(&mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+        let visible = self.visible();
+        let selected = self.entries.iter().filter(|e| e.selected).count();
+        let items = visible
+            .iter()
+            .map(|&i| {
+                let entry = &self.entries[i];
+                let mark = match entry.selected {
+                    true => "[x] ",
+                    false => "[ ] ",
+                };
+                ListItem::new(format!("{}{}", mark, entry.label))
+            })
+            .collect::<Vec<_>>();
+        let title = format!(
+            "Operations ({} of {} selected, {} shown)",
+            selected,
+            self.entries.len(),
+            visible.len()
+        );
+        let search = match self.searching {
+            true => format!("/{}_", self.query),
+            false if self.query.is_empty() => String::new(),
+            false => format!("/{}", self.query),
+        };
+        let help = Spans::from(vec![
+            Span::styled(search, Style::default().fg(Color::Yellow)),
+            Span::raw(match self.searching {
+                true => {
+                    "  type to search by path, method, operation id or tag, enter/esc to finish"
+                }
+                false => "  ↑/↓ move  space toggle  a toggle shown  / search  enter start  q quit",
+            }),
+        ]);
+
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(1)].as_ref())
+                .split(f.size());
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Rounded)
+                        .title(title),
+                )
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+            f.render_stateful_widget(list, chunks[0], &mut self.state);
+            f.render_widget(Paragraph::new(help), chunks[1]);
+        })?;
+        Ok(())
+    }
+
+    /// Handles the key, returns whether the picking is over and the selection is confirmed
+    fn key(&mut self, key: KeyEvent) -> Option<bool> {
+        if self.searching {
+            match key.code {
+                KeyCode::Char(c) => self.query.push(c),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                }
+                KeyCode::Enter | KeyCode::Esc => self.searching = false,
+                _ => {}
+            }
+            self.clamp();
+            return None;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(false),
+            KeyCode::Enter => return Some(true),
+            KeyCode::Down | KeyCode::Char('j') => self.step(true),
+            KeyCode::Up | KeyCode::Char('k') => self.step(false),
+            KeyCode::Home => self.state.select(Some(0)),
+            KeyCode::End => self.state.select(self.visible().len().checked_sub(1)),
+            KeyCode::Char(' ') => {
+                if let Some(i) = self.current() {
+                    self.entries[i].selected = !self.entries[i].selected;
+                }
+            }
+            KeyCode::Char('a') => self.toggle_visible(),
+            KeyCode::Char('/') => self.searching = true,
+            _ => {}
+        }
+        self.clamp();
+        None
+    }
+}
+
+/// Lets the user choose the operations to fuzz, returns the methods and paths of the chosen
+/// operations or `None` when the user quits
+pub fn pick(schema: &OpenAPI) -> Result<Option<Vec<(String, String)>>> {
+    let mut picker = Picker {
+        entries: operation_infos(schema).iter().map(Entry::new).collect(),
+        query: String::new(),
+        searching: false,
+        state: ListState::default(),
+    };
+    picker.clamp();
+
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, terminal::EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+    let confirmed = loop {
+        if let Err(e) = picker.draw(&mut terminal) {
+            break Err(e);
+        }
+        match event::read() {
+            Ok(Event::Key(key)) => {
+                if let Some(confirmed) = picker.key(key) {
+                    break Ok(confirmed);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => break Err(e.into()),
+        }
+    };
+    execute!(terminal.backend_mut(), terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    Ok(confirmed?.then(|| {
+        picker
+            .entries
+            .into_iter()
+            .filter(|entry| entry.selected)
+            .map(|entry| (entry.method, entry.path))
+            .collect()
+    }))
+}
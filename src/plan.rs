@@ -0,0 +1,526 @@
+//! Generator plans of the operations, compiled once at startup: the schemas of parameters and
+//! bodies are walked into trees of generators with the strategies for their formats and their
+//! enum values chosen, and the ways to violate them found, instead of walking the schemas for
+//! each request. Generating from a plan consumes the random data in the same order as walking
+//! the schema, so payloads are generated again from their seeds as before.
+
+use std::{collections::BTreeMap, time::Instant};
+
+use anyhow::{Error, Result};
+use arbitrary::{Arbitrary, Unstructured};
+use openapi_utils::ReferenceOrExt;
+use openapiv3::{
+    OpenAPI, Operation, Parameter, ParameterData, ParameterSchemaOrContent, ReferenceOr, Responses,
+    Schema, SchemaKind, StringFormat, StringType, Type, VariantOrUnknownOrEmpty,
+};
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::binary;
+use crate::idempotency;
+use crate::payload::operations;
+use crate::pool;
+use crate::smart;
+use crate::temporal;
+
+/// How the strings of a schema are generated
+#[derive(Debug, Clone, Copy)]
+enum Strings {
+    Arbitrary,
+    /// Base64 of random bytes, for the `byte` format
+    Base64,
+    /// Raw bytes, for the `binary` format
+    Binary,
+    /// Mostly a date or time relative to now or on a boundary, for temporal formats
+    Temporal {
+        date_only: bool,
+    },
+    /// Mostly a realistic value of the kind recognized by the name or format of the field, if
+    /// it has one of the lengths of the schema
+    Smart {
+        kind: smart::Kind,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+    },
+}
+
+impl Strings {
+    /// Strategy for the strings of the field with the name, the names are not looked at
+    /// without `smart`
+    fn of(string_type: &StringType, name: &str, smart: bool) -> Strings {
+        if let Some(kind) = smart.then(|| smart::kind(name, string_type)).flatten() {
+            return Strings::Smart {
+                kind,
+                min_length: string_type.min_length,
+                max_length: string_type.max_length,
+            };
+        }
+        match string_type.format {
+            VariantOrUnknownOrEmpty::Item(StringFormat::Byte) => Strings::Base64,
+            VariantOrUnknownOrEmpty::Item(StringFormat::Binary) => Strings::Binary,
+            VariantOrUnknownOrEmpty::Item(StringFormat::Date) => {
+                Strings::Temporal { date_only: true }
+            }
+            VariantOrUnknownOrEmpty::Item(StringFormat::DateTime) => {
+                Strings::Temporal { date_only: false }
+            }
+            _ => Strings::Arbitrary,
+        }
+    }
+
+    fn generate(self, gen: &mut Unstructured) -> Result<String> {
+        match self {
+            Strings::Arbitrary => arbitrary_string(gen),
+            Strings::Base64 => Ok(binary::base64(gen)),
+            Strings::Binary => Ok(binary::binary_string(gen)),
+            Strings::Temporal { date_only } => match temporal::value(date_only, gen) {
+                Some(value) => Ok(value),
+                None => arbitrary_string(gen),
+            },
+            Strings::Smart {
+                kind,
+                min_length,
+                max_length,
+            } => match smart::value(kind, gen) {
+                Some(value) if smart::fits(&value, min_length, max_length) => Ok(value),
+                _ => arbitrary_string(gen),
+            },
+        }
+    }
+}
+
+/// Arbitrary string in a pooled allocation, the same one `String::arbitrary` would generate
+fn arbitrary_string(gen: &mut Unstructured) -> Result<String> {
+    Ok(pool::string(<&str>::arbitrary(gen)?))
+}
+
+/// Generator of the values of a schema
+#[derive(Debug)]
+enum Node {
+    /// One of the enum values, the first one when the random data is used up
+    Enum(Vec<Value>),
+    String(Strings),
+    Number,
+    Integer,
+    Boolean,
+    /// Any value, a string
+    Any,
+    Object(Vec<(String, Node)>),
+    Array {
+        items: Box<Node>,
+        min: usize,
+        max: usize,
+    },
+    OneOf(Vec<Node>),
+    AnyOf(Vec<Node>),
+    AllOf(Vec<Node>),
+}
+
+/// Enum values of the schema, empty without an enum
+fn enum_values(schema_type: &Type) -> Vec<Value> {
+    match schema_type {
+        Type::String(string) => string
+            .enumeration
+            .iter()
+            .cloned()
+            .map(Value::from)
+            .collect(),
+        Type::Integer(integer) => integer.enumeration.iter().map(|&v| v.into()).collect(),
+        Type::Number(number) => number.enumeration.iter().map(|&v| v.into()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl Node {
+    /// Generator of the schema of the field with the name, empty at the root of a body
+    fn compile(schema_kind: &SchemaKind, name: &str, smart: bool) -> Node {
+        let all = |schemas: &[ReferenceOr<Schema>]| {
+            schemas
+                .iter()
+                .map(|schema| Node::compile(&schema.to_item_ref().schema_kind, name, smart))
+                .collect()
+        };
+        match schema_kind {
+            SchemaKind::Any(_any) => Node::Any,
+            SchemaKind::Type(schema_type) => Node::compile_type(schema_type, name, smart),
+            SchemaKind::OneOf { one_of } => Node::OneOf(all(one_of)),
+            SchemaKind::AnyOf { any_of } => Node::AnyOf(all(any_of)),
+            SchemaKind::AllOf { all_of } => Node::AllOf(all(all_of)),
+        }
+    }
+
+    fn compile_type(schema_type: &Type, name: &str, smart: bool) -> Node {
+        let values = enum_values(schema_type);
+        if !values.is_empty() {
+            return Node::Enum(values);
+        }
+        match schema_type {
+            Type::String(string_type) => Node::String(Strings::of(string_type, name, smart)),
+            Type::Number(_number_type) => Node::Number,
+            Type::Integer(_integer_type) => Node::Integer,
+            Type::Boolean {} => Node::Boolean,
+            Type::Object(object) => Node::Object(
+                object
+                    .properties
+                    .iter()
+                    .map(|(name, schema)| {
+                        (
+                            name.clone(),
+                            Node::compile(&schema.to_item_ref().schema_kind, name, smart),
+                        )
+                    })
+                    .collect(),
+            ),
+            Type::Array(array) => Node::Array {
+                items: Box::new(Node::compile(
+                    &array.items.to_item_ref().schema_kind,
+                    name,
+                    smart,
+                )),
+                min: array.min_items.unwrap_or(1),
+                max: array.max_items.unwrap_or(10),
+            },
+        }
+    }
+
+    fn all(nodes: &[Node], gen: &mut Unstructured) -> Result<Vec<Value>> {
+        let mut values = pool::array(nodes.len());
+        for node in nodes {
+            values.push(node.generate(gen)?);
+        }
+        Ok(values)
+    }
+
+    fn generate(&self, gen: &mut Unstructured) -> Result<Value> {
+        match self {
+            Node::Enum(values) => Ok(choose(values, gen).clone()),
+            Node::String(strings) => Ok(Value::String(strings.generate(gen)?)),
+            Node::Number => Ok(json!(f64::arbitrary(gen)?)),
+            Node::Integer => Ok(json!(i64::arbitrary(gen)?)),
+            Node::Boolean => Ok(json!(bool::arbitrary(gen)?)),
+            Node::Any => Ok(Value::String(arbitrary_string(gen)?)),
+            Node::Object(properties) => {
+                let mut object = serde_json::Map::new();
+                for (name, node) in properties {
+                    object.insert(pool::string(name), node.generate(gen)?);
+                }
+                Ok(Value::Object(object))
+            }
+            Node::Array { items, min, max } => {
+                let mut array = pool::array(max.saturating_sub(*min) + 1);
+                for _ in *min..=*max {
+                    array.push(items.generate(gen)?);
+                }
+                Ok(Value::Array(array))
+            }
+            Node::OneOf(nodes) => {
+                let mut values = Node::all(nodes, gen)?;
+                if values.is_empty() {
+                    return Err(Error::msg("unable to generate JSON"));
+                }
+                let i = gen.int_in_range(0..=values.len() - 1)?;
+                let value = values.swap_remove(i);
+                pool::recycle(Value::Array(values));
+                Ok(value)
+            }
+            Node::AnyOf(nodes) => {
+                let mut values = pool::array(nodes.len());
+                for value in Node::all(nodes, gen)? {
+                    match bool::arbitrary(gen)? {
+                        true => values.push(value),
+                        false => pool::recycle(value),
+                    }
+                }
+                values.truncate(5);
+                Ok(values.into())
+            }
+            Node::AllOf(nodes) => Ok(Node::all(nodes, gen)?.into()),
+        }
+    }
+}
+
+/// One of the values, the first one when the random data is used up
+fn choose<'v, T>(values: &'v [T], gen: &mut Unstructured) -> &'v T {
+    gen.choose(values).unwrap_or(&values[0])
+}
+
+/// Value of a different type than the schema
+fn wrong_type(schema: &Schema) -> Option<Value> {
+    match &schema.schema_kind {
+        SchemaKind::Type(Type::String(_)) => Some(json!(12345)),
+        SchemaKind::Type(Type::Integer(_)) | SchemaKind::Type(Type::Number(_)) => {
+            Some(json!("not a number"))
+        }
+        SchemaKind::Type(Type::Boolean {}) => Some(json!("not a boolean")),
+        SchemaKind::Type(Type::Object(_)) | SchemaKind::Type(Type::Array(_)) => Some(json!(true)),
+        _ => None,
+    }
+}
+
+/// Values close to but not in the enum of the schema: other cases, surrounding whitespace and
+/// adjacent integers, empty without an enum
+fn near_misses(schema_type: &Type) -> Vec<Value> {
+    match schema_type {
+        Type::String(string) => string
+            .enumeration
+            .iter()
+            .flat_map(|value| {
+                let mut chars = value.chars();
+                let swapped = chars.next().map(|first| match first.is_uppercase() {
+                    true => first.to_lowercase().chain(chars).collect(),
+                    false => first.to_uppercase().chain(chars).collect(),
+                });
+                vec![
+                    Some(value.to_uppercase()),
+                    Some(value.to_lowercase()),
+                    swapped,
+                    Some(format!("{} ", value)),
+                    Some(format!(" {}", value)),
+                ]
+            })
+            .flatten()
+            .filter(|value| !string.enumeration.contains(value))
+            .map(Value::from)
+            .collect(),
+        Type::Integer(integer) => integer
+            .enumeration
+            .iter()
+            .flat_map(|value| vec![value.checked_sub(1), value.checked_add(1)])
+            .flatten()
+            .filter(|value| !integer.enumeration.contains(value))
+            .map(Value::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Type of the schema of the parameter, if it has one
+fn param_type(parameter: &ParameterData) -> Option<&Type> {
+    match &parameter.format {
+        ParameterSchemaOrContent::Schema(ReferenceOr::Item(schema)) => match &schema.schema_kind {
+            SchemaKind::Type(schema_type) => Some(schema_type),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Location {
+    Query,
+    Path,
+    Header,
+    Cookie,
+}
+
+/// Generator of the value of a parameter
+#[derive(Debug)]
+pub struct ParameterPlan {
+    pub location: Location,
+    pub name: String,
+    /// Name under which seeds of the parameter are stored, lowercase for headers
+    pub seed_name: String,
+    pub required: bool,
+    node: Node,
+    /// Values close to the enum values, to violate the schema on purpose
+    pub near_misses: Vec<String>,
+}
+
+impl ParameterPlan {
+    fn compile(parameter: &Parameter, smart: bool) -> ParameterPlan {
+        let (location, parameter_data) = match parameter {
+            Parameter::Query { parameter_data, .. } => (Location::Query, parameter_data),
+            Parameter::Path { parameter_data, .. } => (Location::Path, parameter_data),
+            Parameter::Header { parameter_data, .. } => (Location::Header, parameter_data),
+            Parameter::Cookie { parameter_data, .. } => (Location::Cookie, parameter_data),
+        };
+        let schema_type = param_type(parameter_data).filter(|_| location != Location::Cookie);
+        let node = match schema_type {
+            Some(schema_type) => match Node::compile_type(schema_type, &parameter_data.name, smart)
+            {
+                Node::Enum(values) => Node::Enum(values),
+                Node::String(strings) => Node::String(strings),
+                _ => Node::Any,
+            },
+            None => Node::Any,
+        };
+        let near_misses = schema_type
+            .map(near_misses)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|value| match value {
+                Value::String(value) => value,
+                value => value.to_string(),
+            })
+            .collect();
+        ParameterPlan {
+            location,
+            name: parameter_data.name.clone(),
+            seed_name: match location {
+                Location::Header => parameter_data.name.to_lowercase(),
+                _ => parameter_data.name.clone(),
+            },
+            required: parameter_data.required,
+            node,
+            near_misses,
+        }
+    }
+
+    /// Random value of the parameter
+    pub fn generate(&self, gen: &mut Unstructured) -> Result<String> {
+        match &self.node {
+            Node::Enum(values) => match choose(values, gen) {
+                Value::String(value) => Ok(value.clone()),
+                value => Ok(value.to_string()),
+            },
+            Node::String(strings) => strings.generate(gen),
+            _ => arbitrary_string(gen),
+        }
+    }
+}
+
+/// Property of an object body with the ways to violate its schema
+#[derive(Debug)]
+pub struct PropertyPlan {
+    pub name: String,
+    pub wrong_type: Option<Value>,
+    pub near_misses: Vec<Value>,
+}
+
+/// Generators of the request body
+#[derive(Debug)]
+pub struct BodyPlan {
+    /// JSON bodies of the media types, in their order
+    nodes: Vec<Node>,
+    /// Binary media type the body is sent as when there is no JSON one
+    pub binary: Option<String>,
+    pub required: bool,
+    /// Required fields of the body, if it is an object
+    pub required_fields: Vec<String>,
+    /// Properties of the body, if it is an object
+    pub properties: Vec<PropertyPlan>,
+    pub object: bool,
+}
+
+impl BodyPlan {
+    fn compile(operation: &Operation, smart: bool) -> Option<BodyPlan> {
+        let request_body = operation.request_body.as_ref()?.to_item_ref();
+        let schemas = request_body
+            .content
+            .iter()
+            .filter(|(content, _)| content.contains("json"))
+            .filter_map(|(_, media)| media.schema.as_ref())
+            .map(|schema| schema.to_item_ref())
+            .collect::<Vec<_>>();
+        let object = match schemas.first().map(|schema| &schema.schema_kind) {
+            Some(SchemaKind::Type(Type::Object(object))) => Some(object),
+            _ => None,
+        };
+        Some(BodyPlan {
+            nodes: schemas
+                .iter()
+                .map(|schema| Node::compile(&schema.schema_kind, "", smart))
+                .collect(),
+            binary: request_body
+                .content
+                .keys()
+                .find(|content| binary::is_binary(content))
+                .cloned(),
+            required: request_body.required,
+            required_fields: object.map(|o| o.required.clone()).unwrap_or_default(),
+            properties: object
+                .map(|object| {
+                    object
+                        .properties
+                        .iter()
+                        .map(|(name, schema)| {
+                            let schema = schema.to_item_ref();
+                            PropertyPlan {
+                                name: name.clone(),
+                                wrong_type: wrong_type(schema),
+                                near_misses: match &schema.schema_kind {
+                                    SchemaKind::Type(schema_type) => near_misses(schema_type),
+                                    _ => Vec::new(),
+                                },
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            object: object.is_some(),
+        })
+    }
+
+    /// Random JSON bodies, one for each media type
+    pub fn generate(&self, gen: &mut Unstructured) -> Result<Vec<Value>> {
+        Node::all(&self.nodes, gen)
+    }
+}
+
+/// Generators of the parameters and body of an operation
+#[derive(Debug)]
+pub struct Plan {
+    pub operation_id: Option<String>,
+    pub responses: Responses,
+    pub idempotent: bool,
+    pub parameters: Vec<ParameterPlan>,
+    pub body: Option<BodyPlan>,
+}
+
+impl Plan {
+    /// Plan of the operation, with realistic strings of fields recognized by their names if
+    /// `smart`
+    pub fn compile(method: &str, operation: &Operation, smart: bool) -> Plan {
+        Plan {
+            operation_id: operation.operation_id.clone(),
+            responses: operation.responses.clone(),
+            idempotent: idempotency::is_idempotent(method, operation),
+            parameters: operation
+                .parameters
+                .iter()
+                .map(|parameter| ParameterPlan::compile(parameter.to_item_ref(), smart))
+                .collect(),
+            body: BodyPlan::compile(operation, smart),
+        }
+    }
+}
+
+/// Plans of all operations of the specification, by path and method
+#[derive(Debug, Default)]
+pub struct Plans(BTreeMap<String, Vec<(&'static str, Plan)>>);
+
+impl Plans {
+    pub fn new(schema: &OpenAPI, smart: bool) -> Plans {
+        let start = Instant::now();
+        let mut plans = BTreeMap::new();
+        for (path, ref_or_item) in schema.paths.iter() {
+            let compiled = operations(ref_or_item.to_item_ref())
+                .into_iter()
+                .filter_map(|(method, operation)| {
+                    operation
+                        .as_ref()
+                        .map(|operation| (method, Plan::compile(method, operation, smart)))
+                })
+                .collect::<Vec<_>>();
+            plans.insert(path.clone(), compiled);
+        }
+        debug!(
+            paths = plans.len(),
+            elapsed_ms = start.elapsed().as_millis() as u64,
+            "compiled generator plans"
+        );
+        Plans(plans)
+    }
+
+    /// Plans of the operations of the path with their methods
+    pub fn of_path(&self, path: &str) -> &[(&'static str, Plan)] {
+        self.0.get(path).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn get(&self, method: &str, path: &str) -> Option<&Plan> {
+        self.of_path(path)
+            .iter()
+            .find(|(m, _)| *m == method)
+            .map(|(_, plan)| plan)
+    }
+}
@@ -0,0 +1,112 @@
+//! Pools of the allocations of payload generation: strings and arrays of generated JSON bodies
+//! go back to a free list of the thread once the request is done and bodies are serialized into
+//! a reused buffer, so that allocator pressure stays flat during campaigns of many hours
+
+use std::{cell::RefCell, mem};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+/// Allocations kept in each free list, the others are freed
+const MAX_POOLED: usize = 4096;
+
+/// Larger strings and arrays are freed instead of pooled, they are rare and would keep memory
+const MAX_POOLED_CAPACITY: usize = 64 * 1024;
+
+/// Larger buffers are shrunk after use, e.g. after a huge body
+const MAX_BUFFER_CAPACITY: usize = 1024 * 1024;
+
+thread_local! {
+    static STRINGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static ARRAYS: RefCell<Vec<Vec<Value>>> = const { RefCell::new(Vec::new()) };
+    static BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Copy of the text in a pooled string
+pub fn string(text: &str) -> String {
+    let mut string = STRINGS
+        .with(|strings| strings.borrow_mut().pop())
+        .unwrap_or_default();
+    string.push_str(text);
+    string
+}
+
+/// Empty pooled array
+pub fn array(capacity: usize) -> Vec<Value> {
+    let mut array = ARRAYS
+        .with(|arrays| arrays.borrow_mut().pop())
+        .unwrap_or_default();
+    array.reserve(capacity);
+    array
+}
+
+fn recycle_string(mut string: String) {
+    if string.capacity() <= MAX_POOLED_CAPACITY {
+        string.clear();
+        STRINGS.with(|strings| {
+            let mut strings = strings.borrow_mut();
+            if strings.len() < MAX_POOLED {
+                strings.push(string);
+            }
+        });
+    }
+}
+
+/// Gives the strings and arrays of the value back to the pools
+pub fn recycle(value: Value) {
+    match value {
+        Value::String(string) => recycle_string(string),
+        Value::Array(mut values) => {
+            values.drain(..).for_each(recycle);
+            if values.capacity() <= MAX_POOLED_CAPACITY {
+                ARRAYS.with(|arrays| {
+                    let mut arrays = arrays.borrow_mut();
+                    if arrays.len() < MAX_POOLED {
+                        arrays.push(values);
+                    }
+                });
+            }
+        }
+        Value::Object(object) => {
+            for (key, value) in object {
+                recycle_string(key);
+                recycle(value);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Deep copy of the value in pooled allocations
+pub fn copy(value: &Value) -> Value {
+    match value {
+        Value::String(text) => Value::String(string(text)),
+        Value::Array(values) => {
+            let mut array = array(values.len());
+            array.extend(values.iter().map(copy));
+            Value::Array(array)
+        }
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| (string(key), copy(value)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+/// Calls the function with the value serialized as JSON into the buffer of the thread
+pub fn serialized<R>(value: &Value, f: impl FnOnce(&[u8]) -> R) -> Result<R> {
+    // Taken out of the cell, the function may serialize another value
+    let mut buffer = BUFFER.with(|buffer| mem::take(&mut *buffer.borrow_mut()));
+    buffer.clear();
+    let result = serde_json::to_writer(&mut buffer, value)
+        .context("unable to serialize json")
+        .map(|_| f(&buffer));
+    if buffer.capacity() > MAX_BUFFER_CAPACITY {
+        buffer = Vec::new();
+    }
+    BUFFER.with(|cell| *cell.borrow_mut() = buffer);
+    result
+}
@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use openapiv3::OpenAPI;
+use serde_json::Value;
+use url::Url;
+
+use crate::corpus::{self, Corpus, Seed};
+
+/// Variables from the collection and environment used to resolve `{{name}}` references
+#[derive(Debug, Default)]
+struct Variables(BTreeMap<String, String>);
+
+impl Variables {
+    fn extend(&mut self, variables: &Value) {
+        for variable in variables.as_array().into_iter().flatten() {
+            if variable["enabled"] == Value::Bool(false) {
+                continue;
+            }
+            if let Some(key) = variable["key"].as_str() {
+                self.0
+                    .insert(key.to_string(), to_string(&variable["value"]));
+            }
+        }
+    }
+
+    fn resolve(&self, s: &str) -> String {
+        let mut resolved = s.to_string();
+        for (key, value) in self.0.iter() {
+            resolved = resolved.replace(&format!("{{{{{}}}}}", key), value);
+        }
+        resolved
+    }
+}
+
+fn to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+fn read_json(file: &Path) -> Result<Value> {
+    let content = fs::read_to_string(file).context(format!("unable to read {}", file.display()))?;
+    serde_json::from_str(&content).context(format!("unable to parse {}", file.display()))
+}
+
+/// Returns the path with Postman path variables (`:name`) substituted and query parameters
+fn request_path(url: &Value, variables: &Variables) -> (String, BTreeMap<String, String>) {
+    let mut path_variables = Variables::default();
+    path_variables.extend(&url["variable"]);
+
+    if let Some(segments) = url["path"].as_array() {
+        let path = segments
+            .iter()
+            .map(|segment| {
+                let segment = variables.resolve(&to_string(segment));
+                match segment.strip_prefix(':') {
+                    Some(name) => path_variables.0.get(name).cloned().unwrap_or(segment),
+                    None => segment,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        let query = url["query"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|param| param["disabled"] != Value::Bool(true))
+            .filter_map(|param| {
+                let key = param["key"].as_str()?;
+                Some((
+                    variables.resolve(key),
+                    variables.resolve(&to_string(&param["value"])),
+                ))
+            })
+            .collect();
+        return (path, query);
+    }
+
+    // URL is a plain string, the base part may be unresolved variable e.g. `{{baseUrl}}/items`
+    let raw = variables.resolve(url.as_str().or_else(|| url["raw"].as_str()).unwrap_or(""));
+    match Url::parse(&raw) {
+        Ok(url) => (
+            url.path().to_string(),
+            url.query_pairs().into_owned().collect(),
+        ),
+        Err(_) => {
+            let raw = raw.trim_start_matches(|c| c != '/');
+            let (path, query) = raw.split_once('?').unwrap_or((raw, ""));
+            let query = url::form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect();
+            (path.to_string(), query)
+        }
+    }
+}
+
+fn import_items(
+    items: &Value,
+    variables: &Variables,
+    schema: &OpenAPI,
+    corpus: &mut Corpus,
+) -> usize {
+    let mut imported = 0;
+    for item in items.as_array().into_iter().flatten() {
+        // Folders contain nested items
+        if item["item"].is_array() {
+            imported += import_items(&item["item"], variables, schema, corpus);
+            continue;
+        }
+
+        let request = &item["request"];
+        let method = request["method"].as_str().unwrap_or("GET");
+        let (path, query_params) = request_path(&request["url"], variables);
+        let (template, path_params) = match corpus::match_operation(schema, method, &path) {
+            Some(operation) => operation,
+            None => continue,
+        };
+
+        let headers = request["header"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|header| header["disabled"] != Value::Bool(true))
+            .filter_map(|header| {
+                let key = header["key"].as_str()?;
+                Some((
+                    key.to_lowercase(),
+                    variables.resolve(&to_string(&header["value"])),
+                ))
+            })
+            .collect();
+        let body = match request["body"]["mode"].as_str() {
+            Some("raw") => request["body"]["raw"]
+                .as_str()
+                .and_then(|raw| serde_json::from_str(&variables.resolve(raw)).ok()),
+            _ => None,
+        };
+
+        corpus.add(
+            method,
+            &template,
+            Seed {
+                path_params,
+                query_params,
+                headers,
+                body,
+            },
+        );
+        imported += 1;
+    }
+    imported
+}
+
+/// Adds requests from a Postman collection (v2.0 or v2.1) matching operations of the
+/// specification to the corpus and returns their number
+pub fn import(
+    schema: &OpenAPI,
+    collection: &Path,
+    environment: Option<&Path>,
+    corpus: &mut Corpus,
+) -> Result<usize> {
+    let collection = read_json(collection)?;
+    let mut variables = Variables::default();
+    variables.extend(&collection["variable"]);
+    if let Some(environment) = environment {
+        variables.extend(&read_json(environment)?["values"]);
+    }
+
+    Ok(import_items(
+        &collection["item"],
+        &variables,
+        schema,
+        corpus,
+    ))
+}
@@ -0,0 +1,321 @@
+//! Conditional and range requests: `If-Match`, `If-None-Match`, `If-Modified-Since`,
+//! `If-Unmodified-Since` and `Range` headers with the validators harvested from earlier
+//! responses and with malformed values. Writes succeeding with a stale validator ignore their
+//! preconditions, and error responses or reflected unkeyed headers cacheable by shared caches
+//! indicate cache poisoning.
+
+use url::Url;
+
+use crate::transport::{Request, Response};
+
+/// Validator no resource has, sent as the stale ETag of writes
+const STALE_ETAG: &str = "\"openapi-fuzzer-stale\"";
+
+/// Date every resource was modified after
+const EPOCH_DATE: &str = "Thu, 01 Jan 1970 00:00:00 GMT";
+
+/// Host sent in unkeyed headers, a cached response reflecting it poisons the cache
+const CANARY_HOST: &str = "openapi-fuzzer-canary.example";
+
+/// Headers caches usually leave out of their keys although applications use them
+const UNKEYED_HEADERS: [&str; 2] = ["X-Forwarded-Host", "X-Host"];
+
+/// Malformed preconditions and ranges, which should be ignored or rejected
+const MALFORMED: &[(&str, &str)] = &[
+    ("If-None-Match", "\"unterminated"),
+    ("If-Match", ",,W/,\"\"\""),
+    ("If-Modified-Since", "Thu, 31 Feb 99999 25:61:61 GMT"),
+    ("If-Unmodified-Since", "-1"),
+    ("Range", "bytes=-"),
+    ("Range", "bytes=18446744073709551615-18446744073709551616"),
+    ("Range", "bytes=10-0"),
+    ("Range", "lines=0-1"),
+];
+
+/// Number of overlapping ranges of a single range request
+const OVERLAPPING_RANGES: usize = 200;
+
+/// Validators of a resource from its responses
+#[derive(Debug, Clone, Default)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// Validators of the successful response, `None` without any
+    pub fn of(response: &Response) -> Option<Validators> {
+        if response.status / 100 != 2 {
+            return None;
+        }
+        let validators = Validators {
+            etag: header(response, "etag").map(str::to_string),
+            last_modified: header(response, "last-modified").map(str::to_string),
+        };
+        match (&validators.etag, &validators.last_modified) {
+            (None, None) => None,
+            _ => Some(validators),
+        }
+    }
+}
+
+/// What the API should do with a probe
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Expectation {
+    /// Ignore or reject the header, but not fail
+    NoServerError,
+    /// Fail the precondition of the write
+    PreconditionFailed,
+    /// Leave the unkeyed header out of cacheable responses
+    Unreflected,
+}
+
+/// Request with conditional or range headers
+pub struct Probe {
+    /// Headers of the probe, e.g. `If-Match: "openapi-fuzzer-stale"`
+    pub name: String,
+    pub request: Request,
+    expectation: Expectation,
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response
+        .headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn is_write(method: &str) -> bool {
+    matches!(method, "PUT" | "PATCH" | "DELETE" | "POST")
+}
+
+fn probe(request: &Request, headers: &[(&str, &str)], expectation: Expectation) -> Probe {
+    let mut probed = request.clone();
+    probed.headers.retain(|(name, _)| {
+        !headers
+            .iter()
+            .any(|(header, _)| name.eq_ignore_ascii_case(header))
+    });
+    probed.headers.extend(
+        headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string())),
+    );
+    Probe {
+        name: headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect::<Vec<_>>()
+            .join(", "),
+        request: probed,
+        expectation,
+    }
+}
+
+/// Url with a query parameter making it a new cache key, so probes poison no shared entry
+fn cache_busted(url: &Url) -> Url {
+    let mut url = url.clone();
+    url.query_pairs_mut()
+        .append_pair("openapi-fuzzer-cb", &format!("{:x}", rand::random::<u32>()));
+    url
+}
+
+/// Probes of the successful request with the validators of its resource, writes are only
+/// probed with validators to be stale against. Creations are not probed, what they create has
+/// no validators yet.
+pub fn probes(request: &Request, validators: Option<&Validators>) -> Vec<Probe> {
+    let mut probes = Vec::new();
+    if request.method == "POST" {
+        return probes;
+    }
+    if is_write(&request.method) {
+        let validators = match validators {
+            Some(validators) => validators,
+            None => return probes,
+        };
+        if validators.etag.is_some() {
+            probes.push(probe(
+                request,
+                &[("If-Match", STALE_ETAG)],
+                Expectation::PreconditionFailed,
+            ));
+        }
+        if validators.last_modified.is_some() {
+            probes.push(probe(
+                request,
+                &[("If-Unmodified-Since", EPOCH_DATE)],
+                Expectation::PreconditionFailed,
+            ));
+        }
+        return probes;
+    }
+
+    let mut read = request.clone();
+    read.url = cache_busted(&request.url);
+    for (name, value) in MALFORMED.iter() {
+        probes.push(probe(&read, &[(name, value)], Expectation::NoServerError));
+    }
+    let overlapping = vec!["0-"; OVERLAPPING_RANGES].join(",");
+    probes.push(probe(
+        &read,
+        &[("Range", &format!("bytes={}", overlapping))],
+        Expectation::NoServerError,
+    ));
+    probes.push(probe(
+        &read,
+        &[("If-Range", "\"unterminated"), ("Range", "bytes=0-0")],
+        Expectation::NoServerError,
+    ));
+    if let Some(etag) = validators.and_then(|v| v.etag.as_deref()) {
+        probes.push(probe(
+            &read,
+            &[("If-None-Match", etag)],
+            Expectation::NoServerError,
+        ));
+        probes.push(probe(
+            &read,
+            &[("If-Range", etag), ("Range", "bytes=0-0")],
+            Expectation::NoServerError,
+        ));
+    }
+    if let Some(date) = validators.and_then(|v| v.last_modified.as_deref()) {
+        probes.push(probe(
+            &read,
+            &[("If-Modified-Since", date)],
+            Expectation::NoServerError,
+        ));
+    }
+    for name in UNKEYED_HEADERS.iter() {
+        let mut unkeyed = request.clone();
+        unkeyed.url = cache_busted(&request.url);
+        probes.push(probe(
+            &unkeyed,
+            &[(name, CANARY_HOST)],
+            Expectation::Unreflected,
+        ));
+    }
+    probes
+}
+
+/// Whether shared caches may store the response
+fn cacheable(response: &Response) -> bool {
+    let cache_control = header(response, "cache-control")
+        .unwrap_or_default()
+        .to_lowercase();
+    let directives = cache_control
+        .split(',')
+        .map(|directive| directive.trim())
+        .collect::<Vec<_>>();
+    if directives
+        .iter()
+        .any(|d| matches!(*d, "no-store" | "private" | "no-cache"))
+    {
+        return false;
+    }
+    directives.iter().any(|directive| {
+        *directive == "public"
+            || directive
+                .strip_prefix("s-maxage=")
+                .is_some_and(|age| age != "0")
+            || directive
+                .strip_prefix("max-age=")
+                .is_some_and(|age| age != "0")
+    })
+}
+
+/// How the API failed the probe: a server error or a write ignoring its stale precondition
+pub fn violation(probe: &Probe, response: &Response) -> Option<String> {
+    match (probe.expectation, response.status / 100) {
+        (_, 5) => Some(format!("{} answered with {}", probe.name, response.status)),
+        (Expectation::PreconditionFailed, 2) => Some(format!(
+            "{} succeeded with {} although its precondition fails",
+            probe.request.method, probe.name
+        )),
+        _ => None,
+    }
+}
+
+/// Cache poisoning indicated by the response to the probe: the unkeyed header reflected or
+/// an error caused by the malformed header, in a response shared caches may store
+pub fn cache_poisoning(probe: &Probe, response: &Response) -> Option<String> {
+    if is_write(&probe.request.method) || !cacheable(response) {
+        return None;
+    }
+    match probe.expectation {
+        Expectation::Unreflected => {
+            let reflected = response.body.contains(CANARY_HOST)
+                || response
+                    .headers
+                    .iter()
+                    .any(|(_, value)| value.contains(CANARY_HOST));
+            reflected.then(|| {
+                format!(
+                    "{} reflected in a response cacheable by shared caches",
+                    probe.name
+                )
+            })
+        }
+        Expectation::NoServerError if response.status >= 400 && response.status != 416 => {
+            Some(format!(
+                "{} caused a {} cacheable by shared caches",
+                probe.name, response.status
+            ))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            url: Url::parse("http://localhost/items/1").unwrap(),
+            headers: vec![("If-Match".to_string(), "\"a\"".to_string())],
+            body: None,
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        }
+    }
+
+    #[test]
+    fn probes_writes_with_stale_validators() {
+        let validators = Validators {
+            etag: Some("\"a\"".to_string()),
+            last_modified: None,
+        };
+        assert!(probes(&request("POST"), Some(&validators)).is_empty());
+        assert!(probes(&request("PUT"), None).is_empty());
+        let probes = probes(&request("PUT"), Some(&validators));
+        assert_eq!(probes.len(), 1);
+        assert_eq!(
+            probes[0].request.headers,
+            vec![("If-Match".to_string(), STALE_ETAG.to_string())]
+        );
+    }
+
+    #[test]
+    fn reports_writes_ignoring_preconditions() {
+        let probe = probe(
+            &request("PUT"),
+            &[("If-Match", STALE_ETAG)],
+            Expectation::PreconditionFailed,
+        );
+        let mut response = Response {
+            status: 200,
+            status_text: String::new(),
+            http_version: String::new(),
+            headers: Vec::new(),
+            body: String::new(),
+            events: Vec::new(),
+            elapsed: Default::default(),
+        };
+        assert!(violation(&probe, &response).is_some());
+        response.status = 412;
+        assert!(violation(&probe, &response).is_none());
+    }
+}
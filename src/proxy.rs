@@ -0,0 +1,390 @@
+//! Forward proxy the connections to the API go through, e.g. the mandatory egress proxy of an
+//! enterprise network. Proxies requiring authentication get the Basic credentials of the url
+//! of the proxy, or the `Proxy-Authorization` values printed by a helper command, e.g. one
+//! wrapping `ntlm_auth` or a Kerberos library for NTLM and Negotiate. The clients connect to a
+//! local relay that opens and authenticates the tunnels through the proxy, or without a proxy
+//! the connections of `network` the default client can not make itself. The relay only tunnels
+//! for clients with the secret of the process, other local users can not use it.
+
+use std::{
+    io::{self, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    process::Command,
+    sync::{Arc, OnceLock},
+    thread,
+};
+
+use anyhow::{anyhow, Context, Result};
+use percent_encoding::percent_decode_str;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use crate::{network, tls};
+
+static CONFIGURED: OnceLock<ProxySettings> = OnceLock::new();
+
+/// Address of the relay the clients connect to and the secret they authenticate with
+static RELAY: OnceLock<(SocketAddr, String)> = OnceLock::new();
+
+/// User of the Basic credentials of the clients of the relay
+const RELAY_USER: &str = "openapi-fuzzer";
+
+/// Variable with the challenges of the proxy the helper command is run with
+const CHALLENGE_VAR: &str = "PROXY_CHALLENGE";
+
+/// Authorizations sent for a tunnel before giving up, NTLM takes two
+const MAX_ROUNDS: usize = 3;
+
+/// Longest head of a response of the proxy, or of a request to the relay
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ProxySettings {
+    /// `http://[user:password@]host:port` of the proxy
+    pub url: Url,
+    /// Run with `sh -c` to print the value of the `Proxy-Authorization` header, with the
+    /// challenges of the proxy in `PROXY_CHALLENGE`
+    pub auth_command: Option<String>,
+}
+
+/// Head of an HTTP message, read without the data after it
+fn read_head(stream: &mut TcpStream) -> Result<String> {
+    let mut head = Vec::new();
+    let mut byte = [0; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte)? == 0 {
+            return Err(anyhow!("connection closed before the end of the head"));
+        }
+        head.push(byte[0]);
+        if head.len() > MAX_HEAD_SIZE {
+            return Err(anyhow!("head longer than {} bytes", MAX_HEAD_SIZE));
+        }
+    }
+    Ok(String::from_utf8_lossy(&head).to_string())
+}
+
+/// Values of the header in the head
+fn header_values<'a>(head: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> {
+    head.lines().skip(1).filter_map(move |line| {
+        let (header, value) = line.split_once(':')?;
+        header
+            .trim()
+            .eq_ignore_ascii_case(name)
+            .then(|| value.trim())
+    })
+}
+
+/// Scheme of an authorization or challenge, e.g. `NTLM` of `NTLM TlRMTVNT...`
+fn scheme(value: &str) -> &str {
+    value.split_whitespace().next().unwrap_or_default()
+}
+
+impl ProxySettings {
+    fn address(&self) -> Result<(String, u16)> {
+        if self.url.scheme() != "http" {
+            return Err(anyhow!(
+                "unsupported proxy scheme `{}`, only http proxies are",
+                self.url.scheme()
+            ));
+        }
+        let host = self
+            .url
+            .host_str()
+            .ok_or_else(|| anyhow!("proxy url without host"))?;
+        Ok((host.to_string(), self.url.port().unwrap_or(8080)))
+    }
+
+    /// Basic credentials of the url of the proxy
+    fn basic(&self) -> Option<String> {
+        if self.url.username().is_empty() {
+            return None;
+        }
+        let decoded = |s: &str| percent_decode_str(s).decode_utf8_lossy().to_string();
+        let credentials = format!(
+            "{}:{}",
+            decoded(self.url.username()),
+            decoded(self.url.password().unwrap_or_default())
+        );
+        Some(format!("Basic {}", base64::encode(credentials)))
+    }
+
+    /// Value of `Proxy-Authorization` answering the challenges, printed by the helper command
+    fn helper(&self, command: &str, challenges: &str) -> Result<String> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env(CHALLENGE_VAR, challenges)
+            .output()
+            .context("unable to run the proxy authentication command")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "the proxy authentication command failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let authorization = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        match authorization.is_empty() {
+            true => Err(anyhow!("the proxy authentication command printed nothing")),
+            false => Ok(authorization),
+        }
+    }
+
+    /// Connection to the host tunneled through the proxy
+    fn tunnel(&self, host: &str, port: u16) -> Result<TcpStream> {
+        let address = self.address()?;
+        let connect = || {
+            network::dial(&address.0, address.1)
+                .with_context(|| format!("unable to connect to the proxy {}", self.url))
+        };
+        let mut stream = connect()?;
+        // Basic credentials are sent right away, the helper answers the challenges
+        let mut authorization = match self.auth_command {
+            Some(_) => None,
+            None => self.basic(),
+        };
+        for round in 0..=MAX_ROUNDS {
+            let mut request = format!(
+                "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n\
+                 User-Agent: openapi-fuzzer\r\nProxy-Connection: Keep-Alive\r\n",
+                host = host,
+                port = port
+            );
+            if let Some(authorization) = &authorization {
+                request += &format!("Proxy-Authorization: {}\r\n", authorization);
+            }
+            stream.write_all((request + "\r\n").as_bytes())?;
+            stream.flush()?;
+
+            let head = read_head(&mut stream).context("invalid response of the proxy")?;
+            let status = head.split_whitespace().nth(1).unwrap_or_default();
+            match status {
+                "200" => return Ok(stream),
+                "407" => {}
+                _ => {
+                    return Err(anyhow!(
+                        "the proxy refused the tunnel to {}:{}: {}",
+                        host,
+                        port,
+                        head.lines().next().unwrap_or_default()
+                    ))
+                }
+            }
+            let command = match &self.auth_command {
+                Some(_) if round == MAX_ROUNDS => break,
+                Some(command) => command,
+                None => {
+                    return Err(anyhow!(match authorization {
+                        Some(_) => "the proxy rejected the credentials of its url",
+                        None => "the proxy requires authentication: add credentials to its url or pass --proxy-auth-command",
+                    }))
+                }
+            };
+            // The helper gets the challenge of its scheme once it answered one, e.g. the
+            // second message of NTLM, and all of them before, one per line
+            let offered = header_values(&head, "proxy-authenticate").collect::<Vec<_>>();
+            let challenges = match &authorization {
+                Some(sent) => offered
+                    .iter()
+                    .find(|challenge| scheme(challenge).eq_ignore_ascii_case(scheme(sent)))
+                    .map(|challenge| challenge.to_string())
+                    .unwrap_or_default(),
+                None => offered.join("\n"),
+            };
+            debug!(%challenges, "proxy requires authentication");
+            authorization = Some(self.helper(command, &challenges)?);
+
+            // The body of the response is skipped, or a new connection is needed
+            let length = header_values(&head, "content-length")
+                .next()
+                .and_then(|length| length.parse::<u64>().ok());
+            let closes = header_values(&head, "connection")
+                .chain(header_values(&head, "proxy-connection"))
+                .any(|value| value.eq_ignore_ascii_case("close"));
+            match length {
+                Some(length) if !closes => {
+                    io::copy(&mut (&mut stream).take(length), &mut io::sink())?;
+                }
+                _ => stream = connect()?,
+            }
+        }
+        Err(anyhow!(
+            "the proxy did not accept the authorization after {} rounds",
+            MAX_ROUNDS
+        ))
+    }
+}
+
+/// Copies the data between the client and the tunnel until either side closes
+fn splice(client: TcpStream, tunnel: TcpStream) -> Result<()> {
+    let (mut client_reader, mut tunnel_writer) = (client.try_clone()?, tunnel.try_clone()?);
+    let upstream = thread::spawn(move || {
+        let _ = io::copy(&mut client_reader, &mut tunnel_writer);
+        let _ = tunnel_writer.shutdown(Shutdown::Write);
+    });
+    let (mut tunnel_reader, mut client_writer) = (tunnel, client);
+    let _ = io::copy(&mut tunnel_reader, &mut client_writer);
+    let _ = client_writer.shutdown(Shutdown::Write);
+    let _ = upstream.join();
+    Ok(())
+}
+
+/// Whether the head has the Basic credentials of the relay with the secret
+fn authorized(head: &str, secret: &str) -> bool {
+    let expected = base64::encode(format!("{}:{}", RELAY_USER, secret));
+    header_values(head, "proxy-authorization").any(|value| {
+        value.split_once(' ').is_some_and(|(scheme, credentials)| {
+            scheme.eq_ignore_ascii_case("basic") && credentials.trim() == expected
+        })
+    })
+}
+
+/// Answers a `CONNECT` of a client with a connection to its target
+fn answer(mut client: TcpStream, secret: &str) -> Result<()> {
+    let head = read_head(&mut client)?;
+    if !authorized(&head, secret) {
+        client.write_all(
+            b"HTTP/1.1 407 Proxy Authentication Required\r\nContent-Length: 0\r\n\r\n",
+        )?;
+        return Err(anyhow!("the relay refused a client without its secret"));
+    }
+    let target = match head.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["CONNECT", target, ..] => target.to_string(),
+        _ => return Err(anyhow!("the relay only tunnels CONNECT requests")),
+    };
+    let (host, port) = target
+        .rsplit_once(':')
+        .and_then(|(host, port)| Some((host.to_string(), port.parse().ok()?)))
+        .ok_or_else(|| anyhow!("invalid target {}", target))?;
+    match connect(&host, port) {
+        Ok(tunnel) => {
+            client.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")?;
+            splice(client, tunnel)
+        }
+        Err(e) => {
+            client.write_all(b"HTTP/1.1 502 Bad Gateway\r\nContent-Length: 0\r\n\r\n")?;
+            Err(match is_configured() {
+                true => e.context(format!("unable to tunnel to {} through the proxy", target)),
+                false => e,
+            })
+        }
+    }
+}
+
+/// Starts the relay tunneling the connections of the default client, once per process
+pub(crate) fn relay() -> Result<()> {
+    if RELAY.get().is_some() {
+        return Ok(());
+    }
+    let listener = TcpListener::bind("127.0.0.1:0").context("unable to start the relay")?;
+    let relay = listener.local_addr()?;
+    let secret = rand::random::<[u8; 16]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    debug!(%relay, "relay started");
+    let _ = RELAY.set((relay, secret.clone()));
+    let secret = Arc::new(secret);
+    thread::spawn(move || {
+        for client in listener.incoming().flatten() {
+            let secret = secret.clone();
+            thread::spawn(move || {
+                if let Err(e) = answer(client, &secret) {
+                    warn!("{:#}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Tunnels the connections of the process through the proxy, the settings applied first are
+/// kept for the process
+pub fn configure(settings: &ProxySettings) -> Result<()> {
+    if CONFIGURED.get().is_some() {
+        return Ok(());
+    }
+    settings.address()?;
+    info!(proxy = %settings.url.origin().ascii_serialization(), "using proxy");
+    let _ = CONFIGURED.set(settings.clone());
+    relay()
+}
+
+/// Whether the connections go through a proxy
+pub fn is_configured() -> bool {
+    CONFIGURED.get().is_some()
+}
+
+/// Proxy of the agents, the relay with its secret
+pub(crate) fn agent_proxy() -> Option<ureq::Proxy> {
+    let (relay, secret) = RELAY.get()?;
+    ureq::Proxy::new(format!("http://{}:{}@{}", RELAY_USER, secret, relay)).ok()
+}
+
+/// Connection to the host, through the proxy if there is one
+pub(crate) fn connect(host: &str, port: u16) -> Result<TcpStream> {
+    match CONFIGURED.get() {
+        Some(settings) => settings.tunnel(host, port),
+        None => network::dial(host, port),
+    }
+}
+
+pub(crate) trait Connection: Read + Write {}
+
+impl<T: Read + Write> Connection for T {}
+
+/// Connection to the host of the url made by hand, through the proxy if there is one and over
+/// TLS with the settings of the process for `https` and `wss` urls, returned with the
+/// underlying socket to control its timeouts
+pub(crate) fn open(url: &Url) -> Result<(Box<dyn Connection>, TcpStream)> {
+    let host = url.host_str().ok_or_else(|| anyhow!("url without host"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("url without port"))?;
+    let stream = connect(host, port)?;
+    let socket = stream.try_clone()?;
+    match url.scheme() {
+        "https" | "wss" => {
+            let name = tls::dns_name(host)?;
+            let session =
+                rustls::ClientSession::new(&Arc::new(tls::client_config()), name.as_ref());
+            Ok((Box::new(rustls::StreamOwned::new(session, stream)), socket))
+        }
+        _ => Ok((Box::new(stream), socket)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_header_values() {
+        let head = "HTTP/1.1 407 Proxy Authentication Required\r\n\
+                    Proxy-Authenticate: NTLM\r\n\
+                    proxy-authenticate : Basic realm=\"corp\"\r\n\
+                    Content-Length: 0\r\n\r\n";
+        assert_eq!(
+            header_values(head, "Proxy-Authenticate").collect::<Vec<_>>(),
+            vec!["NTLM", "Basic realm=\"corp\""]
+        );
+        assert_eq!(header_values(head, "Connection").count(), 0);
+    }
+
+    #[test]
+    fn authorizes_clients_with_the_secret() {
+        let credentials = base64::encode("openapi-fuzzer:secret");
+        let head = format!(
+            "CONNECT api:443 HTTP/1.1\r\nProxy-Authorization: basic {}\r\n\r\n",
+            credentials
+        );
+        assert!(authorized(&head, "secret"));
+        assert!(!authorized(&head, "other"));
+        assert!(!authorized("CONNECT api:443 HTTP/1.1\r\n\r\n", "secret"));
+        let bearer = format!(
+            "CONNECT api:443 HTTP/1.1\r\nProxy-Authorization: Bearer {}\r\n\r\n",
+            credentials
+        );
+        assert!(!authorized(&bearer, "secret"));
+    }
+}
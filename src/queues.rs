@@ -0,0 +1,166 @@
+//! Work queues of the operations by their tags: each queue is fuzzed by its own process in
+//! parallel to the others, with its share of the budget of requests and its own rate limit,
+//! e.g. 30% of the requests at 5 requests per second for `payments` and the rest for `search`.
+//! A budget per operation is split by the shares too, the operations of a queue with a larger
+//! share get more requests each.
+
+use anyhow::{anyhow, Result};
+use openapi_utils::ReferenceOrExt;
+use openapiv3::OpenAPI;
+use serde::Deserialize;
+
+use crate::payload::operations;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Queue {
+    /// Tags of the operations of the queue, the operations of no other queue if empty
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Part of the budget of requests, e.g. `0.3`, the queues without one split the rest evenly
+    pub share: Option<f64>,
+    /// Highest number of requests per second sent to all operations of the queue together
+    pub rate_limit: Option<f64>,
+}
+
+impl Queue {
+    pub fn name(&self) -> String {
+        match self.tags.is_empty() {
+            true => "untagged".to_string(),
+            false => self.tags.join(", "),
+        }
+    }
+}
+
+/// Share of the budget of each queue, the queues without one split what the others leave
+pub fn shares(queues: &[Queue]) -> Result<Vec<f64>> {
+    if queues.iter().filter(|queue| queue.tags.is_empty()).count() > 1 {
+        return Err(anyhow!("only one queue can be without tags"));
+    }
+    if let Some(queue) = queues.iter().find(|queue| {
+        queue
+            .rate_limit
+            .is_some_and(|rate_limit| !(rate_limit > 0.0 && rate_limit.is_finite()))
+    }) {
+        return Err(anyhow!(
+            "the rate limit of the queue of {} is not a positive number",
+            queue.name()
+        ));
+    }
+    if let Some(queue) = queues
+        .iter()
+        .find(|queue| queue.share.is_some_and(|share| share <= 0.0 || share > 1.0))
+    {
+        return Err(anyhow!(
+            "the share of the queue of {} is not between 0 and 1",
+            queue.name()
+        ));
+    }
+    let shared = queues.iter().filter_map(|queue| queue.share).sum::<f64>();
+    let unshared = queues.iter().filter(|queue| queue.share.is_none()).count();
+    if shared > 1.0 + f64::EPSILON {
+        return Err(anyhow!("the shares of the queues add up to more than 1"));
+    }
+    let rest = match unshared {
+        0 => 0.0,
+        _ => (1.0 - shared).max(0.0) / unshared as f64,
+    };
+    Ok(queues
+        .iter()
+        .map(|queue| queue.share.unwrap_or(rest))
+        .collect())
+}
+
+/// Operations of each queue, by method and path. An operation belongs to the first queue with
+/// one of its tags, or to the queue without tags.
+pub fn operations_of(schema: &OpenAPI, queues: &[Queue]) -> Result<Vec<Vec<(String, String)>>> {
+    let mut assigned = vec![Vec::new(); queues.len()];
+    for (path, item) in schema.paths.iter() {
+        for (method, operation) in operations(item.to_item_ref()) {
+            let operation = match operation {
+                Some(operation) => operation,
+                None => continue,
+            };
+            let queue = queues
+                .iter()
+                .position(|queue| queue.tags.iter().any(|tag| operation.tags.contains(tag)))
+                .or_else(|| queues.iter().position(|queue| queue.tags.is_empty()));
+            if let Some(queue) = queue {
+                assigned[queue].push((method.to_string(), path.clone()));
+            }
+        }
+    }
+    if let Some(empty) = assigned.iter().position(Vec::is_empty) {
+        return Err(anyhow!(
+            "the queue of {} has no operations",
+            queues[empty].name()
+        ));
+    }
+    Ok(assigned)
+}
+
+/// Requests of the budget the queue with the share gets
+pub fn budget(share: f64, max_requests: u32) -> u32 {
+    ((max_requests as f64 * share).round() as u32).max(1)
+}
+
+/// Requests of the budget per operation each operation of the queue with the share gets, of
+/// the budget of all operations together
+pub fn budget_per_operation(
+    share: f64,
+    max_requests_per_op: u32,
+    operations: usize,
+    all_operations: usize,
+) -> u32 {
+    budget(
+        share * all_operations as f64 / operations.max(1) as f64,
+        max_requests_per_op,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(tags: &[&str], share: Option<f64>, rate_limit: Option<f64>) -> Queue {
+        Queue {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            share,
+            rate_limit,
+        }
+    }
+
+    #[test]
+    fn splits_the_rest_between_queues_without_shares() {
+        let queues = [
+            queue(&["payments"], Some(0.3), Some(5.0)),
+            queue(&["search"], None, None),
+            queue(&[], None, None),
+        ];
+        assert_eq!(shares(&queues).unwrap(), vec![0.3, 0.35, 0.35]);
+        assert!(shares(&[queue(&[], None, None), queue(&[], None, None)]).is_err());
+        assert!(shares(&[
+            queue(&["a"], Some(0.8), None),
+            queue(&["b"], Some(0.3), None)
+        ])
+        .is_err());
+        assert!(shares(&[queue(&["a"], Some(0.0), None)]).is_err());
+    }
+
+    #[test]
+    fn rejects_rate_limits_that_are_not_positive() {
+        assert!(shares(&[queue(&["a"], None, Some(0.0))]).is_err());
+        assert!(shares(&[queue(&["a"], None, Some(-1.0))]).is_err());
+        assert!(shares(&[queue(&["a"], None, Some(f64::NAN))]).is_err());
+        assert!(shares(&[queue(&["a"], None, Some(0.5))]).is_ok());
+    }
+
+    #[test]
+    fn splits_the_budget_per_operation() {
+        // 30% of 10 operations × 100 requests for 2 operations
+        assert_eq!(budget_per_operation(0.3, 100, 2, 10), 150);
+        assert_eq!(budget_per_operation(0.7, 100, 8, 10), 88);
+        assert_eq!(budget(0.3, 1000), 300);
+        assert_eq!(budget(0.001, 10), 1);
+    }
+}
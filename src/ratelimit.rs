@@ -0,0 +1,98 @@
+use std::{
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Result;
+
+use crate::transport::{Request, Response, Transport};
+
+/// Longest time to wait before retrying a rate limited request
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Time to wait when a rate limited response does not have a `Retry-After` header
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Number of retries of a rate limited request, the last response is returned afterwards
+const MAX_RETRIES: u32 = 3;
+
+/// Returns how long to wait before retrying the request, if the response is rate limited
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    if response.status != 429 {
+        return None;
+    }
+
+    let wait = requested_wait(response).unwrap_or(DEFAULT_RETRY_AFTER);
+    Some(wait.min(MAX_RETRY_AFTER))
+}
+
+/// Time the `Retry-After` header of the response asks for, in seconds or until a date
+pub(crate) fn requested_wait(response: &Response) -> Option<Duration> {
+    let value = response.header("retry-after")?.trim();
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()
+            .and_then(|date| date.duration_since(SystemTime::now()).ok()),
+    }
+}
+
+/// Sends the request and retries it when it is rate limited, after the time the server asks for
+pub fn send(transport: &dyn Transport, request: &Request) -> Result<Response> {
+    let mut response = transport.send(request)?;
+    for _ in 0..MAX_RETRIES {
+        match retry_after(&response) {
+            Some(wait) => thread::sleep(wait),
+            None => break,
+        }
+        response = transport.send(request)?;
+    }
+    Ok(response)
+}
+
+/// Limit of requests advertised in the response headers, e.g. `X-RateLimit-Limit: 100`
+fn advertised_limit(response: &Response) -> Option<u32> {
+    ["ratelimit-limit", "x-ratelimit-limit", "x-rate-limit-limit"]
+        .iter()
+        .find_map(|name| response.header(name))
+        // The value may contain a policy, e.g. `100, 100;w=60`
+        .and_then(|value| value.split(|c: char| !c.is_ascii_digit()).next())
+        .and_then(|limit| limit.parse().ok())
+}
+
+/// Sends a burst of the request, one more than the advertised limit if there is one, and
+/// returns the last response with a description of the missing rate limit, unless a request
+/// gets rate limited
+pub fn probe(
+    transport: &dyn Transport,
+    request: &Request,
+    burst: u32,
+) -> Result<Option<(Response, String)>> {
+    let start = Instant::now();
+    let mut response = transport.send(request)?;
+    let limit = advertised_limit(&response);
+    let count = limit.map_or(burst, |limit| limit.saturating_add(1).min(burst));
+
+    for _ in 1..count {
+        if response.status == 429 {
+            return Ok(None);
+        }
+        response = transport.send(request)?;
+    }
+    if response.status == 429 {
+        return Ok(None);
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    let description = match limit {
+        Some(limit) if count > limit => format!(
+            "advertised limit of {} requests not enforced, {} requests sent in {:.1} s were not rate limited",
+            limit, count, elapsed
+        ),
+        _ => format!(
+            "{} requests sent in {:.1} s were not rate limited",
+            count, elapsed
+        ),
+    };
+    Ok(Some((response, description)))
+}
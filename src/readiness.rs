@@ -0,0 +1,79 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+use url::Url;
+
+use crate::transport::{Request, Response};
+
+/// Longest time to wait for the service to become ready, unless set otherwise
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Time between two polls of the readiness endpoint
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest time to wait for the response to a single poll
+const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Endpoint polled before fuzzing until it responds with a 2xx status, e.g. `/health` of a
+/// service that was just started
+#[derive(Debug, Clone)]
+pub struct Readiness {
+    pub url: Url,
+    /// Longest time to wait for the service to become ready
+    pub timeout: Duration,
+}
+
+impl Readiness {
+    /// Polls the endpoint until it is healthy, fails when it is not after the timeout
+    pub fn wait(&self) -> Result<()> {
+        let start = Instant::now();
+        let request = Request {
+            method: "GET".to_string(),
+            url: self.url.clone(),
+            headers: Vec::new(),
+            body: None,
+            raw_body: None,
+            timeout: Some(POLL_TIMEOUT),
+            stream: None,
+        };
+        info!(url = %self.url, "waiting for the service to be ready");
+        loop {
+            let last = match request.send() {
+                Ok(response) if response.status / 100 == 2 => {
+                    info!(
+                        elapsed_ms = start.elapsed().as_millis() as u64,
+                        "service ready"
+                    );
+                    return Ok(());
+                }
+                Ok(response) => format!("{} {}", response.status, response.status_text),
+                Err(e) => e.to_string(),
+            };
+            debug!(last = %last, "service not ready yet");
+            if start.elapsed() + POLL_INTERVAL > self.timeout {
+                bail!(
+                    "{} not ready after {}s, last poll: {}",
+                    self.url,
+                    self.timeout.as_secs(),
+                    last
+                );
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Response to the valid request sent to an operation before fuzzing, saved to
+/// `warm-up.json` in the results directory as the baseline of the run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmUp {
+    pub method: String,
+    pub path: String,
+    pub request: Request,
+    pub response: Response,
+}
@@ -0,0 +1,248 @@
+//! Later replays of new findings none of the replays reproduced when they were reverified.
+//! Staging environments fail on their own from time to time, e.g. during deployments, so the
+//! request of such a finding is replayed again spread over an interval while the run goes on.
+//! Findings reproduced by any of the later replays are intermittent bugs, the others noise of
+//! the environment. The verdict is updated in the findings and their files.
+
+use std::{
+    collections::VecDeque,
+    path::Path,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::encryption::Encryption;
+use crate::finding::{FindingKind, Findings, LaterReplays};
+use crate::ratelimit;
+use crate::signing::Signing;
+use crate::transport::{Request, Transport};
+
+/// Headers APIs deduplicate requests by, a replay with the same key would get the stored
+/// response instead of a new one
+const IDEMPOTENCY_HEADERS: &[&str] = &["idempotency-key", "x-idempotency-key"];
+
+/// Random version 4 UUID
+fn uuid() -> String {
+    let mut bytes = rand::random::<[u8; 16]>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// The request with new idempotency keys, signed again as the signature covers the time
+fn refreshed(request: &Request, signing: Option<&Signing>) -> Result<Request> {
+    let mut request = request.clone();
+    for (name, value) in request.headers.iter_mut() {
+        if IDEMPOTENCY_HEADERS.contains(&name.to_lowercase().as_str()) {
+            *value = uuid();
+        }
+    }
+    if let Some(signing) = signing {
+        signing.sign(&mut request, SystemTime::now())?;
+    }
+    Ok(request)
+}
+
+/// Finding waiting for its later replays
+#[derive(Debug)]
+struct Recheck {
+    path: String,
+    method: String,
+    status: u16,
+    kind: FindingKind,
+    /// File the finding was saved to
+    file: String,
+    request: Request,
+    /// Times the remaining replays are due
+    due: VecDeque<Instant>,
+    statuses: Vec<Option<u16>>,
+}
+
+/// Findings replayed again over an interval
+#[derive(Debug)]
+pub struct Rechecks {
+    interval: Duration,
+    replays: u32,
+    pending: Vec<Recheck>,
+}
+
+impl Rechecks {
+    pub fn new(interval: Duration, replays: u32) -> Rechecks {
+        Rechecks {
+            interval,
+            replays: replays.max(1),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Schedules the replays of the request of a finding evenly over the interval, the last
+    /// one at its end
+    pub fn schedule(
+        &mut self,
+        path: &str,
+        method: &str,
+        status: u16,
+        kind: FindingKind,
+        file: &str,
+        request: &Request,
+    ) {
+        let now = Instant::now();
+        let due = (1..=self.replays)
+            .map(|i| now + self.interval * i / self.replays)
+            .collect();
+        debug!(method, path, %kind, "finding not reproduced, replayed again later");
+        self.pending.push(Recheck {
+            path: path.to_string(),
+            method: method.to_string(),
+            status,
+            kind,
+            file: file.to_string(),
+            request: request.clone(),
+            due,
+            statuses: Vec::new(),
+        });
+    }
+
+    /// Sends the replays that are due and classifies the findings whose replays are all sent
+    pub fn replay_due(
+        &mut self,
+        transport: &dyn Transport,
+        signing: Option<&Signing>,
+        findings: &mut Findings,
+        encryption: &Encryption,
+    ) -> Result<()> {
+        let now = Instant::now();
+        for recheck in self.pending.iter_mut() {
+            while recheck.due.front().is_some_and(|due| *due <= now) {
+                recheck.due.pop_front();
+                let sent = refreshed(&recheck.request, signing)
+                    .and_then(|request| ratelimit::send(transport, &request));
+                let status = match sent {
+                    Ok(response) => Some(response.status),
+                    Err(e) => {
+                        debug!(error = %format!("{:#}", e), "later replay failed");
+                        None
+                    }
+                };
+                recheck.statuses.push(status);
+            }
+        }
+
+        let (done, pending) = self
+            .pending
+            .drain(..)
+            .partition::<Vec<_>, _>(|recheck| recheck.due.is_empty());
+        self.pending = pending;
+        for recheck in done {
+            self.classify(recheck, findings, encryption)?;
+        }
+        Ok(())
+    }
+
+    /// Waits for the remaining replays, at the end of the run
+    pub fn finish(
+        &mut self,
+        transport: &dyn Transport,
+        signing: Option<&Signing>,
+        findings: &mut Findings,
+        encryption: &Encryption,
+    ) -> Result<()> {
+        if let Some(last) = self.pending.iter().filter_map(|r| r.due.back()).max() {
+            info!(
+                findings = self.pending.len(),
+                wait = %humantime::format_duration(Duration::from_secs(
+                    last.saturating_duration_since(Instant::now()).as_secs()
+                )),
+                "waiting for the later replays of findings"
+            );
+        }
+        while let Some(next) = self.pending.iter().filter_map(|r| r.due.front()).min() {
+            thread::sleep(next.saturating_duration_since(Instant::now()));
+            self.replay_due(transport, signing, findings, encryption)?;
+        }
+        Ok(())
+    }
+
+    fn classify(
+        &self,
+        recheck: Recheck,
+        findings: &mut Findings,
+        encryption: &Encryption,
+    ) -> Result<()> {
+        let later = LaterReplays {
+            interval: humantime::format_duration(self.interval).to_string(),
+            replays: recheck.statuses.len() as u32,
+            reproduced: recheck
+                .statuses
+                .iter()
+                .filter(|status| **status == Some(recheck.status))
+                .count() as u32,
+            statuses: recheck.statuses,
+        };
+        let verification = match findings
+            .get_mut(&recheck.path, &recheck.method, recheck.status, recheck.kind)
+            .and_then(|finding| finding.verification.as_mut())
+        {
+            Some(verification) => {
+                verification.replayed_later(later);
+                verification.clone()
+            }
+            None => return Ok(()),
+        };
+        info!(
+            kind = %recheck.kind,
+            method = %recheck.method,
+            path = %recheck.path,
+            status = recheck.status,
+            %verification,
+            "finding replayed later"
+        );
+
+        let file = Path::new(&recheck.file);
+        let mut record: Value = serde_json::from_slice(&encryption.read(file)?)
+            .with_context(|| format!("invalid finding file {}", file.display()))?;
+        record["verification"] = serde_json::to_value(&verification)?;
+        encryption.write(file, &serde_json::to_vec_pretty(&record)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::Url;
+
+    #[test]
+    fn refreshes_idempotency_keys() {
+        let request = Request {
+            method: "POST".to_string(),
+            url: Url::parse("http://localhost/payments").unwrap(),
+            headers: vec![
+                ("Idempotency-Key".to_string(), "a".to_string()),
+                ("Accept".to_string(), "*/*".to_string()),
+            ],
+            body: None,
+            raw_body: None,
+            timeout: None,
+            stream: None,
+        };
+        let refreshed = refreshed(&request, None).unwrap();
+        assert_ne!(refreshed.headers[0].1, "a");
+        assert_eq!(refreshed.headers[0].1.len(), 36);
+        assert_eq!(refreshed.headers[1], request.headers[1]);
+    }
+}
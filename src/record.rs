@@ -0,0 +1,558 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::Read,
+    net::SocketAddr,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+use tiny_http::{Header, Server};
+use tracing::{info, warn};
+use url::Url;
+
+use openapi_fuzzer::corpus::{Corpus, Seed};
+use openapi_fuzzer::encryption::Encryption;
+use openapi_fuzzer::tls;
+use openapi_fuzzer::traffic::{har_entries, har_headers};
+use openapi_fuzzer::transport::relative_path;
+
+/// Recorded bodies and values of each location, later ones are not needed to infer schemas
+const MAX_SAMPLES: usize = 100;
+
+/// Seeds of each operation saved to the corpus
+const MAX_SEEDS: usize = 20;
+
+/// Responses with a longer body are truncated
+const MAX_BODY_SIZE: u64 = 10 * 1024 * 1024;
+
+/// The specification and corpus are saved this often while there is traffic
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Headers that apply to a single connection, or that the proxy sets itself
+const HOP_HEADERS: &[&str] = &[
+    "host",
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "content-length",
+    "accept-encoding",
+    "content-encoding",
+    "upgrade",
+];
+
+/// Traffic of an operation, keyed by method and path template
+#[derive(Debug, Default)]
+struct Observed {
+    requests: usize,
+    path_params: BTreeMap<String, Vec<String>>,
+    /// Values of the query parameters and the number of requests with them
+    query_params: BTreeMap<String, (Vec<String>, usize)>,
+    bodies: Vec<Value>,
+    /// Reasons and JSON bodies of the responses by status
+    responses: BTreeMap<u16, (String, Vec<Value>)>,
+    seeds: Vec<Seed>,
+}
+
+/// Whether the path segment is an identifier rather than a fixed part of the path, e.g.
+/// `42` or `0b6c5a1e-...`
+fn is_identifier(segment: &str) -> bool {
+    let hex = segment.chars().all(|c| c.is_ascii_hexdigit() || c == '-');
+    let digits = segment.chars().filter(char::is_ascii_digit).count();
+    !segment.is_empty()
+        && (digits == segment.len()
+            || hex && segment.len() >= 16
+            || segment.len() >= 16
+                && digits > 0
+                && segment.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Path template of the request path with the values of its parameters, e.g.
+/// `/users/{userId}` for `/users/42`
+fn template(path: &str) -> (String, BTreeMap<String, String>) {
+    let mut template = String::new();
+    let mut params = BTreeMap::new();
+    let mut previous = "";
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        let decoded = percent_encoding::percent_decode_str(segment).decode_utf8_lossy();
+        match is_identifier(&decoded) {
+            true => {
+                let mut name = match previous.strip_suffix('s').unwrap_or(previous) {
+                    "" => "id".to_string(),
+                    resource => format!("{}Id", resource),
+                };
+                while params.contains_key(&name) {
+                    name.push('_');
+                }
+                template += &format!("/{{{}}}", name);
+                params.insert(name, decoded.into_owned());
+            }
+            false => {
+                template += &format!("/{}", segment);
+                previous = segment;
+            }
+        }
+    }
+    match template.is_empty() {
+        true => ("/".to_string(), params),
+        false => (template, params),
+    }
+}
+
+/// Schema of the parameter values, e.g. `integer` when they all are
+fn value_schema(values: &[String]) -> Value {
+    let all = |check: fn(&str) -> bool| !values.is_empty() && values.iter().all(|v| check(v));
+    if all(|value| value.parse::<i64>().is_ok()) {
+        json!({ "type": "integer" })
+    } else if all(|value| value.parse::<f64>().is_ok()) {
+        json!({ "type": "number" })
+    } else if all(|value| value == "true" || value == "false") {
+        json!({ "type": "boolean" })
+    } else {
+        json!({ "type": "string" })
+    }
+}
+
+/// Schema matching all the JSON samples, properties missing in some samples are optional
+fn json_schema(samples: &[&Value]) -> Value {
+    let nullable = samples.iter().any(|sample| sample.is_null());
+    let samples = samples
+        .iter()
+        .copied()
+        .filter(|sample| !sample.is_null())
+        .collect::<Vec<_>>();
+    let all = |check: fn(&Value) -> bool| !samples.is_empty() && samples.iter().all(|v| check(v));
+
+    let mut schema = if all(Value::is_object) {
+        let mut keys = BTreeSet::new();
+        for sample in samples.iter() {
+            keys.extend(sample.as_object().unwrap().keys());
+        }
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for key in keys {
+            let values = samples
+                .iter()
+                .filter_map(|sample| sample.get(key))
+                .collect::<Vec<_>>();
+            if values.len() == samples.len() {
+                required.push(key.clone());
+            }
+            properties.insert(key.clone(), json_schema(&values));
+        }
+        let mut schema = json!({ "type": "object", "properties": properties });
+        if !required.is_empty() {
+            schema["required"] = json!(required);
+        }
+        schema
+    } else if all(Value::is_array) {
+        let items = samples
+            .iter()
+            .flat_map(|sample| sample.as_array().unwrap())
+            .collect::<Vec<_>>();
+        json!({ "type": "array", "items": json_schema(&items) })
+    } else if all(|value| value.is_i64() || value.is_u64()) {
+        json!({ "type": "integer" })
+    } else if all(Value::is_number) {
+        json!({ "type": "number" })
+    } else if all(Value::is_string) {
+        json!({ "type": "string" })
+    } else if all(Value::is_boolean) {
+        json!({ "type": "boolean" })
+    } else {
+        json!({})
+    };
+    if nullable {
+        schema["nullable"] = json!(true);
+    }
+    schema
+}
+
+fn push_sample<T>(samples: &mut Vec<T>, sample: T) {
+    if samples.len() < MAX_SAMPLES {
+        samples.push(sample);
+    }
+}
+
+fn is_json(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(|content_type| content_type.contains("json"))
+}
+
+/// Request proxied to the target and its response
+struct Exchange<'a> {
+    method: &'a str,
+    /// Request path without the query, relative to the target
+    path: &'a str,
+    query: &'a str,
+    request_type: Option<&'a str>,
+    request_body: &'a [u8],
+    status: u16,
+    reason: &'a str,
+    response_type: Option<&'a str>,
+    response_body: &'a [u8],
+}
+
+/// Operations learned from the traffic of an API
+#[derive(Debug, Default)]
+pub struct Recording {
+    operations: BTreeMap<(String, String), Observed>,
+}
+
+impl Recording {
+    fn record(&mut self, exchange: Exchange<'_>) {
+        let (path, path_params) = template(exchange.path);
+        let observed = self
+            .operations
+            .entry((path, exchange.method.to_uppercase()))
+            .or_default();
+        observed.requests += 1;
+        for (name, value) in path_params.iter() {
+            push_sample(
+                observed.path_params.entry(name.clone()).or_default(),
+                value.clone(),
+            );
+        }
+        let query_params = url::form_urlencoded::parse(exchange.query.as_bytes())
+            .into_owned()
+            .collect::<BTreeMap<_, _>>();
+        for (name, value) in query_params.iter() {
+            let (values, count) = observed.query_params.entry(name.clone()).or_default();
+            push_sample(values, value.clone());
+            *count += 1;
+        }
+        let body = match is_json(exchange.request_type) {
+            true => serde_json::from_slice::<Value>(exchange.request_body).ok(),
+            false => None,
+        };
+        if let Some(body) = &body {
+            push_sample(&mut observed.bodies, body.clone());
+        }
+
+        let (_, bodies) = observed
+            .responses
+            .entry(exchange.status)
+            .or_insert_with(|| (exchange.reason.to_string(), Vec::new()));
+        if let Some(body) = is_json(exchange.response_type)
+            .then(|| serde_json::from_slice::<Value>(exchange.response_body).ok())
+            .flatten()
+        {
+            push_sample(bodies, body);
+        }
+
+        // Accepted requests are known-good starting points for fuzzing
+        if exchange.status / 100 == 2 && observed.seeds.len() < MAX_SEEDS {
+            observed.seeds.push(Seed {
+                path_params,
+                query_params,
+                headers: BTreeMap::new(),
+                body,
+            });
+        }
+    }
+
+    /// Draft specification of the recorded operations
+    pub fn specification(&self, target: &Url) -> Value {
+        let mut paths = Map::new();
+        for ((path, method), observed) in self.operations.iter() {
+            let mut parameters = Vec::new();
+            for (name, values) in observed.path_params.iter() {
+                parameters.push(json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": value_schema(values),
+                }));
+            }
+            for (name, (values, count)) in observed.query_params.iter() {
+                parameters.push(json!({
+                    "name": name,
+                    "in": "query",
+                    "required": *count == observed.requests,
+                    "schema": value_schema(values),
+                }));
+            }
+
+            let mut responses = Map::new();
+            for (status, (reason, bodies)) in observed.responses.iter() {
+                let mut response = json!({ "description": reason });
+                if !bodies.is_empty() {
+                    let bodies = bodies.iter().collect::<Vec<_>>();
+                    response["content"] =
+                        json!({ "application/json": { "schema": json_schema(&bodies) } });
+                }
+                responses.insert(status.to_string(), response);
+            }
+
+            let mut operation = json!({ "responses": responses });
+            if !parameters.is_empty() {
+                operation["parameters"] = json!(parameters);
+            }
+            if !observed.bodies.is_empty() {
+                let bodies = observed.bodies.iter().collect::<Vec<_>>();
+                operation["requestBody"] = json!({
+                    "required": observed.bodies.len() == observed.requests,
+                    "content": { "application/json": { "schema": json_schema(&bodies) } },
+                });
+            }
+            let item = paths.entry(path.clone()).or_insert_with(|| json!({}));
+            item[method.to_lowercase()] = operation;
+        }
+
+        json!({
+            "openapi": "3.0.0",
+            "info": {
+                "title": "Recorded API",
+                "version": "draft",
+                "description": format!("Recorded from the traffic of {}", target),
+            },
+            "servers": [{ "url": target.as_str() }],
+            "paths": paths,
+        })
+    }
+
+    fn save(&self, target: &Url, output: &Path, encryption: &Encryption) -> Result<()> {
+        fs::create_dir_all(output)?;
+        fs::write(
+            output.join("openapi.yaml"),
+            serde_yaml::to_string(&self.specification(target))?,
+        )?;
+        let corpus = output.join(encryption.path("corpus.jsonl"));
+        self.corpus().save(&corpus, encryption)?;
+        info!(operations = self.operations.len(), "recording saved");
+        Ok(())
+    }
+
+    /// Seeds of the accepted requests
+    pub fn corpus(&self) -> Corpus {
+        let mut corpus = Corpus::default();
+        for ((path, method), observed) in self.operations.iter() {
+            for seed in observed.seeds.iter() {
+                corpus.add(method, path, seed.clone());
+            }
+        }
+        corpus
+    }
+
+    /// Recording of the requests of the HAR capture to the API at the url, or to the host
+    /// with the most requests, returns the url of the API as well
+    pub fn from_har(har: &Value, api: Option<&Url>) -> Result<(Recording, Url)> {
+        let entries = har_entries(har)?;
+
+        let base = match api {
+            Some(api) => api.clone(),
+            None => {
+                let mut hosts = BTreeMap::<_, usize>::new();
+                for (_, url) in entries.iter() {
+                    *hosts.entry(url.origin().ascii_serialization()).or_default() += 1;
+                }
+                let origin = hosts
+                    .into_iter()
+                    .max_by_key(|(_, count)| *count)
+                    .map(|(origin, _)| origin)
+                    .ok_or_else(|| anyhow!("HAR without requests"))?;
+                Url::parse(&origin)?
+            }
+        };
+
+        let mut recording = Recording::default();
+        for (entry, url) in entries.iter() {
+            let path = match relative_path(url.path(), base.path()) {
+                Some(path) if url.origin() == base.origin() => path,
+                _ => continue,
+            };
+            let request_headers = har_headers(entry.pointer("/request/headers"));
+            let response_headers = har_headers(entry.pointer("/response/headers"));
+            let text = |pointer| {
+                entry
+                    .pointer(pointer)
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+            };
+            let response_body = match text("/response/content/encoding") {
+                "base64" => base64::decode(text("/response/content/text")).unwrap_or_default(),
+                _ => text("/response/content/text").as_bytes().to_vec(),
+            };
+            recording.record(Exchange {
+                method: text("/request/method"),
+                path,
+                query: url.query().unwrap_or_default(),
+                request_type: entry
+                    .pointer("/request/postData/mimeType")
+                    .and_then(Value::as_str)
+                    .or_else(|| content_type(&request_headers)),
+                request_body: text("/request/postData/text").as_bytes(),
+                status: entry
+                    .pointer("/response/status")
+                    .and_then(Value::as_u64)
+                    .unwrap_or_default() as u16,
+                reason: text("/response/statusText"),
+                response_type: entry
+                    .pointer("/response/content/mimeType")
+                    .and_then(Value::as_str)
+                    .or_else(|| content_type(&response_headers)),
+                response_body: &response_body,
+            });
+        }
+        if recording.operations.is_empty() {
+            return Err(anyhow!("The HAR capture has no requests to {}", base));
+        }
+        Ok((recording, base))
+    }
+}
+
+/// Whether the file is a HAR capture
+pub fn is_har(document: &Value) -> bool {
+    document.pointer("/log/entries").is_some()
+}
+
+fn content_type(headers: &[(String, String)]) -> Option<&str> {
+    headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Forwards the request to the target and records the exchange, returns whether it was
+/// recorded
+fn forward(
+    agent: &ureq::Agent,
+    target: &Url,
+    recording: &mut Recording,
+    mut request: tiny_http::Request,
+) -> Result<bool> {
+    let (path, query) = request
+        .url()
+        .split_once('?')
+        .map(|(path, query)| (path.to_string(), query.to_string()))
+        .unwrap_or_else(|| (request.url().to_string(), String::new()));
+    // Requests in absolute form would be forwarded to any host, the proxy is not open
+    let url = match target.join(request.url().trim_start_matches('/')) {
+        Ok(url) if request.url().starts_with('/') && url.origin() == target.origin() => url,
+        _ => {
+            warn!(url = request.url(), "request not for the target rejected");
+            let _ = request.respond(
+                tiny_http::Response::from_string("only requests to the target are proxied")
+                    .with_status_code(400),
+            );
+            return Ok(false);
+        }
+    };
+    let method = request.method().as_str().to_string();
+    let headers = request
+        .headers()
+        .iter()
+        .map(|header| (header.field.to_string(), header.value.to_string()))
+        .filter(|(name, _)| !HOP_HEADERS.contains(&name.to_lowercase().as_str()))
+        .collect::<Vec<_>>();
+    let mut body = Vec::new();
+    request.as_reader().read_to_end(&mut body)?;
+
+    let mut forwarded = agent.request_url(&method, &url);
+    for (name, value) in headers.iter() {
+        forwarded = forwarded.set(name, value);
+    }
+    let result = match body.is_empty() {
+        true => forwarded.call(),
+        false => forwarded.send_bytes(&body),
+    };
+    let response = match result {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(e) => {
+            warn!(%method, %url, error = %e, "target unreachable");
+            let _ = request
+                .respond(tiny_http::Response::from_string(e.to_string()).with_status_code(502));
+            return Ok(false);
+        }
+    };
+
+    let status = response.status();
+    let reason = response.status_text().to_string();
+    let response_headers = response
+        .headers_names()
+        .into_iter()
+        .filter(|name| !HOP_HEADERS.contains(&name.to_lowercase().as_str()))
+        .flat_map(|name| {
+            response
+                .all(&name)
+                .into_iter()
+                .map(|value| (name.clone(), value.to_string()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut response_body = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_BODY_SIZE)
+        .read_to_end(&mut response_body)?;
+
+    recording.record(Exchange {
+        method: &method,
+        path: &path,
+        query: &query,
+        request_type: content_type(&headers),
+        request_body: &body,
+        status,
+        reason: &reason,
+        response_type: content_type(&response_headers),
+        response_body: &response_body,
+    });
+    info!(%method, %url, status, "proxied request");
+
+    let mut proxied = tiny_http::Response::from_data(response_body).with_status_code(status);
+    for (name, value) in response_headers.iter() {
+        if let Ok(header) = Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+            proxied.add_header(header);
+        }
+    }
+    if let Err(e) = request.respond(proxied) {
+        warn!(error = %e, "unable to respond to the client");
+    }
+    Ok(true)
+}
+
+/// Proxies the requests to the target and records the traffic, the draft specification
+/// and the seeds of the accepted requests, encrypted if enabled, are saved to `output` as it
+/// goes
+pub fn run(listen: SocketAddr, target: Url, output: &Path, encryption: &Encryption) -> Result<()> {
+    let server =
+        Server::http(listen).map_err(|e| anyhow!("Failed to listen on {}: {}", listen, e))?;
+    // Redirects are passed on to the client, the proxy is transparent
+    let agent = tls::agent_builder().redirects(0).build();
+    eprintln!(
+        "Recording the traffic of {} on {}, saving to {}",
+        target,
+        listen,
+        output.display()
+    );
+
+    let mut recording = Recording::default();
+    let mut changed = false;
+    let mut last_save = Instant::now();
+    loop {
+        let request = match server.recv_timeout(Duration::from_secs(1))? {
+            Some(request) => request,
+            None => {
+                if changed {
+                    recording.save(&target, output, encryption)?;
+                    changed = false;
+                    last_save = Instant::now();
+                }
+                continue;
+            }
+        };
+
+        match forward(&agent, &target, &mut recording, request) {
+            Ok(recorded) => changed |= recorded,
+            // The client gets no response, the others are proxied on
+            Err(e) => warn!(error = %format!("{:#}", e), "unable to proxy request"),
+        }
+        if last_save.elapsed() >= SAVE_INTERVAL {
+            recording.save(&target, output, encryption)?;
+            changed = false;
+            last_save = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Redaction of credentials and sensitive fields in stored findings and exports, so that they
+//! can be shared. Secrets are replaced with `{{redacted:name}}` placeholders, which the
+//! resender fills in again with `--secret name=value`.
+
+use anyhow::Result;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
+use serde_json::Value;
+use url::form_urlencoded;
+
+use crate::transport::{is_secret_header, Request, Response};
+
+/// Shorter values are not replaced, they would match unrelated text
+const MIN_SECRET_LENGTH: usize = 3;
+
+/// Characters percent-encoded in queries by the URL parser
+const QUERY: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'#').add(b'<').add(b'>');
+
+/// Forms of the value in texts: the value and its encodings in URLs, which differ when it
+/// has reserved characters, encoded ones first as they may contain the value
+fn forms(value: &str) -> Vec<String> {
+    let mut forms = vec![
+        form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>(),
+        utf8_percent_encode(value, NON_ALPHANUMERIC).to_string(),
+        utf8_percent_encode(value, QUERY).to_string(),
+    ];
+    forms.retain(|form| form != value);
+    forms.dedup();
+    forms.push(value.to_string());
+    forms
+}
+
+/// Placeholder of the secret with the name
+pub fn placeholder(name: &str) -> String {
+    format!("{{{{redacted:{}}}}}", name)
+}
+
+/// Secrets found in an exchange, by their placeholder names
+#[derive(Debug, Default)]
+pub struct Secrets(Vec<(String, String)>);
+
+impl Secrets {
+    fn add(&mut self, name: &str, value: &str) {
+        let value = value.trim();
+        if value.len() >= MIN_SECRET_LENGTH && !self.0.iter().any(|(_, v)| v == value) {
+            self.0.push((name.to_lowercase(), value.to_string()));
+            // Longer secrets first, they may contain shorter ones
+            self.0
+                .sort_by_key(|(_, value)| std::cmp::Reverse(value.len()));
+        }
+    }
+
+    fn replace(&self, text: &str) -> String {
+        self.0.iter().fold(text.to_string(), |text, (name, value)| {
+            forms(value).iter().fold(text, |text, form| {
+                text.replace(form.as_str(), &placeholder(name))
+            })
+        })
+    }
+
+    /// Replaces the secrets in all strings of the value, textual bodies stored as base64 in
+    /// the `raw_body` fields too, binary ones are left as they are
+    pub fn redact(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.replace(text),
+            Value::Array(values) => values.iter_mut().for_each(|value| self.redact(value)),
+            Value::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    match (key.as_str(), &value) {
+                        ("raw_body", Value::String(encoded)) => {
+                            let text = base64::decode(encoded)
+                                .ok()
+                                .and_then(|raw| String::from_utf8(raw).ok());
+                            if let Some(text) = text {
+                                *value = base64::encode(self.replace(&text)).into();
+                            }
+                        }
+                        _ => self.redact(value),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The request and response with their secrets replaced
+    pub fn exchange(&self, request: &Request, response: &Response) -> Result<(Request, Response)> {
+        let mut redacted_request = serde_json::to_value(request)?;
+        self.redact(&mut redacted_request);
+        let mut redacted_request: Request = serde_json::from_value(redacted_request)?;
+        redacted_request.timeout = request.timeout;
+        let mut redacted_response = serde_json::to_value(response)?;
+        self.redact(&mut redacted_response);
+        Ok((redacted_request, serde_json::from_value(redacted_response)?))
+    }
+}
+
+/// Finds secrets in requests and responses: values of headers with credentials, cookies and
+/// the configured fields of parameters and JSON bodies
+#[derive(Debug, Default, Clone)]
+pub struct Redactor {
+    /// Names of sensitive headers, query parameters and fields of bodies, in lowercase
+    fields: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new(fields: &[String]) -> Redactor {
+        Redactor {
+            fields: fields.iter().map(|field| field.to_lowercase()).collect(),
+        }
+    }
+
+    fn sensitive(&self, name: &str) -> bool {
+        self.fields.contains(&name.to_lowercase())
+    }
+
+    fn fields(&self, secrets: &mut Secrets, value: &Value) {
+        match value {
+            Value::Array(values) => values.iter().for_each(|value| self.fields(secrets, value)),
+            Value::Object(object) => {
+                for (key, value) in object.iter() {
+                    match value {
+                        Value::String(secret) if is_secret_header(key) || self.sensitive(key) => {
+                            secrets.add(key, secret)
+                        }
+                        Value::Number(secret) if self.sensitive(key) => {
+                            secrets.add(key, &secret.to_string())
+                        }
+                        _ => self.fields(secrets, value),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Secrets in the request, e.g. of a request that got no response
+    pub fn request_secrets(&self, request: &Request) -> Secrets {
+        let mut secrets = Secrets::default();
+        for (name, value) in request.headers.iter() {
+            if name.eq_ignore_ascii_case("cookie") {
+                for (cookie, value) in value.split(';').filter_map(|pair| pair.split_once('=')) {
+                    secrets.add(cookie.trim(), value);
+                }
+            } else if is_secret_header(name) || self.sensitive(name) {
+                // The credentials without the scheme, e.g. `Bearer`
+                let credentials = match value.trim().split_once(' ') {
+                    Some((_, credentials)) if name.to_lowercase().ends_with("authorization") => {
+                        credentials
+                    }
+                    _ => value,
+                };
+                secrets.add(name, credentials);
+            }
+        }
+        for (name, value) in request.url.query_pairs() {
+            if is_secret_header(&name) || self.sensitive(&name) {
+                secrets.add(&name, &value);
+            }
+        }
+        if let Some(body) = &request.body {
+            self.fields(&mut secrets, body);
+        }
+        secrets
+    }
+
+    /// Secrets in the request and its response
+    pub fn secrets(&self, request: &Request, response: &Response) -> Secrets {
+        let mut secrets = self.request_secrets(request);
+        for (name, value) in response.headers.iter() {
+            if name.eq_ignore_ascii_case("set-cookie") {
+                let pair = value
+                    .split(';')
+                    .next()
+                    .and_then(|pair| pair.split_once('='));
+                if let Some((cookie, value)) = pair {
+                    secrets.add(cookie.trim(), value.trim_matches('"'));
+                }
+            } else if is_secret_header(name) || self.sensitive(name) {
+                secrets.add(name, value);
+            }
+        }
+        if let Ok(body) = serde_json::from_str(&response.body) {
+            self.fields(&mut secrets, &body);
+        }
+        secrets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use url::Url;
+
+    fn request(url: &str, headers: &[(&str, &str)], raw_body: Option<Vec<u8>>) -> Request {
+        Request {
+            method: "POST".to_string(),
+            url: Url::parse(url).unwrap(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: None,
+            raw_body,
+            timeout: None,
+            stream: None,
+        }
+    }
+
+    fn response(body: &str) -> Response {
+        Response {
+            status: 200,
+            status_text: "OK".to_string(),
+            http_version: "HTTP/1.1".to_string(),
+            headers: Vec::new(),
+            body: body.to_string(),
+            elapsed: Duration::default(),
+            events: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn redacts_percent_encoded_query_secrets() {
+        let request = request(
+            "http://localhost/items?api_key=a%2Fb%2Bc%3D%3D&q=1",
+            &[],
+            None,
+        );
+        let secrets = Redactor::default().request_secrets(&request);
+        let (redacted, _) = secrets.exchange(&request, &response("")).unwrap();
+        assert_eq!(
+            redacted.url.query(),
+            Some("api_key={{redacted:api_key}}&q=1")
+        );
+
+        // Spaces are encoded as `+` by forms and as `%20` in URLs
+        let mut secrets = Secrets::default();
+        secrets.add("token", "a b&c");
+        assert_eq!(
+            secrets.replace("?token=a+b%26c&other=a%20b&c"),
+            "?token={{redacted:token}}&other={{redacted:token}}"
+        );
+    }
+
+    #[test]
+    fn redacts_header_secrets() {
+        let request = request(
+            "http://localhost/items",
+            &[
+                ("Authorization", "Bearer s3cr3t-t0ken"),
+                ("X-Api-Key", "k3y"),
+                ("Accept", "application/json"),
+            ],
+            None,
+        );
+        let secrets = Redactor::default().request_secrets(&request);
+        let (redacted, response) = secrets
+            .exchange(&request, &response("{\"echo\": \"s3cr3t-t0ken\"}"))
+            .unwrap();
+        assert_eq!(
+            redacted.headers,
+            vec![
+                (
+                    "Authorization".to_string(),
+                    "Bearer {{redacted:authorization}}".to_string()
+                ),
+                (
+                    "X-Api-Key".to_string(),
+                    "{{redacted:x-api-key}}".to_string()
+                ),
+                ("Accept".to_string(), "application/json".to_string()),
+            ]
+        );
+        assert_eq!(response.body, "{\"echo\": \"{{redacted:authorization}}\"}");
+    }
+
+    #[test]
+    fn keeps_binary_bodies() {
+        let binary = vec![0x50, 0x4b, 0x03, 0x04, 0xff, 0xfe, b'k', b'3', b'y'];
+        let request = request(
+            "http://localhost/upload",
+            &[("X-Api-Key", "k3y")],
+            Some(binary.clone()),
+        );
+        let secrets = Redactor::default().request_secrets(&request);
+        let (redacted, _) = secrets.exchange(&request, &response("")).unwrap();
+        assert_eq!(redacted.raw_body, Some(binary));
+
+        let text = Request {
+            raw_body: Some(b"{\"key\": \"k3y\"}".to_vec()),
+            ..request
+        };
+        let (redacted, _) = secrets.exchange(&text, &response("")).unwrap();
+        assert_eq!(
+            redacted.raw_body.as_deref(),
+            Some(&b"{\"key\": \"{{redacted:x-api-key}}\"}"[..])
+        );
+    }
+}
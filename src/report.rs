@@ -0,0 +1,246 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use crate::finding::{Findings, Severity};
+use crate::fuzzer::Stats;
+
+/// Number of the slowest operations listed in the Markdown summary
+const SLOWEST_OPERATIONS: usize = 5;
+
+/// Plain text table with columns aligned to their widest cell
+pub fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let widths = header
+        .iter()
+        .enumerate()
+        .map(|(i, title)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(title.len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    let line = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+            + "\n"
+    };
+
+    let mut table = line(header.to_vec());
+    for row in rows.iter() {
+        table += &line(row.iter().map(String::as_str).collect());
+    }
+    table
+}
+
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+/// Prints findings as GitHub Actions workflow commands, so they are shown as annotations
+pub fn github_annotations(findings: &Findings) {
+    for finding in findings.iter() {
+        let command = match finding.severity {
+            Severity::Low | Severity::Medium => "warning",
+            Severity::High | Severity::Critical => "error",
+        };
+        let mut message = format!(
+            "{} {} returned {} ({} times), payload saved in {}",
+            finding.method, finding.path, finding.status, finding.count, finding.file
+        );
+        if let Some(verification) = &finding.verification {
+            message += &format!(", {}", verification);
+        }
+        let labels = finding.classification.labels();
+        if !labels.is_empty() {
+            message += &format!(" ({})", labels);
+        }
+        if !finding.details.is_empty() {
+            message += &format!("\n{}", finding.details.join("\n"));
+        }
+        println!(
+            "::{} title={}::{}",
+            command,
+            escape_property(&finding.operation()),
+            escape_data(&message)
+        );
+    }
+}
+
+fn findings_table(findings: &Findings) -> String {
+    if findings.is_empty() {
+        return String::new();
+    }
+
+    let mut table =
+        String::from("| Operation | Method | Path | Status | Severity | CWE | OWASP | Count |\n");
+    table += "|---|---|---|---|---|---|---|---|\n";
+    for finding in findings.iter() {
+        table += &format!(
+            "| {} | {} | `{}` | {} | {} | {} | {} | {} |\n",
+            finding.operation(),
+            finding.method,
+            finding.path,
+            finding.status,
+            finding.severity,
+            finding.classification.cwe.unwrap_or("-"),
+            finding.classification.owasp.unwrap_or("-"),
+            finding.count
+        );
+    }
+    table
+}
+
+/// Appends a Markdown table of findings to the file in `GITHUB_STEP_SUMMARY`, if it is set
+pub fn github_step_summary(findings: &Findings) -> Result<()> {
+    let summary_file = match env::var_os("GITHUB_STEP_SUMMARY") {
+        Some(file) => file,
+        None => return Ok(()),
+    };
+
+    let summary = format!(
+        "### OpenAPI fuzzer found {} issue(s)\n\n{}",
+        findings.len(),
+        findings_table(findings)
+    );
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&summary_file)
+        .and_then(|mut file| file.write_all(summary.as_bytes()))
+        .context("unable to write GitHub step summary")
+}
+
+fn escape_csv(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Writes a summary of findings as CSV
+pub fn csv(findings: &Findings, file: &Path) -> Result<()> {
+    let mut csv = String::from(
+        "operation,method,path,status,classification,severity,count,first_seen,cwe,owasp,verdict\n",
+    );
+    for finding in findings.iter() {
+        let row = [
+            finding.operation(),
+            finding.method.clone(),
+            finding.path.clone(),
+            finding.status.to_string(),
+            finding.kind.to_string(),
+            finding.severity.to_string(),
+            finding.count.to_string(),
+            humantime::format_rfc3339_seconds(finding.first_seen).to_string(),
+            finding.classification.cwe.unwrap_or_default().to_string(),
+            finding.classification.owasp.unwrap_or_default().to_string(),
+            finding
+                .verification
+                .as_ref()
+                .map(|verification| verification.verdict.to_string())
+                .unwrap_or_default(),
+        ];
+        csv += &row
+            .iter()
+            .map(|s| escape_csv(s))
+            .collect::<Vec<_>>()
+            .join(",");
+        csv += "\n";
+    }
+
+    fs::write(file, csv).context(format!("unable to write {}", file.display()))
+}
+
+/// Writes a compact Markdown summary of the run, e.g. to be posted as a pull request comment
+pub fn markdown_summary(
+    findings: &Findings,
+    stats: &Stats,
+    operations: usize,
+    namespace: Option<&str>,
+    file: &Path,
+) -> Result<()> {
+    let tries = stats
+        .frequencies
+        .iter()
+        .flat_map(|(path, methods)| {
+            methods
+                .iter()
+                .map(move |(method, tries)| (method, path, tries))
+        })
+        .collect::<Vec<_>>();
+    let covered = tries.iter().filter(|(_, _, t)| t.documented > 0).count();
+    let coverage = 100.0 * covered as f64 / operations.max(1) as f64;
+
+    let mut summary = String::from("## OpenAPI fuzzer summary\n\n");
+    summary += &format!(
+        "Sent **{}** requests, **{}/{}** operations ({:.1}%) returned a documented response.\n\n",
+        stats.total, covered, operations, coverage
+    );
+    if let Some(namespace) = namespace {
+        summary += &format!(
+            "Emails, usernames and names created by the run start with `{}`.\n\n",
+            namespace
+        );
+    }
+
+    summary += &format!("### Findings ({})\n\n", findings.len());
+    match findings.is_empty() {
+        true => summary += "No findings.\n\n",
+        false => summary += &(findings_table(findings) + "\n"),
+    }
+
+    if !stats.outages.is_empty() {
+        summary += "### Outages\n\n";
+        summary += "| Operation | From | To | Responses |\n|---|---|---|---|\n";
+        for outage in stats.outages.iter() {
+            let operation = match &outage.operation {
+                Some((method, path)) => format!("{} `{}`", method, path),
+                None => "whole API".to_string(),
+            };
+            summary += &format!(
+                "| {} | {} | {} | {} |\n",
+                operation, outage.start, outage.end, outage.responses
+            );
+        }
+        summary += "\n";
+    }
+
+    let mut slowest = tries;
+    slowest.sort_by_key(|(_, _, tries)| std::cmp::Reverse(tries.average_time()));
+    if !slowest.is_empty() {
+        summary += "### Slowest operations\n\n";
+        summary += "| Method | Path | Requests | Average time |\n|---|---|---|---|\n";
+        for (method, path, tries) in slowest.iter().take(SLOWEST_OPERATIONS) {
+            summary += &format!(
+                "| {} | `{}` | {} | {} ms |\n",
+                method,
+                path,
+                tries.total,
+                tries.average_time().as_millis()
+            );
+        }
+    }
+
+    fs::write(file, summary).context(format!("unable to write {}", file.display()))
+}
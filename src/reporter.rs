@@ -0,0 +1,321 @@
+//! Sinks of the findings of a run: each new finding is reported to all of them as it is found,
+//! and all findings once the run is over. Implement `Reporter` for another target of the
+//! reports and add it to the fuzzer with `FuzzerBuilder::reporter`.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::finding::{Finding, Findings, Severity};
+use crate::issues::{IssueTemplate, Issues, Tracker};
+use crate::notify::Notifier;
+use crate::report;
+
+/// Target of the reports of a run
+pub trait Reporter: Send {
+    /// Reports a new deduplicated finding as soon as it is found
+    fn finding(&mut self, _finding: &Finding) -> Result<()> {
+        Ok(())
+    }
+
+    /// Reports all findings of the run once it is over
+    fn finish(&mut self, _findings: &Findings, _requests: u32) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Reporter given as `kind` or `kind:target`: `console`, `jsonl:<file>`, `sarif:<file>`,
+/// `webhook:<url>`, `webhook-findings:<url>`, `github:<owner/repo>` or `gitlab:<project>`
+#[derive(Debug, Clone)]
+pub enum ReporterSpec {
+    Console,
+    Jsonl(PathBuf),
+    Sarif(PathBuf),
+    Webhook { url: Url, each_finding: bool },
+    Issues { tracker: Tracker, project: String },
+}
+
+impl FromStr for ReporterSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, target) = match s.split_once(':') {
+            Some((kind, target)) => (kind, Some(target)),
+            None => (s, None),
+        };
+        let webhook = |each_finding| match target.map(Url::parse) {
+            Some(Ok(url)) => Ok(ReporterSpec::Webhook { url, each_finding }),
+            _ => Err(format!(
+                "invalid reporter `{}`, expected `{}:<url>`",
+                s, kind
+            )),
+        };
+        match (kind, target) {
+            ("console", None) => Ok(ReporterSpec::Console),
+            ("jsonl", Some(file)) if !file.is_empty() => Ok(ReporterSpec::Jsonl(file.into())),
+            ("sarif", Some(file)) if !file.is_empty() => Ok(ReporterSpec::Sarif(file.into())),
+            ("webhook", _) => webhook(false),
+            ("webhook-findings", _) => webhook(true),
+            ("github" | "gitlab", Some(project)) if !project.is_empty() => {
+                Ok(ReporterSpec::Issues {
+                    tracker: kind.parse()?,
+                    project: project.to_string(),
+                })
+            }
+            _ => Err(format!(
+                "invalid reporter `{}`, expected console, jsonl:<file>, sarif:<file>, \
+                 webhook:<url>, webhook-findings:<url>, github:<owner/repo> or \
+                 gitlab:<project>",
+                s
+            )),
+        }
+    }
+}
+
+impl ReporterSpec {
+    /// The reporter, the results of SARIF reports point to the specification file and issues
+    /// are written with the template
+    pub fn build(&self, specfile: &str, issues: &IssueTemplate) -> Result<Box<dyn Reporter>> {
+        Ok(match self {
+            ReporterSpec::Console => Box::new(Console),
+            ReporterSpec::Jsonl(file) => Box::new(Jsonl::create(file)?),
+            ReporterSpec::Sarif(file) => Box::new(Sarif {
+                file: file.clone(),
+                specfile: specfile.to_string(),
+            }),
+            ReporterSpec::Webhook { url, each_finding } => {
+                Box::new(Notifier::new(url.clone(), *each_finding))
+            }
+            ReporterSpec::Issues { tracker, project } => {
+                Box::new(Issues::new(*tracker, project, issues.clone())?)
+            }
+        })
+    }
+}
+
+impl Reporter for Notifier {
+    fn finding(&mut self, finding: &Finding) -> Result<()> {
+        Notifier::finding(self, finding)
+    }
+
+    fn finish(&mut self, findings: &Findings, requests: u32) -> Result<()> {
+        self.summary(findings, requests)
+    }
+}
+
+/// Prints a table of the findings to the standard error once the run is over
+pub struct Console;
+
+impl Reporter for Console {
+    fn finish(&mut self, findings: &Findings, requests: u32) -> Result<()> {
+        let rows = findings
+            .iter()
+            .map(|finding| {
+                vec![
+                    finding.severity.to_string(),
+                    finding.kind.to_string(),
+                    finding.method.clone(),
+                    finding.path.clone(),
+                    finding.status.to_string(),
+                    finding.count.to_string(),
+                    finding.file.clone(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        if !rows.is_empty() {
+            eprint!(
+                "{}",
+                report::table(
+                    &["SEVERITY", "KIND", "METHOD", "PATH", "STATUS", "COUNT", "FILE"],
+                    &rows
+                )
+            );
+        }
+        eprintln!("{} findings in {} requests", findings.len(), requests);
+        Ok(())
+    }
+}
+
+/// Appends each finding as a line of JSON to the file as it is found, e.g. to tail it
+pub struct Jsonl {
+    file: File,
+}
+
+impl Jsonl {
+    pub fn create(path: &PathBuf) -> Result<Jsonl> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(format!("unable to open {}", path.display()))?;
+        Ok(Jsonl { file })
+    }
+}
+
+impl Reporter for Jsonl {
+    fn finding(&mut self, finding: &Finding) -> Result<()> {
+        let mut line = serde_json::to_vec(finding)?;
+        line.push(b'\n');
+        self.file
+            .write_all(&line)
+            .context("unable to write the finding")
+    }
+}
+
+/// Writes the findings as a SARIF log once the run is over, e.g. for GitHub code scanning
+pub struct Sarif {
+    file: PathBuf,
+    specfile: String,
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Low => "note",
+        Severity::Medium => "warning",
+        Severity::High | Severity::Critical => "error",
+    }
+}
+
+/// Score GitHub ranks security alerts by
+fn security_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Low => "3.0",
+        Severity::Medium => "5.0",
+        Severity::High => "7.5",
+        Severity::Critical => "9.5",
+    }
+}
+
+impl Reporter for Sarif {
+    fn finish(&mut self, findings: &Findings, _requests: u32) -> Result<()> {
+        let mut rules = BTreeMap::new();
+        let mut results = Vec::new();
+        for finding in findings.iter() {
+            let labels = finding
+                .classification
+                .cwe
+                .iter()
+                .chain(finding.classification.owasp.iter())
+                .collect::<Vec<_>>();
+            rules.entry(finding.kind.to_string()).or_insert_with(|| {
+                json!({
+                    "id": finding.kind.to_string(),
+                    "shortDescription": {"text": finding.kind.to_string()},
+                    "properties": {
+                        "tags": labels,
+                        "security-severity": security_severity(finding.severity),
+                    },
+                })
+            });
+            let mut message = format!(
+                "{} {} returned {} ({} times), payload saved in {}",
+                finding.method, finding.path, finding.status, finding.count, finding.file
+            );
+            for detail in finding.details.iter() {
+                message += &format!("\n{}", detail);
+            }
+            results.push(json!({
+                "ruleId": finding.kind.to_string(),
+                "level": level(finding.severity),
+                "message": {"text": message},
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {"uri": self.specfile},
+                        "region": {"startLine": 1},
+                    },
+                    "logicalLocations": [{"name": finding.operation(), "kind": "function"}],
+                }],
+                "partialFingerprints": {
+                    "operation": format!(
+                        "{} {} {} {}",
+                        finding.method, finding.path, finding.status, finding.kind
+                    ),
+                },
+            }));
+        }
+        let log = json!({
+            "version": "2.1.0",
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "openapi-fuzzer",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "rules": rules.into_values().collect::<Vec<Value>>(),
+                    },
+                },
+                "results": results,
+            }],
+        });
+        fs::write(&self.file, serde_json::to_vec_pretty(&log)?)
+            .context(format!("unable to write {}", self.file.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_reporters() {
+        assert!(matches!("console".parse(), Ok(ReporterSpec::Console)));
+        assert!(matches!(
+            "jsonl:findings.jsonl".parse(),
+            Ok(ReporterSpec::Jsonl(file)) if file.to_str() == Some("findings.jsonl")
+        ));
+        assert!(matches!(
+            "sarif:results.sarif".parse(),
+            Ok(ReporterSpec::Sarif(file)) if file.to_str() == Some("results.sarif")
+        ));
+        assert!(matches!(
+            "webhook:https://hooks.example.com/a".parse(),
+            Ok(ReporterSpec::Webhook { url, each_finding: false })
+                if url.as_str() == "https://hooks.example.com/a"
+        ));
+        assert!(matches!(
+            "webhook-findings:https://hooks.example.com/a".parse(),
+            Ok(ReporterSpec::Webhook {
+                each_finding: true,
+                ..
+            })
+        ));
+        assert!(matches!(
+            "github:owner/repo".parse(),
+            Ok(ReporterSpec::Issues { tracker: Tracker::Github, project }) if project == "owner/repo"
+        ));
+        assert!(matches!(
+            "gitlab:group/project".parse(),
+            Ok(ReporterSpec::Issues {
+                tracker: Tracker::Gitlab,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_reporters() {
+        for spec in [
+            "console:stderr",
+            "jsonl",
+            "jsonl:",
+            "sarif",
+            "webhook",
+            "webhook:not a url",
+            "github:",
+            "slack:channel",
+        ] {
+            assert!(spec.parse::<ReporterSpec>().is_err(), "{}", spec);
+        }
+    }
+}
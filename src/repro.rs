@@ -0,0 +1,382 @@
+//! Reproduction tests of recorded findings in the stack of the developers fixing them: Rust tests
+//! with `reqwest`, pytest tests with `requests` or a k6 script. Each test sends the request of
+//! the finding to `API_URL`, the scheme, host and port of the API, and fails until the finding
+//! is fixed.
+
+use std::{fmt::Write, str::FromStr};
+
+use anyhow::Result;
+
+use crate::database::Recorded;
+use crate::encryption::Encryption;
+use crate::finding::FindingKind;
+use crate::transport::Request;
+use crate::triage;
+
+/// Headers of the request that the HTTP clients set themselves
+const SKIPPED_HEADERS: [&str; 3] = ["host", "content-length", "transfer-encoding"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestFormat {
+    Rust,
+    Pytest,
+    K6,
+}
+
+impl FromStr for TestFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rust" => Ok(TestFormat::Rust),
+            "pytest" | "python" => Ok(TestFormat::Pytest),
+            "k6" => Ok(TestFormat::K6),
+            _ => Err(format!(
+                "unknown test format `{}`, expected rust, pytest or k6",
+                s
+            )),
+        }
+    }
+}
+
+/// What the response must be like once the finding is fixed
+#[derive(Debug, PartialEq)]
+enum Expectation {
+    NoServerError,
+    /// The invalid request is rejected
+    ClientError,
+    NotStatus(u16),
+    /// No generic check, e.g. of a contract violation, the test fails with the details
+    Manual(String),
+}
+
+impl Expectation {
+    fn of(finding: &Recorded) -> Expectation {
+        let kind = serde_json::from_value::<FindingKind>(finding.kind.clone().into()).ok();
+        match kind {
+            Some(FindingKind::ServerError) => Expectation::NoServerError,
+            Some(FindingKind::PathTraversal) if finding.status >= 500 => Expectation::NoServerError,
+            Some(FindingKind::ValidationGap) => Expectation::ClientError,
+            Some(FindingKind::UndocumentedStatus) | Some(FindingKind::UnexpectedStatus) => {
+                Expectation::NotStatus(finding.status)
+            }
+            _ if finding.details.is_empty() => Expectation::Manual(format!(
+                "{} (status {}), check the response",
+                finding.kind, finding.status
+            )),
+            _ => Expectation::Manual(finding.details.join("; ")),
+        }
+    }
+}
+
+/// Request of a finding split into what the tests need
+struct Reproduction {
+    name: String,
+    title: String,
+    method: String,
+    /// Path and query, appended to `API_URL`
+    target: String,
+    origin: String,
+    headers: Vec<(String, String)>,
+    body: Option<Vec<u8>>,
+    expectation: Expectation,
+}
+
+impl Reproduction {
+    fn new(finding: &Recorded, request: Request) -> Result<Reproduction> {
+        let mut target = request.url.path().to_string();
+        if let Some(query) = request.url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+        let mut headers = request
+            .headers
+            .into_iter()
+            .filter(|(name, _)| !SKIPPED_HEADERS.contains(&name.to_lowercase().as_str()))
+            .collect::<Vec<_>>();
+        let body = match (request.raw_body, request.body) {
+            (Some(raw), _) => Some(raw),
+            (None, Some(body)) => {
+                // Set by the transport when it sends JSON
+                if !headers
+                    .iter()
+                    .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+                {
+                    headers.push(("content-type".to_string(), "application/json".to_string()));
+                }
+                Some(serde_json::to_vec(&body)?)
+            }
+            (None, None) => None,
+        };
+        let name = format!("{}_{}", finding.kind, finding.signature)
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c,
+                false => '_',
+            })
+            .collect();
+        Ok(Reproduction {
+            name,
+            title: format!(
+                "{} of {} {}, status {}",
+                finding.kind, finding.method, finding.path, finding.status
+            ),
+            method: request.method,
+            target,
+            origin: request.url.origin().ascii_serialization(),
+            headers,
+            body,
+            expectation: Expectation::of(finding),
+        })
+    }
+}
+
+/// Escaped string, valid in Python and JavaScript
+fn json_string(s: &str) -> String {
+    serde_json::Value::from(s).to_string()
+}
+
+/// Escaped bytes of a Rust or Python byte string
+fn byte_string(bytes: &[u8]) -> String {
+    let escaped = bytes
+        .iter()
+        .flat_map(|byte| std::ascii::escape_default(*byte))
+        .map(char::from)
+        .collect::<String>();
+    format!("b\"{}\"", escaped)
+}
+
+fn rust(reproductions: &[Reproduction]) -> Result<String> {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "//! Reproductions of findings of openapi-fuzzer, they fail until the findings are fixed."
+    )?;
+    writeln!(
+        out,
+        "//! Needs `reqwest` with the `blocking` feature as a dev-dependency."
+    )?;
+    if let Some(first) = reproductions.first() {
+        writeln!(out)?;
+        writeln!(out, "fn api_url() -> String {{")?;
+        writeln!(
+            out,
+            "    std::env::var(\"API_URL\").unwrap_or_else(|_| {:?}.to_string())",
+            first.origin
+        )?;
+        writeln!(out, "}}")?;
+    }
+    for reproduction in reproductions.iter() {
+        writeln!(out)?;
+        writeln!(out, "/// {}", reproduction.title)?;
+        writeln!(out, "#[test]")?;
+        writeln!(out, "fn {}() {{", reproduction.name)?;
+        writeln!(out, "    let response = reqwest::blocking::Client::new()")?;
+        writeln!(
+            out,
+            "        .request(reqwest::Method::from_bytes({:?}.as_bytes()).unwrap(), format!(\"{{}}{{}}\", api_url(), {:?}))",
+            reproduction.method, reproduction.target
+        )?;
+        for (name, value) in reproduction.headers.iter() {
+            writeln!(out, "        .header({:?}, {:?})", name, value)?;
+        }
+        match reproduction.body.as_deref().map(std::str::from_utf8) {
+            Some(Ok(body)) => writeln!(out, "        .body({:?})", body)?,
+            Some(Err(_)) => writeln!(
+                out,
+                "        .body(&{}[..])",
+                byte_string(reproduction.body.as_deref().unwrap_or_default())
+            )?,
+            None => {}
+        }
+        writeln!(out, "        .send()")?;
+        writeln!(out, "        .unwrap();")?;
+        writeln!(out, "    let status = response.status().as_u16();")?;
+        match &reproduction.expectation {
+            Expectation::NoServerError => {
+                writeln!(out, "    assert!(status < 500, \"server error {{}}\", status);")?
+            }
+            Expectation::ClientError => writeln!(
+                out,
+                "    assert!((400..500).contains(&status), \"invalid request accepted with {{}}\", status);"
+            )?,
+            Expectation::NotStatus(expected) => writeln!(
+                out,
+                "    assert_ne!(status, {}, \"the status is still {}\");",
+                expected, expected
+            )?,
+            Expectation::Manual(details) => {
+                writeln!(out, "    let _ = status;")?;
+                writeln!(out, "    panic!(\"{{}}\", {:?});", details)?;
+            }
+        }
+        writeln!(out, "}}")?;
+    }
+    Ok(out)
+}
+
+fn pytest(reproductions: &[Reproduction]) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "\"\"\"Reproductions of findings of openapi-fuzzer, they fail until the findings are fixed.\"\"\"")?;
+    writeln!(out)?;
+    writeln!(out, "import os")?;
+    writeln!(out)?;
+    writeln!(out, "import pytest")?;
+    writeln!(out, "import requests")?;
+    if let Some(first) = reproductions.first() {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "API_URL = os.environ.get(\"API_URL\", {})",
+            json_string(&first.origin)
+        )?;
+    }
+    for reproduction in reproductions.iter() {
+        writeln!(out)?;
+        writeln!(out)?;
+        writeln!(out, "def test_{}():", reproduction.name)?;
+        writeln!(out, "    \"\"\"{}\"\"\"", reproduction.title)?;
+        writeln!(out, "    response = requests.request(")?;
+        writeln!(out, "        {},", json_string(&reproduction.method))?;
+        writeln!(
+            out,
+            "        API_URL + {},",
+            json_string(&reproduction.target)
+        )?;
+        writeln!(out, "        headers={{")?;
+        for (name, value) in reproduction.headers.iter() {
+            writeln!(
+                out,
+                "            {}: {},",
+                json_string(name),
+                json_string(value)
+            )?;
+        }
+        writeln!(out, "        }},")?;
+        match reproduction.body.as_deref().map(std::str::from_utf8) {
+            Some(Ok(body)) => writeln!(out, "        data={}.encode(),", json_string(body))?,
+            Some(Err(_)) => writeln!(
+                out,
+                "        data={},",
+                byte_string(reproduction.body.as_deref().unwrap_or_default())
+            )?,
+            None => {}
+        }
+        writeln!(out, "    )")?;
+        match &reproduction.expectation {
+            Expectation::NoServerError => writeln!(
+                out,
+                "    assert response.status_code < 500, f\"server error {{response.status_code}}\""
+            )?,
+            Expectation::ClientError => writeln!(
+                out,
+                "    assert 400 <= response.status_code < 500, f\"invalid request accepted with {{response.status_code}}\""
+            )?,
+            Expectation::NotStatus(expected) => writeln!(
+                out,
+                "    assert response.status_code != {}, \"the status is still {}\"",
+                expected, expected
+            )?,
+            Expectation::Manual(details) => {
+                writeln!(out, "    pytest.fail({})", json_string(details))?
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn k6(reproductions: &[Reproduction]) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "// Reproductions of findings of openapi-fuzzer, the checks fail until the findings are fixed.")?;
+    writeln!(out, "import http from \"k6/http\";")?;
+    writeln!(out, "import encoding from \"k6/encoding\";")?;
+    writeln!(out, "import {{ check, fail, group }} from \"k6\";")?;
+    writeln!(out)?;
+    writeln!(out, "export const options = {{")?;
+    writeln!(out, "  iterations: 1,")?;
+    writeln!(out, "  thresholds: {{ checks: [\"rate==1.0\"] }},")?;
+    writeln!(out, "}};")?;
+    if let Some(first) = reproductions.first() {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "const API_URL = __ENV.API_URL || {};",
+            json_string(&first.origin)
+        )?;
+    }
+    writeln!(out)?;
+    writeln!(out, "export default function () {{")?;
+    for reproduction in reproductions.iter() {
+        writeln!(
+            out,
+            "  group({}, () => {{",
+            json_string(&reproduction.title)
+        )?;
+        let body = match &reproduction.body {
+            Some(body) => format!("encoding.b64decode({})", json_string(&base64::encode(body))),
+            None => "null".to_string(),
+        };
+        writeln!(
+            out,
+            "    const response = http.request({}, API_URL + {}, {}, {{",
+            json_string(&reproduction.method),
+            json_string(&reproduction.target),
+            body
+        )?;
+        writeln!(out, "      headers: {{")?;
+        for (name, value) in reproduction.headers.iter() {
+            writeln!(
+                out,
+                "        {}: {},",
+                json_string(name),
+                json_string(value)
+            )?;
+        }
+        writeln!(out, "      }},")?;
+        writeln!(out, "    }});")?;
+        let check = match &reproduction.expectation {
+            Expectation::NoServerError => "\"no server error\": (r) => r.status < 500".to_string(),
+            Expectation::ClientError => {
+                "\"invalid request rejected\": (r) => r.status >= 400 && r.status < 500".to_string()
+            }
+            Expectation::NotStatus(expected) => format!(
+                "\"status is not {}\": (r) => r.status !== {}",
+                expected, expected
+            ),
+            Expectation::Manual(details) => {
+                writeln!(out, "    fail({});", json_string(details))?;
+                writeln!(out, "  }});")?;
+                continue;
+            }
+        };
+        writeln!(
+            out,
+            "    check(response, {{ {} }}, {{ finding: {} }});",
+            check,
+            json_string(&reproduction.name)
+        )?;
+        writeln!(out, "  }});")?;
+    }
+    writeln!(out, "}}")?;
+    Ok(out)
+}
+
+/// Reproduction tests of the findings in the format, from the requests stored in their files
+pub fn render(
+    format: TestFormat,
+    findings: &[Recorded],
+    encryption: &Encryption,
+) -> Result<String> {
+    let reproductions = findings
+        .iter()
+        .map(|finding| {
+            let (request, _) = triage::exchange(finding, encryption)?;
+            Reproduction::new(finding, request)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    match format {
+        TestFormat::Rust => rust(&reproductions),
+        TestFormat::Pytest => pytest(&reproductions),
+        TestFormat::K6 => k6(&reproductions),
+    }
+}
@@ -0,0 +1,157 @@
+//! Round trips of resources: the current representation of the resource an update is sent to
+//! is fetched with the `GET` of its path, a few of its fields are changed and it is sent back,
+//! whole with `PUT` and only the changed fields with `PATCH`. The payloads keep the values,
+//! formats and relations of a real resource, which schema generated ones rarely have.
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+use serde_json::{Map, Value};
+
+/// Probability that an update is sent as a round trip if the resource could be fetched
+pub const ROUND_TRIP_PROBABILITY: f64 = 0.5;
+
+/// Most fields changed in a representation
+const MAX_MUTATIONS: usize = 3;
+
+/// Nesting of the representation searched for fields to change
+const MAX_DEPTH: usize = 5;
+
+/// Whether the method updates the resource at its path
+pub fn is_update(method: &str) -> bool {
+    matches!(method, "PUT" | "PATCH")
+}
+
+/// Pointers to the fields of the value, nested objects are searched for more
+fn fields(value: &Value, pointer: String, depth: usize, pointers: &mut Vec<String>) {
+    if let Value::Object(object) = value {
+        for (name, value) in object.iter() {
+            let pointer = format!("{}/{}", pointer, name.replace('~', "~0").replace('/', "~1"));
+            match value {
+                Value::Object(_) if depth < MAX_DEPTH => {
+                    fields(value, pointer, depth + 1, pointers)
+                }
+                _ => pointers.push(pointer),
+            }
+        }
+    }
+}
+
+/// Another value of the same kind, or one a client might send by mistake
+fn mutated(value: &Value, rng: &mut StdRng) -> Value {
+    match value {
+        Value::Bool(b) => Value::Bool(!b),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(n), _) => [
+                n.wrapping_add(1),
+                n.wrapping_sub(1),
+                0,
+                n.wrapping_neg(),
+                i64::MAX,
+                i64::MIN,
+            ]
+            .choose(rng)
+            .map(|&n| Value::from(n))
+            .unwrap_or(Value::Null),
+            (None, Some(n)) => [n * 1e6, -n, 0.0, n / 3.0]
+                .choose(rng)
+                .map(|&n| Value::from(n))
+                .unwrap_or(Value::Null),
+            _ => Value::from(0),
+        },
+        Value::String(s) => match rng.gen_range(0..5) {
+            0 => Value::String(String::new()),
+            1 => Value::String(s.repeat(64).chars().take(4096).collect()),
+            2 => Value::String(format!("{} ü€𝄞", s)),
+            3 => Value::String(s.to_uppercase()),
+            _ => Value::String(format!(" {} ", s)),
+        },
+        Value::Array(items) => match items.first() {
+            Some(item) if rng.gen_bool(0.5) => {
+                let mut items = items.clone();
+                items.push(item.clone());
+                Value::Array(items)
+            }
+            _ => Value::Array(Vec::new()),
+        },
+        Value::Object(_) => Value::Object(Map::new()),
+        Value::Null => Value::String(String::new()),
+    }
+}
+
+/// The fetched representation with a few fields changed, set to null or removed, with only
+/// the changed top level fields if the update is partial. `None` if it is not an object.
+pub fn mutate(representation: &Value, partial: bool, rng: &mut StdRng) -> Option<Value> {
+    let mut pointers = Vec::new();
+    fields(representation, String::new(), 0, &mut pointers);
+    if pointers.is_empty() {
+        return None;
+    }
+    let count = rng.gen_range(1..=MAX_MUTATIONS.min(pointers.len()));
+    let changed = pointers
+        .choose_multiple(rng, count)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let mut body = representation.clone();
+    for pointer in changed.iter() {
+        let (parent, name) = pointer.rsplit_once('/')?;
+        let name = name.replace("~1", "/").replace("~0", "~");
+        let object = match body.pointer_mut(parent) {
+            Some(Value::Object(object)) => object,
+            _ => continue,
+        };
+        match rng.gen_range(0..10) {
+            0 => {
+                object.remove(&name);
+            }
+            1 => {
+                object.insert(name, Value::Null);
+            }
+            _ => {
+                if let Some(value) = object.get_mut(&name) {
+                    *value = mutated(value, rng);
+                }
+            }
+        }
+    }
+    if !partial {
+        return Some(body);
+    }
+
+    // Removed fields cannot be sent in a partial update, they are sent as null instead
+    let mut patch = Map::new();
+    for pointer in changed.iter() {
+        let name = pointer[1..].split('/').next().unwrap_or_default();
+        let name = name.replace("~1", "/").replace("~0", "~");
+        let value = body.get(&name).cloned().unwrap_or(Value::Null);
+        patch.insert(name, value);
+    }
+    Some(Value::Object(patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use serde_json::json;
+
+    #[test]
+    fn mutates_extreme_integers() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for n in [i64::MAX, i64::MIN] {
+            for _ in 0..32 {
+                assert!(mutated(&Value::from(n), &mut rng).is_i64());
+            }
+        }
+    }
+
+    #[test]
+    fn sends_only_changed_fields_of_partial_updates() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let representation = json!({"id": 1, "name": "a", "address": {"city": "b"}});
+        let patch = mutate(&representation, true, &mut rng).unwrap();
+        let patch = patch.as_object().unwrap();
+        assert!(!patch.is_empty());
+        assert!(patch.keys().all(|name| representation.get(name).is_some()));
+        assert!(mutate(&json!([1]), false, &mut rng).is_none());
+    }
+}
@@ -0,0 +1,193 @@
+//! Samples of the successful responses of each operation, as evidence of what normal looked
+//! like when triaging findings: the first responses with a status and shape of the body no
+//! sample of the operation has yet are kept, a bounded number per operation, and saved to
+//! `samples.jsonl` in the results directory. Its lines are entries of a corpus, so later runs
+//! can start from the seeds of the samples with `--corpus`.
+
+use std::{collections::BTreeMap, fs::OpenOptions, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::corpus::Seed;
+use crate::encryption::Encryption;
+use crate::redact::Secrets;
+use crate::transport::Response;
+
+/// File of the samples in the results directory
+pub const SAMPLES_FILE: &str = "samples.jsonl";
+
+/// Successful response of an operation with the seed of its request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    pub method: String,
+    pub path: String,
+    pub seed: Seed,
+    /// Status and structure of the body the samples are told apart by
+    pub shape: String,
+    pub response: Response,
+}
+
+/// Structure of the JSON value: the names and types of the fields, arrays by their first
+/// element
+fn structure(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(_) => "bool".to_string(),
+        Value::Number(_) => "number".to_string(),
+        Value::String(_) => "string".to_string(),
+        Value::Array(values) => format!("[{}]", values.first().map(structure).unwrap_or_default()),
+        Value::Object(object) => {
+            let mut fields = object
+                .iter()
+                .map(|(name, value)| format!("{}:{}", name, structure(value)))
+                .collect::<Vec<_>>();
+            fields.sort();
+            format!("{{{}}}", fields.join(","))
+        }
+    }
+}
+
+/// Digest of the status and structure of the body, of its content type if it is not JSON
+fn shape(response: &Response) -> String {
+    let structure = match serde_json::from_str::<Value>(&response.body) {
+        Ok(body) => structure(&body),
+        Err(_) => response
+            .header("content-type")
+            .unwrap_or_default()
+            .to_string(),
+    };
+    let key = format!("{}\n{}", response.status, structure);
+    ring::digest::digest(&ring::digest::SHA256, key.as_bytes())
+        .as_ref()
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Distinct successful responses of the operations, by method and path
+#[derive(Debug, Default)]
+pub struct Samples {
+    max_per_operation: usize,
+    samples: BTreeMap<(String, String), Vec<Sample>>,
+    /// Whether the run took samples, the others were loaded
+    taken: bool,
+}
+
+impl Samples {
+    pub fn new(max_per_operation: usize) -> Samples {
+        Samples {
+            max_per_operation,
+            ..Samples::default()
+        }
+    }
+
+    /// Whether a sample of the shape would be kept for the operation
+    fn accepts(&self, method: &str, path: &str, shape: &str) -> bool {
+        self.samples
+            .get(&(method.to_string(), path.to_string()))
+            .is_none_or(|samples| {
+                samples.len() < self.max_per_operation
+                    && !samples.iter().any(|sample| sample.shape == shape)
+            })
+    }
+
+    /// Keeps the sample unless the operation has enough or one of the same shape
+    fn insert(&mut self, sample: Sample) -> bool {
+        if !self.accepts(&sample.method, &sample.path, &sample.shape) {
+            return false;
+        }
+        self.samples
+            .entry((sample.method.clone(), sample.path.clone()))
+            .or_default()
+            .push(sample);
+        true
+    }
+
+    /// Keeps the successful response if it is the first of its shape for the operation, with
+    /// the secrets replaced
+    pub fn observe(
+        &mut self,
+        method: &str,
+        path: &str,
+        seed: impl FnOnce() -> Seed,
+        response: &Response,
+        secrets: Option<&Secrets>,
+    ) -> Result<()> {
+        if response.status / 100 != 2 {
+            return Ok(());
+        }
+        let shape = shape(response);
+        if !self.accepts(method, path, &shape) {
+            return Ok(());
+        }
+        let mut sample = Sample {
+            method: method.to_string(),
+            path: path.to_string(),
+            seed: seed(),
+            shape,
+            response: response.clone(),
+        };
+        if let Some(secrets) = secrets {
+            let mut value = serde_json::to_value(&sample)?;
+            secrets.redact(&mut value);
+            sample = serde_json::from_value(value)?;
+        }
+        self.taken |= self.insert(sample);
+        Ok(())
+    }
+
+    /// Sample of the operation taken last
+    pub fn latest(&self, method: &str, path: &str) -> Option<&Sample> {
+        self.samples
+            .get(&(method.to_string(), path.to_string()))
+            .and_then(|samples| samples.last())
+    }
+
+    /// Adds the samples of the file, if there is one
+    pub fn load(&mut self, file: &Path, encryption: &Encryption) -> Result<()> {
+        if !file.exists() {
+            return Ok(());
+        }
+        let contents = encryption.read_to_string(file)?;
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let sample = serde_json::from_str(line)
+                .with_context(|| format!("invalid sample on line {}", i + 1))?;
+            self.insert(sample);
+        }
+        Ok(())
+    }
+
+    /// Adds the samples taken by the run to the file, after the ones saved there in the
+    /// meantime, e.g. by the other jobs. The file is locked while it is read and written, so
+    /// that jobs saving at the same time keep each other's samples.
+    pub fn save(&self, file: &Path, encryption: &Encryption) -> Result<()> {
+        if !self.taken {
+            return Ok(());
+        }
+        let lock = format!("{}.lock", file.display());
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock)
+            .context(format!("unable to open {}", lock))?;
+        lock.lock()?;
+        let mut saved = Samples::new(self.max_per_operation);
+        saved.load(file, encryption)?;
+        for sample in self.samples.values().flatten() {
+            saved.insert(sample.clone());
+        }
+        let mut contents = String::new();
+        for sample in saved.samples.values().flatten() {
+            contents += &serde_json::to_string(sample)?;
+            contents.push('\n');
+        }
+        encryption.write(file, contents.as_bytes())
+    }
+}
@@ -0,0 +1,362 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use openapi_utils::ReferenceOrExt;
+use openapiv3::{OpenAPI, Operation};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::payload::{operations, Payload};
+use crate::transport::Response;
+
+/// Probability that a field chosen to be fuzzed is mutated in a run of the scenario
+const MUTATE_PROBABILITY: f64 = 0.5;
+
+/// Probability that a mutated field takes the value generated from the schema of the
+/// operation rather than an edge case, if the generated payload has the field
+const GENERATED_PROBABILITY: f64 = 0.5;
+
+/// Ordered operations with variables bound from the responses, e.g. creating a cart, adding
+/// an item to it and checking it out. Each round runs the steps in order while mutating the
+/// fields chosen with `fuzz`, the first run mutates nothing.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// The file name without the extension by default
+    #[serde(default)]
+    pub name: String,
+    /// Initial values of the variables
+    #[serde(default)]
+    pub variables: BTreeMap<String, Value>,
+    pub steps: Vec<Step>,
+}
+
+/// Request of a scenario, `{{name}}` in strings is replaced by the value of the variable
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    /// Method and path of the operation in the specification, e.g. `POST /carts/{cartId}`.
+    /// Path parameters without a value in `path` take the variable with their name.
+    pub request: String,
+    #[serde(default)]
+    pub path: BTreeMap<String, String>,
+    #[serde(default)]
+    pub query: BTreeMap<String, String>,
+    /// Sent in addition to the headers passed to the fuzzer
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: Option<Value>,
+    /// Fields mutated by the fuzzer, e.g. `body.items.0.quantity` (or just
+    /// `items.0.quantity`), `query.coupon`, `headers.x-currency` or `path.cartId`
+    #[serde(default)]
+    pub fuzz: Vec<String>,
+    /// Variables bound from the response, e.g. `cartId: body.id` or
+    /// `location: headers.location`
+    #[serde(default)]
+    pub bind: BTreeMap<String, String>,
+}
+
+/// Method, path and operation of the specification given as in `POST /carts/{cartId}`
+pub(crate) fn operation<'a>(
+    schema: &'a OpenAPI,
+    request: &str,
+) -> Result<(&'static str, &'a str, &'a Operation)> {
+    let (method, path) = request
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| anyhow!("expected a method and a path, got `{}`", request))?;
+    let (path, item) = schema
+        .paths
+        .iter()
+        .find(|(p, _)| p.as_str() == path.trim())
+        .ok_or_else(|| anyhow!("no path `{}` in the specification", path.trim()))?;
+    operations(item.to_item_ref())
+        .into_iter()
+        .find_map(|(m, operation)| match operation {
+            Some(operation) if m.eq_ignore_ascii_case(method) => Some((m, operation)),
+            _ => None,
+        })
+        .map(|(method, operation)| (method, path.as_str(), operation))
+        .ok_or_else(|| anyhow!("no operation `{}`", request))
+}
+
+/// Part of a request or response
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Location<'a> {
+    Path(&'a str),
+    Query(&'a str),
+    Header(&'a str),
+    /// Keys and array indices, empty for the whole body
+    Body(Vec<&'a str>),
+}
+
+impl<'a> Location<'a> {
+    pub(crate) fn parse(location: &'a str) -> Location<'a> {
+        let (part, rest) = location.split_once('.').unwrap_or((location, ""));
+        match part {
+            "path" if !rest.is_empty() => Location::Path(rest),
+            "query" if !rest.is_empty() => Location::Query(rest),
+            "header" | "headers" if !rest.is_empty() => Location::Header(rest),
+            "body" if rest.is_empty() => Location::Body(Vec::new()),
+            "body" => Location::Body(rest.split('.').collect()),
+            _ => Location::Body(location.split('.').collect()),
+        }
+    }
+}
+
+impl Scenario {
+    /// Loads the scenario from a YAML or TOML file and checks that its operations exist
+    pub fn load(file: &Path, schema: &OpenAPI) -> Result<Scenario> {
+        let mut scenario: Scenario = crate::config::load(file)?;
+        if scenario.name.is_empty() {
+            scenario.name = file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        for (i, step) in scenario.steps.iter().enumerate() {
+            step.operation(schema).with_context(|| {
+                format!("invalid step {} of scenario `{}`", i + 1, scenario.name)
+            })?;
+        }
+        Ok(scenario)
+    }
+}
+
+impl Step {
+    /// Method, path and operation of the step in the specification
+    pub fn operation<'a>(
+        &self,
+        schema: &'a OpenAPI,
+    ) -> Result<(&'static str, &'a str, &'a Operation)> {
+        operation(schema, &self.request)
+    }
+
+    /// Replaces the generated values of the payload by the ones of the step, except for the
+    /// fields to fuzz that are chosen to be mutated. Fails when a variable is not bound.
+    pub fn apply<'a>(
+        &'a self,
+        payload: &mut Payload<'a>,
+        extra_headers: &[(String, String)],
+        variables: &BTreeMap<String, Value>,
+        mutate: bool,
+        rng: &mut StdRng,
+    ) -> Result<()> {
+        let mutated = self
+            .fuzz
+            .iter()
+            .filter(|_| mutate && rng.gen_bool(MUTATE_PROBABILITY))
+            .map(|field| Location::parse(field))
+            .collect::<Vec<_>>();
+        for (name, value) in payload.path_params.iter_mut() {
+            if mutated.contains(&Location::Path(name)) {
+                continue;
+            }
+            *value = match self.path.get(*name) {
+                Some(template) => render(template, variables)?,
+                None => match variables.get(*name) {
+                    Some(variable) => to_string(variable),
+                    None => return Err(anyhow!("variable `{}` is not bound", name)),
+                },
+            };
+        }
+
+        let generated_query = std::mem::take(&mut payload.query_params);
+        for (name, template) in self.query.iter() {
+            let value = render(template, variables)?;
+            let value = match mutated.contains(&Location::Query(name)) {
+                true => {
+                    let generated = generated_query
+                        .iter()
+                        .find(|(n, _)| n == name)
+                        .map(|(_, value)| Value::String(value.clone()));
+                    to_string(&mutated_value(generated, &Value::String(value), rng))
+                }
+                false => value,
+            };
+            payload.query_params.push((name, value));
+        }
+
+        let generated_headers = std::mem::take(&mut payload.headers);
+        for (name, value) in generated_headers.iter() {
+            if extra_headers
+                .iter()
+                .any(|(n, _)| n.eq_ignore_ascii_case(name))
+            {
+                payload.headers.push((name, value.clone()));
+            }
+        }
+        for (name, template) in self.headers.iter() {
+            let value = render(template, variables)?;
+            let value = match mutated.contains(&Location::Header(name)) {
+                true => {
+                    let generated = generated_headers
+                        .iter()
+                        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                        .map(|(_, value)| Value::String(value.clone()));
+                    // Other characters can not be sent in headers
+                    to_string(&mutated_value(generated, &Value::String(value), rng))
+                        .chars()
+                        .filter(|c| c.is_ascii_graphic() || *c == ' ')
+                        .collect()
+                }
+                false => value,
+            };
+            payload
+                .headers
+                .retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+            payload.headers.push((name, value));
+        }
+
+        let generated_body = payload.body.first().cloned();
+        payload.body.clear();
+        if let Some(template) = &self.body {
+            let mut body = render_json(template, variables)?;
+            for location in mutated.iter() {
+                if let Location::Body(keys) = location {
+                    let generated = generated_body
+                        .as_ref()
+                        .and_then(|generated| get(generated, keys))
+                        .cloned();
+                    if let Some(value) = get_mut(&mut body, keys) {
+                        *value = mutated_value(generated, value, rng);
+                    }
+                }
+            }
+            payload.body.push(body);
+        }
+        Ok(())
+    }
+
+    /// Binds the variables from the response, fails when one is missing
+    pub fn bind(&self, response: &Response, variables: &mut BTreeMap<String, Value>) -> Result<()> {
+        let body = serde_json::from_str::<Value>(&response.body).ok();
+        for (variable, location) in self.bind.iter() {
+            let value = match Location::parse(location) {
+                Location::Header(name) => response.header(name).map(Value::from),
+                Location::Body(keys) => body.as_ref().and_then(|body| get(body, &keys)).cloned(),
+                _ => None,
+            };
+            match value {
+                Some(value) => variables.insert(variable.clone(), value),
+                None => {
+                    return Err(anyhow!(
+                        "no `{}` in the response to bind `{}` to",
+                        location,
+                        variable
+                    ))
+                }
+            };
+        }
+        Ok(())
+    }
+}
+
+/// Value of a mutated field, the one generated from the schema or an edge case
+fn mutated_value(generated: Option<Value>, value: &Value, rng: &mut StdRng) -> Value {
+    match generated {
+        Some(generated) if rng.gen_bool(GENERATED_PROBABILITY) => generated,
+        _ => edge_value(value, rng),
+    }
+}
+
+/// Replaces `{{name}}` in the string by the value of the variable
+fn render(template: &str, variables: &BTreeMap<String, Value>) -> Result<String> {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| anyhow!("unterminated variable in `{}`", template))?;
+        let name = rest[start + 2..start + end].trim();
+        let value = variables
+            .get(name)
+            .ok_or_else(|| anyhow!("variable `{}` is not bound", name))?;
+        result.push_str(&to_string(value));
+        rest = &rest[start + end + 2..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Renders the strings in the value, a string that is just a variable takes its value, e.g.
+/// a number
+fn render_json(template: &Value, variables: &BTreeMap<String, Value>) -> Result<Value> {
+    Ok(match template {
+        Value::String(s) => {
+            let name = s
+                .strip_prefix("{{")
+                .and_then(|s| s.strip_suffix("}}"))
+                .map(str::trim)
+                .filter(|name| !name.contains("{{"));
+            match name.and_then(|name| variables.get(name)) {
+                Some(value) => value.clone(),
+                None => Value::String(render(s, variables)?),
+            }
+        }
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| render_json(value, variables))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), render_json(value, variables)?)))
+                .collect::<Result<_>>()?,
+        ),
+        value => value.clone(),
+    })
+}
+
+pub(crate) fn to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+pub(crate) fn get<'a>(value: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    keys.iter().try_fold(value, |value, key| match value {
+        Value::Array(values) => values.get(key.parse::<usize>().ok()?),
+        value => value.get(key),
+    })
+}
+
+fn get_mut<'a>(value: &'a mut Value, keys: &[&str]) -> Option<&'a mut Value> {
+    keys.iter().try_fold(value, |value, key| match value {
+        Value::Array(values) => values.get_mut(key.parse::<usize>().ok()?),
+        value => value.get_mut(key),
+    })
+}
+
+/// Value likely to be mishandled in place of the value, mostly of the same type
+fn edge_value(value: &Value, rng: &mut StdRng) -> Value {
+    let candidates: Vec<Value> = match value {
+        Value::Number(_) => vec![
+            0.into(),
+            (-1).into(),
+            i64::MAX.into(),
+            i64::MIN.into(),
+            1e308.into(),
+            Value::from("1"),
+        ],
+        Value::Bool(b) => vec![(!b).into(), Value::from(b.to_string()), Value::Null],
+        Value::Array(_) => vec![Value::Array(Vec::new()), Value::Null],
+        Value::Object(_) => vec![Value::Object(Default::default()), Value::Null],
+        _ => vec![
+            "".into(),
+            "A".repeat(10_000).into(),
+            "' OR '1'='1".into(),
+            "../../../../etc/passwd".into(),
+            "\u{0}".into(),
+            "\u{202e}\u{1f4a9}".into(),
+            Value::Null,
+        ],
+    };
+    candidates.choose(rng).cloned().unwrap_or(Value::Null)
+}
@@ -0,0 +1,147 @@
+//! Timestamps and HMAC signatures of requests to APIs with replay protection, added to every
+//! request before it is sent
+
+use std::{
+    fmt,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use ring::hmac;
+
+use crate::transport::Request;
+
+/// Message signed by default, `{path}` includes the query
+pub const DEFAULT_MESSAGE: &str = "{timestamp}\\n{method}\\n{path}\\n{body}";
+
+/// Format of the timestamp in the header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeFormat {
+    /// Seconds since the epoch
+    Unix,
+    /// Milliseconds since the epoch
+    UnixMillis,
+    Rfc3339,
+    /// e.g. `Sun, 06 Nov 1994 08:49:37 GMT`, as in the `Date` header
+    HttpDate,
+}
+
+impl TimeFormat {
+    pub fn format(&self, time: SystemTime) -> String {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        match self {
+            TimeFormat::Unix => since_epoch.as_secs().to_string(),
+            TimeFormat::UnixMillis => since_epoch.as_millis().to_string(),
+            TimeFormat::Rfc3339 => humantime::format_rfc3339_seconds(time).to_string(),
+            TimeFormat::HttpDate => httpdate::fmt_http_date(time),
+        }
+    }
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unix" => Ok(TimeFormat::Unix),
+            "unix-ms" => Ok(TimeFormat::UnixMillis),
+            "rfc3339" => Ok(TimeFormat::Rfc3339),
+            "http-date" => Ok(TimeFormat::HttpDate),
+            _ => Err(format!(
+                "unknown timestamp format `{}`, expected unix, unix-ms, rfc3339 or http-date",
+                s
+            )),
+        }
+    }
+}
+
+/// Header with the time of the request given as `Name: format` or just `Name`, the format is
+/// `http-date` for the `Date` header and `unix` for others by default
+#[derive(Debug, Clone)]
+pub struct TimestampHeader {
+    pub name: String,
+    pub format: TimeFormat,
+}
+
+impl FromStr for TimestampHeader {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, format) = match s.split_once(':') {
+            Some((name, format)) => (name.trim(), Some(format.trim().parse()?)),
+            None => (s.trim(), None),
+        };
+        if name.is_empty() {
+            return Err("missing name of the timestamp header".to_string());
+        }
+        let format = format.unwrap_or(match name.eq_ignore_ascii_case("date") {
+            true => TimeFormat::HttpDate,
+            false => TimeFormat::Unix,
+        });
+        Ok(TimestampHeader {
+            name: name.to_lowercase(),
+            format,
+        })
+    }
+}
+
+impl fmt::Display for TimestampHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Stamps requests with their time and signs them with HMAC-SHA256
+#[derive(Debug, Clone)]
+pub struct Signing {
+    pub timestamp: Option<TimestampHeader>,
+    /// Header with the hex encoded signature and the secret
+    pub signature: Option<(String, String)>,
+    /// Signed message with the `{timestamp}`, `{method}`, `{path}` and `{body}` placeholders
+    /// and `\n` for line breaks
+    pub message: String,
+}
+
+/// Replaces the value of the header or adds it
+fn set_header(request: &mut Request, name: &str, value: String) {
+    request
+        .headers
+        .retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+    request.headers.push((name.to_string(), value));
+}
+
+impl Signing {
+    /// Sets the timestamp of the request to the time and signs it
+    pub fn sign(&self, request: &mut Request, time: SystemTime) -> Result<()> {
+        let timestamp = match &self.timestamp {
+            Some(header) => {
+                let timestamp = header.format.format(time);
+                set_header(request, &header.name, timestamp.clone());
+                timestamp
+            }
+            None => String::new(),
+        };
+        if let Some((header, secret)) = &self.signature {
+            let mut path = request.url.path().to_string();
+            if let Some(query) = request.url.query() {
+                path = path + "?" + query;
+            }
+            let message = self
+                .message
+                .replace("\\n", "\n")
+                .replace("{timestamp}", &timestamp)
+                .replace("{method}", &request.method)
+                .replace("{path}", &path)
+                .replace("{body}", &request.body_string()?.unwrap_or_default());
+            let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+            let signature = hmac::sign(&key, message.as_bytes())
+                .as_ref()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            set_header(request, header, signature);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+//! Limits of the sizes of requests learned from the responses rejecting them outright: the
+//! smallest body answered with `413 Content Too Large`, headers answered with `431 Request
+//! Header Fields Too Large` and URL answered with `414 URI Too Long` of each operation. Later
+//! payloads of the operation are shrunk below the limit, halving their generated strings and
+//! arrays, so the requests reach the handlers instead of the limits of the server. Raw bodies,
+//! e.g. multipart ones and filled templates, are sent as they are, cutting them would break
+//! their syntax. Some are still sent as generated to notice a limit that was raised or depends
+//! on more than the size.
+
+use std::collections::BTreeMap;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::payload::Payload;
+use crate::pool;
+use crate::transport::Response;
+
+/// Probability that a payload above the limit of its operation is sent as generated
+const PROBE_PROBABILITY: f64 = 0.1;
+
+/// Times a payload is halved at most, the sizes of payloads that are still above the limit
+/// then are not in their generated parts
+const MAX_SHRINKS: usize = 16;
+
+/// Parts of a request whose size a server limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    Body,
+    Headers,
+    Url,
+}
+
+const PARTS: [Part; 3] = [Part::Body, Part::Headers, Part::Url];
+
+/// Halves the string, false if it has a character or none
+fn halve_string(string: &mut String) -> bool {
+    let chars = string.chars().count();
+    match string.char_indices().nth(chars / 2) {
+        Some((end, _)) if chars > 1 => {
+            string.truncate(end);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Halves the strings and arrays of the value, arrays keep an item, false if none is left to
+/// halve
+fn halve_value(value: &mut Value) -> bool {
+    match value {
+        Value::String(string) => halve_string(string),
+        Value::Array(items) => {
+            let mut halved = false;
+            if items.len() > 1 {
+                items
+                    .split_off(items.len().div_ceil(2))
+                    .into_iter()
+                    .for_each(pool::recycle);
+                halved = true;
+            }
+            items
+                .iter_mut()
+                .fold(halved, |halved, item| halve_value(item) | halved)
+        }
+        Value::Object(object) => object
+            .values_mut()
+            .fold(false, |halved, value| halve_value(value) | halved),
+        _ => false,
+    }
+}
+
+impl Part {
+    /// Part of the request too large for the server, by the status of its response
+    fn rejected_by(status: u16) -> Option<Part> {
+        match status {
+            413 => Some(Part::Body),
+            414 => Some(Part::Url),
+            431 => Some(Part::Headers),
+            _ => None,
+        }
+    }
+
+    /// Size of the part of the request of the payload, before it is encoded
+    fn size(self, payload: &Payload) -> usize {
+        match self {
+            Part::Body => match (&payload.raw_body, payload.body.first()) {
+                (Some(raw), _) => raw.len(),
+                (None, Some(body)) => serde_json::to_vec(body).map_or(0, |body| body.len()),
+                (None, None) => 0,
+            },
+            Part::Headers => payload
+                .headers
+                .iter()
+                .map(|(name, value)| name.len() + value.len() + 4)
+                .sum(),
+            Part::Url => {
+                payload.path.len()
+                    + payload
+                        .path_params
+                        .iter()
+                        .map(|(_, value)| value.len())
+                        .sum::<usize>()
+                    + payload
+                        .query_params
+                        .iter()
+                        .map(|(name, value)| name.len() + value.len() + 2)
+                        .sum::<usize>()
+            }
+        }
+    }
+
+    /// Halves the generated values of the part, the headers of the command line and config are
+    /// kept, false if none is left to halve
+    fn halve(self, payload: &mut Payload, extra_headers: &[(String, String)]) -> bool {
+        match self {
+            Part::Body => match (&payload.raw_body, payload.body.first_mut()) {
+                (None, Some(body)) => halve_value(body),
+                _ => false,
+            },
+            Part::Headers => payload
+                .headers
+                .iter_mut()
+                .filter(|(name, _)| {
+                    !extra_headers
+                        .iter()
+                        .any(|(extra, _)| extra.eq_ignore_ascii_case(name))
+                })
+                .fold(false, |halved, (_, value)| halve_string(value) | halved),
+            Part::Url => payload
+                .path_params
+                .iter_mut()
+                .chain(payload.query_params.iter_mut())
+                .fold(false, |halved, (_, value)| halve_string(value) | halved),
+        }
+    }
+}
+
+/// Smallest sizes of the parts of requests rejected, by method and path of the operation
+#[derive(Debug, Default)]
+pub struct SizeLimits {
+    limits: BTreeMap<(String, String), [Option<usize>; 3]>,
+}
+
+impl SizeLimits {
+    /// Learns the limit of the part of the request the response rejects as too large, or
+    /// forgets the limit a larger part got through. Empty parts rejected as too large have no
+    /// size to learn.
+    pub fn observe(&mut self, payload: &Payload, response: &Response) {
+        let rejected = Part::rejected_by(response.status);
+        let key = (payload.method.to_string(), payload.path.to_string());
+        let limits = match (self.limits.get_mut(&key), rejected) {
+            (Some(limits), _) => limits,
+            (None, Some(_)) => self.limits.entry(key).or_default(),
+            (None, None) => return,
+        };
+        for (i, part) in PARTS.iter().enumerate() {
+            let size = part.size(payload);
+            match (rejected == Some(*part), limits[i]) {
+                (true, _) if size == 0 => {}
+                (true, None) => {
+                    info!(
+                        method = payload.method,
+                        path = payload.path,
+                        ?part,
+                        size,
+                        "request too large, later ones are kept smaller"
+                    );
+                    limits[i] = Some(size);
+                }
+                (true, Some(limit)) if size < limit => limits[i] = Some(size),
+                (false, Some(limit)) if rejected.is_none() && size >= limit => {
+                    debug!(
+                        method = payload.method,
+                        path = payload.path,
+                        ?part,
+                        size,
+                        "larger request accepted, limit forgotten"
+                    );
+                    limits[i] = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Shrinks the parts of the payload at or above the limits of its operation, except for
+    /// some probing whether the limits still hold
+    pub fn fit(&self, payload: &mut Payload, extra_headers: &[(String, String)]) {
+        let limits = match self
+            .limits
+            .get(&(payload.method.to_string(), payload.path.to_string()))
+        {
+            Some(limits) => *limits,
+            None => return,
+        };
+        // Drawn from the seed of the payload to leave the other random choices as they were
+        let mut rng = StdRng::seed_from_u64(payload.seed.rotate_left(16));
+        for (part, limit) in PARTS.iter().zip(limits.iter()) {
+            let limit = match limit {
+                Some(limit) if part.size(payload) >= *limit => *limit,
+                _ => continue,
+            };
+            if rng.gen_bool(PROBE_PROBABILITY) {
+                debug!(
+                    method = payload.method,
+                    path = payload.path,
+                    ?part,
+                    "probing the size limit"
+                );
+                continue;
+            }
+            let mut shrinks = 0;
+            while part.size(payload) >= limit
+                && shrinks < MAX_SHRINKS
+                && part.halve(payload, extra_headers)
+            {
+                shrinks += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openapiv3::Responses;
+    use serde_json::json;
+    use url::Url;
+
+    #[test]
+    fn halves_strings_and_arrays() {
+        let mut value = json!({"name": "abcd", "tags": ["ab", "c", "d"], "n": 1});
+        assert!(halve_value(&mut value));
+        assert_eq!(value, json!({"name": "ab", "tags": ["a", "c"], "n": 1}));
+        let mut value = json!({"name": "a", "tags": ["a"], "n": 1});
+        assert!(!halve_value(&mut value));
+        let mut string = "żółw".to_string();
+        assert!(halve_string(&mut string));
+        assert_eq!(string, "żó");
+    }
+
+    #[test]
+    fn learns_limits_of_parts_with_a_size() {
+        let url = Url::parse("http://localhost").unwrap();
+        let responses = Responses::default();
+        let mut payload = Payload {
+            url: &url,
+            method: "POST",
+            path: "/uploads",
+            operation_id: None,
+            query_params: Vec::new(),
+            path_params: Vec::new(),
+            headers: Vec::new(),
+            body: Vec::new(),
+            raw_body: Some(b"--boundary".to_vec()),
+            seed: 0,
+            responses: &responses,
+            idempotent: false,
+            violation: None,
+            negative: false,
+            timeout: None,
+            endpoint: None,
+        };
+        let response = Response {
+            status: 413,
+            status_text: String::new(),
+            http_version: String::new(),
+            headers: Vec::new(),
+            body: String::new(),
+            events: Vec::new(),
+            elapsed: Default::default(),
+        };
+        let mut limits = SizeLimits::default();
+        limits.observe(&payload, &response);
+        // Raw bodies are not cut
+        assert!(!Part::Body.halve(&mut payload, &[]));
+
+        payload.raw_body = None;
+        limits.observe(&payload, &response);
+        let key = ("POST".to_string(), "/uploads".to_string());
+        assert_eq!(limits.limits[&key][0], Some(10));
+    }
+}
@@ -0,0 +1,183 @@
+//! Realistic strings of fields recognized by their names, e.g. `email`, `websiteUrl`, `phone`,
+//! `zipCode` or `color`, or by formats the generators have no strategy of their own for. Many
+//! specifications leave out the formats, and backends validating the fields reject random
+//! strings before they reach interesting code. The fields are recognized like the ones `faker`
+//! fills, the values here follow no locale and are drawn from the random data of the payload.
+//! Values outside of the lengths of the schema are left random.
+
+use arbitrary::Unstructured;
+use openapiv3::{StringType, VariantOrUnknownOrEmpty};
+
+pub use crate::faker::Kind;
+
+use crate::faker;
+
+/// Percentage of the values of a recognized field drawn from its kind, the others are
+/// arbitrary strings probing the validation of the field
+const SMART_PERCENTAGE: u8 = 80;
+
+const USERS: &[&str] = &["alice", "bob", "carol", "dave", "erin", "frank"];
+
+const WORDS: &[&str] = &["shop", "api", "docs", "blog", "files", "status"];
+
+const COUNTRIES: &[&str] = &["US", "GB", "DE", "FR", "JP", "BR", "IN", "NL"];
+
+const CURRENCIES: &[&str] = &["USD", "EUR", "GBP", "JPY", "CHF", "BRL"];
+
+const LANGUAGES: &[&str] = &["en", "en-US", "de-DE", "fr", "ja", "pt-BR"];
+
+const TIME_ZONES: &[&str] = &[
+    "UTC",
+    "Europe/Berlin",
+    "America/New_York",
+    "Asia/Tokyo",
+    "Australia/Sydney",
+];
+
+/// Kind of the string field with the name, by its format if it has one, `None` for the kinds
+/// without a value here
+pub fn kind(name: &str, string_type: &StringType) -> Option<Kind> {
+    // The values would not match the pattern, and the other formats have their own strategies
+    let format = match &string_type.format {
+        _ if string_type.pattern.is_some() => return None,
+        VariantOrUnknownOrEmpty::Unknown(format) => Some(format.as_str()),
+        VariantOrUnknownOrEmpty::Empty => None,
+        VariantOrUnknownOrEmpty::Item(_) => return None,
+    };
+    faker::kind(name, format).filter(|kind| {
+        matches!(
+            kind,
+            Kind::Email
+                | Kind::Url
+                | Kind::Phone
+                | Kind::PostalCode
+                | Kind::Color
+                | Kind::Uuid
+                | Kind::Ipv4
+                | Kind::Ipv6
+                | Kind::Hostname
+                | Kind::Country
+                | Kind::Currency
+                | Kind::Language
+                | Kind::TimeZone
+        )
+    })
+}
+
+/// Whether the value has a length between the lengths of the schema
+pub fn fits(value: &str, min_length: Option<usize>, max_length: Option<usize>) -> bool {
+    let length = value.chars().count();
+    min_length.is_none_or(|min| length >= min) && max_length.is_none_or(|max| length <= max)
+}
+
+fn digits(count: usize, gen: &mut Unstructured) -> Option<String> {
+    (0..count)
+        .map(|_| Some(char::from(b'0' + gen.int_in_range(0..=9u8).ok()?)))
+        .collect()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Realistic value of the kind, `None` to keep the random string
+pub fn value(kind: Kind, gen: &mut Unstructured) -> Option<String> {
+    let keep_random = gen.int_in_range(0..=99u8).ok()? >= SMART_PERCENTAGE;
+    if keep_random {
+        return None;
+    }
+    Some(match kind {
+        Kind::Email => format!(
+            "{}{}@example.com",
+            gen.choose(USERS).ok()?,
+            gen.int_in_range(1..=999u16).ok()?
+        ),
+        Kind::Url => format!(
+            "https://{}.example.com/{}",
+            gen.choose(WORDS).ok()?,
+            gen.choose(WORDS).ok()?
+        ),
+        Kind::Phone => format!("+1555{}", digits(7, gen)?),
+        Kind::PostalCode => digits(5, gen)?,
+        Kind::Color => format!("#{}", hex(&gen.arbitrary::<[u8; 3]>().ok()?)),
+        Kind::Uuid => {
+            let mut bytes = gen.arbitrary::<[u8; 16]>().ok()?;
+            // Version 4 and the variant of RFC 4122
+            bytes[6] = (bytes[6] & 0x0f) | 0x40;
+            bytes[8] = (bytes[8] & 0x3f) | 0x80;
+            let hex = hex(&bytes);
+            format!(
+                "{}-{}-{}-{}-{}",
+                &hex[..8],
+                &hex[8..12],
+                &hex[12..16],
+                &hex[16..20],
+                &hex[20..]
+            )
+        }
+        Kind::Ipv4 => {
+            let [a, b, c, d] = gen.arbitrary::<[u8; 4]>().ok()?;
+            format!("{}.{}.{}.{}", a.max(1), b, c, d)
+        }
+        Kind::Ipv6 => {
+            let groups = gen.arbitrary::<[u16; 6]>().ok()?;
+            let groups = groups
+                .iter()
+                .map(|group| format!("{:x}", group))
+                .collect::<Vec<_>>();
+            format!("2001:db8:{}", groups.join(":"))
+        }
+        Kind::Hostname => format!("{}.example.com", gen.choose(WORDS).ok()?),
+        Kind::Country => gen.choose(COUNTRIES).ok()?.to_string(),
+        Kind::Currency => gen.choose(CURRENCIES).ok()?.to_string(),
+        Kind::Language => gen.choose(LANGUAGES).ok()?.to_string(),
+        Kind::TimeZone => gen.choose(TIME_ZONES).ok()?.to_string(),
+        // Names and addresses are left to `faker`, which knows their locale
+        Kind::FirstName
+        | Kind::LastName
+        | Kind::FullName
+        | Kind::Street
+        | Kind::City
+        | Kind::Iban => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openapiv3::StringFormat;
+
+    #[test]
+    fn recognizes_fields_by_name_and_format() {
+        let plain = StringType::default();
+        assert_eq!(kind("contactEmail", &plain), Some(Kind::Email));
+        assert_eq!(kind("website_url", &plain), Some(Kind::Url));
+        assert_eq!(kind("zipCode", &plain), Some(Kind::PostalCode));
+        assert_eq!(kind("ip", &plain), Some(Kind::Ipv4));
+        // Names and addresses are filled by `faker` only
+        assert_eq!(kind("firstName", &plain), None);
+        assert_eq!(kind("description", &plain), None);
+
+        let format = |format: VariantOrUnknownOrEmpty<StringFormat>| StringType {
+            format,
+            ..StringType::default()
+        };
+        let uuid = format(VariantOrUnknownOrEmpty::Unknown("uuid".to_string()));
+        assert_eq!(kind("reference", &uuid), Some(Kind::Uuid));
+        let date = format(VariantOrUnknownOrEmpty::Item(StringFormat::Date));
+        assert_eq!(kind("email", &date), None);
+        let pattern = StringType {
+            pattern: Some("^[a-z]+$".to_string()),
+            ..StringType::default()
+        };
+        assert_eq!(kind("email", &pattern), None);
+    }
+
+    #[test]
+    fn checks_the_lengths() {
+        assert!(fits("de-DE", None, None));
+        assert!(fits("de-DE", Some(5), Some(5)));
+        assert!(!fits("de-DE", None, Some(2)));
+        assert!(!fits("de", Some(5), None));
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    io::{self, Write},
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use url::Url;
+
+use crate::proxy::{self, Connection};
+use crate::transport::{Request, Response};
+
+/// Time to wait for the first byte of the response, servers waiting for the rest of the body
+/// indicate a disagreement on the length of the request
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time to wait for more data after a part of the response was received
+const IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Length of the raw probe included in the details of the finding
+const MAX_RAW_LENGTH: usize = 300;
+
+/// Request with malformed or conflicting framing
+struct Probe {
+    name: &'static str,
+    raw: String,
+    /// The request must be rejected, accepting it is a finding
+    must_reject: bool,
+}
+
+fn probes(request: &Request) -> Vec<Probe> {
+    let mut target = request.url.path().to_string();
+    if let Some(query) = request.url.query() {
+        target = target + "?" + query;
+    }
+    let host = match (request.url.host_str(), request.url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => String::new(),
+    };
+    let method = match request.method.as_str() {
+        "GET" | "HEAD" | "OPTIONS" | "TRACE" => "POST",
+        method => method,
+    };
+    let head = |method: &str, headers: &str| {
+        format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\n{}\r\n",
+            method, target, host, headers
+        )
+    };
+
+    vec![
+        Probe {
+            name: "CL.TE",
+            raw: head(
+                method,
+                "Content-Length: 4\r\nTransfer-Encoding: chunked\r\n",
+            ) + "1\r\nZ\r\nQ",
+            must_reject: false,
+        },
+        Probe {
+            name: "TE.CL",
+            raw: head(
+                method,
+                "Content-Length: 6\r\nTransfer-Encoding: chunked\r\n",
+            ) + "0\r\n\r\nX",
+            must_reject: false,
+        },
+        Probe {
+            name: "obfuscated Transfer-Encoding",
+            raw: head(
+                method,
+                "Content-Length: 4\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: x\r\n",
+            ) + "1\r\nZ\r\nQ",
+            must_reject: false,
+        },
+        Probe {
+            name: "duplicate Content-Length",
+            raw: head(method, "Content-Length: 2\r\nContent-Length: 7\r\n") + "{}",
+            must_reject: true,
+        },
+        Probe {
+            name: "oversized chunk extension",
+            raw: head(method, "Transfer-Encoding: chunked\r\n")
+                + &format!("2;{}\r\n{{}}\r\n0\r\n\r\n", "a".repeat(65536)),
+            must_reject: true,
+        },
+        Probe {
+            name: "malformed request line",
+            raw: format!(
+                "{}  {} HTTP/1.1 x\r\nHost: {}\r\n\r\n",
+                request.method, target, host
+            ),
+            must_reject: true,
+        },
+        Probe {
+            name: "bare line feeds",
+            raw: format!(
+                "{} {} HTTP/1.1\nHost: {}\nContent-Length: 0\n\n",
+                request.method, target, host
+            ),
+            must_reject: false,
+        },
+    ]
+}
+
+/// Opens a connection to the host of the url, returns it with the underlying socket to
+/// control its timeouts
+fn connect(url: &Url) -> Result<(Box<dyn Connection>, TcpStream)> {
+    let (connection, socket) = proxy::open(url)?;
+    socket.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    Ok((connection, socket))
+}
+
+/// Sends the raw request and returns the raw response, `None` if the server did not respond
+pub(crate) fn send_raw(url: &Url, raw: &str) -> Result<(Option<Vec<u8>>, Duration)> {
+    let (mut connection, socket) = connect(url)?;
+    let start = Instant::now();
+    // Servers may close the connection before the whole request is sent
+    match connection
+        .write_all(raw.as_bytes())
+        .and_then(|_| connection.flush())
+    {
+        Err(e)
+            if !matches!(
+                e.kind(),
+                io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset
+            ) =>
+        {
+            return Err(e.into())
+        }
+        _ => {}
+    }
+    let received = receive(&mut connection, &socket)?;
+    let elapsed = start.elapsed();
+    match received.is_empty() {
+        true => Ok((None, elapsed)),
+        false => Ok((Some(received), elapsed)),
+    }
+}
+
+/// Reads the responses until the server closes the connection or stops sending
+pub(crate) fn receive(connection: &mut dyn Connection, socket: &TcpStream) -> Result<Vec<u8>> {
+    let mut received = Vec::new();
+    let mut buffer = [0; 8192];
+    loop {
+        match connection.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => {
+                received.extend_from_slice(&buffer[..n]);
+                // Wait only shortly for more responses once something was received
+                socket.set_read_timeout(Some(IDLE_TIMEOUT))?;
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                break
+            }
+            // Servers often reset the connection after rejecting the request
+            Err(e) if e.kind() == io::ErrorKind::ConnectionReset && !received.is_empty() => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(received)
+}
+
+/// Parses the first response from raw data
+pub(crate) fn parse_response(raw: &[u8], elapsed: Duration) -> Response {
+    let raw = String::from_utf8_lossy(raw);
+    let (head, body) = raw.split_once("\r\n\r\n").unwrap_or((&raw, ""));
+    let mut lines = head.lines();
+    let mut status_line = lines.next().unwrap_or_default().splitn(3, ' ');
+    let http_version = status_line.next().unwrap_or_default().to_string();
+    let status = status_line
+        .next()
+        .and_then(|status| status.parse().ok())
+        .unwrap_or_default();
+    let status_text = status_line.next().unwrap_or_default().to_string();
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    Response {
+        status,
+        status_text,
+        http_version,
+        headers,
+        body: body.to_string(),
+        elapsed,
+        events: Vec::new(),
+    }
+}
+
+/// Number of HTTP responses in raw data
+fn count_responses(raw: &[u8]) -> usize {
+    let raw = String::from_utf8_lossy(raw);
+    raw.match_indices("HTTP/1.")
+        .filter(|(i, _)| *i == 0 || raw[..*i].ends_with('\n'))
+        .count()
+}
+
+/// Sends requests with malformed and conflicting framing to the endpoint of the request and
+/// returns the desync indicators with the first response showing them. A response without
+/// status code stands for no response.
+pub fn probe(request: &Request) -> Result<Option<(Response, Vec<String>)>> {
+    let mut indicators = Vec::new();
+    let mut first_response = None;
+    for probe in probes(request) {
+        let (raw, elapsed) = send_raw(&request.url, &probe.raw)?;
+        let (response, indicator) = match raw {
+            None => (
+                parse_response(b"", elapsed),
+                Some(format!(
+                    "{}: no response in {} s, the server may wait for the rest of the body",
+                    probe.name,
+                    RESPONSE_TIMEOUT.as_secs()
+                )),
+            ),
+            Some(raw) => {
+                let response = parse_response(&raw, elapsed);
+                let responses = count_responses(&raw);
+                let indicator = if responses > 1 {
+                    Some(format!(
+                        "{}: {} responses to a single request, part of the body was processed as another request",
+                        probe.name, responses
+                    ))
+                } else if response.status / 100 == 5 {
+                    Some(format!("{}: server error {}", probe.name, response.status))
+                } else if probe.must_reject && response.status / 100 == 2 {
+                    Some(format!(
+                        "{}: request accepted with status {}",
+                        probe.name, response.status
+                    ))
+                } else {
+                    None
+                };
+                (response, indicator)
+            }
+        };
+
+        if let Some(indicator) = indicator {
+            let raw = probe.raw.escape_debug().to_string();
+            let raw = match raw.char_indices().nth(MAX_RAW_LENGTH) {
+                Some((i, _)) => format!("{}...", &raw[..i]),
+                None => raw,
+            };
+            indicators.push(format!("{}, sent `{}`", indicator, raw));
+            first_response.get_or_insert(response);
+        }
+    }
+
+    Ok(first_response.map(|response| (response, indicators)))
+}
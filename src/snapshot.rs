@@ -0,0 +1,60 @@
+use std::{fs, path::Path, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::corpus::Seed;
+use crate::encryption::Encryption;
+use crate::finding::StoredFinding;
+use crate::fuzzer::Stats;
+
+/// Version of the format of snapshots, bump it when the format changes
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Complete state of a fuzzer, a run restored from it continues as the original run would
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub fuzzer_version: String,
+    /// SHA-256 of the specification file
+    pub spec_hash: String,
+    pub run_seed: u64,
+    /// Seed the random generator is reset to when the snapshot is taken
+    pub rng_seed: u64,
+    /// Number of finished rounds
+    pub rounds: u32,
+    pub fuzzing_time: Duration,
+    pub stats: Stats,
+    pub findings: Vec<StoredFinding>,
+    /// Seeds of the operations, with their methods and paths
+    pub corpus: Vec<(String, String, Seed)>,
+    /// Number of idempotency checks done for each operation, by path and method
+    pub idempotency_checks: Vec<(String, String, u32)>,
+}
+
+impl Snapshot {
+    pub fn load(file: &Path, encryption: &Encryption) -> Result<Snapshot> {
+        let content = encryption.read_to_string(file)?;
+        let snapshot: Snapshot = serde_json::from_str(&content)
+            .context(format!("invalid snapshot {}", file.display()))?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(anyhow!(
+                "unsupported version {} of snapshot {}",
+                snapshot.version,
+                file.display()
+            ));
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the snapshot to a temporary file first, so an interrupted write does not
+    /// destroy the previous snapshot
+    pub fn save(&self, file: &Path, encryption: &Encryption) -> Result<()> {
+        if let Some(dir) = file.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let partial = file.with_extension("partial");
+        encryption.write(&partial, &serde_json::to_vec(self)?)?;
+        fs::rename(&partial, file).context(format!("unable to write {}", file.display()))
+    }
+}
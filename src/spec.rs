@@ -0,0 +1,254 @@
+//! Loading of large specifications, e.g. the aggregated specification of a gateway with
+//! thousands of operations: the document is parsed once and shared with the generators reading
+//! it, the path items are converted and their references resolved in parallel, and the
+//! operations left out by the filters are dropped before the references are resolved, which
+//! copies the referenced schemas into every operation and takes most of the startup time.
+
+use std::thread;
+
+use anyhow::{anyhow, Context, Result};
+use openapi_utils::SpecExt;
+use openapiv3::{OpenAPI, PathItem, ReferenceOr};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::debug;
+
+use crate::payload::operations;
+use crate::status::Operations;
+
+/// Path items a thread converts or resolves at least, fewer are not worth a thread
+const MIN_CHUNK: usize = 64;
+
+/// Contiguous chunks of the items, one per available thread
+fn chunks<T>(items: Vec<T>) -> Vec<Vec<T>> {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let size = items.len().div_ceil(threads).max(MIN_CHUNK);
+    let mut chunks = Vec::new();
+    let mut items = items.into_iter().peekable();
+    while items.peek().is_some() {
+        chunks.push(items.by_ref().take(size).collect::<Vec<_>>());
+    }
+    chunks
+}
+
+/// Applies the function to the chunks on a thread each, the results in the order of the chunks
+fn parallel<T: Send, R: Send>(chunks: Vec<Vec<T>>, f: impl Fn(Vec<T>) -> R + Sync) -> Vec<R> {
+    if chunks.len() == 1 {
+        return chunks.into_iter().map(f).collect();
+    }
+    let f = &f;
+    thread::scope(|scope| {
+        let handles = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || f(chunk)))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .expect("a thread loading the specification panicked")
+            })
+            .collect()
+    })
+}
+
+/// Specification as it was read, for the generators reading what the typed specification
+/// leaves out
+#[derive(Debug)]
+pub struct Raw {
+    /// Content of the file, with the overlays and constraints of OpenAPI documents applied
+    pub content: String,
+    /// Document of the content, `Null` for GraphQL schemas
+    pub document: Value,
+}
+
+/// Parses the document of a specification, JSON ones with the JSON parser, which is much faster
+/// than the YAML one on them
+pub fn document(specfile: &str) -> Result<Value> {
+    Ok(match specfile.trim_start().starts_with('{') {
+        true => serde_json::from_str(specfile)?,
+        false => serde_yaml::from_str(specfile)?,
+    })
+}
+
+/// Path item of the document, the one a local `$ref` points to
+fn path_item(document: &Value, path: &str, item: &Value) -> Result<PathItem> {
+    let item = match item.get("$ref").and_then(Value::as_str) {
+        Some(reference) => reference
+            .strip_prefix('#')
+            .and_then(|pointer| document.pointer(pointer))
+            .ok_or_else(|| anyhow!("unresolved path item reference {}", reference))?,
+        None => item,
+    };
+    PathItem::deserialize(item).with_context(|| format!("invalid path item {}", path))
+}
+
+/// Converts the OpenAPI document, the path items in parallel
+pub fn parse(document: &Value) -> Result<OpenAPI> {
+    let paths = match document.get("paths") {
+        Some(Value::Object(paths)) => paths.iter().collect::<Vec<_>>(),
+        Some(Value::Null) | None => Vec::new(),
+        Some(_) => return Err(anyhow!("`paths` is not an object")),
+    };
+    let mut rest = document
+        .as_object()
+        .ok_or_else(|| anyhow!("the specification is not an object"))?
+        .iter()
+        .filter(|(name, _)| *name != "paths")
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect::<Map<_, _>>();
+    rest.insert("paths".to_string(), Value::Object(Map::new()));
+    let mut schema: OpenAPI = serde_json::from_value(Value::Object(rest))?;
+    let converted = parallel(chunks(paths), |chunk| {
+        chunk
+            .into_iter()
+            .map(|(path, item)| {
+                let item = path_item(document, path, item)?;
+                Ok((path.clone(), ReferenceOr::Item(item)))
+            })
+            .collect::<Result<Vec<_>>>()
+    });
+    for chunk in converted {
+        schema.paths.extend(chunk?);
+    }
+    Ok(schema)
+}
+
+/// Leaves out the operations matching none of the filters and the paths left without
+/// operations, nothing without filters
+pub fn select(schema: &mut OpenAPI, filters: &[Operations]) {
+    if filters.is_empty() {
+        return;
+    }
+    for (path, item) in schema.paths.iter_mut() {
+        let item = match item {
+            ReferenceOr::Item(item) => item,
+            ReferenceOr::Reference { .. } => continue,
+        };
+        for (method, operation) in [
+            ("GET", &mut item.get),
+            ("PUT", &mut item.put),
+            ("POST", &mut item.post),
+            ("DELETE", &mut item.delete),
+            ("OPTIONS", &mut item.options),
+            ("HEAD", &mut item.head),
+            ("PATCH", &mut item.patch),
+            ("TRACE", &mut item.trace),
+        ] {
+            let selected = operation.as_ref().is_some_and(|operation| {
+                filters
+                    .iter()
+                    .any(|filter| filter.matches(method, path, operation.operation_id.as_deref()))
+            });
+            if !selected {
+                *operation = None;
+            }
+        }
+    }
+    // References were resolved by the parser, the ones left can not be selected from
+    schema.paths.retain(|_, item| match item {
+        ReferenceOr::Item(item) => operations(item)
+            .iter()
+            .any(|(_, operation)| operation.is_some()),
+        ReferenceOr::Reference { .. } => false,
+    });
+    debug!(paths = schema.paths.len(), "operations selected");
+}
+
+/// Resolves the references of the path items, in parallel. Each chunk is resolved with the
+/// components, all but the last one with a copy of them.
+pub fn resolve(mut schema: OpenAPI) -> OpenAPI {
+    let paths = std::mem::take(&mut schema.paths)
+        .into_iter()
+        .collect::<Vec<_>>();
+    let chunks = chunks(paths);
+    let mut components = schema.components.take();
+    let count = chunks.len();
+    let specs = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            vec![OpenAPI {
+                paths: chunk.into_iter().collect(),
+                components: match i + 1 == count {
+                    true => components.take(),
+                    false => components.clone(),
+                },
+                ..OpenAPI::default()
+            }]
+        })
+        .collect::<Vec<_>>();
+    let resolved = parallel(specs, |mut spec| spec.pop().map(SpecExt::deref_all));
+    for resolved in resolved.into_iter().flatten() {
+        schema.paths.extend(resolved.paths);
+        if resolved.components.is_some() {
+            schema.components = resolved.components;
+        }
+    }
+    // Left if there are no paths
+    schema.components = schema.components.or(components);
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openapi_utils::ReferenceOrExt;
+    use serde_json::json;
+
+    fn spec() -> Value {
+        json!({
+            "openapi": "3.0.0",
+            "info": {"title": "pets", "version": "1"},
+            "paths": {
+                "/pets": {
+                    "get": {"operationId": "listPets", "responses": {}},
+                    "post": {"operationId": "addPet", "responses": {}}
+                },
+                "/pets/{id}": {"$ref": "#/x-path-items/pet"}
+            },
+            "x-path-items": {
+                "pet": {"delete": {"operationId": "deletePet", "responses": {}}}
+            },
+            "components": {"schemas": {"Pet": {"type": "object"}}}
+        })
+    }
+
+    #[test]
+    fn resolves_references_of_path_items() {
+        let schema = parse(&spec()).unwrap();
+        let item = schema.paths["/pets/{id}"].to_item_ref();
+        assert!(item.delete.is_some());
+
+        let mut broken = spec();
+        broken["paths"]["/pets/{id}"] = json!({"$ref": "pets.yaml#/pet"});
+        assert!(parse(&broken).is_err());
+    }
+
+    #[test]
+    fn selects_the_operations_of_the_filters() {
+        let mut schema = parse(&spec()).unwrap();
+        select(
+            &mut schema,
+            &["GET /pets".parse().unwrap(), "deletePet".parse().unwrap()],
+        );
+        let pets = schema.paths["/pets"].to_item_ref();
+        assert!(pets.get.is_some() && pets.post.is_none());
+        assert!(schema.paths["/pets/{id}"].to_item_ref().delete.is_some());
+
+        select(&mut schema, &["POST".parse().unwrap()]);
+        assert!(schema.paths.is_empty());
+    }
+
+    #[test]
+    fn keeps_the_components_when_resolving() {
+        let schema = resolve(parse(&spec()).unwrap());
+        assert_eq!(schema.paths.len(), 2);
+        assert!(schema.components.unwrap().schemas.contains_key("Pet"));
+
+        let mut empty = spec();
+        empty["paths"] = json!({});
+        assert!(resolve(parse(&empty).unwrap()).components.is_some());
+    }
+}
@@ -0,0 +1,178 @@
+use std::str::FromStr;
+
+/// Status code or a class of status codes, e.g. `404` or `4xx`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatusPattern {
+    Code(u16),
+    Class(u16),
+}
+
+impl StatusPattern {
+    fn matches(&self, status: u16) -> bool {
+        match self {
+            StatusPattern::Code(code) => *code == status,
+            StatusPattern::Class(class) => *class == status / 100,
+        }
+    }
+}
+
+impl FromStr for StatusPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || format!("invalid status code `{}`", s);
+        match s.to_lowercase().strip_suffix("xx") {
+            Some(class) if class.len() == 1 => {
+                Ok(StatusPattern::Class(class.parse().map_err(|_| invalid())?))
+            }
+            Some(_) => Err(invalid()),
+            None => Ok(StatusPattern::Code(s.parse().map_err(|_| invalid())?)),
+        }
+    }
+}
+
+/// Operations a rule applies to, all (`*`), of a method (`DELETE`), an endpoint
+/// (`GET /items/{id}`), of paths (`/legacy/*`) or an operation with the operationId
+/// (`getItem`). Paths may contain `*` matching any characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operations {
+    All,
+    Method(String),
+    Endpoint(String, String),
+    Path(String),
+    OperationId(String),
+}
+
+/// Whether the path matches the pattern, `*` matches any characters
+fn glob_matches(pattern: &str, path: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match path.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts = parts.collect::<Vec<_>>();
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+const METHODS: [&str; 8] = [
+    "GET", "PUT", "POST", "DELETE", "OPTIONS", "HEAD", "PATCH", "TRACE",
+];
+
+impl Operations {
+    pub fn matches(&self, method: &str, path: &str, operation_id: Option<&str>) -> bool {
+        match self {
+            Operations::All => true,
+            Operations::Method(m) => m == method,
+            Operations::Endpoint(m, p) => (m == method || m == "*") && glob_matches(p, path),
+            Operations::Path(p) => glob_matches(p, path),
+            Operations::OperationId(id) => Some(id.as_str()) == operation_id,
+        }
+    }
+}
+
+impl FromStr for Operations {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() || s == "*" {
+            return Ok(Operations::All);
+        }
+        if s.starts_with('/') {
+            return Ok(Operations::Path(s.to_string()));
+        }
+        match s.split_once(' ') {
+            Some((method, path)) => Ok(Operations::Endpoint(
+                method.to_uppercase(),
+                path.trim().to_string(),
+            )),
+            None if METHODS.contains(&s.to_uppercase().as_str()) => {
+                Ok(Operations::Method(s.to_uppercase()))
+            }
+            None => Ok(Operations::OperationId(s.to_string())),
+        }
+    }
+}
+
+/// Status codes of an operation or of all operations, e.g. `404`, `DELETE=404`,
+/// `GET /items/{id}=404,410` or `getItem=4xx`
+#[derive(Debug, Clone)]
+pub struct StatusRule {
+    operations: Operations,
+    statuses: Vec<StatusPattern>,
+}
+
+impl FromStr for StatusRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (operations, statuses) = s.rsplit_once('=').unwrap_or(("", s));
+        Ok(StatusRule {
+            operations: operations.parse()?,
+            statuses: statuses
+                .split(',')
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl StatusRule {
+    pub fn matches(
+        &self,
+        method: &str,
+        path: &str,
+        operation_id: Option<&str>,
+        status: u16,
+    ) -> bool {
+        self.operations.matches(method, path, operation_id)
+            && self.statuses.iter().any(|pattern| pattern.matches(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_matches("/items/{id}", "/items/{id}"));
+        assert!(!glob_matches("/items/{id}", "/items/{id}/tags"));
+        assert!(glob_matches("/legacy/*", "/legacy/orders/{id}"));
+        assert!(glob_matches("/legacy/*", "/legacy/"));
+        assert!(!glob_matches("/legacy/*", "/legacy"));
+        assert!(glob_matches("*/export", "/reports/export"));
+        assert!(glob_matches("/a/*/c/*/e", "/a/b/c/d/e"));
+        assert!(!glob_matches("/a/*/c/*/e", "/a/b/d/e"));
+        assert!(!glob_matches("/a*a", "/a"));
+        assert!(glob_matches("*", ""));
+    }
+
+    #[test]
+    fn matches_operations() {
+        let parse = |s: &str| s.parse::<Operations>().unwrap();
+        assert_eq!(parse(" * "), Operations::All);
+        assert_eq!(parse("delete"), Operations::Method("DELETE".to_string()));
+        assert_eq!(
+            parse("getItem"),
+            Operations::OperationId("getItem".to_string())
+        );
+        assert!(parse("get /items/*").matches("GET", "/items/{id}", None));
+        assert!(!parse("get /items/*").matches("POST", "/items/{id}", None));
+        assert!(parse("* /items/*").matches("POST", "/items/{id}", None));
+        assert!(parse("/legacy/*").matches("PUT", "/legacy/x", None));
+        assert!(parse("getItem").matches("GET", "/items/{id}", Some("getItem")));
+        assert!(!parse("getItem").matches("GET", "/items/{id}", None));
+    }
+}
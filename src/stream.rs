@@ -0,0 +1,270 @@
+//! Streaming responses, Server-Sent Events and newline delimited JSON: they are read within a
+//! time and byte budget instead of waiting for a body that never ends, and their events are
+//! checked one by one
+
+use std::{
+    io::{self, Read},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use openapiv3::{ReferenceOr, Responses};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Media types of streams, Server-Sent Events first
+const STREAM_TYPES: [&str; 5] = [
+    "text/event-stream",
+    "application/x-ndjson",
+    "application/jsonl",
+    "application/stream+json",
+    "application/json-seq",
+];
+
+/// Time a stream is read for, when it is not configured
+pub const DEFAULT_STREAM_DURATION: Duration = Duration::from_secs(5);
+
+/// Bytes of a stream read at most, when it is not configured
+pub const DEFAULT_STREAM_BYTES: u64 = 1024 * 1024;
+
+/// Chunks read ahead of the stream at most
+const CHUNKS: usize = 16;
+
+/// How long and how much of a streaming response is read
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamBudget {
+    pub duration: Duration,
+    pub max_bytes: u64,
+}
+
+impl Default for StreamBudget {
+    fn default() -> Self {
+        StreamBudget {
+            duration: DEFAULT_STREAM_DURATION,
+            max_bytes: DEFAULT_STREAM_BYTES,
+        }
+    }
+}
+
+/// Event of a stream, a line of newline delimited JSON has only data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub data: String,
+}
+
+fn is_stream_type(content_type: &str) -> bool {
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    STREAM_TYPES
+        .iter()
+        .any(|stream| media_type.eq_ignore_ascii_case(stream))
+}
+
+/// Whether a response of the operation is declared as a stream
+pub fn declared(responses: &Responses) -> bool {
+    responses
+        .responses
+        .values()
+        .chain(responses.default.iter())
+        .any(|response| match response {
+            ReferenceOr::Item(response) => response.content.keys().any(|c| is_stream_type(c)),
+            ReferenceOr::Reference { .. } => false,
+        })
+}
+
+/// Whether the response with the content type is a stream
+pub fn is_stream(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(is_stream_type)
+}
+
+/// Reads the stream until it ends, the budget of bytes is spent or its time since the start of
+/// the request passes, a read timing out ends the stream instead of failing the request. The
+/// reads are made on a thread of their own, so that a stream sending nothing is cut in time too
+pub fn read(
+    reader: impl Read + Send + 'static,
+    budget: &StreamBudget,
+    start: Instant,
+) -> io::Result<Vec<u8>> {
+    let (sender, receiver) = mpsc::sync_channel(CHUNKS);
+    let max_bytes = budget.max_bytes;
+    // Left to end on its own, at the next read once nothing receives its chunks
+    thread::spawn(move || {
+        let mut reader = reader.take(max_bytes);
+        let mut buffer = [0; 8192];
+        loop {
+            let chunk = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => Ok(buffer[..read].to_vec()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => Err(e),
+            };
+            let failed = chunk.is_err();
+            if sender.send(chunk).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let deadline = start + budget.duration;
+    let mut body = Vec::new();
+    loop {
+        match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(Ok(chunk)) => body.extend_from_slice(&chunk),
+            Ok(Err(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                ) =>
+            {
+                debug!(bytes = body.len(), "stream budget spent");
+                break;
+            }
+            Ok(Err(e)) => return Err(e),
+            Err(RecvTimeoutError::Timeout) => {
+                debug!(bytes = body.len(), "stream budget spent");
+                break;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Ok(body)
+}
+
+/// Events of the body of a stream with the content type
+pub fn events(content_type: Option<&str>, body: &str) -> Vec<Event> {
+    let media_type = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    match media_type.as_str() {
+        "text/event-stream" => server_sent_events(body),
+        _ if is_stream_type(&media_type) => body
+            .lines()
+            // Records of JSON text sequences start with a record separator
+            .map(|line| line.trim_start_matches('\u{1e}').trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| Event {
+                event: None,
+                id: None,
+                data: line.to_string(),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Events of Server-Sent Events, an event is dispatched by an empty line and the last one
+/// also at the end of a cut stream
+fn server_sent_events(body: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let (mut event, mut id, mut data) = (None, None, Vec::<&str>::new());
+    for line in body.lines().chain(std::iter::once("")) {
+        if line.is_empty() {
+            if !data.is_empty() {
+                events.push(Event {
+                    event: event.take(),
+                    id: id.clone(),
+                    data: data.join("\n"),
+                });
+            }
+            event = None;
+            data.clear();
+            continue;
+        }
+        let (field, value) = match line.split_once(':') {
+            // Comments, e.g. keep-alives
+            Some(("", _)) => continue,
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => event = Some(value.to_string()),
+            "data" => data.push(value),
+            // The id of the last event is kept by the following ones
+            "id" => id = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stream sending its chunks and then nothing for a long time
+    struct Stalled(Vec<&'static str>);
+
+    impl Read for Stalled {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop() {
+                Some(chunk) => {
+                    buffer[..chunk.len()].copy_from_slice(chunk.as_bytes());
+                    Ok(chunk.len())
+                }
+                None => {
+                    thread::sleep(Duration::from_secs(10));
+                    Ok(0)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reads_within_the_budget() {
+        let budget = StreamBudget {
+            duration: Duration::from_millis(100),
+            max_bytes: 1024,
+        };
+        let start = Instant::now();
+        let body = read(Stalled(vec!["b\n", "a\n"]), &budget, start).unwrap();
+        assert_eq!(body, b"a\nb\n");
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let budget = StreamBudget {
+            max_bytes: 3,
+            ..budget
+        };
+        let body = read(&b"abcdef"[..], &budget, Instant::now()).unwrap();
+        assert_eq!(body, b"abc");
+    }
+
+    #[test]
+    fn splits_server_sent_events() {
+        let body = ": keep-alive\nevent: created\nid: 1\ndata: a\ndata: b\n\ndata:c\n\ndata: d";
+        assert_eq!(
+            events(Some("text/event-stream; charset=utf-8"), body),
+            vec![
+                Event {
+                    event: Some("created".to_string()),
+                    id: Some("1".to_string()),
+                    data: "a\nb".to_string(),
+                },
+                Event {
+                    event: None,
+                    id: Some("1".to_string()),
+                    data: "c".to_string(),
+                },
+                Event {
+                    event: None,
+                    id: Some("1".to_string()),
+                    data: "d".to_string(),
+                },
+            ]
+        );
+        let lines = events(
+            Some("application/x-ndjson"),
+            "{\"a\":1}\n\n\u{1e}{\"b\":2}\n",
+        );
+        assert_eq!(
+            lines.iter().map(|e| e.data.as_str()).collect::<Vec<_>>(),
+            vec!["{\"a\":1}", "{\"b\":2}"]
+        );
+        assert!(events(Some("application/json"), "{}").is_empty());
+    }
+}
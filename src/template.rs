@@ -0,0 +1,313 @@
+//! Raw bodies of operations from templates of the config, for specifications whose schema of
+//! the body is too wrong to generate from: the `{{placeholder}}` slots of a known-good envelope
+//! are filled with generated values. A placeholder names a field of the generated body or a
+//! parameter, e.g. `{{customerId}}` or the JSON pointer `{{/order/items/0/sku}}`, or else the
+//! kind of a value, `string`, `integer`, `number`, `boolean` or the kind of a field recognized
+//! by its name, e.g. `uuid` or `email`. The values are inserted as JSON into JSON bodies, escaped
+//! inside their string literals, and as text into the others.
+
+use std::str::FromStr;
+
+use arbitrary::{Arbitrary, Unstructured};
+use openapiv3::StringType;
+use rand::{rngs::StdRng, Rng, RngCore};
+use serde_json::Value;
+
+use crate::config::OperationOverride;
+use crate::payload::{Generator, Payload};
+use crate::pool;
+use crate::smart;
+use crate::status::Operations;
+
+/// Bytes of random data each generated value of a kind is drawn from
+const RANDOM_BYTES: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Placeholder(String),
+}
+
+/// Body with `{{placeholder}}` slots
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("{{") {
+            let end = rest[start..]
+                .find("}}")
+                .map(|end| start + end)
+                .ok_or_else(|| format!("unclosed placeholder in `{}`", &rest[start..]))?;
+            let name = rest[start + 2..end].trim();
+            if name.is_empty() || name.contains("{{") {
+                return Err(format!("invalid placeholder `{}`", &rest[start..end + 2]));
+            }
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            parts.push(Part::Placeholder(name.to_string()));
+            rest = &rest[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Template { parts })
+    }
+}
+
+/// First value of a field with the name in the generated body, depth first
+fn field<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(object) => object
+            .get(name)
+            .or_else(|| object.values().find_map(|value| field(value, name))),
+        Value::Array(items) => items.iter().find_map(|item| field(item, name)),
+        _ => None,
+    }
+}
+
+/// Generated value the placeholder names, from the body or the parameters of the payload
+fn generated(payload: &Payload, name: &str) -> Option<Value> {
+    let body = payload.body.first();
+    let value = match name.strip_prefix('/') {
+        Some(_) => body.and_then(|body| body.pointer(name)),
+        None => body.and_then(|body| field(body, name)),
+    };
+    if let Some(value) = value {
+        return Some(value.clone());
+    }
+    payload
+        .path_params
+        .iter()
+        .chain(payload.query_params.iter())
+        .chain(payload.headers.iter())
+        .find(|(param, _)| *param == name)
+        .map(|(_, value)| Value::String(value.clone()))
+}
+
+/// Random value of the kind, random strings for unknown kinds and for all strings if `plain`
+fn random(kind: &str, plain: bool, rng: &mut StdRng) -> Value {
+    let mut bytes = [0; RANDOM_BYTES];
+    rng.fill_bytes(&mut bytes);
+    let mut gen = Unstructured::new(&bytes);
+    match kind {
+        "integer" => Value::from(rng.gen::<i32>()),
+        "number" => Value::from(rng.gen_range(-1e6..1e6)),
+        "boolean" => Value::Bool(rng.gen()),
+        _ => {
+            let realistic = smart::kind(kind, &StringType::default())
+                .filter(|_| !plain)
+                .and_then(|kind| smart::value(kind, &mut gen));
+            Value::String(match realistic {
+                Some(realistic) => realistic,
+                None => pool::string(<&str>::arbitrary(&mut gen).unwrap_or_default()),
+            })
+        }
+    }
+}
+
+/// Whether the text leaves a JSON string literal open, given whether it starts in one
+fn in_string(text: &str, mut open: bool) -> bool {
+    let mut escaped = false;
+    for c in text.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if open => escaped = true,
+            '"' => open = !open,
+            _ => {}
+        }
+    }
+    open
+}
+
+/// Contents of a JSON string literal with the value, without the quotes
+fn escaped(value: &Value) -> String {
+    let text = match value {
+        Value::String(string) => string.clone(),
+        value => value.to_string(),
+    };
+    let quoted = Value::String(text).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+impl Template {
+    /// Whether the template is JSON once its placeholders are filled
+    fn is_json(&self) -> bool {
+        let mut body = String::new();
+        let mut open = false;
+        for part in self.parts.iter() {
+            match part {
+                Part::Text(text) => {
+                    body.push_str(text);
+                    open = in_string(text, open);
+                }
+                Part::Placeholder(_) if !open => body.push_str("null"),
+                Part::Placeholder(_) => {}
+            }
+        }
+        serde_json::from_str::<Value>(&body).is_ok()
+    }
+
+    /// Body with the placeholders filled from the payload, JSON values if `json`, random
+    /// strings of kinds if `plain`
+    fn fill(&self, payload: &Payload, json: bool, plain: bool, rng: &mut StdRng) -> Vec<u8> {
+        let mut body = String::new();
+        let mut open = false;
+        for part in self.parts.iter() {
+            match part {
+                Part::Text(text) => {
+                    body.push_str(text);
+                    open = json && in_string(text, open);
+                }
+                Part::Placeholder(name) => {
+                    let value =
+                        generated(payload, name).unwrap_or_else(|| random(name, plain, rng));
+                    match value {
+                        _ if open => body.push_str(&escaped(&value)),
+                        Value::String(string) if !json => body.push_str(&string),
+                        value => body.push_str(&value.to_string()),
+                    }
+                }
+            }
+        }
+        body.into_bytes()
+    }
+}
+
+/// Replaces the generated bodies of the operations with templates in the config, after the
+/// other generators so their values are used
+pub struct BodyTemplates {
+    templates: Vec<(Operations, Template)>,
+    /// Whether the placeholders of kinds get random strings, see `--plain-strings`
+    plain: bool,
+}
+
+impl BodyTemplates {
+    pub fn new(overrides: &[OperationOverride], plain: bool) -> Option<BodyTemplates> {
+        let templates = overrides
+            .iter()
+            .filter_map(|o| Some((o.operations.clone(), o.body_template.clone()?)))
+            .collect::<Vec<_>>();
+        (!templates.is_empty()).then_some(BodyTemplates { templates, plain })
+    }
+}
+
+impl Generator for BodyTemplates {
+    fn generate(&self, payload: &mut Payload<'_>, rng: &mut StdRng) {
+        // Payloads made invalid on purpose keep their body, the template may lose the violation
+        if payload.violation.is_some() {
+            return;
+        }
+        let template = match self.templates.iter().rev().find(|(operations, _)| {
+            operations.matches(payload.method, payload.path, payload.operation_id)
+        }) {
+            Some((_, template)) => template,
+            None => return,
+        };
+        let content_type = payload
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| value.to_lowercase());
+        let json = match &content_type {
+            Some(content_type) => content_type.contains("json"),
+            None => template.is_json(),
+        };
+        if content_type.is_none() && json {
+            payload
+                .headers
+                .push(("Content-Type", "application/json".to_string()));
+        }
+        let body = template.fill(payload, json, self.plain, rng);
+        payload.body.drain(..).for_each(pool::recycle);
+        payload.raw_body = Some(body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openapiv3::Responses;
+    use rand::SeedableRng;
+    use serde_json::json;
+    use url::Url;
+
+    fn fill(template: &str, body: Value, json: bool) -> String {
+        let url = Url::parse("http://localhost").unwrap();
+        let responses = Responses::default();
+        let payload = Payload {
+            url: &url,
+            method: "POST",
+            path: "/orders",
+            operation_id: None,
+            query_params: Vec::new(),
+            path_params: vec![("customerId", "a\"b".to_string())],
+            headers: Vec::new(),
+            body: vec![body],
+            raw_body: None,
+            seed: 0,
+            responses: &responses,
+            idempotent: false,
+            violation: None,
+            negative: false,
+            timeout: None,
+            endpoint: None,
+        };
+        let template = template.parse::<Template>().unwrap();
+        let body = template.fill(&payload, json, false, &mut StdRng::seed_from_u64(0));
+        String::from_utf8(body).unwrap()
+    }
+
+    #[test]
+    fn parses_placeholders() {
+        let template = "{\"id\": {{ id }}, \"sku\": \"{{/items/0/sku}}\"}"
+            .parse::<Template>()
+            .unwrap();
+        assert_eq!(
+            template.parts,
+            vec![
+                Part::Text("{\"id\": ".to_string()),
+                Part::Placeholder("id".to_string()),
+                Part::Text(", \"sku\": \"".to_string()),
+                Part::Placeholder("/items/0/sku".to_string()),
+                Part::Text("\"}".to_string()),
+            ]
+        );
+        assert!(template.is_json());
+        assert!("id={{id".parse::<Template>().is_err());
+        assert!("id={{}}".parse::<Template>().is_err());
+        assert!(!"id={{id}}".parse::<Template>().unwrap().is_json());
+    }
+
+    #[test]
+    fn fills_placeholders() {
+        let body = json!({"id": 7, "items": [{"sku": "x"}]});
+        assert_eq!(
+            fill(
+                "{\"id\": {{id}}, \"sku\": {{/items/0/sku}}}",
+                body.clone(),
+                true
+            ),
+            "{\"id\": 7, \"sku\": \"x\"}"
+        );
+        assert_eq!(
+            fill(
+                "{\"customer\": \"{{customerId}}\", \"note\": \"\\\"{{id}}\"}",
+                body.clone(),
+                true
+            ),
+            "{\"customer\": \"a\\\"b\", \"note\": \"\\\"7\"}"
+        );
+        assert_eq!(
+            fill("<order customer=\"{{customerId}}\"/>", body, false),
+            "<order customer=\"a\"b\"/>"
+        );
+    }
+}
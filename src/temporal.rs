@@ -0,0 +1,123 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use arbitrary::Unstructured;
+
+/// Probability that a `date` or `date-time` string gets a temporal value, the rest stay
+/// random strings
+const TEMPORAL_PROBABILITY: f64 = 0.8;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Offsets in days from now: yesterday, tomorrow, a month and a year around now
+const RELATIVE_DAYS: &[i64] = &[0, -1, 1, -30, 30, 365, -365];
+
+/// Dates and times on boundaries that are often mishandled: epoch, 32-bit overflow (2038),
+/// the extremes of 4-digit years, leap days and seconds and DST transitions
+const BOUNDARIES: &[&str] = &[
+    "1970-01-01T00:00:00Z",
+    "1969-12-31T23:59:59Z",
+    "2038-01-19T03:14:07Z",
+    "2038-01-19T03:14:08Z",
+    "9999-12-31T23:59:59Z",
+    "0001-01-01T00:00:00Z",
+    "0000-01-01T00:00:00Z",
+    "2024-02-29T12:00:00Z",
+    "2023-02-29T12:00:00Z",
+    "2016-12-31T23:59:60Z",
+    "2024-12-31T23:59:59.999999999Z",
+    // Missing and repeated hours of DST transitions in the US and the EU
+    "2024-03-10T02:30:00-05:00",
+    "2024-11-03T01:30:00-04:00",
+    "2024-03-31T02:30:00+01:00",
+    "2024-10-27T02:30:00+02:00",
+];
+
+/// Offsets of the time zones at the extremes and with unusual minutes
+const OFFSETS: &[&str] = &[
+    "Z", "+00:00", "-00:00", "+14:00", "-12:00", "+05:45", "+23:59",
+];
+
+/// Date and time as `YYYY-MM-DDTHH:MM:SS` of the seconds since the epoch
+fn civil(seconds: i64) -> String {
+    // Days to civil date from http://howardhinnant.github.io/date_algorithms.html
+    let days = seconds.div_euclid(DAY);
+    let time = seconds.rem_euclid(DAY);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Value of a string with the `date` (`date_only`) or `date-time` format, `None` to keep the
+/// random string
+pub fn value(date_only: bool, gen: &mut Unstructured) -> Option<String> {
+    let keep_random = gen.int_in_range(0..=99u8).ok()? >= (TEMPORAL_PROBABILITY * 100.0) as u8;
+    if keep_random {
+        return None;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+    let date_time = match gen.int_in_range(0..=2u8).ok()? {
+        0 => {
+            let days = gen.choose(RELATIVE_DAYS).ok()?;
+            format!("{}Z", civil(now + days * DAY))
+        }
+        1 => gen.choose(BOUNDARIES).ok()?.to_string(),
+        _ => format!("{}{}", civil(now), gen.choose(OFFSETS).ok()?),
+    };
+    Some(match date_only {
+        true => date_time[..date_time.find('T').unwrap_or(date_time.len())].to_string(),
+        false => date_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+    #[test]
+    fn converts_seconds_to_civil_dates() {
+        assert_eq!(civil(0), "1970-01-01T00:00:00");
+        assert_eq!(civil(-1), "1969-12-31T23:59:59");
+        assert_eq!(civil(i32::MAX as i64), "2038-01-19T03:14:07");
+        assert_eq!(civil(1_709_208_000), "2024-02-29T12:00:00");
+        assert_eq!(civil(253_402_300_799), "9999-12-31T23:59:59");
+    }
+
+    #[test]
+    fn formats_dates_and_date_times() {
+        let mut temporal = 0;
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..256 {
+            let mut bytes = [0; 64];
+            rng.fill_bytes(&mut bytes);
+            let date_time = value(false, &mut Unstructured::new(&bytes));
+            let date = value(true, &mut Unstructured::new(&bytes));
+            assert_eq!(date.is_some(), date_time.is_some());
+            if let (Some(date), Some(date_time)) = (date, date_time) {
+                temporal += 1;
+                assert_eq!(date.len(), 10, "{}", date);
+                assert!(date_time.starts_with(&date), "{}", date_time);
+                assert_eq!(&date_time[10..11], "T", "{}", date_time);
+            }
+        }
+        assert!(temporal > 0 && temporal < 256, "{}", temporal);
+    }
+}
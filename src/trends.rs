@@ -0,0 +1,176 @@
+//! Trends of the runs recorded in the database: findings by severity and operation and
+//! coverage over time, to see whether the API gets more robust from release to release
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::database::{Database, RecordedRun};
+use crate::report;
+
+const SEVERITIES: &[&str] = &["critical", "high", "medium", "low"];
+
+#[derive(Debug, Serialize)]
+pub struct RunTrend {
+    #[serde(flatten)]
+    pub run: RecordedRun,
+    /// Percentage of the operations that returned a documented response
+    pub coverage: f64,
+    /// Change of the coverage since the previous run
+    pub coverage_drift: Option<f64>,
+    pub findings: usize,
+    /// Findings seen for the first time or again after they were fixed
+    pub new: usize,
+    pub severities: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OperationTrend {
+    pub operation: String,
+    /// Number of findings of the operation in each of the runs
+    pub findings: Vec<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Trends {
+    pub runs: Vec<RunTrend>,
+    pub operations: Vec<OperationTrend>,
+}
+
+impl Trends {
+    /// Trends of the last runs, optionally only those against the API
+    pub fn load(database: &Database, api: Option<&str>, last: usize) -> Result<Trends> {
+        let runs = database.runs(api, last)?;
+        let sightings = match runs.first() {
+            Some(first) => database.sightings(first.id)?,
+            None => Vec::new(),
+        };
+        let index = runs
+            .iter()
+            .enumerate()
+            .map(|(i, run)| (run.id, i))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut operations: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        let mut trends = Vec::new();
+        let mut previous_coverage = None;
+        for run in runs.into_iter() {
+            let seen = sightings.iter().filter(|s| s.run == run.id);
+            let mut severities = SEVERITIES
+                .iter()
+                .map(|severity| (severity.to_string(), 0))
+                .collect::<BTreeMap<_, _>>();
+            let (mut findings, mut new) = (0, 0);
+            for sighting in seen {
+                findings += 1;
+                new += usize::from(sighting.new);
+                *severities.entry(sighting.severity.clone()).or_default() += 1;
+            }
+            let coverage = 100.0 * f64::from(run.covered) / f64::from(run.operations.max(1));
+            trends.push(RunTrend {
+                run,
+                coverage,
+                coverage_drift: previous_coverage.map(|previous| coverage - previous),
+                findings,
+                new,
+                severities,
+            });
+            previous_coverage = Some(coverage);
+        }
+        for sighting in sightings.iter() {
+            if let Some(&i) = index.get(&sighting.run) {
+                operations
+                    .entry(sighting.operation.clone())
+                    .or_insert_with(|| vec![0; trends.len()])[i] += 1;
+            }
+        }
+
+        Ok(Trends {
+            runs: trends,
+            operations: operations
+                .into_iter()
+                .map(|(operation, findings)| OperationTrend {
+                    operation,
+                    findings,
+                })
+                .collect(),
+        })
+    }
+
+    /// Prints the trends as tables of the runs and of the operations, or as JSON
+    pub fn print(&self, json: bool) -> Result<()> {
+        if json {
+            println!("{}", serde_json::to_string_pretty(self)?);
+            return Ok(());
+        }
+        if self.runs.is_empty() {
+            println!("No runs recorded yet");
+            return Ok(());
+        }
+
+        let rows = self
+            .runs
+            .iter()
+            .map(|trend| {
+                let mut row = vec![
+                    trend.run.id.to_string(),
+                    trend.run.finished.clone(),
+                    trend.run.requests.to_string(),
+                    format!("{:.1}%", trend.coverage),
+                    match trend.coverage_drift {
+                        Some(drift) => format!("{:+.1}", drift),
+                        None => "-".to_string(),
+                    },
+                    trend.findings.to_string(),
+                    trend.new.to_string(),
+                ];
+                row.extend(
+                    SEVERITIES
+                        .iter()
+                        .map(|severity| trend.severities[*severity].to_string()),
+                );
+                row
+            })
+            .collect::<Vec<_>>();
+        let mut header = vec![
+            "RUN", "FINISHED", "REQUESTS", "COVERAGE", "DRIFT", "FINDINGS", "NEW",
+        ];
+        header.extend(["CRITICAL", "HIGH", "MEDIUM", "LOW"]);
+        print!("{}", report::table(&header, &rows));
+
+        if self.operations.is_empty() {
+            return Ok(());
+        }
+        let runs = self
+            .runs
+            .iter()
+            .map(|trend| format!("#{}", trend.run.id))
+            .collect::<Vec<_>>();
+        let mut header = vec!["OPERATION"];
+        header.extend(runs.iter().map(String::as_str));
+        header.push("TREND");
+        let rows = self
+            .operations
+            .iter()
+            .map(|operation| {
+                let mut row = vec![operation.operation.clone()];
+                row.extend(operation.findings.iter().map(usize::to_string));
+                let first = operation.findings.first().copied().unwrap_or_default();
+                let last = operation.findings.last().copied().unwrap_or_default();
+                row.push(
+                    match last.cmp(&first) {
+                        std::cmp::Ordering::Less => "improving",
+                        std::cmp::Ordering::Equal => "stable",
+                        std::cmp::Ordering::Greater => "worsening",
+                    }
+                    .to_string(),
+                );
+                row
+            })
+            .collect::<Vec<_>>();
+        println!();
+        print!("{}", report::table(&header, &rows));
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+//! Interactive triage of the findings recorded in the database: the request and response of
+//! the selected finding side by side, resending the request, possibly edited, and setting the
+//! triage status
+
+use std::{
+    env, fs,
+    io::{self, Stdout},
+    path::Path,
+    process,
+};
+
+use anyhow::{anyhow, Context, Result};
+use crossterm::{
+    event::{self, Event, KeyCode},
+    execute, terminal,
+};
+use tui::{
+    backend::CrosstermBackend,
+    layout::{Alignment, Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    widgets::{Block, BorderType, Borders, Cell, Paragraph, Row, Table, TableState, Wrap},
+    Terminal,
+};
+
+use crate::database::{Database, Recorded, Triage};
+use crate::encryption::Encryption;
+use crate::transport::{Request, Response, Transport};
+
+const HELP: &str = "↑/↓ select, PgUp/PgDn scroll, `n`ew / `a`cknowledged / `f`ixed, \
+                    `r` resend, `e` edit and resend, `q` quit";
+
+/// Request and response stored in the file of a finding
+pub fn exchange(finding: &Recorded, encryption: &Encryption) -> Result<(Request, Response)> {
+    let stored: serde_json::Value =
+        serde_json::from_slice(&encryption.read(Path::new(&finding.file))?)?;
+    let request = serde_json::from_value(stored["request"].clone())
+        .context("the finding has no request, upgrade it with openapi-fuzzer-resender")?;
+    let response = serde_json::from_value(stored["response"].clone())
+        .context("the finding has no response")?;
+    Ok((request, response))
+}
+
+/// Lets the user edit the request as JSON in `$EDITOR`
+fn edit(request: &Request) -> Result<Request> {
+    let file = env::temp_dir().join(format!("openapi-fuzzer-request-{}.json", process::id()));
+    fs::write(&file, serde_json::to_string_pretty(request)?)?;
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The editor may come with arguments, e.g. `code --wait`
+    let mut command = editor.split_whitespace();
+    let status = process::Command::new(command.next().unwrap_or("vi"))
+        .args(command)
+        .arg(&file)
+        .status()
+        .with_context(|| format!("unable to run {}", editor))?;
+    let edited = fs::read_to_string(&file);
+    fs::remove_file(&file).ok();
+    if !status.success() {
+        return Err(anyhow!("{} exited with {}", editor, status));
+    }
+    let mut edited: Request =
+        serde_json::from_str(&edited?).context("unable to parse the edited request")?;
+    edited.timeout = request.timeout;
+    Ok(edited)
+}
+
+struct Screen {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl Screen {
+    fn open() -> Result<Screen> {
+        terminal::enable_raw_mode().context("unable to go to raw mode")?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        terminal.clear()?;
+        Ok(Screen { terminal })
+    }
+
+    /// Restores the terminal, e.g. while the editor runs
+    fn suspend(&mut self) -> Result<()> {
+        terminal::disable_raw_mode()?;
+        execute!(io::stdout(), terminal::LeaveAlternateScreen)?;
+        self.terminal.show_cursor()?;
+        Ok(())
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen)?;
+        self.terminal.clear()?;
+        Ok(())
+    }
+}
+
+/// Selected finding with its request and the last response to it
+struct Selected {
+    exchange: Result<(Request, Response)>,
+    resent: bool,
+}
+
+/// Triage of the findings, they are resent with the transport
+pub fn run(database: &mut Database, transport: &dyn Transport) -> Result<()> {
+    let mut findings = database.findings(None)?;
+    if findings.is_empty() {
+        println!("No findings recorded yet");
+        return Ok(());
+    }
+    let mut screen = Screen::open()?;
+    let result = triage(database, transport, &mut findings, &mut screen);
+    screen.suspend()?;
+    result
+}
+
+fn triage(
+    database: &mut Database,
+    transport: &dyn Transport,
+    findings: &mut [Recorded],
+    screen: &mut Screen,
+) -> Result<()> {
+    let mut table = TableState::default();
+    table.select(Some(0));
+    let mut selected = Selected {
+        exchange: exchange(&findings[0], database.encryption()),
+        resent: false,
+    };
+    let mut scroll = 0;
+    let mut message = HELP.to_string();
+
+    loop {
+        let current = table.selected().unwrap_or_default();
+        draw(screen, findings, &mut table, &selected, scroll, &message)?;
+        let key = match event::read()? {
+            Event::Key(key) => key,
+            _ => continue,
+        };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => {
+                table.select(Some((current + 1) % findings.len()));
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                table.select(Some((current + findings.len() - 1) % findings.len()));
+            }
+            KeyCode::Home => table.select(Some(0)),
+            KeyCode::End => table.select(Some(findings.len() - 1)),
+            KeyCode::PageDown => scroll += 10,
+            KeyCode::PageUp => scroll = scroll.saturating_sub(10),
+            KeyCode::Char(status @ ('n' | 'a' | 'f')) => {
+                let status = match status {
+                    'n' => Triage::New,
+                    'a' => Triage::Acknowledged,
+                    _ => Triage::Fixed,
+                };
+                let finding = &mut findings[current];
+                database.triage(&finding.signature, status)?;
+                finding.triage = status.to_string();
+                message = format!("{} marked as {}", finding.signature, status);
+            }
+            KeyCode::Char(key @ ('r' | 'e')) => {
+                let request = match &selected.exchange {
+                    Ok((request, _)) => request.clone(),
+                    Err(_) => continue,
+                };
+                let request = match key {
+                    'e' => {
+                        screen.suspend()?;
+                        let edited = edit(&request);
+                        screen.resume()?;
+                        match edited {
+                            Ok(edited) => edited,
+                            Err(e) => {
+                                message = format!("{:#}", e);
+                                continue;
+                            }
+                        }
+                    }
+                    _ => request,
+                };
+                message = match transport.send(&request) {
+                    Ok(response) => {
+                        let message = format!(
+                            "resent, status {} (was {})",
+                            response.status, findings[current].status
+                        );
+                        selected = Selected {
+                            exchange: Ok((request, response)),
+                            resent: true,
+                        };
+                        scroll = 0;
+                        message
+                    }
+                    Err(e) => format!("unable to resend: {:#}", e),
+                };
+            }
+            _ => {}
+        }
+        if table.selected() != Some(current) {
+            selected = Selected {
+                exchange: exchange(
+                    &findings[table.selected().unwrap_or_default()],
+                    database.encryption(),
+                ),
+                resent: false,
+            };
+            scroll = 0;
+            message = HELP.to_string();
+        }
+    }
+}
+
+fn draw(
+    screen: &mut Screen,
+    findings: &[Recorded],
+    table: &mut TableState,
+    selected: &Selected,
+    scroll: u16,
+    message: &str,
+) -> Result<()> {
+    let (request, response) = match &selected.exchange {
+        Ok((request, response)) => (
+            request.to_raw().unwrap_or_else(|e| e.to_string()),
+            response.to_raw(),
+        ),
+        Err(e) => (format!("{:#}", e), String::new()),
+    };
+    let block = |title: &str| {
+        Block::default()
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::White))
+            .title(title.to_string())
+            .border_type(BorderType::Plain)
+    };
+    let rows = findings
+        .iter()
+        .map(|finding| {
+            let severity = match finding.severity.as_str() {
+                "critical" | "high" => Style::default().fg(Color::Red),
+                "medium" => Style::default().fg(Color::Yellow),
+                _ => Style::default(),
+            };
+            Row::new(vec![
+                Cell::from(finding.signature.clone()),
+                Cell::from(finding.severity.clone()).style(severity),
+                Cell::from(finding.triage.clone()),
+                Cell::from(format!("{} {}", finding.method, finding.path)),
+                Cell::from(finding.status.to_string()),
+                Cell::from(finding.kind.clone()),
+                Cell::from(finding.runs.to_string()),
+            ])
+        })
+        .collect::<Vec<_>>();
+    let widths = [
+        Constraint::Length(16),
+        Constraint::Length(9),
+        Constraint::Length(13),
+        Constraint::Percentage(40),
+        Constraint::Length(6),
+        Constraint::Percentage(25),
+        Constraint::Length(5),
+    ];
+    let findings_table = Table::new(rows)
+        .header(
+            Row::new(vec![
+                "Signature",
+                "Severity",
+                "Triage",
+                "Operation",
+                "Status",
+                "Kind",
+                "Runs",
+            ])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .block(block("Findings"))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .widths(&widths);
+    let response_title = match selected.resent {
+        true => "Response (resent)",
+        false => "Response",
+    };
+
+    screen
+        .terminal
+        .draw(|frame| {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(
+                    [
+                        Constraint::Percentage(35),
+                        Constraint::Min(4),
+                        Constraint::Length(3),
+                    ]
+                    .as_ref(),
+                )
+                .split(frame.size());
+            let panes = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(chunks[1]);
+
+            frame.render_stateful_widget(findings_table, chunks[0], table);
+            for (pane, (title, text)) in panes
+                .into_iter()
+                .zip([("Request", request), (response_title, response)])
+            {
+                let paragraph = Paragraph::new(text.replace('\r', ""))
+                    .block(block(title))
+                    .wrap(Wrap { trim: false })
+                    .scroll((scroll, 0));
+                frame.render_widget(paragraph, pane);
+            }
+            let message = Paragraph::new(message)
+                .style(Style::default().fg(Color::LightCyan))
+                .alignment(Alignment::Center)
+                .block(block("Message"));
+            frame.render_widget(message, chunks[2]);
+        })
+        .context("unable to draw tui")?;
+    Ok(())
+}
@@ -213,14 +213,20 @@ impl Tui {
         frame.render_widget(message, rect);
     }
 
+    /// Restores the terminal
+    pub fn close(&mut self) -> Result<()> {
+        terminal::disable_raw_mode()?;
+        self.terminal.clear()?;
+        self.terminal.show_cursor()?;
+        Ok(())
+    }
+
     pub fn display(&mut self, stats: &Stats, message: &Option<String>) -> Result<bool> {
         for e in self.receiver.try_iter() {
             match e {
                 Event::Input(event) => match event.code {
                     KeyCode::Char('q') => {
-                        terminal::disable_raw_mode()?;
-                        self.terminal.clear()?;
-                        self.terminal.show_cursor()?;
+                        self.close()?;
                         return Ok(true);
                     }
                     KeyCode::Down => self.table.next(),
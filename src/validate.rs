@@ -0,0 +1,199 @@
+use openapi_utils::ReferenceOrExt;
+use openapiv3::{
+    AdditionalProperties, ReferenceOr, Responses, Schema, SchemaKind, StatusCode, Type,
+};
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::transport::Response;
+
+/// Way a response breaks the contract of its operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// Status code neither declared nor covered by a `default` response
+    UndeclaredStatus(u16),
+    /// Field at the pointer missing from the schema
+    UndocumentedField(String),
+    /// Any other violation, described
+    Invalid(String),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::UndeclaredStatus(status) => write!(f, "undeclared status code {}", status),
+            Violation::UndocumentedField(pointer) => write!(f, "{}: undocumented field", pointer),
+            Violation::Invalid(violation) => f.write_str(violation),
+        }
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn violations_of_any(
+    schemas: &[ReferenceOr<Schema>],
+    value: &Value,
+    pointer: &str,
+) -> Vec<Vec<Violation>> {
+    schemas
+        .iter()
+        .map(|schema| validate_schema(schema.to_item_ref(), value, pointer))
+        .collect()
+}
+
+fn validate_type(schema_type: &Type, value: &Value, pointer: &str) -> Vec<Violation> {
+    let wrong_type = |expected: &str| {
+        vec![Violation::Invalid(format!(
+            "{}: expected {}, found {}",
+            pointer,
+            expected,
+            type_name(value)
+        ))]
+    };
+
+    match (schema_type, value) {
+        (Type::String(string), Value::String(s)) => {
+            if !string.enumeration.is_empty() && !string.enumeration.contains(s) {
+                return vec![Violation::Invalid(format!(
+                    "{}: `{}` is not one of the enum values",
+                    pointer, s
+                ))];
+            }
+            Vec::new()
+        }
+        (Type::String(_), _) => wrong_type("string"),
+        (Type::Number(_), Value::Number(_)) => Vec::new(),
+        (Type::Number(_), _) => wrong_type("number"),
+        (Type::Integer(_), Value::Number(n)) if n.is_i64() || n.is_u64() => Vec::new(),
+        (Type::Integer(_), _) => wrong_type("integer"),
+        (Type::Boolean {}, Value::Bool(_)) => Vec::new(),
+        (Type::Boolean {}, _) => wrong_type("boolean"),
+        (Type::Array(array), Value::Array(items)) => {
+            let items_schema = array.items.to_item_ref();
+            items
+                .iter()
+                .enumerate()
+                .flat_map(|(i, item)| {
+                    validate_schema(items_schema, item, &format!("{}/{}", pointer, i))
+                })
+                .collect()
+        }
+        (Type::Array(_), _) => wrong_type("array"),
+        (Type::Object(object), Value::Object(properties)) => {
+            let mut violations = object
+                .required
+                .iter()
+                .filter(|name| !properties.contains_key(*name))
+                .map(|name| {
+                    Violation::Invalid(format!("{}: missing required field `{}`", pointer, name))
+                })
+                .collect::<Vec<_>>();
+
+            for (name, value) in properties {
+                let field_pointer = format!("{}/{}", pointer, name);
+                match (object.properties.get(name), &object.additional_properties) {
+                    (Some(schema), _) => violations.extend(validate_schema(
+                        schema.to_item_ref(),
+                        value,
+                        &field_pointer,
+                    )),
+                    (None, Some(AdditionalProperties::Schema(schema))) => violations
+                        .extend(validate_schema(schema.to_item_ref(), value, &field_pointer)),
+                    (None, Some(AdditionalProperties::Any(true))) => {}
+                    // Fields not in the specification are reported unless explicitly allowed
+                    (None, _) => violations.push(Violation::UndocumentedField(field_pointer)),
+                }
+            }
+            violations
+        }
+        (Type::Object(_), _) => wrong_type("object"),
+    }
+}
+
+fn validate_schema(schema: &Schema, value: &Value, pointer: &str) -> Vec<Violation> {
+    if value.is_null() && schema.schema_data.nullable {
+        return Vec::new();
+    }
+
+    match &schema.schema_kind {
+        SchemaKind::Type(schema_type) => validate_type(schema_type, value, pointer),
+        SchemaKind::AllOf { all_of } => violations_of_any(all_of, value, pointer)
+            .into_iter()
+            .flatten()
+            // Each of the schemas covers only a part of the fields
+            .filter(|violation| !matches!(violation, Violation::UndocumentedField(_)))
+            .collect(),
+        SchemaKind::OneOf { one_of: schemas } | SchemaKind::AnyOf { any_of: schemas } => {
+            let violations = violations_of_any(schemas, value, pointer);
+            match violations.iter().any(Vec::is_empty) || violations.is_empty() {
+                true => Vec::new(),
+                false => vec![Violation::Invalid(format!(
+                    "{}: does not match any of the schemas",
+                    pointer
+                ))],
+            }
+        }
+        SchemaKind::Any(_) => Vec::new(),
+    }
+}
+
+/// Response declared for the status code, exactly or by its range, e.g. `2XX`
+fn declared(responses: &Responses, status: u16) -> Option<&ReferenceOr<openapiv3::Response>> {
+    responses
+        .responses
+        .get(&StatusCode::Code(status))
+        .or_else(|| responses.responses.get(&StatusCode::Range(status / 100)))
+}
+
+/// Whether the status code is declared for the operation, exactly or by its range, the
+/// `default` response does not document any status code
+pub fn is_documented(responses: &Responses, status: u16) -> bool {
+    declared(responses, status).is_some()
+}
+
+/// Validates the response against the responses declared in the specification and returns
+/// the violations of the contract
+pub fn validate_response(responses: &Responses, response: &Response) -> Vec<Violation> {
+    let declared = declared(responses, response.status).or(responses.default.as_ref());
+    let declared = match declared {
+        Some(declared) => declared.to_item_ref(),
+        None => return vec![Violation::UndeclaredStatus(response.status)],
+    };
+
+    let mut violations = declared
+        .headers
+        .iter()
+        .filter(|(name, header)| header.to_item_ref().required && response.header(name).is_none())
+        .map(|(name, _)| Violation::Invalid(format!("missing required header `{}`", name)))
+        .collect::<Vec<_>>();
+
+    let content_type = response.header("content-type").unwrap_or_default();
+    let schema = declared
+        .content
+        .iter()
+        .filter(|(media_type, _)| media_type.contains("json"))
+        .find_map(|(_, media)| media.schema.as_ref());
+    if let Some(schema) = schema {
+        if !content_type.contains("json") {
+            violations.push(Violation::Invalid(format!(
+                "expected JSON body, found `{}` content type",
+                content_type
+            )));
+        }
+        match serde_json::from_str(&response.body) {
+            Ok(body) => violations.extend(validate_schema(schema.to_item_ref(), &body, "")),
+            Err(e) => violations.push(Violation::Invalid(format!("body is not valid JSON: {}", e))),
+        }
+    }
+    violations
+}
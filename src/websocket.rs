@@ -0,0 +1,464 @@
+//! WebSocket endpoints: operations answering with `101 Switching Protocols` or marked with the
+//! `x-websocket` extension are upgraded instead of requested, their generated request bodies
+//! are sent as messages and the messages of the server are checked as events of a stream.
+//!
+//! The extension is either `true` or `{message: <schema>}`, the schema of the messages when
+//! the operation has no request body.
+
+use std::{
+    collections::BTreeSet,
+    convert::TryInto,
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use openapiv3::{OpenAPI, ReferenceOr, StatusCode};
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use serde_json::{json, Value};
+use tracing::debug;
+use url::Position;
+
+use crate::proxy;
+use crate::smuggling;
+use crate::stream::Event;
+use crate::transport::{Request, Response, Transport};
+
+/// Appended to the key of the handshake before it is hashed into the accept header
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Time the server is given to send more messages, once it is quiet the connection is closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Close code standing for a connection dropped without a close frame
+const NO_CLOSE_FRAME: u16 = 1006;
+
+/// Close codes of a server failing rather than rejecting the message: no close frame,
+/// internal error and bad gateway
+const ABNORMAL_CLOSE_CODES: [u16; 3] = [NO_CLOSE_FRAME, 1011, 1014];
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// Headers of the request replaced by the ones of the handshake
+const HANDSHAKE_HEADERS: [&str; 6] = [
+    "host",
+    "connection",
+    "upgrade",
+    "content-length",
+    "content-type",
+    "transfer-encoding",
+];
+
+/// WebSocket operations of the specification, by method and path
+#[derive(Debug, Clone, Default)]
+pub struct WebSockets {
+    operations: BTreeSet<(String, String)>,
+}
+
+/// Documents the upgrade of the operations with the extension: a `101` response and the
+/// schema of their messages as a JSON request body
+pub fn document(schema: &mut OpenAPI) {
+    for (path, ref_or_item) in schema.paths.iter_mut() {
+        let operation = match ref_or_item {
+            ReferenceOr::Item(item) => match &mut item.get {
+                Some(operation) => operation,
+                None => continue,
+            },
+            ReferenceOr::Reference { .. } => continue,
+        };
+        let message = match operation.extensions.get("x-websocket") {
+            Some(Value::Bool(true)) => None,
+            Some(Value::Object(extension)) => extension.get("message").cloned(),
+            _ => continue,
+        };
+        debug!(path = %path, "WebSocket endpoint");
+        if let Ok(response) = serde_json::from_value(json!({"description": "Switching Protocols"}))
+        {
+            operation
+                .responses
+                .responses
+                .entry(StatusCode::Code(101))
+                .or_insert(ReferenceOr::Item(response));
+        }
+        if let (Some(message), None) = (message, &operation.request_body) {
+            let body = json!({"content": {"application/json": {"schema": message}}});
+            match serde_json::from_value(body) {
+                Ok(body) => operation.request_body = Some(ReferenceOr::Item(body)),
+                Err(e) => debug!(path = %path, error = %e, "invalid schema of the messages"),
+            }
+        }
+    }
+}
+
+impl WebSockets {
+    /// GET operations switching protocols, `None` if the specification has none
+    pub fn new(schema: &OpenAPI) -> Option<WebSockets> {
+        let operations = schema
+            .paths
+            .iter()
+            .filter_map(|(path, item)| match item {
+                ReferenceOr::Item(item) => Some((path, item.get.as_ref()?)),
+                ReferenceOr::Reference { .. } => None,
+            })
+            .filter(|(_, operation)| {
+                operation
+                    .responses
+                    .responses
+                    .contains_key(&StatusCode::Code(101))
+            })
+            .map(|(path, _)| ("GET".to_string(), path.clone()))
+            .collect::<BTreeSet<_>>();
+        (!operations.is_empty()).then_some(WebSockets { operations })
+    }
+
+    pub fn contains(&self, method: &str, path: &str) -> bool {
+        self.operations
+            .contains(&(method.to_string(), path.to_string()))
+    }
+}
+
+/// Whether the close event of the response is abnormal, with its code and reason
+pub fn abnormal_closure(response: &Response) -> Option<&str> {
+    response
+        .events
+        .iter()
+        .filter(|event| event.event.as_deref() == Some("close"))
+        .map(|event| event.data.as_str())
+        .find(|close| {
+            close
+                .split(' ')
+                .next()
+                .and_then(|code| code.parse().ok())
+                .is_some_and(|code: u16| ABNORMAL_CLOSE_CODES.contains(&code))
+        })
+}
+
+/// Sends the requests with an `Upgrade: websocket` header over WebSocket and the other ones
+/// with the transport
+pub struct Upgrading<T> {
+    pub transport: T,
+}
+
+impl<T: Transport> Transport for Upgrading<T> {
+    fn send(&self, request: &Request) -> Result<Response> {
+        let upgrade = request.headers.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("upgrade") && value.eq_ignore_ascii_case("websocket")
+        });
+        match upgrade {
+            true => send(request),
+            false => self.transport.send(request),
+        }
+    }
+}
+
+/// Masked frame of a client
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(0x80 | len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    let mask = rand::random::<[u8; 4]>();
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+    frame
+}
+
+/// Frame of the server at the start of the buffer with its length, `None` until it is whole
+fn parse_frame(buffer: &[u8]) -> Option<(bool, u8, Vec<u8>, usize)> {
+    let (first, second) = (*buffer.first()?, *buffer.get(1)?);
+    let (mut len, mut offset) = ((second & 0x7f) as usize, 2);
+    if len == 126 {
+        len = u16::from_be_bytes(buffer.get(2..4)?.try_into().ok()?) as usize;
+        offset = 4;
+    } else if len == 127 {
+        len = u64::from_be_bytes(buffer.get(2..10)?.try_into().ok()?) as usize;
+        offset = 10;
+    }
+    // Servers must not mask their frames, some still do
+    let mask = match second & 0x80 {
+        0 => None,
+        _ => {
+            offset += 4;
+            Some(buffer.get(offset - 4..offset)?.to_vec())
+        }
+    };
+    let end = offset.checked_add(len)?;
+    let mut payload = buffer.get(offset..end)?.to_vec();
+    if let Some(mask) = mask {
+        payload
+            .iter_mut()
+            .zip(mask.iter().cycle())
+            .for_each(|(b, m)| *b ^= m);
+    }
+    Some((first & 0x80 != 0, first & 0x0f, payload, end))
+}
+
+/// Close event of the payload of a close frame, its code and reason
+fn close_event(payload: &[u8]) -> Event {
+    let data = match payload.get(..2) {
+        Some(code) => format!(
+            "{} {}",
+            u16::from_be_bytes([code[0], code[1]]),
+            String::from_utf8_lossy(&payload[2..])
+        ),
+        // No status code received
+        None => "1005".to_string(),
+    };
+    Event {
+        event: Some("close".to_string()),
+        id: None,
+        data: data.trim_end().to_string(),
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Performs the handshake to the url of the request and sends its body as a message, the
+/// response has the messages of the server until it is quiet or the stream budget is spent,
+/// followed by how the connection was closed. A handshake that is refused is the response.
+pub fn send(request: &Request) -> Result<Response> {
+    let budget = request.stream.unwrap_or_default();
+    let deadline = Instant::now() + budget.duration;
+    let url = &request.url;
+    let (mut connection, socket) = proxy::open(url)?;
+    socket.set_read_timeout(Some(budget.duration))?;
+
+    let key = base64::encode(rand::random::<[u8; 16]>());
+    let mut handshake = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n",
+        &url[Position::BeforePath..Position::AfterQuery],
+        &url[Position::BeforeHost..Position::AfterPort],
+        key
+    );
+    for (name, value) in request.headers.iter().filter(|(name, _)| {
+        let name = name.to_lowercase();
+        !HANDSHAKE_HEADERS.contains(&name.as_str()) && !name.starts_with("sec-websocket-")
+    }) {
+        handshake.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    handshake.push_str("\r\n");
+    let start = Instant::now();
+    connection.write_all(handshake.as_bytes())?;
+    connection.flush()?;
+
+    let mut received = Vec::new();
+    let mut buffer = [0; 8192];
+    let head_end = loop {
+        if let Some(end) = received.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+        match connection.read(&mut buffer) {
+            Ok(0) => return Err(anyhow!("connection closed during the handshake")),
+            Ok(read) => received.extend_from_slice(&buffer[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e).context("no response to the handshake"),
+        }
+    };
+    let elapsed = start.elapsed();
+    let mut response = smuggling::parse_response(&received[..head_end], elapsed);
+    if response.status != 101 {
+        // The body of the refusal is whatever arrives until the server is quiet
+        socket.set_read_timeout(Some(IDLE_TIMEOUT))?;
+        let mut body = received.split_off(head_end);
+        while (body.len() as u64) < budget.max_bytes {
+            match connection.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => body.extend_from_slice(&buffer[..read]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+        response.body = String::from_utf8_lossy(&body).to_string();
+        return Ok(response);
+    }
+    let accept = base64::encode(digest(
+        &SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{}{}", key, ACCEPT_GUID).as_bytes(),
+    ));
+    let accepted = response
+        .headers
+        .iter()
+        .any(|(name, value)| name.eq_ignore_ascii_case("sec-websocket-accept") && value == &accept);
+    if !accepted {
+        return Err(anyhow!("handshake without a valid Sec-WebSocket-Accept"));
+    }
+
+    let message = match (&request.raw_body, &request.body) {
+        (Some(raw), _) => Some(frame(OPCODE_BINARY, raw)),
+        (None, Some(body)) => Some(frame(OPCODE_TEXT, serde_json::to_string(body)?.as_bytes())),
+        (None, None) => None,
+    };
+    if let Some(message) = message {
+        connection.write_all(&message)?;
+        connection.flush()?;
+    }
+
+    let mut pending = received.split_off(head_end);
+    let mut events = Vec::new();
+    let mut fragments: Option<(u8, Vec<u8>)> = None;
+    let mut read_bytes = 0;
+    let mut close = None;
+    let mut closing = false;
+    loop {
+        while let Some((fin, opcode, payload, len)) = parse_frame(&pending) {
+            pending.drain(..len);
+            read_bytes += len as u64;
+            match opcode {
+                OPCODE_CLOSE => close = Some(close_event(&payload)),
+                OPCODE_PING => connection.write_all(&frame(OPCODE_PONG, &payload))?,
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    let (opcode, mut data) = match (opcode, fragments.take()) {
+                        (OPCODE_CONTINUATION, Some((opcode, data))) => (opcode, data),
+                        (opcode, _) => (opcode, Vec::new()),
+                    };
+                    data.extend_from_slice(&payload);
+                    match fin {
+                        true => events.push(Event {
+                            event: Some(
+                                match opcode {
+                                    OPCODE_BINARY => "binary",
+                                    _ => "message",
+                                }
+                                .to_string(),
+                            ),
+                            id: None,
+                            data: String::from_utf8_lossy(&data).to_string(),
+                        }),
+                        false => fragments = Some((opcode, data)),
+                    }
+                }
+                _ => {}
+            }
+        }
+        if close.is_some() {
+            break;
+        }
+        let now = Instant::now();
+        if !closing && (now >= deadline || read_bytes >= budget.max_bytes) {
+            connection.write_all(&frame(OPCODE_CLOSE, &1000u16.to_be_bytes()))?;
+            closing = true;
+        }
+        // The server is given a moment to answer a close
+        match closing {
+            true => socket.set_read_timeout(Some(IDLE_TIMEOUT))?,
+            false => socket.set_read_timeout(Some(IDLE_TIMEOUT.min(deadline - now)))?,
+        }
+        match connection.read(&mut buffer) {
+            Ok(0) => {
+                if !closing {
+                    close = Some(Event {
+                        event: Some("close".to_string()),
+                        id: None,
+                        data: format!("{} connection closed without a close frame", NO_CLOSE_FRAME),
+                    });
+                }
+                break;
+            }
+            Ok(read) => pending.extend_from_slice(&buffer[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // The server is quiet, it had its chance to close the connection itself
+            Err(e) if is_timeout(&e) && !closing => {
+                connection.write_all(&frame(OPCODE_CLOSE, &1000u16.to_be_bytes()))?;
+                closing = true;
+            }
+            Err(e) if is_timeout(&e) => break,
+            // A reset before the server closed the connection is as abnormal as a drop
+            Err(e) if !closing => {
+                close = Some(Event {
+                    event: Some("close".to_string()),
+                    id: None,
+                    data: format!("{} {}", NO_CLOSE_FRAME, e),
+                });
+                break;
+            }
+            Err(_) => break,
+        }
+    }
+    let _ = connection.flush();
+
+    response.body = events
+        .iter()
+        .map(|event| event.data.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    response.events = events.into_iter().chain(close).collect();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_masked_frames_of_every_length() {
+        for len in [0, 125, 126, u16::MAX as usize, u16::MAX as usize + 1] {
+            let payload = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+            let frame = frame(OPCODE_BINARY, &payload);
+            let (fin, opcode, parsed, end) = parse_frame(&frame).unwrap();
+            assert!(fin);
+            assert_eq!(opcode, OPCODE_BINARY);
+            assert_eq!(parsed, payload);
+            assert_eq!(end, frame.len());
+            assert!(parse_frame(&frame[..frame.len() - 1]).is_none());
+        }
+    }
+
+    #[test]
+    fn parses_unmasked_fragments() {
+        let mut buffer = vec![OPCODE_TEXT, 2, b'h', b'i'];
+        buffer.extend_from_slice(&[0x80 | OPCODE_CONTINUATION, 1, b'!']);
+        let (fin, opcode, payload, end) = parse_frame(&buffer).unwrap();
+        assert_eq!(
+            (fin, opcode, payload.as_slice(), end),
+            (false, OPCODE_TEXT, &b"hi"[..], 4)
+        );
+        let (fin, opcode, payload, _) = parse_frame(&buffer[end..]).unwrap();
+        assert_eq!(
+            (fin, opcode, payload.as_slice()),
+            (true, OPCODE_CONTINUATION, &b"!"[..])
+        );
+        assert!(parse_frame(&[0x81, 126, 0]).is_none());
+    }
+
+    #[test]
+    fn reports_abnormal_closures() {
+        assert_eq!(close_event(b"\x03\xe8").data, "1000");
+        assert_eq!(
+            close_event(b"\x03\xf3internal error").data,
+            "1011 internal error"
+        );
+        assert_eq!(close_event(b"").data, "1005");
+
+        let mut response = Response {
+            status: 101,
+            status_text: "Switching Protocols".to_string(),
+            http_version: "HTTP/1.1".to_string(),
+            headers: Vec::new(),
+            body: String::new(),
+            elapsed: Duration::default(),
+            events: vec![close_event(b"\x03\xe8")],
+        };
+        assert_eq!(abnormal_closure(&response), None);
+        response.events = vec![close_event(b"\x03\xf3internal error")];
+        assert_eq!(abnormal_closure(&response), Some("1011 internal error"));
+    }
+}